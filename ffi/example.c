@@ -0,0 +1,73 @@
+/* Hides a message in a PNG and finds it again through the C interface.
+ *
+ *   cargo build --features ffi   (or --profile release-ffi, see rust_stego.h)
+ *   cc ffi/example.c -I ffi -L target/debug -lrust_stego -o example
+ *   LD_LIBRARY_PATH=target/debug ./example cover.png out.png "meet at noon"
+ *
+ * Exits with the steg_status of the call that failed. */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "rust_stego.h"
+
+static uint8_t *read_file(const char *path, size_t *len) {
+    FILE *f = fopen(path, "rb");
+    if (!f) return NULL;
+    fseek(f, 0, SEEK_END);
+    long size = ftell(f);
+    fseek(f, 0, SEEK_SET);
+    uint8_t *data = malloc(size > 0 ? size : 1);
+    *len = fread(data, 1, size, f);
+    fclose(f);
+    return data;
+}
+
+static int write_file(const char *path, const uint8_t *data, size_t len) {
+    FILE *f = fopen(path, "wb");
+    if (!f) return -1;
+    size_t written = fwrite(data, 1, len, f);
+    fclose(f);
+    return written == len ? 0 : -1;
+}
+
+int main(int argc, char **argv) {
+    if (argc != 4) {
+        fprintf(stderr, "usage: %s <cover.png> <out.png> <message>\n", argv[0]);
+        return 1;
+    }
+
+    size_t len;
+    uint8_t *cover = read_file(argv[1], &len);
+    if (!cover) {
+        fprintf(stderr, "can't read %s\n", argv[1]);
+        return STEG_ERR_IO;
+    }
+
+    uint8_t *stego;
+    size_t stego_len;
+    int status = steg_hide_png(cover, len, (const uint8_t *)argv[3], strlen(argv[3]), &stego, &stego_len);
+    free(cover);
+    if (status != STEG_OK) {
+        fprintf(stderr, "hide failed (%d): %s\n", status, steg_last_error());
+        return status;
+    }
+    if (write_file(argv[2], stego, stego_len) != 0) {
+        fprintf(stderr, "can't write %s\n", argv[2]);
+        steg_free(stego, stego_len);
+        return STEG_ERR_IO;
+    }
+
+    uint8_t *found;
+    size_t found_len;
+    status = steg_find_png(stego, stego_len, &found, &found_len);
+    steg_free(stego, stego_len);
+    if (status != STEG_OK) {
+        fprintf(stderr, "find failed (%d): %s\n", status, steg_last_error());
+        return status;
+    }
+    printf("%.*s\n", (int)found_len, (const char *)found);
+    steg_free(found, found_len);
+    return STEG_OK;
+}
@@ -0,0 +1,56 @@
+/* C interface to rust-stego, in librust_stego when built with `cargo build --features ffi`, or
+ * `cargo build --profile release-ffi --features ffi` for an optimized one in target/release-ffi.
+ * Not `--release`: that profile aborts on a panic, which then can't come back as STEG_ERR_PANIC.
+ *
+ * Every function returns STEG_OK or an error code. On success *out points to a buffer of *out_len
+ * bytes that the caller releases with steg_free; on failure *out is NULL and steg_last_error() says
+ * what went wrong. What steg_hide_X hides, steg_find_X finds; payloads go in as given, without the
+ * framing the rust-stego CLI adds to its own. */
+
+#ifndef RUST_STEGO_H
+#define RUST_STEGO_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+enum steg_status {
+    STEG_OK = 0,
+    STEG_ERR_OTHER = 1,
+    STEG_ERR_INVALID_ARGUMENT = 2,
+    STEG_ERR_IO = 3,
+    STEG_ERR_UNSUPPORTED_FORMAT = 4, /* or a carrier that breaks its format's spec */
+    STEG_ERR_CAPACITY_EXCEEDED = 5,
+    STEG_ERR_NO_PAYLOAD_FOUND = 6,
+    STEG_ERR_CHECKSUM_MISMATCH = 7,
+    STEG_ERR_TRUNCATED = 8,
+    STEG_ERR_PANIC = 9
+};
+
+/* LSB in the R, G and B channels of a PNG, one bit each per pixel */
+int steg_hide_png(const uint8_t *carrier, size_t len, const uint8_t *payload, size_t plen, uint8_t **out, size_t *out_len);
+int steg_find_png(const uint8_t *carrier, size_t len, uint8_t **out, size_t *out_len);
+
+/* LSB in 16-bit PCM WAV samples, one bit per sample */
+int steg_hide_wav(const uint8_t *carrier, size_t len, const uint8_t *payload, size_t plen, uint8_t **out, size_t *out_len);
+int steg_find_wav(const uint8_t *carrier, size_t len, uint8_t **out, size_t *out_len);
+
+/* APP11 segments of a JPEG, the scan left as it was */
+int steg_hide_jpeg(const uint8_t *carrier, size_t len, const uint8_t *payload, size_t plen, uint8_t **out, size_t *out_len);
+int steg_find_jpeg(const uint8_t *carrier, size_t len, uint8_t **out, size_t *out_len);
+
+/* Releases a buffer returned through out/out_len. NULL is ignored. */
+void steg_free(uint8_t *buf, size_t len);
+
+/* The message for the last failed call on this thread, "" if none. Valid until the next failure on
+ * the same thread; not to be freed. */
+const char *steg_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
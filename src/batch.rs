@@ -0,0 +1,194 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::steg_algorithms::error::StegError;
+
+// hide/find/detect/capacity over every file in a directory, or every file a glob pattern matches. A
+// file that fails or doesn't apply is reported and the batch carries on; the summary at the end lists
+// what happened to each. Ctrl-C stops it after the file in progress, and the summary then names the
+// files it didn't get to.
+
+pub enum Outcome {
+    Done,
+    /// Not attempted, or didn't fit, with the reason.
+    Skipped(String),
+    Failed(String),
+}
+
+#[derive(Default)]
+pub struct Summary {
+    done: Vec<String>,
+    skipped: Vec<(String, String)>,
+    failed: Vec<(String, String)>,
+    not_started: Vec<String>,
+}
+
+/// The files directly in `dir` (subdirectories aren't entered), sorted by name.
+pub fn files(dir: &Path) -> Result<Vec<PathBuf>, StegError> {
+    let entries = fs::read_dir(dir).map_err(|e| StegError::io(format!("Failed to read {}", dir.display()), e))?;
+    let mut files: Vec<PathBuf> = entries.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.is_file()).collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Whether `-i` is a glob pattern (`covers/*.png`) rather than a path: it has a wildcard in it and
+/// nothing by that name exists. Patterns are expanded here, not left to the shell, which cmd.exe doesn't
+/// do.
+pub fn is_pattern(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '[']) && !path.exists()
+}
+
+/// The files `pattern` matches, sorted. Matching nothing is an error.
+pub fn expand(pattern: &Path) -> Result<Vec<PathBuf>, StegError> {
+    let text = pattern.to_string_lossy();
+    let paths = glob::glob(&text).map_err(|e| format!("Bad pattern '{}': {}", text, e))?;
+    let mut files: Vec<PathBuf> = paths.filter_map(|p| p.ok()).filter(|p| p.is_file()).collect();
+    if files.is_empty() {
+        return Err(format!("'{}' matches no files", text).into());
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// A dotfile, which a pattern's matches leave out.
+pub fn is_hidden(path: &Path) -> bool {
+    path.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.'))
+}
+
+/// Make sure `out_dir` can take the outputs for `files` (matched by `pattern`): it is a directory (made
+/// when missing, unless it has an extension and so was likely meant as a file), none of them is in it,
+/// and no two would be written under the same name.
+pub fn prepare_output_dir_for(pattern: &Path, files: &[PathBuf], out_dir: &Path) -> Result<(), StegError> {
+    if !out_dir.is_dir() && (out_dir.exists() || out_dir.extension().is_some()) {
+        return Err(format!("'{}' matches {} files, so -o has to be a directory", pattern.display(), files.len()).into());
+    }
+    fs::create_dir_all(out_dir).map_err(|e| StegError::io(format!("Failed to create {}", out_dir.display()), e))?;
+    let out = fs::canonicalize(out_dir).ok();
+    for (i, file) in files.iter().enumerate() {
+        if file.parent().and_then(|d| fs::canonicalize(if d.as_os_str().is_empty() { Path::new(".") } else { d }).ok()) == out {
+            return Err(format!("{} is in the output directory, it would be overwritten", file.display()).into());
+        }
+        if let Some(other) = files[..i].iter().find(|f| f.file_name() == file.file_name()) {
+            return Err(format!("{} and {} would both be written as {}", other.display(), file.display(), file_name(file)).into());
+        }
+    }
+    Ok(())
+}
+
+/// `prepare_output_dir` or `prepare_output_dir_for`, whichever `source` (what -i said) calls for.
+pub fn prepare_output(source: &Path, files: &[PathBuf], out_dir: &Path) -> Result<(), StegError> {
+    if source.is_dir() { prepare_output_dir(source, out_dir) } else { prepare_output_dir_for(source, files, out_dir) }
+}
+
+/// Make sure `out_dir` can take the outputs for `in_dir` without overwriting the inputs.
+pub fn prepare_output_dir(in_dir: &Path, out_dir: &Path) -> Result<(), StegError> {
+    if out_dir.exists() && !out_dir.is_dir() {
+        return Err(format!("{} is a directory, so -o has to be one too", in_dir.display()).into());
+    }
+    fs::create_dir_all(out_dir).map_err(|e| StegError::io(format!("Failed to create {}", out_dir.display()), e))?;
+    if fs::canonicalize(in_dir).ok() == fs::canonicalize(out_dir).ok() {
+        return Err("The output directory is the input directory, the carriers would be overwritten".into());
+    }
+    Ok(())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned())
+}
+
+impl Summary {
+    /// Count `outcome` for the file at `path`, reporting failures and skips right away.
+    pub fn record(&mut self, path: &Path, outcome: Outcome) {
+        let name = file_name(path);
+        match outcome {
+            Outcome::Done => self.done.push(name),
+            Outcome::Skipped(why) => {
+                log::warn!("skipped {}: {}", name, why);
+                self.skipped.push((name, why));
+            }
+            Outcome::Failed(e) => {
+                log::error!("failed {}: {}", name, e);
+                self.failed.push((name, e));
+            }
+        }
+    }
+
+    /// The batch was interrupted before it got to `rest`.
+    pub fn interrupted(&mut self, rest: &[PathBuf]) {
+        self.not_started.extend(rest.iter().map(|p| file_name(p)));
+    }
+
+    pub fn was_interrupted(&self) -> bool {
+        !self.not_started.is_empty()
+    }
+
+    pub fn any_done(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    pub fn any_failed(&self) -> bool {
+        !self.failed.is_empty()
+    }
+
+    /// Print the lists, `done` naming what a success means ("hidden", "found").
+    pub fn print(&self, done: &str) {
+        println!("{} {}, {} skipped, {} failed", self.done.len(), done, self.skipped.len(), self.failed.len());
+        if !self.done.is_empty() {
+            println!("{}: {}", done, self.done.join(", "));
+        }
+        for (name, why) in &self.skipped {
+            println!("skipped: {} ({})", name, why);
+        }
+        for (name, e) in &self.failed {
+            println!("failed: {} ({})", name, e);
+        }
+        if self.was_interrupted() {
+            println!("interrupted, not started: {}", self.not_started.join(", "));
+            println!("run the same command again to pick up where it stopped");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn lists_files_only_and_guards_the_output_dir() {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("in");
+        fs::create_dir_all(input.join("nested")).unwrap();
+        for name in ["b.png", "a.wav", "nested/c.png"] {
+            fs::write(input.join(name), b"x").unwrap();
+        }
+        assert_eq!(files(&input).unwrap(), [input.join("a.wav"), input.join("b.png")]);
+
+        let out = dir.path().join("out/deeper");
+        prepare_output_dir(&input, &out).unwrap();
+        assert!(out.is_dir());
+        assert!(prepare_output_dir(&input, &input.join("nested/..")).unwrap_err().to_string().contains("overwritten"));
+        assert!(prepare_output_dir(&input, &input.join("a.wav")).is_err());
+    }
+
+    #[test]
+    fn patterns_expand_to_sorted_files() {
+        let dir = tempdir().unwrap();
+        for name in ["b.png", "a.png", ".hidden.png", "c.wav"] {
+            fs::write(dir.path().join(name), b"x").unwrap();
+        }
+        fs::create_dir(dir.path().join("d.png")).unwrap();
+        let pattern = dir.path().join("*.png");
+        assert!(is_pattern(&pattern));
+        assert!(!is_pattern(&dir.path().join("a.png")));
+        let files = expand(&pattern).unwrap();
+        assert_eq!(files.iter().filter(|f| !is_hidden(f)).collect::<Vec<_>>(), [&dir.path().join("a.png"), &dir.path().join("b.png")]);
+        assert!(expand(&dir.path().join("*.gif")).unwrap_err().to_string().contains("matches no files"));
+
+        assert!(prepare_output_dir_for(&pattern, &files, &dir.path().join("a.png")).unwrap_err().to_string().contains("directory"));
+        assert!(prepare_output_dir_for(&pattern, &files, &dir.path().join("new.png")).unwrap_err().to_string().contains("directory"));
+        assert!(prepare_output_dir_for(&pattern, &files, dir.path()).unwrap_err().to_string().contains("overwritten"));
+        prepare_output_dir_for(&pattern, &files, &dir.path().join("out")).unwrap();
+        let twins = [dir.path().join("a.png"), dir.path().join("out/a.png")];
+        assert!(prepare_output_dir_for(&pattern, &twins, &dir.path().join("other")).unwrap_err().to_string().contains("both"));
+    }
+}
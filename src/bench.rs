@@ -0,0 +1,190 @@
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use image::{Rgb, RgbImage};
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use crate::{Cli, CliError, Command};
+use crate::steg_algorithms::picture::general::{overlay, transcode};
+use crate::steg_algorithms::picture::general::lsb::LsbOptions;
+use crate::steg_algorithms::progress::{self, Stage};
+use crate::steg_algorithms::registry::{self, Options};
+use crate::steg_algorithms::report::{BenchReport, BenchResult, FindPhases, HidePhases};
+use crate::steg_algorithms::error::StegError;
+
+// `bench`: how fast hide and find are on this machine, as a number to track instead of test times.
+// Carriers of the asked size are made up in the workspace (a busy PNG, the same picture as a JPEG, and a
+// WAV with as many samples as the picture has pixels), and each algorithm hides a random payload as big
+// as it holds (up to MAX_PAYLOAD) and finds it again, --iterations times. Times are medians. The phases
+// are where the carrier reads and writes reported in (see progress.rs): decoding is everything up to the
+// end of the last read, encoding everything from the start of the write, so algorithms that don't read
+// or write through there only get totals. WAV LSB writes as it reads, which makes its hide all decoding.
+
+/// The largest payload a run hides, so marker's 4 GB of room doesn't turn into a 4 GB payload.
+const MAX_PAYLOAD: usize = 1024 * 1024;
+
+/// A carrier size given as `WxH`.
+pub fn parse_size(s: &str) -> Result<(u32, u32), StegError> {
+    let parsed = s.split_once(['x', 'X']).and_then(|(w, h)| Some((w.trim().parse::<u32>().ok()?, h.trim().parse::<u32>().ok()?)));
+    match parsed {
+        Some((w, h)) if w >= overlay::MIN_SIDE && h >= overlay::MIN_SIDE && w <= 16384 && h <= 16384 => Ok((w, h)),
+        Some(_) => Err(format!("both sides have to be between {} and 16384 pixels, got '{}'", overlay::MIN_SIDE, s).into()),
+        None => Err(format!("expected WIDTHxHEIGHT, got '{}'", s).into()),
+    }
+}
+
+/// Run the benchmark the bench command describes and print it.
+pub fn run(cli: &Cli) -> Result<(), CliError> {
+    let Command::Bench { size: (w, h), iterations } = &cli.cmd else {
+        unreachable!("bench::run is only called for the bench command");
+    };
+    let workspace = crate::workspace(cli)?;
+    let (png, jpeg, audio) = (workspace.file(".png"), workspace.file(".jpg"), workspace.file(".wav"));
+    synthesize(*w, *h, &png, &jpeg, &audio)?;
+    workspace.check_quota()?;
+
+    let mut report = BenchReport { size: format!("{}x{}", w, h), iterations: *iterations, results: Vec::new() };
+    for (ft, alg, cover) in [("picture", "lsb", &png), ("picture", "overlay", &png), ("picture", "marker", &jpeg), ("audio", "lsb", &audio)] {
+        if !cli.json {
+            crate::status(format_args!("bench: {} {}", ft, alg));
+        }
+        let result = measure(ft, alg, cover, &workspace.file(&format!(".{}", ext(cover))), *iterations)
+            .map_err(|e| format!("bench failed on {} {}: {}", ft, alg, e))?;
+        report.results.push(result);
+        workspace.check_quota()?;
+    }
+    if cli.json {
+        println!("{}", serde_json::to_string(&report).expect("reports always serialize"));
+    } else {
+        print_table(&report);
+    }
+    Ok(())
+}
+
+fn ext(path: &Path) -> String {
+    path.extension().map_or_else(String::new, |e| e.to_string_lossy().into_owned())
+}
+
+// a gradient with a little noise, smooth like a photo (which overlay needs) but not something PNG can
+// squeeze to nothing, and a WAV of as many samples
+fn synthesize(w: u32, h: u32, png: &Path, jpeg: &Path, wav_path: &Path) -> Result<(), StegError> {
+    let mut rng = ChaCha20Rng::seed_from_u64(u64::from(w) << 32 | u64::from(h));
+    let mut noisy = |v: u32| (v as i32 + (rng.next_u32() % 5) as i32 - 2).clamp(0, 255) as u8;
+    RgbImage::from_fn(w, h, |x, y| Rgb([noisy(x * 255 / w), noisy(y * 255 / h), noisy((x + y) * 127 / (w + h) + 64)]))
+        .save(png)
+        .map_err(|e| format!("Failed to write {}: {}", png.display(), e))?;
+    std::fs::write(jpeg, transcode::to_jpeg(png, 90)?).map_err(|e| StegError::io(format!("Failed to write {}", jpeg.display()), e))?;
+    let spec = hound::WavSpec { channels: 1, sample_rate: 44100, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+    let mut writer = hound::WavWriter::create(wav_path, spec)?;
+    for i in 0..w as u64 * h as u64 {
+        writer.write_sample(((i * 7919) % 20000) as i16 - 10000)?;
+    }
+    writer.finalize().map_err(StegError::from)
+}
+
+/// Hide and find with `alg` `iterations` times, hiding into `out`.
+fn measure(ft: &str, alg: &str, cover: &Path, out: &Path, iterations: u32) -> Result<BenchResult, StegError> {
+    let (room, _) = crate::carrier_capacity(ft, alg, cover, &LsbOptions::default(), 1)?;
+    let payload_len = room?.min(MAX_PAYLOAD);
+    let mut payload = vec![0u8; payload_len];
+    ChaCha20Rng::seed_from_u64(payload_len as u64).fill_bytes(&mut payload);
+    let (algorithm, look) = (registry::get(ft, alg)?, Options::default());
+
+    let (mut hides, mut finds) = (Vec::new(), Vec::new());
+    for _ in 0..iterations {
+        hides.push(timed(|| algorithm.hide(cover, &payload, out, &look))?);
+        let found = timed(|| crate::extract(ft, alg, out, &look, None).map(|(data, _)| data))?;
+        if found.value != payload {
+            return Err("the payload didn't come back the same".into());
+        }
+        finds.push(found);
+    }
+    let size = |path: &Path| std::fs::metadata(path).map(|m| m.len()).map_err(|e| e.to_string());
+    let (carrier_bytes, stego_bytes) = (size(cover)?, size(out)?);
+    let (hide_ms, find_ms) = (median(hides.iter().map(|t| t.total)), median(finds.iter().map(|t| t.total)));
+    Ok(BenchResult {
+        filetype: ft.to_string(),
+        algorithm: alg.to_string(),
+        carrier_bytes,
+        stego_bytes,
+        payload_bytes: payload_len,
+        hide_ms,
+        find_ms,
+        hide_phases: phases(&hides).map(|(decode_ms, embed_ms, encode_ms)| HidePhases { decode_ms, embed_ms, encode_ms }),
+        find_phases: phases(&finds).map(|(decode_ms, extract_ms, _)| FindPhases { decode_ms, extract_ms }),
+        hide_mb_per_s: megabytes_per_second(stego_bytes, hide_ms),
+        find_mb_per_s: megabytes_per_second(stego_bytes, find_ms),
+    })
+}
+
+/// One run: how long it took in all, and where the reads and writes split it, in milliseconds.
+struct Timed<T> {
+    value: T,
+    total: f64,
+    read_end: Option<f64>,
+    write_start: Option<f64>,
+}
+
+fn timed<T>(work: impl FnOnce() -> Result<T, StegError>) -> Result<Timed<T>, StegError> {
+    let marks: Rc<RefCell<(Option<Duration>, Option<Duration>)>> = Rc::default();
+    let sink = Rc::clone(&marks);
+    let start = Instant::now();
+    let value = progress::report_to(move |stage, done, total| {
+        let mut marks = sink.borrow_mut();
+        match stage {
+            // the last read before the output is started
+            Stage::Reading if marks.1.is_none() && total == Some(done) => marks.0 = Some(start.elapsed()),
+            Stage::Writing if marks.1.is_none() => marks.1 = Some(start.elapsed()),
+            _ => {}
+        }
+    }, work)?;
+    let total = start.elapsed();
+    let (read_end, write_start) = *marks.borrow();
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    Ok(Timed { value, total: ms(total), read_end: read_end.map(ms), write_start: write_start.map(ms) })
+}
+
+// the median time up to the end of the reads, from there to the start of the write and from there to
+// the end, when every run reported its reads
+fn phases<T>(runs: &[Timed<T>]) -> Option<(f64, f64, f64)> {
+    let split: Option<Vec<(f64, f64, f64)>> = runs.iter().map(|t| {
+        let read_end = t.read_end?;
+        let write_start = t.write_start.unwrap_or(t.total).max(read_end);
+        Some((read_end, write_start - read_end, t.total - write_start))
+    }).collect();
+    let split = split.filter(|s| !s.is_empty())?;
+    Some((median(split.iter().map(|s| s.0)), median(split.iter().map(|s| s.1)), median(split.iter().map(|s| s.2))))
+}
+
+fn median(values: impl Iterator<Item = f64>) -> f64 {
+    let mut values: Vec<f64> = values.collect();
+    values.sort_by(f64::total_cmp);
+    match values.len() {
+        0 => 0.0,
+        n if n % 2 == 1 => values[n / 2],
+        n => (values[n / 2 - 1] + values[n / 2]) / 2.0,
+    }
+}
+
+fn megabytes_per_second(bytes: u64, ms: f64) -> f64 {
+    if ms > 0.0 { bytes as f64 / 1e6 / (ms / 1000.0) } else { 0.0 }
+}
+
+fn print_table(report: &BenchReport) {
+    println!("{} carriers, median of {} runs, sizes in bytes, times in ms, MB/s of the stego file", report.size, report.iterations);
+    println!(
+        "{:<16} {:>10} {:>10} {:>9}  {:>8} {:>8} {:>8} {:>8}  {:>8} {:>8} {:>8}  {:>9} {:>9}",
+        "algorithm", "carrier", "stego", "payload", "hide", "decode", "embed", "encode", "find", "decode", "extract", "hide MB/s", "find MB/s"
+    );
+    let ms = |v: Option<f64>| v.map_or_else(|| "-".to_string(), |v| format!("{:.1}", v));
+    for r in &report.results {
+        let (hd, he, hc) = r.hide_phases.as_ref().map_or((None, None, None), |p| (Some(p.decode_ms), Some(p.embed_ms), Some(p.encode_ms)));
+        let (fd, fe) = r.find_phases.as_ref().map_or((None, None), |p| (Some(p.decode_ms), Some(p.extract_ms)));
+        println!(
+            "{:<16} {:>10} {:>10} {:>9}  {:>8.1} {:>8} {:>8} {:>8}  {:>8.1} {:>8} {:>8}  {:>9.1} {:>9.1}",
+            format!("{} {}", r.filetype, r.algorithm), r.carrier_bytes, r.stego_bytes, r.payload_bytes,
+            r.hide_ms, ms(hd), ms(he), ms(hc), r.find_ms, ms(fd), ms(fe), r.hide_mb_per_s, r.find_mb_per_s
+        );
+    }
+}
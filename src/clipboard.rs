@@ -0,0 +1,15 @@
+use arboard::Clipboard;
+use crate::steg_algorithms::error::StegError;
+
+/// Read the current clipboard contents as text.
+pub fn read_text() -> Result<String, StegError> {
+    let mut cb = Clipboard::new().map_err(|e| e.to_string())?;
+    cb.get_text().map_err(|e| e.to_string().into())
+}
+
+/// Put `text` on the clipboard.
+/// On X11/Wayland the contents only outlive this process if a clipboard manager grabs them.
+pub fn write_text(text: &str) -> Result<(), StegError> {
+    let mut cb = Clipboard::new().map_err(|e| e.to_string())?;
+    cb.set_text(text).map_err(|e| e.to_string().into())
+}
@@ -0,0 +1,62 @@
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use crate::steg_algorithms::error::StegError;
+
+// The optional config file: --config, else $RUST_STEGO_CONFIG, else rust-stego/config.toml under
+// $XDG_CONFIG_HOME (~/.config). A missing default file is an empty config; a missing file that was
+// asked for by name is an error.
+//
+//   cover_corpus = "~/stego/covers"   # where hide --auto-cover picks covers from
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub cover_corpus: Option<PathBuf>,
+}
+
+fn default_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(base.join("rust-stego").join("config.toml"))
+}
+
+// `~/...` in a configured path
+fn expand_home(path: PathBuf) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => Path::new(&home).join(rest),
+        _ => path,
+    }
+}
+
+pub fn parse(text: &str) -> Result<Config, StegError> {
+    let mut config: Config = toml::from_str(text).map_err(|e| e.to_string())?;
+    config.cover_corpus = config.cover_corpus.map(expand_home);
+    Ok(config)
+}
+
+/// Load the config file, `explicit` being --config.
+pub fn load(explicit: Option<&Path>) -> Result<Config, StegError> {
+    let named = explicit.map(Path::to_path_buf).or_else(|| std::env::var_os("RUST_STEGO_CONFIG").map(PathBuf::from));
+    let path = match (&named, default_path()) {
+        (Some(p), _) => p.clone(),
+        (None, Some(p)) if p.exists() => p,
+        (None, _) => return Ok(Config::default()),
+    };
+    let text = std::fs::read_to_string(&path).map_err(|e| StegError::io(format!("Failed to read config {}", path.display()), e))?;
+    parse(&text).map_err(|e| format!("Bad config {}: {}", path.display(), e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_known_keys_only() {
+        let config = parse("cover_corpus = \"/srv/covers\"\n").unwrap();
+        assert_eq!(config.cover_corpus.as_deref(), Some(Path::new("/srv/covers")));
+        assert!(parse("").unwrap().cover_corpus.is_none());
+        assert!(parse("cover_corpse = \"/srv\"").is_err(), "a typo shouldn't be ignored silently");
+    }
+}
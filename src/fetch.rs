@@ -0,0 +1,110 @@
+use crate::steg_algorithms::error::StegError;
+
+// `-i https://...` for hide and find: the carrier's bytes fetched over HTTP(S) into memory, then handled
+// like a piped one. Only in builds with the `http` feature, the rest say how to get it.
+
+/// How long a whole download, redirects included, gets before it's given up on.
+#[cfg(feature = "http")]
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+#[cfg_attr(not(feature = "http"), allow(dead_code))]
+pub struct FetchOptions {
+    pub max_redirects: u32,
+    pub max_bytes: u64,
+}
+
+/// A downloaded carrier.
+pub struct Fetched {
+    pub bytes: Vec<u8>,
+    /// The Content-Type header without its parameters, lowercased.
+    pub content_type: Option<String>,
+}
+
+/// Whether `input` names a URL rather than a file.
+pub fn is_url(input: &str) -> bool {
+    let lower = input.get(..8).unwrap_or(input).to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+/// The extension of the last segment of `url`'s path, lowercased, if it has one.
+pub fn url_extension(url: &str) -> Option<String> {
+    let after_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let path = after_scheme.split(['?', '#']).next().unwrap_or_default();
+    let (_, name) = path.split_once('/')?;
+    let name = name.rsplit('/').next().unwrap_or_default();
+    let (stem, ext) = name.rsplit_once('.')?;
+    (!stem.is_empty() && !ext.is_empty()).then(|| ext.to_ascii_lowercase())
+}
+
+/// The extension files of `content_type` have.
+pub fn content_type_extension(content_type: &str) -> Option<&'static str> {
+    Some(match content_type {
+        "image/png" => "png",
+        "image/jpeg" | "image/jpg" | "image/pjpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/bmp" | "image/x-bmp" | "image/x-ms-bmp" => "bmp",
+        "image/webp" => "webp",
+        "image/tiff" => "tiff",
+        "image/qoi" => "qoi",
+        "image/x-portable-anymap" | "image/x-portable-pixmap" | "image/x-portable-graymap" => "pnm",
+        "audio/wav" | "audio/wave" | "audio/x-wav" | "audio/vnd.wave" => "wav",
+        "video/mp4" => "mp4",
+        "video/webm" => "webm",
+        "video/x-matroska" => "mkv",
+        "video/quicktime" => "mov",
+        "application/dicom" => "dcm",
+        "application/fits" | "image/fits" => "fits",
+        "text/plain" => "txt",
+        _ => return None,
+    })
+}
+
+/// Download `url`, following at most `opts.max_redirects` redirects and refusing bodies over
+/// `opts.max_bytes`.
+#[cfg(feature = "http")]
+pub fn fetch(url: &str, opts: &FetchOptions) -> Result<Fetched, StegError> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .max_redirects(opts.max_redirects)
+        .max_redirects_will_error(true)
+        .timeout_global(Some(TIMEOUT))
+        .build()
+        .into();
+    let failed = |e: ureq::Error| match e {
+        ureq::Error::TooManyRedirects => format!("{} redirects more than {} times (see --max-redirects)", url, opts.max_redirects),
+        ureq::Error::BodyExceedsLimit(_) => format!("{} is over {} bytes (see --max-download)", url, opts.max_bytes),
+        ureq::Error::StatusCode(status) => format!("Failed to fetch {}: the server answered {}", url, status),
+        e => format!("Failed to fetch {}: {}", url, e),
+    };
+    let mut response = agent.get(url).call().map_err(failed)?;
+    // with no redirects allowed the redirect itself comes back rather than an error
+    if response.status().is_redirection() {
+        return Err(failed(ureq::Error::TooManyRedirects).into());
+    }
+    let content_type = response.headers().get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(|v| v.trim().to_ascii_lowercase())
+        .filter(|v| !v.is_empty());
+    let bytes = response.body_mut().with_config().limit(opts.max_bytes).read_to_vec().map_err(failed)?;
+    Ok(Fetched { bytes, content_type })
+}
+
+#[cfg(not(feature = "http"))]
+pub fn fetch(url: &str, _opts: &FetchOptions) -> Result<Fetched, StegError> {
+    Err(format!("This build can't fetch {}: URLs as -i need the `http` feature (cargo build --features http)", url).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn urls_and_their_extensions() {
+        assert!(is_url("https://host/img.jpg") && is_url("HTTP://host") && !is_url("http.png") && !is_url("-"));
+        assert_eq!(url_extension("https://host/covers/img.JPG?size=large#top").as_deref(), Some("jpg"));
+        assert_eq!(url_extension("https://host/download?name=a.png"), None);
+        assert_eq!(url_extension("https://files.example.com"), None);
+        assert_eq!(url_extension("https://host/.hidden"), None);
+        assert_eq!(content_type_extension("audio/x-wav"), Some("wav"));
+    }
+}
@@ -0,0 +1,259 @@
+//! A C interface to PNG LSB, WAV LSB and JPEG marker hiding, built with the `ffi` feature. The
+//! declarations are in `ffi/rust_stego.h` and `ffi/example.c` shows them in use.
+//!
+//! Every function takes its carrier and payload as pointer and length, returns `STEG_OK` or an error
+//! code, and on success hands back a buffer the caller releases with `steg_free`. After an error,
+//! `steg_last_error` says what went wrong on the thread that called. Payloads are embedded as given,
+//! the way `steg`'s functions do, without the framing the CLI wraps its own in.
+//!
+//! Panics are caught at the boundary and returned as `STEG_ERR_PANIC`. The release profile aborts on
+//! a panic instead, so release builds of the library are made with `--profile release-ffi`, which
+//! unwinds.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::{ptr, slice};
+
+use crate::steg_algorithms::audio::wav::lsb as wav;
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::options::{FindOptions, HideOptions};
+use crate::steg_algorithms::picture::general::lsb;
+use crate::steg_algorithms::picture::jpg::marker_hijacking as marker;
+
+/// Success.
+pub const STEG_OK: i32 = 0;
+/// A null pointer where there should have been data, or a payload too large for its length prefix.
+pub const STEG_ERR_INVALID_ARGUMENT: i32 = 2;
+/// A bug: the call panicked. The message is the panic's.
+pub const STEG_ERR_PANIC: i32 = 9;
+// the rest are `StegError::code`: 1 other, 3 I/O, 4 unsupported or malformed format, 5 doesn't fit,
+// 6 nothing found, 7 checksum mismatch, 8 truncated
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// Hide `plen` bytes at `payload` in the PNG at `carrier`, one bit in each of R, G and B per pixel.
+///
+/// # Safety
+/// `carrier` and `payload` must point to `len` and `plen` readable bytes (either may be null if its
+/// length is 0), and `out` and `out_len` must be writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn steg_hide_png(carrier: *const u8, len: usize, payload: *const u8, plen: usize, out: *mut *mut u8, out_len: *mut usize) -> i32 {
+    unsafe { hide(carrier, len, payload, plen, out, out_len, |c, p| lsb::hide_bytes_with(c, p, &HideOptions::default())) }
+}
+
+/// Find what `steg_hide_png` hid in the PNG at `carrier`.
+///
+/// # Safety
+/// As `steg_hide_png`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn steg_find_png(carrier: *const u8, len: usize, out: *mut *mut u8, out_len: *mut usize) -> i32 {
+    unsafe { find(carrier, len, out, out_len, |c| lsb::find_bytes_with(c, &FindOptions::default().stride(1))) }
+}
+
+/// Hide `plen` bytes at `payload` in the 16-bit PCM WAV at `carrier`, one bit per sample.
+///
+/// # Safety
+/// As `steg_hide_png`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn steg_hide_wav(carrier: *const u8, len: usize, payload: *const u8, plen: usize, out: *mut *mut u8, out_len: *mut usize) -> i32 {
+    unsafe { hide(carrier, len, payload, plen, out, out_len, |c, p| wav::hide_bytes_with(c, p, &HideOptions::default())) }
+}
+
+/// Find what `steg_hide_wav` hid in the WAV at `carrier`.
+///
+/// # Safety
+/// As `steg_hide_png`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn steg_find_wav(carrier: *const u8, len: usize, out: *mut *mut u8, out_len: *mut usize) -> i32 {
+    unsafe { find(carrier, len, out, out_len, |c| wav::find_bytes_with(c, &FindOptions::default().stride(1))) }
+}
+
+/// Hide `plen` bytes at `payload` in APP11 segments of the JPEG at `carrier`, leaving its scan as it
+/// was.
+///
+/// # Safety
+/// As `steg_hide_png`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn steg_hide_jpeg(carrier: *const u8, len: usize, payload: *const u8, plen: usize, out: *mut *mut u8, out_len: *mut usize) -> i32 {
+    unsafe { hide(carrier, len, payload, plen, out, out_len, |c, p| marker::hide_in_bytes(c, p)) }
+}
+
+/// Find what `steg_hide_jpeg` hid in the JPEG at `carrier`.
+///
+/// # Safety
+/// As `steg_hide_png`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn steg_find_jpeg(carrier: *const u8, len: usize, out: *mut *mut u8, out_len: *mut usize) -> i32 {
+    unsafe { find(carrier, len, out, out_len, |c| marker::find_bytes_with(c, &FindOptions::default())) }
+}
+
+/// Release a buffer one of the functions here returned. Null is ignored.
+///
+/// # Safety
+/// `buf` and `len` must be exactly what a `steg_*` call wrote to `out` and `out_len`, and the buffer
+/// must not be used or freed again.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn steg_free(buf: *mut u8, len: usize) {
+    if !buf.is_null() {
+        drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(buf, len)) });
+    }
+}
+
+/// What went wrong in the last call on this thread that failed, as a NUL-terminated string, empty if
+/// none has. It stays valid until the next failing call on the same thread; don't free it.
+#[unsafe(no_mangle)]
+pub extern "C" fn steg_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ptr())
+}
+
+unsafe fn hide(
+    carrier: *const u8,
+    len: usize,
+    payload: *const u8,
+    plen: usize,
+    out: *mut *mut u8,
+    out_len: *mut usize,
+    work: impl FnOnce(&[u8], &[u8]) -> Result<Vec<u8>, StegError>,
+) -> i32 {
+    let (Some(carrier), Some(payload)) = (unsafe { bytes(carrier, len) }, unsafe { bytes(payload, plen) }) else {
+        return fail(STEG_ERR_INVALID_ARGUMENT, "carrier or payload is null");
+    };
+    unsafe { respond(out, out_len, || work(carrier, payload)) }
+}
+
+unsafe fn find(carrier: *const u8, len: usize, out: *mut *mut u8, out_len: *mut usize, work: impl FnOnce(&[u8]) -> Result<Vec<u8>, StegError>) -> i32 {
+    let Some(carrier) = (unsafe { bytes(carrier, len) }) else {
+        return fail(STEG_ERR_INVALID_ARGUMENT, "carrier is null");
+    };
+    unsafe { respond(out, out_len, || work(carrier)) }
+}
+
+// null is only a valid pointer to nothing
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    match (data.is_null(), len) {
+        (true, 0) => Some(&[]),
+        (true, _) => None,
+        (false, _) => Some(unsafe { slice::from_raw_parts(data, len) }),
+    }
+}
+
+// runs `work`, turning its result or panic into a status code and its bytes into a buffer for
+// `steg_free`; `out` is null whenever this doesn't return STEG_OK
+unsafe fn respond(out: *mut *mut u8, out_len: *mut usize, work: impl FnOnce() -> Result<Vec<u8>, StegError>) -> i32 {
+    if out.is_null() || out_len.is_null() {
+        return fail(STEG_ERR_INVALID_ARGUMENT, "out or out_len is null");
+    }
+    unsafe {
+        *out = ptr::null_mut();
+        *out_len = 0;
+    }
+    match panic::catch_unwind(AssertUnwindSafe(work)) {
+        Ok(Ok(data)) => {
+            let data = data.into_boxed_slice();
+            unsafe {
+                *out_len = data.len();
+                *out = Box::into_raw(data).cast();
+            }
+            STEG_OK
+        }
+        Ok(Err(e)) => fail(e.code(), &e.to_string()),
+        Err(panic) => {
+            let msg = panic.downcast_ref::<&str>().copied().or(panic.downcast_ref::<String>().map(String::as_str)).unwrap_or("unknown panic");
+            fail(STEG_ERR_PANIC, &format!("panicked: {}", msg))
+        }
+    }
+}
+
+fn fail(code: i32, msg: &str) -> i32 {
+    let msg = CString::new(msg.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = msg);
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+    use std::io::Cursor;
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(steg_last_error()) }.to_string_lossy().into_owned()
+    }
+
+    // calls `f` the way C would and copies its buffer out
+    fn call(f: impl FnOnce(*mut *mut u8, *mut usize) -> i32) -> Result<Vec<u8>, (i32, String)> {
+        let (mut out, mut out_len) = (ptr::null_mut(), 0);
+        match f(&mut out, &mut out_len) {
+            STEG_OK => {
+                let data = unsafe { slice::from_raw_parts(out, out_len) }.to_vec();
+                unsafe { steg_free(out, out_len) };
+                Ok(data)
+            }
+            code => {
+                assert!(out.is_null());
+                Err((code, last_error()))
+            }
+        }
+    }
+
+    fn png(w: u32, h: u32) -> Vec<u8> {
+        let img = image::RgbImage::from_fn(w, h, |x, y| image::Rgb([x as u8, y as u8, 77]));
+        let mut out = Cursor::new(Vec::new());
+        img.write_to(&mut out, image::ImageFormat::Png).unwrap();
+        out.into_inner()
+    }
+
+    fn wav() -> Vec<u8> {
+        let spec = hound::WavSpec { channels: 1, sample_rate: 8000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let mut out = Cursor::new(Vec::new());
+        let mut w = hound::WavWriter::new(&mut out, spec).unwrap();
+        (0..4000).for_each(|i| w.write_sample((i * 7 % 3000) as i16).unwrap());
+        w.finalize().unwrap();
+        out.into_inner()
+    }
+
+    fn jpeg() -> Vec<u8> {
+        let img = image::RgbImage::from_fn(16, 16, |x, y| image::Rgb([x as u8 * 16, y as u8 * 16, 0]));
+        let mut out = Cursor::new(Vec::new());
+        img.write_to(&mut out, image::ImageFormat::Jpeg).unwrap();
+        out.into_inner()
+    }
+
+    #[test]
+    fn each_algorithm_round_trips() {
+        type Pair = (Vec<u8>, unsafe extern "C" fn(*const u8, usize, *const u8, usize, *mut *mut u8, *mut usize) -> i32, unsafe extern "C" fn(*const u8, usize, *mut *mut u8, *mut usize) -> i32);
+        let pairs: [Pair; 3] = [(png(32, 32), steg_hide_png, steg_find_png), (wav(), steg_hide_wav, steg_find_wav), (jpeg(), steg_hide_jpeg, steg_find_jpeg)];
+        let msg = b"across the border";
+        for (cover, hide, find) in pairs {
+            let stego = call(|o, l| unsafe { hide(cover.as_ptr(), cover.len(), msg.as_ptr(), msg.len(), o, l) }).unwrap();
+            let found = call(|o, l| unsafe { find(stego.as_ptr(), stego.len(), o, l) }).unwrap();
+            assert_eq!(found, msg);
+        }
+    }
+
+    #[test]
+    fn errors_come_back_as_codes_with_a_message() {
+        let cover = png(4, 4);
+        let msg = [0u8; 64];
+        let (code, message) = call(|o, l| unsafe { steg_hide_png(cover.as_ptr(), cover.len(), msg.as_ptr(), msg.len(), o, l) }).unwrap_err();
+        assert_eq!(code, 5);
+        assert!(message.starts_with("Carrier too small"), "{}", message);
+
+        let (code, _) = call(|o, l| unsafe { steg_find_png(ptr::null(), 10, o, l) }).unwrap_err();
+        assert_eq!(code, STEG_ERR_INVALID_ARGUMENT);
+        assert_eq!(unsafe { steg_find_png(cover.as_ptr(), cover.len(), ptr::null_mut(), ptr::null_mut()) }, STEG_ERR_INVALID_ARGUMENT);
+
+        let (code, _) = call(|o, l| unsafe { steg_find_jpeg(cover.as_ptr(), cover.len(), o, l) }).unwrap_err();
+        assert_ne!(code, STEG_OK);
+    }
+
+    #[test]
+    fn panics_stop_at_the_boundary() {
+        let (code, message) = call(|o, l| unsafe { respond(o, l, || panic!("boom")) }).unwrap_err();
+        assert_eq!(code, STEG_ERR_PANIC);
+        assert_eq!(message, "panicked: boom");
+    }
+}
@@ -0,0 +1,176 @@
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use clap::Parser;
+use crate::{Cli, CliError};
+use crate::steg_algorithms::formats;
+use crate::steg_algorithms::error::StegError;
+
+// `interactive`: hide and find by answering questions instead of writing a command line. Each answer is
+// checked as it's given (the input has to exist, the message has to fit), and the answers become the
+// equivalent command line, which is shown and then run as if it had been typed: nothing is decided here
+// that hide and find don't decide themselves. Ctrl-C or the end of input cancels, and nothing is written
+// before the last answer.
+
+/// Ask for a hide or find, then run it.
+pub fn run(cli: &Cli) -> Result<(), CliError> {
+    if cli.json {
+        return Err("interactive asks its questions on the terminal, it has no --json output".into());
+    }
+    let stdin = std::io::stdin();
+    let mut ask = Asker { input: stdin.lock() };
+    eprintln!("Ctrl-C cancels at any point, nothing is written before the last question");
+
+    let op = ask.choose("hide or find", &["hide", "find"], Some("hide"))?;
+    let input = ask.until("input file", None, |a| {
+        let path = PathBuf::from(a);
+        if path.is_file() { Ok(path) } else { Err(format!("{} isn't a file", if a.is_empty() { "that" } else { a }).into()) }
+    })?;
+    let mut report = crate::info(&None, &input, &mut Vec::new())?;
+    let ft = match report.filetype.clone() {
+        Some(ft) => ft,
+        None => {
+            let ft = ask.choose("filetype", &crate::CARRIER_FILETYPES, None)?;
+            report = crate::info(&Some(ft.clone()), &input, &mut Vec::new())?;
+            ft
+        }
+    };
+    crate::print_info(&input, &report);
+
+    // hiding needs room, finding only an algorithm that applies
+    let usable: Vec<&str> = report.algorithms.iter()
+        .filter(|a| op == "find" || a.room.as_ref().is_some_and(|r| r.payload_bytes > 0))
+        .map(|a| a.algorithm.as_str())
+        .collect();
+    if usable.is_empty() {
+        return Err(format!("No algorithm can {} anything in {}", if op == "hide" { "hide" } else { "find" }, input.display()).into());
+    }
+    let ext = input.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    let default = formats::default_algorithm(&ft, &ext).ok().map(|(alg, _)| alg).filter(|alg| usable.contains(alg)).unwrap_or(usable[0]);
+    let alg = ask.choose("algorithm", &usable, Some(default))?;
+
+    let mut args: Vec<String> = vec![op.clone(), "--filetype".into(), ft, "-a".into(), alg.clone(), "-i".into(), input.display().to_string()];
+    if op == "hide" {
+        let room = report.algorithms.iter().find(|a| a.algorithm == alg).and_then(|a| a.room.as_ref()).map_or(usize::MAX, |r| r.payload_bytes);
+        let payload = ask.until("message, or @FILE to hide a file", None, |a| {
+            let (flag, value, size) = match a.strip_prefix('@') {
+                Some(file) => {
+                    let meta = std::fs::metadata(file).map_err(|e| StegError::io(file, e))?;
+                    let name = Path::new(file).file_name().map_or(0, |n| n.len());
+                    ("--msg-file", file, meta.len() as usize + name)
+                }
+                None if a.is_empty() => return Err("there has to be something to hide".into()),
+                None => ("--msg", a, a.len()),
+            };
+            if size > room {
+                return Err(format!("that's {} bytes, {} holds {} in this file", size, alg, room).into());
+            }
+            Ok([flag.to_string(), value.to_string()])
+        })?;
+        args.extend(payload);
+        let stem = input.file_stem().map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+        let suggested = input.with_file_name(format!("{}.stego.{}", stem, ext));
+        let out = output(&mut ask, "output file", Some(&suggested.display().to_string()), &mut args)?;
+        if !ask.confirm(&format!("write {}", out.display()), true)? {
+            return Err(cancelled());
+        }
+    } else {
+        output(&mut ask, "save the payload to (empty prints it)", None, &mut args)?;
+    }
+
+    eprintln!("running: {}", std::iter::once("rust-stego").chain(args.iter().map(String::as_str)).map(quote).collect::<Vec<_>>().join(" "));
+    let parsed = Cli::try_parse_from(std::iter::once("rust-stego".to_string()).chain(args)).map_err(|e| CliError::new(e.to_string(), 2))?;
+    crate::run(&Cli {
+        verbose: cli.verbose,
+        audit_log: cli.audit_log.clone(),
+        json: false,
+        strict: cli.strict,
+        config: cli.config.clone(),
+        quiet: cli.quiet,
+        tmpdir: cli.tmpdir.clone(),
+        tmp_quota: cli.tmp_quota,
+        max_redirects: cli.max_redirects,
+        max_download: cli.max_download,
+        cmd: parsed.cmd,
+    })
+}
+
+// ask for an output path (none when `default` is and the answer is empty), confirming an overwrite,
+// and add it to `args`
+fn output<R: BufRead>(ask: &mut Asker<R>, question: &str, default: Option<&str>, args: &mut Vec<String>) -> Result<PathBuf, CliError> {
+    loop {
+        let answer = ask.line(question, default)?;
+        if answer.is_empty() {
+            return Ok(PathBuf::new());
+        }
+        let out = PathBuf::from(&answer);
+        if out.is_dir() || (out.exists() && !ask.confirm(&format!("{} exists, overwrite it", out.display()), false)?) {
+            continue;
+        }
+        if out.exists() && args[0] == "hide" {
+            args.push("--force".to_string());
+        }
+        args.extend(["-o".to_string(), answer]);
+        return Ok(out);
+    }
+}
+
+fn cancelled() -> CliError {
+    CliError::new("cancelled, nothing was written", 130)
+}
+
+// an argument as it would be typed into a shell
+fn quote(arg: &str) -> String {
+    if !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./:=@,".contains(c)) {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+struct Asker<R> {
+    input: R,
+}
+
+impl<R: BufRead> Asker<R> {
+    /// One answer, `default` when it's left empty. The end of input cancels.
+    fn line(&mut self, question: &str, default: Option<&str>) -> Result<String, CliError> {
+        match default {
+            Some(d) if !d.is_empty() => eprint!("{} [{}]: ", question, d),
+            _ => eprint!("{}: ", question),
+        }
+        let _ = std::io::stderr().flush();
+        let mut line = String::new();
+        if self.input.read_line(&mut line).map_err(|e| StegError::io("Failed to read an answer", e))? == 0 {
+            eprintln!();
+            return Err(cancelled());
+        }
+        let answer = line.trim();
+        Ok(if answer.is_empty() { default.unwrap_or_default().to_string() } else { answer.to_string() })
+    }
+
+    /// Ask again until `check` takes the answer, saying what was wrong with each one it doesn't.
+    fn until<T>(&mut self, question: &str, default: Option<&str>, check: impl Fn(&str) -> Result<T, StegError>) -> Result<T, CliError> {
+        loop {
+            match check(&self.line(question, default)?) {
+                Ok(v) => return Ok(v),
+                Err(e) => eprintln!("  {}", e),
+            }
+        }
+    }
+
+    fn choose(&mut self, question: &str, choices: &[&str], default: Option<&str>) -> Result<String, CliError> {
+        let question = format!("{} ({})", question, choices.join(", "));
+        self.until(&question, default, |a| {
+            choices.iter().find(|c| **c == a).map(|c| c.to_string()).ok_or_else(|| format!("pick one of {}", choices.join(", ")).into())
+        })
+    }
+
+    fn confirm(&mut self, question: &str, default: bool) -> Result<bool, CliError> {
+        let question = format!("{}? {}", question, if default { "[Y/n]" } else { "[y/N]" });
+        self.until(&question, None, |a| match a.to_lowercase().as_str() {
+            "" => Ok(default),
+            "y" | "yes" => Ok(true),
+            "n" | "no" => Ok(false),
+            _ => Err("y or n".into()),
+        })
+    }
+}
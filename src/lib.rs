@@ -0,0 +1,31 @@
+//! Hide data inside pictures, audio and other files, the library behind the `rust-stego` binary.
+//!
+//! The stable surface is [`steg`]: LSB in pictures and WAV audio, JPEG marker segments, and the
+//! framing every payload the CLI hides is wrapped in. Everything takes paths or bytes and reports
+//! errors as a [`steg::StegError`], which tells a missing file, a payload that doesn't fit and a
+//! carrier with nothing in it apart.
+//!
+//! ```
+//! use rust_stego::steg::picture::lsb;
+//!
+//! let dir = tempfile::tempdir().unwrap();
+//! let (cover, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+//! image::RgbImage::from_fn(64, 64, |x, y| image::Rgb([x as u8 * 4, y as u8 * 4, 128])).save(&cover).unwrap();
+//!
+//! lsb::hide(&cover, "meet at noon", &out)?;
+//! assert_eq!(lsb::find(&out)?, "meet at noon");
+//! # Ok::<(), rust_stego::steg::StegError>(())
+//! ```
+
+pub mod steg;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+// Everything the binary is built from. Public so the CLI can reach it, but not part of the API: it
+// changes with the CLI, use `steg` instead.
+#[doc(hidden)]
+pub mod steg_algorithms;
@@ -1,300 +1,3348 @@
-use std::path::PathBuf;
-use clap::{Parser, Subcommand};
+use std::path::{Path, PathBuf};
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use regex::bytes::Regex;
 
-mod steg_algorithms; // your module
+mod batch;
+mod bench;
+mod clipboard;
+mod config;
+mod fetch;
+mod interactive;
+mod progress_bar;
+mod repl;
+mod serve;
+
+use rust_stego::steg_algorithms;
+
+use steg_algorithms::formats;
+use steg_algorithms::hexdump;
+use steg_algorithms::astro::fits;
+use steg_algorithms::medical::dicom;
+use steg_algorithms::picture::raw;
+use steg_algorithms::atomic;
+use steg_algorithms::cancel::{self, Interrupt};
+use steg_algorithms::chunking;
+use steg_algorithms::crypto::Cipher;
+use steg_algorithms::error::StegError;
+use steg_algorithms::params::AlgorithmSpec;
+use steg_algorithms::manifest::{CarrierRecord, Manifest, PayloadRecord, Secrets};
+use steg_algorithms::options::{FindOptions, HideOptions};
+use steg_algorithms::parse;
+use steg_algorithms::redact;
+use steg_algorithms::registry::{self, Options};
+use steg_algorithms::payload::{self, DecodeOptions, FrameOptions, Payload};
+use steg_algorithms::audio::wav::lsb::TimeRange;
+use steg_algorithms::picture::general::lsb::{Embedding, LsbOptions, Region, Traversal};
+use steg_algorithms::picture::jpg::marker_hijacking::MarkerOptions;
+use steg_algorithms::shares::{self, Share};
+use steg_algorithms::report::{AlgorithmRoom, BatchReport, CapacityReport, ConfidenceReport, Entry, FileReport, FindReport, InfoReport, MetaReport, Response, RoomReport, VerifyReport};
+
+const EXIT_CODES: &str = "\
+Exit codes (verify and detect have their own, see their --help):
+  0    success
+  1    any other failure
+  2    bad usage
+  3    a file couldn't be read or written
+  4    unsupported format
+  5    the payload doesn't fit the carrier
+  6    no payload found
+  7    checksum or authentication failed (wrong password or key, or a modified payload)
+  8    the payload is cut short
+  130  interrupted";
 
 #[derive(Parser, Debug)]
-#[command(version, about = "rust-steganography_thing — CLI", long_about = None)]
+#[command(version, about = "rust-steganography_thing — CLI", long_about = None, after_help = EXIT_CODES)]
 struct Cli {
-    /// Verbose output
-    #[arg(short, long)]
-    verbose: bool,
+    /// Say more on stderr about what's going on: -v for debug messages, -vv for trace. Payloads and
+    /// passwords never are, only their lengths and hashes
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Append a hash-chained JSON line describing each hide/find to this file (hashes and settings only,
+    /// never passwords or keys)
+    #[arg(long, global = true)]
+    audit_log: Option<PathBuf>,
+
+    /// Print the result of find, verify, capacity, detect, bench, list-algorithms and hide --dry-run as
+    /// JSON on stdout (find, verify, capacity and detect as one object with `ok`, `warnings` and
+    /// `error`), everything else goes to stderr
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Refuse carriers that break their format's spec (JPEG segment lengths, PNG chunk CRCs, RIFF chunk
+    /// sizes) instead of reading as much of them as can be made out
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Config file to use instead of $RUST_STEGO_CONFIG or ~/.config/rust-stego/config.toml
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Only print errors: no warnings, no lines about what was done and no progress while reading and
+    /// writing big carriers (progress is also off when stderr isn't a terminal, and with --json)
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Directory for intermediate files, instead of $TMPDIR. Each run works in a fresh directory in
+    /// it, removed when the run ends, fails or is interrupted
+    #[arg(long, global = true, value_name = "DIR")]
+    tmpdir: Option<PathBuf>,
+
+    /// Fail instead of putting more than this many bytes of intermediate files into --tmpdir
+    #[arg(long, global = true, value_name = "BYTES")]
+    tmp_quota: Option<u64>,
+
+    /// Follow at most this many redirects fetching an http(s) URL given as -i
+    #[arg(long, global = true, value_name = "N", default_value_t = 5)]
+    max_redirects: u32,
+
+    /// Refuse an http(s) URL given as -i that's more than this many bytes
+    #[arg(long, global = true, value_name = "BYTES", default_value_t = 256 * 1024 * 1024)]
+    max_download: u64,
 
     #[command(subcommand)]
     cmd: Command,
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum FormatChange {
+    /// Explain the problem and stop
+    Abort,
+    /// Switch to an algorithm that survives the output format
+    Auto,
+}
+
+/// How find interprets the extracted bytes
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum PayloadFormat {
+    /// Framed if there's a header, otherwise legacy when it looks like an old text message
+    Auto,
+    /// Only the framed format (magic header)
+    V1,
+    /// The bare message of carriers made before the framing existed
+    Legacy,
+}
+
+/// AEAD for --password, mirrors `crypto::Cipher`
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum CipherChoice {
+    #[value(name = "aes-256-gcm")]
+    Aes256Gcm,
+    /// Faster on CPUs without AES instructions (small ARM boards)
+    #[value(name = "xchacha20-poly1305")]
+    XChaCha20Poly1305,
+}
+
+impl From<CipherChoice> for Cipher {
+    fn from(c: CipherChoice) -> Self {
+        match c {
+            CipherChoice::Aes256Gcm => Cipher::Aes256Gcm,
+            CipherChoice::XChaCha20Poly1305 => Cipher::XChaCha20Poly1305,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Pad {
+    Bytes(usize),
+    Random,
+}
+
+fn parse_quality(s: &str) -> Result<f64, StegError> {
+    match s.parse::<f64>() {
+        Ok(q) if (0.0..=1.0).contains(&q) => Ok(q),
+        _ => Err(format!("expected a quality between 0 and 1, got '{}'", s).into()),
+    }
+}
+
+fn parse_sigma(s: &str) -> Result<f64, StegError> {
+    match s.parse::<f64>() {
+        Ok(sigma) if sigma > 0.0 && sigma <= 16.0 => Ok(sigma),
+        _ => Err(format!("expected a standard deviation above 0 and up to 16, got '{}'", s).into()),
+    }
+}
+
+/// `rs` or `rs:<parity bytes>`
+fn parse_fec(s: &str) -> Result<usize, StegError> {
+    let parity = match s.split_once(':') {
+        None if s == "rs" => steg_algorithms::fec::DEFAULT_PARITY,
+        Some(("rs", n)) => n.parse().map_err(|_| format!("expected a parity byte count after 'rs:', got '{}'", n))?,
+        _ => return Err(format!("expected 'rs' or 'rs:<parity>', got '{}'", s).into()),
+    };
+    if !(2..=128).contains(&parity) {
+        return Err(format!("parity must be between 2 and 128 bytes, got {}", parity).into());
+    }
+    Ok(parity)
+}
+
+fn parse_sha256(s: &str) -> Result<String, StegError> {
+    if s.len() != 64 || !s.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("expected 64 hex digits, got '{}'", s).into());
+    }
+    Ok(s.to_ascii_lowercase())
+}
+
+fn parse_pad(s: &str) -> Result<Pad, StegError> {
+    if s.eq_ignore_ascii_case("random") {
+        return Ok(Pad::Random);
+    }
+    s.parse().map(Pad::Bytes).map_err(|_| format!("expected a byte count or \"random\", got '{}'", s).into())
+}
+
 #[derive(Subcommand, Debug)]
 enum Command {
     /// Hide a message/file into a carrier
+    #[command(group(ArgGroup::new("payload").required(true).args(["message", "msg_file", "msg_from_clipboard"])))]
     Hide {
-        /// File type (audio, picture, text, video). If omitted will be guessed from input file extension.
+        /// File type (audio, picture, text, video, medical, astro). If omitted will be guessed from input file extension.
         #[arg(short, long)]
         filetype: Option<String>,
 
-        /// Algorithm to use (lsb, ...). If omitted a sensible default will be chosen by filetype.
-        #[arg(short, long)]
-        algorithm: Option<String>,
+        /// Algorithm to use (lsb, ...), optionally with settings: lsb:bits=2,channels=rg,stride=3 or
+        /// marker:app=0xEC,id=MyApp (these win over --stride, --key and --offset). If omitted one that suits
+        /// the input extension is chosen: marker for JPEG, appext for GIF, tag for DICOM, lsb otherwise (-v
+        /// says which and why).
+        #[arg(short, long, value_parser = AlgorithmSpec::parse)]
+        algorithm: Option<AlgorithmSpec>,
 
-        /// Input file path
-        #[arg(short = 'i', long)]
-        in_path: PathBuf,
+        /// Input file path, or a directory to hide into every supported file in it, or a glob pattern
+        /// ('covers/*.png', quoted) to hide into every supported file it matches (-o then a directory).
+        /// `-` reads the cover from stdin, which needs --filetype as there's no extension to go by, and an
+        /// http(s) URL fetches it (builds with the `http` feature).
+        #[arg(short = 'i', long, required_unless_present = "auto_cover")]
+        in_path: Option<PathBuf>,
 
-        /// Output path (where the stego file will be written)
+        /// Instead of -i, pick the cover from the config file's cover_corpus directory: the one of the
+        /// output's media type with the least room that still holds the payload
+        #[arg(long, conflicts_with = "in_path")]
+        auto_cover: bool,
+
+        /// Output path (where the stego file will be written), a directory when -i is one
         #[arg(short = 'o', long)]
         out_path: PathBuf,
 
         /// Message to hide (for text hiding). If embedding a file, change to reading bytes from a file instead.
         #[arg(long = "msg")]
-        message: String,
+        message: Option<String>,
+
+        /// Hide the contents of this file instead of --msg. Its filename is stored so find can restore it.
+        #[arg(long)]
+        msg_file: Option<PathBuf>,
+
+        /// Take the message from the system clipboard instead of --msg
+        #[arg(long)]
+        msg_from_clipboard: bool,
+
+        /// Deflate the payload before embedding (stored raw if that doesn't make it smaller)
+        #[arg(long)]
+        compress: bool,
+
+        /// Encrypt the payload (AES-256-GCM, key derived with Argon2id). Find needs the same password.
+        /// marker encrypts each segment on its own, so the segments that survive still decrypt.
+        #[arg(long)]
+        password: Option<String>,
+
+        /// Encrypt under a random key that is split into two share files (give this twice). find needs
+        /// both shares, so no single holder can open the payload. The files must not exist yet.
+        #[arg(long, value_name = "FILE", conflicts_with = "password")]
+        key_share: Vec<PathBuf>,
+
+        /// Append an HMAC-SHA256 tag keyed with this. The payload stays readable, but find --hmac-key
+        /// can prove it wasn't changed. Works with or without --password.
+        #[arg(long)]
+        hmac_key: Option<String>,
+
+        /// Cipher for --password. Recorded in the payload, so find picks it up by itself.
+        #[arg(long, value_enum, default_value_t = CipherChoice::Aes256Gcm, requires = "password")]
+        cipher: CipherChoice,
+
+        /// Pad the embedded data with random bytes up to this many bytes ("random" picks a size),
+        /// so the stored length doesn't give the payload size away. Shrunk to fit the carrier if needed.
+        #[arg(long, value_parser = parse_pad)]
+        pad: Option<Pad>,
+
+        /// appext only: 11-byte GIF application identifier (8-byte name + 3-byte auth code)
+        #[arg(long, default_value = DEFAULT_APP_ID)]
+        app_id: String,
+
+        /// LSB only: put a bit in every Nth pixel channel/sample instead of every one
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        stride: u32,
+
+        /// LSB only: scatter the bits (header included) over the whole carrier in an order only this key
+        /// reproduces. find needs the same --key.
+        #[arg(long, conflicts_with = "stride")]
+        key: Option<String>,
+
+        /// LSB pictures only: leave the first N RGB channel slots alone (three per pixel, row by row from
+        /// the top left), e.g. to keep the top rows untouched. find needs the same --offset.
+        #[arg(long, default_value_t = 0, conflicts_with = "target_quality")]
+        offset: usize,
+
+        /// LSB pictures only: keep the payload inside this rectangle of pixels, x,y,w,h from the top left
+        /// (a noisy patch of sky, say), so cropping or painting over the rest of the picture leaves it be.
+        /// Every pixel outside it comes out as it went in. find needs the same --region.
+        #[arg(long, value_parser = Region::parse, value_name = "X,Y,W,H", conflicts_with_all = ["perturb", "prenoise"])]
+        region: Option<Region>,
+
+        /// LSB on WAV only: keep the payload inside this stretch of the file, START..END in seconds
+        /// (10s..45s) or sample frames (441000..1984500), either end left open, so fading or trimming the
+        /// ends leaves it be. Every sample outside it comes out as it went in. find locates the payload
+        /// on its own, except with --key, which needs the same --range.
+        #[arg(long, value_parser = TimeRange::parse, value_name = "START..END", conflicts_with = "perturb")]
+        range: Option<TimeRange>,
+
+        /// overlay only: how far (in 0-255 steps) each pixel's brightness is pushed. Higher survives more abuse but shows.
+        #[arg(long, default_value_t = steg_algorithms::picture::general::overlay::DEFAULT_STRENGTH,
+              value_parser = clap::value_parser!(u8).range(1..=32))]
+        strength: u8,
+
+        /// lineshift only: how many pixels each marked text line moves. Use 2+ for print-and-scan.
+        #[arg(long, default_value_t = steg_algorithms::picture::general::lineshift::DEFAULT_SHIFT as u32,
+              value_parser = clap::value_parser!(u32).range(1..=8))]
+        shift: u32,
+
+        /// LSB only: also flip this many random LSBs outside the payload, so the output never hashes the same
+        /// as the input or as an earlier run with the same message (dedup/CDN systems compare hashes)
+        #[arg(long, default_value_t = 0)]
+        perturb: usize,
+
+        /// LSB pictures only: add Gaussian noise with this standard deviation (in 8-bit levels, up to 16) to
+        /// the cover before embedding, so the payload's LSBs sit in a noisy baseline. Seeded from --key, or
+        /// from the cover without one, so the same inputs always give the same output.
+        #[arg(long, value_name = "SIGMA", conflicts_with = "target_quality", value_parser = parse_sigma)]
+        prenoise: Option<f64>,
+
+        /// Pictures only: try the algorithm/stride/strength/compression combinations that fit and use the least
+        /// visible one, failing if even that scores below this SSIM (0..1). Prints the chosen settings.
+        #[arg(long, conflicts_with_all = ["pad", "stride", "strength"], value_parser = parse_quality)]
+        target_quality: Option<f64>,
+
+        /// LSB only: Reed-Solomon protect the payload so find can repair damaged bytes. `rs` uses 32 parity
+        /// bytes per 255 (fixes 16 bad bytes in each), `rs:N` picks N; more parity costs more capacity
+        #[arg(long, value_parser = parse_fec, value_name = "rs[:PARITY]")]
+        fec: Option<usize>,
+
+        /// LSB only: store every bit this many times (odd, 3-15) and let find take a majority vote. Survives
+        /// a few edited pixels or a clipped end of a WAV; needs N times the room. find detects it by itself.
+        #[arg(long, default_value_t = 1, conflicts_with = "target_quality",
+              value_parser = clap::value_parser!(u32).range(1..=steg_algorithms::redundancy::MAX_REDUNDANCY as i64))]
+        redundancy: u32,
+
+        /// LSB only: store the payload under this name next to any named payloads the input already holds
+        /// (one with the same name is replaced). find --name gets it back.
+        #[arg(long, conflicts_with = "target_quality")]
+        name: Option<String>,
+
+        /// Stamp the payload with a small metadata block (time, tool version, algorithm) that
+        /// find --show-meta prints. It isn't part of the payload bytes and adds about 20 bytes.
+        #[arg(long)]
+        meta: bool,
+
+        /// JPEG and PNG outputs: leave out the EXIF, XMP and IPTC segments and comments of a JPEG, or the
+        /// tEXt, zTXt, iTXt and eXIf chunks of a PNG. Segments marker writes itself stay
+        #[arg(long)]
+        strip_metadata: bool,
+
+        /// Fail, and delete the output, if it isn't exactly as long as the input
+        #[arg(long)]
+        preserve_length: bool,
+
+        /// Print the size and SHA-256 of input and output and how many bytes changed
+        #[arg(long)]
+        report_delta: bool,
+
+        /// Pictures and WAV: measure the noise the output carries against the cover (MSE, PSNR in dB,
+        /// bits flipped per megapixel or million samples) and append it to this file as a line of JSON,
+        /// or print it with -
+        #[arg(long, value_name = "FILE")]
+        noise_report: Option<PathBuf>,
+
+        /// Write the filetype, algorithm, its settings and the SHA-256 of the payload, input and output
+        /// to this JSON file, which find --manifest reads them back from. It never holds the key or the
+        /// password, only whether find needs them. A directory gets `<output name>.json` in it, one per
+        /// carrier when -i is a directory too.
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["dry_run", "span"])]
+        manifest: Option<PathBuf>,
+
+        /// Read the payload back from the output and fail, removing it, if it doesn't match. On by
+        /// default for every output
+        #[arg(long, overrides_with = "no_verify")]
+        verify: bool,
+
+        /// Skip the read-back check (it costs a full decode, which adds up for huge carriers)
+        #[arg(long, overrides_with = "verify")]
+        no_verify: bool,
+
+        /// lsb on pictures and WAV: go as far as encoding the output, then report the bits needed, how
+        /// much of the capacity that is, how many pixels or samples would change and the output size,
+        /// without writing anything
+        #[arg(long, conflicts_with = "span")]
+        dry_run: bool,
+
+        /// What to do when the output extension changes the container in a way the algorithm doesn't survive
+        #[arg(long, value_enum, default_value_t = FormatChange::Abort)]
+        on_format_change: FormatChange,
+
+        /// Replace an existing output file. When it is the input itself, the cover is only replaced
+        /// once the hide went through.
+        #[arg(long)]
+        force: bool,
+
+        /// With -i a directory: spread the payload over its files in content-defined chunks plus a
+        /// manifest, instead of hiding all of it in each. Run again with --force after changing the
+        /// payload and only the carriers whose chunk changed (and the manifest's) are rewritten.
+        #[arg(long, conflicts_with_all = ["auto_cover", "name", "key_share", "target_quality"])]
+        span: bool,
+
+        /// --span only: average chunk size in bytes (chunks are a quarter to four times this). Smaller
+        /// chunks fit smaller carriers and keep updates smaller, but take more carriers.
+        #[arg(long, default_value_t = steg_algorithms::chunking::DEFAULT_CHUNK, requires = "span",
+              value_parser = clap::value_parser!(u32).range(64..=1 << 20))]
+        chunk_size: u32,
     },
 
     /// Find/extract hidden message from a carrier
     Find {
-        /// File type (audio, picture, text, video). If omitted will be guessed from input file extension.
+        /// File type (audio, picture, text, video, medical, astro). If omitted will be guessed from input file extension.
         #[arg(short, long)]
         filetype: Option<String>,
 
-        /// Algorithm to use (lsb, ...). If omitted a sensible default will be chosen by filetype.
-        #[arg(short, long)]
-        algorithm: Option<String>,
+        /// Algorithm to use (lsb, ...), optionally with settings: lsb:bits=2,channels=rg,stride=3 or
+        /// marker:app=0xEC,id=MyApp (these win over --stride, --key and --offset). If omitted one that suits
+        /// the input extension is chosen: marker for JPEG, appext for GIF, tag for DICOM, lsb otherwise (-v
+        /// says which and why).
+        #[arg(short, long, value_parser = AlgorithmSpec::parse)]
+        algorithm: Option<AlgorithmSpec>,
 
-        /// Input file path (the stego/carrier)
+        /// Input file path (the stego/carrier), or a directory to try every supported file in it, or a
+        /// glob pattern ('stego/*.png', quoted) to try every supported file it matches. `-` reads the
+        /// carrier from stdin, which needs --filetype as there's no extension to go by, and an http(s)
+        /// URL fetches it (builds with the `http` feature).
         #[arg(short = 'i', long)]
         in_path: PathBuf,
 
         /// Optional output path (for extracted payload). If omitted, prints to stdout.
+        /// If it is a directory the payload is written there under its original filename. In batch mode
+        /// (-i a directory) it has to be a directory and gets one `<carrier>.payload` per carrier.
+        /// `-` writes the payload's raw bytes to stdout, everything else going to stderr.
         #[arg(short = 'o', long)]
         out_path: Option<PathBuf>,
+
+        /// With -o -, write a binary payload even when stdout is a terminal (instead of a hexdump preview)
+        #[arg(long)]
+        force: bool,
+
+        /// Copy the recovered message to the system clipboard instead of printing it
+        #[arg(long)]
+        to_clipboard: bool,
+
+        /// Password the payload was encrypted with at hide time
+        #[arg(long)]
+        password: Option<String>,
+
+        /// One of the two share files hide --key-share wrote (give this twice)
+        #[arg(long, value_name = "FILE", conflicts_with = "password")]
+        key_share: Vec<PathBuf>,
+
+        /// Verify the payload's HMAC tag with this key, failing if it is missing or doesn't match
+        #[arg(long)]
+        hmac_key: Option<String>,
+
+        /// appext only: GIF application identifier used at hide time
+        #[arg(long, default_value = DEFAULT_APP_ID)]
+        app_id: String,
+
+        /// LSB only: stride used at hide time. If omitted, strides up to 64 are tried.
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        stride: Option<u32>,
+
+        /// LSB only: key used to scatter the bits at hide time
+        #[arg(long, conflicts_with = "stride")]
+        key: Option<String>,
+
+        /// LSB pictures only: --offset used at hide time
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+
+        /// LSB pictures only: --region used at hide time
+        #[arg(long, value_parser = Region::parse, value_name = "X,Y,W,H")]
+        region: Option<Region>,
+
+        /// LSB on WAV only: --range used at hide time. Without it the payload is looked for wherever a
+        /// range starts, which a keyed one can't be
+        #[arg(long, value_parser = TimeRange::parse, value_name = "START..END")]
+        range: Option<TimeRange>,
+
+        /// Extract the payload stored under this name. Without it, a carrier holding named payloads
+        /// lists them instead.
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Print the metadata block stored by hide --meta (or say there is none)
+        #[arg(long)]
+        show_meta: bool,
+
+        /// Payload format to expect. auto falls back to legacy for old carriers without a header.
+        #[arg(long, value_enum, default_value_t = PayloadFormat::Auto)]
+        format: PayloadFormat,
+
+        /// With -i a directory: put back together a payload hide --span spread over its files
+        #[arg(long, conflicts_with_all = ["to_clipboard", "key_share", "name", "show_meta", "redact_pattern"])]
+        span: bool,
+
+        /// Replace every match of this regex in the payload before it is written or printed (give it
+        /// again for more patterns). The SHA-256 of the unredacted payload is reported and audit-logged.
+        #[arg(long, value_name = "REGEX", value_parser = redact::pattern)]
+        redact_pattern: Vec<Regex>,
+
+        /// What --redact-pattern matches are replaced with
+        #[arg(long, default_value = redact::DEFAULT_MARK, requires = "redact_pattern")]
+        redact_with: String,
+
+        /// Only the first N bytes of the payload, for a peek at a big one. lsb stops reading the carrier
+        /// there when the payload isn't compressed, encrypted or signed (those are read whole, then cut)
+        #[arg(long, value_name = "N", conflicts_with = "span")]
+        max_bytes: Option<usize>,
+
+        /// Print the payload as a hexdump (offset, hex, ASCII), which is what a payload that isn't
+        /// UTF-8 gets anyway. With -o the file still gets the raw bytes, and -v dumps them on stderr.
+        #[arg(long, conflicts_with = "to_clipboard")]
+        hex: bool,
+
+        /// Take the filetype, algorithm and its settings from a manifest hide --manifest wrote (a
+        /// directory: `<carrier name>.json` in it) and check the payload against its SHA-256. --key,
+        /// --password and --key-share still have to be given when it says they were used.
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["algorithm", "stride", "offset", "region", "range", "app_id", "span"])]
+        manifest: Option<PathBuf>,
     },
-}
 
-fn main() {
-    let cli = Cli::parse();
+    /// Check that a carrier still holds an expected payload, for CI: exits 0 when it does, 1 when it holds
+    /// a different one and 2 when none can be read from it
+    #[command(group(ArgGroup::new("expected").required(true).args(["expect_file", "expect_sha256"])))]
+    Verify {
+        /// File type (audio, picture, text, video, medical, astro). If omitted will be guessed from input file extension.
+        #[arg(short, long)]
+        filetype: Option<String>,
 
-    // helper closure to decide filetype (prefer explicit arg, fallback to file extension)
-    let detect_filetype = |ft_opt: &Option<String>, in_path: &PathBuf| -> Result<String, String> {
-        // if user explicitly passed a filetype, accept a few synonyms and normalize
-        if let Some(ft) = ft_opt {
-            let ft_l = ft.to_lowercase();
-            return match ft_l.as_str() {
-                "picture" | "image" | "img" => Ok("picture".to_string()),
-                "video" | "movie" => Ok("video".to_string()),
-                "audio" | "sound" => Ok("audio".to_string()),
-                "text" | "txt" | "string" => Ok("text".to_string()),
-                other => Err(format!("Unknown filetype '{}'. Use picture/video/audio/text.", other)),
-            };
-        }
+        /// Algorithm the payload was hidden with, with its settings (as for find). If omitted one that
+        /// suits the input extension is assumed.
+        #[arg(short, long, value_parser = AlgorithmSpec::parse)]
+        algorithm: Option<AlgorithmSpec>,
 
-        // otherwise try to guess from extension
-        let ext = in_path
-            .extension()
-            .and_then(|e| e.to_str())
-            .ok_or_else(|| "Could not detect file extension; provide --filetype".to_string())?
-            .to_lowercase();
+        /// The carrier to check
+        #[arg(short = 'i', long)]
+        in_path: PathBuf,
 
-        match ext.as_str() {
-            // images
-            "png" | "jpg" | "jpeg" | "bmp" | "gif" | "webp" | "tiff" | "tif" |
-            "heic" | "heif" | "avif" | "ico" => Ok("picture".to_string()),
+        /// The payload it should hold (what was hidden, before any --compress or --password)
+        #[arg(long, value_name = "FILE")]
+        expect_file: Option<PathBuf>,
 
-            // video
-            "mp4" | "mkv" | "mov" | "avi" | "webm" | "flv" | "mpeg" | "mpg" |
-            "m4v" | "ogv" | "3gp" => Ok("video".to_string()),
+        /// The SHA-256 of the payload it should hold, in hex
+        #[arg(long, value_name = "HASH", value_parser = parse_sha256)]
+        expect_sha256: Option<String>,
 
-            // audio
-            "wav" | "mp3" | "flac" | "ogg" | "opus" | "aac" | "m4a" | "wma" | "alac" => Ok("audio".to_string()),
+        /// Password the payload was encrypted with at hide time
+        #[arg(long)]
+        password: Option<String>,
 
-            // text-ish
-            "txt" | "md" | "markdown" | "csv" | "json" | "xml" | "yml" | "yaml" | "html" | "htm" => Ok("text".to_string()),
+        /// Verify the payload's HMAC tag with this key too, failing if it is missing or doesn't match
+        #[arg(long)]
+        hmac_key: Option<String>,
 
-            other => Err(format!("Unrecognized extension '{}'. Provide --filetype (picture/video/audio/text).", other)),
-        }
-    };
+        /// appext only: GIF application identifier used at hide time
+        #[arg(long, default_value = DEFAULT_APP_ID)]
+        app_id: String,
 
-    match &cli.cmd {
-        Command::Hide { filetype, algorithm, in_path, out_path, message } => {
-            let ft = match detect_filetype(filetype, in_path) {
-                Ok(v) => v,
-                Err(e) => { eprintln!("{}", e); std::process::exit(1); }
-            };
-            let alg = algorithm.as_deref().unwrap_or_else(|| match ft.as_str() {
-                "wav" | "wave" | "audio" => "lsb",
-                "picture" => "lsb",
-                _ => "lsb", // default fallback
-            });
+        /// LSB only: stride used at hide time. If omitted, strides up to 64 are tried.
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        stride: Option<u32>,
 
-            if cli.verbose {
-                println!("hide — filetype: {}, algorithm: {}, in: {:?}, out: {:?}, msg: {}",
-                         ft, alg, in_path, out_path, message);
-            }
-
-            match ft.as_str() {
-                "wav" | "wave" | "audio" => {
-                    match alg {
-                        "lsb" => {
-                            // build bits (32-bit len header + msg bytes, MSB-first)
-                            let msg_len = message.len() as u32;
-                            let mut bits: Vec<u8> = Vec::with_capacity(32 + message.len() * 8);
-                            for i in (0..32).rev() { bits.push(((msg_len >> i) & 1) as u8); }
-                            for b in message.bytes() {
-                                for i in (0..8).rev() { bits.push(((b >> i) & 1) as u8); }
-                            }
-
-                            // call your module
-                            if let Err(e) = steg_algorithms::audio::wav::lsb::hide_wav(in_path, out_path, &bits) {
-                                eprintln!("hide failed: {}", e);
-                                std::process::exit(1);
-                            } else if cli.verbose {
-                                println!("hide succeeded!");
-                            }
-                        }
-                        other => {
-                            eprintln!("Unsupported algorithm '{}' for audio", other);
-                            std::process::exit(1);
-                        }
-                    }
-                }
+        /// LSB only: key used to scatter the bits at hide time
+        #[arg(long, conflicts_with = "stride")]
+        key: Option<String>,
 
-                "picture" => {
-                    match alg {
-                        "lsb" => {
-                            if let Err(e) = steg_algorithms::picture::general::lsb::hide(in_path, message, out_path) {
-                                eprintln!("hide failed: {}", e);
-                                std::process::exit(1);
-                            } else if cli.verbose {
-                                println!("hide succeeded!");
-                            }
-                        }
-                        
-                        "marker" => {
-                            let ext = in_path.extension()
-                                .and_then(|e| e.to_str())
-                                .ok_or("Invalid file extension")
-                                .unwrap();
-                            if ext == "jpg" || ext == "jpeg" {
-                                if let Err(e) = steg_algorithms::picture::jpg::marker_hijacking::hide(in_path, message, out_path) {
-                                    eprintln!("hide failed: {}", e);
-                                } else if cli.verbose {
-                                    println!("hide succeeded! :3")
-                                }
-                            } else { 
-                                println!("You can only use marker hijacking with jpeg files >:(")
-                            }
-                        }
-                        other => {
-                            eprintln!("Unsupported algorithm '{}' for picture", other);
-                            std::process::exit(1);
-                        }
-                    }
-                }
+        /// LSB pictures only: --offset used at hide time
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
 
-                other => {
-                    eprintln!("Unsupported filetype '{}'", other);
-                    std::process::exit(1);
-                }
-            }
-        }
+        /// LSB pictures only: --region used at hide time
+        #[arg(long, value_parser = Region::parse, value_name = "X,Y,W,H")]
+        region: Option<Region>,
 
-        Command::Find { filetype, algorithm, in_path, out_path } => {
-            let ft = match detect_filetype(filetype, in_path) {
-                Ok(v) => v,
-                Err(e) => { eprintln!("{}", e); std::process::exit(1); }
-            };
-            let alg = algorithm.as_deref().unwrap_or_else(|| match ft.as_str() {
-                "wav" | "wave" | "audio" => "lsb",
-                "png" | "bmp" | "picture" => "lsb",
-                _ => "lsb",
-            });
+        /// LSB on WAV only: --range used at hide time
+        #[arg(long, value_parser = TimeRange::parse, value_name = "START..END")]
+        range: Option<TimeRange>,
 
-            if cli.verbose {
-                println!("find — filetype: {}, algorithm: {}, in: {:?}", ft, alg, in_path);
-            }
-
-            match ft.as_str() {
-                "wav" | "wave" | "audio" => {
-                    match alg {
-                        "lsb" => {
-                            let bits = match steg_algorithms::audio::wav::lsb::find_wav(in_path) {
-                                Ok(v) => v,
-                                Err(e) => { eprintln!("find failed: {}", e); std::process::exit(1); }
-                            };
-
-                            // read 32-bit big-endian length
-                            if bits.len() < 32 {
-                                eprintln!("Not enough data for header");
-                                std::process::exit(1);
-                            }
-                            let mut len: u32 = 0;
-                            for i in 0..32 {
-                                len = (len << 1) | (bits[i] as u32);
-                            }
-
-                            let mut bytes: Vec<u8> = Vec::with_capacity(len as usize);
-                            let start = 32;
-                            for byte_idx in 0..(len as usize) {
-                                let base = start + byte_idx * 8;
-                                let mut b: u8 = 0;
-                                for j in 0..8 {
-                                    b = (b << 1) | (bits[base + j] & 1);
-                                }
-                                bytes.push(b);
-                            }
-
-                            let output = String::from_utf8(bytes).unwrap_or_else(|_| "<invalid utf8>".to_string());
-                            if let Some(out) = out_path {
-                                // write to file
-                                if let Err(e) = std::fs::write(out, output.as_bytes()) {
-                                    eprintln!("Failed to write output file: {}", e);
-                                    std::process::exit(1);
-                                }
-                                if cli.verbose { println!("Wrote decoded output to {:?}", out); }
-                            } else {
-                                println!("{}", output);
-                            }
-                        }
-                        other => {
-                            eprintln!("Unsupported algorithm '{}' for audio", other);
-                            std::process::exit(1);
-                        }
-                    }
-                }
+        /// The named payload to check, when the carrier holds several
+        #[arg(long)]
+        name: Option<String>,
+    },
 
-                "picture" => {
-                    match alg {
-                        "lsb" => {
-                            let a = steg_algorithms::picture::general::lsb::find(in_path);
-                            if let Err(e) = a {
-                                eprintln!("find failed: {}", e);
-                                std::process::exit(1);
-                            } else if cli.verbose {
-                                println!("find succeeded, result!");
-                            }
-                            
-                            println!("Result: {}", a.unwrap())
-                        }
-
-                        "marker" => {
-                            let ext = in_path.extension()
-                                .and_then(|e| e.to_str())
-                                .ok_or("Invalid file extension")
-                                .unwrap();
-                            if ext == "jpg" || ext == "jpeg" {
-                                let a = steg_algorithms::picture::jpg::marker_hijacking::find(in_path);
-                                if let Err(e) = &a {
-                                    eprintln!("hide failed: {}", e);
-                                } else if cli.verbose {
-                                    println!("hide succeeded! :3")
-                                }
-                                println!("Result: {}", a.unwrap())
-                            } else {
-                                println!("You can only use marker hijacking with jpeg files >:(")
-                            }
-                        }
-                        
-                        other => {
-                            eprintln!("Unsupported algorithm '{}' for picture", other);
-                            std::process::exit(1);
-                        }
-                    }
-                }
+    /// Move a payload to another carrier or algorithm without decoding it (e.g. PNG lsb to JPEG marker)
+    Convert {
+        /// File type of the input. If omitted will be guessed from input file extension.
+        #[arg(short, long)]
+        filetype: Option<String>,
 
-                other => {
-                    eprintln!("Unsupported filetype '{}'", other);
-                    std::process::exit(1);
-                }
-            }
-        }
-    }
+        /// The carrier holding the payload
+        #[arg(short = 'i', long)]
+        in_path: PathBuf,
+
+        /// Algorithm the payload was hidden with, with its settings (lsb:stride=3, marker:id=MyApp, ...).
+        /// If omitted one that suits the input extension is assumed.
+        #[arg(long, value_parser = AlgorithmSpec::parse)]
+        from: Option<AlgorithmSpec>,
+
+        /// Where the new carrier goes; it may be the input itself (with --force)
+        #[arg(short = 'o', long)]
+        out_path: PathBuf,
+
+        /// Algorithm to move it to, with its settings. If omitted one that suits the output extension is
+        /// chosen (marker for .jpg, ...).
+        #[arg(long, value_parser = AlgorithmSpec::parse)]
+        to: Option<AlgorithmSpec>,
+
+        /// Cover to embed into instead of the input. Without it the input is the cover, and the old copy
+        /// of the payload is wiped from it first (as wipe -a <from> would).
+        #[arg(long)]
+        cover: Option<PathBuf>,
+
+        /// Only needed when the source is marker sealed with --password: the frame is encrypted again
+        /// with it for the destination
+        #[arg(long)]
+        password: Option<String>,
+
+        /// Cipher to encrypt with in that case
+        #[arg(long, value_enum, default_value_t = CipherChoice::Aes256Gcm, requires = "password")]
+        cipher: CipherChoice,
+
+        /// Sign the re-encrypted frame again, when the source carried an HMAC tag
+        #[arg(long, requires = "password")]
+        hmac_key: Option<String>,
+
+        /// Replace an existing output file
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Print how many bytes of payload a carrier holds with the given algorithm and options (for an
+    /// uncompressed --msg; a --msg-file filename takes up to 255 more)
+    Capacity {
+        /// File type (audio, picture, medical, astro). If omitted will be guessed from input file extension.
+        #[arg(short, long)]
+        filetype: Option<String>,
+
+        /// Algorithm to size for (lsb, ...), with settings as for hide. If omitted the one hide would use.
+        #[arg(short, long, value_parser = AlgorithmSpec::parse)]
+        algorithm: Option<AlgorithmSpec>,
+
+        /// The cover, or a glob pattern ('covers/*.png', quoted) to size every supported file it matches
+        #[arg(short = 'i', long)]
+        in_path: PathBuf,
+
+        /// LSB only: put a bit in every Nth pixel channel/sample
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        stride: u32,
+
+        /// LSB pictures only: RGB channel slots left alone at the start
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+
+        /// LSB only: store every bit this many times
+        #[arg(long, default_value_t = 1,
+              value_parser = clap::value_parser!(u32).range(1..=steg_algorithms::redundancy::MAX_REDUNDANCY as i64))]
+        redundancy: u32,
+
+        /// LSB only: account for a Reed-Solomon envelope (`rs` or `rs:N`)
+        #[arg(long, value_parser = parse_fec, value_name = "rs[:PARITY]")]
+        fec: Option<usize>,
+
+        /// Account for --password encryption with this cipher
+        #[arg(long, value_enum)]
+        cipher: Option<CipherChoice>,
+
+        /// Account for an --hmac-key tag
+        #[arg(long)]
+        hmac: bool,
+
+        /// Account for a --meta block
+        #[arg(long)]
+        meta: bool,
+    },
+
+    /// Describe a carrier: what it is, its dimensions or audio format, its JPEG segments, and how much
+    /// each algorithm that applies can hide in it. Whatever can't be worked out is left out with a note.
+    Info {
+        /// File type, as for hide. If omitted guessed from the extension, then from the content.
+        #[arg(short, long)]
+        filetype: Option<String>,
+
+        #[arg(short = 'i', long)]
+        in_path: PathBuf,
+    },
+
+    /// Try every algorithm that applies to a file and report which find a payload. Exits 0 when at least
+    /// one does, 1 when none does, 2 when the file can't be probed at all.
+    Detect {
+        /// The file, a directory to probe every supported file in, or a glob pattern ('inbox/*', quoted)
+        /// to probe every supported file it matches
+        #[arg(short = 'i', long)]
+        in_path: PathBuf,
+
+        /// With a directory, walk the whole tree under it (symlinks aren't followed)
+        #[arg(short, long)]
+        recursive: bool,
+
+        /// With a directory, skip files bigger than this many bytes
+        #[arg(long, value_name = "BYTES")]
+        max_size: Option<u64>,
+    },
+
+    /// Make a cleaned copy of a file: cut out the segments/extensions and appended data payloads hide in,
+    /// and randomize LSB planes
+    Wipe {
+        #[arg(short = 'i', long)]
+        in_path: PathBuf,
+
+        /// Where the cleaned copy goes
+        #[arg(short = 'o', long, required_unless_present = "dry_run")]
+        out_path: Option<PathBuf>,
+
+        /// Only wipe this one kind of hiding place
+        #[arg(short, long, value_parser = clap::builder::PossibleValuesParser::new(steg_algorithms::wipe::SCOPES))]
+        algorithm: Option<String>,
+
+        /// Only report what would be removed, write nothing
+        #[arg(long, conflicts_with = "out_path")]
+        dry_run: bool,
+    },
+
+    /// Embed a unique canary beacon (URL or DNS name that alerts when fetched) and record who got the copy
+    #[command(group(ArgGroup::new("beacon").required(true).args(["token_url", "token_domain"])))]
+    Canary {
+        /// File type (audio, picture). If omitted will be guessed from input file extension.
+        #[arg(short, long)]
+        filetype: Option<String>,
+
+        #[arg(short = 'i', long)]
+        in_path: PathBuf,
+
+        #[arg(short = 'o', long)]
+        out_path: PathBuf,
+
+        /// Beacon URL; the token is appended as a path segment, or replaces `{token}`
+        #[arg(long)]
+        token_url: Option<String>,
+
+        /// Beacon domain; the token becomes a subdomain (DNS canary)
+        #[arg(long)]
+        token_domain: Option<String>,
+
+        /// Who this copy goes to, stored in the registry for `identify`
+        #[arg(long)]
+        recipient: Option<String>,
+
+        /// JSON-lines file mapping tokens to assets and recipients
+        #[arg(long, default_value = "canaries.jsonl")]
+        registry: PathBuf,
+
+        /// LSB only: scatter the beacon with this key (find then needs it too)
+        #[arg(long)]
+        key: Option<String>,
+    },
+
+    /// Map a triggered canary (token, URL or hostname) back to the asset and recipient
+    Identify {
+        /// The token, or the URL/hostname the canary service reported
+        triggered: String,
+
+        #[arg(long, default_value = "canaries.jsonl")]
+        registry: PathBuf,
+    },
+
+    /// Put a stego picture through a chain of lossy transformations and report which payloads survive
+    Simulate {
+        /// The stego picture
+        #[arg(short = 'i', long)]
+        in_path: PathBuf,
+
+        /// Comma separated steps: jpeg:QUALITY, resize:FACTOR, crop:FRACTION, blur:SIGMA, png
+        #[arg(long, required = true, value_delimiter = ',', value_parser = steg_algorithms::picture::general::simulate::Op::parse)]
+        ops: Vec<steg_algorithms::picture::general::simulate::Op>,
+
+        /// LSB only: stride used at hide time. If omitted, strides up to 64 are tried.
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        stride: Option<u32>,
+
+        /// LSB only: key used to scatter the bits at hide time
+        #[arg(long, conflicts_with = "stride")]
+        key: Option<String>,
+    },
+
+    /// Look for hidden data (our payloads, appended files, LSB statistics) in files and directories
+    Scan {
+        /// Files or directories to scan
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Walk directories recursively, pre-filter cheaply and only run the detectors on suspicious
+        /// files, most suspicious first
+        #[arg(long)]
+        deep: bool,
+
+        /// Skip files bigger than this many bytes
+        #[arg(long)]
+        max_size: Option<u64>,
+
+        /// Only print findings this matches, e.g. "score > 0.8 && filetype == 'jpeg'". Fields: score,
+        /// filetype, detector, detail, path; operators: == != < <= > >= && || ! and parentheses
+        #[arg(long = "where", value_name = "EXPR", value_parser = steg_algorithms::filter::Filter::parse)]
+        filter: Option<steg_algorithms::filter::Filter>,
+    },
+
+    /// Dump bit planes of a picture's gray or RGB channels (at the picture's own 8 or 16 bits) or a PCM16
+    /// WAV's samples to a raw file, one bit per channel or sample in the order LSB hide uses, packed MSB first
+    ExportPlane {
+        /// File type (audio, picture). If omitted will be guessed from input file extension.
+        #[arg(short, long)]
+        filetype: Option<String>,
+
+        #[arg(short = 'i', long)]
+        in_path: PathBuf,
+
+        /// The raw plane file
+        #[arg(short = 'o', long)]
+        out_path: PathBuf,
+
+        /// Comma separated bit planes, 0 being the LSB, up to 7 for 8-bit pictures and 15 for 16-bit ones
+        /// and WAV. With several, each channel's (or sample's) bits follow one another in this order.
+        #[arg(long, value_delimiter = ',', default_value = "0", value_parser = clap::value_parser!(u8).range(0..16))]
+        bits: Vec<u8>,
+
+        /// Pictures only: which of the r, g and b channels to take (a gray picture has just the one)
+        #[arg(long, default_value = "rgb")]
+        channels: String,
+    },
+
+    /// Write a copy of a picture or WAV with its bit planes replaced by a raw file from export-plane
+    ImportPlane {
+        /// File type (audio, picture). If omitted will be guessed from input file extension.
+        #[arg(short, long)]
+        filetype: Option<String>,
+
+        /// The carrier the plane was exported from
+        #[arg(short = 'i', long)]
+        in_path: PathBuf,
+
+        /// The (modified) raw plane file; it must be exactly as long as the export was
+        #[arg(long)]
+        plane: PathBuf,
+
+        /// Where the new carrier goes (a lossless picture format, or .wav)
+        #[arg(short = 'o', long)]
+        out_path: PathBuf,
+
+        /// The --bits the plane was exported with
+        #[arg(long, value_delimiter = ',', default_value = "0", value_parser = clap::value_parser!(u8).range(0..16))]
+        bits: Vec<u8>,
+
+        /// The --channels the plane was exported with
+        #[arg(long, default_value = "rgb")]
+        channels: String,
+    },
+
+    /// Check the hash chain of an --audit-log file
+    AuditVerify {
+        log: PathBuf,
+    },
+
+    /// Hide or find by answering questions: the input file, the algorithm (with what each one holds),
+    /// the message and the output. The answers are checked as they're given, then run as the hide or
+    /// find command line they add up to, which is shown first
+    Interactive,
+
+    /// Look at a suspicious file interactively: open it once, then try algorithms, keys and offsets
+    /// against it and decode what they find with one password after another, without reading and
+    /// decoding the file again for every attempt (type help at the prompt for the commands)
+    Repl {
+        /// File type, as for find, for every file opened. If omitted guessed from each file's extension.
+        #[arg(short, long)]
+        filetype: Option<String>,
+
+        /// The file to open first
+        #[arg(short = 'i', long)]
+        in_path: Option<PathBuf>,
+
+        /// appext only: GIF application identifier used at hide time
+        #[arg(long, default_value = DEFAULT_APP_ID)]
+        app_id: String,
+    },
+
+    /// Time hide and find with each algorithm on carriers made up for the purpose, for comparing
+    /// algorithms and machines and catching slowdowns (a table, or one JSON object with --json)
+    Bench {
+        /// Size of the made-up picture; the WAV gets as many samples as it has pixels
+        #[arg(long, value_parser = bench::parse_size, value_name = "WxH", default_value = "1024x1024")]
+        size: (u32, u32),
+
+        /// Runs of each algorithm, the medians are reported
+        #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+        iterations: u32,
+    },
+
+    /// List every filetype's algorithms and the options each one takes
+    ///
+    /// With --json, a machine-readable description (option types, ranges, defaults) for front-ends
+    #[command(visible_alias = "algorithms")]
+    ListAlgorithms,
+
+    /// Answer hide and find requests over HTTP: POST /hide and POST /extract with a multipart body
+    /// (needs a build with the `serve` feature)
+    ///
+    /// /hide takes a `carrier` part, a `payload` part and optionally an `options` part, JSON with
+    /// password, hmac-key, compress, stride and key, and answers with the stego carrier. /extract takes
+    /// `carrier` and `options` and answers with the payload, or a 404 with a JSON error when there's
+    /// none. Nothing is written to disk. /hide/stream is /hide for WAVs and JPEGs of any size: with
+    /// `carrier` as the last part, it's marked as it's uploaded and sent straight back in chunks, so
+    /// the client has to read the response while it uploads.
+    Serve {
+        /// Address and port to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: String,
+
+        /// Refuse request bodies over this many bytes
+        #[arg(long, value_name = "BYTES", default_value_t = 64 * 1024 * 1024)]
+        max_upload: usize,
+
+        /// Refuse /hide/stream carriers over this many bytes (they're marked as they arrive, never held whole)
+        #[arg(long, value_name = "BYTES", default_value_t = 4 << 30)]
+        max_stream: u64,
+
+        /// Requests answered at once, the rest wait; memory stays under about workers × 2 × --max-upload
+        #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..))]
+        workers: u32,
+    },
+}
+
+// decide the filetype (prefer the explicit arg, fall back to the file extension)
+fn detect_filetype(ft_opt: &Option<String>, in_path: &Path) -> Result<String, StegError> {
+    // if user explicitly passed a filetype, accept a few synonyms and normalize
+    if let Some(ft) = ft_opt {
+        let ft_l = ft.to_lowercase();
+        return match ft_l.as_str() {
+            "picture" | "image" | "img" => Ok("picture".to_string()),
+            "video" | "movie" => Ok("video".to_string()),
+            "audio" | "sound" => Ok("audio".to_string()),
+            "text" | "txt" | "string" => Ok("text".to_string()),
+            "medical" | "dicom" | "dcm" => Ok("medical".to_string()),
+            "astro" | "astronomy" | "fits" => Ok("astro".to_string()),
+            other => Err(format!("Unknown filetype '{}'. Use picture/video/audio/text/medical/astro.", other).into()),
+        };
+    }
+
+    // otherwise try to guess from extension
+    let ext = in_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .ok_or_else(|| "Could not detect file extension; provide --filetype".to_string())?
+        .to_lowercase();
+
+    match ext.as_str() {
+        // images
+        "png" | "jpg" | "jpeg" | "bmp" | "gif" | "webp" | "tiff" | "tif" |
+        "heic" | "heif" | "avif" | "ico" |
+        "ppm" | "pgm" | "pnm" | "pam" | "ff" | "qoi" => Ok("picture".to_string()),
+
+        // video
+        "mp4" | "mkv" | "mov" | "avi" | "webm" | "flv" | "mpeg" | "mpg" |
+        "m4v" | "ogv" | "3gp" => Ok("video".to_string()),
+
+        // audio
+        "wav" | "mp3" | "flac" | "ogg" | "opus" | "aac" | "m4a" | "wma" | "alac" => Ok("audio".to_string()),
+
+        // medical imaging
+        "dcm" | "dicom" => Ok("medical".to_string()),
+
+        // astronomy
+        "fits" | "fit" | "fts" => Ok("astro".to_string()),
+
+        // text-ish
+        "txt" | "md" | "markdown" | "csv" | "json" | "xml" | "yml" | "yaml" | "html" | "htm" => Ok("text".to_string()),
+
+        other => Err(format!("Unrecognized extension '{}'. Provide --filetype (picture/video/audio/text/medical/astro).", other).into()),
+    }
+}
+
+/// `--algorithm` if given (its parameters checked against `ft`), otherwise the one that suits the file
+/// at `path` (see `formats::default_algorithm`), saying why with --verbose.
+fn pick_algorithm<'a>(algorithm: Option<&'a AlgorithmSpec>, ft: &str, path: &Path) -> Result<&'a str, StegError> {
+    if let Some(spec) = algorithm {
+        registry::get(ft, &spec.name)?;
+        spec.check(ft)?;
+        return Ok(&spec.name);
+    }
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    let (alg, why) = formats::default_algorithm(ft, &ext)?;
+    log::debug!("auto-selected algorithm '{}': {}", alg, why);
+    Ok(alg)
+}
+
+/// A command that didn't succeed: what to print on stderr and the exit status.
+#[derive(Debug)]
+struct CliError {
+    /// Empty when everything there was to say is already out (a --json response, detect's table).
+    message: String,
+    code: i32,
+}
+
+impl CliError {
+    fn new(message: impl Into<String>, code: i32) -> Self {
+        CliError { message: message.into(), code }
+    }
+
+    /// Exit with `code` without printing anything more.
+    fn silent(code: i32) -> Self {
+        CliError::new(String::new(), code)
+    }
+}
+
+impl From<String> for CliError {
+    fn from(e: String) -> Self {
+        CliError::new(e, 1)
+    }
+}
+
+impl From<&str> for CliError {
+    fn from(e: &str) -> Self {
+        CliError::new(e, 1)
+    }
+}
+
+/// The exit status for each kind of failure, and what to try next where there's something to suggest.
+/// Listed in `--help` as EXIT_CODES.
+fn exit_status(e: &StegError) -> (i32, Option<&'static str>) {
+    let hint = match e {
+        StegError::UnsupportedFormat { .. } => Some("list-algorithms shows what each algorithm takes"),
+        StegError::Malformed { .. } if parse::mode() == parse::Mode::Strict => Some("without --strict, damage like this is read past"),
+        StegError::CapacityExceeded { .. } => Some("capacity shows how much each algorithm can hide in it"),
+        StegError::NoPayloadFound => Some("detect tries every algorithm that applies to the file"),
+        StegError::ChecksumMismatch => Some("check --password and --hmac-key"),
+        _ => None,
+    };
+    (e.code(), hint)
+}
+
+impl From<StegError> for CliError {
+    fn from(e: StegError) -> Self {
+        match exit_status(&e) {
+            (code, Some(hint)) => CliError::new(format!("{} ({})", e, hint), code),
+            (code, None) => CliError::new(e.to_string(), code),
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    init_logging(&cli);
+    if cli.strict {
+        parse::set_mode(parse::Mode::Strict);
+    }
+    // batches and scans stop after the file in progress, anything else rolls back what it was writing
+    let _ = ctrlc::set_handler(|| match steg_algorithms::cancel::interrupt() {
+        Interrupt::Stopping => eprintln!("\ninterrupted: finishing the file in progress, Ctrl-C again to stop right away"),
+        Interrupt::RolledBack(removed) => {
+            eprintln!();
+            for p in removed {
+                eprintln!("interrupted: removed the unfinished {}", p.display());
+            }
+            std::process::exit(130);
+        }
+    });
+    if let Err(e) = with_progress(&cli, || run(&cli)) {
+        if !e.message.is_empty() {
+            eprintln!("{}", e.message);
+        }
+        std::process::exit(e.code);
+    }
+}
+
+/// Log to stderr at the level -v and --quiet ask for: warnings by default, debug with -v, trace with -vv
+/// and only errors with --quiet. RUST_STEGO_LOG, in env_logger's filter syntax, overrides that. The crates
+/// underneath only get to say something when it's a warning or worse.
+fn init_logging(cli: &Cli) {
+    use std::io::Write;
+    use log::LevelFilter;
+
+    let level = match (cli.quiet, cli.verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Warn,
+        (false, 1) => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    env_logger::Builder::new()
+        .filter_level(level.min(LevelFilter::Warn))
+        .filter_module(module_path!(), level)
+        .parse_env("RUST_STEGO_LOG")
+        .format(|buf, record| {
+            let label = match record.level() {
+                log::Level::Warn => "warning",
+                level => &level.as_str().to_lowercase(),
+            };
+            writeln!(buf, "{}: {}", label, record.args())
+        })
+        .init();
+}
+
+/// A line on stderr about what the command did, left out with --quiet.
+fn status(line: impl std::fmt::Display) {
+    if log::log_enabled!(log::Level::Warn) {
+        eprintln!("{}", line);
+    }
+}
+
+/// Run `work` with a progress bar on stderr, unless that would get in the way.
+fn with_progress<T>(cli: &Cli, work: impl FnOnce() -> T) -> T {
+    use std::io::IsTerminal;
+
+    if cli.quiet || cli.json || !std::io::stderr().is_terminal() {
+        return work();
+    }
+    let bar = std::rc::Rc::new(std::cell::RefCell::new(progress_bar::Bar::new()));
+    let sink = std::rc::Rc::clone(&bar);
+    let result = steg_algorithms::progress::report_to(move |stage, done, total| sink.borrow_mut().update(stage, done, total), work);
+    bar.borrow().clear();
+    result
+}
+
+fn run(cli: &Cli) -> Result<(), CliError> {
+    match &cli.cmd {
+        Command::Hide { in_path: Some(in_path), out_path, span: true, .. } => hide_span(cli, in_path, out_path),
+        Command::Hide { out_path, .. } if fetch::is_url(&out_path.to_string_lossy()) => {
+            Err(CliError::new(format!("hide writes -o to a file, {} is a URL (those only work for -i)", out_path.display()), 2))
+        }
+        Command::Hide { filetype, in_path: Some(in_path), out_path, .. } if in_path == Path::new("-") => {
+            let (_workspace, piped) = piped_carrier(cli, filetype)?;
+            Ok(hide(cli, &piped, out_path)?)
+        }
+        Command::Hide { filetype, in_path: Some(url), out_path, .. } if fetch::is_url(&url.to_string_lossy()) => {
+            let (_workspace, fetched) = fetched_carrier(cli, filetype, &url.to_string_lossy())?;
+            Ok(hide(cli, &fetched, out_path)?)
+        }
+        Command::Hide { in_path: Some(in_path), out_path, .. } if in_path.is_dir() => hide_batch(cli, in_path, &batch::files(in_path)?, out_path),
+        Command::Hide { filetype, in_path: Some(pattern), out_path, .. } if batch::is_pattern(pattern) => {
+            match glob_inputs(filetype, pattern)?.as_slice() {
+                [one] if out_path.is_dir() => Ok(hide(cli, one, &out_path.join(one.file_name().unwrap_or_default()))?),
+                [one] => Ok(hide(cli, one, out_path)?),
+                files => hide_batch(cli, pattern, files, out_path),
+            }
+        }
+        Command::Hide { in_path: Some(in_path), out_path, .. } => Ok(hide(cli, in_path, out_path)?),
+        Command::Hide { in_path: None, out_path, .. } => Ok(hide_auto_cover(cli, out_path)?),
+
+        Command::Find { in_path, out_path, span: true, .. } => find_span(cli, in_path, out_path.as_deref()),
+        Command::Find { filetype, in_path, out_path, .. } if in_path == Path::new("-") || fetch::is_url(&in_path.to_string_lossy()) => {
+            let mut warnings = Vec::new();
+            let input = in_path.to_string_lossy();
+            let scratch = if input == "-" { piped_carrier(cli, filetype) } else { fetched_carrier(cli, filetype, &input) };
+            let result = scratch.and_then(|(_workspace, carrier)| find(cli, &carrier, out_path.as_deref(), &mut warnings));
+            if cli.json {
+                return print_result(result, warnings);
+            }
+            result.map(|_| ()).map_err(CliError::from)
+        }
+        Command::Find { in_path, out_path, .. } if in_path.is_dir() => find_batch(cli, in_path, &batch::files(in_path)?, out_path.as_deref()),
+        Command::Find { filetype, in_path: pattern, out_path, .. } if batch::is_pattern(pattern) => {
+            let files = glob_inputs(filetype, pattern)?;
+            if files.len() > 1 {
+                return find_batch(cli, pattern, &files, out_path.as_deref());
+            }
+            let mut warnings = Vec::new();
+            let result = find(cli, &files[0], out_path.as_deref(), &mut warnings);
+            if cli.json {
+                return print_result(result, warnings);
+            }
+            result.map(|_| ()).map_err(CliError::from)
+        }
+        Command::Find { in_path, out_path, .. } => {
+            let mut warnings = Vec::new();
+            let result = find(cli, in_path, out_path.as_deref(), &mut warnings);
+            if cli.json {
+                return print_result(result, warnings);
+            }
+            result.map(|_| ()).map_err(CliError::from)
+        }
+
+        Command::Verify { in_path, .. } => {
+            let mut warnings = Vec::new();
+            let result = verify(cli, &mut warnings);
+            let matches = result.as_ref().is_ok_and(|r| r.matches);
+            if cli.json {
+                print_response(&Response::new(result, warnings), 2)?;
+            } else {
+                let r = result.map_err(|e| CliError::new(e.to_string(), 2))?;
+                let expected = r.expected_size.map_or_else(String::new, |n| format!("{} bytes, ", n));
+                if r.matches {
+                    println!("match: {} holds the expected payload ({} bytes, sha256 {})", in_path.display(), r.found_size, r.found_sha256);
+                } else {
+                    println!(
+                        "MISMATCH: {} holds {} bytes with sha256 {}, expected {}sha256 {}",
+                        in_path.display(), r.found_size, r.found_sha256, expected, r.expected_sha256
+                    );
+                }
+            }
+            if !matches {
+                return Err(CliError::silent(1));
+            }
+            Ok(())
+        }
+
+        Command::Convert { .. } => Ok(convert(cli)?),
+
+        Command::Canary { filetype, in_path, out_path, token_url, token_domain, recipient, registry, key } => {
+            use steg_algorithms::canary::{self, Beacon};
+
+            let ft = detect_filetype(filetype, in_path)?;
+            // clap's ArgGroup guarantees exactly one of these is present
+            let kind = match (token_url, token_domain) {
+                (Some(url), _) => Beacon::Url(url),
+                (_, Some(domain)) => Beacon::Dns(domain),
+                _ => unreachable!(),
+            };
+            let token = canary::new_token(&mut ChaCha20Rng::from_entropy());
+            let beacon = kind.render(&token);
+            let framed = Payload::from_text(&beacon).encode(&FrameOptions::default())?;
+            let alg = embed_beacon(&ft, in_path, out_path, &framed, key.as_deref()).map_err(|e| e.context("canary failed"))?;
+            let entry = canary::entry(token, beacon, recipient.clone(), in_path, out_path, file_hash(out_path)?);
+            if let Err(e) = canary::record(registry, &entry) {
+                let _ = std::fs::remove_file(out_path);
+                return Err(format!("{}, removed the unregistered canary", e).into());
+            }
+            println!("{} ({}) -> {}", entry.token, alg, entry.beacon);
+            Ok(())
+        }
+
+        Command::Identify { triggered, registry } => {
+            let e = steg_algorithms::canary::identify(registry, triggered)?;
+            println!("token:     {}", e.token);
+            println!("beacon:    {}", e.beacon);
+            println!("recipient: {}", e.recipient.as_deref().unwrap_or("(not recorded)"));
+            println!("asset:     {} -> {} (sha256 {})", e.asset, e.output, e.output_sha256);
+            println!("created:   {} (unix time)", e.unix_time);
+            Ok(())
+        }
+
+        Command::Capacity { filetype, in_path: pattern, .. } if batch::is_pattern(pattern) => {
+            let files = glob_inputs(filetype, pattern)?;
+            capacity_batch(cli, &files)
+        }
+        Command::Capacity { in_path, .. } => {
+            let result = capacity(cli, in_path);
+            if cli.json {
+                return print_result(result, Vec::new());
+            }
+            let r = result?;
+            status(format_args!(
+                "{}: {} {} holds {} bytes of payload ({} bytes of carrier space, limited by {})",
+                in_path.display(), r.filetype, r.algorithm, r.payload_bytes, r.carrier_bytes, r.limited_by
+            ));
+            println!("{}", r.payload_bytes);
+            Ok(())
+        }
+
+        Command::Info { filetype, in_path } => {
+            let mut warnings = Vec::new();
+            let result = info(filetype, in_path, &mut warnings);
+            if cli.json {
+                return print_result(result, warnings);
+            }
+            print_info(in_path, &result?);
+            Ok(())
+        }
+
+        Command::Detect { in_path, recursive, max_size } if in_path.is_dir() => detect_tree(cli, in_path, *recursive, *max_size),
+        Command::Detect { in_path, recursive: true, .. } => {
+            Err(CliError::new(format!("--recursive walks a directory, {} isn't one", in_path.display()), 2))
+        }
+        Command::Detect { in_path: pattern, .. } if batch::is_pattern(pattern) => {
+            let files = glob_inputs(&None, pattern)?;
+            detect_batch(cli, &files)
+        }
+        // exit status 2 when the file couldn't be probed, 1 when it could but nothing turned up
+        Command::Detect { in_path, .. } => {
+            let result = steg_algorithms::detect::detect(in_path);
+            if cli.json {
+                let hits = result.as_ref().map_or(0, |r| r.hits());
+                print_response(&Response::new(result, Vec::new()), 2)?;
+                return if hits == 0 { Err(CliError::silent(1)) } else { Ok(()) };
+            }
+            let report = result.map_err(|e| CliError::new(e.to_string(), 2))?;
+            print_detection(in_path, &report);
+            if report.hits() == 0 {
+                return Err(CliError::silent(1));
+            }
+            Ok(())
+        }
+
+        Command::Wipe { in_path, out_path, algorithm, dry_run } => {
+            let mut rng = ChaCha20Rng::from_entropy();
+            let removals = steg_algorithms::wipe::wipe(in_path, out_path.as_deref(), algorithm.as_deref(), &mut rng)
+                .map_err(|e| e.context("wipe failed"))?;
+            for r in &removals {
+                println!("{} [{}] {}", if *dry_run { "would remove" } else { "removed" }, r.scope, r.detail);
+            }
+            if removals.is_empty() {
+                println!("nothing to remove");
+            }
+            Ok(())
+        }
+
+        Command::ExportPlane { filetype, in_path, out_path, bits, channels } => {
+            use steg_algorithms::plane::{self, Planes};
+
+            let ft = detect_filetype(filetype, in_path)?;
+            let packed = plane::export(&ft, in_path, &Planes::new(bits, channels)?)?;
+            std::fs::write(out_path, &packed).map_err(|e| StegError::io(out_path.display(), e))?;
+            println!("wrote {} bytes of bit planes to {}", packed.len(), out_path.display());
+            Ok(())
+        }
+
+        Command::ImportPlane { filetype, in_path, plane: plane_path, out_path, bits, channels } => {
+            use steg_algorithms::plane::{self, Planes};
+
+            let ft = detect_filetype(filetype, in_path)?;
+            let packed = std::fs::read(plane_path).map_err(|e| StegError::io(plane_path.display(), e))?;
+            plane::import(&ft, in_path, &packed, out_path, &Planes::new(bits, channels)?)?;
+            println!("wrote {}", out_path.display());
+            Ok(())
+        }
+
+        Command::AuditVerify { log } => {
+            let n = steg_algorithms::audit::verify(log).map_err(|e| format!("{}: {}", log.display(), e))?;
+            println!("{}: {} entries, hash chain intact", log.display(), n);
+            Ok(())
+        }
+
+        Command::Simulate { in_path, ops, stride, key } => {
+            use steg_algorithms::picture::general::simulate;
+
+            let (carried, steps) = simulate::run(in_path, ops, key.as_deref(), stride.map(|s| s as usize))?;
+            let found: Vec<String> = carried.iter().map(|c| format!("{} ({} bytes)", c.label(), c.frame.len())).collect();
+            println!("payloads in {}: {}", in_path.display(), found.join(", "));
+            for (i, step) in steps.iter().enumerate() {
+                println!("{}. {}", i + 1, step.op);
+                for (c, result) in carried.iter().zip(&step.results) {
+                    println!("   {}: {}", c.label(), result);
+                }
+            }
+            let last = steps.last().map(|s| s.results.iter().filter(|r| matches!(r, simulate::Survival::Intact { .. })).count());
+            if let Some(kept) = last && kept < carried.len() {
+                return Err(format!("{} of {} payloads didn't survive the whole chain", carried.len() - kept, carried.len()).into());
+            }
+            Ok(())
+        }
+
+        Command::Scan { paths, deep, max_size, filter } => {
+            use std::sync::atomic::{AtomicUsize, Ordering};
+
+            let opts = steg_algorithms::scan::ScanOptions { deep: *deep, max_size: *max_size };
+            let shown = AtomicUsize::new(0);
+            let summary = steg_algorithms::scan::scan(paths, opts, |f| {
+                if filter.as_ref().is_none_or(|w| w.matches(f)) {
+                    shown.fetch_add(1, Ordering::Relaxed);
+                    println!("{:.2}  {}  [{}] {}", f.score, f.path.display(), f.detector, f.detail);
+                }
+            });
+            status(format_args!(
+                "{} files, {} in a known format, {} probed: {} findings{}{}",
+                summary.files, summary.candidates, summary.probed, summary.findings,
+                if filter.is_some() { format!(" ({} matching --where)", shown.into_inner()) } else { String::new() },
+                if summary.unreadable > 0 { format!(", {} unreadable", summary.unreadable) } else { String::new() }
+            ));
+            if summary.interrupted > 0 {
+                eprintln!("interrupted: {} files weren't looked at, the findings above only cover the rest", summary.interrupted);
+                return Err(CliError::silent(130));
+            }
+            Ok(())
+        }
+
+        Command::Interactive => interactive::run(cli),
+        Command::Bench { .. } => bench::run(cli),
+
+        Command::Repl { filetype, in_path, app_id } => Ok(repl::run(filetype, in_path.as_deref(), app_id)?),
+
+        Command::ListAlgorithms => {
+            list_algorithms(cli.json);
+            Ok(())
+        }
+
+        Command::Serve { listen, max_upload, max_stream, workers } => {
+            Ok(serve::serve(&serve::ServeOptions { listen: listen.clone(), max_upload: *max_upload, max_stream: *max_stream, workers: *workers as usize })?)
+        }
+    }
+}
+
+// filetypes hide and find have algorithms for
+const CARRIER_FILETYPES: [&str; 4] = ["picture", "audio", "medical", "astro"];
+
+/// Why a file in a batch isn't attempted, if it isn't.
+fn batch_skip(filetype: &Option<String>, path: &Path) -> Option<String> {
+    match detect_filetype(filetype, path) {
+        Ok(ft) if CARRIER_FILETYPES.contains(&ft.as_str()) => None,
+        Ok(ft) => Some(format!("unsupported extension (no {} algorithms)", ft)),
+        Err(_) => Some("unsupported extension".to_string()),
+    }
+}
+
+fn print_detection(path: &Path, report: &steg_algorithms::detect::Report) {
+    use steg_algorithms::detect::Outcome;
+
+    println!("{}: {}", path.display(), report.carrier);
+    for probe in &report.probes {
+        match &probe.outcome {
+            Outcome::Found { embedded, detail } => println!("  {:<8} found    {} bytes embedded: {}", probe.algorithm, embedded, detail),
+            Outcome::Nothing { reason } => println!("  {:<8} nothing  ({})", probe.algorithm, reason),
+        }
+    }
+    println!("{} of {} probes found a payload", report.hits(), report.probes.len());
+}
+
+/// detect over the files a pattern matched: each one's probes, then the batch summary. Exits 1 when
+/// none of them turned anything up.
+fn detect_batch(cli: &Cli, files: &[PathBuf]) -> Result<(), CliError> {
+    let mut summary = batch::Summary::default();
+    let mut reports = Vec::new();
+    let _cooperating = cancel::cooperate();
+    for (i, path) in files.iter().enumerate() {
+        if cancel::requested() {
+            summary.interrupted(&files[i..]);
+            break;
+        }
+        let result = steg_algorithms::detect::detect(path);
+        let outcome = match &result {
+            Ok(r) if r.hits() > 0 => batch::Outcome::Done,
+            Ok(_) => batch::Outcome::Skipped("nothing found".to_string()),
+            Err(e) => batch::Outcome::Failed(e.to_string()),
+        };
+        if !cli.json && let Ok(r) = &result {
+            print_detection(path, r);
+        }
+        reports.push(FileReport { path: path.clone(), response: Response::new(result, Vec::new()) });
+        summary.record(path, outcome);
+    }
+    batch_result(cli, summary, reports, Vec::new(), "flagged", "Nothing found in any of the files")
+}
+
+/// detect over a directory, or with `recursive` the tree under it: every file with a supported extension
+/// is probed and its hits go out as they turn up, a table row each, or a JSON line each with --json.
+/// Files that can't be probed are reported and passed over. Finding nothing isn't an error, only a
+/// directory that can't be read at all is.
+fn detect_tree(cli: &Cli, dir: &Path, recursive: bool, max_size: Option<u64>) -> Result<(), CliError> {
+    use std::sync::atomic::AtomicUsize;
+    use steg_algorithms::detect::Outcome;
+
+    std::fs::read_dir(dir).map_err(|e| CliError::new(format!("Failed to read {}: {}", dir.display(), e), 2))?;
+    let unreadable_dirs = AtomicUsize::new(0);
+    let mut files = steg_algorithms::scan::walk(&[dir.to_path_buf()], recursive, &unreadable_dirs);
+    files.sort();
+    let (mut probed, mut flagged, mut hits, mut failed, mut interrupted) = (0, 0, 0, unreadable_dirs.into_inner(), 0);
+    if !cli.json {
+        println!("{:<9} {:>10}  path", "algorithm", "bytes");
+    }
+    let _cooperating = cancel::cooperate();
+    for (i, path) in files.iter().enumerate() {
+        if cancel::requested() {
+            interrupted = files.len() - i;
+            break;
+        }
+        let too_big = max_size.filter(|&max| std::fs::metadata(path).is_ok_and(|m| m.len() > max));
+        let skip = if batch::is_hidden(path) {
+            Some("hidden file".to_string())
+        } else if let Some(max) = too_big {
+            Some(format!("bigger than {} bytes", max))
+        } else {
+            batch_skip(&None, path)
+        };
+        if let Some(why) = skip {
+            log::debug!("skipping {}: {}", path.display(), why);
+            continue;
+        }
+        probed += 1;
+        let report = match steg_algorithms::detect::detect(path) {
+            Ok(report) => report,
+            Err(e) => {
+                failed += 1;
+                if cli.json {
+                    println!("{}", serde_json::json!({ "path": path, "error": e.to_string() }));
+                } else {
+                    log::warn!("{}", e);
+                }
+                continue;
+            }
+        };
+        flagged += usize::from(report.hits() > 0);
+        for probe in &report.probes {
+            let Outcome::Found { embedded, detail } = &probe.outcome else { continue };
+            hits += 1;
+            if cli.json {
+                println!("{}", serde_json::json!({ "path": path, "algorithm": probe.algorithm, "embedded": embedded, "detail": detail }));
+            } else {
+                println!("{:<9} {:>10}  {}", probe.algorithm, embedded, path.display());
+            }
+        }
+    }
+    status(format_args!(
+        "{} files, {} probed: {} hits in {} files{}",
+        files.len(), probed, hits, flagged,
+        if failed > 0 { format!(", {} couldn't be read", failed) } else { String::new() }
+    ));
+    if interrupted > 0 {
+        eprintln!("interrupted: {} files weren't looked at, the hits above only cover the rest", interrupted);
+        return Err(CliError::silent(130));
+    }
+    Ok(())
+}
+
+/// capacity over the files a pattern matched, one line each and then the batch summary.
+fn capacity_batch(cli: &Cli, files: &[PathBuf]) -> Result<(), CliError> {
+    let mut summary = batch::Summary::default();
+    let mut reports = Vec::new();
+    let _cooperating = cancel::cooperate();
+    for (i, path) in files.iter().enumerate() {
+        if cancel::requested() {
+            summary.interrupted(&files[i..]);
+            break;
+        }
+        let result = capacity(cli, path);
+        let outcome = match &result {
+            Ok(r) => {
+                if !cli.json {
+                    println!("{}: {} bytes ({} {}, limited by {})", path.display(), r.payload_bytes, r.filetype, r.algorithm, r.limited_by);
+                }
+                batch::Outcome::Done
+            }
+            Err(e) => batch::Outcome::Failed(e.to_string()),
+        };
+        reports.push(FileReport { path: path.clone(), response: Response::new(result, Vec::new()) });
+        summary.record(path, outcome);
+    }
+    batch_result(cli, summary, reports, Vec::new(), "sized", "None of the files could be sized")
+}
+
+/// The end of a batch: the JSON report (with `warnings`) or the printed summary, and the exit status
+/// (130 when interrupted, 1 when not one file came out `done`).
+fn batch_result<T: serde::Serialize>(
+    cli: &Cli,
+    summary: batch::Summary,
+    reports: Vec<FileReport<T>>,
+    warnings: Vec<String>,
+    done: &str,
+    none_done: &str,
+) -> Result<(), CliError> {
+    if cli.json {
+        let mut response = Response::new(Ok(BatchReport { files: reports }), warnings);
+        if summary.was_interrupted() {
+            response.ok = false;
+            response.error = Some("Interrupted before every file was tried".to_string());
+        } else if !summary.any_done() {
+            response.ok = false;
+            response.error = Some(none_done.to_string());
+        }
+        return print_response(&response, if summary.was_interrupted() { 130 } else { 1 });
+    }
+    summary.print(done);
+    if summary.was_interrupted() {
+        return Err(CliError::silent(130));
+    }
+    if !summary.any_done() {
+        return Err(CliError::silent(1));
+    }
+    Ok(())
+}
+
+/// The files the glob `pattern` given as -i matches, without the hidden ones and those no algorithm
+/// takes (-v names them). Failing when that leaves nothing.
+fn glob_inputs(filetype: &Option<String>, pattern: &Path) -> Result<Vec<PathBuf>, StegError> {
+    let mut files = Vec::new();
+    for path in batch::expand(pattern)? {
+        let skip = if batch::is_hidden(&path) { Some("hidden file".to_string()) } else { batch_skip(filetype, &path) };
+        match skip {
+            Some(why) => log::debug!("skipping {}: {}", path.display(), why),
+            None => files.push(path),
+        }
+    }
+    if files.is_empty() {
+        return Err(format!("'{}' matches no file any algorithm takes (-v says why)", pattern.display()).into());
+    }
+    Ok(files)
+}
+
+/// hide with `-i` a directory or a pattern (`source`): every supported file in it, or matched by it,
+/// goes to `out_dir` under the same name.
+fn hide_batch(cli: &Cli, source: &Path, files: &[PathBuf], out_dir: &Path) -> Result<(), CliError> {
+    let Command::Hide { filetype, key_share, manifest, force, .. } = &cli.cmd else {
+        unreachable!("hide_batch is only called for the hide command");
+    };
+    if !key_share.is_empty() {
+        return Err("--key-share splits the key of a single carrier, not a directory's worth".into());
+    }
+    batch::prepare_output(source, files, out_dir)?;
+    // one manifest per carrier, so --manifest is where they go
+    if let Some(dir) = manifest {
+        std::fs::create_dir_all(dir).map_err(|e| StegError::io(format!("Failed to create the manifest directory {}", dir.display()), e))?;
+    }
+    let mut summary = batch::Summary::default();
+    let _cooperating = cancel::cooperate();
+    for (i, path) in files.iter().enumerate() {
+        if cancel::requested() {
+            summary.interrupted(&files[i..]);
+            break;
+        }
+        let out = path.file_name().map(|name| out_dir.join(name));
+        let outcome = match (batch_skip(filetype, path), out) {
+            (Some(why), _) => batch::Outcome::Skipped(why),
+            // done by an earlier run, which makes running the batch again resume it
+            (None, Some(out)) if out.exists() && !*force => batch::Outcome::Skipped(format!("{} exists, pass --force to redo it", out.display())),
+            (None, Some(out)) => match hide(cli, path, &out) {
+                Ok(()) => batch::Outcome::Done,
+                Err(StegError::CapacityExceeded { needed, available }) => {
+                    batch::Outcome::Skipped(format!("too small, the payload needs {} bytes but it holds {}", needed, available))
+                }
+                Err(e) => batch::Outcome::Failed(e.to_string()),
+            },
+            (None, None) => continue,
+        };
+        summary.record(path, outcome);
+    }
+    summary.print("hidden");
+    if summary.was_interrupted() {
+        return Err(CliError::silent(130));
+    }
+    if summary.any_failed() {
+        return Err(CliError::silent(1));
+    }
+    Ok(())
+}
+
+/// find with `-i` a directory or a pattern (`source`): try every supported file in it, or matched by it,
+/// printing each one's result under its name. With `-o` each payload is written to
+/// `<carrier file name>.payload` in that directory.
+fn find_batch(cli: &Cli, source: &Path, files: &[PathBuf], out_dir: Option<&Path>) -> Result<(), CliError> {
+    let Command::Find { filetype, to_clipboard, .. } = &cli.cmd else {
+        unreachable!("find_batch is only called for the find command");
+    };
+    if *to_clipboard {
+        return Err("--to-clipboard takes a single payload, not a directory's worth".into());
+    }
+    if out_dir.is_some_and(|d| d == Path::new("-")) {
+        return Err("-o - takes a single payload, not a directory's worth (give -o a directory)".into());
+    }
+    if let Some(out) = out_dir {
+        batch::prepare_output(source, files, out)?;
+    }
+    let mut summary = batch::Summary::default();
+    let (mut reports, mut skipped) = (Vec::new(), Vec::new());
+    let _cooperating = cancel::cooperate();
+    for (i, path) in files.iter().enumerate() {
+        if cancel::requested() {
+            summary.interrupted(&files[i..]);
+            break;
+        }
+        let outcome = match (batch_skip(filetype, path), path.file_name()) {
+            (Some(why), _) => {
+                skipped.push(format!("skipped {}: {}", path.display(), why));
+                batch::Outcome::Skipped(why)
+            }
+            (None, Some(name)) => {
+                if cli.json {
+                    eprintln!("== {}", path.display());
+                } else {
+                    println!("== {}", path.display());
+                }
+                let out = out_dir.map(|d| d.join(format!("{}.payload", name.to_string_lossy())));
+                let mut warnings = Vec::new();
+                let result = find(cli, path, out.as_deref(), &mut warnings);
+                let outcome = match &result {
+                    Ok(_) => batch::Outcome::Done,
+                    Err(e) => batch::Outcome::Failed(e.to_string()),
+                };
+                reports.push(FileReport { path: path.clone(), response: Response::new(result, warnings) });
+                outcome
+            }
+            (None, None) => continue,
+        };
+        summary.record(path, outcome);
+    }
+    batch_result(cli, summary, reports, skipped, "found", "No payload found in any of the files")
+}
+
+/// The span record hidden in `path`, read with the given settings.
+fn read_span_record(filetype: &Option<String>, algorithm: Option<&AlgorithmSpec>, path: &Path, lsb: &LsbOptions, password: Option<&str>, app_id: &str) -> Result<chunking::Record, StegError> {
+    let ft = detect_filetype(filetype, path)?;
+    let alg = pick_algorithm(algorithm, &ft, path)?;
+    let opts = Options { password: password.map(String::from), app_id: parse_app_id(app_id)?, ..lookup(algorithm, &ft, alg, lsb.clone())? };
+    let (bytes, _) = extract(&ft, alg, path, &opts, None)?;
+    let bytes = payload::unprotect(&bytes)?.map_or(bytes, |(inner, _)| inner);
+    let found = Payload::decode(&bytes, &DecodeOptions { password: password.map(String::from), hmac_key: None })?;
+    chunking::Record::parse(&found.data)?.ok_or_else(|| "not part of a span".into())
+}
+
+/// hide --span: spread the payload over the files in `in_dir`, one chunk (or the manifest) per carrier,
+/// into `out_dir`. Carriers there that already hold a chunk the payload still needs are left alone.
+fn hide_span(cli: &Cli, in_dir: &Path, out_dir: &Path) -> Result<(), CliError> {
+    use std::collections::HashSet;
+    use chunking::{Manifest, Record};
+
+    let Command::Hide { filetype, algorithm, password, app_id, stride, key, offset, region, range, chunk_size, force, .. } = &cli.cmd else {
+        unreachable!("hide_span is only called for the hide command");
+    };
+    if !in_dir.is_dir() {
+        return Err("--span spreads the payload over a directory of carriers, -i has to be one".into());
+    }
+    batch::prepare_output_dir(in_dir, out_dir)?;
+    let covers: Vec<PathBuf> = batch::files(in_dir)?.into_iter().filter(|p| batch_skip(filetype, p).is_none()).collect();
+    let out_of = |cover: &Path| out_dir.join(cover.file_name().unwrap_or_default());
+    if !*force && let Some(taken) = covers.iter().map(|c| out_of(c)).find(|o| o.exists()) {
+        return Err(format!("{} exists, pass --force to update the span in {}", taken.display(), out_dir.display()).into());
+    }
+
+    // what an earlier run left in out_dir
+    let lsb = LsbOptions { offset: *offset, region: *region, range: *range, stride: key.is_none().then_some(*stride as usize), key: key.clone(), ..LsbOptions::default() };
+    let mut held: Vec<(PathBuf, chunking::ChunkId)> = Vec::new();
+    let (mut old_manifest, mut free): (Option<(PathBuf, u32)>, Vec<PathBuf>) = (None, Vec::new());
+    for cover in &covers {
+        let out = out_of(cover);
+        let record = out.exists().then(|| read_span_record(filetype, algorithm.as_ref(), &out, &lsb, password.as_deref(), app_id));
+        match record {
+            Some(Ok(Record::Piece(data))) => held.push((cover.clone(), chunking::chunk_id(&data))),
+            Some(Ok(Record::Manifest(m))) if old_manifest.as_ref().is_none_or(|(_, g)| m.generation > *g) => {
+                free.extend(old_manifest.replace((cover.clone(), m.generation)).map(|(p, _)| p));
+            }
+            _ => free.push(cover.clone()),
+        }
+    }
+
+    let payload = load_payload(cli)?;
+    let generation = old_manifest.as_ref().map_or(0, |(_, g)| g.wrapping_add(1));
+    let (manifest, pieces) = Manifest::new(&payload.data, payload.name.clone(), *chunk_size as usize, generation);
+    let needed: HashSet<chunking::ChunkId> = manifest.chunks.iter().map(|(id, _)| *id).collect();
+    let mut in_place = HashSet::new();
+    for (cover, id) in held {
+        // a chunk the payload no longer needs, or a second copy of one, frees its carrier
+        if !needed.contains(&id) || !in_place.insert(id) {
+            free.push(cover);
+        }
+    }
+    free.sort();
+    // the manifest goes last, so an interrupted update still has every chunk written so far to reuse,
+    // and preferably where the old one was
+    let mut records: Vec<(String, Record)> = pieces
+        .iter()
+        .filter(|p| !in_place.contains(&chunking::chunk_id(p)))
+        .map(|p| (format!("chunk {}", chunking::hex(&chunking::chunk_id(p))), Record::Piece(p.to_vec())))
+        .collect();
+    records.push(("the manifest".to_string(), Record::Manifest(manifest.clone())));
+
+    let _cooperating = cancel::cooperate();
+    let mut written = Vec::new();
+    for (what, record) in records {
+        if cancel::requested() {
+            eprintln!("interrupted: the span in {} is incomplete, run the same command with --force to finish it", out_dir.display());
+            return Err(CliError::silent(130));
+        }
+        if matches!(record, Record::Manifest(_)) && let Some((cover, _)) = old_manifest.take() {
+            free.insert(0, cover);
+        }
+        let carried = Payload { name: None, data: record.encode() };
+        let mut placed = None;
+        for (i, cover) in free.iter().enumerate() {
+            match hide_with(cli, cover, &out_of(cover), &carried) {
+                Ok(()) => {
+                    placed = Some(i);
+                    break;
+                }
+                Err(StegError::CapacityExceeded { .. }) => continue,
+                Err(e) => return Err(format!("{}: {}", cover.display(), e).into()),
+            }
+        }
+        let Some(i) = placed else {
+            return Err(format!("No carrier left in {} holds {} ({} bytes), try a smaller --chunk-size", in_dir.display(), what, carried.data.len()).into());
+        };
+        let cover = free.remove(i);
+        log::debug!("{} -> {}", what, out_of(&cover).display());
+        written.push(cover);
+    }
+    println!(
+        "{} chunks ({} distinct): {} already in place, {} written, manifest in {}",
+        manifest.chunks.len(), pieces.len(), in_place.len(), written.len() - 1,
+        written.last().map(|c| out_of(c).display().to_string()).unwrap_or_default()
+    );
+    Ok(())
+}
+
+/// find --span: collect the chunks and the newest manifest from the files in `in_dir` and put the payload
+/// back together.
+fn find_span(cli: &Cli, in_dir: &Path, out_path: Option<&Path>) -> Result<(), CliError> {
+    use std::collections::HashMap;
+    use chunking::Record;
+
+    let Command::Find { filetype, algorithm, password, app_id, stride, key, offset, region, range, force, hex, .. } = &cli.cmd else {
+        unreachable!("find_span is only called for the find command");
+    };
+    if !in_dir.is_dir() {
+        return Err("--span reads a payload spread over a directory of carriers, -i has to be one".into());
+    }
+    if cli.json {
+        return Err("--span has no --json output".into());
+    }
+    let lsb = LsbOptions { offset: *offset, region: *region, range: *range, stride: stride.map(|s| s as usize), key: key.clone(), ..LsbOptions::default() };
+    let (mut manifest, mut pieces, mut other) = (None::<chunking::Manifest>, HashMap::new(), 0);
+    for path in batch::files(in_dir)?.into_iter().filter(|p| batch_skip(filetype, p).is_none()) {
+        match read_span_record(filetype, algorithm.as_ref(), &path, &lsb, password.as_deref(), app_id) {
+            Ok(Record::Manifest(m)) => {
+                if manifest.as_ref().is_none_or(|have| m.generation > have.generation) {
+                    manifest = Some(m);
+                }
+            }
+            Ok(Record::Piece(data)) => {
+                pieces.insert(chunking::chunk_id(&data), data);
+            }
+            Err(e) => {
+                log::debug!("{}: {}", path.display(), e);
+                other += 1;
+            }
+        }
+    }
+    let manifest = manifest.ok_or_else(|| format!("No span manifest in {} ({} files hold no span record)", in_dir.display(), other))?;
+    let data = manifest.assemble(&pieces).map_err(|e| e.context("find failed"))?;
+    status(format_args!("span: {} bytes from {} chunks", data.len(), manifest.chunks.len()));
+
+    let to_stdout = out_path.is_some_and(|p| p == Path::new("-"));
+    match out_path {
+        _ if to_stdout => write_stdout(&data, *force)?,
+        Some(out) => {
+            let target = match manifest.name.as_deref().and_then(|n| Path::new(n).file_name()) {
+                Some(name) if out.is_dir() => out.join(name),
+                None if out.is_dir() => return Err("Payload has no stored filename; pass a file path to -o instead of a directory".into()),
+                _ => out.to_path_buf(),
+            };
+            std::fs::write(&target, &data).map_err(|e| StegError::io("Failed to write output file", e))?;
+            log::debug!("wrote the decoded output to {}", target.display());
+        }
+        None if *hex || (manifest.name.is_none() && std::str::from_utf8(&data).is_err()) => print!("{}", hexdump::dump(&data)),
+        None if manifest.name.is_some() => {
+            println!("Recovered file '{}' ({} bytes), use -o to save it", manifest.name.as_deref().unwrap_or_default(), data.len());
+        }
+        None => println!("Result: {}", String::from_utf8_lossy(&data)),
+    }
+    if *hex && cli.verbose > 0 && out_path.is_some() {
+        eprint!("{}", hexdump::dump(&data));
+    }
+    Ok(())
+}
+
+/// Whether hide sets the LSBs right in a netpbm/farbfeld/QOI file: raw format in and out, so there's no
+/// decode/encode trip. Any QOI output goes through our own encoder, which checks its round trip.
+fn raw_lsb(ft: &str, alg: &str, in_ext: &str, out_ext: &str) -> bool {
+    let (raw_in, raw_out) = (raw::Format::from_extension(in_ext), raw::Format::from_extension(out_ext));
+    ft == "picture" && alg == "lsb" && (raw_out == Some(raw::Format::Qoi) || (raw_in.is_some() && raw_in == raw_out))
+}
+
+/// Bytes of framed payload `alg` can put into the cover at `path`, `None` for the segment based
+/// carriers (marker, appext, ...), which have no capacity worth clamping to, and lineshift, whose
+/// payload isn't framed.
+fn carrier_room(ft: &str, alg: &str, path: &Path, lsb: &LsbOptions, copies: usize) -> Option<usize> {
+    if matches!(alg, "marker" | "appext" | "tag" | "cards" | "lineshift") {
+        return None;
+    }
+    carrier_capacity(ft, alg, path, lsb, copies).ok()?.0.ok()
+}
+
+/// hide --auto-cover: try the corpus covers of the output's media type from the least room up, until
+/// one holds the payload.
+fn hide_auto_cover(cli: &Cli, out_path: &Path) -> Result<(), StegError> {
+    let Command::Hide { filetype, algorithm, stride, key, offset, region, range, redundancy, force, .. } = &cli.cmd else {
+        unreachable!("hide_auto_cover is only called for the hide command");
+    };
+    check_output(out_path, *force)?;
+    let corpus = config::load(cli.config.as_deref())?
+        .cover_corpus
+        .ok_or("--auto-cover needs a cover_corpus directory in the config file")?;
+    let ft = detect_filetype(filetype, out_path)?;
+    let alg = pick_algorithm(algorithm.as_ref(), &ft, out_path)?;
+    let base = LsbOptions { offset: *offset, region: *region, range: *range, stride: key.is_none().then_some(*stride as usize), key: key.clone(), ..LsbOptions::default() };
+    let lsb = lookup(algorithm.as_ref(), &ft, alg, base)?.lsb;
+    let mut covers: Vec<(usize, PathBuf)> = batch::files(&corpus)?
+        .into_iter()
+        .filter(|p| detect_filetype(&None, p).is_ok_and(|t| t == ft))
+        .map(|p| (carrier_room(&ft, alg, &p, &lsb, *redundancy as usize).unwrap_or(usize::MAX), p))
+        .collect();
+    if covers.is_empty() {
+        return Err(format!("{} has no {} covers", corpus.display(), ft).into());
+    }
+    covers.sort();
+    // learned from the first cover that is too small, so the rest of those can be skipped unopened
+    let mut need = 0;
+    for (room, cover) in covers {
+        if room < need {
+            continue;
+        }
+        match hide(cli, &cover, out_path) {
+            Ok(()) => {
+                status(format_args!("auto-cover: used {}", cover.display()));
+                return Ok(());
+            }
+            Err(StegError::CapacityExceeded { needed, .. }) => need = needed,
+            Err(e) => log::warn!("auto-cover: skipping {}: {}", cover.display(), e),
+        }
+    }
+    Err(match need {
+        0 => format!("None of the {} covers in {} worked", ft, corpus.display()),
+        n => format!("No {} cover in {} holds the payload ({} bytes)", ft, corpus.display(), n),
+    }.into())
+}
+
+/// A fresh workspace for intermediate files, where --tmpdir and --tmp-quota say.
+fn workspace(cli: &Cli) -> Result<steg_algorithms::workspace::Workspace, StegError> {
+    use steg_algorithms::workspace::{Workspace, WorkspaceOptions};
+
+    Workspace::new(&WorkspaceOptions { root: cli.tmpdir.clone(), quota: cli.tmp_quota })
+}
+
+/// The carrier piped in for `-i -`, read once into a workspace file whose extension matches its content
+/// (which is what the decoders and the default algorithm go by). The file lasts as long as the workspace.
+fn piped_carrier(cli: &Cli, filetype: &Option<String>) -> Result<(steg_algorithms::workspace::Workspace, PathBuf), StegError> {
+    use std::io::Read;
+
+    let Some(ft) = filetype else {
+        return Err("-i - reads the carrier from stdin, where there's no file extension to tell what it is: \
+                    pass --filetype (picture, audio, video, text, medical or astro)".into());
+    };
+    let ft = detect_filetype(&Some(ft.clone()), Path::new("-"))?;
+    let mut stdin = std::io::stdin().lock();
+    // enough to recognise the content, the rest is copied straight after it
+    let mut head = Vec::new();
+    (&mut stdin).take(PIPE_HEAD).read_to_end(&mut head).map_err(|e| StegError::io("Failed to read stdin", e))?;
+    if head.is_empty() {
+        return Err("-i - found nothing on stdin".into());
+    }
+    let ext = piped_extension(&head, &ft).ok_or_else(|| {
+        format!("Couldn't tell what kind of {} came in on stdin, save it to a file with the right extension and pass that to -i", ft)
+    })?;
+    scratch_carrier(cli, ext, &head, &mut stdin)
+}
+
+/// The carrier at `url` for `-i https://...`, fetched into a workspace file like a piped one. Its extension
+/// comes from the URL, else the Content-Type, else the content.
+fn fetched_carrier(cli: &Cli, filetype: &Option<String>, url: &str) -> Result<(steg_algorithms::workspace::Workspace, PathBuf), StegError> {
+    let fetched = fetch::fetch(url, &fetch::FetchOptions { max_redirects: cli.max_redirects, max_bytes: cli.max_download })?;
+    let from_content = || piped_extension(&fetched.bytes, &detect_filetype(filetype, Path::new("-")).unwrap_or_default());
+    let ext = fetch::url_extension(url)
+        .filter(|ext| detect_filetype(&None, Path::new(&format!("x.{}", ext))).is_ok())
+        .or_else(|| fetched.content_type.as_deref().and_then(fetch::content_type_extension).map(str::to_string))
+        .or_else(|| from_content().map(str::to_string))
+        .ok_or_else(|| format!("Couldn't tell what kind of file {} is from its name, Content-Type or content, pass --filetype", url))?;
+    log::debug!("fetched {} ({} bytes, treated as .{})", url, fetched.bytes.len(), ext);
+    scratch_carrier(cli, &ext, &fetched.bytes, &mut std::io::empty())
+}
+
+/// `head` and then the rest of `rest`, in a workspace file with extension `ext`.
+fn scratch_carrier(cli: &Cli, ext: &str, head: &[u8], rest: &mut impl std::io::Read) -> Result<(steg_algorithms::workspace::Workspace, PathBuf), StegError> {
+    use std::io::Write;
+
+    let workspace = workspace(cli)?;
+    let path = workspace.file(&format!(".{}", ext));
+    let mut file = std::fs::File::create(&path).map_err(|e| StegError::io(format!("Failed to create {}", path.display()), e))?;
+    file.write_all(head)
+        .and_then(|_| std::io::copy(rest, &mut file))
+        .and_then(|_| file.flush())
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    workspace.check_quota()?;
+    Ok((workspace, path))
+}
+
+/// How much of a piped carrier `piped_extension` looks at.
+const PIPE_HEAD: u64 = 64 * 1024;
+
+/// The extension a piped carrier of filetype `ft` starting with `head` would have had.
+fn piped_extension(head: &[u8], ft: &str) -> Option<&'static str> {
+    if let Some((format, _)) = sniff_carrier(head) {
+        return match format.as_str() {
+            "WAV" => Some("wav"),
+            "DICOM" => Some("dcm"),
+            "FITS" => Some("fits"),
+            "NETPBM" => Some("pnm"),
+            "FARBFELD" => Some("ff"),
+            "QOI" => Some("qoi"),
+            _ => image::guess_format(head).ok().and_then(|f| f.extensions_str().first().copied()),
+        };
+    }
+    match ft {
+        "video" if head.get(4..8) == Some(b"ftyp") => Some("mp4"),
+        "video" if head.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) => Some("mkv"),
+        "text" => Some("txt"),
+        _ => None,
+    }
+}
+
+/// Where the manifest of `carrier` goes, or is read from: `manifest` itself, or `<carrier name>.json` in
+/// it when it is a directory.
+fn manifest_path(manifest: &Path, carrier: &Path) -> PathBuf {
+    match carrier.file_name() {
+        Some(name) if manifest.is_dir() => manifest.join(format!("{}.json", name.to_string_lossy())),
+        _ => manifest.to_path_buf(),
+    }
+}
+
+/// Refuse to replace an existing `out_path` unless hide was given --force.
+fn check_output(out_path: &Path, force: bool) -> Result<(), StegError> {
+    if !force && out_path.exists() {
+        return Err(format!("{} exists, pass --force to overwrite it", out_path.display()).into());
+    }
+    Ok(())
+}
+
+/// The payload named on the hide command line.
+fn load_payload(cli: &Cli) -> Result<Payload, StegError> {
+    let Command::Hide { message, msg_file, msg_from_clipboard, .. } = &cli.cmd else {
+        unreachable!("load_payload is only called for the hide command");
+    };
+    // clap's ArgGroup guarantees exactly one of these is present
+    Ok(if let Some(f) = msg_file {
+        Payload::from_file(f)?
+    } else if *msg_from_clipboard {
+        match clipboard::read_text() {
+            Ok(v) => Payload::from_text(&v),
+            Err(e) => return Err(e.context("Failed to read clipboard")),
+        }
+    } else {
+        Payload::from_text(message.as_deref().unwrap_or_default())
+    })
+}
+
+/// Hide into one carrier.
+fn hide(cli: &Cli, in_path: &Path, out_path: &Path) -> Result<(), StegError> {
+    hide_with(cli, in_path, out_path, &load_payload(cli)?)
+}
+
+/// Hide `payload` into one carrier, with the settings on the hide command line.
+fn hide_with(cli: &Cli, in_path: &Path, out_path: &Path, payload: &Payload) -> Result<(), StegError> {
+    let Command::Hide { filetype, algorithm, compress, password, key_share, hmac_key, cipher, pad, app_id, stride, key, offset, region, range, strength, shift, perturb, prenoise, target_quality, fec, redundancy, name, meta, strip_metadata, preserve_length, report_delta, noise_report, manifest, verify, no_verify, dry_run, on_format_change, force, .. } = &cli.cmd else {
+        unreachable!("hide is only called for the hide command");
+    };
+    check_output(out_path, *force)?;
+    let manifest = manifest.as_deref().map(|m| manifest_path(m, out_path));
+    if let Some(m) = &manifest {
+        check_output(m, *force)?;
+    }
+    // the output is written to a temporary file next to it, which only replaces it once everything
+    // below went through: a failure never leaves the cover half written, or a previous output gone
+    let staged = if *dry_run { None } else { Some(atomic::Staged::new(out_path)?) };
+    let dest = staged.as_ref().map_or(out_path, |t| t.path());
+    let discarded = match out_path.exists() {
+        true => format!("{} left as it was", out_path.display()),
+        false => format!("nothing written to {}", out_path.display()),
+    };
+    let ft = detect_filetype(filetype, in_path)?;
+    if noise_report.is_some() && ft != "picture" && ft != "audio" {
+        return Err(format!("--noise-report is for pictures and WAV audio, not {}", ft).into());
+    }
+    if parse::mode() == parse::Mode::Strict {
+        let buf = std::fs::read(in_path).map_err(|e| StegError::io(format!("Failed to read {}", in_path.display()), e))?;
+        structure_problems(&buf).map_err(|e| e.context(in_path.display()))?;
+    }
+    let mut frame_opts = FrameOptions {
+        compress: *compress,
+        password: password.clone(),
+        cipher: (*cipher).into(),
+        hmac_key: hmac_key.clone(),
+        fec_parity: *fec,
+        meta: None,
+    };
+    // the shares are written once the output is, so a failed hide leaves none behind
+    let mut split = None;
+    if !key_share.is_empty() {
+        let [a, b] = key_share.as_slice() else {
+            return Err("--key-share takes two files, give it twice".into());
+        };
+        if a == b {
+            return Err("The two key shares need different files".into());
+        }
+        if let Some(taken) = [a, b].into_iter().find(|p| p.exists()) {
+            return Err(format!("{} already exists, key shares are never overwritten", taken.display()).into());
+        }
+        let (secret, pair) = shares::split(&mut ChaCha20Rng::from_entropy());
+        frame_opts.password = Some(secret);
+        split = Some(pair);
+    }
+    let mut alg = pick_algorithm(algorithm.as_ref(), &ft, in_path)?;
+
+    // catch `-i photo.png -o photo.jpg` style container changes before they eat the payload
+    let ext_of = |p: &Path| p.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    let (in_ext, out_ext) = (ext_of(in_path), ext_of(out_path));
+    if formats::normalize_ext(&in_ext) != formats::normalize_ext(&out_ext)
+        && let Some(problem) = formats::output_problem(&ft, alg, &out_ext)
+    {
+        match (on_format_change, formats::surviving_algorithm(&ft, &out_ext)) {
+            (FormatChange::Auto, Some(other)) => {
+                log::warn!("{}; switching to '{}'", problem, other);
+                alg = other;
+            }
+            (FormatChange::Auto, None) => {
+                return Err(format!("{}, and no other {} algorithm survives a .{} output", problem, ft, out_ext).into());
+            }
+            (FormatChange::Abort, _) => {
+                return Err(format!(
+                    "Input is .{} but output is .{}: {}.\nChange -o, or pass --on-format-change auto to pick an algorithm that survives it.",
+                    in_ext, out_ext, problem
+                ).into());
+            }
+        }
+    }
+
+    if *strip_metadata && !formats::is_jpeg(&out_ext) && formats::normalize_ext(&out_ext) != "png" {
+        return Err(format!("--strip-metadata knows JPEG and PNG metadata, not .{}", out_ext).into());
+    }
+
+    if *meta {
+        frame_opts.meta = Some(payload::Meta::now(alg));
+    }
+    let (mut stride, mut strength) = (*stride, *strength);
+    if let Some(target) = target_quality {
+        if ft != "picture" {
+            return Err("--target-quality only works for pictures".into());
+        }
+        if algorithm.as_ref().is_some_and(AlgorithmSpec::has_params) {
+            return Err("--target-quality picks the settings itself, give --algorithm without parameters".into());
+        }
+        let only = algorithm.as_ref().map(|a| a.name.as_str());
+        let tuned = steg_algorithms::picture::general::tune::search(in_path, payload, &frame_opts, &out_ext, only, key.as_deref(), *target)?;
+        let setting = match tuned.algorithm {
+            "overlay" => format!("strength {}", tuned.strength),
+            _ if key.is_some() => "keyed".to_string(),
+            _ => format!("stride {}", tuned.stride),
+        };
+        status(format_args!(
+            "chose {} ({}, compression {}), quality {:.4}",
+            tuned.algorithm, setting, if tuned.compress { "on" } else { "off" }, tuned.quality
+        ));
+        alg = tuned.algorithm;
+        stride = tuned.stride as u32;
+        strength = tuned.strength;
+        frame_opts.compress = tuned.compress;
+        if let Some(m) = &mut frame_opts.meta {
+            m.algorithm = alg.to_string();
+        }
+    }
+    // marker seals each of its segments instead (see marker_hijacking), so its frame goes in unencrypted
+    let segment_password = if alg == "marker" { frame_opts.password.clone() } else { None };
+    let encode_opts = FrameOptions { password: frame_opts.password.clone().filter(|_| segment_password.is_none()), ..frame_opts.clone() };
+    let mut framed = match payload.encode(&encode_opts) {
+        Ok(v) => v,
+        Err(e) => return Err(e.context("Failed to encrypt payload")),
+    };
+
+    // --stride, --key, --offset, --region and --range with the parameters of --algorithm laid over them
+    let base = LsbOptions { offset: *offset, region: *region, range: *range, stride: key.is_none().then_some(stride as usize), key: key.clone(), ..LsbOptions::default() };
+    let look = lookup(algorithm.as_ref(), &ft, alg, base)?;
+    let lsb = &look.lsb;
+    let (stride, key) = (lsb.stride.unwrap_or(1), lsb.key.as_deref());
+    if key.is_some() && alg != "lsb" {
+        return Err("--key is only supported by lsb".into());
+    }
+    let copies = *redundancy as usize;
+    steg_algorithms::redundancy::check(copies)?;
+    if copies > 1 && alg != "lsb" {
+        return Err("--redundancy is only supported by lsb".into());
+    }
+    if fec.is_some() && alg != "lsb" {
+        return Err("--fec is only supported by lsb".into());
+    }
+    if *perturb > 0 && alg != "lsb" {
+        return Err(format!("--perturb only works with lsb (there's no spare LSB space in '{}')", alg).into());
+    }
+    let raw_lsb = raw_lsb(&ft, alg, &in_ext, &out_ext);
+    if *perturb > 0 && (raw_lsb || ft == "medical" || ft == "astro") {
+        return Err(format!("--perturb isn't supported for .{} files", in_ext).into());
+    }
+    if lsb.offset > 0 && (ft != "picture" || alg != "lsb") {
+        return Err("--offset only works with lsb on pictures".into());
+    }
+    if lsb.offset > 0 && raw_lsb {
+        return Err(format!("--offset isn't supported for .{} files", in_ext).into());
+    }
+    if lsb.region.is_some() && (ft != "picture" || alg != "lsb") {
+        return Err("--region only works with lsb on pictures".into());
+    }
+    if lsb.region.is_some() && raw_lsb {
+        return Err(format!("--region isn't supported for .{} files", in_ext).into());
+    }
+    if lsb.range.is_some() && (ft != "audio" || alg != "lsb") {
+        return Err("--range only works with lsb on WAV audio".into());
+    }
+    if (!lsb.plain_layout() || lsb.embedding != Embedding::Replace) && raw_lsb {
+        return Err(format!("lsb bits, channels, alpha, traversal and matching aren't supported for .{} files", in_ext).into());
+    }
+    if *perturb > 0 && !lsb.plain_layout() {
+        return Err("--perturb only works with the plain lsb layout (bits=1, channels=rgb, no alpha, row traversal)".into());
+    }
+    // the noised cover only stands in for the input where the pixels get embedded into
+    let noisy = match prenoise {
+        Some(_) if ft != "picture" || alg != "lsb" => return Err("--prenoise only works with lsb on pictures".into()),
+        Some(_) if raw_lsb => return Err(format!("--prenoise isn't supported for .{} files", in_ext).into()),
+        Some(sigma) => {
+            let ws = workspace(cli)?;
+            let copy = ws.file(".png");
+            steg_algorithms::picture::general::prenoise::noisy_copy(in_path, *sigma, key, &copy)?;
+            ws.check_quota()?;
+            Some((ws, copy))
+        }
+        None => None,
+    };
+    let cover = noisy.as_ref().map_or(in_path, |(_, copy)| copy.as_path());
+
+    if let Some(name) = name {
+        if alg != "lsb" {
+            return Err("--name is only supported by lsb".into());
+        }
+        // keep whatever the input already carries, read with the same stride/key
+        let find = FindOptions { lsb: LsbOptions { stride: Some(stride), ..lsb.clone() }, ..FindOptions::default() };
+        let existing = match ft.as_str() {
+            "audio" => steg_algorithms::audio::wav::lsb::find_wav_with(in_path, &find).map(|(data, _)| data),
+            "medical" => dicom::find_lsb(in_path, Some(stride), key),
+            "astro" => fits::find_lsb(in_path, Some(stride), key),
+            _ if raw::handles(in_path) => {
+                raw::find_payload(in_path, Some(stride), key)
+            }
+            _ => steg_algorithms::picture::general::lsb::find_with(in_path, &find).map(|(data, _)| data),
+        }
+        .ok()
+        .map(|raw| payload::unprotect(&raw).ok().flatten().map_or(raw, |(inner, _)| inner))
+        .filter(|raw| raw.starts_with(&payload::MAGIC));
+        let mut table = match existing.as_deref().map(payload::Table::parse) {
+            Some(Ok(Some(t))) => t,
+            Some(Ok(None)) => {
+                log::warn!("the input already holds an unnamed payload, keeping it as 'unnamed'");
+                let mut t = payload::Table::default();
+                let _ = t.insert("unnamed", existing.unwrap_or_default());
+                t
+            }
+            Some(Err(e)) => return Err(format!("The input's payload table is damaged ({}), refusing to overwrite it", e).into()),
+            None => payload::Table::default(),
+        };
+        // the envelope goes around the whole table, not each entry
+        let entry = match payload.encode(&FrameOptions { fec_parity: None, ..frame_opts.clone() }) {
+            Ok(v) => v,
+            Err(e) => return Err(e.context("Failed to encrypt payload")),
+        };
+        framed = table.insert(name, entry).and_then(|_| table.encode(*fec))?;
+        log::debug!("named payloads: {}", table.names().collect::<Vec<_>>().join(", "));
+    }
+
+    let capacity = carrier_room(&ft, alg, in_path, lsb, copies);
+    if let Some(pad) = pad {
+        let mut rng = ChaCha20Rng::from_entropy();
+        let mut target = match pad {
+            Pad::Bytes(n) => *n,
+            Pad::Random => {
+                let max = capacity.unwrap_or(framed.len() * 2 + 4096).max(framed.len());
+                rng.gen_range(framed.len()..=max)
+            }
+        };
+        if let Some(cap) = capacity && target > cap {
+            log::warn!("padding reduced from {} to {} bytes to fit the carrier", target, cap);
+            target = cap;
+        }
+        payload::pad_to(&mut framed, target, &mut rng);
+    }
+    if let Some(have) = capacity && framed.len() > have {
+        return Err(StegError::CapacityExceeded { needed: framed.len(), available: have });
+    }
+
+    log::debug!("hide — filetype: {}, algorithm: {}, in: {:?}, out: {:?}, payload: {} bytes{} ({} framed)",
+                ft, alg, in_path, out_path, payload.data.len(),
+                payload.name.as_deref().map(|n| format!(" ({})", n)).unwrap_or_default(),
+                framed.len());
+    log::trace!("payload sha256 {}, framed sha256 {}", steg_algorithms::delta::sha256_hex(&payload.data), steg_algorithms::delta::sha256_hex(&framed));
+
+    if *dry_run {
+        use steg_algorithms::audio::wav::lsb as wav_lsb;
+        use steg_algorithms::picture::general::lsb as picture_lsb;
+
+        let plan = match (ft.as_str(), alg) {
+            ("audio", "lsb") => wav_lsb::plan(in_path, &framed, Some(stride).filter(|_| key.is_none()), key, copies, lsb.range.as_ref()),
+            ("picture", "lsb") if !raw_lsb => picture_lsb::plan(cover, &framed, out_path, lsb, copies),
+            _ => return Err(format!("--dry-run works out lsb on pictures and WAV, not {} on .{} files", alg, in_ext).into()),
+        }
+        .map_err(|e| e.context("hide failed"))?;
+        if cli.json {
+            println!("{}", serde_json::to_string(&plan).expect("plans always serialize"));
+        } else {
+            println!("dry run, {} left alone:", out_path.display());
+            println!("  bits      {} ({} bytes framed{})", plan.bits, framed.len(), if copies > 1 { format!(", {} copies", copies) } else { String::new() });
+            println!("  capacity  {:.4}% of {} bits", plan.capacity_used, plan.capacity_bits);
+            println!("  changes   {} of {} {}s", plan.changed, plan.units, plan.unit);
+            println!("  output    {} bytes", plan.output_bytes);
+        }
+        return Ok(());
+    }
+
+    // an interrupt from here on removes the half written output
+    let writing = cancel::pending(dest);
+    let algorithm = registry::get(&ft, alg)?;
+    let embedded = match alg {
+        // a page only holds a few bits, the framing header alone wouldn't fit
+        "lineshift" if frame_opts.password.is_some() || hmac_key.is_some() || *compress || pad.is_some() || *meta || payload.name.is_some() => {
+            return Err("lineshift only holds a few raw bytes: --password, --hmac-key, --compress, --pad, --meta and --msg-file aren't supported".into());
+        }
+        // the output is a JPEG anyway, so the carrier gets re-encoded first and that is hijacked
+        "marker" if !formats::is_jpeg(&in_ext) && formats::is_jpeg(&out_ext) => {
+            log::warn!("re-encoding {:?} as JPEG for marker hijacking", in_path);
+            &framed
+        }
+        "marker" if !formats::is_jpeg(&in_ext) => return Err("You can only use marker hijacking with jpeg files >:(".into()),
+        _ if !algorithm.framed() => &payload.data,
+        _ => &framed,
+    };
+    let opts = Options {
+        copies,
+        password: segment_password.clone(),
+        cipher: frame_opts.cipher,
+        strength,
+        shift: *shift as usize,
+        app_id: parse_app_id(app_id)?,
+        ..look.clone()
+    };
+    if let Err(e) = algorithm.hide(cover, embedded, dest, &opts) {
+        return Err(e.context("hide failed"));
+    }
+    log::debug!("hide succeeded, {} bytes into {}", framed.len(), dest.display());
+
+    if *perturb > 0 {
+        let mut rng = ChaCha20Rng::from_entropy();
+        // perturb only knows the plain layout, so hand it a length that covers all the copies
+        let used = if copies > 1 { steg_algorithms::redundancy::plain_equivalent_len(framed.len(), copies) } else { framed.len() };
+        let res = match (ft.as_str(), key) {
+            ("picture", Some(k)) => steg_algorithms::picture::general::lsb::perturb_keyed(dest, used, lsb.offset, k, *perturb, &mut rng),
+            ("picture", None) => steg_algorithms::picture::general::lsb::perturb(dest, used, lsb.offset, stride, *perturb, &mut rng),
+            (_, Some(k)) => steg_algorithms::audio::wav::lsb::perturb_keyed(dest, used, k, *perturb, &mut rng),
+            _ => steg_algorithms::audio::wav::lsb::perturb(dest, used, stride, *perturb, &mut rng),
+        };
+        match res {
+            Ok(n) if n < *perturb => log::warn!("only room to perturb {} of {} LSBs", n, perturb),
+            Ok(_) => {}
+            Err(e) => return Err(e.context("perturb failed")),
+        }
+    }
+
+    if *strip_metadata {
+        let res = std::fs::read(dest)
+            .map_err(StegError::from)
+            .and_then(|buf| steg_algorithms::metadata::strip(&buf, (alg == "marker").then_some(&look.marker)))
+            .and_then(|(buf, cut)| std::fs::write(dest, buf).map(|_| cut).map_err(StegError::from));
+        match res {
+            Ok(cut) => cut.iter().for_each(|c| log::debug!("stripped {}", c)),
+            Err(e) => {
+                let _ = std::fs::remove_file(dest);
+                return Err(format!("Stripping the metadata failed, {}: {}", discarded, e).into());
+            }
+        }
+    }
+
+    if *verify || !*no_verify {
+        // lineshift carries the bare message, everything else the frame
+        let expected = if alg == "lineshift" { &payload.data } else { &framed };
+        let problem = match extract(&ft, alg, dest, &opts, None) {
+            Ok((back, _)) if back == *expected => None,
+            Ok((back, _)) if back.len() == expected.len() => {
+                let differ = back.iter().zip(expected).filter(|(a, b)| a != b).count();
+                Some(format!("{} of {} bytes read back different", differ, expected.len()))
+            }
+            Ok((back, _)) => Some(format!("{} bytes read back instead of {}", back.len(), expected.len())),
+            Err(e) => Some(format!("the payload can't be read back ({})", e)),
+        };
+        if let Some(problem) = problem {
+            let _ = std::fs::remove_file(dest);
+            return Err(format!(
+                "Verification failed, {}: {}.\nLikely cause: {}",
+                discarded, problem, formats::likely_loss(&ft, alg, &out_ext)
+            ).into());
+        }
+        log::debug!("verified: the payload reads back from {:?}", out_path);
+    }
+
+    if let Some(report) = noise_report {
+        let noise = steg_algorithms::noise::measure(&ft, alg, in_path, dest, framed.len(), alg == "lsb" && lsb.use_alpha)
+            .map_err(|e| e.context("Failed to measure the noise"))?;
+        if report == Path::new("-") {
+            println!("{}", noise.to_json());
+        } else {
+            noise.append(report)?;
+        }
+    }
+
+    if *preserve_length || *report_delta || log::log_enabled!(log::Level::Warn) {
+        let delta = match steg_algorithms::delta::compare(in_path, dest) {
+            Ok(d) => d,
+            Err(e) => return Err(e.context("Failed to compare input and output")),
+        };
+        if *report_delta {
+            println!("input:  {} bytes  sha256 {}", delta.in_len, delta.in_sha256);
+            println!("output: {} bytes  sha256 {}", delta.out_len, delta.out_sha256);
+            match delta.changed_bytes {
+                Some(n) => println!("same length, {} bytes differ", n),
+                None => println!("length changed by {:+} bytes", delta.out_len as i64 - delta.in_len as i64),
+            }
+        }
+        if delta.identical() {
+            log::warn!("output is byte-identical to the input (the carrier already held these bits), pass --perturb to make it differ");
+        }
+        if *preserve_length && !delta.same_length() {
+            let _ = std::fs::remove_file(dest);
+            return Err(format!("Output is {} bytes but the input is {}, {} (--preserve-length)", delta.out_len, delta.in_len, discarded).into());
+        }
+    }
+
+    if let Some(pair) = &split {
+        let mut written = Vec::new();
+        for (share, path) in pair.iter().zip(key_share) {
+            if let Err(e) = share.write(path) {
+                for p in written.iter().chain([&dest]) {
+                    let _ = std::fs::remove_file(p);
+                }
+                return Err(format!("{}; {}, nothing could open it", e, discarded).into());
+            }
+            written.push(path.as_path());
+        }
+        status(format_args!("key shares written to {} and {}, find needs both", key_share[0].display(), key_share[1].display()));
+    }
+
+    if let Some(tmp) = staged {
+        tmp.commit().inspect_err(|_| {
+            for p in key_share {
+                let _ = std::fs::remove_file(p);
+            }
+        })?;
+    }
+    drop(writing);
+
+    if let Some(path) = &manifest {
+        let options = HideOptions { lsb: lsb.clone(), copies, marker: look.marker.clone(), password: None, cipher: frame_opts.cipher };
+        let mut record = Manifest::new(&ft, alg, options);
+        record.app_id = (alg == "appext").then(|| app_id.clone());
+        record.secrets = Secrets {
+            key: key.is_some(),
+            password: frame_opts.password.is_some() && split.is_none(),
+            key_shares: split.is_some(),
+            hmac_key: hmac_key.is_some(),
+        };
+        record.payload = PayloadRecord {
+            name: payload.name.clone(),
+            stored_as: name.clone(),
+            len: payload.data.len(),
+            sha256: steg_algorithms::delta::sha256_hex(&payload.data),
+            framed_len: framed.len(),
+            compressed: frame_opts.compress,
+            fec_parity: *fec,
+        };
+        record.carrier = CarrierRecord {
+            input: in_path.display().to_string(),
+            input_sha256: steg_algorithms::audit::file_sha256(in_path)?,
+            output: out_path.display().to_string(),
+            output_sha256: steg_algorithms::audit::file_sha256(out_path)?,
+        };
+        // the carrier is done, a manifest that can't be written doesn't take it with it
+        record.write(path).map_err(|e| e.context(format!("{} was written but its manifest wasn't", out_path.display())))?;
+        log::debug!("wrote the manifest to {}", path.display());
+    }
+
+    if let Some(log) = &cli.audit_log {
+        let mut params = serde_json::json!({
+            "compress": compress,
+            "encrypted": frame_opts.password.is_some(),
+            "key_shares": split.is_some(),
+            "hmac": hmac_key.is_some(),
+            "framed_len": framed.len(),
+            "meta": meta,
+        });
+        if frame_opts.password.is_some() {
+            params["cipher"] = cipher.to_possible_value().map(|v| v.get_name().to_string()).into();
+        }
+        match alg {
+            "lsb" => {
+                params["keyed"] = key.is_some().into();
+                if key.is_none() {
+                    params["stride"] = stride.into();
+                }
+                params["offset"] = lsb.offset.into();
+                if let Some(region) = lsb.region {
+                    params["region"] = region.to_string().into();
+                }
+                if let Some(range) = lsb.range {
+                    params["range"] = range.to_string().into();
+                }
+                if !lsb.plain_layout() {
+                    params["bits"] = lsb.bits.into();
+                    params["channels"] = lsb.channels.iter().map(|&c| ["r", "g", "b"][c]).collect::<String>().into();
+                    params["alpha"] = lsb.use_alpha.into();
+                    params["traversal"] = lsb.traversal.name().into();
+                    if lsb.traversal == Traversal::Texture {
+                        params["threshold"] = lsb.threshold.into();
+                    }
+                }
+                if lsb.embedding == Embedding::Match {
+                    params["matching"] = true.into();
+                }
+                params["perturb"] = (*perturb).into();
+                params["prenoise"] = (*prenoise).into();
+                params["fec_parity"] = (*fec).into();
+                params["redundancy"] = copies.into();
+            }
+            "overlay" => params["strength"] = strength.into(),
+            "marker" if look.marker != MarkerOptions::default() => {
+                params["app"] = look.marker.app.into();
+                params["id"] = String::from_utf8_lossy(&look.marker.id).into();
+            }
+            "lineshift" => params["shift"] = (*shift).into(),
+            "appext" => params["app_id"] = app_id.as_str().into(),
+            _ => {}
+        }
+        audit(log, steg_algorithms::audit::Record {
+            op: "hide",
+            filetype: ft.clone(),
+            algorithm: alg.to_string(),
+            input: in_path.display().to_string(),
+            input_sha256: file_hash(in_path)?,
+            output: Some(out_path.display().to_string()),
+            output_sha256: Some(file_hash(out_path)?),
+            payload_sha256: steg_algorithms::delta::sha256_hex(&payload.data),
+            params,
+        })?;
+    }
+    Ok(())
+}
+
+/// Move the payload in the carrier named on the convert command line to another one.
+fn convert(cli: &Cli) -> Result<(), StegError> {
+    use steg_algorithms::convert::{self, Sealing};
+
+    let Command::Convert { filetype, in_path, from, out_path, to, cover, password, cipher, hmac_key, force } = &cli.cmd else {
+        unreachable!("convert is only called for the convert command");
+    };
+    check_output(out_path, *force)?;
+    let ft = detect_filetype(filetype, in_path)?;
+    let from_alg = pick_algorithm(from.as_ref(), &ft, in_path)?;
+    let from_look = lookup(from.as_ref(), &ft, from_alg, LsbOptions::default())?;
+    let cover_path = cover.as_deref().unwrap_or(in_path);
+    let to_ft = if cover.is_some() { detect_filetype(&None, cover_path)? } else { ft.clone() };
+    // the output's extension is what decides which algorithm suits the destination
+    let to_alg = pick_algorithm(to.as_ref(), &to_ft, out_path)?;
+    let to_look = lookup(to.as_ref(), &to_ft, to_alg, LsbOptions::default())?;
+    let out_ext = out_path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    if let Some(problem) = formats::output_problem(&to_ft, to_alg, &out_ext) {
+        return Err(format!("{}, pick another --to or output extension", problem).into());
+    }
+    if to_alg == "lineshift" {
+        return Err("lineshift only holds a few raw bytes, not a frame, it can't be converted to".into());
+    }
+
+    if parse::mode() == parse::Mode::Strict {
+        let buf = std::fs::read(cover_path).map_err(|e| StegError::io(format!("Failed to read {}", cover_path.display()), e))?;
+        structure_problems(&buf).map_err(|e| e.context(cover_path.display()))?;
+    }
+    let sealed = from_alg == "marker"
+        && std::fs::read(in_path).is_ok_and(|buf| steg_algorithms::picture::jpg::marker_hijacking::holds_sealed(&buf, &from_look.marker));
+    let (carried, _) = extract(&ft, from_alg, in_path, &Options { password: password.clone(), ..from_look.clone() }, None)
+        .map_err(|e| format!("Nothing to convert, {} found no payload: {}", from_alg, e))?;
+    let room = carrier_room(&to_ft, to_alg, cover_path, &to_look.lsb, 1);
+    let opts = convert::Options { password: password.clone(), cipher: (*cipher).into(), hmac_key: hmac_key.clone() };
+    let framed = convert::reframe(carried, if sealed { Sealing::Segments } else { Sealing::Frame }, room, &opts)?;
+    log::debug!("convert — {} {} -> {} {}, {} bytes framed{}", ft, from_alg, to_ft, to_alg, framed.len(), if sealed { " (re-encrypted)" } else { "" });
+
+    let staged = atomic::Staged::new(out_path)?;
+    let dest = staged.path();
+    let writing = cancel::pending(dest);
+    // moving within one carrier: take the old copy out of it first, where wipe knows how and the output
+    // would keep it
+    let ws = workspace(cli)?;
+    let mut base = cover_path.to_path_buf();
+    if cover.is_none()
+        && steg_algorithms::wipe::SCOPES.contains(&from_alg)
+        && matches!(ft.as_str(), "picture" | "audio")
+        && formats::output_problem(&ft, from_alg, &out_ext).is_none()
+    {
+        let in_ext = in_path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let wiped = ws.file(&format!(".{}", in_ext));
+        let removed = steg_algorithms::wipe::wipe(in_path, Some(&wiped), Some(from_alg), &mut ChaCha20Rng::from_entropy())
+            .map_err(|e| e.context("Failed to wipe the old copy"))?;
+        ws.check_quota()?;
+        for r in removed {
+            log::debug!("wiped [{}] {}", r.scope, r.detail);
+        }
+        base = wiped;
+    }
+    registry::get(&to_ft, to_alg)?.hide(&base, &framed, dest, &to_look).map_err(|e| e.context("convert failed"))?;
+
+    // the destination has to give back exactly the frame, or the move didn't happen
+    match extract(&to_ft, to_alg, dest, &to_look, None) {
+        Ok((back, _)) if back == framed => {}
+        _ => {
+            let _ = std::fs::remove_file(dest);
+            return Err(format!("The payload doesn't read back from the new carrier, nothing written. Likely cause: {}", formats::likely_loss(&to_ft, to_alg, &out_ext)).into());
+        }
+    }
+    staged.commit()?;
+    drop(writing);
+    status(format_args!("moved {} bytes from {} to {} ({})", framed.len(), from_alg, to_alg, out_path.display()));
+
+    if let Some(log) = &cli.audit_log {
+        audit(log, steg_algorithms::audit::Record {
+            op: "convert",
+            filetype: to_ft.clone(),
+            algorithm: to_alg.to_string(),
+            input: in_path.display().to_string(),
+            input_sha256: file_hash(in_path)?,
+            output: Some(out_path.display().to_string()),
+            output_sha256: Some(file_hash(out_path)?),
+            payload_sha256: steg_algorithms::delta::sha256_hex(&framed),
+            params: serde_json::json!({ "from": from_alg, "reencrypted": sealed, "framed_len": framed.len() }),
+        })?;
+    }
+    Ok(())
+}
+
+/// How much the carrier at `in_path` can hold, with the settings on the capacity command line.
+fn capacity(cli: &Cli, in_path: &Path) -> Result<CapacityReport, StegError> {
+    let Command::Capacity { filetype, algorithm, in_path: _, stride, offset, redundancy, fec, cipher, hmac, meta } = &cli.cmd else {
+        unreachable!("capacity is only called for the capacity command");
+    };
+    let ft = detect_filetype(filetype, in_path)?;
+    let alg = pick_algorithm(algorithm.as_ref(), &ft, in_path)?;
+    let copies = *redundancy as usize;
+    steg_algorithms::redundancy::check(copies)?;
+    if alg != "lsb" && (copies > 1 || fec.is_some() || *stride > 1) {
+        return Err("--stride, --redundancy and --fec are only supported by lsb".into());
+    }
+    let lsb = lookup(algorithm.as_ref(), &ft, alg, LsbOptions { offset: *offset, stride: Some(*stride as usize), ..LsbOptions::default() })?.lsb;
+    if lsb.offset > 0 && (ft != "picture" || alg != "lsb" || raw::handles(in_path)) {
+        return Err("--offset only works with lsb on pictures".into());
+    }
+    if !lsb.plain_layout() && raw::handles(in_path) {
+        return Err(format!("lsb bits, channels, alpha, traversal and matching aren't supported for {}", in_path.display()).into());
+    }
+    let (room, limit) = carrier_capacity(&ft, alg, in_path, &lsb, copies)?;
+    let room = room.map_err(|e| format!("Failed to size {}: {}", in_path.display(), e))?;
+    let opts = FrameOptions {
+        password: cipher.map(|_| String::new()),
+        cipher: cipher.map(Cipher::from).unwrap_or_default(),
+        hmac_key: hmac.then(String::new),
+        fec_parity: *fec,
+        meta: meta.then(|| payload::Meta::now(alg)),
+        ..Default::default()
+    };
+    let bytes = payload_room(alg, room, &opts);
+    Ok(CapacityReport { filetype: ft, algorithm: alg.to_string(), payload_bytes: bytes, carrier_bytes: room, limited_by: limit })
+}
+
+/// Bytes the carrier itself takes with `alg` (after its own length header), and what limits them.
+/// The outer error is an algorithm that doesn't exist for `ft`, the inner one a cover it can't size.
+fn carrier_capacity(ft: &str, alg: &str, path: &Path, lsb: &LsbOptions, copies: usize) -> Result<(Result<usize, StegError>, &'static str), StegError> {
+    let algorithm = registry::get(ft, alg)?;
+    let opts = Options { lsb: lsb.clone(), copies, ..Options::default() };
+    Ok((algorithm.capacity(path, &opts), algorithm.limited_by()))
+}
+
+/// What fits of a payload framed with `opts` into `room` carrier bytes. lineshift stores the raw bytes,
+/// everything else a framed payload.
+fn payload_room(alg: &str, room: usize, opts: &FrameOptions) -> usize {
+    if alg == "lineshift" { room } else { payload::max_data_len(room, opts).unwrap_or(0) }
+}
+
+/// The format the content of a file says it's in, and the filetype that goes with it.
+fn sniff_carrier(buf: &[u8]) -> Option<(String, &'static str)> {
+    if buf.len() > 12 && buf.starts_with(b"RIFF") && &buf[8..12] == b"WAVE" {
+        return Some(("WAV".to_string(), "audio"));
+    }
+    if buf.get(128..132) == Some(b"DICM") {
+        return Some(("DICOM".to_string(), "medical"));
+    }
+    if buf.starts_with(b"SIMPLE  =") {
+        return Some(("FITS".to_string(), "astro"));
+    }
+    if let Some(format) = raw::Format::sniff(buf) {
+        return Some((format!("{:?}", format).to_uppercase(), "picture"));
+    }
+    image::guess_format(buf).ok().map(|f| (format!("{:?}", f).to_uppercase(), "picture"))
+}
+
+/// The spec violations the container walkers got past in the JPEG header, PNG or RIFF file in `buf`
+/// (nothing for other formats). In strict mode the first one is an error instead.
+fn structure_problems(buf: &[u8]) -> Result<Vec<String>, StegError> {
+    use steg_algorithms::audio::wav::riff;
+    use steg_algorithms::picture::general::png_chunks;
+    use steg_algorithms::picture::jpg::marker_hijacking;
+
+    let mode = parse::mode();
+    Ok(if buf.starts_with(&png_chunks::SIGNATURE) {
+        png_chunks::chunks(buf, mode)?.problems
+    } else if buf.starts_with(b"RIFF") {
+        riff::chunks(buf, mode)?.1.problems
+    } else if buf.starts_with(&[0xFF, 0xD8]) {
+        marker_hijacking::header_segments(buf, mode)?.problems
+    } else {
+        Vec::new()
+    })
+}
+
+fn picture_info(path: &Path) -> Result<steg_algorithms::report::PictureInfo, StegError> {
+    use image::ImageDecoder;
+
+    let decoder = image::ImageReader::open(path)
+        .and_then(|r| r.with_guessed_format())?
+        .into_decoder()?;
+    let ((width, height), color) = (decoder.dimensions(), decoder.color_type());
+    Ok(steg_algorithms::report::PictureInfo {
+        width,
+        height,
+        color_type: format!("{:?}", color),
+        bits_per_channel: color.bits_per_pixel() / color.channel_count() as u16,
+    })
+}
+
+fn audio_info(path: &Path) -> Result<steg_algorithms::report::AudioInfo, StegError> {
+    let reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    Ok(steg_algorithms::report::AudioInfo {
+        sample_rate: spec.sample_rate,
+        channels: spec.channels,
+        bits_per_sample: spec.bits_per_sample,
+        frames: reader.duration(),
+    })
+}
+
+/// Everything `info` can find out about the file at `path`. Only a file that can't be read at all is an
+/// error; a probe that fails leaves its part of the report out and adds a warning.
+fn info(filetype: &Option<String>, path: &Path, warnings: &mut Vec<String>) -> Result<InfoReport, StegError> {
+    use steg_algorithms::picture::jpg::marker_hijacking;
+    use steg_algorithms::report::SegmentInfo;
+
+    let buf = std::fs::read(path).map_err(|e| StegError::io(format!("Failed to read {}", path.display()), e))?;
+    let sniffed = sniff_carrier(&buf);
+    let ft = match (detect_filetype(filetype, path), &sniffed) {
+        (Ok(ft), _) => Some(ft),
+        // hide and find would want --filetype, but the content is clear enough to go on with
+        (Err(e), Some((_, ft))) => {
+            note(warnings, format!("{}; going by the content instead, which is a {}", e, ft));
+            Some(ft.to_string())
+        }
+        (Err(e), None) => {
+            note(warnings, e.to_string());
+            None
+        }
+    };
+    let format = sniffed.map(|(format, _)| format);
+    if format.is_none() {
+        note(warnings, "The content isn't in a format this tool recognizes".to_string());
+    }
+    let mut report = InfoReport { size: buf.len() as u64, filetype: ft.clone(), format: format.clone(), ..Default::default() };
+    match structure_problems(&buf) {
+        Ok(problems) => problems.into_iter().for_each(|p| note(warnings, format!("Damaged structure, read past it: {}", p))),
+        Err(e) => note(warnings, format!("Damaged structure: {}", e)),
+    }
+
+    match ft.as_deref() {
+        Some("picture") => report.picture = picture_info(path).map_err(|e| note(warnings, format!("Can't read the picture header: {}", e))).ok(),
+        Some("audio") => report.audio = audio_info(path).map_err(|e| note(warnings, format!("Can't read the WAV header: {}", e))).ok(),
+        _ => {}
+    }
+    if format.as_deref() == Some("JPEG") {
+        // in strict mode a damaged header has no segments to list, the warning above says why
+        report.segments = marker_hijacking::app_segments(&buf).ok().map(|segments| segments.into_iter().map(|(marker, body)| {
+            let id: String = body.iter().take_while(|b| b.is_ascii_graphic() || **b == b' ').take(24).map(|&b| b as char).collect();
+            SegmentInfo {
+                marker: if marker == 0xFE { "COM".to_string() } else { format!("APP{}", marker - 0xE0) },
+                size: body.len(),
+                identifier: Some(id.trim_end().to_string()).filter(|id| !id.is_empty()),
+            }
+        }).collect());
+    }
+    if matches!(format.as_deref(), Some("JPEG" | "PNG")) {
+        report.metadata = steg_algorithms::metadata::find(&buf, None).ok().map(|found| found.into_iter().map(|(.., what)| what).collect());
+    }
+
+    let Some(ft) = ft else { return Ok(report) };
+    for a in registry::for_filetype(&ft) {
+        // the segment carriers only go into their own container
+        let needs = match a.name() {
+            "marker" => Some("JPEG"),
+            "appext" => Some("GIF"),
+            _ => None,
+        };
+        if needs.is_some_and(|n| format.as_deref() != Some(n)) {
+            continue;
+        }
+        report.algorithms.push(match a.capacity(path, &Options::default()) {
+            Ok(room) => AlgorithmRoom {
+                algorithm: a.name().to_string(),
+                room: Some(RoomReport { payload_bytes: payload_room(a.name(), room, &FrameOptions::default()), carrier_bytes: room, limited_by: a.limited_by() }),
+                error: None,
+            },
+            Err(e) => AlgorithmRoom { algorithm: a.name().to_string(), room: None, error: Some(e.to_string()) },
+        });
+    }
+    Ok(report)
+}
+
+fn print_info(path: &Path, r: &InfoReport) {
+    let kind = [r.filetype.as_deref(), r.format.as_deref()].into_iter().flatten().collect::<Vec<_>>().join(", ");
+    println!("{}: {}{} bytes", path.display(), if kind.is_empty() { String::new() } else { format!("{}, ", kind) }, r.size);
+    if let Some(p) = &r.picture {
+        println!("  picture    {}x{} {}, {} bits per channel", p.width, p.height, p.color_type, p.bits_per_channel);
+    }
+    if let Some(a) = &r.audio {
+        println!("  audio      {} Hz, {} channels, {}-bit, {} frames ({:.1} s)",
+            a.sample_rate, a.channels, a.bits_per_sample, a.frames, a.frames as f64 / a.sample_rate.max(1) as f64);
+    }
+    for (i, seg) in r.segments.iter().flatten().enumerate() {
+        let line = format!("  {:<10} {:<6} {:>6} bytes  {}", if i == 0 { "segments" } else { "" }, seg.marker, seg.size, seg.identifier.as_deref().unwrap_or(""));
+        println!("{}", line.trim_end());
+    }
+    match r.metadata.as_deref() {
+        Some([]) => println!("  metadata   none"),
+        Some(found) => found.iter().enumerate().for_each(|(i, what)| println!("  {:<10} {}", if i == 0 { "metadata" } else { "" }, what)),
+        None => {}
+    }
+    for a in &r.algorithms {
+        match (&a.room, &a.error) {
+            (Some(room), _) => println!("  {:<10} holds {} bytes of payload ({} bytes of carrier space, limited by {})",
+                a.algorithm, room.payload_bytes, room.carrier_bytes, room.limited_by),
+            (None, e) => println!("  {:<10} can't be sized: {}", a.algorithm, e.as_deref().unwrap_or("unknown error")),
+        }
+    }
+    if r.algorithms.is_empty() {
+        println!("  no algorithm hides anything in this file");
+    }
+}
+
+/// `lsb` (from --stride, --key and --offset) and the default marker segments, with the parameters of
+/// `--algorithm` laid over whichever of them `alg` uses. The rest of the options are the defaults.
+fn lookup(spec: Option<&AlgorithmSpec>, ft: &str, alg: &str, lsb: LsbOptions) -> Result<Options, StegError> {
+    let mut found = Options { lsb, ..Options::default() };
+    let Some(spec) = spec.filter(|s| s.has_params()) else {
+        return Ok(found);
+    };
+    if spec.name != alg {
+        return Err(format!("The parameters given for {} don't apply to {}, the algorithm used", spec.name, alg).into());
+    }
+    match alg {
+        "lsb" => found.lsb = spec.lsb(ft, found.lsb)?,
+        "marker" => found.marker = spec.marker()?,
+        _ => spec.check(ft)?,
+    }
+    Ok(found)
+}
+
+/// Read back the bytes `alg` carries in the `ft` file at `path`, with a per-byte confidence from the
+/// algorithms that vote. Shared by find and hide --verify.
+fn extract(ft: &str, alg: &str, path: &Path, opts: &Options, limit: Option<usize>) -> Result<(Vec<u8>, Option<Vec<f32>>), StegError> {
+    let algorithm = registry::get(ft, alg)?;
+    if opts.lsb.offset > 0 && (ft, alg) != ("picture", "lsb") {
+        return Err("--offset only works with lsb on pictures".into());
+    }
+    if opts.lsb.region.is_some() && (ft, alg) != ("picture", "lsb") {
+        return Err("--region only works with lsb on pictures".into());
+    }
+    if opts.lsb.range.is_some() && (ft, alg) != ("audio", "lsb") {
+        return Err("--range only works with lsb on WAV audio".into());
+    }
+    algorithm.extract(path, opts, limit)
+}
+
+/// Recover the payload the verify command points at, as find would, and compare it with the expected one.
+fn verify(cli: &Cli, warnings: &mut Vec<String>) -> Result<VerifyReport, StegError> {
+    use steg_algorithms::delta::sha256_hex;
+
+    let Command::Verify { filetype, algorithm, in_path, expect_file, expect_sha256, password, hmac_key, app_id, stride, key, offset, region, range, name } = &cli.cmd else {
+        unreachable!("verify is only called for the verify command");
+    };
+    // clap's ArgGroup guarantees one of these is present
+    let (expected_sha256, expected_size) = match (expect_file, expect_sha256) {
+        (Some(f), _) => {
+            let data = std::fs::read(f).map_err(|e| StegError::io(format!("Failed to read {}", f.display()), e))?;
+            (sha256_hex(&data), Some(data.len()))
+        }
+        (None, hash) => (hash.clone().unwrap_or_default(), None),
+    };
+    let ft = detect_filetype(filetype, in_path)?;
+    let alg = pick_algorithm(algorithm.as_ref(), &ft, in_path)?;
+    let base = LsbOptions { offset: *offset, region: *region, range: *range, stride: stride.map(|s| s as usize), key: key.clone(), ..LsbOptions::default() };
+    let look = Options { password: password.clone(), app_id: parse_app_id(app_id)?, ..lookup(algorithm.as_ref(), &ft, alg, base)? };
+    let (bytes, _) = extract(&ft, alg, in_path, &look, None).map_err(|e| format!("verify failed: {}", e))?;
+
+    // lineshift carries the bare message, everything else a frame that's decrypted and inflated here
+    let found = if alg == "lineshift" {
+        bytes
+    } else {
+        let bytes = payload::unprotect(&bytes)?.map_or(bytes, |(inner, _)| inner);
+        let opts = DecodeOptions { password: password.clone(), hmac_key: hmac_key.clone() };
+        let decoded = match (payload::Table::parse(&bytes)?, name) {
+            (Some(table), Some(n)) => match table.get(n) {
+                Some(frame) => Payload::decode_verified(frame, &opts),
+                None => Err(format!("No payload named '{}' (have: {})", n, table.names().collect::<Vec<_>>().join(", ")).into()),
+            },
+            (Some(table), None) => Err(format!("This carrier holds named payloads ({}), pick one with --name", table.names().collect::<Vec<_>>().join(", ")).into()),
+            (None, Some(_)) => Err("This carrier holds a single unnamed payload, drop --name".into()),
+            (None, None) => Payload::decode_verified(&bytes, &opts),
+        };
+        let (payload, auth) = decoded.map_err(|e| format!("verify failed: {}", e))?;
+        if auth == payload::Auth::Unchecked {
+            note(warnings, "payload has an HMAC tag, pass --hmac-key to check it too".to_string());
+        }
+        payload.data
+    };
+    let found_sha256 = sha256_hex(&found);
+    Ok(VerifyReport {
+        filetype: ft,
+        algorithm: alg.to_string(),
+        matches: found_sha256 == expected_sha256,
+        found_size: found.len(),
+        found_sha256,
+        expected_size,
+        expected_sha256,
+    })
+}
+
+/// Extract, decode and deliver the payload in `in_path`, printing it unless --json is on. Notes go to
+/// stderr and into `warnings`.
+fn find(cli: &Cli, in_path: &Path, out_path: Option<&Path>, warnings: &mut Vec<String>) -> Result<FindReport, StegError> {
+    let Command::Find { filetype, algorithm, in_path: _, out_path: _, force, to_clipboard, password, key_share, hmac_key, app_id, stride, key, offset, region, range, name, show_meta, format, span: _, redact_pattern, redact_with, max_bytes, hex, manifest } = &cli.cmd else {
+        unreachable!("find is only called for the find command");
+    };
+    // the payload has stdout to itself
+    let to_stdout = out_path.is_some_and(|p| p == Path::new("-"));
+    if to_stdout && cli.json {
+        return Err("-o - and --json both write to stdout, pick one".into());
+    }
+    if *hex && cli.json {
+        return Err("--hex and --json both decide how the payload is printed, pick one".into());
+    }
+    let password = match key_share.as_slice() {
+        [] => password.clone(),
+        [a, b] => Some(shares::combine(&Share::read(a)?, &Share::read(b)?)?),
+        _ => return Err("--key-share takes both share files, give it twice".into()),
+    };
+    let manifest = match manifest {
+        Some(m) => Some(Manifest::load(&manifest_path(m, in_path))?),
+        None => None,
+    };
+    let (ft, alg) = match &manifest {
+        Some(m) => {
+            if let Some(given) = filetype && *given != m.filetype {
+                return Err(format!("--filetype {} doesn't match the manifest, which is for a {} carrier", given, m.filetype).into());
+            }
+            (m.filetype.clone(), registry::get(&m.filetype, &m.algorithm)?.name())
+        }
+        None => {
+            let ft = detect_filetype(filetype, in_path)?;
+            let alg = pick_algorithm(algorithm.as_ref(), &ft, in_path)?;
+            (ft, alg)
+        }
+    };
+    if let Some(m) = &manifest {
+        if m.secrets.key && key.is_none() {
+            return Err("The manifest says the bits were scattered with a key, pass it with --key".into());
+        }
+        if m.secrets.key_shares && password.is_none() {
+            return Err("The manifest says the payload is encrypted under a split key, pass both --key-share files".into());
+        }
+        if m.secrets.password && password.is_none() {
+            return Err("The manifest says the payload is encrypted, pass --password".into());
+        }
+        if steg_algorithms::audit::file_sha256(in_path).is_ok_and(|sha| sha != m.carrier.output_sha256) {
+            note(warnings, format!("{} isn't the file the manifest was written for (its sha256 differs), reading it anyway", in_path.display()));
+        }
+    }
+    // the named payload the manifest was written for, unless another is asked for
+    let name = &name.clone().or_else(|| manifest.as_ref().and_then(|m| m.payload.stored_as.clone()));
+
+    log::debug!("find — filetype: {}, algorithm: {}, in: {:?}", ft, alg, in_path);
+
+    // per carried byte, from the algorithms that vote
+    let mut confidence = None;
+    let look = match &manifest {
+        Some(m) => Options {
+            lsb: LsbOptions { key: key.clone(), ..m.options.lsb.clone() },
+            marker: m.options.marker.clone(),
+            password: password.clone(),
+            cipher: m.options.cipher,
+            app_id: parse_app_id(m.app_id.as_deref().unwrap_or(app_id))?,
+            ..Options::default()
+        },
+        None => {
+            let base = LsbOptions { offset: *offset, region: *region, range: *range, stride: stride.map(|s| s as usize), key: key.clone(), ..LsbOptions::default() };
+            Options { password: password.clone(), app_id: parse_app_id(app_id)?, ..lookup(algorithm.as_ref(), &ft, alg, base)? }
+        }
+    };
+    // a peek reads as far as a plain frame's header and the bytes asked for, anything else only decodes
+    // whole (compressed, encrypted, signed, legacy) and is read again in full
+    let limit = max_bytes.map(|n| n.saturating_add(payload::PREFIX_HEADER_MAX));
+    let mut extracted = extract(&ft, alg, in_path, &look, limit);
+    if limit.is_some() && extracted.as_ref().is_ok_and(|(bytes, _)| !matches!(Payload::decode_prefix(bytes, 0), Ok(Some(_)))) {
+        extracted = extract(&ft, alg, in_path, &look, None);
+    }
+    let raw = extracted.map(|(data, c)| { confidence = c; data });
+    // the whole payload's length, when only the start of it is kept
+    let mut total_len = None;
+
+    // what the carrier turned out to hold
+    enum Found {
+        Payload(Payload, payload::Auth, Option<payload::Meta>),
+        Table(Vec<Entry>),
+    }
+
+    let decode_opts = DecodeOptions { password: password.clone(), hmac_key: hmac_key.clone() };
+    let found = match raw.and_then(|bytes| match alg {
+        // raw tag, no framing (see lineshift.rs)
+        "lineshift" if hmac_key.is_some() => Err("lineshift payloads can't carry an HMAC".into()),
+        "lineshift" => Ok(Found::Payload(Payload { name: None, data: bytes }, payload::Auth::Absent, None)),
+        _ if *format == PayloadFormat::Legacy => {
+            if password.is_some() || hmac_key.is_some() || name.is_some() {
+                return Err("legacy payloads are plain text: --password, --hmac-key and --name don't apply".into());
+            }
+            Ok(Found::Payload(Payload { name: None, data: steg_algorithms::legacy::unpack(&bytes, ft == "audio") }, payload::Auth::Absent, None))
+        }
+        // an HMAC or a name can only be satisfied by a framed payload
+        _ if *format == PayloadFormat::Auto && hmac_key.is_none() && name.is_none()
+            && let Some(msg) = steg_algorithms::legacy::detect(&bytes, ft == "audio") =>
+        {
+            note(warnings, "no payload header, reading it as a legacy (pre-framing) message".to_string());
+            Ok(Found::Payload(Payload { name: None, data: msg }, payload::Auth::Absent, None))
+        }
+        _ if let Some(n) = max_bytes && name.is_none() && let Some((p, total)) = Payload::decode_prefix(&bytes, *n)? => {
+            total_len = Some(total).filter(|&t| t > p.data.len());
+            Ok(Found::Payload(p, payload::Auth::Absent, None))
+        }
+        _ => {
+            let bytes = match payload::unprotect(&bytes)? {
+                Some((inner, fixed)) => {
+                    if fixed > 0 {
+                        note(warnings, format!("repaired {} damaged payload bytes", fixed));
+                    }
+                    inner
+                }
+                None => bytes,
+            };
+            match (payload::Table::parse(&bytes)?, name) {
+                (Some(table), Some(n)) => match table.get(n) {
+                    Some(frame) => Payload::decode_verified(frame, &decode_opts).map(|(p, a)| Found::Payload(p, a, Payload::meta(frame))),
+                    None => Err(format!("No payload named '{}' (have: {})", n, table.names().collect::<Vec<_>>().join(", ")).into()),
+                },
+                (Some(table), None) => Ok(Found::Table(table.summary().into_iter()
+                    .map(|(n, size, encrypted)| Entry { name: n.to_string(), size, encrypted })
+                    .collect())),
+                (None, Some(_)) => Err("This carrier holds a single unnamed payload, drop --name".into()),
+                (None, None) => Payload::decode_verified(&bytes, &decode_opts).map(|(p, a)| Found::Payload(p, a, Payload::meta(&bytes))),
+            }
+        }
+    }) {
+        Ok(v) => v,
+        Err(e) => return Err(e.context("find failed")),
+    };
+    let confidence = confidence.map(ConfidenceReport::new);
+    if let Some(c) = &confidence {
+        status(format_args!("confidence: lowest byte {:.2}, mean {:.2} over {} bytes (1 = every vote agreed)", c.min, c.mean, c.per_byte.len()));
+    }
+    let mut report = FindReport { filetype: ft.clone(), algorithm: alg.to_string(), confidence, ..Default::default() };
+    let (mut payload, auth, meta) = match found {
+        Found::Payload(payload, auth, meta) => (payload, auth, meta),
+        Found::Table(entries) => {
+            if !cli.json {
+                for e in &entries {
+                    say(to_stdout, &format!("{}  {} bytes{}", e.name, e.size, if e.encrypted { " (encrypted)" } else { "" }));
+                }
+            }
+            return Ok(FindReport { entries: Some(entries), ..report });
+        }
+    };
+    match auth {
+        payload::Auth::Verified => status("HMAC verified"),
+        payload::Auth::Unchecked => note(warnings, "payload has an HMAC tag, pass --hmac-key to verify it".to_string()),
+        payload::Auth::Absent => {}
+    }
+    if *show_meta && !cli.json {
+        match &meta {
+            Some(m) => say(to_stdout, &format!("meta: created {}, rust-stego {}, algorithm {}", m.created_utc(), m.tool_version, m.algorithm)),
+            None => say(to_stdout, "meta: no metadata"),
+        }
+    }
+    log::debug!("find succeeded, {} bytes recovered", payload.data.len());
+    log::trace!("payload sha256 {}", steg_algorithms::delta::sha256_hex(&payload.data));
+    // only the whole payload has the manifest's hash
+    if let Some(m) = &manifest && total_len.is_none() && *name == m.payload.stored_as {
+        let sha256 = steg_algorithms::delta::sha256_hex(&payload.data);
+        if sha256 != m.payload.sha256 {
+            return Err(format!("find failed: the payload's sha256 is {} but the manifest recorded {}", sha256, m.payload.sha256).into());
+        }
+        status("payload matches the manifest's sha256");
+    }
+    if let Some(n) = max_bytes && payload.data.len() > *n {
+        total_len = Some(payload.data.len());
+        payload.data.truncate(*n);
+    }
+    if let Some(total) = total_len {
+        note(warnings, format!("truncated: this is the first {} of the payload's {} bytes", payload.data.len(), total));
+        report.truncated = true;
+        report.total_len = Some(total);
+    }
+    // from here on only the redacted copy is seen
+    let mut redacted = None;
+    if !redact_pattern.is_empty() {
+        let redact::Redacted { data, matches, original_sha256 } = redact::redact(&payload.data, redact_pattern, redact_with);
+        status(format_args!("redacted {} matches, original payload sha256 {}", matches, original_sha256));
+        payload.data = data;
+        report.original_sha256 = Some(original_sha256.clone());
+        report.redactions = Some(matches);
+        redacted = Some((matches, original_sha256));
+    }
+    report.name = payload.name.clone();
+    report.payload_size = payload.data.len();
+    report.hmac = Some(auth);
+    report.meta = meta.as_ref().map(MetaReport::from);
+
+    let payload_sha256 = steg_algorithms::delta::sha256_hex(&payload.data);
+    let mut written = None;
+    if *to_clipboard {
+        match std::str::from_utf8(&payload.data) {
+            Ok(text) => copy_to_clipboard(text)?,
+            Err(_) => return Err("Payload is not text, refusing to put it on the clipboard".into()),
+        }
+    } else if to_stdout {
+        write_stdout(&payload.data, *force)?;
+        if *hex && cli.verbose > 0 {
+            eprint!("{}", hexdump::dump(&payload.data));
+        }
+    } else if let Some(out) = out_path {
+        let target = if out.is_dir() {
+            // only ever use the bare filename so a crafted name can't escape the directory
+            match payload.name.as_deref().and_then(|n| std::path::Path::new(n).file_name()) {
+                Some(name) => out.join(name),
+                None => {
+                    return Err("Payload has no stored filename; pass a file path to -o instead of a directory".into());
+                }
+            }
+        } else {
+            out.to_path_buf()
+        };
+        if let Err(e) = std::fs::write(&target, &payload.data) {
+            return Err(StegError::io("Failed to write output file", e));
+        }
+        log::debug!("wrote the decoded output to {}", target.display());
+        if *hex && cli.verbose > 0 {
+            eprint!("{}", hexdump::dump(&payload.data));
+        }
+        written = Some(target);
+    } else if cli.json {
+        report.payload = Some(payload.data);
+    } else if *hex || (payload.name.is_none() && std::str::from_utf8(&payload.data).is_err()) {
+        print!("{}", hexdump::dump(&payload.data));
+    } else if let Some(name) = &payload.name {
+        println!("Recovered file '{}' ({} bytes), use -o to save it", name, payload.data.len());
+    } else {
+        println!("Result: {}", String::from_utf8_lossy(&payload.data));
+    }
+    report.written = written.clone();
+
+    if let Some(log) = &cli.audit_log {
+        let mut params = serde_json::json!({
+            "password": password.is_some(),
+            "hmac": format!("{:?}", auth).to_lowercase(),
+        });
+        if let Some((matches, _)) = &redacted {
+            // a pattern can spell out what it hides, so only how many
+            params["redact_patterns"] = redact_pattern.len().into();
+            params["redactions"] = (*matches).into();
+        }
+        if let Some(total) = total_len {
+            // the hashes are of the part that was read
+            params["truncated_from"] = total.into();
+        }
+        if alg == "lsb" {
+            params["keyed"] = look.lsb.key.is_some().into();
+            params["stride"] = look.lsb.stride.into();
+            params["offset"] = look.lsb.offset.into();
+            if let Some(region) = look.lsb.region {
+                params["region"] = region.to_string().into();
+            }
+            if let Some(range) = look.lsb.range {
+                params["range"] = range.to_string().into();
+            }
+        }
+        audit(log, steg_algorithms::audit::Record {
+            op: "find",
+            filetype: ft.clone(),
+            algorithm: alg.to_string(),
+            input: in_path.display().to_string(),
+            input_sha256: file_hash(in_path)?,
+            output_sha256: written.as_ref().map(|_| payload_sha256.clone()),
+            output: written.map(|p| p.display().to_string()),
+            // the record is of what was recovered, the redacted output being one view of it
+            payload_sha256: redacted.map_or(payload_sha256, |(_, original)| original),
+            params,
+        })?;
+    }
+    Ok(report)
+}
+
+/// Hide a canary beacon with whatever algorithm survives the output format, returning its name.
+fn embed_beacon(ft: &str, in_path: &std::path::Path, out_path: &std::path::Path, framed: &[u8], key: Option<&str>) -> Result<&'static str, StegError> {
+    use steg_algorithms::picture::{general, gif, jpg};
+
+    let out_ext = out_path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    let in_ext = in_path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    match ft {
+        "audio" => {
+            match key {
+                Some(k) => steg_algorithms::audio::wav::lsb::hide_wav_keyed(in_path, out_path, framed, k)?,
+                None => steg_algorithms::audio::wav::lsb::hide_wav(in_path, out_path, framed)?,
+            }
+            Ok("lsb")
+        }
+        "picture" if key.is_some() && !formats::is_lossless_picture(&out_ext) => {
+            Err(format!("--key needs lsb, which a .{} output doesn't survive", out_ext).into())
+        }
+        "picture" if formats::is_gif(&out_ext) && formats::is_gif(&in_ext) => {
+            gif::app_extension::hide(in_path, framed, out_path, &parse_app_id(DEFAULT_APP_ID)?)?;
+            Ok("appext")
+        }
+        "picture" if formats::is_jpeg(&out_ext) => {
+            let jpeg = if formats::is_jpeg(&in_ext) {
+                std::fs::read(in_path)?
+            } else {
+                general::transcode::to_jpeg(in_path, 90)?
+            };
+            std::fs::write(out_path, jpg::marker_hijacking::hide_in_bytes(&jpeg, framed)?)?;
+            Ok("marker")
+        }
+        "picture" if formats::is_lossless_picture(&out_ext) => {
+            match key {
+                Some(k) => general::lsb::hide_keyed(in_path, framed, out_path, k)?,
+                None => general::lsb::hide(in_path, framed, out_path)?,
+            }
+            Ok("lsb")
+        }
+        "picture" => Err(format!("No algorithm for a .{} canary, use png, jpg or gif", out_ext).into()),
+        other => Err(format!("Canaries aren't supported for {} files", other).into()),
+    }
+}
+
+fn file_hash(path: &std::path::Path) -> Result<String, StegError> {
+    steg_algorithms::audit::file_sha256(path).map_err(|e| format!("Failed to hash for the audit log: {}", e).into())
+}
+
+/// The operation already happened, but an unlogged one must not look like success.
+fn audit(log: &std::path::Path, record: steg_algorithms::audit::Record) -> Result<(), StegError> {
+    steg_algorithms::audit::append(log, &record)
+        .map_err(|e| format!("{} succeeded but the audit log could not be written: {}", record.op, e).into())
+}
+
+fn list_algorithms(json: bool) {
+    use steg_algorithms::catalog;
+
+    if json {
+        println!("{}", catalog::to_json());
+        return;
+    }
+    let all: Vec<catalog::AlgorithmInfo> = registry::all().iter().map(|a| a.info()).collect();
+    let mut filetypes: Vec<&str> = all.iter().map(|a| a.filetype).collect();
+    filetypes.dedup();
+    for ft in filetypes {
+        println!("{}", ft);
+        for a in all.iter().filter(|a| a.filetype == ft) {
+            println!("  {:<10} {}", a.name, a.summary);
+            println!("{:13}capacity: {}", "", a.capacity);
+            println!("{:13}outputs: {}{}", "", a.outputs.join(", "), if a.lossless_only { " (lossless carriers only)" } else { "" });
+            let opts: Vec<String> = a.hide_options.iter().map(|o| format!("--{}", o.name)).collect();
+            if !opts.is_empty() {
+                println!("{:13}options: {}", "", opts.join(" "));
+            }
+            let keys = steg_algorithms::params::keys(a.filetype, a.name);
+            if !keys.is_empty() {
+                println!("{:13}parameters: -a {}:KEY=VALUE,... with {}", "", a.name, keys.join(", "));
+            }
+            if !a.framed {
+                println!("{:13}takes none of the payload options below", "");
+            }
+        }
+    }
+    let framing: Vec<String> = catalog::framing_options().iter().map(|o| format!("--{}", o.name)).collect();
+    println!("payload options (every algorithm but the raw ones): {}", framing.join(" "));
+}
+
+/// The GIF application identifier appext uses unless --app-id says otherwise.
+const DEFAULT_APP_ID: &str = "RSTEGANO1.0";
+
+fn parse_app_id(id: &str) -> Result<[u8; 11], StegError> {
+    id.as_bytes()
+        .try_into()
+        .map_err(|_| format!("--app-id must be exactly 11 bytes (8-byte name + 3-byte auth code), got {}", id.len()).into())
+}
+
+/// Print a --json response on stdout, failing with exit status `code` if the command failed.
+fn print_response<T: serde::Serialize>(response: &Response<T>, code: i32) -> Result<(), CliError> {
+    println!("{}", response.to_json());
+    if !response.ok {
+        return Err(CliError::silent(code));
+    }
+    Ok(())
+}
+
+/// [`print_response`] for a single command, exiting with the status its error calls for.
+fn print_result<T: serde::Serialize>(result: Result<T, StegError>, warnings: Vec<String>) -> Result<(), CliError> {
+    let code = result.as_ref().err().map_or(1, |e| exit_status(e).0);
+    print_response(&Response::new(result, warnings), code)
+}
+
+/// Log a warning and keep it for --json's `warnings`.
+fn note(warnings: &mut Vec<String>, msg: String) {
+    log::warn!("{}", msg);
+    warnings.push(msg);
+}
+
+/// Print a line for the user on stdout, or on stderr when stdout carries the payload (`find -o -`).
+fn say(to_stderr: bool, line: &str) {
+    if to_stderr {
+        eprintln!("{}", line);
+    } else {
+        println!("{}", line);
+    }
+}
+
+/// `find -o -`: the payload's bytes, exactly, on stdout. A terminal gets a hexdump preview of a binary
+/// payload instead, unless `force`.
+fn write_stdout(data: &[u8], force: bool) -> Result<(), StegError> {
+    use std::io::{IsTerminal, Write};
+
+    let mut stdout = std::io::stdout().lock();
+    let binary = std::str::from_utf8(data).map_or(true, |text| text.chars().any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t')));
+    if binary && !force && stdout.is_terminal() {
+        eprintln!("warning: not writing {} bytes of binary payload to a terminal, pass --force or redirect stdout; the first bytes are:", data.len());
+        for (i, row) in data.chunks(16).take(16).enumerate() {
+            eprintln!("{}", hexdump::line(i * 16, row));
+        }
+        return Ok(());
+    }
+    match stdout.write_all(data).and_then(|_| stdout.flush()) {
+        // whatever reads the pipe has seen enough (`| head -c 100`)
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        res => res.map_err(|e| format!("Failed to write the payload to stdout: {}", e).into()),
+    }
+}
+
+fn copy_to_clipboard(text: &str) -> Result<(), StegError> {
+    clipboard::write_text(text).map_err(|e| e.context("Failed to write clipboard"))?;
+    log::debug!("copied {} bytes to the clipboard", text.len());
+    Ok(())
 }
 //bingus
\ No newline at end of file
@@ -0,0 +1,65 @@
+use std::time::{Duration, Instant};
+use crate::steg_algorithms::progress::Stage;
+
+// The progress line on stderr while a command reads or writes a big carrier (see
+// steg_algorithms::progress). Nothing is drawn for the first half second of a stage, so small files
+// never flash a bar.
+
+const DELAY: Duration = Duration::from_millis(500);
+const REDRAW: Duration = Duration::from_millis(100);
+
+pub struct Bar {
+    stage: Option<Stage>,
+    started: Instant,
+    drawn: Option<Instant>,
+}
+
+fn megabytes(bytes: f64) -> String {
+    format!("{:.1} MB", bytes / 1_000_000.0)
+}
+
+impl Bar {
+    pub fn new() -> Self {
+        Bar { stage: None, started: Instant::now(), drawn: None }
+    }
+
+    pub fn update(&mut self, stage: Stage, done: u64, total: Option<u64>) {
+        let now = Instant::now();
+        if total == Some(done) {
+            // over, and whatever gets printed next shouldn't land on the bar's line
+            self.clear();
+            self.stage = None;
+            self.drawn = None;
+            return;
+        }
+        if self.stage != Some(stage) {
+            self.stage = Some(stage);
+            self.started = now;
+        }
+        let elapsed = now - self.started;
+        if elapsed < DELAY || self.drawn.is_some_and(|t| now - t < REDRAW) {
+            return;
+        }
+        self.drawn = Some(now);
+        let label = match stage {
+            Stage::Reading => "reading",
+            Stage::Writing => "writing",
+        };
+        let rate = megabytes(done as f64 / elapsed.as_secs_f64());
+        let line = match total {
+            Some(total) if total > 0 => format!(
+                "{} {:5.1}%  {} of {}, {}/s",
+                label, (done as f64 / total as f64 * 100.0).min(100.0), megabytes(done as f64), megabytes(total as f64), rate
+            ),
+            _ => format!("{} {}, {}/s", label, megabytes(done as f64), rate),
+        };
+        eprint!("\r\x1b[K{}", line);
+    }
+
+    /// Take the line off the screen again, if one was drawn.
+    pub fn clear(&self) {
+        if self.drawn.is_some() {
+            eprint!("\r\x1b[K");
+        }
+    }
+}
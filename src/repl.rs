@@ -0,0 +1,243 @@
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::Path;
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::params::AlgorithmSpec;
+use crate::steg_algorithms::payload::Auth;
+use crate::steg_algorithms::picture::general::lsb::{LsbOptions, Traversal};
+use crate::steg_algorithms::registry::Options;
+use crate::steg_algorithms::session::{Carrier, Kept, Opened, Session};
+
+// `repl`: one command per line against a session (see steg_algorithms::session) that keeps the carrier
+// decoded and every payload found, so trying another key, offset or password doesn't start over. A
+// command that fails says why and the session carries on; end of input or `quit` ends it.
+
+const HELP: &str = "\
+open FILE              decode FILE and look at it from now on
+find [ALGORITHM]       try an algorithm on it, with settings as for find -a (lsb:key=k,offset=3)
+decode N[/NAME]        decode payload N again with the keys set now (NAME picks one out of a table)
+show N[/NAME]          print payload N
+save N[/NAME] FILE     write payload N to FILE
+payloads               list the payloads found so far
+set password|hmac-key VALUE, unset password|hmac-key
+status                 the carrier, the keys set and what is cached
+quit                   end the session (so does end of input)";
+
+/// Read commands from stdin until it ends or `quit`, starting with `open` on `in_path` if one is given.
+pub fn run(filetype: &Option<String>, in_path: Option<&Path>, app_id: &str) -> Result<(), StegError> {
+    let mut session = Session::default();
+    if let Some(path) = in_path {
+        open(&mut session, filetype, path)?;
+    }
+    let stdin = std::io::stdin();
+    let interactive = stdin.is_terminal();
+    if interactive {
+        eprintln!("type help for the commands");
+    }
+    let mut lines = stdin.lock().lines();
+    loop {
+        if interactive {
+            eprint!("steg> ");
+            let _ = std::io::stderr().flush();
+        }
+        let Some(line) = lines.next() else { break };
+        let line = line.map_err(|e| StegError::io("Failed to read a command", e))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (cmd, rest) = line.split_once(char::is_whitespace).map_or((line, ""), |(c, r)| (c, r.trim()));
+        let result = match cmd {
+            "quit" | "exit" => break,
+            "help" => {
+                println!("{}", HELP);
+                Ok(())
+            }
+            "open" => open(&mut session, filetype, Path::new(rest)),
+            "find" => find(&mut session, rest, app_id),
+            "decode" => decode(&session, rest),
+            "show" => show(&session, rest),
+            "save" => save(&session, rest),
+            "payloads" => {
+                for (i, kept) in session.kept.iter().enumerate() {
+                    println!("{}. {} bytes from {}", i + 1, kept.carried.len(), kept.label);
+                }
+                Ok(())
+            }
+            "set" | "unset" => set(&mut session, cmd == "set", rest),
+            "status" => {
+                status(&session);
+                Ok(())
+            }
+            other => Err(format!("Unknown command '{}', type help for the commands", other).into()),
+        };
+        if let Err(e) = result {
+            eprintln!("error: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn open(session: &mut Session, filetype: &Option<String>, path: &Path) -> Result<(), StegError> {
+    if path.as_os_str().is_empty() {
+        return Err("open takes a file".into());
+    }
+    let ft = crate::detect_filetype(filetype, path)?;
+    let carrier = Carrier::open(path, &ft)?;
+    println!("{} ({}): {}", path.display(), ft, carrier.describe());
+    session.carrier = Some(carrier);
+    Ok(())
+}
+
+fn find(session: &mut Session, typed: &str, app_id: &str) -> Result<(), StegError> {
+    let carrier = session.carrier.as_mut().ok_or("Open a carrier first")?;
+    let spec = (!typed.is_empty()).then(|| AlgorithmSpec::parse(typed)).transpose()?;
+    let (ft, path) = (carrier.filetype.clone(), carrier.path.clone());
+    let alg = crate::pick_algorithm(spec.as_ref(), &ft, &path)?;
+    let look = Options {
+        password: session.password.clone(),
+        app_id: crate::parse_app_id(app_id)?,
+        ..crate::lookup(spec.as_ref(), &ft, alg, LsbOptions::default())?
+    };
+    let cached = if alg == "lsb" { carrier.find_lsb(&look.lsb, None) } else { None };
+    let (carried, confidence) = match cached {
+        Some(found) => found?,
+        None => crate::extract(&ft, alg, &path, &look, None)?,
+    };
+    let name = path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned());
+    let settings = if alg == "lsb" { spec_text(&look.lsb) } else if typed.is_empty() { alg.to_string() } else { typed.to_string() };
+    let label = format!("{} on {}", settings, name);
+    println!("{}. {} bytes from {}", session.kept.len() + 1, carried.len(), label);
+    if let Some(per_byte) = &confidence {
+        let min = per_byte.iter().copied().fold(1.0, f32::min);
+        println!("   confidence: lowest byte {:.2} over {} bytes", min, per_byte.len());
+    }
+    session.kept.push(Kept { label, carried, confidence, bare: alg == "lineshift" });
+    describe(session, session.kept.len(), None);
+    Ok(())
+}
+
+// the settings an lsb attempt ran with, as they'd be written after find -a
+fn spec_text(lsb: &LsbOptions) -> String {
+    let mut params = Vec::new();
+    if lsb.bits != 1 {
+        params.push(format!("bits={}", lsb.bits));
+    }
+    if lsb.channels != [0, 1, 2] {
+        params.push(format!("channels={}", lsb.channels.iter().map(|&c| ['r', 'g', 'b'][c]).collect::<String>()));
+    }
+    if lsb.use_alpha {
+        params.push("alpha=true".to_string());
+    }
+    if lsb.traversal != Traversal::Row {
+        params.push(format!("traversal={}", lsb.traversal.name()));
+    }
+    if lsb.traversal == Traversal::Texture {
+        params.push(format!("threshold={}", lsb.threshold));
+    }
+    if let Some(s) = lsb.stride {
+        params.push(format!("stride={}", s));
+    }
+    if let Some(k) = &lsb.key {
+        params.push(format!("key={}", k));
+    }
+    if lsb.offset > 0 {
+        params.push(format!("offset={}", lsb.offset));
+    }
+    if params.is_empty() { "lsb".to_string() } else { format!("lsb:{}", params.join(",")) }
+}
+
+// "N" or "N/NAME"
+fn payload_ref(arg: &str) -> Result<(usize, Option<&str>), StegError> {
+    let (n, name) = arg.split_once('/').map_or((arg, None), |(n, name)| (n, Some(name)));
+    let n = n.parse().map_err(|_| format!("'{}' isn't a payload number, see payloads", arg))?;
+    Ok((n, name))
+}
+
+fn opened(session: &Session, arg: &str) -> Result<Opened, StegError> {
+    let (n, name) = payload_ref(arg)?;
+    let audio = session.carrier.as_ref().is_some_and(|c| c.filetype == "audio");
+    session.get(n)?.open(&session.decode_options(), name, audio)
+}
+
+// one line on what payload `n` decodes to with the keys set now
+fn describe(session: &Session, n: usize, name: Option<&str>) {
+    let arg = name.map_or_else(|| n.to_string(), |name| format!("{}/{}", n, name));
+    match opened(session, &arg) {
+        Ok(Opened::Payload(p, auth)) => {
+            let what = p.name.as_deref().map_or_else(|| "message".to_string(), |name| format!("file '{}'", name));
+            let hmac = match auth {
+                Auth::Verified => ", HMAC verified",
+                Auth::Unchecked => ", HMAC not checked (set hmac-key)",
+                Auth::Absent => "",
+            };
+            println!("   {}, {} bytes{}", what, p.data.len(), hmac);
+        }
+        Ok(Opened::Table(entries)) => {
+            for (name, size, encrypted) in entries {
+                println!("   {}/{}  {} bytes{}", n, name, size, if encrypted { " (encrypted)" } else { "" });
+            }
+        }
+        Ok(Opened::Legacy(msg)) => println!("   legacy message, {} bytes", msg.len()),
+        Err(e) => println!("   doesn't decode: {}", e),
+    }
+}
+
+fn decode(session: &Session, arg: &str) -> Result<(), StegError> {
+    let (n, name) = payload_ref(arg)?;
+    session.get(n)?;
+    describe(session, n, name);
+    Ok(())
+}
+
+// the bytes payload `arg` decodes to
+fn data(session: &Session, arg: &str) -> Result<Vec<u8>, StegError> {
+    match opened(session, arg)? {
+        Opened::Payload(p, _) => Ok(p.data),
+        Opened::Legacy(msg) => Ok(msg),
+        Opened::Table(entries) => Err(format!(
+            "Payload {} is a table, pick one of it: {}",
+            arg,
+            entries.iter().map(|(name, ..)| format!("{}/{}", arg, name)).collect::<Vec<_>>().join(", ")
+        ).into()),
+    }
+}
+
+fn show(session: &Session, arg: &str) -> Result<(), StegError> {
+    crate::write_stdout(&data(session, arg)?, false)?;
+    println!();
+    Ok(())
+}
+
+fn save(session: &Session, args: &str) -> Result<(), StegError> {
+    let (arg, path) = args.split_once(char::is_whitespace).ok_or("save takes a payload number and a file")?;
+    let path = Path::new(path.trim());
+    let data = data(session, arg)?;
+    std::fs::write(path, &data).map_err(|e| StegError::io(format!("Failed to write {}", path.display()), e))?;
+    println!("wrote {} bytes to {}", data.len(), path.display());
+    Ok(())
+}
+
+fn set(session: &mut Session, set: bool, args: &str) -> Result<(), StegError> {
+    let (what, value) = args.split_once(char::is_whitespace).map_or((args, ""), |(w, v)| (w, v.trim()));
+    let value = match (set, value.is_empty()) {
+        (true, true) => return Err(format!("set {} takes a value", what).into()),
+        (true, false) => Some(value.to_string()),
+        (false, _) => None,
+    };
+    match what {
+        "password" => session.password = value,
+        "hmac-key" => session.hmac_key = value,
+        other => return Err(format!("Nothing called '{}' to set, there's password and hmac-key", other).into()),
+    }
+    Ok(())
+}
+
+fn status(session: &Session) {
+    match &session.carrier {
+        Some(c) => println!("carrier: {} ({}), {}", c.path.display(), c.filetype, c.describe()),
+        None => println!("carrier: none, open one"),
+    }
+    let set = |v: &Option<String>| if v.is_some() { "set" } else { "not set" };
+    println!("password: {}, hmac-key: {}", set(&session.password), set(&session.hmac_key));
+    println!("payloads: {}", session.kept.len());
+}
@@ -0,0 +1,853 @@
+// The module only comes alive with the `serve` feature; without it the parsing and handlers stay for
+// their tests and `serve` says how to get it
+#![cfg_attr(not(feature = "serve"), allow(dead_code))]
+
+use std::io::{self, BufWriter, Read, Write};
+
+use serde::Deserialize;
+
+use crate::steg_algorithms::audio::wav::lsb as wav;
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::options::{FindOptions, HideOptions};
+use crate::steg_algorithms::payload::{DecodeOptions, FrameOptions, Payload};
+use crate::steg_algorithms::picture::general::lsb::{self, LsbOptions};
+use crate::steg_algorithms::picture::jpg::marker_hijacking as marker;
+
+// `serve --listen ADDR`: hide and find over HTTP for callers that can't run the CLI. POST /hide takes a
+// multipart/form-data body with a `carrier` part, a `payload` part and an optional `options` part (JSON,
+// see `RequestOptions`) and answers with the stego carrier; POST /extract takes `carrier` and `options`
+// and answers with the payload, or a 404 with a JSON error when there is none. The carrier's first bytes
+// pick the algorithm: lsb for WAVs and pictures, marker for JPEGs, framed the way the CLI frames them so
+// its find reads what the server hid and the other way round.
+//
+// Nothing is ever written to disk. /hide and /extract bodies are read into memory, refused past
+// --max-upload before any of them is read when the client says how long they are. Requests are answered
+// by a fixed number of worker threads, so the memory the server takes stays under about workers × 2 ×
+// --max-upload (the body, plus the carrier written back) however many clients connect at once; the rest
+// wait for a worker.
+//
+// POST /hide/stream is /hide for carriers too big for that: a WAV or JPEG of gigabytes, uploaded chunked
+// or not. Its parts have to come in order, `options` and `payload` (read whole, within --max-upload)
+// before `carrier`, which is marked as it arrives by the stream APIs (`wav::hide_stream_with` a sample at
+// a time, its header sized from the carrier's; `marker::hide_stream_with` past the header) and sent
+// straight back as a chunked response, a chunk's worth buffered at a time, up to --max-stream bytes. A
+// picture is decoded whole, so it's refused there and goes to /hide. The carrier is only read as fast as
+// the client takes the response, which is the backpressure, so a client has to read the response while
+// it uploads (curl does). Anything refused before the first chunk goes out gets an error reply; after
+// that an error can only cut the response short, without its last chunk. A client that goes away ends
+// the read or the send with an error, and the work stops there.
+
+pub struct ServeOptions {
+    pub listen: String,
+    pub max_upload: usize,
+    /// The most bytes a /hide/stream carrier can have. It's never held whole, so this can be far past
+    /// `max_upload`.
+    pub max_stream: u64,
+    pub workers: usize,
+}
+
+/// What the `options` part of a request can set. Unknown fields are refused rather than ignored.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct RequestOptions {
+    /// Encrypt the payload with (or decrypt it with) a key derived from this.
+    password: Option<String>,
+    /// Tag the payload with (or check its tag with) an HMAC keyed with this.
+    hmac_key: Option<String>,
+    /// Deflate the payload before hiding it.
+    compress: bool,
+    /// lsb only: use every Nth slot. Probed when extracting without one.
+    stride: Option<usize>,
+    /// lsb only: scatter the bits in an order only this reproduces.
+    key: Option<String>,
+}
+
+/// A response, before it's handed to the HTTP side.
+#[derive(Debug)]
+struct Reply {
+    status: u16,
+    content_type: String,
+    /// For Content-Disposition, when the payload came with a filename.
+    filename: Option<String>,
+    body: Vec<u8>,
+}
+
+impl Reply {
+    fn bytes(content_type: &str, body: Vec<u8>) -> Self {
+        Reply { status: 200, content_type: content_type.to_string(), filename: None, body }
+    }
+
+    fn error(status: u16, msg: impl std::fmt::Display, code: i32) -> Self {
+        let body = serde_json::json!({ "error": msg.to_string(), "code": code }).to_string().into_bytes();
+        Reply { status, content_type: "application/json".to_string(), filename: None, body }
+    }
+
+    // a request that isn't what the endpoint takes: exit code 2, like a bad command line
+    fn bad_request(msg: impl std::fmt::Display) -> Self {
+        Reply::error(400, msg, 2)
+    }
+
+    // the CLI's exit code goes along, so callers can tell errors apart without reading the message
+    fn from_steg(e: &StegError) -> Self {
+        let status = match e {
+            StegError::NoPayloadFound => 404,
+            StegError::Io(_) => 500,
+            _ => 422,
+        };
+        Reply::error(status, e, e.code())
+    }
+}
+
+/// Listen on `opts.listen` and answer requests until the process is stopped.
+#[cfg(feature = "serve")]
+pub fn serve(opts: &ServeOptions) -> Result<(), StegError> {
+    use std::sync::Arc;
+
+    let server = tiny_http::Server::http(&opts.listen).map_err(|e| format!("Can't listen on {}: {}", opts.listen, e))?;
+    let server = Arc::new(server);
+    log::info!("listening on http://{} with {} workers", opts.listen, opts.workers);
+    eprintln!("serving POST /hide, POST /hide/stream and POST /extract on http://{}", opts.listen);
+    let workers: Vec<_> = (0..opts.workers.max(1))
+        .map(|_| {
+            let (server, max_upload, max_stream) = (Arc::clone(&server), opts.max_upload, opts.max_stream);
+            std::thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    answer(request, max_upload, max_stream);
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        let _ = worker.join();
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "serve"))]
+pub fn serve(_opts: &ServeOptions) -> Result<(), StegError> {
+    Err(StegError::not_built("serve", "serve"))
+}
+
+#[cfg(feature = "serve")]
+fn answer(mut request: tiny_http::Request, max_upload: usize, max_stream: u64) {
+    let content_type = request.headers().iter().find(|h| h.field.equiv("Content-Type")).map(|h| h.value.to_string());
+    let streamed = request.method() == &tiny_http::Method::Post && request.url().split('?').next() == Some("/hide/stream");
+    let reply = if streamed && request.http_version() == &tiny_http::HTTPVersion(1, 1) {
+        // tiny_http only hands out the body and the connection together through `upgrade`. The 100
+        // Continue it sends that way is an interim reply clients skip (and the one a client that sent
+        // Expect: 100-continue waits for); the response after it is `hide_streamed`'s
+        let url = request.url().to_string();
+        let conn = std::cell::RefCell::new(request.upgrade("rust-stego", tiny_http::Response::empty(100)));
+        match hide_streamed(Side(&conn), content_type.as_deref(), max_upload, max_stream, Side(&conn)) {
+            Ok(()) => log::debug!("POST {} answered in chunks", url),
+            Err(e) => log::warn!("POST {}: {}", url, e),
+        }
+        return;
+    } else if streamed {
+        Reply::error(505, "POST /hide/stream answers in chunks, which takes HTTP/1.1", 2)
+    } else {
+        let declared = request.body_length();
+        match read_capped(request.as_reader(), declared, max_upload) {
+            Ok(body) => handle(request.method().as_str(), request.url(), content_type.as_deref(), &body),
+            Err(reply) => reply,
+        }
+    };
+    log::debug!("{} {} -> {}", request.method(), request.url(), reply.status);
+    let response = tiny_http::Response::from_data(reply.body).with_status_code(reply.status);
+    respond(request, response, reply.content_type, reply.filename);
+}
+
+// one side of a connection taken over from tiny_http: the request body to read, or the response to
+// write, in turn
+#[cfg(feature = "serve")]
+struct Side<'a, T>(&'a std::cell::RefCell<T>);
+
+#[cfg(feature = "serve")]
+impl<T: Read> Read for Side<'_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.borrow_mut().read(buf)
+    }
+}
+
+#[cfg(feature = "serve")]
+impl<T: Write> Write for Side<'_, T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.borrow_mut().flush()
+    }
+}
+
+#[cfg(feature = "serve")]
+fn respond<R: Read>(request: tiny_http::Request, mut response: tiny_http::Response<R>, content_type: String, filename: Option<String>) {
+    let mut headers = vec![("Content-Type", content_type)];
+    if let Some(name) = filename {
+        headers.push(("Content-Disposition", format!("attachment; filename=\"{}\"", name.replace(['"', '\\', '\r', '\n'], "_"))));
+    }
+    for (field, value) in headers {
+        if let Ok(header) = tiny_http::Header::from_bytes(field.as_bytes(), value.as_bytes()) {
+            response.add_header(header);
+        }
+    }
+    if let Err(e) = request.respond(response) {
+        log::warn!("couldn't send a response: {}", e);
+    }
+}
+
+/// The whole body from `reader`, or a 413 once it goes past `max` bytes. A `declared` length over
+/// `max` is refused without reading any of it.
+fn read_capped(reader: impl Read, declared: Option<usize>, max: usize) -> Result<Vec<u8>, Reply> {
+    let too_big = || Reply::error(413, format!("Uploads are limited to {} bytes (--max-upload)", max), 5);
+    if declared.is_some_and(|n| n > max) {
+        return Err(too_big());
+    }
+    let mut body = Vec::with_capacity(declared.unwrap_or(0));
+    reader.take(max as u64 + 1).read_to_end(&mut body).map_err(|e| Reply::error(400, format!("Failed to read the request body: {}", e), 3))?;
+    if body.len() > max {
+        return Err(too_big());
+    }
+    Ok(body)
+}
+
+fn handle(method: &str, url: &str, content_type: Option<&str>, body: &[u8]) -> Reply {
+    let path = url.split('?').next().unwrap_or_default();
+    // a /hide/stream body that's already in memory (`answer` streams the ones off the network) is a /hide one
+    let hide = match path {
+        "/hide" | "/hide/stream" => true,
+        "/extract" => false,
+        _ => return Reply::error(404, format!("No endpoint at {}, there are POST /hide, POST /hide/stream and POST /extract", path), 2),
+    };
+    if method != "POST" {
+        return Reply::error(405, format!("{} only takes POST", path), 2);
+    }
+    let Some(boundary) = content_type.and_then(boundary) else {
+        return Reply::bad_request("The body has to be multipart/form-data");
+    };
+    // a body that isn't well-formed multipart is the request's fault, not the carrier's
+    let parts = match multipart(body, &boundary) {
+        Ok(parts) => parts,
+        Err(e) => return Reply::bad_request(e),
+    };
+    let part = |name: &str| parts.iter().find(|p| p.name == name);
+    let opts: RequestOptions = match part("options").map(|p| serde_json::from_slice(p.data)) {
+        None => RequestOptions::default(),
+        Some(Ok(opts)) => opts,
+        Some(Err(e)) => return Reply::bad_request(format!("Bad options: {}", e)),
+    };
+    let Some(carrier) = part("carrier") else {
+        return Reply::bad_request("There's no carrier part");
+    };
+    let result = if hide {
+        let Some(payload) = part("payload") else {
+            return Reply::bad_request("There's no payload part");
+        };
+        hide_in(carrier.data, Payload { name: payload.filename.clone(), data: payload.data.to_vec() }, &opts)
+    } else {
+        extract_from(carrier.data, &opts)
+    };
+    result.unwrap_or_else(|e| Reply::from_steg(&e))
+}
+
+/// What a carrier is, going by its first bytes.
+enum Carrier {
+    Wav,
+    Jpeg,
+    Picture(image::ImageFormat),
+}
+
+impl Carrier {
+    fn sniff(data: &[u8]) -> Result<Self, StegError> {
+        if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WAVE") {
+            Ok(Carrier::Wav)
+        } else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Ok(Carrier::Jpeg)
+        } else {
+            image::guess_format(data).map(Carrier::Picture).map_err(|_| StegError::UnsupportedFormat { found: "a carrier that's neither a WAV nor a picture".to_string() })
+        }
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self {
+            Carrier::Wav => "audio/wav",
+            Carrier::Jpeg => "image/jpeg",
+            Carrier::Picture(format) => format.to_mime_type(),
+        }
+    }
+}
+
+fn lsb_options(opts: &RequestOptions) -> LsbOptions {
+    LsbOptions { stride: opts.stride.filter(|_| opts.key.is_none()), key: opts.key.clone(), ..LsbOptions::default() }
+}
+
+fn hide_in(carrier: &[u8], payload: Payload, opts: &RequestOptions) -> Result<Reply, StegError> {
+    let kind = Carrier::sniff(carrier)?;
+    let (frame, hide) = framed(&kind, payload, opts)?;
+    let stego = match kind {
+        Carrier::Wav => wav::hide_bytes_with(carrier, &frame, &hide)?,
+        Carrier::Jpeg => marker::hide_bytes_with(carrier, &frame, &hide)?,
+        Carrier::Picture(_) => lsb::hide_bytes_with(carrier, &frame, &hide)?,
+    };
+    Ok(Reply::bytes(kind.content_type(), stego))
+}
+
+// the payload framed for a `kind` carrier, and what to hide it with
+fn framed(kind: &Carrier, payload: Payload, opts: &RequestOptions) -> Result<(Vec<u8>, HideOptions), StegError> {
+    if matches!(kind, Carrier::Jpeg) && (opts.stride.is_some() || opts.key.is_some()) {
+        return Err("stride and key are for lsb, JPEGs get marker".into());
+    }
+    // marker seals its segments with the password instead of the frame, as the CLI's hide does
+    let segment_password = matches!(kind, Carrier::Jpeg).then(|| opts.password.clone()).flatten();
+    let frame = payload.encode(&FrameOptions {
+        compress: opts.compress,
+        password: opts.password.clone().filter(|_| segment_password.is_none()),
+        hmac_key: opts.hmac_key.clone(),
+        ..FrameOptions::default()
+    })?;
+    Ok((frame, HideOptions { lsb: lsb_options(opts), password: segment_password, ..HideOptions::default() }))
+}
+
+/// /hide/stream: the whole HTTP response to the form `body` brings, written to `out`. The carrier, the
+/// last part, is read a chunk at a time and its stego copy sent as it's made; a request refused before
+/// the first chunk goes out gets the error reply instead. The `Err` is for a response that was cut short.
+fn hide_streamed(body: impl Read, content_type: Option<&str>, max_upload: usize, max_stream: u64, out: impl Write) -> io::Result<()> {
+    let mut response = Chunked { out, content_type: "", started: false };
+    match stream_into(body, content_type, max_upload, max_stream, &mut response) {
+        Ok(()) => response.finish(),
+        Err(reply) if !response.started => write_reply(response.out, &reply),
+        Err(reply) => Err(io::Error::other(format!("cut short after the first chunk: {}", String::from_utf8_lossy(&reply.body)))),
+    }
+}
+
+fn stream_into(body: impl Read, content_type: Option<&str>, max_upload: usize, max_stream: u64, response: &mut Chunked<impl Write>) -> Result<(), Reply> {
+    let Some(boundary) = content_type.and_then(boundary) else {
+        return Err(Reply::bad_request("The body has to be multipart/form-data"));
+    };
+    let too_big = || Reply::error(413, format!("The parts before the carrier are limited to {} bytes (--max-upload)", max_upload), 5);
+    let mut form = FormReader::new(body, &boundary);
+    let (mut opts, mut payload, mut held) = (RequestOptions::default(), None, 0);
+    loop {
+        match form.next_part().map_err(Reply::bad_request)? {
+            None => return Err(Reply::bad_request("There's no carrier part")),
+            Some((name, _)) if name == "carrier" => break,
+            Some((name, filename)) => {
+                let mut data = Vec::new();
+                (&mut form).take((max_upload - held) as u64 + 1).read_to_end(&mut data).map_err(|e| Reply::bad_request(format!("Failed to read the request body: {}", e)))?;
+                held += data.len();
+                if held > max_upload {
+                    return Err(too_big());
+                }
+                match name.as_str() {
+                    "options" => opts = serde_json::from_slice(&data).map_err(|e| Reply::bad_request(format!("Bad options: {}", e)))?,
+                    "payload" => payload = Some(Payload { name: filename, data }),
+                    _ => {}
+                }
+            }
+        }
+    }
+    let Some(payload) = payload else {
+        return Err(Reply::bad_request("The payload part has to come before the carrier, which is read as it arrives"));
+    };
+
+    // enough of the carrier to tell what it is, then the rest as it comes
+    let mut head = Vec::new();
+    (&mut form).take(16).read_to_end(&mut head).map_err(|e| Reply::bad_request(format!("Failed to read the request body: {}", e)))?;
+    let kind = Carrier::sniff(&head).map_err(|e| Reply::from_steg(&e))?;
+    let over = || Reply::error(413, format!("Streamed carriers are limited to {} bytes (--max-stream)", max_stream), 5);
+    // a WAV says how long it is, so one that's too long is refused before any of it is marked
+    if let Carrier::Wav = kind && u32::from_le_bytes([head[4], head[5], head[6], head[7]]) as u64 + 8 > max_stream {
+        return Err(over());
+    }
+    let (frame, hide) = framed(&kind, payload, &opts).map_err(|e| Reply::from_steg(&e))?;
+    response.content_type = kind.content_type();
+
+    let mut carrier = Capped { inner: io::BufReader::with_capacity(FORM_CHUNK, io::Cursor::new(head).chain(form)), left: max_stream, over: false };
+    let mut out = BufWriter::with_capacity(FORM_CHUNK, &mut *response);
+    let hidden = match kind {
+        Carrier::Wav => wav::hide_stream_with(&mut carrier, &mut out, &frame, &hide).map(drop),
+        Carrier::Jpeg => marker::hide_stream_with(&mut carrier, &mut out, &frame, &hide),
+        Carrier::Picture(_) => Err(StegError::UnsupportedFormat { found: "a picture, which is decoded whole: send it to /hide".to_string() }),
+    };
+    match hidden.and_then(|()| out.flush().map_err(StegError::from)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            // what's still buffered is dropped, so an error before the first chunk can be the reply
+            drop(out.into_parts());
+            Err(if carrier.over { over() } else { Reply::from_steg(&e) })
+        }
+    }
+}
+
+/// The 200 response of /hide/stream, sent in chunks. Its head goes out with the first one, so until
+/// then the request can still be answered with an error.
+struct Chunked<W> {
+    out: W,
+    content_type: &'static str,
+    started: bool,
+}
+
+impl<W: Write> Chunked<W> {
+    fn start(&mut self) -> io::Result<()> {
+        if !self.started {
+            self.started = true;
+            write!(self.out, "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n", self.content_type)?;
+        }
+        Ok(())
+    }
+
+    // the last chunk, which tells the client it has all of it
+    fn finish(mut self) -> io::Result<()> {
+        self.start()?;
+        self.out.write_all(b"0\r\n\r\n")?;
+        self.out.flush()
+    }
+}
+
+impl<W: Write> Write for Chunked<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // an empty chunk would be the last one
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.start()?;
+        write!(self.out, "{:x}\r\n", buf.len())?;
+        self.out.write_all(buf)?;
+        self.out.write_all(b"\r\n")?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+// `reply` as a whole HTTP/1.1 response, for a connection taken over from tiny_http
+fn write_reply(mut out: impl Write, reply: &Reply) -> io::Result<()> {
+    let reason = match reply.status {
+        400 => "Bad Request",
+        413 => "Content Too Large",
+        422 => "Unprocessable Content",
+        500 => "Internal Server Error",
+        _ => "",
+    };
+    write!(out, "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", reply.status, reason, reply.content_type, reply.body.len())?;
+    out.write_all(&reply.body)?;
+    out.flush()
+}
+
+// `inner`, failing once more than `left` bytes have come out of it
+struct Capped<R> {
+    inner: R,
+    left: u64,
+    over: bool,
+}
+
+impl<R: Read> Read for Capped<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.left = self.left.checked_sub(n as u64).ok_or_else(|| {
+            self.over = true;
+            io::Error::other("over --max-stream")
+        })?;
+        Ok(n)
+    }
+}
+
+/// A multipart/form-data body read a part at a time as it arrives: `next_part` moves to the next part's
+/// headers, and reading gives that part's data up to its closing boundary. Only a chunk and a boundary's
+/// length of the body are held at once.
+struct FormReader<R> {
+    body: R,
+    // read from `body`, not handed on yet
+    buf: Vec<u8>,
+    // CRLF and the delimiter, which is what ends a part's data; the body gets a CRLF in front so the
+    // first delimiter looks the same
+    closing: Vec<u8>,
+    // the bytes of `body` dropped from `buf`, for where a malformed body goes wrong
+    taken: usize,
+    in_data: bool,
+    ended: bool,
+}
+
+const FORM_CHUNK: usize = 64 * 1024;
+const MAX_PART_HEAD: usize = 16 * 1024;
+
+impl<R: Read> FormReader<R> {
+    fn new(body: R, boundary: &str) -> Self {
+        // whatever comes before the first delimiter (a preamble, usually nothing) is read as data and dropped
+        FormReader { body, buf: b"\r\n".to_vec(), closing: format!("\r\n--{}", boundary).into_bytes(), taken: 0, in_data: true, ended: false }
+    }
+
+    // at least `want` bytes in `buf`, unless the body ends first
+    fn fill(&mut self, want: usize) -> io::Result<()> {
+        let mut chunk = [0; 8192];
+        while self.buf.len() < want && !self.ended {
+            match self.body.read(&mut chunk)? {
+                0 => self.ended = true,
+                n => self.buf.extend_from_slice(&chunk[..n]),
+            }
+        }
+        Ok(())
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.buf.drain(..n);
+        self.taken += n;
+    }
+
+    /// The name and filename of the next part, once the rest of this one is skipped; `None` past the last.
+    fn next_part(&mut self) -> Result<Option<(String, Option<String>)>, StegError> {
+        io::copy(self, &mut io::sink())?;
+        let malformed = |at: usize, what: &str| StegError::Malformed { at, what: what.to_string() };
+        let delimiter = self.closing.len();
+        self.fill(delimiter + 2)?;
+        match self.buf.get(delimiter..delimiter + 2) {
+            Some(b"--") => return Ok(None),
+            Some(b"\r\n") => self.consume(delimiter + 2),
+            _ => return Err(malformed(self.taken, "A multipart boundary isn't followed by a line break")),
+        }
+        let head_end = loop {
+            if let Some(at) = find(&self.buf, b"\r\n\r\n") {
+                break at;
+            }
+            if self.ended || self.buf.len() > MAX_PART_HEAD {
+                return Err(malformed(self.taken, "A part's headers don't end"));
+            }
+            self.fill(self.buf.len() + 1)?;
+        };
+        let head = std::str::from_utf8(&self.buf[..head_end]).map_err(|_| malformed(self.taken, "A part's headers aren't UTF-8"))?;
+        let (name, filename) = disposition(head).ok_or_else(|| malformed(self.taken, "A part has no Content-Disposition name"))?;
+        self.consume(head_end + 4);
+        self.in_data = true;
+        Ok(Some((name, filename)))
+    }
+}
+
+impl<R: Read> Read for FormReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if !self.in_data || out.is_empty() {
+            return Ok(0);
+        }
+        // enough past what's handed on to see a delimiter that arrived split across reads, and only
+        // looked for where one could start in what's handed on
+        self.fill(self.closing.len() + out.len().min(FORM_CHUNK))?;
+        let window = self.buf.len().min(out.len() + self.closing.len() - 1);
+        let n = match find(&self.buf[..window], &self.closing) {
+            Some(0) => {
+                self.in_data = false;
+                return Ok(0);
+            }
+            Some(at) => at,
+            None if self.ended && window == self.buf.len() => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "The body ends inside a part")),
+            None => (self.buf.len() + 1 - self.closing.len()).min(out.len()),
+        };
+        out[..n].copy_from_slice(&self.buf[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+fn extract_from(carrier: &[u8], opts: &RequestOptions) -> Result<Reply, StegError> {
+    let kind = Carrier::sniff(carrier)?;
+    let mut decode = DecodeOptions { password: opts.password.clone(), hmac_key: opts.hmac_key.clone() };
+    let mut find = FindOptions { lsb: lsb_options(opts), ..FindOptions::default() };
+    if let Carrier::Jpeg = kind {
+        find.password = decode.password.take();
+    }
+    let found = match kind {
+        Carrier::Wav => wav::find_bytes_with(carrier, &find)?,
+        Carrier::Jpeg => marker::find_bytes_with(carrier, &find)?,
+        Carrier::Picture(_) => lsb::find_bytes_with(carrier, &find)?,
+    };
+    let payload = Payload::decode(&found, &decode)?;
+    Ok(Reply { filename: payload.name, ..Reply::bytes("application/octet-stream", payload.data) })
+}
+
+/// One part of a multipart/form-data body, borrowing its bytes from the body.
+struct Part<'a> {
+    name: String,
+    filename: Option<String>,
+    data: &'a [u8],
+}
+
+/// The boundary of a multipart/form-data Content-Type.
+fn boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';').map(str::trim);
+    if !params.next()?.eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    params.filter_map(|p| p.split_once('=')).find(|(k, _)| k.trim().eq_ignore_ascii_case("boundary")).map(|(_, v)| v.trim().trim_matches('"').to_string()).filter(|b| !b.is_empty())
+}
+
+/// The parts of `body` (RFC 7578), without copying their contents.
+fn multipart<'a>(body: &'a [u8], boundary: &str) -> Result<Vec<Part<'a>>, StegError> {
+    // where in the body `rest` starts, for the error
+    let malformed = |rest: &[u8], what: &str| StegError::Malformed { at: body.len() - rest.len(), what: what.to_string() };
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let start = find(body, &delimiter).ok_or_else(|| malformed(body, "The body has no parts"))?;
+    let mut rest = &body[start + delimiter.len()..];
+    let mut parts = Vec::new();
+    let closing = [b"\r\n".as_slice(), &delimiter].concat();
+    loop {
+        if rest.starts_with(b"--") {
+            return Ok(parts);
+        }
+        rest = rest.strip_prefix(b"\r\n").ok_or_else(|| malformed(rest, "A multipart boundary isn't followed by a line break"))?;
+        let head_end = find(rest, b"\r\n\r\n").ok_or_else(|| malformed(rest, "A part's headers don't end"))?;
+        let head = std::str::from_utf8(&rest[..head_end]).map_err(|_| malformed(rest, "A part's headers aren't UTF-8"))?;
+        let data_start = head_end + 4;
+        let data_len = find(&rest[data_start..], &closing).ok_or_else(|| malformed(&rest[data_start..], "The body ends inside a part"))?;
+        let (name, filename) = disposition(head).ok_or_else(|| malformed(rest, "A part has no Content-Disposition name"))?;
+        parts.push(Part { name, filename, data: &rest[data_start..data_start + data_len] });
+        rest = &rest[data_start + data_len + closing.len()..];
+    }
+}
+
+/// The name and filename of a part's Content-Disposition header.
+fn disposition(head: &str) -> Option<(String, Option<String>)> {
+    let value = head.split("\r\n").find_map(|line| {
+        let (field, value) = line.split_once(':')?;
+        field.trim().eq_ignore_ascii_case("content-disposition").then_some(value)
+    })?;
+    let param = |key: &str| {
+        value.split(';').filter_map(|p| p.split_once('=')).find(|(k, _)| k.trim().eq_ignore_ascii_case(key)).map(|(_, v)| v.trim().trim_matches('"').to_string())
+    };
+    Some((param("name")?, param("filename").filter(|f| !f.is_empty())))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const BOUNDARY: &str = "----steg7MA4YWxkTrZu0gW";
+
+    fn form(parts: &[(&str, Option<&str>, &[u8])]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (name, filename, data) in parts {
+            body.extend_from_slice(format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"", BOUNDARY, name).as_bytes());
+            if let Some(f) = filename {
+                body.extend_from_slice(format!("; filename=\"{}\"", f).as_bytes());
+            }
+            body.extend_from_slice(b"\r\nContent-Type: application/octet-stream\r\n\r\n");
+            body.extend_from_slice(data);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{}--\r\n", BOUNDARY).as_bytes());
+        body
+    }
+
+    fn post(path: &str, parts: &[(&str, Option<&str>, &[u8])]) -> Reply {
+        handle("POST", path, Some(&format!("multipart/form-data; boundary={}", BOUNDARY)), &form(parts))
+    }
+
+    fn picture(w: u32, h: u32, format: image::ImageFormat) -> Vec<u8> {
+        let mut out = Cursor::new(Vec::new());
+        image::RgbImage::from_fn(w, h, |x, y| image::Rgb([(x * 4) as u8, (y * 4) as u8, 128])).write_to(&mut out, format).unwrap();
+        out.into_inner()
+    }
+
+    fn png(w: u32, h: u32) -> Vec<u8> {
+        picture(w, h, image::ImageFormat::Png)
+    }
+
+    fn wav(samples: usize) -> Vec<u8> {
+        let spec = hound::WavSpec { channels: 1, sample_rate: 8000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let mut out = Cursor::new(Vec::new());
+        let mut w = hound::WavWriter::new(&mut out, spec).unwrap();
+        for i in 0..samples {
+            w.write_sample((i % 300) as i16 * 50).unwrap();
+        }
+        w.finalize().unwrap();
+        out.into_inner()
+    }
+
+    fn error_code(reply: &Reply) -> i64 {
+        let v: serde_json::Value = serde_json::from_slice(&reply.body).unwrap();
+        assert!(v["error"].is_string());
+        v["code"].as_i64().unwrap()
+    }
+
+    #[test]
+    fn hide_then_extract_round_trips_pictures_wavs_and_jpegs() {
+        let jpeg = picture(64, 64, image::ImageFormat::Jpeg);
+        for (carrier, content_type) in [(png(64, 64), "image/png"), (wav(20_000), "audio/wav"), (jpeg, "image/jpeg")] {
+            let options = br#"{"password": "pw", "compress": true}"#;
+            let hidden = post("/hide", &[("carrier", Some("c"), &carrier), ("payload", Some("note.txt"), b"meet at noon"), ("options", None, options)]);
+            assert_eq!((hidden.status, hidden.content_type.as_str()), (200, content_type));
+
+            let found = post("/extract", &[("options", None, options), ("carrier", None, &hidden.body)]);
+            assert_eq!(found.status, 200);
+            assert_eq!((found.body.as_slice(), found.filename.as_deref()), (b"meet at noon".as_slice(), Some("note.txt")));
+            let wrong = post("/extract", &[("carrier", None, &hidden.body), ("options", None, br#"{"password": "nope"}"#)]);
+            assert_eq!(wrong.status, 422);
+        }
+    }
+
+    #[test]
+    fn nothing_to_extract_is_a_json_404() {
+        let reply = post("/extract", &[("carrier", None, &png(32, 32))]);
+        assert_eq!((reply.status, reply.content_type.as_str()), (404, "application/json"));
+        assert_eq!(error_code(&reply), 6);
+    }
+
+    #[test]
+    fn requests_that_arent_what_the_endpoints_take_are_refused() {
+        let carrier = png(32, 32);
+        assert_eq!(post("/hide", &[("carrier", None, &carrier)]).status, 400);
+        assert_eq!(post("/hide", &[("payload", None, b"x")]).status, 400);
+        assert_eq!(post("/hide", &[("carrier", None, &carrier), ("payload", None, b"x"), ("options", None, br#"{"strid": 2}"#)]).status, 400);
+        assert_eq!(post("/hide", &[("carrier", None, b"plain text"), ("payload", None, b"x")]).status, 422);
+        assert_eq!(post("/hide", &[("carrier", None, &png(2, 2)), ("payload", None, b"too much for four pixels")]).status, 422);
+        assert_eq!(post("/elsewhere", &[]).status, 404);
+        assert_eq!(handle("GET", "/hide", None, b"").status, 405);
+        assert_eq!(handle("POST", "/hide", Some("application/json"), b"{}").status, 400);
+        let cut = &form(&[("carrier", None, &carrier)])[..100];
+        assert_eq!(handle("POST", "/extract", Some(&format!("multipart/form-data; boundary={}", BOUNDARY)), cut).status, 400);
+    }
+
+    #[test]
+    fn uploads_are_capped_whether_or_not_their_length_is_declared() {
+        assert_eq!(read_capped(Cursor::new(vec![0; 100]), Some(100), 100).unwrap().len(), 100);
+        assert_eq!(read_capped(Cursor::new(vec![0; 101]), None, 100).unwrap_err().status, 413);
+        // refused on the declared length alone, nothing read
+        let reply = read_capped(std::io::repeat(0), Some(1 << 40), 100).unwrap_err();
+        assert_eq!((reply.status, error_code(&reply)), (413, 5));
+    }
+
+    #[test]
+    fn multipart_parts_borrow_the_body() {
+        let body = form(&[("a", Some("x.bin"), b"one\r\ntwo"), ("b", None, b"")]);
+        let parts = multipart(&body, BOUNDARY).unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!((parts[0].name.as_str(), parts[0].filename.as_deref(), parts[0].data), ("a", Some("x.bin"), b"one\r\ntwo".as_slice()));
+        assert_eq!((parts[1].name.as_str(), parts[1].data), ("b", b"".as_slice()));
+        assert!(matches!(multipart(&body[..body.len() - 10], BOUNDARY), Err(StegError::Malformed { .. })));
+        assert_eq!(boundary("multipart/form-data; boundary=\"abc\""), Some("abc".to_string()));
+        assert_eq!(boundary("text/plain; boundary=abc"), None);
+    }
+
+    // a body that arrives a few bytes at a time, so boundaries land split across reads
+    struct Trickle(Cursor<Vec<u8>>, usize);
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.1 = self.1 % 7 + 1;
+            let n = buf.len().min(self.1);
+            self.0.read(&mut buf[..n])
+        }
+    }
+
+    // the HTTP response /hide/stream writes, and whether it came to an end
+    fn stream_response(parts: &[(&str, Option<&str>, &[u8])], max_upload: usize, max_stream: u64) -> (Vec<u8>, bool) {
+        let body = Trickle(Cursor::new(form(parts)), 0);
+        let mut out = Vec::new();
+        let ended = hide_streamed(body, Some(&format!("multipart/form-data; boundary={}", BOUNDARY)), max_upload, max_stream, &mut out).is_ok();
+        (out, ended)
+    }
+
+    // the stego carrier of a 200 /hide/stream response, its chunks put together, or the error reply
+    fn stream(parts: &[(&str, Option<&str>, &[u8])], max_upload: usize, max_stream: u64) -> Result<Vec<u8>, Reply> {
+        let (out, ended) = stream_response(parts, max_upload, max_stream);
+        assert!(ended);
+        let head_end = find(&out, b"\r\n\r\n").unwrap();
+        let head = std::str::from_utf8(&out[..head_end]).unwrap();
+        let mut rest = &out[head_end + 4..];
+        let status = head[9..12].parse().unwrap();
+        if status != 200 {
+            assert!(head.contains(&format!("Content-Length: {}", rest.len())));
+            return Err(Reply { status, content_type: "application/json".to_string(), filename: None, body: rest.to_vec() });
+        }
+        assert!(head.contains("Transfer-Encoding: chunked"));
+        let mut data = Vec::new();
+        loop {
+            let line = find(rest, b"\r\n").unwrap();
+            let n = usize::from_str_radix(std::str::from_utf8(&rest[..line]).unwrap(), 16).unwrap();
+            rest = &rest[line + 2..];
+            if n == 0 {
+                assert_eq!(rest, b"\r\n");
+                return Ok(data);
+            }
+            data.extend_from_slice(&rest[..n]);
+            assert_eq!(&rest[n..n + 2], b"\r\n");
+            rest = &rest[n + 2..];
+        }
+    }
+
+    #[test]
+    fn streamed_hides_match_the_buffered_ones() {
+        let jpeg = picture(64, 64, image::ImageFormat::Jpeg);
+        for carrier in [wav(20_000), jpeg] {
+            let options = br#"{"compress": true, "hmac-key": "k"}"#;
+            let parts = [("options", None, options.as_slice()), ("payload", Some("note.txt"), b"meet at noon\r\n--not a boundary"), ("carrier", None, &carrier)];
+            let streamed = stream(&parts, 1024, 1 << 20).unwrap();
+            assert_eq!(streamed, post("/hide", &parts).body);
+            let found = post("/extract", &[("options", None, options), ("carrier", None, &streamed)]);
+            assert_eq!(found.body, b"meet at noon\r\n--not a boundary");
+        }
+    }
+
+    #[test]
+    fn streamed_hides_refuse_what_they_cant_stream() {
+        let carrier = wav(20_000);
+        let status = |r: Result<Vec<u8>, Reply>| r.unwrap_err().status;
+        // the payload has to be in before the carrier starts
+        assert_eq!(status(stream(&[("carrier", None, &carrier), ("payload", None, b"x")], 1024, 1 << 20)), 400);
+        assert_eq!(status(stream(&[("payload", None, b"x")], 1024, 1 << 20)), 400);
+        assert_eq!(status(stream(&[("payload", None, b"x"), ("carrier", None, &png(32, 32))], 1024, 1 << 20)), 422);
+        assert_eq!(status(stream(&[("payload", None, &[0; 2000]), ("carrier", None, &carrier)], 1024, 1 << 20)), 413);
+        let reply = stream(&[("payload", None, b"x"), ("carrier", None, &carrier)], 1024, 10_000).unwrap_err();
+        assert_eq!((reply.status, error_code(&reply)), (413, 5));
+        assert!(stream(&[("payload", None, b"x"), ("carrier", None, &carrier)], 1024, carrier.len() as u64).is_ok());
+    }
+
+    #[test]
+    fn streamed_hides_send_chunks_as_they_go() {
+        // a JPEG whose marked copy is several chunks long
+        let mut noise = 7u32;
+        let jpeg = image::RgbImage::from_fn(512, 512, |_, _| {
+            noise = noise.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            image::Rgb([(noise >> 8) as u8, (noise >> 16) as u8, (noise >> 24) as u8])
+        });
+        let mut carrier = Cursor::new(Vec::new());
+        jpeg.write_to(&mut carrier, image::ImageFormat::Jpeg).unwrap();
+        let carrier = carrier.into_inner();
+        assert!(carrier.len() > 3 * FORM_CHUNK);
+        let parts = [("payload", None, b"x".as_slice()), ("carrier", None, &carrier)];
+        assert_eq!(stream(&parts, 1024, 1 << 30).unwrap(), post("/hide", &parts).body);
+
+        // going over --max-stream once it's under way can only cut it short, without the last chunk
+        let (out, ended) = stream_response(&parts, 1024, carrier.len() as u64 - 1);
+        assert!(!ended && out.starts_with(b"HTTP/1.1 200 OK\r\n") && !out.ends_with(b"0\r\n\r\n"));
+    }
+
+    #[test]
+    fn form_reader_hands_on_each_part_up_to_its_boundary() {
+        let mut body = b"a preamble to skip\r\n".to_vec();
+        body.extend(form(&[("a", Some("x.bin"), b"one\r\n--two"), ("b", None, b""), ("c", None, &[7; 100_000])]));
+        let mut reader = FormReader::new(Trickle(Cursor::new(body.clone()), 0), BOUNDARY);
+        let read = |reader: &mut FormReader<Trickle>| {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).unwrap();
+            data
+        };
+        assert_eq!(reader.next_part().unwrap(), Some(("a".to_string(), Some("x.bin".to_string()))));
+        assert_eq!(read(&mut reader), b"one\r\n--two");
+        assert_eq!(reader.next_part().unwrap(), Some(("b".to_string(), None)));
+        // c's data is skipped, not read
+        assert_eq!(reader.next_part().unwrap(), Some(("c".to_string(), None)));
+        assert_eq!(reader.next_part().unwrap(), None);
+
+        let mut cut = FormReader::new(Trickle(Cursor::new(body[..body.len() - 50].to_vec()), 0), BOUNDARY);
+        cut.next_part().unwrap();
+        assert_eq!(read(&mut cut), b"one\r\n--two");
+        cut.next_part().unwrap();
+        cut.next_part().unwrap();
+        assert!(cut.next_part().is_err());
+    }
+}
@@ -0,0 +1,75 @@
+//! The public API: the algorithms other projects can depend on, under the names they keep across
+//! releases. The examples write their carriers into a temporary directory. Besides paths, the
+//! algorithms take carriers as byte slices (`hide_bytes_with`) and as streams (`hide_stream_with`).
+//!
+//! Everything past the carrier and the payload goes in a [`HideOptions`] or [`FindOptions`], which
+//! every `_with` function takes, so a new option doesn't change any signature. The functions that
+//! took their options one by one before these existed are deprecated and go in the next release.
+//!
+//! Each filetype is a Cargo feature, all on by default: `picture` (the `image` and `png` crates),
+//! `audio` (`hound`) and `jpeg-marker` (no dependencies). Turn off the defaults and pick the ones you
+//! use to leave the others' dependencies out.
+
+pub use crate::steg_algorithms::error::StegError;
+pub use crate::steg_algorithms::options::{FindOptions, HideOptions};
+
+/// Algorithms for pictures, with the `picture` feature.
+#[cfg(feature = "picture")]
+pub mod picture {
+    /// Least-significant-bit embedding in the RGB channels of lossless pictures (PNG, BMP, ...). The
+    /// payload gets a 32-bit length prefix and takes one bit per channel, so a W×H picture holds about
+    /// `W * H * 3 / 8` bytes (`W * H / 8` for a grayscale one, which stays grayscale), and exactly what
+    /// `capacity` says for given options. `LsbOptions` takes up to 4 bits a channel and alpha as a
+    /// fourth. 16-bit PNGs and TIFFs keep their depth, the bits going into the low end of each 16-bit
+    /// sample.
+    ///
+    /// `find` gives the payload as text and `find_bytes` as bytes, for a carrier at a path;
+    /// `find_bytes_with` reads one already in memory.
+    pub mod lsb {
+        pub use crate::steg_algorithms::picture::general::lsb::{
+            capacity, capacity_bytes, find, find_bytes, find_bytes_with, find_stream_with, find_with, hide, hide_bytes_with, hide_stream_with, hide_with, Embedding, LsbOptions,
+            Region, Traversal,
+        };
+        #[allow(deprecated)]
+        pub use crate::steg_algorithms::picture::general::lsb::{capacity_with, find_stream, hide_bytes, hide_stream};
+    }
+}
+
+/// Algorithms for audio, with the `audio` feature.
+#[cfg(feature = "audio")]
+pub mod audio {
+    /// Algorithms for WAV files.
+    pub mod wav {
+        /// Least-significant-bit embedding in 16-bit PCM samples, one bit per sample.
+        pub mod lsb {
+            pub use crate::steg_algorithms::audio::wav::lsb::{
+                find_bytes_with, find_stream_with, find_wav, find_wav_with, hide_bytes_with, hide_stream_with, hide_wav, hide_wav_with, TimeRange,
+            };
+            #[allow(deprecated)]
+            pub use crate::steg_algorithms::audio::wav::lsb::{find_bytes, find_stream, hide_bytes, hide_stream};
+        }
+    }
+}
+
+/// Algorithms for JPEGs, with the `jpeg-marker` feature.
+#[cfg(feature = "jpeg-marker")]
+pub mod jpeg {
+    /// The payload in APPn segments before the scan, split over as many as it takes. The pixels are
+    /// left alone, so it survives anything that keeps the header and nothing that re-encodes.
+    pub mod marker {
+        pub use crate::steg_algorithms::picture::jpg::marker_hijacking::{
+            extract_payload_from_bytes, find, find_bytes_with, find_stream_with, find_with, hide, hide_bytes_with, hide_stream_with, hide_with,
+            insert_or_replace_appn, MarkerOptions,
+        };
+        #[allow(deprecated)]
+        pub use crate::steg_algorithms::picture::jpg::marker_hijacking::{find_stream, hide_sealed_stream, hide_stream};
+    }
+}
+
+/// The framing the CLI wraps every payload in before an algorithm embeds it: a magic number, flags,
+/// the original filename and the data, optionally compressed, encrypted, authenticated or protected
+/// with error correction. Use it to read payloads the CLI hid, or to hide ones it can read.
+pub mod payload {
+    pub use crate::steg_algorithms::crypto::Cipher;
+    pub use crate::steg_algorithms::payload::{Auth, DecodeOptions, FrameOptions, Meta, Payload, Table};
+}
@@ -0,0 +1,325 @@
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use crate::steg_algorithms::atomic;
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::picture::general::lsb::{self, Order};
+use crate::steg_algorithms::redundancy;
+
+// FITS (https://fits.gsfc.nasa.gov/fits_standard.html): a sequence of HDUs, each a header of 80-character
+// ASCII cards ending with END, then big-endian data, both padded to 2880-byte blocks.
+//
+// Two places for a payload:
+//   lsb    the lowest mantissa bit of every finite sample in the floating-point images (BITPIX -32/-64,
+//          the primary HDU and IMAGE extensions in file order), with the bitstream and options `lsb`
+//          uses. NaNs and infinities are skipped, flipping their low bit could turn one into the other.
+//   cards  COMMENT cards at the end of the primary header, "RSTEGO " and then hex, holding a 4-byte
+//          length and the bytes. Marked HISTORY cards are read as well.
+// Everything else, other cards and data alike, stays byte for byte the same.
+
+const BLOCK: usize = 2880;
+const CARD: usize = 80;
+const MARKER: &str = "RSTEGO ";
+// 80 columns minus the 8-column keyword and the marker, rounded down to whole bytes
+const HEX_PER_CARD: usize = 64;
+
+struct Hdu {
+    /// The header cards, END included, without the padding.
+    cards: Range<usize>,
+    /// Where the data starts (after the header padding), and its unpadded length.
+    data_start: usize,
+    data_len: usize,
+    bitpix: i64,
+    image: bool,
+}
+
+fn keyword(card: &[u8]) -> &str {
+    std::str::from_utf8(&card[..8]).unwrap_or_default().trim_end()
+}
+
+// the value of a `KEYWORD = value / comment` card, quotes removed from strings
+fn value(card: &[u8]) -> Option<&str> {
+    if &card[8..10] != b"= " {
+        return None;
+    }
+    let text = std::str::from_utf8(&card[10..]).ok()?.trim_start();
+    match text.strip_prefix('\'') {
+        Some(quoted) => quoted.split('\'').next().map(str::trim_end),
+        None => text.split('/').next().map(str::trim),
+    }
+}
+
+// parse the HDU whose header starts at `pos`
+fn hdu_at(buf: &[u8], pos: usize) -> Result<Hdu, StegError> {
+    let (mut bitpix, mut naxis, mut pcount, mut gcount) = (None, Vec::new(), 0i64, 1i64);
+    let (mut xtension, mut groups) = (None, false);
+    let mut at = pos;
+    loop {
+        let card = buf.get(at..at + CARD).ok_or_else(|| format!("Header at offset {} has no END card", pos))?;
+        at += CARD;
+        let int = || value(card).and_then(|v| v.parse::<i64>().ok());
+        match keyword(card) {
+            "END" => break,
+            "BITPIX" => bitpix = int(),
+            "NAXIS" => naxis.resize(int().unwrap_or(0).clamp(0, 999) as usize, 0),
+            "PCOUNT" => pcount = int().unwrap_or(0),
+            "GCOUNT" => gcount = int().unwrap_or(1),
+            "XTENSION" => xtension = value(card).map(str::to_string),
+            "GROUPS" => groups = value(card) == Some("T"),
+            k if k.starts_with("NAXIS") => {
+                if let Some(n) = k[5..].parse::<usize>().ok().filter(|&n| n >= 1 && n <= naxis.len()) {
+                    naxis[n - 1] = int().unwrap_or(0);
+                }
+            }
+            _ => {}
+        }
+    }
+    let bitpix = bitpix.filter(|b| matches!(b, 8 | 16 | 32 | 64 | -32 | -64)).ok_or("Header has no valid BITPIX")?;
+    // random groups leave NAXIS1 at 0 and don't count it
+    let samples = if naxis.is_empty() {
+        Some(0)
+    } else {
+        naxis.iter().skip(groups as usize).try_fold(1i64, |n, &axis| n.checked_mul(axis))
+            .and_then(|axes| axes.checked_add(pcount))
+            .and_then(|n| n.checked_mul(gcount))
+    };
+    let data_len = samples.and_then(|n| n.checked_mul(bitpix.abs() / 8)).and_then(|n| usize::try_from(n).ok())
+        .ok_or("Header has invalid axis sizes")?;
+    let data_start = pos + (at - pos).div_ceil(BLOCK) * BLOCK;
+    if data_start.checked_add(data_len).is_none_or(|end| end > buf.len()) {
+        return Err(format!("Data of the HDU at offset {} is truncated", pos).into());
+    }
+    let image = match (pos, xtension.as_deref()) {
+        (0, _) => !groups,
+        (_, Some("IMAGE")) => true,
+        _ => false,
+    };
+    Ok(Hdu { cards: pos..at, data_start, data_len, bitpix, image })
+}
+
+fn parse(buf: &[u8]) -> Result<Vec<Hdu>, StegError> {
+    if !buf.starts_with(b"SIMPLE  =") {
+        return Err("Not a FITS file (doesn't start with SIMPLE)".into());
+    }
+    let mut hdus = vec![hdu_at(buf, 0)?];
+    loop {
+        let last = &hdus[hdus.len() - 1];
+        let next = last.data_start + last.data_len.div_ceil(BLOCK) * BLOCK;
+        // anything after the last HDU that isn't an extension is left alone
+        if !buf.get(next..).is_some_and(|rest| rest.starts_with(b"XTENSION=")) {
+            break;
+        }
+        hdus.push(hdu_at(buf, next)?);
+    }
+    Ok(hdus)
+}
+
+fn read(path: &Path) -> Result<(Vec<u8>, Vec<Hdu>), StegError> {
+    if !path.exists() {
+        return Err(StegError::not_found(path));
+    }
+    let buf = fs::read(path)?;
+    let hdus = parse(&buf)?;
+    Ok((buf, hdus))
+}
+
+/// Offsets of the byte holding the lowest mantissa bit of every finite floating-point image sample.
+fn mantissa_bytes(buf: &[u8], hdus: &[Hdu]) -> Result<Vec<usize>, StegError> {
+    let mut slots = Vec::new();
+    for hdu in hdus.iter().filter(|h| h.image && h.bitpix < 0) {
+        let width = (hdu.bitpix.unsigned_abs() / 8) as usize;
+        for (i, sample) in buf[hdu.data_start..hdu.data_start + hdu.data_len].chunks_exact(width).enumerate() {
+            // all-ones exponent: NaN or infinity
+            let finite = match width {
+                4 => (u32::from_be_bytes(sample.try_into().unwrap()) >> 23) & 0xFF != 0xFF,
+                _ => (u64::from_be_bytes(sample.try_into().unwrap()) >> 52) & 0x7FF != 0x7FF,
+            };
+            if finite {
+                slots.push(hdu.data_start + (i + 1) * width - 1);
+            }
+        }
+    }
+    if slots.is_empty() {
+        return Err("No floating-point image (BITPIX -32 or -64) with finite samples in this file".into());
+    }
+    Ok(slots)
+}
+
+/// How many bytes `hide_lsb` can embed into the file at `path` with the given stride.
+pub fn capacity(path: &Path, stride: usize) -> Result<usize, StegError> {
+    if stride == 0 {
+        return Err("Stride must be at least 1".into());
+    }
+    let (buf, hdus) = read(path)?;
+    Ok((mantissa_bytes(&buf, &hdus)?.len().div_ceil(stride) / 8).saturating_sub(4))
+}
+
+/// Hide `msg` in the lowest mantissa bits of the FITS file at `path` and write it to `out_path`. `key`,
+/// `stride` and `copies` work as in `raw::hide`.
+pub fn hide_lsb(path: &Path, msg: impl AsRef<[u8]>, out_path: &Path, stride: usize, key: Option<&str>, copies: usize) -> Result<(), StegError> {
+    if stride == 0 {
+        return Err("Stride must be at least 1".into());
+    }
+    let (mut buf, hdus) = read(path)?;
+    let slots = mantissa_bytes(&buf, &hdus)?;
+    let order = key.map_or(Order::Strided(stride), Order::Keyed);
+    let bits = redundancy::bitstream(msg.as_ref(), copies)?;
+    let capacity_bits = order.usable(slots.len());
+    if bits.len() > capacity_bits {
+        return Err(StegError::CapacityExceeded { needed: bits.len().div_ceil(8), available: capacity_bits / 8 });
+    }
+    for (slot, &bit) in order.slots(slots.len()).zip(&bits) {
+        let at = slots[slot];
+        buf[at] = (buf[at] & !1) | bit;
+    }
+    atomic::write(out_path, buf)
+}
+
+/// Extract a payload written by `hide_lsb`. Without `key` and `stride` the stride is probed like
+/// `lsb::find_payload_sparse` does.
+pub fn find_lsb(path: &Path, stride: Option<usize>, key: Option<&str>) -> Result<Vec<u8>, StegError> {
+    let (buf, hdus) = read(path)?;
+    let bits: Vec<u8> = mantissa_bytes(&buf, &hdus)?.iter().map(|&at| buf[at] & 1).collect();
+    match key {
+        Some(k) => lsb::extract_keyed(&bits, k),
+        None => lsb::extract_sparse(&bits, stride),
+    }
+}
+
+fn is_ours(card: &[u8]) -> bool {
+    matches!(keyword(card), "COMMENT" | "HISTORY") && card[8..].starts_with(MARKER.as_bytes())
+}
+
+/// Largest payload `hide_cards` takes, set by its 4-byte length.
+pub fn cards_capacity() -> usize {
+    u32::MAX as usize
+}
+
+/// Hide `msg` in COMMENT cards of the primary header of the FITS file at `path` and write it to
+/// `out_path`. Cards from an earlier run are replaced.
+pub fn hide_cards(path: &Path, msg: impl AsRef<[u8]>, out_path: &Path) -> Result<(), StegError> {
+    let msg = msg.as_ref();
+    if msg.len() > cards_capacity() {
+        return Err(StegError::CapacityExceeded { needed: msg.len(), available: cards_capacity() });
+    }
+    let (buf, hdus) = read(path)?;
+    let primary = &hdus[0];
+    let mut hex: String = (msg.len() as u32).to_be_bytes().iter().chain(msg).map(|b| format!("{:02X}", b)).collect();
+    let mut header: Vec<u8> = buf[primary.cards.clone()].chunks(CARD).filter(|c| !is_ours(c)).flatten().copied().collect();
+    let end = header.split_off(header.len() - CARD);
+    while !hex.is_empty() {
+        let rest = hex.split_off(hex.len().min(HEX_PER_CARD));
+        header.extend(format!("{:<8}{}{:<65}", "COMMENT", MARKER, hex).bytes());
+        hex = rest;
+    }
+    header.extend(end);
+    header.resize(header.len().div_ceil(BLOCK) * BLOCK, b' ');
+    header.extend_from_slice(&buf[primary.data_start..]);
+    atomic::write(out_path, header)
+}
+
+/// Extract a payload written by `hide_cards`.
+pub fn find_cards(path: &Path) -> Result<Vec<u8>, StegError> {
+    let (buf, hdus) = read(path)?;
+    let hex: String = buf[hdus[0].cards.clone()]
+        .chunks(CARD)
+        .filter(|c| is_ours(c))
+        .map(|c| String::from_utf8_lossy(&c[8 + MARKER.len()..]).trim_end().to_string())
+        .collect();
+    if hex.is_empty() {
+        return Err(format!("No {}cards in the primary header", MARKER).into());
+    }
+    let bytes = (0..hex.len() / 2)
+        .map(|i| hex.get(i * 2..i * 2 + 2).and_then(|h| u8::from_str_radix(h, 16).ok()))
+        .collect::<Option<Vec<u8>>>()
+        .filter(|_| hex.len().is_multiple_of(2))
+        .ok_or("Payload cards hold something other than hex")?;
+    let len = bytes.get(..4).map(|b| u32::from_be_bytes(b.try_into().unwrap()) as usize).ok_or("Payload cards are too short")?;
+    bytes.get(4..4 + len).map(<[u8]>::to_vec).ok_or_else(|| "Payload cards are shorter than their length header".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn header(cards: &[String]) -> Vec<u8> {
+        let mut h: Vec<u8> = cards.iter().chain([&"END".to_string()]).flat_map(|c| format!("{:<80}", c).into_bytes()).collect();
+        h.resize(h.len().div_ceil(BLOCK) * BLOCK, b' ');
+        h
+    }
+
+    fn padded(mut data: Vec<u8>) -> Vec<u8> {
+        data.resize(data.len().div_ceil(BLOCK) * BLOCK, 0);
+        data
+    }
+
+    // a 40x30 float image with a few NaNs and infinities, then a 12x10 double IMAGE extension and a
+    // binary table that has to be left alone
+    fn fits() -> Vec<u8> {
+        let card = |k: &str, v: &str| format!("{:<8}= {:>20}", k, v);
+        let mut buf = header(&[card("SIMPLE", "T"), card("BITPIX", "-32"), card("NAXIS", "2"), card("NAXIS1", "40"),
+            card("NAXIS2", "30"), card("EXTEND", "T"), "COMMENT   observed with a very real telescope".to_string()]);
+        buf.extend(padded((0..1200u32).flat_map(|i| match i % 97 {
+            0 => f32::NAN,
+            1 => f32::INFINITY,
+            _ => (i as f32 * 0.37).sin() * 1000.0,
+        }.to_be_bytes()).collect()));
+        buf.extend(header(&[card("XTENSION", "'IMAGE   '"), card("BITPIX", "-64"), card("NAXIS", "2"), card("NAXIS1", "12"),
+            card("NAXIS2", "10"), card("PCOUNT", "0"), card("GCOUNT", "1")]));
+        buf.extend(padded((0..120u32).flat_map(|i| (i as f64 / 7.0).to_be_bytes()).collect()));
+        buf.extend(header(&[card("XTENSION", "'BINTABLE'"), card("BITPIX", "8"), card("NAXIS", "2"), card("NAXIS1", "4"),
+            card("NAXIS2", "5"), card("PCOUNT", "0"), card("GCOUNT", "1"), card("TFIELDS", "1")]));
+        buf.extend(padded(vec![0xEE; 20]));
+        buf
+    }
+
+    #[test]
+    fn mantissa_bits_skip_non_finite_samples() {
+        let dir = tempdir().unwrap();
+        let (cover, stego) = (dir.path().join("c.fits"), dir.path().join("s.fits"));
+        let orig = fits();
+        fs::write(&cover, &orig).unwrap();
+        // 1200 floats less 13 NaNs and 13 infinities, plus 120 doubles
+        assert_eq!(capacity(&cover, 1).unwrap(), (1174 + 120) / 8 - 4);
+
+        let msg: Vec<u8> = (0..150u8).collect();
+        hide_lsb(&cover, &msg, &stego, 1, None, 1).unwrap();
+        assert_eq!(find_lsb(&stego, Some(1), None).unwrap(), msg);
+        hide_lsb(&cover, b"keyed", &stego, 1, Some("k"), 3).unwrap();
+        assert_eq!(find_lsb(&stego, None, Some("k")).unwrap(), b"keyed");
+
+        let out = fs::read(&stego).unwrap();
+        let (a, b) = (parse(&orig).unwrap(), parse(&out).unwrap());
+        assert_eq!(a.len(), 3);
+        assert_eq!(out.len(), orig.len());
+        let floats = |buf: &[u8]| -> Vec<u32> { buf[BLOCK..BLOCK + 4800].chunks(4).map(|c| u32::from_be_bytes(c.try_into().unwrap())).collect() };
+        for (x, y) in floats(&orig).into_iter().zip(floats(&out)) {
+            let finite = f32::from_bits(x).is_finite();
+            assert!(if finite { x ^ y <= 1 } else { x == y }, "{:08X} became {:08X}", x, y);
+        }
+        assert_eq!(out[b[2].cards.start..], orig[a[2].cards.start..], "the table is untouched");
+    }
+
+    #[test]
+    fn cards_replace_earlier_ones_and_keep_the_data() {
+        let dir = tempdir().unwrap();
+        let (cover, stego) = (dir.path().join("c.fits"), dir.path().join("s.fits"));
+        let orig = fits();
+        fs::write(&cover, &orig).unwrap();
+
+        // long enough to need more than one header block
+        let msg = "provenance: ".repeat(200);
+        hide_cards(&cover, &msg, &stego).unwrap();
+        assert_eq!(find_cards(&stego).unwrap(), msg.as_bytes());
+        let out = fs::read(&stego).unwrap();
+        assert_eq!(out.len() % BLOCK, 0);
+        assert_eq!(out[out.len() - (orig.len() - BLOCK)..], orig[BLOCK..], "data and extensions follow unchanged");
+        assert!(String::from_utf8_lossy(&out[..BLOCK]).contains("observed with a very real telescope"));
+
+        hide_cards(&stego, b"short", &stego).unwrap();
+        assert_eq!(find_cards(&stego).unwrap(), b"short");
+        assert_eq!(fs::read(&stego).unwrap().len(), orig.len());
+        assert!(find_cards(&cover).is_err());
+    }
+}
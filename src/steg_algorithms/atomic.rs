@@ -0,0 +1,168 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tempfile::TempPath;
+use crate::steg_algorithms::error::StegError;
+
+// Outputs are written to a temporary file next to where they go and renamed over it once complete, so a
+// write that fails or is cut short (disk full, Ctrl-C, a panic) leaves the destination as it was:
+// missing, the previous output, or the cover when a cover is its own output. The temporary file is in
+// the same directory, so the rename stays on one filesystem where it's atomic, and keeps the extension,
+// which the image encoders pick the format by.
+//
+// On Windows the rename replaces an existing file as well (tempfile persists with MoveFileEx and
+// MOVEFILE_REPLACE_EXISTING), but is refused while another process has the destination open, which a
+// virus scanner or indexer looking at the previous output often does for a moment. That is retried a few
+// times before giving up. No handle to the temporary file is held while it is renamed, for the same
+// reason.
+
+/// Attempts at the rename on Windows, and the wait between them.
+#[cfg(windows)]
+const PERSIST_ATTEMPTS: u32 = 10;
+#[cfg(windows)]
+const PERSIST_WAIT: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// An output being written: a temporary file next to `dest`, removed when dropped unless `commit`
+/// moved it into place.
+pub struct Staged {
+    temp: TempPath,
+    dest: PathBuf,
+}
+
+impl Staged {
+    /// An empty temporary file in the directory of `dest`, with its extension.
+    pub fn new(dest: &Path) -> Result<Staged, StegError> {
+        let dir = dest.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let suffix = dest.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+        let mut builder = tempfile::Builder::new();
+        builder.prefix(".rust-stego-").suffix(&suffix);
+        // tempfile makes its files readable by the owner only, an output should get what any new file gets
+        #[cfg(unix)]
+        builder.permissions(std::os::unix::fs::PermissionsExt::from_mode(0o666));
+        let file = builder
+            .tempfile_in(dir)
+            .map_err(|e| StegError::io(format!("Failed to create a temporary file in {}", dir.display()), e))?;
+        Ok(Staged { temp: file.into_temp_path(), dest: dest.to_path_buf() })
+    }
+
+    /// Where to write the output.
+    pub fn path(&self) -> &Path {
+        &self.temp
+    }
+
+    /// Move the finished output over the destination, keeping the permissions the destination had.
+    pub fn commit(self) -> Result<(), StegError> {
+        if let Ok(meta) = fs::metadata(&self.dest) {
+            let _ = fs::set_permissions(&self.temp, meta.permissions());
+        }
+        persist(self.temp, &self.dest).map_err(|e| StegError::io(format!("Failed to replace {}", self.dest.display()), e))
+    }
+}
+
+#[cfg(not(windows))]
+fn persist(temp: TempPath, dest: &Path) -> io::Result<()> {
+    temp.persist(dest).map_err(|e| e.error)
+}
+
+#[cfg(windows)]
+fn persist(mut temp: TempPath, dest: &Path) -> io::Result<()> {
+    for _ in 1..PERSIST_ATTEMPTS {
+        match temp.persist(dest) {
+            Ok(()) => return Ok(()),
+            Err(e) if e.error.kind() == io::ErrorKind::PermissionDenied => {
+                temp = e.path;
+                std::thread::sleep(PERSIST_WAIT);
+            }
+            Err(e) => return Err(e.error),
+        }
+    }
+    temp.persist(dest).map_err(|e| e.error)
+}
+
+/// Write `dest` through `write`, which gets the temporary file. It only replaces `dest` once `write`
+/// returned `Ok` and the data is on disk.
+pub fn write_with<T>(dest: &Path, write: impl FnOnce(&File) -> Result<T, StegError>) -> Result<T, StegError> {
+    let staged = Staged::new(dest)?;
+    let file = OpenOptions::new().write(true).open(staged.path())?;
+    let value = write(&file)?;
+    file.sync_all()?;
+    drop(file);
+    staged.commit()?;
+    Ok(value)
+}
+
+/// `fs::write`, all or nothing.
+pub fn write(dest: &Path, contents: impl AsRef<[u8]>) -> Result<(), StegError> {
+    write_with(dest, |mut file| Ok(file.write_all(contents.as_ref())?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufWriter;
+
+    // a disk that fills up after `room` bytes
+    struct Full<W> {
+        inner: W,
+        room: usize,
+    }
+
+    impl<W: Write> Write for Full<W> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.room == 0 {
+                return Err(io::Error::new(io::ErrorKind::StorageFull, "no space left on device"));
+            }
+            let n = buf.len().min(self.room);
+            self.room -= n;
+            self.inner.write(&buf[..n])
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.inner.flush()
+        }
+    }
+
+    #[test]
+    fn a_failed_write_leaves_the_destination_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("out.png");
+        fs::write(&dest, b"the previous output").unwrap();
+
+        let failed = write_with(&dest, |file| {
+            let mut out = BufWriter::new(Full { inner: file, room: 1000 });
+            for _ in 0..100 {
+                out.write_all(&[7; 64])?;
+            }
+            Ok(out.flush()?)
+        });
+        assert!(failed.unwrap_err().to_string().contains("no space left"));
+        assert_eq!(fs::read(&dest).unwrap(), b"the previous output");
+        // and the half written temporary file went with the error
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        write(&dest, b"the new one").unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), b"the new one");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        let fresh = dir.path().join("fresh.wav");
+        write(&fresh, b"RIFF").unwrap();
+        assert_eq!(fs::read(&fresh).unwrap(), b"RIFF");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn outputs_keep_the_permissions_they_had() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("cover.wav");
+        fs::write(&dest, b"x").unwrap();
+        fs::set_permissions(&dest, fs::Permissions::from_mode(0o640)).unwrap();
+        write(&dest, b"y").unwrap();
+        assert_eq!(fs::metadata(&dest).unwrap().permissions().mode() & 0o777, 0o640);
+        // a new one gets what fs::write would give it, not tempfile's owner-only
+        let (fresh, plain) = (dir.path().join("fresh.wav"), dir.path().join("plain.wav"));
+        write(&fresh, b"y").unwrap();
+        fs::write(&plain, b"y").unwrap();
+        assert_eq!(fs::metadata(&fresh).unwrap().permissions(), fs::metadata(&plain).unwrap().permissions());
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use sha2::{Digest, Sha256};
+use crate::steg_algorithms::audio::wav::lsb::{read_samples, write_samples};
+use crate::steg_algorithms::error::StegError;
+
+// Payload frames that start on the beats (`--algorithm beat`), so edits that keep the musical structure
+// (looping a bar, cutting on a beat) keep the payload too. The payload is cut into small chunks and every
+// onset gets a frame; onset i carries chunk i % total, so a loop only repeats frames and a cut only loses
+// the chunks whose every copy was in the part that went.
+//
+// Onsets are jumps in log energy per window of HOP samples, found on the samples with their LSB dropped:
+// the embedding can't move them. An edit shifts the window grid, so find looks for the frame's magic within
+// SEARCH windows either side of every onset it detects instead of trusting the exact sample.
+//
+// A frame sits in the LSBs of the consecutive (interleaved) samples from its onset on. Integers are
+// big-endian:
+//
+//   magic   4 bytes   "RSBT"
+//   seq     2 bytes   which chunk
+//   total   2 bytes   how many chunks the payload has
+//   len     1 byte    chunk length, at most CHUNK
+//   check   4 bytes   first 4 bytes of SHA-256 over seq..chunk
+//   chunk   len bytes
+
+const MAGIC: [u8; 4] = *b"RSBT";
+const HEADER: usize = 13;
+/// Most payload bytes one frame carries.
+pub const CHUNK: usize = 32;
+// onset detection: samples per window, the rise in log energy that counts as an onset, how many windows
+// either side the rise has to stand out from the average of, and the shortest gap between two onsets
+const HOP: usize = 1024;
+const RISE: f64 = 1.0;
+const LOCAL: usize = 8;
+const MIN_GAP_SECS: f64 = 0.1;
+// how many windows either side of a detected onset find looks for a frame
+const SEARCH: usize = 2;
+
+const FRAME_BITS: usize = (HEADER + CHUNK) * 8;
+
+/// Interleaved sample index of every onset in `samples`, in order.
+pub fn onsets(samples: &[i16], channels: usize, rate: u32) -> Vec<usize> {
+    let window = HOP * channels.max(1);
+    let energy: Vec<f64> = samples
+        .chunks_exact(window)
+        .map(|w| (w.iter().map(|&s| ((s >> 1) as f64).powi(2)).sum::<f64>() / window as f64 + 1.0).ln())
+        .collect();
+    let flux: Vec<f64> = (0..energy.len()).map(|i| if i == 0 { 0.0 } else { (energy[i] - energy[i - 1]).max(0.0) }).collect();
+    let min_gap = (rate as f64 * MIN_GAP_SECS / HOP as f64).ceil() as usize;
+
+    let mut found: Vec<usize> = Vec::new();
+    for (i, &f) in flux.iter().enumerate() {
+        let local = &flux[i.saturating_sub(LOCAL)..(i + LOCAL + 1).min(flux.len())];
+        let mean = local.iter().sum::<f64>() / local.len() as f64;
+        let peak = flux[i.saturating_sub(1)..(i + 2).min(flux.len())].iter().all(|&other| other <= f);
+        if peak && f >= RISE && f > mean * 2.0 && found.last().is_none_or(|&last| i - last >= min_gap) {
+            found.push(i);
+        }
+    }
+    found.into_iter().map(|i| i * window).collect()
+}
+
+// the onsets a whole frame fits after, before the next one and the end
+fn usable(onsets: &[usize], len: usize) -> Vec<usize> {
+    onsets
+        .iter()
+        .enumerate()
+        .filter(|&(i, &at)| at + FRAME_BITS <= onsets.get(i + 1).copied().unwrap_or(len))
+        .map(|(_, &at)| at)
+        .collect()
+}
+
+/// Bytes of payload the beats of the WAV at `path` hold, one chunk per beat.
+pub fn capacity(path: &Path) -> Result<usize, StegError> {
+    let (spec, samples) = read_samples(path)?;
+    let beats = usable(&onsets(&samples, spec.channels as usize, spec.sample_rate), samples.len()).len();
+    Ok(beats.min(u16::MAX as usize) * CHUNK)
+}
+
+pub fn hide(path_in: &Path, path_out: &Path, msg: &[u8]) -> Result<(), StegError> {
+    let (spec, mut samples) = read_samples(path_in)?;
+    let beats = usable(&onsets(&samples, spec.channels as usize, spec.sample_rate), samples.len());
+    let mut chunks: Vec<&[u8]> = msg.chunks(CHUNK).collect();
+    if chunks.is_empty() {
+        chunks.push(&[]);
+    }
+    let total = u16::try_from(chunks.len()).map_err(|_| "Payload too big for beat frames".to_string())?;
+    if beats.len() < chunks.len() {
+        return Err(format!(
+            "Only {} beats found, the payload needs {} ({} bytes each)",
+            beats.len(),
+            chunks.len(),
+            CHUNK
+        ).into());
+    }
+    for (i, &at) in beats.iter().enumerate() {
+        let seq = (i % chunks.len()) as u16;
+        for (k, bit) in frame_bits(seq, total, chunks[seq as usize]).enumerate() {
+            samples[at + k] = (samples[at + k] & !1) | bit as i16;
+        }
+    }
+    write_samples(path_out, spec, &samples)
+}
+
+fn frame_bits(seq: u16, total: u16, chunk: &[u8]) -> impl Iterator<Item = u8> {
+    let body = [&seq.to_be_bytes()[..], &total.to_be_bytes(), &[chunk.len() as u8], chunk].concat();
+    let check = check(&body);
+    let frame = [&MAGIC[..], &body[..4], &body[4..5], &check, &body[5..]].concat();
+    frame.into_iter().flat_map(|b| (0..8).rev().map(move |j| (b >> j) & 1))
+}
+
+fn check(body: &[u8]) -> [u8; 4] {
+    Sha256::digest(body)[..4].try_into().expect("sha256 is longer than 4 bytes")
+}
+
+/// Re-detect the onsets of the WAV at `path` and put the payload back together from the frames near
+/// them.
+pub fn find(path: &Path) -> Result<Vec<u8>, StegError> {
+    let (spec, samples) = read_samples(path)?;
+    let channels = spec.channels as usize;
+    let lsbs: Vec<u8> = samples.iter().map(|&s| (s & 1) as u8).collect();
+    let byte_at = |at: usize| lsbs[at..at + 8].iter().fold(0u8, |b, &bit| (b << 1) | bit);
+
+    let beats = onsets(&samples, channels, spec.sample_rate);
+    let reach = SEARCH * HOP * channels;
+    let mut seen = HashSet::new();
+    let (mut chunks, mut totals) = (BTreeMap::new(), BTreeMap::new());
+    for onset in &beats {
+        let end = (onset + reach + 1).min(lsbs.len().saturating_sub(HEADER * 8 - 1));
+        for at in onset.saturating_sub(reach)..end {
+            if !seen.insert(at) || (0..4).any(|i| byte_at(at + i * 8) != MAGIC[i]) {
+                continue;
+            }
+            let header: Vec<u8> = (4..HEADER).map(|i| byte_at(at + i * 8)).collect();
+            let len = header[4] as usize;
+            if len > CHUNK || at + (HEADER + len) * 8 > lsbs.len() {
+                continue;
+            }
+            let chunk: Vec<u8> = (0..len).map(|i| byte_at(at + (HEADER + i) * 8)).collect();
+            if check(&[&header[..5], &chunk].concat()) != header[5..9] {
+                continue;
+            }
+            let (seq, total) = (u16::from_be_bytes([header[0], header[1]]), u16::from_be_bytes([header[2], header[3]]));
+            *totals.entry(total).or_insert(0) += 1;
+            chunks.entry((total, seq)).or_insert(chunk);
+        }
+    }
+    // frames of an older payload could be left past the end of a shorter one, the most common total wins
+    let Some((&total, _)) = totals.iter().max_by_key(|&(_, n)| *n) else {
+        return Err(format!("No beat frames found near the {} onsets detected", beats.len()).into());
+    };
+    let missing: Vec<String> = (0..total).filter(|seq| !chunks.contains_key(&(total, *seq))).map(|seq| seq.to_string()).collect();
+    if !missing.is_empty() {
+        return Err(format!("{} of {} chunks have no frame left (chunk {})", missing.len(), total, missing.join(", ")).into());
+    }
+    Ok((0..total).flat_map(|seq| chunks.remove(&(total, seq)).unwrap_or_default()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+
+    const RATE: u32 = 8000;
+
+    // a drum hit every half second: a noise burst that decays over a quarter second, over quiet hiss
+    fn drums(seconds: usize) -> Vec<i16> {
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(5);
+        (0..seconds * RATE as usize)
+            .map(|i| {
+                let since = (i % (RATE as usize / 2)) as f64;
+                let level = 8000.0 * (-since / 400.0).exp() + 40.0;
+                (rng.gen_range(-1.0..1.0) * level) as i16
+            })
+            .collect()
+    }
+
+    fn wav(path: &Path, samples: &[i16]) {
+        let spec = hound::WavSpec { channels: 1, sample_rate: RATE, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        write_samples(path, spec, samples).unwrap();
+    }
+
+    #[test]
+    fn onsets_land_on_the_hits() {
+        let found = onsets(&drums(5), 1, RATE);
+        assert!((9..=10).contains(&found.len()), "{:?}", found);
+        for at in found {
+            let off = at % (RATE as usize / 2);
+            assert!(off < HOP || off > RATE as usize / 2 - HOP, "{}", at);
+        }
+    }
+
+    #[test]
+    fn loops_and_cuts_keep_the_payload() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cover, out, edited) = (dir.path().join("drums.wav"), dir.path().join("out.wav"), dir.path().join("edited.wav"));
+        wav(&cover, &drums(12));
+        let msg: Vec<u8> = (0..100u8).collect();
+        assert!(capacity(&cover).unwrap() >= msg.len() * 2);
+        hide(&cover, &out, &msg).unwrap();
+        assert_eq!(find(&out).unwrap(), msg);
+
+        let (_, stego) = read_samples(&out).unwrap();
+        // a cut a little after the third beat, off the window grid
+        wav(&edited, &stego[3 * RATE as usize / 2 + 333..]);
+        assert_eq!(find(&edited).unwrap(), msg);
+        // the first four seconds looped
+        wav(&edited, &[&stego[..4 * RATE as usize], &stego[..4 * RATE as usize]].concat());
+        assert_eq!(find(&edited).unwrap(), msg);
+
+        assert!(find(&cover).is_err());
+        assert!(hide(&cover, &out, &vec![7u8; 40 * CHUNK]).unwrap_err().to_string().contains("beats found"));
+    }
+}
@@ -1,64 +1,714 @@
-use hound::{WavReader, WavWriter, SampleFormat};
-use std::path::Path;
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::payload::MAGIC;
+use crate::steg_algorithms::redundancy;
+use crate::steg_algorithms::scatter::KeyedOrder;
+use serde::{Deserialize, Serialize};
 
-pub fn hide_wav(path_in: &Path, path_out: &Path, msg: &[u8]) -> Result<(), String> {
-    let mut r = WavReader::open(path_in).map_err(|e| e.to_string())?;
+// Without the `audio` feature only `TimeRange` and the bit-level extraction are left, for the LSB
+// options and for callers with samples of their own.
+#[cfg(feature = "audio")]
+use {
+    crate::steg_algorithms::atomic,
+    crate::steg_algorithms::options::{FindOptions, HideOptions},
+    crate::steg_algorithms::picture::general::lsb::LsbOptions,
+    crate::steg_algorithms::plan::Plan,
+    crate::steg_algorithms::progress,
+    hound::{SampleFormat, WavReader, WavWriter},
+    rand::{Rng, RngCore},
+    std::collections::HashSet,
+    std::io::{self, BufWriter, Cursor, Read, Write},
+    std::path::Path,
+};
+
+/// `find_wav_sparse` without an explicit stride tries every stride up to this one.
+pub const MAX_PROBE_STRIDE: usize = 64;
+
+// A payload kept to a `TimeRange` that doesn't start at the first sample opens with `RANGE_SYNC` and
+// the index of the sample the range starts at, 32 bits each in the LSBs of the first 64 samples of the
+// range, stride and key or not. The bitstream follows in the rest of the range, laid out as it would be
+// in a whole file. The sync word is what `find_in_lsbs` scans for, so find doesn't need the range (except
+// with a key, whose order depends on where the range ends) and still finds it after the intro was cut.
+const RANGE_SYNC: u32 = u32::from_be_bytes(*b"rNgE");
+const OPENING_BITS: usize = 64;
+
+/// Part of a WAV file by time, as `--range` takes it: `10s..45s`, `1.5s..`, `..441000`. A bare number
+/// counts sample frames (a sample of every channel), a number ending in `s` seconds. Serialized the
+/// same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct TimeRange {
+    /// The start of the file when `None`.
+    pub start: Option<At>,
+    /// The end of the file when `None`.
+    pub end: Option<At>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum At {
+    Millis(u64),
+    Frame(u64),
+}
+
+impl TimeRange {
+    pub fn parse(s: &str) -> Result<TimeRange, StegError> {
+        let (start, end) = s.split_once("..").ok_or_else(|| format!("expected START..END, like 10s..45s, got '{}'", s))?;
+        let at = |t: &str| -> Result<Option<At>, StegError> {
+            let t = t.trim();
+            if t.is_empty() {
+                return Ok(None);
+            }
+            let at = match t.strip_suffix('s') {
+                Some(secs) => secs.parse::<f64>().ok().filter(|v| v.is_finite() && *v >= 0.0).map(|v| At::Millis((v * 1000.0).round() as u64)),
+                None => t.parse().ok().map(At::Frame),
+            };
+            at.map(Some).ok_or_else(|| format!("expected seconds (12.5s) or a sample frame (551250), got '{}'", t).into())
+        };
+        Ok(TimeRange { start: at(start)?, end: at(end)? })
+    }
+
+    /// The samples the range covers in a file of `samples` samples laid out as `spec` says, as indexes
+    /// into them (all channels interleaved), end exclusive.
+    #[cfg(feature = "audio")]
+    pub fn window(&self, spec: &hound::WavSpec, samples: usize) -> Result<(usize, usize), StegError> {
+        let channels = spec.channels.max(1) as usize;
+        let frames = samples / channels;
+        let frame = |at: At| match at {
+            At::Millis(ms) => (ms as u128 * spec.sample_rate as u128 / 1000) as usize,
+            At::Frame(n) => n as usize,
+        };
+        let (start, end) = (self.start.map_or(0, frame), self.end.map_or(frames, frame));
+        if end > frames {
+            return Err(format!(
+                "Range {} ends at frame {} but the file is {} frames ({:.2}s) long",
+                self, end, frames, frames as f64 / spec.sample_rate.max(1) as f64
+            ).into());
+        }
+        if start >= end {
+            return Err(format!("Range {} starts at frame {}, which leaves nothing before its end at {}", self, start, end).into());
+        }
+        Ok((start * channels, end * channels))
+    }
+}
+
+impl std::fmt::Display for TimeRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let show = |at: Option<At>| match at {
+            None => String::new(),
+            Some(At::Frame(n)) => n.to_string(),
+            Some(At::Millis(ms)) if ms % 1000 == 0 => format!("{}s", ms / 1000),
+            Some(At::Millis(ms)) => format!("{}s", format!("{}.{:03}", ms / 1000, ms % 1000).trim_end_matches('0')),
+        };
+        write!(f, "{}..{}", show(self.start), show(self.end))
+    }
+}
+
+impl From<TimeRange> for String {
+    fn from(r: TimeRange) -> String {
+        r.to_string()
+    }
+}
+
+impl TryFrom<String> for TimeRange {
+    type Error = StegError;
+
+    fn try_from(s: String) -> Result<TimeRange, StegError> {
+        TimeRange::parse(&s)
+    }
+}
+
+/// How many bytes `hide_wav_sparse` can embed at the given stride (after the 32-bit length header).
+#[cfg(feature = "audio")]
+pub fn capacity(path: &Path, stride: usize) -> Result<usize, StegError> {
+    if stride == 0 { return Err("Stride must be at least 1".into()); }
+    let r = WavReader::open(path)?;
     let spec = r.spec();
     if spec.sample_format != SampleFormat::Int || spec.bits_per_sample != 16 {
         return Err("Only PCM16 WAV supported".into());
     }
-    let mut samples: Vec<i16> = r.samples::<i16>().map(|s| s.unwrap()).collect();
+    // len() counts samples across all channels, which is what we embed into
+    Ok(((r.len() as usize).div_ceil(stride) / 8).saturating_sub(4))
+}
+
+/// `capacity` for a payload kept to `range`.
+#[cfg(feature = "audio")]
+pub fn capacity_in(path: &Path, stride: usize, range: Option<&TimeRange>) -> Result<usize, StegError> {
+    let Some(range) = range else { return capacity(path, stride) };
+    if stride == 0 { return Err("Stride must be at least 1".into()); }
+    let r = WavReader::open(path)?;
+    let (start, end) = range.window(&r.spec(), r.len() as usize)?;
+    let usable = room(range, start, end, stride)?;
+    Ok((usable / 8).saturating_sub(4))
+}
 
-    // make bit stream: 32-bit len header (big-endian) + message (MSB-first per byte)
-    let len = msg.len() as u32;
-    let mut bits = Vec::with_capacity(32 + msg.len() * 8);
-    for i in (0..32).rev() { bits.push(((len >> i) & 1) as u8); }
-    for &b in msg {
-        for i in (0..8).rev() { bits.push(((b >> i) & 1) as u8); }
+// how many samples of the window carry the bitstream, failing when that isn't even its length header
+#[cfg(feature = "audio")]
+fn room(range: &TimeRange, start: usize, end: usize, stride: usize) -> Result<usize, StegError> {
+    let prefix = if start > 0 { OPENING_BITS } else { 0 };
+    let usable = (end - start).saturating_sub(prefix).div_ceil(stride);
+    if usable < 32 {
+        let header = prefix + 31 * stride + 1;
+        return Err(format!("Range {} holds {} samples, too few for the header, which takes {} at stride {}", range, end - start, header, stride).into());
     }
-    if bits.len() > samples.len() {
-        return Err(format!("Too big: need {} samples, have {}", bits.len(), samples.len()));
+    Ok(usable)
+}
+
+/// Hide `msg` in the lowest bit of every sample of the 16-bit WAV at `path_in`, writing the result to
+/// `path_out`.
+///
+/// ```
+/// use rust_stego::steg::audio::wav::lsb::{find_wav, hide_wav};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let (cover, out) = (dir.path().join("cover.wav"), dir.path().join("out.wav"));
+/// let spec = hound::WavSpec { channels: 1, sample_rate: 8000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+/// let mut writer = hound::WavWriter::create(&cover, spec).unwrap();
+/// for i in 0..4000 {
+///     writer.write_sample((i * 37 % 2000) as i16 - 1000).unwrap();
+/// }
+/// writer.finalize().unwrap();
+///
+/// hide_wav(&cover, &out, b"in the noise")?;
+/// assert_eq!(find_wav(&out)?, b"in the noise");
+/// # Ok::<(), rust_stego::steg::StegError>(())
+/// ```
+#[cfg(feature = "audio")]
+pub fn hide_wav(path_in: &Path, path_out: &Path, msg: &[u8]) -> Result<(), StegError> {
+    hide_wav_sparse(path_in, path_out, msg, 1)
+}
+
+/// Like `hide_wav`, but only every `stride`-th sample carries a bit.
+#[cfg(feature = "audio")]
+pub fn hide_wav_sparse(path_in: &Path, path_out: &Path, msg: &[u8], stride: usize) -> Result<(), StegError> {
+    if stride == 0 { return Err("Stride must be at least 1".into()); }
+    embed(path_in, path_out, msg, Some(stride), None, 1, None)
+}
+
+/// Like `hide_wav`, but the bits (length header included) go into samples in an order derived from
+/// `key`, spread over the whole duration. Same capacity as `hide_wav`.
+#[cfg(feature = "audio")]
+pub fn hide_wav_keyed(path_in: &Path, path_out: &Path, msg: &[u8], key: &str) -> Result<(), StegError> {
+    embed(path_in, path_out, msg, None, Some(key), 1, None)
+}
+
+/// Like `hide_wav_sparse`/`hide_wav_keyed`, but every bit is stored `copies` times (odd) and the copies
+/// follow each other, so clipping the end of the clip only costs the last copy. See `redundancy`.
+#[cfg(feature = "audio")]
+pub fn hide_wav_redundant(path_in: &Path, path_out: &Path, msg: &[u8], stride: usize, key: Option<&str>, copies: usize) -> Result<(), StegError> {
+    if stride == 0 { return Err("Stride must be at least 1".into()); }
+    embed(path_in, path_out, msg, Some(stride).filter(|_| key.is_none()), key, copies, None)
+}
+
+/// Like `hide_wav_redundant`, but only the samples in `range` change (see `TimeRange`). find needs
+/// the same range for a keyed payload, and finds an unkeyed one without it.
+#[cfg(feature = "audio")]
+pub fn hide_wav_in(path_in: &Path, path_out: &Path, msg: &[u8], stride: usize, key: Option<&str>, copies: usize, range: &TimeRange) -> Result<(), StegError> {
+    if stride == 0 { return Err("Stride must be at least 1".into()); }
+    embed(path_in, path_out, msg, Some(stride).filter(|_| key.is_none()), key, copies, Some(range))
+}
+
+/// The general form of the hide functions: the samples `opts.lsb`'s stride or key picks, within its
+/// range, each bit `opts.copies` times. The bits and channels are the picture's business and ignored.
+#[cfg(feature = "audio")]
+pub fn hide_wav_with(path_in: &Path, path_out: &Path, msg: &[u8], opts: &HideOptions) -> Result<(), StegError> {
+    embed(path_in, path_out, msg, picked_by(&opts.lsb), opts.lsb.key.as_deref(), opts.copies, opts.lsb.range.as_ref())
+}
+
+// the stride counts only without a key, which orders every sample
+#[cfg(feature = "audio")]
+fn picked_by(lsb: &LsbOptions) -> Option<usize> {
+    lsb.stride.filter(|_| lsb.key.is_none())
+}
+
+// either a stride or a key picks the samples. They're written as they're read, into a temporary file
+// that only replaces the output at the end, so a cover can be its own output
+#[cfg(feature = "audio")]
+fn embed(path_in: &Path, path_out: &Path, msg: &[u8], stride: Option<usize>, key: Option<&str>, copies: usize, range: Option<&TimeRange>) -> Result<(), StegError> {
+    // the read reports the progress, the write keeps pace with it
+    let cover = progress::open(path_in)?;
+    atomic::write_with(path_out, |file| mark(cover, file, msg, stride, key, copies, range).map(drop))
+}
+
+/// `hide_wav_with` for a carrier that's in memory rather than on disk, an upload say. The result comes
+/// back as the bytes of a PCM16 WAV.
+#[cfg(feature = "audio")]
+pub fn hide_bytes_with(carrier: &[u8], payload: &[u8], opts: &HideOptions) -> Result<Vec<u8>, StegError> {
+    let mut out = Cursor::new(Vec::new());
+    hide_stream_with(carrier, &mut out, payload, opts)?;
+    Ok(out.into_inner())
+}
+
+/// `hide_wav_with` between streams, for a carrier that isn't a file: an object store body, a zip entry.
+/// Samples are read, marked and written one at a time, so however long the WAV is only the payload's
+/// bits are held in memory. The header goes out first, sized from the carrier's, so `out` can be a
+/// socket or a pipe.
+#[cfg(feature = "audio")]
+pub fn hide_stream_with(carrier: impl Read, out: impl Write, payload: &[u8], opts: &HideOptions) -> Result<Plan, StegError> {
+    mark(carrier, out, payload, picked_by(&opts.lsb), opts.lsb.key.as_deref(), opts.copies, opts.lsb.range.as_ref())
+}
+
+/// `hide_bytes_with` with the layout as it was passed before `HideOptions`.
+#[cfg(feature = "audio")]
+#[deprecated(note = "use hide_bytes_with and HideOptions, this goes in the next release")]
+pub fn hide_bytes(carrier: &[u8], payload: &[u8], stride: Option<usize>, key: Option<&str>, copies: usize, range: Option<&TimeRange>) -> Result<Vec<u8>, StegError> {
+    let mut out = Cursor::new(Vec::new());
+    mark(carrier, &mut out, payload, stride, key, copies, range)?;
+    Ok(out.into_inner())
+}
+
+/// `hide_stream_with` with the layout as it was passed before `HideOptions`.
+#[cfg(feature = "audio")]
+#[deprecated(note = "use hide_stream_with and HideOptions, this goes in the next release")]
+pub fn hide_stream(carrier: impl Read, out: impl Write, payload: &[u8], stride: Option<usize>, key: Option<&str>, copies: usize, range: Option<&TimeRange>) -> Result<Plan, StegError> {
+    mark(carrier, out, payload, stride, key, copies, range)
+}
+
+#[cfg(feature = "audio")]
+fn mark(carrier: impl Read, out: impl Write, payload: &[u8], stride: Option<usize>, key: Option<&str>, copies: usize, range: Option<&TimeRange>) -> Result<Plan, StegError> {
+    if stride == Some(0) { return Err("Stride must be at least 1".into()); }
+    let reader = pcm16(WavReader::new(carrier)?)?;
+    let (spec, total) = (reader.spec(), reader.len() as usize);
+    let (placed, mut plan) = place(&spec, total, payload, stride, key, copies, range)?;
+    let mut placed = placed.peekable();
+    let mut w = BufWriter::new(out);
+    w.write_all(&header(spec, total)?)?;
+    for (i, sample) in reader.into_samples::<i16>().enumerate() {
+        let mut sample = sample?;
+        if let Some((_, bit)) = placed.next_if(|&(at, _)| at == i) {
+            let marked = (sample & !1) | bit as i16; // set LSB
+            plan.changed += (marked != sample) as usize;
+            sample = marked;
+        }
+        w.write_all(&sample.to_le_bytes())?;
     }
+    w.flush()?;
+    Ok(plan)
+}
+
+// the header `WavWriter` writes for `spec`, with the sizes of `samples` PCM16 samples in it already,
+// which it would otherwise seek back to fill in
+#[cfg(feature = "audio")]
+fn header(spec: hound::WavSpec, samples: usize) -> Result<Vec<u8>, StegError> {
+    let mut head = Cursor::new(Vec::new());
+    WavWriter::new(&mut head, spec)?.finalize()?;
+    let mut head = head.into_inner();
+    let end = head.len();
+    let too_long = || StegError::from("The WAV is too long for a RIFF header");
+    let data = u32::try_from(samples * 2).map_err(|_| too_long())?;
+    let riff = data.checked_add(end as u32 - 8).ok_or_else(too_long)?;
+    head[4..8].copy_from_slice(&riff.to_le_bytes());
+    head[end - 4..].copy_from_slice(&data.to_le_bytes());
+    Ok(head)
+}
+
+/// What `hide_wav_redundant` (or, with one copy, `hide_wav_sparse`/`hide_wav_keyed`, or with a range
+/// `hide_wav_in`) would do to the cover, without writing anything.
+#[cfg(feature = "audio")]
+pub fn plan(path_in: &Path, msg: &[u8], stride: Option<usize>, key: Option<&str>, copies: usize, range: Option<&TimeRange>) -> Result<Plan, StegError> {
+    let mut tally = Tally::default();
+    let mut plan = mark(progress::open(path_in)?, &mut tally, msg, stride, key, copies, range)?;
+    plan.output_bytes = tally.len;
+    Ok(plan)
+}
 
-    // embed 1 LSB per sample
-    for (i, bit) in bits.iter().enumerate() {
-        let s = samples[i];
-        samples[i] = (s & !1) | (*bit as i16); // set LSB
+// the bits of `msg` for a file of `total` samples laid out as `spec`, each with the sample it goes into,
+// in file order; and the plan, its changes still to be counted
+#[cfg(feature = "audio")]
+fn place(spec: &hound::WavSpec, total: usize, msg: &[u8], stride: Option<usize>, key: Option<&str>, copies: usize, range: Option<&TimeRange>) -> Result<(Placed, Plan), StegError> {
+    // make bit stream: 32-bit len header (big-endian) + message (MSB-first per byte), `copies` times over
+    let bits = redundancy::bitstream(msg, copies)?;
+    let stride = stride.unwrap_or(1);
+    let (start, end) = match range {
+        Some(r) => r.window(spec, total)?,
+        None => (0, total),
+    };
+    let usable = match range {
+        Some(r) => room(r, start, end, stride)?,
+        None => total.div_ceil(stride),
+    };
+    if bits.len() > usable {
+        return Err(StegError::CapacityExceeded { needed: bits.len().div_ceil(8), available: usable / 8 });
     }
+    let plan = Plan::new(bits.len(), usable, "sample", total, 0);
+    let mut opening = Vec::new();
+    let mut body = start;
+    if start > 0 {
+        let word = (RANGE_SYNC as u64) << 32 | start as u64;
+        opening = (0..OPENING_BITS).map(|i| (start + i, ((word >> (OPENING_BITS - 1 - i)) & 1) as u8)).collect();
+        body += OPENING_BITS;
+    }
+    let body: Placed = match key {
+        // the keyed order jumps all over the file, sorted it can be laid in as the samples stream past
+        Some(k) => {
+            let mut keyed: Vec<(usize, u8)> = KeyedOrder::new(k, end - body).map(|i| body + i).zip(bits).collect();
+            keyed.sort_unstable();
+            Box::new(keyed.into_iter())
+        }
+        None => Box::new((body..end).step_by(stride).zip(bits)),
+    };
+    Ok((Box::new(opening.into_iter().chain(body)), plan))
+}
+
+#[cfg(feature = "audio")]
+type Placed = Box<dyn Iterator<Item = (usize, u8)>>;
 
-    // write out
-    let mut w = WavWriter::create(path_out, spec).map_err(|e| e.to_string())?;
-    for s in samples { w.write_sample(s).map_err(|e| e.to_string())?; }
-    w.finalize().map_err(|e| e.to_string())
+// an output that's only measured, for `plan`
+#[derive(Default)]
+#[cfg(feature = "audio")]
+struct Tally {
+    len: u64,
 }
 
-pub fn find_wav(path: &Path) -> Result<Vec<u8>, String> {
-    let mut r = WavReader::open(path).map_err(|e| e.to_string())?;
+#[cfg(feature = "audio")]
+impl Write for Tally {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.len += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// write a PCM16 file through `progress`
+#[cfg(feature = "audio")]
+pub(crate) fn write_samples(path_out: &Path, spec: hound::WavSpec, samples: &[i16]) -> Result<(), StegError> {
+    atomic::write_with(path_out, |file| {
+        let mut w = WavWriter::new(progress::create(file, Some(samples.len() as u64 * 2)), spec)?;
+        for &s in samples { w.write_sample(s)?; }
+        w.finalize().map_err(StegError::from)
+    })
+}
+
+// every sample of a PCM16 file, read through `progress`
+#[cfg(feature = "audio")]
+pub(crate) fn read_samples(path: &Path) -> Result<(hound::WavSpec, Vec<i16>), StegError> {
+    let mut r = pcm16(WavReader::new(progress::open(path)?)?)?;
+    let samples = r.samples::<i16>().collect::<Result<Vec<_>, _>>()?;
+    Ok((r.spec(), samples))
+}
+
+// `r`, unless it holds anything but 16-bit integer PCM
+#[cfg(feature = "audio")]
+fn pcm16<R: Read>(r: WavReader<R>) -> Result<WavReader<R>, StegError> {
+    let spec = r.spec();
+    if spec.sample_format != SampleFormat::Int || spec.bits_per_sample != 16 {
+        return Err("Only PCM16 WAV supported".into());
+    }
+    Ok(r)
+}
+
+/// Flip the LSB of `count` random samples past the end of a `payload_len` byte payload hidden at `stride`,
+/// rewriting `path` in place. Makes the file's hash differ even when the payload bits happened to match.
+/// Returns how many samples were flipped (fewer than `count` if the tail is too short).
+#[cfg(feature = "audio")]
+pub fn perturb(path: &Path, payload_len: usize, stride: usize, count: usize, rng: &mut impl RngCore) -> Result<usize, StegError> {
+    let mut r = WavReader::open(path)?;
     let spec = r.spec();
     if spec.sample_format != SampleFormat::Int || spec.bits_per_sample != 16 {
         return Err("Only PCM16 WAV supported".into());
     }
-    let samples: Vec<i16> = r.samples::<i16>().map(|s| s.unwrap()).collect();
-    let bits: Vec<u8> = samples.iter().map(|&s| (s as u16 & 1) as u8).collect();
+    let mut samples = r.samples::<i16>().collect::<Result<Vec<_>, _>>()?;
+    drop(r);
+
+    let used = ((32 + payload_len * 8 - 1) * stride + 1).min(samples.len());
+    let free = samples.len() - used;
+    let count = count.min(free);
+    for i in rand::seq::index::sample(rng, free, count) {
+        samples[used + i] ^= 1;
+    }
+
+    write_samples(path, spec, &samples)?;
+    Ok(count)
+}
+
+/// Replace the LSB of every sample with a random bit, rewriting `path` in place. Destroys an LSB payload
+/// whatever stride or key it was hidden with. Returns the number of samples.
+#[cfg(feature = "audio")]
+pub fn randomize(path: &Path, rng: &mut impl RngCore) -> Result<usize, StegError> {
+    let mut r = WavReader::open(path)?;
+    let spec = r.spec();
+    if spec.sample_format != SampleFormat::Int || spec.bits_per_sample != 16 {
+        return Err("Only PCM16 WAV supported".into());
+    }
+    let mut samples = r.samples::<i16>().collect::<Result<Vec<_>, _>>()?;
+    drop(r);
+
+    for s in &mut samples {
+        *s = (*s & !1) | (rng.next_u32() & 1) as i16;
+    }
+    write_samples(path, spec, &samples)?;
+    Ok(samples.len())
+}
+
+/// `perturb` for files made with `hide_wav_keyed`: the flipped samples are picked at random among the
+/// ones the key's order didn't use for the payload.
+#[cfg(feature = "audio")]
+pub fn perturb_keyed(path: &Path, payload_len: usize, key: &str, count: usize, rng: &mut impl RngCore) -> Result<usize, StegError> {
+    let mut r = WavReader::open(path)?;
+    let spec = r.spec();
+    if spec.sample_format != SampleFormat::Int || spec.bits_per_sample != 16 {
+        return Err("Only PCM16 WAV supported".into());
+    }
+    let mut samples = r.samples::<i16>().collect::<Result<Vec<_>, _>>()?;
+    drop(r);
+
+    let mut taken: HashSet<usize> = KeyedOrder::new(key, samples.len()).take((4 + payload_len) * 8).collect();
+    let count = count.min(samples.len() - taken.len());
+    let mut flipped = 0;
+    while flipped < count {
+        let i = rng.gen_range(0..samples.len());
+        if taken.insert(i) {
+            samples[i] ^= 1;
+            flipped += 1;
+        }
+    }
+
+    write_samples(path, spec, &samples)?;
+    Ok(count)
+}
+
+/// The bytes `hide_wav` put in the WAV at `path`. Fails when there is no payload there.
+///
+/// ```
+/// use rust_stego::steg::audio::wav::lsb::find_wav;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let silence = dir.path().join("silence.wav");
+/// let spec = hound::WavSpec { channels: 1, sample_rate: 8000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+/// let mut writer = hound::WavWriter::create(&silence, spec).unwrap();
+/// for _ in 0..4000 {
+///     writer.write_sample(0i16).unwrap();
+/// }
+/// writer.finalize().unwrap();
+///
+/// // all zero bits read as an empty payload
+/// assert_eq!(find_wav(&silence)?, b"");
+/// # Ok::<(), rust_stego::steg::StegError>(())
+/// ```
+#[cfg(feature = "audio")]
+pub fn find_wav(path: &Path) -> Result<Vec<u8>, StegError> {
+    find_wav_sparse(path, Some(1))
+}
 
+/// Extract a payload written by `hide_wav_sparse`. With `stride: None` the stride is recovered by trying
+/// 1..=MAX_PROBE_STRIDE and picking the first one whose payload starts with the framing magic.
+#[cfg(feature = "audio")]
+pub fn find_wav_sparse(path: &Path, stride: Option<usize>) -> Result<Vec<u8>, StegError> {
+    find_wav_scored(path, stride, None).map(|(data, _)| data)
+}
+
+/// Extract a payload written by `hide_wav_keyed` with the same key.
+#[cfg(feature = "audio")]
+pub fn find_wav_keyed(path: &Path, key: &str) -> Result<Vec<u8>, StegError> {
+    find_wav_scored(path, None, Some(key)).map(|(data, _)| data)
+}
+
+/// `find_wav_keyed` with a key, `find_wav_sparse` without, plus a confidence per byte when the payload
+/// was stored with --redundancy.
+#[cfg(feature = "audio")]
+pub fn find_wav_scored(path: &Path, stride: Option<usize>, key: Option<&str>) -> Result<(Vec<u8>, Option<Vec<f32>>), StegError> {
+    find_wav_limited(path, stride, key, None)
+}
+
+/// `find_wav_scored` that stops after the first `limit` bytes behind the length prefix, for a preview.
+/// A redundant payload is still read whole.
+#[cfg(feature = "audio")]
+pub fn find_wav_limited(path: &Path, stride: Option<usize>, key: Option<&str>, limit: Option<usize>) -> Result<(Vec<u8>, Option<Vec<f32>>), StegError> {
+    find_wav_in(path, stride, key, None, limit)
+}
+
+/// `find_wav_limited` for a payload hidden with `hide_wav_in`, with the range it was hidden in. Without
+/// one, the payload is looked for at the start and then wherever a range opens (keyed ones excepted).
+#[cfg(feature = "audio")]
+pub fn find_wav_in(path: &Path, stride: Option<usize>, key: Option<&str>, range: Option<&TimeRange>, limit: Option<usize>) -> Result<(Vec<u8>, Option<Vec<f32>>), StegError> {
+    if stride == Some(0) { return Err("Stride must be at least 1".into()); }
+    find_in(progress::open(path)?, stride, key, range, limit)
+}
+
+/// The general form of the find functions: the payload `hide_wav_with` hid with the stride or key and
+/// range in `opts.lsb`, up to `opts.limit` bytes of it, with a confidence per byte when it was stored
+/// more than once. Without a stride every one up to `MAX_PROBE_STRIDE` is tried.
+#[cfg(feature = "audio")]
+pub fn find_wav_with(path: &Path, opts: &FindOptions) -> Result<(Vec<u8>, Option<Vec<f32>>), StegError> {
+    find_wav_in(path, picked_by(&opts.lsb), opts.lsb.key.as_deref(), opts.lsb.range.as_ref(), opts.limit)
+}
+
+/// `find_wav_with` for a carrier in memory, as `hide_bytes_with` returns it, without the confidence
+/// scores.
+#[cfg(feature = "audio")]
+pub fn find_bytes_with(carrier: &[u8], opts: &FindOptions) -> Result<Vec<u8>, StegError> {
+    find_stream_with(carrier, opts)
+}
+
+/// `find_bytes_with` for a carrier read from a stream, see `hide_stream_with`. Only the LSBs are kept,
+/// a byte a sample, and none past the end of the range.
+#[cfg(feature = "audio")]
+pub fn find_stream_with(carrier: impl Read, opts: &FindOptions) -> Result<Vec<u8>, StegError> {
+    let stride = picked_by(&opts.lsb);
+    if stride == Some(0) { return Err("Stride must be at least 1".into()); }
+    find_in(carrier, stride, opts.lsb.key.as_deref(), opts.lsb.range.as_ref(), opts.limit).map(|(data, _)| data)
+}
+
+/// `find_bytes_with` with the layout as it was passed before `FindOptions`.
+#[cfg(feature = "audio")]
+#[deprecated(note = "use find_bytes_with and FindOptions, this goes in the next release")]
+pub fn find_bytes(carrier: &[u8], stride: Option<usize>, key: Option<&str>, range: Option<&TimeRange>) -> Result<Vec<u8>, StegError> {
+    if stride == Some(0) { return Err("Stride must be at least 1".into()); }
+    find_in(carrier, stride, key, range, None).map(|(data, _)| data)
+}
+
+/// `find_stream_with` with the layout as it was passed before `FindOptions`.
+#[cfg(feature = "audio")]
+#[deprecated(note = "use find_stream_with and FindOptions, this goes in the next release")]
+pub fn find_stream(carrier: impl Read, stride: Option<usize>, key: Option<&str>, range: Option<&TimeRange>) -> Result<Vec<u8>, StegError> {
+    if stride == Some(0) { return Err("Stride must be at least 1".into()); }
+    find_in(carrier, stride, key, range, None).map(|(data, _)| data)
+}
+
+#[cfg(feature = "audio")]
+fn find_in(carrier: impl Read, stride: Option<usize>, key: Option<&str>, range: Option<&TimeRange>, limit: Option<usize>) -> Result<(Vec<u8>, Option<Vec<f32>>), StegError> {
+    let reader = pcm16(WavReader::new(carrier)?)?;
+    let (spec, total) = (reader.spec(), reader.len() as usize);
+    let (start, end) = match range {
+        Some(r) => r.window(&spec, total)?,
+        None => (0, total),
+    };
+    let bits = reader.into_samples::<i16>().take(end).map(|s| s.map(|s| (s as u16 & 1) as u8)).collect::<Result<Vec<_>, _>>()?;
+    match range {
+        Some(range) => find_in_window(&bits, start, stride, key, limit).map_err(|e| format!("{} (in --range {})", e, range).into()),
+        None => find_in_lsbs(&bits, stride, key, limit),
+    }
+}
+
+/// `find_wav_limited` on sample LSBs already read with `lsbs`.
+pub fn find_in_lsbs(bits: &[u8], stride: Option<usize>, key: Option<&str>, limit: Option<usize>) -> Result<(Vec<u8>, Option<Vec<f32>>), StegError> {
+    if stride == Some(0) { return Err("Stride must be at least 1".into()); }
+    let found = extract(bits, stride, key, limit);
+    if key.is_some() || found.as_ref().is_ok_and(|(data, _)| data.starts_with(&MAGIC)) {
+        return found;
+    }
+    // nothing framed at the start, maybe further in: the first range opening that holds a frame
+    range_starts(bits)
+        .find_map(|at| {
+            let data = extract(bits.get(at + OPENING_BITS..)?, stride, None, limit).ok().filter(|(data, _)| data.starts_with(&MAGIC))?;
+            log::debug!("wav lsb: nothing framed at the start, a range opening at sample {} holds a frame", at);
+            Some(data)
+        })
+        .map_or(found, Ok)
+}
+
+fn extract(bits: &[u8], stride: Option<usize>, key: Option<&str>, limit: Option<usize>) -> Result<(Vec<u8>, Option<Vec<f32>>), StegError> {
+    match key {
+        Some(k) => extract_keyed(bits, k, limit),
+        None => extract_sparse(bits, stride, limit),
+    }
+}
+
+// the payload in the range that opens at sample `start` of `bits` and runs to their end
+#[cfg(feature = "audio")]
+fn find_in_window(bits: &[u8], start: usize, stride: Option<usize>, key: Option<&str>, limit: Option<usize>) -> Result<(Vec<u8>, Option<Vec<f32>>), StegError> {
+    if start == 0 {
+        return extract(bits, stride, key, limit);
+    }
+    if opening(bits, start).is_none() {
+        let elsewhere = range_starts(bits).next().map(|at| match opening(bits, at) {
+            Some(hidden) if hidden != at => format!(", one opens at sample {} (hidden at {}, the file lost samples before it)", at, hidden),
+            _ => format!(", one opens at sample {}", at),
+        });
+        return Err(format!("No payload opens at sample {}{}", start, elsewhere.unwrap_or_default()).into());
+    }
+    extract(&bits[start + OPENING_BITS..], stride, key, limit)
+}
+
+// the sample a range opening at `at` was hidden at, if one opens there
+#[cfg(feature = "audio")]
+fn opening(bits: &[u8], at: usize) -> Option<usize> {
+    let word = bits.get(at..at + OPENING_BITS)?.iter().fold(0u64, |v, &b| (v << 1) | b as u64);
+    ((word >> 32) as u32 == RANGE_SYNC).then_some(word as u32 as usize)
+}
+
+// every sample the sync word of a range opening starts at
+fn range_starts(bits: &[u8]) -> impl Iterator<Item = usize> + '_ {
+    let mut last = 0u32;
+    bits.iter().enumerate().filter_map(move |(i, &b)| {
+        last = (last << 1) | b as u32;
+        (i + 1).checked_sub(32).filter(|_| last == RANGE_SYNC)
+    })
+}
+
+fn extract_sparse(bits: &[u8], stride: Option<usize>, limit: Option<usize>) -> Result<(Vec<u8>, Option<Vec<f32>>), StegError> {
+    let stride = match stride {
+        Some(s) => s,
+        // fall back to 1 so unframed data still decodes the way it always did
+        None => (1..=MAX_PROBE_STRIDE)
+            .find(|&s| has_magic(bits, s) || redundancy::header(&strided_slots(bits, s, 32 * redundancy::MAX_REDUNDANCY)).is_some())
+            .unwrap_or(1),
+    };
+    if let Some((data, confidence)) = redundancy::find_scored(|count| strided_slots(bits, stride, count))? {
+        return Ok((data, Some(confidence)));
+    }
+    Ok((decode_strided(bits, stride, limit)?, None))
+}
+
+fn extract_keyed(bits: &[u8], key: &str, limit: Option<usize>) -> Result<(Vec<u8>, Option<Vec<f32>>), StegError> {
     if bits.len() < 32 { return Err("Too short for header".into()); }
-    // read 32-bit len
-    let mut len: u32 = 0;
-    for i in 0..32 { len = (len << 1) | bits[i] as u32; }
-    let need = (len as usize) * 8;
-    if bits.len() < 32 + need { return Err("Truncated payload".into()); }
+    if let Some((data, confidence)) = redundancy::find_scored(|count| KeyedOrder::new(key, bits.len()).take(count).map(|i| bits[i]).collect())? {
+        return Ok((data, Some(confidence)));
+    }
+    let mut order = KeyedOrder::new(key, bits.len());
+    let mut next_bytes = |count: usize| -> Vec<u8> {
+        (0..count).map(|_| order.by_ref().take(8).fold(0u8, |b, i| (b << 1) | bits[i])).collect()
+    };
 
-    let mut out = Vec::with_capacity(len as usize);
-    let start = 32;
-    for i in 0..len as usize {
-        let mut b = 0u8;
-        for j in 0..8 { b = (b << 1) | bits[start + i*8 + j]; }
-        out.push(b);
+    let len = u32::from_be_bytes(next_bytes(4).try_into().unwrap());
+    let available = (bits.len() - 32) / 8;
+    if len as u64 > available as u64 {
+        return Err(StegError::NoPayloadFound);
     }
-    Ok(out)
+    Ok((next_bytes(limit.map_or(len as usize, |l| l.min(len as usize))), None))
+}
+
+/// The LSB of every sample, in order.
+pub fn lsbs(samples: &[i16]) -> Vec<u8> {
+    samples.iter().map(|&s| (s as u16 & 1) as u8).collect()
+}
+
+// the first `count` bits of every `stride`-th LSB
+fn strided_slots(bits: &[u8], stride: usize, count: usize) -> Vec<u8> {
+    bits.iter().step_by(stride).take(count).copied().collect()
+}
+
+// read `count` bytes (MSB-first) from every `stride`-th LSB, starting at the `start`-th of those
+fn read_bytes(bits: &[u8], stride: usize, start: usize, count: usize) -> Vec<u8> {
+    (0..count)
+        .map(|i| {
+            let mut b = 0u8;
+            for j in 0..8 { b = (b << 1) | bits[(start + i*8 + j) * stride]; }
+            b
+        })
+        .collect()
 }
 
-#[cfg(test)]
+fn has_magic(bits: &[u8], stride: usize) -> bool {
+    bits.len().div_ceil(stride) >= 64 && read_bytes(bits, stride, 32, 4) == MAGIC
+}
+
+fn decode_strided(bits: &[u8], stride: usize, limit: Option<usize>) -> Result<Vec<u8>, StegError> {
+    let slots = bits.len().div_ceil(stride);
+    if slots < 32 { return Err("Too short for header".into()); }
+    // read 32-bit len
+    let len = u32::from_be_bytes(read_bytes(bits, stride, 0, 4).try_into().unwrap());
+    // check the claimed length fits before allocating anything proportional to it
+    let available = (slots - 32) / 8;
+    if len as u64 > available as u64 {
+        return Err(StegError::NoPayloadFound);
+    }
+
+    Ok(read_bytes(bits, stride, 32, limit.map_or(len as usize, |l| l.min(len as usize))))
+}
+
+#[cfg(all(test, feature = "audio"))]
 mod tests {
     use super::*;
     use hound::{WavWriter, WavSpec, SampleFormat};
@@ -67,6 +717,11 @@ mod tests {
 
     // helper: make a silent 16-bit PCM wav with N samples
     fn make_test_wav(path: &PathBuf, samples: usize) {
+        make_filled_wav(path, samples, 0);
+    }
+
+    // helper: 16-bit PCM wav where every sample is `value`
+    fn make_filled_wav(path: &PathBuf, samples: usize, value: i16) {
         let spec = WavSpec {
             channels: 2,
             sample_rate: 44100,
@@ -75,7 +730,7 @@ mod tests {
         };
         let mut w = WavWriter::create(path, spec).unwrap();
         for _ in 0..samples {
-            w.write_sample::<i16>(0).unwrap(); // silence
+            w.write_sample::<i16>(value).unwrap();
         }
         w.finalize().unwrap();
     }
@@ -96,6 +751,21 @@ mod tests {
         assert_eq!(decoded, msg);
     }
 
+    #[test]
+    fn plan_counts_the_samples_hide_changes() {
+        let dir = tempdir().unwrap();
+        let (in_path, out_path) = (dir.path().join("in.wav"), dir.path().join("out.wav"));
+        make_test_wav(&in_path, 10000);
+        let msg = b"\x0f\xff";
+
+        let plan = plan(&in_path, msg, Some(2), None, 1, None).unwrap();
+        // silence has every LSB at 0, so only the 1 bits change anything: one in the length, 12 in the message
+        assert_eq!((plan.bits, plan.capacity_bits, plan.changed, plan.units), (48, 5000, 13, 10000));
+        assert!(!out_path.exists());
+        hide_wav_sparse(&in_path, &out_path, msg, 2).unwrap();
+        assert_eq!(plan.output_bytes, std::fs::metadata(&out_path).unwrap().len());
+    }
+
     #[test]
     fn hide_empty_message() {
         let dir = tempdir().unwrap();
@@ -136,4 +806,288 @@ mod tests {
         let res = find_wav(&in_path);
         assert!(res.is_err());
     }
+
+    #[test]
+    fn sparse_roundtrip_and_probe() {
+        use crate::steg_algorithms::payload::{FrameOptions, Payload};
+
+        let dir = tempdir().unwrap();
+        let in_path = dir.path().join("in.wav");
+        let out_path = dir.path().join("out.wav");
+        make_test_wav(&in_path, 20000);
+
+        let framed = Payload::from_text("every fifth sample").encode(&FrameOptions::default()).unwrap();
+        hide_wav_sparse(&in_path, &out_path, &framed, 5).unwrap();
+
+        assert_eq!(find_wav_sparse(&out_path, Some(5)).unwrap(), framed);
+        assert_eq!(find_wav_sparse(&out_path, None).unwrap(), framed);
+    }
+
+    #[test]
+    fn encrypted_roundtrip() {
+        use crate::steg_algorithms::payload::{DecodeOptions, FrameOptions, Payload};
+
+        let dir = tempdir().unwrap();
+        let in_path = dir.path().join("in.wav");
+        let out_path = dir.path().join("out.wav");
+        make_test_wav(&in_path, 4096);
+
+        let opts = FrameOptions { password: Some("correct horse".to_string()), ..Default::default() };
+        let unlock = DecodeOptions { password: Some("correct horse".to_string()), ..Default::default() };
+        for text in ["", "nobody can hear this"] {
+            hide_wav(&in_path, &out_path, &Payload::from_text(text).encode(&opts).unwrap()).unwrap();
+            let decoded = Payload::decode(&find_wav(&out_path).unwrap(), &unlock).unwrap();
+            assert_eq!(decoded.data, text.as_bytes());
+        }
+    }
+
+    #[test]
+    fn hmac_roundtrip_and_tamper() {
+        use crate::steg_algorithms::payload::{Auth, DecodeOptions, FrameOptions, Payload};
+
+        let dir = tempdir().unwrap();
+        let in_path = dir.path().join("in.wav");
+        let out_path = dir.path().join("out.wav");
+        make_test_wav(&in_path, 4096);
+
+        let key = Some("shared".to_string());
+        let framed = Payload::from_text("tamper evident").encode(&FrameOptions { hmac_key: key.clone(), ..Default::default() }).unwrap();
+        hide_wav(&in_path, &out_path, &framed).unwrap();
+
+        let check = DecodeOptions { hmac_key: key, ..Default::default() };
+        let (p, auth) = Payload::decode_verified(&find_wav(&out_path).unwrap(), &check).unwrap();
+        assert_eq!((p.data.as_slice(), auth), (&b"tamper evident"[..], Auth::Verified));
+
+        // flip one payload LSB (sample 32 + 8*15 sits inside the message text)
+        let mut r = WavReader::open(&out_path).unwrap();
+        let spec = r.spec();
+        let mut samples: Vec<i16> = r.samples::<i16>().map(|s| s.unwrap()).collect();
+        samples[32 + 8 * 15] ^= 1;
+        let mut w = WavWriter::create(&out_path, spec).unwrap();
+        for s in samples { w.write_sample(s).unwrap(); }
+        w.finalize().unwrap();
+        assert!(Payload::decode(&find_wav(&out_path).unwrap(), &check).is_err());
+    }
+
+    #[test]
+    fn fec_repairs_flipped_bytes() {
+        use crate::steg_algorithms::payload::{DecodeOptions, FrameOptions, Payload};
+
+        let dir = tempdir().unwrap();
+        let in_path = dir.path().join("in.wav");
+        let out_path = dir.path().join("out.wav");
+        make_test_wav(&in_path, 8000);
+
+        let framed = Payload::from_text("static on the line").encode(&FrameOptions { fec_parity: Some(32), ..Default::default() }).unwrap();
+        hide_wav(&in_path, &out_path, &framed).unwrap();
+
+        // flip a whole byte of samples (sample 32 + 8*b starts message byte b)
+        let damage = |bytes: &[usize]| {
+            let mut r = WavReader::open(&out_path).unwrap();
+            let spec = r.spec();
+            let mut samples: Vec<i16> = r.samples::<i16>().map(|s| s.unwrap()).collect();
+            for &b in bytes {
+                for s in &mut samples[32 + 8 * b..32 + 8 * b + 8] {
+                    *s ^= 1;
+                }
+            }
+            let mut w = WavWriter::create(&out_path, spec).unwrap();
+            for s in samples { w.write_sample(s).unwrap(); }
+            w.finalize().unwrap();
+        };
+
+        damage(&[0, 12, 33, 50, 51, 52, 53, 70, 90]);
+        let p = Payload::decode(&find_wav(&out_path).unwrap(), &DecodeOptions::default()).unwrap();
+        assert_eq!(p.data, b"static on the line");
+
+        damage(&(34..60).collect::<Vec<_>>());
+        let err = Payload::decode(&find_wav(&out_path).unwrap(), &DecodeOptions::default()).unwrap_err().to_string();
+        assert!(err.contains("Too much damage"), "{}", err);
+    }
+
+    #[test]
+    fn redundancy_survives_clipping_and_flips() {
+        let dir = tempdir().unwrap();
+        let in_path = dir.path().join("in.wav");
+        let out_path = dir.path().join("out.wav");
+        make_test_wav(&in_path, 1200);
+        let msg = b"clip the ending";
+        let data_bits = msg.len() * 8;
+
+        assert!(matches!(hide_wav_redundant(&in_path, &out_path, &[0u8; 50], 1, None, 3), Err(StegError::CapacityExceeded { .. })));
+        hide_wav_redundant(&in_path, &out_path, msg, 1, None, 3).unwrap();
+
+        // cut the clip halfway through the third copy, then flip bits in the first
+        let mut r = WavReader::open(&out_path).unwrap();
+        let spec = r.spec();
+        let mut samples: Vec<i16> = r.samples::<i16>().map(|s| s.unwrap()).collect();
+        samples.truncate(96 + 2 * data_bits + data_bits / 2);
+        for s in &mut samples[96 + 10..96 + 30] {
+            *s ^= 1;
+        }
+        let mut w = WavWriter::create(&out_path, spec).unwrap();
+        for s in samples { w.write_sample(s).unwrap(); }
+        w.finalize().unwrap();
+
+        assert_eq!(find_wav(&out_path).unwrap(), msg);
+    }
+
+    #[test]
+    fn perturb_changes_file_but_not_payload() {
+        use rand::SeedableRng;
+
+        let dir = tempdir().unwrap();
+        let in_path = dir.path().join("in.wav");
+        let out_path = dir.path().join("out.wav");
+        make_test_wav(&in_path, 4096);
+
+        hide_wav_sparse(&in_path, &out_path, b"same bits", 3).unwrap();
+        let before = std::fs::read(&out_path).unwrap();
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(1);
+        assert_eq!(perturb(&out_path, 9, 3, 5, &mut rng).unwrap(), 5);
+
+        let after = std::fs::read(&out_path).unwrap();
+        assert_eq!(before.iter().zip(&after).filter(|(a, b)| a != b).count(), 5);
+        assert_eq!(find_wav_sparse(&out_path, Some(3)).unwrap(), b"same bits");
+    }
+
+    #[test]
+    fn keyed_roundtrip_needs_the_key() {
+        use crate::steg_algorithms::payload::{FrameOptions, Payload};
+
+        let dir = tempdir().unwrap();
+        let in_path = dir.path().join("in.wav");
+        let out_path = dir.path().join("out.wav");
+        make_test_wav(&in_path, 8000);
+
+        let framed = Payload::from_text("spread over the whole clip").encode(&FrameOptions::default()).unwrap();
+        hide_wav_keyed(&in_path, &out_path, &framed, "s3cret").unwrap();
+        assert_eq!(find_wav_keyed(&out_path, "s3cret").unwrap(), framed);
+
+        // regression: keyless find (plain and stride probing) must not recover it
+        assert_ne!(find_wav(&out_path).ok(), Some(framed.clone()));
+        assert_ne!(find_wav_sparse(&out_path, None).ok(), Some(framed.clone()));
+        assert_ne!(find_wav_keyed(&out_path, "guess").ok(), Some(framed.clone()));
+
+        // and the changed samples aren't bunched up at the start
+        let lsbs_in = lsbs(&read_samples(&in_path).unwrap().1);
+        let lsbs_out = lsbs(&read_samples(&out_path).unwrap().1);
+        let last_changed = (0..lsbs_in.len()).rev().find(|&i| lsbs_in[i] != lsbs_out[i]).unwrap();
+        assert!(last_changed > lsbs_in.len() / 2, "last change at {}", last_changed);
+    }
+
+    #[test]
+    fn capacity_is_exact() {
+        let dir = tempdir().unwrap();
+        let in_path = dir.path().join("in.wav");
+        make_test_wav(&in_path, 1000);
+
+        let cap = capacity(&in_path, 2).unwrap();
+        assert_eq!(cap, 500 / 8 - 4);
+        assert!(hide_wav_sparse(&in_path, &dir.path().join("a.wav"), &vec![7u8; cap], 2).is_ok());
+        assert!(hide_wav_sparse(&in_path, &dir.path().join("b.wav"), &vec![7u8; cap + 1], 2).is_err());
+    }
+
+    #[test]
+    fn range_leaves_the_ends_alone_and_is_found_without_the_flag() {
+        use crate::steg_algorithms::payload::{FrameOptions, Payload};
+
+        let dir = tempdir().unwrap();
+        let in_path = dir.path().join("in.wav");
+        let out_path = dir.path().join("out.wav");
+        // 5000 stereo frames, every LSB 1 so the silence around the range can't be mistaken for it
+        make_filled_wav(&in_path, 10000, 1);
+        let range = TimeRange::parse("1000..3000").unwrap();
+        assert_eq!(range.to_string(), "1000..3000");
+        assert_eq!(TimeRange::parse("1.5s..").unwrap().to_string(), "1.5s..");
+
+        let framed = Payload::from_text("between the fades").encode(&FrameOptions::default()).unwrap();
+        assert_eq!(capacity_in(&in_path, 1, Some(&range)).unwrap(), (4000 - 64) / 8 - 4);
+        hide_wav_in(&in_path, &out_path, &framed, 1, None, 1, &range).unwrap();
+        let ((_, a), (_, b)) = (read_samples(&in_path).unwrap(), read_samples(&out_path).unwrap());
+        assert_eq!((&a[..2000], &a[6000..]), (&b[..2000], &b[6000..]));
+        assert_eq!(find_wav_in(&out_path, None, None, Some(&range), None).unwrap().0, framed);
+        assert_eq!(find_wav_limited(&out_path, None, None, None).unwrap().0, framed);
+        assert!(find_wav_in(&out_path, None, None, Some(&TimeRange::parse("1001..").unwrap()), None).unwrap_err().to_string().contains("one opens at sample 2000"));
+
+        // a keyed payload's order depends on where the range ends, so it takes the range to find it
+        hide_wav_in(&in_path, &out_path, &framed, 1, Some("k"), 1, &range).unwrap();
+        assert_eq!(find_wav_in(&out_path, None, Some("k"), Some(&range), None).unwrap().0, framed);
+        assert_ne!(find_wav_limited(&out_path, None, Some("k"), None).ok().map(|(d, _)| d), Some(framed.clone()));
+
+        let past = TimeRange::parse("..6000").unwrap();
+        assert!(hide_wav_in(&in_path, &out_path, &framed, 1, None, 1, &past).unwrap_err().to_string().contains("5000 frames"));
+        let short = TimeRange::parse("10..40").unwrap();
+        assert!(hide_wav_in(&in_path, &out_path, &framed, 1, None, 1, &short).unwrap_err().to_string().contains("too few for the header"));
+        assert!(TimeRange::parse("10s").is_err() && TimeRange::parse("xs..").is_err());
+    }
+
+    #[test]
+    fn all_zero_wav_decodes_empty() {
+        let dir = tempdir().unwrap();
+        let in_path = dir.path().join("zero.wav");
+        make_test_wav(&in_path, 4096);
+
+        // all-zero LSBs read as a zero-length header, which is a valid (empty) payload
+        assert_eq!(find_wav(&in_path).unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn noise_header_is_rejected() {
+        let dir = tempdir().unwrap();
+        let in_path = dir.path().join("ones.wav");
+        // -1 is all ones, so the header claims 0xFFFFFFFF bytes
+        make_filled_wav(&in_path, 4096, -1);
+
+        assert!(matches!(find_wav(&in_path), Err(StegError::NoPayloadFound)), "noise should not decode");
+    }
+
+    #[test]
+    fn bytes_round_trip_without_touching_disk() {
+        let spec = WavSpec { channels: 1, sample_rate: 8000, bits_per_sample: 16, sample_format: SampleFormat::Int };
+        let mut carrier = Cursor::new(Vec::new());
+        let mut w = WavWriter::new(&mut carrier, spec).unwrap();
+        for i in 0..16000 { w.write_sample((i % 200) as i16).unwrap(); }
+        w.finalize().unwrap();
+        let later = TimeRange::parse("1s..").unwrap();
+
+        let opts = HideOptions::default().stride(2).range(later);
+        let stego = hide_bytes_with(carrier.get_ref(), b"uploaded", &opts).unwrap();
+        assert_eq!(find_bytes_with(&stego, &opts.to_find()).unwrap(), b"uploaded");
+        let keyed = hide_bytes_with(carrier.get_ref(), b"uploaded", &HideOptions::default().key("k").copies(3)).unwrap();
+        assert_eq!(find_bytes_with(&keyed, &FindOptions::default().key("k")).unwrap(), b"uploaded");
+        assert!(hide_bytes_with(b"RIFF nonsense", b"x", &HideOptions::default()).is_err());
+        // and the same through the signatures before the options
+        #[allow(deprecated)]
+        let old = hide_bytes(carrier.get_ref(), b"uploaded", Some(2), None, 1, Some(&later)).unwrap();
+        assert_eq!(old, stego);
+    }
+
+    #[test]
+    fn streams_without_seeking_the_carrier() {
+        let dir = tempdir().unwrap();
+        let (in_path, out_path) = (dir.path().join("in.wav"), dir.path().join("out.wav"));
+        make_filled_wav(&in_path, 20000, 3);
+        let cover = std::fs::read(&in_path).unwrap();
+
+        // a byte slice reads and a Vec writes without seeking, like a socket; the header is written
+        // up front the way hound finishes it
+        let mut out = Cursor::new(Vec::new());
+        let laid = hide_stream_with(&cover[..], out.get_mut(), b"streamed", &HideOptions::default().key("k")).unwrap();
+        assert_eq!(find_stream_with(&out.get_ref()[..], &FindOptions::default().key("k")).unwrap(), b"streamed");
+        assert_eq!((out.get_ref().len(), &out.get_ref()[..44]), (cover.len(), &cover[..44]));
+
+        // the path functions are the same thing through files, hiding into the cover itself included
+        hide_wav_keyed(&in_path, &out_path, b"streamed", "k").unwrap();
+        assert_eq!(std::fs::read(&out_path).unwrap(), *out.get_ref());
+        let planned = plan(&in_path, b"streamed", None, Some("k"), 1, None).unwrap();
+        assert_eq!((planned.changed, planned.output_bytes), (laid.changed, out.get_ref().len() as u64));
+        hide_wav_keyed(&in_path, &in_path, b"streamed", "k").unwrap();
+        assert_eq!(std::fs::read(&in_path).unwrap(), *out.get_ref());
+
+        // a failed hide leaves no output behind
+        std::fs::remove_file(&out_path).unwrap();
+        assert!(hide_wav(&in_path, &out_path, &[0; 4096]).is_err());
+        assert!(!out_path.exists());
+    }
 }
\ No newline at end of file
@@ -1 +1,4 @@
-pub mod lsb;
\ No newline at end of file
+#[cfg(feature = "audio")]
+pub mod beat;
+pub mod lsb;
+pub mod riff;
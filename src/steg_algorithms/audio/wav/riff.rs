@@ -0,0 +1,75 @@
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::parse::{Chunk, Mode, Parsed, Walker};
+
+// RIFF's chunk layout, which WAV files are in. Integers are little-endian:
+//
+//   "RIFF"  4 bytes
+//   size    4 bytes, of everything after it up to the end of the last chunk
+//   form    4 bytes, "WAVE" for WAV
+//
+// then chunks of id (4 bytes), size (4 bytes, of the data) and data, padded to an even length. Whatever
+// follows the RIFF chunk isn't part of the file (see scan for what it may be).
+
+/// The form type and chunks of the RIFF file in `buf`, the walk's `end` being the end of the RIFF chunk.
+/// Lenient mode goes by the file's length when the RIFF size is wrong, and keeps a chunk cut short.
+pub fn chunks(buf: &[u8], mode: Mode) -> Result<([u8; 4], Parsed<Chunk>), StegError> {
+    if buf.len() < 12 || !buf.starts_with(b"RIFF") {
+        return Err("Not a RIFF file".into());
+    }
+    let mut walk = Walker::new(mode);
+    let form = [buf[8], buf[9], buf[10], buf[11]];
+    let size = u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]) as usize;
+    let mut end = 8 + size;
+    if end > buf.len() {
+        walk.violation(4, format!("The RIFF size says {} bytes but the file has {}", size, buf.len() - 8))?;
+        end = buf.len();
+    }
+    let mut pos = 12;
+    while pos < end {
+        let Some(header) = buf.get(pos..pos + 8).filter(|_| pos + 8 <= end) else {
+            walk.violation(pos, "Chunk header cut short")?;
+            break;
+        };
+        let id = [header[0], header[1], header[2], header[3]];
+        let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        let padded = pos + 8 + len.div_ceil(2) * 2;
+        if padded > end {
+            let name = String::from_utf8_lossy(&id).into_owned();
+            // the pad byte of the very last chunk is often left out
+            if pos + 8 + len == end {
+                walk.violation(pos, format!("'{}' chunk has no pad byte", name))?;
+            } else {
+                walk.violation(pos, format!("'{}' chunk runs past the end of the RIFF chunk", name))?;
+            }
+            walk.push(Chunk { id, start: pos, end });
+            break;
+        }
+        walk.push(Chunk { id, start: pos, end: padded });
+        pos = padded;
+    }
+    Ok((form, walk.finish(Some(end))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_are_checked_or_clamped() {
+        let mut wav = b"RIFF\0\0\0\0WAVEfmt \x02\0\0\0abdata\x03\0\0\0xyz\0".to_vec();
+        let size = (wav.len() - 8) as u32;
+        wav[4..8].copy_from_slice(&size.to_le_bytes());
+        let (form, parsed) = chunks(&wav, Mode::Strict).unwrap();
+        assert_eq!(&form, b"WAVE");
+        assert_eq!(parsed.items.iter().map(|c| (&c.id, c.end)).collect::<Vec<_>>(), [(b"fmt ", 22), (b"data", wav.len())]);
+        assert_eq!(parsed.end, Some(wav.len()));
+
+        // a truncated download: the sizes promise more than is there
+        let cut = &wav[..wav.len() - 2];
+        assert!(chunks(cut, Mode::Strict).unwrap_err().to_string().contains("RIFF size"));
+        let (_, parsed) = chunks(cut, Mode::Lenient).unwrap();
+        assert_eq!((parsed.items.len(), parsed.end, parsed.problems.len()), (2, Some(cut.len()), 2));
+
+        assert!(chunks(b"RIFX\0\0\0\0WAVE", Mode::Lenient).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use serde_json::Value;
+use crate::steg_algorithms::delta::sha256_hex;
+use crate::steg_algorithms::error::StegError;
+
+// Append-only audit log for `--audit-log`, one JSON object per line. Every line carries `prev`, the
+// SHA-256 of the line before it (all zeros for the first), so editing or dropping an earlier entry breaks
+// the chain and `verify` points at it. Only hashes and settings are recorded, never passwords or keys.
+
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// One hide/extract operation, as the caller describes it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Record {
+    /// "hide" or "find"
+    pub op: &'static str,
+    pub filetype: String,
+    pub algorithm: String,
+    pub input: String,
+    pub input_sha256: String,
+    pub output: Option<String>,
+    pub output_sha256: Option<String>,
+    /// Hash of the message/file that was embedded or recovered.
+    pub payload_sha256: String,
+    /// Settings that shape the output. Say whether a password or key was used, don't include it.
+    pub params: Value,
+}
+
+/// SHA-256 of a file, for `Record`.
+pub fn file_sha256(path: &Path) -> Result<String, StegError> {
+    fs::read(path).map(|b| sha256_hex(&b)).map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+/// Append `record` to the log at `path` (created if missing), chained to the last entry.
+pub fn append(path: &Path, record: &Record) -> Result<(), StegError> {
+    let existing = match fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Failed to read audit log {}: {}", path.display(), e).into()),
+    };
+    let last = existing.lines().rfind(|l| !l.trim().is_empty());
+    let seq = match last {
+        Some(l) => parse(l)?.get("seq").and_then(Value::as_u64).ok_or("Audit log's last entry has no seq")? + 1,
+        None => 0,
+    };
+
+    let mut entry = serde_json::to_value(record).map_err(|e| e.to_string())?;
+    let obj = entry.as_object_mut().ok_or("audit record must be an object")?;
+    obj.insert("seq".into(), seq.into());
+    obj.insert("unix_time".into(), SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()).into());
+    obj.insert("prev".into(), last.map_or_else(|| GENESIS.to_string(), |l| sha256_hex(l.as_bytes())).into());
+
+    let mut f = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open audit log {}: {}", path.display(), e))?;
+    // a log whose last line lost its newline would glue two entries together
+    let sep = if existing.is_empty() || existing.ends_with('\n') { "" } else { "\n" };
+    writeln!(f, "{}{}", sep, entry).map_err(|e| format!("Failed to write audit log {}: {}", path.display(), e).into())
+}
+
+/// Check the hash chain of the log at `path`, returning how many entries it holds.
+pub fn verify(path: &Path) -> Result<usize, StegError> {
+    let text = fs::read_to_string(path).map_err(|e| StegError::io(format!("Failed to read audit log {}", path.display()), e))?;
+    let mut prev = GENESIS.to_string();
+    let mut count = 0;
+    for (i, line) in text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        let entry = parse(line).map_err(|e| format!("line {}: {}", i + 1, e))?;
+        if entry.get("prev").and_then(Value::as_str) != Some(prev.as_str()) {
+            return Err(format!("line {}: hash chain broken, an earlier entry was changed or removed", i + 1).into());
+        }
+        if entry.get("seq").and_then(Value::as_u64) != Some(count as u64) {
+            return Err(format!("line {}: expected entry {}", i + 1, count).into());
+        }
+        prev = sha256_hex(line.as_bytes());
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn parse(line: &str) -> Result<Value, StegError> {
+    serde_json::from_str(line).map_err(|e| format!("Audit log entry is not valid JSON: {}", e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    fn record(n: u32) -> Record {
+        Record {
+            op: "hide",
+            filetype: "picture".into(),
+            algorithm: "lsb".into(),
+            input: format!("in{}.png", n),
+            input_sha256: sha256_hex(b"in"),
+            output: Some("out.png".into()),
+            output_sha256: Some(sha256_hex(b"out")),
+            payload_sha256: sha256_hex(b"msg"),
+            params: json!({ "stride": n, "encrypted": true }),
+        }
+    }
+
+    #[test]
+    fn chain_verifies_and_detects_edits() {
+        let dir = tempdir().unwrap();
+        let log = dir.path().join("audit.jsonl");
+        for n in 0..3 {
+            append(&log, &record(n)).unwrap();
+        }
+        assert_eq!(verify(&log).unwrap(), 3);
+
+        let text = fs::read_to_string(&log).unwrap();
+        fs::write(&log, text.replacen("in1.png", "in9.png", 1)).unwrap();
+        let err = verify(&log).unwrap_err().to_string();
+        assert!(err.starts_with("line 3: hash chain broken"), "{}", err);
+
+        // dropping an entry is caught too
+        let lines: Vec<&str> = text.lines().collect();
+        fs::write(&log, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        assert!(verify(&log).is_err());
+    }
+}
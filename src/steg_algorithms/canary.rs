@@ -0,0 +1,142 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use crate::steg_algorithms::error::StegError;
+
+// Canary (honeytoken) payloads. `canary` embeds a beacon that calls home when someone follows it: a URL
+// (`--token-url https://canary.example/x` -> https://canary.example/x/<token>) or a DNS name
+// (`--token-domain canary.example` -> <token>.canary.example). Every asset gets a fresh token, and the
+// registry (JSON lines) remembers which asset went to which recipient, so `identify` can turn a
+// triggered alert back into "this is the copy we sent to X".
+
+/// Random bytes per token, printed as lowercase hex.
+const TOKEN_BYTES: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Beacon<'a> {
+    /// Base URL, the token is appended as a path segment (or replaces `{token}`).
+    Url(&'a str),
+    /// Domain, the token becomes the leftmost label.
+    Dns(&'a str),
+}
+
+impl Beacon<'_> {
+    /// The beacon text for `token`.
+    pub fn render(self, token: &str) -> String {
+        match self {
+            Beacon::Url(base) if base.contains("{token}") => base.replace("{token}", token),
+            Beacon::Url(base) => format!("{}/{}", base.trim_end_matches('/'), token),
+            Beacon::Dns(domain) => format!("{}.{}", token, domain.trim_start_matches('.')),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub token: String,
+    pub beacon: String,
+    pub recipient: Option<String>,
+    /// The cover the canary was made from.
+    pub asset: String,
+    pub output: String,
+    pub output_sha256: String,
+    pub unix_time: u64,
+}
+
+pub fn new_token(rng: &mut impl RngCore) -> String {
+    let mut raw = [0u8; TOKEN_BYTES];
+    rng.fill_bytes(&mut raw);
+    raw.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Pull a token out of whatever the alert handed us: the token itself, the URL or the hostname.
+pub fn extract_token(triggered: &str) -> Option<String> {
+    let len = TOKEN_BYTES * 2;
+    let chars: Vec<char> = triggered.chars().collect();
+    chars
+        .split(|c| !c.is_ascii_hexdigit())
+        .find(|run| run.len() == len)
+        .map(|run| run.iter().collect::<String>().to_lowercase())
+}
+
+/// Build a registry entry stamped with the current time.
+pub fn entry(token: String, beacon: String, recipient: Option<String>, asset: &Path, output: &Path, output_sha256: String) -> Entry {
+    Entry {
+        token,
+        beacon,
+        recipient,
+        asset: asset.display().to_string(),
+        output: output.display().to_string(),
+        output_sha256,
+        unix_time: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+    }
+}
+
+pub fn record(registry: &Path, entry: &Entry) -> Result<(), StegError> {
+    let line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+    let mut f = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(registry)
+        .map_err(|e| format!("Failed to open canary registry {}: {}", registry.display(), e))?;
+    writeln!(f, "{}", line).map_err(|e| format!("Failed to write canary registry {}: {}", registry.display(), e).into())
+}
+
+/// Find the registry entry for the token in `triggered`.
+pub fn identify(registry: &Path, triggered: &str) -> Result<Entry, StegError> {
+    let token = extract_token(triggered).ok_or_else(|| format!("No canary token (32 hex digits) in '{}'", triggered))?;
+    let text = fs::read_to_string(registry).map_err(|e| StegError::io(format!("Failed to read canary registry {}", registry.display()), e))?;
+    for (i, line) in text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        let entry: Entry = serde_json::from_str(line).map_err(|e| format!("{} line {}: {}", registry.display(), i + 1, e))?;
+        if entry.token == token {
+            return Ok(entry);
+        }
+    }
+    Err(format!("Token {} is not in {}", token, registry.display()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use tempfile::tempdir;
+
+    #[test]
+    fn beacons_and_token_extraction() {
+        let token = new_token(&mut rand_chacha::ChaCha20Rng::seed_from_u64(1));
+        assert_eq!(token.len(), 32);
+
+        let url = Beacon::Url("https://canary.example/x/").render(&token);
+        assert_eq!(url, format!("https://canary.example/x/{}", token));
+        assert_eq!(Beacon::Url("https://c.example/{token}.png").render("ab"), "https://c.example/ab.png");
+        let host = Beacon::Dns("canary.example").render(&token);
+        assert_eq!(host, format!("{}.canary.example", token));
+
+        for alert in [url, host, token.to_uppercase(), format!("GET /x/{}?utm=1", token)] {
+            assert_eq!(extract_token(&alert).as_deref(), Some(token.as_str()), "{}", alert);
+        }
+        assert_eq!(extract_token("https://canary.example/x/deadbeef"), None);
+    }
+
+    #[test]
+    fn registry_maps_token_back_to_recipient() {
+        let dir = tempdir().unwrap();
+        let registry = dir.path().join("canaries.jsonl");
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(2);
+        let mut tokens = Vec::new();
+        for who in ["alice", "bob"] {
+            let token = new_token(&mut rng);
+            let beacon = Beacon::Dns("c.example").render(&token);
+            let e = entry(token.clone(), beacon, Some(who.to_string()), Path::new("deck.png"), Path::new("out.png"), "00".into());
+            record(&registry, &e).unwrap();
+            tokens.push(token);
+        }
+
+        let hit = identify(&registry, &format!("{}.c.example", tokens[1])).unwrap();
+        assert_eq!(hit.recipient.as_deref(), Some("bob"));
+        assert!(identify(&registry, &"0".repeat(32)).unwrap_err().to_string().contains("not in"));
+    }
+}
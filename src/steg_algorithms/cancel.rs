@@ -0,0 +1,131 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use crate::steg_algorithms::workspace;
+
+// Ctrl-C. Work that goes in units (the files of a batch, the files of a scan) cooperates: while it runs
+// an interrupt only sets a flag, the unit in progress finishes, the rest isn't started and a summary says
+// how far it got. Anything else can't stop halfway cleanly, so an interrupt rolls back instead: the
+// outputs being written are removed, as are the workspaces, and the process exits. A second Ctrl-C
+// always rolls back right away.
+
+/// The interrupt state. The process has one (behind the free functions below); tests make their own.
+#[derive(Default)]
+pub struct Cancel {
+    cancelled: AtomicBool,
+    cooperating: AtomicUsize,
+    // outputs that are being written and don't hold anything useful until that's done
+    pending: Mutex<Vec<PathBuf>>,
+}
+
+static PROCESS: Cancel = Cancel::new();
+
+/// Marks work that checks `requested()` between units, for as long as it's alive.
+pub struct Cooperating<'a>(&'a Cancel);
+
+impl Drop for Cooperating<'_> {
+    fn drop(&mut self) {
+        self.0.cooperating.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// An output that an interrupt removes until the guard is dropped, which is when whatever wrote it has
+/// either finished it or cleaned up after itself.
+pub struct Pending<'a>(&'a Cancel, PathBuf);
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        let mut pending = self.0.pending.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(at) = pending.iter().position(|p| *p == self.1) {
+            pending.remove(at);
+        }
+    }
+}
+
+/// What an interrupt should do now.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Interrupt {
+    /// Cooperating work will stop after its current unit.
+    Stopping,
+    /// Nothing could stop cleanly: these partial outputs were removed, and the process should exit.
+    RolledBack(Vec<PathBuf>),
+}
+
+impl Cancel {
+    pub const fn new() -> Self {
+        Cancel { cancelled: AtomicBool::new(false), cooperating: AtomicUsize::new(0), pending: Mutex::new(Vec::new()) }
+    }
+
+    pub fn requested(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn cooperate(&self) -> Cooperating<'_> {
+        self.cooperating.fetch_add(1, Ordering::SeqCst);
+        Cooperating(self)
+    }
+
+    pub fn pending(&self, path: &Path) -> Pending<'_> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).push(path.to_path_buf());
+        Pending(self, path.to_path_buf())
+    }
+
+    pub fn interrupt(&self) -> Interrupt {
+        if self.cooperating.load(Ordering::SeqCst) > 0 && !self.cancelled.swap(true, Ordering::SeqCst) {
+            return Interrupt::Stopping;
+        }
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        Interrupt::RolledBack(pending.drain(..).filter(|p| fs::remove_file(p).is_ok()).collect())
+    }
+}
+
+/// Whether an interrupt asked cooperating work to stop after the current unit.
+pub fn requested() -> bool {
+    PROCESS.requested()
+}
+
+pub fn cooperate() -> Cooperating<'static> {
+    PROCESS.cooperate()
+}
+
+pub fn pending(path: &Path) -> Pending<'static> {
+    PROCESS.pending(path)
+}
+
+/// Handle one interrupt of the process (from the Ctrl-C handler). A roll back takes the workspaces
+/// with it.
+pub fn interrupt() -> Interrupt {
+    let outcome = PROCESS.interrupt();
+    if let Interrupt::RolledBack(_) = outcome {
+        workspace::remove_all();
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn interrupts_stop_cooperating_work_and_roll_back_the_rest() {
+        let cancel = Cancel::new();
+        let dir = tempdir().unwrap();
+        let (done, partial) = (dir.path().join("done.png"), dir.path().join("partial.png"));
+        fs::write(&done, b"x").unwrap();
+        fs::write(&partial, b"x").unwrap();
+        drop(cancel.pending(&done));
+        let _writing = cancel.pending(&partial);
+        assert_eq!(cancel.interrupt(), Interrupt::RolledBack(vec![partial.clone()]), "nothing could stop cleanly");
+        fs::write(&partial, b"x").unwrap();
+        let _writing = cancel.pending(&partial);
+
+        let batch = cancel.cooperate();
+        assert_eq!(cancel.interrupt(), Interrupt::Stopping);
+        assert!(cancel.requested() && partial.exists());
+        assert_eq!(cancel.interrupt(), Interrupt::RolledBack(vec![partial.clone()]), "the second one doesn't wait");
+        drop(batch);
+        assert!(done.exists() && !partial.exists());
+    }
+}
@@ -0,0 +1,356 @@
+use serde::Serialize;
+use serde_json::{Value, json};
+use crate::steg_algorithms::{fec, redundancy};
+#[cfg(feature = "picture")]
+use crate::steg_algorithms::picture::general::{lineshift, overlay};
+
+// Machine-readable description of every algorithm and its options, so front-ends can build their
+// forms from this instead of knowing each algorithm. `list-algorithms --json` prints `to_json()`.
+// Option names are the CLI long flags without the dashes; keep this in sync with main.rs.
+
+/// What kind of value an option takes.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OptionKind {
+    /// On/off switch, no value.
+    Flag,
+    Integer { min: i64, max: Option<i64> },
+    Number { min: f64, max: f64 },
+    /// Free text, `format` describes anything stricter.
+    Text { format: Option<&'static str> },
+    Choice { values: Vec<&'static str> },
+    Path,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OptionInfo {
+    pub name: &'static str,
+    #[serde(flatten)]
+    pub kind: OptionKind,
+    pub help: &'static str,
+    pub default: Option<Value>,
+    /// Options that can't be combined with this one.
+    pub conflicts: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AlgorithmInfo {
+    pub name: &'static str,
+    pub filetype: &'static str,
+    pub summary: &'static str,
+    /// How much fits, in words.
+    pub capacity: &'static str,
+    /// Output extensions the payload survives.
+    pub outputs: Vec<&'static str>,
+    /// Whether the payload is framed, i.e. whether `framing_options()` apply.
+    pub framed: bool,
+    /// Whether the payload lives in the samples themselves, so only a lossless carrier keeps it.
+    pub lossless_only: bool,
+    pub hide_options: Vec<OptionInfo>,
+    pub find_options: Vec<OptionInfo>,
+}
+
+fn opt(name: &'static str, kind: OptionKind, help: &'static str, default: Option<Value>) -> OptionInfo {
+    OptionInfo { name, kind, help, default, conflicts: Vec::new() }
+}
+
+fn conflicting(mut o: OptionInfo, with: &[&'static str]) -> OptionInfo {
+    o.conflicts = with.to_vec();
+    o
+}
+
+fn text() -> OptionKind {
+    OptionKind::Text { format: None }
+}
+
+/// Options every framed algorithm accepts at hide time (find only needs password and hmac-key back).
+pub fn framing_options() -> Vec<OptionInfo> {
+    vec![
+        opt("compress", OptionKind::Flag, "Deflate the payload before embedding", None),
+        opt("password", text(), "Encrypt the payload, find needs the same password", None),
+        opt(
+            "cipher",
+            OptionKind::Choice { values: vec!["aes-256-gcm", "xchacha20-poly1305"] },
+            "Cipher for password",
+            Some(json!("aes-256-gcm")),
+        ),
+        opt("hmac-key", text(), "Append an HMAC-SHA256 tag find can verify", None),
+        opt(
+            "pad",
+            OptionKind::Text { format: Some("byte count or \"random\"") },
+            "Pad the embedded data with random bytes up to this size",
+            None,
+        ),
+        opt("meta", OptionKind::Flag, "Stamp the payload with time, tool version and algorithm", None),
+    ]
+}
+
+fn lsb_options(stride_help: &'static str) -> (Vec<OptionInfo>, Vec<OptionInfo>) {
+    let stride = conflicting(
+        opt("stride", OptionKind::Integer { min: 1, max: None }, stride_help, Some(json!(1))),
+        &["key"],
+    );
+    let key = conflicting(opt("key", text(), "Scatter the bits in an order only this key reproduces", None), &["stride"]);
+    let hide = vec![
+        stride.clone(),
+        key.clone(),
+        opt(
+            "perturb",
+            OptionKind::Integer { min: 0, max: None },
+            "Also flip this many random LSBs outside the payload",
+            Some(json!(0)),
+        ),
+        opt(
+            "fec",
+            OptionKind::Text { format: Some("rs or rs:<parity 2-128>") },
+            "Reed-Solomon protect the payload",
+            None,
+        ),
+        conflicting(
+            opt(
+                "redundancy",
+                OptionKind::Integer { min: 1, max: Some(redundancy::MAX_REDUNDANCY as i64) },
+                "Store every bit this many times (odd) and majority-vote on find",
+                Some(json!(1)),
+            ),
+            &["target-quality"],
+        ),
+    ];
+    let find = vec![
+        OptionInfo { default: None, help: "Stride used at hide time, probed when omitted", ..stride },
+        key,
+    ];
+    (hide, find)
+}
+
+#[cfg(feature = "picture")]
+fn picture_lsb_options() -> (Vec<OptionInfo>, Vec<OptionInfo>) {
+    let (mut picture_lsb_hide, mut picture_lsb_find) = lsb_options("Put a bit in every Nth pixel channel");
+    let offset = opt(
+        "offset",
+        OptionKind::Integer { min: 0, max: None },
+        "Leave this many RGB channel slots at the start alone, find needs the same",
+        Some(json!(0)),
+    );
+    picture_lsb_hide.push(conflicting(offset.clone(), &["target-quality"]));
+    picture_lsb_find.push(offset);
+    picture_lsb_hide.push(conflicting(
+        opt(
+            "prenoise",
+            OptionKind::Number { min: 0.0, max: 16.0 },
+            "Add Gaussian noise with this standard deviation to the cover first, seeded from the key",
+            None,
+        ),
+        &["target-quality"],
+    ));
+    (picture_lsb_hide, picture_lsb_find)
+}
+
+/// The algorithms in this build, the ones a feature left out not listed.
+pub fn algorithms() -> Vec<AlgorithmInfo> {
+    #[cfg(feature = "picture")]
+    let (picture_lsb_hide, picture_lsb_find) = picture_lsb_options();
+    #[cfg(feature = "audio")]
+    let (wav_lsb_hide, wav_lsb_find) = lsb_options("Put a bit in every Nth sample");
+    let (mut dicom_lsb_hide, dicom_lsb_find) = lsb_options("Put a bit in every Nth pixel sample");
+    dicom_lsb_hide.retain(|o| o.name != "perturb");
+    let (mut fits_lsb_hide, fits_lsb_find) = lsb_options("Put a bit in every Nth finite sample");
+    fits_lsb_hide.retain(|o| o.name != "perturb");
+    let app_id = opt(
+        "app-id",
+        OptionKind::Text { format: Some("exactly 11 bytes") },
+        "GIF application identifier",
+        Some(json!("RSTEGANO1.0")),
+    );
+
+    vec![
+        #[cfg(feature = "picture")]
+        AlgorithmInfo {
+            name: "lsb",
+            filetype: "picture",
+            summary: "Least significant bits of the RGB channels",
+            capacity: "1 to 4 bits per RGB channel (lsb:bits=N, fewer channels with channels=b or b:2, alpha too with alpha=true, divided by stride, only pixels at least threshold=N textured with traversal=texture), minus a 4-byte length",
+            outputs: vec!["png", "bmp", "tif", "tga", "qoi", "ppm", "pgm", "pnm", "pam", "ff"],
+            framed: true,
+            lossless_only: true,
+            hide_options: picture_lsb_hide,
+            find_options: picture_lsb_find,
+        },
+        #[cfg(feature = "jpeg-marker")]
+        AlgorithmInfo {
+            name: "marker",
+            filetype: "picture",
+            summary: "Payload in JPEG APPn segments, pixels untouched",
+            capacity: "unlimited (split over 64 KB segments)",
+            outputs: vec!["jpg"],
+            framed: true,
+            lossless_only: false,
+            hide_options: Vec::new(),
+            find_options: Vec::new(),
+        },
+        #[cfg(feature = "picture")]
+        AlgorithmInfo {
+            name: "overlay",
+            filetype: "picture",
+            summary: "Spread-spectrum brightness pattern that survives JPEG, resizing and being photographed",
+            capacity: "62 bytes, pictures at least 256 px on each side",
+            outputs: vec!["png", "jpg", "bmp", "tif", "webp"],
+            framed: true,
+            lossless_only: false,
+            hide_options: vec![opt(
+                "strength",
+                OptionKind::Integer { min: 1, max: Some(32) },
+                "How far each pixel's brightness is pushed",
+                Some(json!(overlay::DEFAULT_STRENGTH)),
+            )],
+            find_options: Vec::new(),
+        },
+        #[cfg(feature = "picture")]
+        AlgorithmInfo {
+            name: "lineshift",
+            filetype: "picture",
+            summary: "Moves text lines of a scanned document up or down",
+            capacity: "1 bit per two text lines, raw bytes only",
+            outputs: vec!["png", "jpg", "bmp", "tif"],
+            framed: false,
+            lossless_only: false,
+            hide_options: vec![opt(
+                "shift",
+                OptionKind::Integer { min: 1, max: Some(8) },
+                "How many pixels each marked line moves",
+                Some(json!(lineshift::DEFAULT_SHIFT)),
+            )],
+            find_options: Vec::new(),
+        },
+        AlgorithmInfo {
+            name: "appext",
+            filetype: "picture",
+            summary: "Payload in a GIF application extension block",
+            capacity: "unlimited",
+            outputs: vec!["gif"],
+            framed: true,
+            lossless_only: false,
+            hide_options: vec![app_id.clone()],
+            find_options: vec![app_id],
+        },
+        #[cfg(feature = "audio")]
+        AlgorithmInfo {
+            name: "lsb",
+            filetype: "audio",
+            summary: "Least significant bit of PCM16 WAV samples",
+            capacity: "1 bit per sample (divided by stride), minus a 4-byte length",
+            outputs: vec!["wav"],
+            framed: true,
+            lossless_only: true,
+            hide_options: wav_lsb_hide,
+            find_options: wav_lsb_find,
+        },
+        #[cfg(feature = "audio")]
+        AlgorithmInfo {
+            name: "beat",
+            filetype: "audio",
+            summary: "Payload frames in the sample LSBs at each detected beat, so loops and beat-aligned cuts keep it",
+            capacity: "32 bytes per beat, each chunk repeated over the beats left over",
+            outputs: vec!["wav"],
+            framed: true,
+            lossless_only: true,
+            hide_options: Vec::new(),
+            find_options: Vec::new(),
+        },
+        AlgorithmInfo {
+            name: "tag",
+            filetype: "medical",
+            summary: "Payload in a private DICOM element, pixels and other tags untouched",
+            capacity: "unlimited (up to 4 GB)",
+            outputs: vec!["dcm"],
+            framed: true,
+            lossless_only: false,
+            hide_options: Vec::new(),
+            find_options: Vec::new(),
+        },
+        AlgorithmInfo {
+            name: "lsb",
+            filetype: "medical",
+            summary: "Least significant bits of uncompressed 8/16-bit DICOM pixel data",
+            capacity: "1 bit per pixel sample (divided by stride), minus a 4-byte length",
+            outputs: vec!["dcm"],
+            framed: true,
+            lossless_only: true,
+            hide_options: dicom_lsb_hide,
+            find_options: dicom_lsb_find,
+        },
+        AlgorithmInfo {
+            name: "lsb",
+            filetype: "astro",
+            summary: "Lowest mantissa bit of floating-point FITS image samples",
+            capacity: "1 bit per finite sample (divided by stride), minus a 4-byte length",
+            outputs: vec!["fits"],
+            framed: true,
+            lossless_only: true,
+            hide_options: fits_lsb_hide,
+            find_options: fits_lsb_find,
+        },
+        AlgorithmInfo {
+            name: "cards",
+            filetype: "astro",
+            summary: "Payload as hex in COMMENT cards of the FITS primary header, data untouched",
+            capacity: "unlimited (up to 4 GB)",
+            outputs: vec!["fits"],
+            framed: true,
+            lossless_only: false,
+            hide_options: Vec::new(),
+            find_options: Vec::new(),
+        },
+    ]
+}
+
+/// Everything above as one JSON document.
+pub fn to_json() -> String {
+    let doc = json!({
+        "algorithms": algorithms(),
+        "framing_options": framing_options(),
+        "fec_default_parity": fec::DEFAULT_PARITY,
+    });
+    // plain data, serializing can't fail
+    serde_json::to_string_pretty(&doc).expect("catalog serializes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "picture")]
+    #[test]
+    fn json_describes_lsb_stride() {
+        let doc: Value = serde_json::from_str(&to_json()).unwrap();
+        let lsb = doc["algorithms"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|a| a["name"] == "lsb" && a["filetype"] == "picture")
+            .unwrap();
+        let stride = lsb["hide_options"].as_array().unwrap().iter().find(|o| o["name"] == "stride").unwrap();
+        assert_eq!(stride["type"], "integer");
+        assert_eq!(stride["min"], 1);
+        assert_eq!(stride["default"], 1);
+        assert_eq!(stride["conflicts"], json!(["key"]));
+    }
+
+    #[test]
+    fn only_sample_carriers_need_lossless_outputs() {
+        for a in algorithms() {
+            assert_eq!(a.lossless_only, a.name == "lsb" || a.name == "beat", "{} {}", a.filetype, a.name);
+            if a.lossless_only {
+                assert!(!a.outputs.contains(&"jpg"), "{} {} can't list a lossy output", a.filetype, a.name);
+            }
+        }
+    }
+
+    #[test]
+    fn names_are_unique_per_filetype() {
+        let all = algorithms();
+        for a in &all {
+            assert_eq!(all.iter().filter(|b| b.name == a.name && b.filetype == a.filetype).count(), 1);
+        }
+    }
+}
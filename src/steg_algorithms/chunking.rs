@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use crate::steg_algorithms::error::StegError;
+
+// Spreading one payload over a directory of carriers (`hide --span`). The payload is cut at
+// content-defined boundaries (a gear rolling hash, as in FastCDC), so an edit only moves the boundaries
+// near it and every other chunk comes out byte for byte the same. A chunk's id is a hash of its bytes,
+// which is what lets a second `hide --span` see which carriers already hold a chunk it needs and leave
+// them alone: only the chunks around the edit and the manifest get written again.
+//
+// Each carrier holds one record as its payload data (framed like any other payload, so --password etc.
+// apply per carrier). All integers are big-endian:
+//
+//   magic     4 bytes   "RSCD"
+//   kind      1 byte    KIND_PIECE or KIND_MANIFEST
+//   piece:    the chunk's bytes, its id being chunk_id() of them
+//   manifest: generation (4 bytes) | total_len (8 bytes) | sha256 (32 bytes) of the whole payload
+//             | name_len (1 byte) | name | count (4 bytes) | count x (id (16 bytes) | len (4 bytes))
+//
+// The generation goes up by one with every update, so a stale manifest left in a carrier that was too
+// small for the new one loses to it.
+
+pub const MAGIC: [u8; 4] = *b"RSCD";
+const KIND_PIECE: u8 = 0;
+const KIND_MANIFEST: u8 = 1;
+pub const ID_LEN: usize = 16;
+/// Average chunk size when --chunk-size isn't given.
+pub const DEFAULT_CHUNK: u32 = 4096;
+
+pub type ChunkId = [u8; ID_LEN];
+
+// 256 fixed pseudo-random words for the rolling hash (splitmix64), the same in every build
+const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+pub fn chunk_id(data: &[u8]) -> ChunkId {
+    Sha256::digest(data)[..ID_LEN].try_into().expect("sha256 is longer than an id")
+}
+
+pub fn hex(id: &[u8]) -> String {
+    id.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Length of the first chunk of `data`: a boundary is where the hash's top bits are all zero, at least
+/// `avg / 4` and at most `avg * 4` bytes in.
+fn boundary(data: &[u8], avg: usize) -> usize {
+    let (min, max) = ((avg / 4).max(1), avg.saturating_mul(4).min(data.len()));
+    if data.len() <= min {
+        return data.len();
+    }
+    // the high bits of a gear hash depend on the most bytes, so that's where the mask goes
+    let bits = avg.next_power_of_two().trailing_zeros();
+    let mask = if bits == 0 { 0 } else { u64::MAX << (64 - bits) };
+    let mut hash = 0u64;
+    for (i, &b) in data.iter().enumerate().take(max).skip(min) {
+        hash = (hash << 1).wrapping_add(GEAR[b as usize]);
+        if hash & mask == 0 {
+            return i + 1;
+        }
+    }
+    max
+}
+
+/// Cut `data` into content-defined chunks averaging about `avg` bytes.
+pub fn cut(data: &[u8], avg: usize) -> Vec<&[u8]> {
+    let mut chunks = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let (chunk, tail) = rest.split_at(boundary(rest, avg));
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
+
+/// Which chunks, in which order, make up the payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    pub generation: u32,
+    /// The payload's filename, when it came from --msg-file.
+    pub name: Option<String>,
+    pub len: u64,
+    pub sha256: [u8; 32],
+    pub chunks: Vec<(ChunkId, u32)>,
+}
+
+impl Manifest {
+    /// Cut `data` up, returning the manifest and the distinct chunks it needs (a chunk that repeats is
+    /// only stored once).
+    pub fn new(data: &[u8], name: Option<String>, avg: usize, generation: u32) -> (Manifest, Vec<&[u8]>) {
+        let mut distinct: Vec<&[u8]> = Vec::new();
+        let mut chunks = Vec::new();
+        for chunk in cut(data, avg) {
+            let id = chunk_id(chunk);
+            if !chunks.iter().any(|(seen, _)| *seen == id) {
+                distinct.push(chunk);
+            }
+            chunks.push((id, chunk.len() as u32));
+        }
+        let manifest = Manifest { generation, name, len: data.len() as u64, sha256: Sha256::digest(data).into(), chunks };
+        (manifest, distinct)
+    }
+
+    /// Put the payload back together from the pieces found, keyed by `chunk_id`.
+    pub fn assemble(&self, pieces: &HashMap<ChunkId, Vec<u8>>) -> Result<Vec<u8>, StegError> {
+        let missing: Vec<String> = self.chunks.iter().filter(|(id, _)| !pieces.contains_key(id)).map(|(id, _)| hex(id)).collect();
+        if !missing.is_empty() {
+            return Err(format!("{} of {} chunks are missing: {}", missing.len(), self.chunks.len(), missing.join(", ")).into());
+        }
+        let data: Vec<u8> = self.chunks.iter().flat_map(|(id, _)| pieces[id].iter().copied()).collect();
+        if data.len() as u64 != self.len || <[u8; 32]>::from(Sha256::digest(&data)) != self.sha256 {
+            return Err("The chunks don't add up to the payload the manifest describes".into());
+        }
+        Ok(data)
+    }
+}
+
+/// What one carrier of a span holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Record {
+    Piece(Vec<u8>),
+    Manifest(Manifest),
+}
+
+impl Record {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        match self {
+            Record::Piece(data) => {
+                out.push(KIND_PIECE);
+                out.extend_from_slice(data);
+            }
+            Record::Manifest(m) => {
+                out.push(KIND_MANIFEST);
+                out.extend_from_slice(&m.generation.to_be_bytes());
+                out.extend_from_slice(&m.len.to_be_bytes());
+                out.extend_from_slice(&m.sha256);
+                let name = m.name.as_deref().unwrap_or("").as_bytes();
+                out.push(name.len() as u8);
+                out.extend_from_slice(name);
+                out.extend_from_slice(&(m.chunks.len() as u32).to_be_bytes());
+                for (id, len) in &m.chunks {
+                    out.extend_from_slice(id);
+                    out.extend_from_slice(&len.to_be_bytes());
+                }
+            }
+        }
+        out
+    }
+
+    /// `Ok(None)` when `buf` isn't a span record at all.
+    pub fn parse(buf: &[u8]) -> Result<Option<Record>, StegError> {
+        let Some(rest) = buf.strip_prefix(&MAGIC) else { return Ok(None) };
+        let truncated = || "Span manifest truncated".to_string();
+        let (&kind, rest) = rest.split_first().ok_or_else(truncated)?;
+        match kind {
+            KIND_PIECE => Ok(Some(Record::Piece(rest.to_vec()))),
+            KIND_MANIFEST => {
+                let mut pos = 0;
+                let mut take = |n: usize| {
+                    let field = rest.get(pos..pos + n).ok_or_else(truncated);
+                    pos += n;
+                    field
+                };
+                let generation = u32::from_be_bytes(take(4)?.try_into().expect("4 bytes"));
+                let len = u64::from_be_bytes(take(8)?.try_into().expect("8 bytes"));
+                let sha256 = take(32)?.try_into().expect("32 bytes");
+                let name_len = take(1)?[0] as usize;
+                let name = String::from_utf8(take(name_len)?.to_vec()).map_err(|_| "Span manifest name is not valid UTF-8".to_string())?;
+                let count = u32::from_be_bytes(take(4)?.try_into().expect("4 bytes")) as usize;
+                let mut chunks = Vec::new();
+                for _ in 0..count {
+                    let id = take(ID_LEN)?.try_into().expect("ID_LEN bytes");
+                    chunks.push((id, u32::from_be_bytes(take(4)?.try_into().expect("4 bytes"))));
+                }
+                Ok(Some(Record::Manifest(Manifest { generation, name: Some(name).filter(|n| !n.is_empty()), len, sha256, chunks })))
+            }
+            other => Err(format!("Unknown span record kind {}", other).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{RngCore, SeedableRng};
+
+    fn random(len: usize, seed: u64) -> Vec<u8> {
+        let mut data = vec![0u8; len];
+        rand_chacha::ChaCha20Rng::seed_from_u64(seed).fill_bytes(&mut data);
+        data
+    }
+
+    #[test]
+    fn an_edit_only_changes_the_chunks_around_it() {
+        let data = random(64 * 1024, 3);
+        let mut edited = data.clone();
+        edited.splice(30_000..30_000, b"a few inserted bytes".iter().copied());
+
+        let ids = |d: &[u8]| cut(d, 1024).into_iter().map(chunk_id).collect::<Vec<_>>();
+        let (before, after) = (ids(&data), ids(&edited));
+        assert!(before.len() > 20, "{} chunks", before.len());
+        let changed = after.iter().filter(|id| !before.contains(id)).count();
+        assert!(changed <= 2, "{} of {} chunks changed", changed, after.len());
+        for chunk in cut(&data, 1024) {
+            assert!(chunk.len() <= 4096);
+        }
+    }
+
+    #[test]
+    fn records_roundtrip_and_reassemble() {
+        let data = random(20_000, 9);
+        let (manifest, pieces) = Manifest::new(&data, Some("notes.txt".to_string()), 512, 2);
+        let record = Record::Manifest(manifest.clone());
+        assert_eq!(Record::parse(&record.encode()).unwrap(), Some(record));
+        assert_eq!(Record::parse(b"RSTG not a span").unwrap(), None);
+
+        let mut found: HashMap<ChunkId, Vec<u8>> = pieces
+            .iter()
+            .map(|p| match Record::parse(&Record::Piece(p.to_vec()).encode()).unwrap() {
+                Some(Record::Piece(d)) => (chunk_id(&d), d),
+                other => panic!("{:?}", other),
+            })
+            .collect();
+        assert_eq!(manifest.assemble(&found).unwrap(), data);
+        found.remove(&manifest.chunks[1].0);
+        assert!(manifest.assemble(&found).unwrap_err().to_string().contains("1 of"));
+    }
+}
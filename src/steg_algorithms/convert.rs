@@ -0,0 +1,97 @@
+use crate::steg_algorithms::crypto::Cipher;
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::payload::{self, DecodeOptions, FrameOptions, Payload};
+
+// `convert`: move a payload from one carrier/algorithm to another (PNG lsb to JPEG marker before a
+// platform recompresses it, say). What moves is the frame the source carries, not the payload: it is
+// never decoded, so compression, encryption, the HMAC tag, FEC and the metadata block come across exactly
+// as hide wrote them, and no --password is needed. Everything stays in memory; the only file written is
+// the destination.
+//
+// The exception is marker with --password, which seals each segment around an unencrypted frame instead
+// (see marker_hijacking). Coming out of those segments the frame is opened, so it gets encrypted with the
+// same password before it goes anywhere else. Going into marker the frame stays encrypted as it is and
+// the segments aren't sealed; find --password reads both.
+
+/// Where the source's password protection sits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sealing {
+    /// In the frame itself (or nowhere): the frame moves as is.
+    Frame,
+    /// Around the marker segments, the frame inside being unencrypted.
+    Segments,
+}
+
+/// What re-encrypting a frame from sealed segments takes.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub password: Option<String>,
+    pub cipher: Cipher,
+    /// To sign the frame again when it carried an HMAC tag (re-encrypting changes the bytes it covers).
+    pub hmac_key: Option<String>,
+}
+
+/// The bytes the destination should carry, given the bytes the source did. Fails before anything is
+/// written when they don't fit in `room` (the destination's capacity, `None` when it has no fixed one).
+pub fn reframe(carried: Vec<u8>, from: Sealing, room: Option<usize>, opts: &Options) -> Result<Vec<u8>, StegError> {
+    if !carried.starts_with(&payload::MAGIC) {
+        return Err("The source holds no framed payload (a legacy or lineshift message), only framed payloads can be converted".into());
+    }
+    let framed = match from {
+        Sealing::Frame => carried,
+        Sealing::Segments => reseal(&carried, opts)?,
+    };
+    if let Some(have) = room && framed.len() > have {
+        return Err(StegError::CapacityExceeded { needed: framed.len(), available: have });
+    }
+    Ok(framed)
+}
+
+// encrypt the frame that came out of sealed segments, keeping its other flags
+fn reseal(frame: &[u8], opts: &Options) -> Result<Vec<u8>, StegError> {
+    let password = opts.password.clone().ok_or("The source is encrypted segment by segment, pass --password to convert it")?;
+    let flags = frame.get(5).copied().unwrap_or_default();
+    if flags & payload::FLAG_HMAC != 0 && opts.hmac_key.is_none() {
+        return Err("The payload's HMAC tag doesn't cover it once it is encrypted, pass --hmac-key to sign it again".into());
+    }
+    let decode = DecodeOptions { password: None, hmac_key: opts.hmac_key.clone() };
+    let frame_opts = FrameOptions {
+        compress: flags & payload::FLAG_COMPRESSED != 0,
+        password: Some(password),
+        cipher: opts.cipher,
+        hmac_key: opts.hmac_key.clone().filter(|_| flags & payload::FLAG_HMAC != 0),
+        fec_parity: None,
+        meta: Payload::meta(frame),
+    };
+    Payload::decode(frame, &decode)?.encode(&frame_opts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_move_untouched() {
+        let opts = FrameOptions { compress: true, password: Some("pw".to_string()), fec_parity: Some(16), ..FrameOptions::default() };
+        let framed = Payload::from_text(&"spread over a few codewords ".repeat(20)).encode(&opts).unwrap();
+        assert_eq!(reframe(framed.clone(), Sealing::Frame, Some(framed.len()), &Options::default()).unwrap(), framed);
+        let too_small = reframe(framed.clone(), Sealing::Frame, Some(framed.len() - 1), &Options::default());
+        assert!(matches!(too_small, Err(StegError::CapacityExceeded { needed, available }) if needed == framed.len() && available == needed - 1));
+        assert!(reframe(b"\x00\x00\x00\x05hello".to_vec(), Sealing::Frame, None, &Options::default()).is_err());
+    }
+
+    #[test]
+    fn sealed_segments_become_an_encrypted_frame() {
+        let original = Payload { name: Some("notes.txt".to_string()), data: b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa".to_vec() };
+        let inner = original.encode(&FrameOptions { compress: true, hmac_key: Some("mac".to_string()), ..FrameOptions::default() }).unwrap();
+        let opts = Options { password: Some("pw".to_string()), hmac_key: Some("mac".to_string()), ..Options::default() };
+
+        let framed = reframe(inner.clone(), Sealing::Segments, None, &opts).unwrap();
+        assert_eq!(framed[5], payload::FLAG_COMPRESSED | payload::FLAG_ENCRYPTED | payload::FLAG_HMAC);
+        let back = DecodeOptions { password: Some("pw".to_string()), hmac_key: Some("mac".to_string()) };
+        assert_eq!(Payload::decode(&framed, &back).unwrap(), original);
+
+        assert!(reframe(inner.clone(), Sealing::Segments, None, &Options { hmac_key: None, ..opts.clone() }).unwrap_err().to_string().contains("--hmac-key"));
+        assert!(reframe(inner, Sealing::Segments, None, &Options::default()).unwrap_err().to_string().contains("--password"));
+    }
+}
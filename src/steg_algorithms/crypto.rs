@@ -0,0 +1,241 @@
+use aes_gcm::aead::generic_array::GenericArray;
+use aes_gcm::aead::{Aead, KeyInit, OsRng, Payload as AeadPayload};
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::Aes256Gcm;
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::XChaCha20Poly1305;
+use crate::steg_algorithms::error::StegError;
+use serde::{Deserialize, Serialize};
+
+// Password based encryption for payload bodies.
+// Sealed layout: cipher id (1) | salt (16) | nonce (12 or 24) | ciphertext + 16-byte tag.
+// The key is Argon2id(password, salt) with the parameters pinned below, changing them breaks old carriers.
+// Every cipher shares the KDF and layout, only the nonce length differs.
+//
+// `seal_chunks` seals the pieces of one payload separately so each opens on its own. They share a
+// salt (one key) and a random base nonce, stored in every chunk in the layout above; chunk i is
+// encrypted under the base nonce with i XORed into its last 4 bytes, so no nonce repeats under the key.
+
+pub const SALT_LEN: usize = 16;
+pub const TAG_LEN: usize = 16;
+
+/// AEAD used to seal a payload. The discriminant is the id byte stored in front of the salt.
+/// Serialized under the names `--cipher` takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Cipher {
+    #[default]
+    #[serde(rename = "aes-256-gcm")]
+    Aes256Gcm = 1,
+    /// For machines without AES instructions, where it is much faster than software AES.
+    #[serde(rename = "xchacha20-poly1305")]
+    XChaCha20Poly1305 = 2,
+}
+
+impl Cipher {
+    pub fn from_id(id: u8) -> Result<Self, StegError> {
+        match id {
+            1 => Ok(Cipher::Aes256Gcm),
+            2 => Ok(Cipher::XChaCha20Poly1305),
+            other => Err(format!("Payload is encrypted with unknown cipher id {}, it was probably created by a newer version", other).into()),
+        }
+    }
+
+    pub fn nonce_len(self) -> usize {
+        match self {
+            Cipher::Aes256Gcm => 12,
+            Cipher::XChaCha20Poly1305 => 24,
+        }
+    }
+
+    /// Bytes `seal` adds on top of the plaintext.
+    pub fn overhead(self) -> usize {
+        1 + SALT_LEN + self.nonce_len() + TAG_LEN
+    }
+}
+
+// OWASP's recommended Argon2id baseline: 19 MiB, 2 passes, 1 lane
+const ARGON2_M_COST: u32 = 19 * 1024;
+const ARGON2_T_COST: u32 = 2;
+const ARGON2_P_COST: u32 = 1;
+
+fn derive_key(password: &str, salt: &[u8]) -> Result<[u8; 32], StegError> {
+    let params = Params::new(ARGON2_M_COST, ARGON2_T_COST, ARGON2_P_COST, Some(32)).map_err(|e| e.to_string())?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+/// Run the AEAD `C`. `nonce` must already have the cipher's nonce length.
+fn run<C: Aead + KeyInit>(key: &[u8; 32], nonce: &[u8], msg: &[u8], aad: &[u8], encrypt: bool) -> Result<Vec<u8>, ()> {
+    let cipher = C::new_from_slice(key).map_err(|_| ())?;
+    let nonce = GenericArray::from_slice(nonce);
+    let payload = AeadPayload { msg, aad };
+    if encrypt { cipher.encrypt(nonce, payload) } else { cipher.decrypt(nonce, payload) }.map_err(|_| ())
+}
+
+fn run_cipher(cipher: Cipher, key: &[u8; 32], nonce: &[u8], msg: &[u8], aad: &[u8], encrypt: bool) -> Result<Vec<u8>, ()> {
+    match cipher {
+        Cipher::Aes256Gcm => run::<Aes256Gcm>(key, nonce, msg, aad, encrypt),
+        Cipher::XChaCha20Poly1305 => run::<XChaCha20Poly1305>(key, nonce, msg, aad, encrypt),
+    }
+}
+
+/// Encrypt `plaintext` under `password`. `aad` is authenticated but not encrypted (we pass the clear header).
+pub fn seal(cipher: Cipher, password: &str, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, StegError> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = vec![0u8; cipher.nonce_len()];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let key = derive_key(password, &salt)?;
+    // the cipher id is covered too, so it can't be swapped without failing authentication
+    let aad = [aad, &[cipher as u8]].concat();
+    let ct = run_cipher(cipher, &key, &nonce, plaintext, &aad, true).map_err(|_| "encryption failed".to_string())?;
+
+    let mut out = Vec::with_capacity(cipher.overhead() + plaintext.len());
+    out.push(cipher as u8);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ct);
+    Ok(out)
+}
+
+// base nonce with `index` XORed into its last 4 bytes
+fn chunk_nonce(base: &[u8], index: u32) -> Vec<u8> {
+    let mut nonce = base.to_vec();
+    let tail = nonce.len() - 4;
+    for (n, i) in nonce[tail..].iter_mut().zip(index.to_be_bytes()) {
+        *n ^= i;
+    }
+    nonce
+}
+
+/// Seal each of `chunks` on its own under one key, see the top of the file. `aad(i)` is chunk i's
+/// associated data, which should pin down its position so chunks can't be swapped or dropped unnoticed.
+pub fn seal_chunks(cipher: Cipher, password: &str, chunks: &[&[u8]], aad: impl Fn(u32) -> Vec<u8>) -> Result<Vec<Vec<u8>>, StegError> {
+    let mut salt = [0u8; SALT_LEN];
+    let mut base = vec![0u8; cipher.nonce_len()];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut base);
+    let key = derive_key(password, &salt)?;
+
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let i = u32::try_from(i).map_err(|_| "too many chunks to seal".to_string())?;
+            let aad = [&aad(i)[..], &[cipher as u8]].concat();
+            let ct = run_cipher(cipher, &key, &chunk_nonce(&base, i), chunk, &aad, true).map_err(|_| "encryption failed".to_string())?;
+            let mut out = Vec::with_capacity(cipher.overhead() + chunk.len());
+            out.push(cipher as u8);
+            out.extend_from_slice(&salt);
+            out.extend_from_slice(&base);
+            out.extend_from_slice(&ct);
+            Ok(out)
+        })
+        .collect()
+}
+
+/// Reverse of `seal_chunks` for whichever chunks are at hand, as (index, sealed). Each opens or fails
+/// on its own; the key is derived once per salt, not per chunk.
+pub fn open_chunks(password: &str, chunks: &[(u32, &[u8])], aad: impl Fn(u32) -> Vec<u8>) -> Vec<Result<Vec<u8>, StegError>> {
+    let mut keys: Vec<([u8; SALT_LEN], [u8; 32])> = Vec::new();
+    chunks
+        .iter()
+        .map(|&(i, sealed)| {
+            let (&id, rest) = sealed.split_first().ok_or("Encrypted chunk is truncated")?;
+            let cipher = Cipher::from_id(id)?;
+            if sealed.len() < cipher.overhead() {
+                return Err(StegError::Truncated { expected: cipher.overhead(), got: sealed.len() });
+            }
+            let (salt, rest) = rest.split_at(SALT_LEN);
+            let (base, ct) = rest.split_at(cipher.nonce_len());
+            let key = match keys.iter().find(|(s, _)| s == salt) {
+                Some(&(_, key)) => key,
+                None => {
+                    let key = derive_key(password, salt)?;
+                    keys.push((salt.try_into().expect("split at SALT_LEN"), key));
+                    key
+                }
+            };
+            let aad = [&aad(i)[..], &[id]].concat();
+            run_cipher(cipher, &key, &chunk_nonce(base, i), ct, &aad, false)
+                .map_err(|_| StegError::ChecksumMismatch)
+        })
+        .collect()
+}
+
+/// Reverse of `seal`, the cipher comes from the id byte. A wrong password and tampered data look
+/// the same: [`StegError::ChecksumMismatch`].
+pub fn open(password: &str, sealed: &[u8], aad: &[u8]) -> Result<Vec<u8>, StegError> {
+    let (&id, rest) = sealed.split_first().ok_or("Encrypted payload is truncated")?;
+    let cipher = Cipher::from_id(id)?;
+    if sealed.len() < cipher.overhead() {
+        return Err(StegError::Truncated { expected: cipher.overhead(), got: sealed.len() });
+    }
+    let (salt, rest) = rest.split_at(SALT_LEN);
+    let (nonce, ct) = rest.split_at(cipher.nonce_len());
+
+    let key = derive_key(password, salt)?;
+    let aad = [aad, &[id]].concat();
+    run_cipher(cipher, &key, nonce, ct, &aad, false)
+        .map_err(|_| StegError::ChecksumMismatch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOTH: [Cipher; 2] = [Cipher::Aes256Gcm, Cipher::XChaCha20Poly1305];
+
+    #[test]
+    fn seal_open_roundtrip() {
+        for cipher in BOTH {
+            let sealed = seal(cipher, "hunter2", b"attack at dawn", b"hdr").unwrap();
+            assert_eq!(sealed.len(), cipher.overhead() + 14);
+            assert_eq!(sealed[0], cipher as u8);
+            // open picks the cipher from the id byte by itself
+            assert_eq!(open("hunter2", &sealed, b"hdr").unwrap(), b"attack at dawn");
+        }
+    }
+
+    #[test]
+    fn wrong_password_or_aad_fails() {
+        for cipher in BOTH {
+            let sealed = seal(cipher, "hunter2", b"attack at dawn", b"hdr").unwrap();
+            assert!(matches!(open("hunter3", &sealed, b"hdr"), Err(StegError::ChecksumMismatch)));
+            assert!(matches!(open("hunter2", &sealed, b"HDR"), Err(StegError::ChecksumMismatch)));
+        }
+    }
+
+    #[test]
+    fn same_input_never_repeats() {
+        let a = seal(Cipher::Aes256Gcm, "pw", b"same", b"").unwrap();
+        let b = seal(Cipher::Aes256Gcm, "pw", b"same", b"").unwrap();
+        assert_ne!(a, b, "salt and nonce must be fresh per call");
+    }
+
+    #[test]
+    fn chunks_open_alone_and_only_in_place() {
+        let aad = |i: u32| i.to_be_bytes().to_vec();
+        let sealed = seal_chunks(Cipher::XChaCha20Poly1305, "pw", &[b"same", b"same", b"tail"], aad).unwrap();
+        assert_ne!(sealed[0], sealed[1], "equal chunks get different nonces");
+        assert_eq!(sealed[0][..1 + SALT_LEN], sealed[1][..1 + SALT_LEN], "one salt, one key derivation");
+
+        // chunk 1 lost, the others still open; a chunk moved to another index doesn't
+        let opened = open_chunks("pw", &[(0, &sealed[0]), (2, &sealed[2]), (1, &sealed[2])], aad);
+        assert_eq!(opened[0].as_deref().unwrap(), b"same");
+        assert_eq!(opened[1].as_deref().unwrap(), b"tail");
+        assert!(opened[2].is_err());
+        assert!(open_chunks("pv", &[(0, &sealed[0])], aad)[0].is_err());
+    }
+
+    #[test]
+    fn unknown_cipher_id_is_a_version_error() {
+        let mut sealed = seal(Cipher::XChaCha20Poly1305, "pw", b"data", b"").unwrap();
+        sealed[0] = 99;
+        assert!(open("pw", &sealed, b"").unwrap_err().to_string().contains("newer version"));
+    }
+}
@@ -0,0 +1,67 @@
+use std::fs;
+use std::path::Path;
+use sha2::{Digest, Sha256};
+use crate::steg_algorithms::error::StegError;
+
+// Size/hash comparison between a carrier and the stego file made from it. Cloud sync and CDN dedup
+// work on whole-file hashes, so "did the bytes change, and by how much" is what users want to know.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDelta {
+    pub in_len: u64,
+    pub out_len: u64,
+    pub in_sha256: String,
+    pub out_sha256: String,
+    /// Differing byte positions, only meaningful (and only counted) when both files are the same length.
+    pub changed_bytes: Option<usize>,
+}
+
+impl FileDelta {
+    pub fn identical(&self) -> bool {
+        self.in_sha256 == self.out_sha256
+    }
+
+    pub fn same_length(&self) -> bool {
+        self.in_len == self.out_len
+    }
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+pub fn compare(in_path: &Path, out_path: &Path) -> Result<FileDelta, StegError> {
+    let a = fs::read(in_path).map_err(|e| StegError::io(in_path.display(), e))?;
+    let b = fs::read(out_path).map_err(|e| StegError::io(out_path.display(), e))?;
+    Ok(FileDelta {
+        in_len: a.len() as u64,
+        out_len: b.len() as u64,
+        in_sha256: sha256_hex(&a),
+        out_sha256: sha256_hex(&b),
+        changed_bytes: (a.len() == b.len()).then(|| a.iter().zip(&b).filter(|(x, y)| x != y).count()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn counts_changed_bytes_only_for_equal_lengths() {
+        let dir = tempdir().unwrap();
+        let (a, b, c) = (dir.path().join("a"), dir.path().join("b"), dir.path().join("c"));
+        fs::write(&a, b"hello world").unwrap();
+        fs::write(&b, b"hellO worlD").unwrap();
+        fs::write(&c, b"hello world!").unwrap();
+
+        let d = compare(&a, &b).unwrap();
+        assert_eq!(d.changed_bytes, Some(2));
+        assert!(!d.identical() && d.same_length());
+        assert_eq!(d.in_sha256, "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9");
+
+        let d = compare(&a, &c).unwrap();
+        assert_eq!((d.changed_bytes, d.out_len - d.in_len), (None, 1));
+        assert!(compare(&a, &a).unwrap().identical());
+    }
+}
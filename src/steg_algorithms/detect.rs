@@ -0,0 +1,189 @@
+use std::fs;
+use std::path::Path;
+use serde::Serialize;
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::legacy;
+use crate::steg_algorithms::payload::{self, DecodeOptions, Payload, Table};
+use crate::steg_algorithms::picture::jpg::marker_hijacking;
+#[cfg(feature = "picture")]
+use crate::steg_algorithms::picture::raw;
+use crate::steg_algorithms::registry::{self, CarrierKind, Options};
+
+// `detect`: run every extractor that applies to one file (picked by its content, not its name) with
+// their default settings and say which of them come up with a plausible payload. A probe failing, for
+// whatever reason, only ever makes that probe miss. The probes are the registered algorithms that
+// support the carrier, except lineshift: its bytes carry no header, so anything it reads would look as
+// plausible as anything else.
+
+/// What the file turned out to be, as a label and as the kind of carrier the registry's algorithms
+/// say they support.
+fn sniff(buf: &[u8]) -> Option<(&'static str, CarrierKind)> {
+    let picture = |format: &str| CarrierKind::new("picture", format);
+    match buf {
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => Some(("WAV audio", CarrierKind::new("audio", "wav"))),
+        [0xFF, 0xD8, 0xFF, ..] => Some(("JPEG picture", picture("jpg"))),
+        [b'G', b'I', b'F', b'8', ..] => Some(("GIF picture", picture("gif"))),
+        _ => sniff_picture(buf),
+    }
+}
+
+// raw formats and whatever else the image crate decodes
+#[cfg(feature = "picture")]
+fn sniff_picture(buf: &[u8]) -> Option<(&'static str, CarrierKind)> {
+    let picture = |format: &str| CarrierKind::new("picture", format);
+    match raw::Format::sniff(buf) {
+        Some(format) => Some(("raw picture", picture(format.extension()))),
+        None => {
+            let format = image::guess_format(buf).ok()?;
+            let label = match format {
+                image::ImageFormat::Png => "PNG picture",
+                image::ImageFormat::Bmp => "BMP picture",
+                image::ImageFormat::Tiff => "TIFF picture",
+                _ => "picture",
+            };
+            Some((label, picture(format.extensions_str().first().copied().unwrap_or_default())))
+        }
+    }
+}
+
+// nothing in this build would read those
+#[cfg(not(feature = "picture"))]
+fn sniff_picture(_: &[u8]) -> Option<(&'static str, CarrierKind)> {
+    None
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum Outcome {
+    /// `embedded` bytes came out and look like one of our payloads (or a legacy one).
+    Found { embedded: usize, detail: String },
+    /// Nothing plausible.
+    Nothing { reason: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Probe {
+    pub algorithm: &'static str,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+}
+
+#[derive(Serialize)]
+pub struct Report {
+    /// What the content says the file is.
+    pub carrier: &'static str,
+    pub probes: Vec<Probe>,
+}
+
+impl Report {
+    pub fn hits(&self) -> usize {
+        self.probes.iter().filter(|p| matches!(p.outcome, Outcome::Found { .. })).count()
+    }
+}
+
+/// Validate what a carrier gave back: a frame we can parse (short of decrypting it), or legacy text.
+fn judge(raw: &[u8], audio: bool) -> Outcome {
+    if !legacy::is_framed(raw) {
+        return match legacy::detect(raw, audio) {
+            Some(msg) => Outcome::Found { embedded: raw.len(), detail: format!("legacy (pre-framing) text, {} bytes", msg.len()) },
+            None => Outcome::Nothing { reason: "no payload header".to_string() },
+        };
+    }
+    let (frame, fec) = match payload::unprotect(raw) {
+        Ok(Some((inner, corrected))) => (inner, Some(corrected)),
+        Ok(None) => (raw.to_vec(), None),
+        Err(e) => return Outcome::Nothing { reason: format!("FEC envelope, but {}", e) },
+    };
+    let mut parts = Vec::new();
+    match fec {
+        Some(0) => parts.push("fec".to_string()),
+        Some(n) => parts.push(format!("fec ({} bytes repaired)", n)),
+        None => {}
+    }
+    match Table::parse(&frame) {
+        Ok(Some(table)) => parts.push(format!("named payloads: {}", table.names().collect::<Vec<_>>().join(", "))),
+        Err(e) => return Outcome::Nothing { reason: format!("damaged payload table: {}", e) },
+        Ok(None) => {
+            let flags = frame.get(5).copied().unwrap_or_default();
+            for (flag, name) in [(payload::FLAG_COMPRESSED, "compressed"), (payload::FLAG_ENCRYPTED, "encrypted"), (payload::FLAG_HMAC, "hmac")] {
+                if flags & flag != 0 {
+                    parts.push(name.to_string());
+                }
+            }
+            // everything but an encrypted body can be checked all the way
+            if flags & payload::FLAG_ENCRYPTED == 0 {
+                match Payload::decode(&frame, &DecodeOptions::default()) {
+                    Ok(p) => parts.push(match p.name {
+                        Some(n) => format!("file '{}', {} bytes", n, p.data.len()),
+                        None => format!("{} bytes of data", p.data.len()),
+                    }),
+                    Err(e) => return Outcome::Nothing { reason: format!("payload header, but {}", e) },
+                }
+            }
+        }
+    }
+    Outcome::Found { embedded: raw.len(), detail: parts.join(", ") }
+}
+
+/// Probe the file at `path` with every applicable algorithm. Only fails when the file can't be read
+/// or isn't a carrier of any kind we know.
+pub fn detect(path: &Path) -> Result<Report, StegError> {
+    let buf = fs::read(path).map_err(|e| StegError::io(format!("Failed to read {}", path.display()), e))?;
+    let (carrier, kind) = sniff(&buf)
+        .ok_or_else(|| format!("{} isn't a picture or WAV file this tool can read", path.display()))?;
+    let audio = kind.filetype == "audio";
+    let probes = registry::all()
+        .iter()
+        .filter(|a| a.framed() && a.supports(&kind))
+        .map(|a| {
+            let outcome = match (a.name(), marker_hijacking::sealed_segments(&buf)) {
+                // sealed segments carry no readable frame, their headers are all there is to go on
+                ("marker", Some((present, total, sealed))) => Outcome::Found {
+                    embedded: sealed,
+                    detail: format!("encrypted segment by segment, {} of {} segments present", present, total),
+                },
+                _ => match a.extract(path, &Options::default(), None) {
+                    Ok((raw, _)) => judge(&raw, audio),
+                    Err(e) => Outcome::Nothing { reason: e.to_string() },
+                },
+            };
+            Probe { algorithm: a.name(), outcome }
+        })
+        .collect();
+    Ok(Report { carrier, probes })
+}
+
+#[cfg(all(test, feature = "picture", feature = "jpeg-marker"))]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+    use tempfile::tempdir;
+    use crate::steg_algorithms::payload::FrameOptions;
+    use crate::steg_algorithms::picture::general::lsb;
+
+    #[test]
+    fn finds_what_each_carrier_holds_and_swallows_the_rest() {
+        let dir = tempdir().unwrap();
+        let (cover, stego) = (dir.path().join("c.png"), dir.path().join("s.png"));
+        RgbImage::from_fn(300, 300, |x, y| Rgb([(x % 256) as u8, (y % 256) as u8, 90])).save(&cover).unwrap();
+        let framed = Payload::from_text(&"detect me ".repeat(4)).encode(&FrameOptions { compress: true, ..Default::default() }).unwrap();
+        lsb::hide_sparse(&cover, &framed, &stego, 3).unwrap();
+
+        let report = detect(&stego).unwrap();
+        assert_eq!(report.carrier, "PNG picture");
+        assert_eq!(report.hits(), 1);
+        assert_eq!(report.probes[0].outcome, Outcome::Found { embedded: framed.len(), detail: "compressed, 40 bytes of data".to_string() });
+        assert!(matches!(report.probes[1].outcome, Outcome::Nothing { .. }), "overlay misses on a plain picture");
+        assert_eq!(detect(&cover).unwrap().hits(), 0);
+
+        // a legacy carrier still counts
+        let legacy = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/legacy/marker.jpg");
+        let report = detect(&legacy).unwrap();
+        assert_eq!(report.probes[0].algorithm, "marker");
+        assert!(matches!(&report.probes[0].outcome, Outcome::Found { detail, .. } if detail.starts_with("legacy")));
+
+        let junk = dir.path().join("notes.png");
+        fs::write(&junk, b"not a picture at all").unwrap();
+        assert!(detect(&junk).is_err());
+    }
+}
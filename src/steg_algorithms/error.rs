@@ -0,0 +1,116 @@
+//! What can go wrong, as something callers can match on instead of a string to grep.
+
+use std::io;
+use std::path::Path;
+
+/// An error from any of the algorithms or the framing around them.
+///
+/// The specific variants cover what a caller might want to react to: a missing file, a payload that
+/// doesn't fit, a carrier with nothing in it, a payload that was damaged or the wrong key, a container
+/// that breaks its spec, an lsb depth out of range. Everything else (bad parameters, unsupported
+/// options) is [`StegError::Other`] with a message meant for people.
+#[derive(Debug, thiserror::Error)]
+pub enum StegError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("Unsupported format '{found}'")]
+    UnsupportedFormat { found: String },
+    #[error("Carrier too small: the payload needs {needed} bytes but it holds {available}")]
+    CapacityExceeded { needed: usize, available: usize },
+    #[error("No hidden payload found")]
+    NoPayloadFound,
+    /// A checksum, MAC or authentication tag didn't match: the payload was damaged, tampered with or
+    /// read with the wrong key.
+    #[error("Checksum mismatch: the payload is damaged or the key is wrong")]
+    ChecksumMismatch,
+    /// The data ended before the length it announced.
+    #[error("Truncated: expected {expected} bytes, got {got}")]
+    Truncated { expected: usize, got: usize },
+    /// The container's structure breaks its spec at byte `at` (a JPEG segment length that can't be, a
+    /// PNG chunk running past the end of the file), as a walker in strict mode or a rewrite that
+    /// can't go on finds it.
+    #[error("{what} (at byte {at})")]
+    Malformed { at: usize, what: String },
+    /// An lsb layout asked for more low bits of each channel than `lsb::MAX_BITS`, or none.
+    #[error("bits must be 1 to {max}, not {bits}")]
+    UnsupportedDepth { bits: u8, max: u8 },
+    #[error("{0}")]
+    Other(String),
+}
+
+impl From<String> for StegError {
+    fn from(e: String) -> Self {
+        StegError::Other(e)
+    }
+}
+
+impl From<&str> for StegError {
+    fn from(e: &str) -> Self {
+        StegError::Other(e.to_string())
+    }
+}
+
+#[cfg(feature = "picture")]
+impl From<image::ImageError> for StegError {
+    fn from(e: image::ImageError) -> Self {
+        match e {
+            image::ImageError::IoError(e) => StegError::Io(e),
+            e => StegError::Other(e.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+impl From<hound::Error> for StegError {
+    fn from(e: hound::Error) -> Self {
+        match e {
+            hound::Error::IoError(e) => StegError::Io(e),
+            e => StegError::Other(e.to_string()),
+        }
+    }
+}
+
+impl StegError {
+    /// An I/O error with what was being done in front of its message ("Failed to read a.png: No such
+    /// file or directory"), keeping its kind so callers can still tell a missing file from the rest.
+    pub fn io(context: impl std::fmt::Display, e: io::Error) -> Self {
+        StegError::Io(io::Error::new(e.kind(), format!("{}: {}", context, e)))
+    }
+
+    /// `self` with `what` in front of its message ("find failed: ..."), for the kinds whose message is
+    /// free text. The others say what went wrong on their own and keep their variant untouched.
+    pub fn context(self, what: impl std::fmt::Display) -> Self {
+        match self {
+            StegError::Io(e) => StegError::io(what, e),
+            StegError::Other(m) => StegError::Other(format!("{}: {}", what, m)),
+            StegError::Malformed { at, what: m } => StegError::Malformed { at, what: format!("{}: {}", what, m) },
+            e => e,
+        }
+    }
+
+    /// A number for each kind, the CLI's exit code and the FFI's error code: 3 I/O, 4 unsupported or
+    /// malformed format, 5 doesn't fit, 6 nothing found, 7 checksum mismatch, 8 truncated, 1 anything
+    /// else.
+    pub fn code(&self) -> i32 {
+        match self {
+            StegError::Io(_) => 3,
+            StegError::UnsupportedFormat { .. } | StegError::Malformed { .. } => 4,
+            StegError::CapacityExceeded { .. } => 5,
+            StegError::NoPayloadFound => 6,
+            StegError::ChecksumMismatch => 7,
+            StegError::Truncated { .. } => 8,
+            StegError::UnsupportedDepth { .. } | StegError::Other(_) => 1,
+        }
+    }
+
+    /// The error for `what` (an algorithm, a filetype) when this build was made without the Cargo
+    /// `feature` it needs.
+    pub fn not_built(what: impl std::fmt::Display, feature: &str) -> Self {
+        StegError::Other(format!("{} isn't in this build, it was built without {} support (cargo build --features {})", what, feature, feature))
+    }
+
+    /// The error for a `path` that isn't there, an [`io::ErrorKind::NotFound`].
+    pub fn not_found(path: &Path) -> Self {
+        StegError::Io(io::Error::new(io::ErrorKind::NotFound, format!("Path {} doesn't exist!", path.display())))
+    }
+}
@@ -0,0 +1,313 @@
+use crate::steg_algorithms::error::StegError;
+
+// Reed-Solomon forward error correction over GF(2^8), for payloads that have to survive a few damaged
+// bytes. Classic systematic RS(n, k) with n <= 255, `parity` check bytes per codeword, correcting up
+// to parity/2 wrong bytes in each codeword. Data longer than one codeword is split into equal blocks
+// and the codewords are interleaved byte by byte, so a burst of damage is shared between blocks
+// instead of wiping out one of them.
+// Decoding is Berlekamp-Massey + Chien search + Forney, following the usual textbook layout
+// (primitive polynomial 0x11d, generator roots a^0..a^(parity-1)).
+
+/// Default check bytes per codeword: fixes 16 bad bytes in every 255.
+pub const DEFAULT_PARITY: usize = 32;
+
+const PRIM: u16 = 0x11d;
+
+struct Gf {
+    exp: [u8; 512],
+    log: [u8; 256],
+}
+
+impl Gf {
+    fn new() -> Self {
+        let mut gf = Gf { exp: [0; 512], log: [0; 256] };
+        let mut x: u16 = 1;
+        for i in 0..255 {
+            gf.exp[i] = x as u8;
+            gf.log[x as usize] = i as u8;
+            x <<= 1;
+            if x & 0x100 != 0 {
+                x ^= PRIM;
+            }
+        }
+        for i in 255..512 {
+            gf.exp[i] = gf.exp[i - 255];
+        }
+        gf
+    }
+
+    fn mul(&self, a: u8, b: u8) -> u8 {
+        if a == 0 || b == 0 { 0 } else { self.exp[self.log[a as usize] as usize + self.log[b as usize] as usize] }
+    }
+
+    fn div(&self, a: u8, b: u8) -> u8 {
+        if a == 0 { 0 } else { self.exp[(self.log[a as usize] as usize + 255 - self.log[b as usize] as usize) % 255] }
+    }
+
+    fn pow(&self, x: u8, power: i32) -> u8 {
+        self.exp[(self.log[x as usize] as i32 * power).rem_euclid(255) as usize]
+    }
+
+    fn inv(&self, x: u8) -> u8 {
+        self.exp[255 - self.log[x as usize] as usize]
+    }
+
+    // polynomials are big-endian: highest degree first
+
+    fn poly_scale(&self, p: &[u8], x: u8) -> Vec<u8> {
+        p.iter().map(|&c| self.mul(c, x)).collect()
+    }
+
+    fn poly_add(&self, p: &[u8], q: &[u8]) -> Vec<u8> {
+        let n = p.len().max(q.len());
+        let mut r = vec![0u8; n];
+        for (i, &c) in p.iter().enumerate() {
+            r[i + n - p.len()] = c;
+        }
+        for (i, &c) in q.iter().enumerate() {
+            r[i + n - q.len()] ^= c;
+        }
+        r
+    }
+
+    fn poly_mul(&self, p: &[u8], q: &[u8]) -> Vec<u8> {
+        let mut r = vec![0u8; p.len() + q.len() - 1];
+        for (j, &b) in q.iter().enumerate() {
+            for (i, &a) in p.iter().enumerate() {
+                r[i + j] ^= self.mul(a, b);
+            }
+        }
+        r
+    }
+
+    fn poly_eval(&self, p: &[u8], x: u8) -> u8 {
+        p.iter().skip(1).fold(p[0], |y, &c| self.mul(y, x) ^ c)
+    }
+
+    /// Remainder of p / q (q monic).
+    fn poly_rem(&self, p: &[u8], q: &[u8]) -> Vec<u8> {
+        let mut out = p.to_vec();
+        for i in 0..p.len().saturating_sub(q.len() - 1) {
+            let coef = out[i];
+            if coef != 0 {
+                for j in 1..q.len() {
+                    out[i + j] ^= self.mul(q[j], coef);
+                }
+            }
+        }
+        let keep = (q.len() - 1).min(out.len());
+        out[out.len() - keep..].to_vec()
+    }
+
+    fn generator_poly(&self, parity: usize) -> Vec<u8> {
+        (0..parity).fold(vec![1], |g, i| self.poly_mul(&g, &[1, self.pow(2, i as i32)]))
+    }
+
+    fn encode_block(&self, msg: &[u8], generator: &[u8]) -> Vec<u8> {
+        let parity = generator.len() - 1;
+        let mut out = msg.to_vec();
+        out.resize(msg.len() + parity, 0);
+        for i in 0..msg.len() {
+            let coef = out[i];
+            if coef != 0 {
+                for j in 1..generator.len() {
+                    out[i + j] ^= self.mul(generator[j], coef);
+                }
+            }
+        }
+        out[..msg.len()].copy_from_slice(msg);
+        out
+    }
+
+    fn syndromes(&self, cw: &[u8], parity: usize) -> Vec<u8> {
+        // leading 0 keeps the indices lined up with the textbook formulas
+        std::iter::once(0).chain((0..parity).map(|i| self.poly_eval(cw, self.pow(2, i as i32)))).collect()
+    }
+
+    /// Fix `cw` in place, returning how many bytes were wrong.
+    fn correct_block(&self, cw: &mut [u8], parity: usize) -> Result<usize, ()> {
+        let synd = self.syndromes(cw, parity);
+        if synd.iter().all(|&s| s == 0) {
+            return Ok(0);
+        }
+
+        // Berlekamp-Massey: error locator polynomial
+        let mut err_loc = vec![1u8];
+        let mut old_loc = vec![1u8];
+        for i in 0..parity {
+            let k = i + 1;
+            let mut delta = synd[k];
+            for j in 1..err_loc.len() {
+                delta ^= self.mul(err_loc[err_loc.len() - 1 - j], synd[k - j]);
+            }
+            old_loc.push(0);
+            if delta != 0 {
+                if old_loc.len() > err_loc.len() {
+                    let new_loc = self.poly_scale(&old_loc, delta);
+                    old_loc = self.poly_scale(&err_loc, self.inv(delta));
+                    err_loc = new_loc;
+                }
+                err_loc = self.poly_add(&err_loc, &self.poly_scale(&old_loc, delta));
+            }
+        }
+        let first = err_loc.iter().position(|&c| c != 0).ok_or(())?;
+        let err_loc = &err_loc[first..];
+        let errs = err_loc.len() - 1;
+        if errs * 2 > parity {
+            return Err(());
+        }
+
+        // Chien search: roots of the locator give the positions
+        let rev: Vec<u8> = err_loc.iter().rev().copied().collect();
+        let n = cw.len();
+        let err_pos: Vec<usize> = (0..n).filter(|&i| self.poly_eval(&rev, self.pow(2, i as i32)) == 0).map(|i| n - 1 - i).collect();
+        if err_pos.len() != errs {
+            return Err(());
+        }
+
+        // Forney: magnitudes
+        let coef_pos: Vec<usize> = err_pos.iter().map(|&p| n - 1 - p).collect();
+        let errata_loc = coef_pos
+            .iter()
+            .fold(vec![1u8], |acc, &i| self.poly_mul(&acc, &self.poly_add(&[1], &[self.pow(2, i as i32), 0])));
+        let synd_rev: Vec<u8> = synd.iter().rev().copied().collect();
+        let mut divisor = vec![0u8; errata_loc.len() + 1];
+        divisor[0] = 1;
+        let err_eval = self.poly_rem(&self.poly_mul(&synd_rev, &errata_loc), &divisor);
+
+        let x: Vec<u8> = coef_pos.iter().map(|&p| self.pow(2, p as i32 - 255)).collect();
+        for (i, &xi) in x.iter().enumerate() {
+            let xi_inv = self.inv(xi);
+            let loc_prime = x
+                .iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .fold(1u8, |acc, (_, &xj)| self.mul(acc, 1 ^ self.mul(xi_inv, xj)));
+            if loc_prime == 0 {
+                return Err(());
+            }
+            let y = self.mul(xi, self.poly_eval(&err_eval, xi_inv));
+            cw[err_pos[i]] ^= self.div(y, loc_prime);
+        }
+
+        if self.syndromes(cw, parity).iter().any(|&s| s != 0) {
+            return Err(());
+        }
+        Ok(errs)
+    }
+}
+
+/// How the data is cut into codewords: (blocks, data bytes per block).
+fn layout(data_len: usize, parity: usize) -> (usize, usize) {
+    let k_max = 255 - parity;
+    let blocks = data_len.div_ceil(k_max).max(1);
+    (blocks, data_len.div_ceil(blocks).max(1))
+}
+
+/// Size of `encode`'s output for `data_len` bytes of data.
+pub fn encoded_len(data_len: usize, parity: usize) -> usize {
+    let (blocks, k) = layout(data_len, parity);
+    blocks * (k + parity)
+}
+
+fn check_parity(parity: usize) -> Result<(), StegError> {
+    if !(2..=128).contains(&parity) {
+        return Err(format!("Reed-Solomon parity must be between 2 and 128 bytes, got {}", parity).into());
+    }
+    Ok(())
+}
+
+pub fn encode(data: &[u8], parity: usize) -> Result<Vec<u8>, StegError> {
+    check_parity(parity)?;
+    let gf = Gf::new();
+    let generator = gf.generator_poly(parity);
+    let (blocks, k) = layout(data.len(), parity);
+    let n = k + parity;
+
+    let mut out = vec![0u8; blocks * n];
+    for b in 0..blocks {
+        // the last block is zero filled, the caller stores the real length
+        let mut msg = data.get(b * k..((b + 1) * k).min(data.len())).unwrap_or(&[]).to_vec();
+        msg.resize(k, 0);
+        for (j, byte) in gf.encode_block(&msg, &generator).into_iter().enumerate() {
+            out[j * blocks + b] = byte;
+        }
+    }
+    Ok(out)
+}
+
+/// Undo `encode`, fixing what can be fixed. Returns the data and how many bytes had to be corrected.
+pub fn decode(buf: &[u8], data_len: usize, parity: usize) -> Result<(Vec<u8>, usize), StegError> {
+    check_parity(parity)?;
+    let gf = Gf::new();
+    let (blocks, k) = layout(data_len, parity);
+    let n = k + parity;
+    if buf.len() < blocks * n {
+        return Err(StegError::Truncated { expected: blocks * n, got: buf.len() });
+    }
+
+    let mut data = Vec::with_capacity(blocks * k);
+    let mut corrected = 0;
+    for b in 0..blocks {
+        let mut cw: Vec<u8> = (0..n).map(|j| buf[j * blocks + b]).collect();
+        corrected += gf.correct_block(&mut cw, parity).map_err(|_| {
+            format!(
+                "Too much damage to correct: block {} of {} has more than {} bad bytes",
+                b + 1,
+                blocks,
+                parity / 2
+            )
+        })?;
+        data.extend_from_slice(&cw[..k]);
+    }
+    data.truncate(data_len);
+    Ok((data, corrected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 31 + 7) as u8).collect()
+    }
+
+    #[test]
+    fn roundtrip_clean() {
+        for len in [0, 1, 100, 223, 224, 1000] {
+            let enc = encode(&sample(len), 32).unwrap();
+            assert_eq!(enc.len(), encoded_len(len, 32));
+            assert_eq!(decode(&enc, len, 32).unwrap(), (sample(len), 0));
+        }
+    }
+
+    #[test]
+    fn corrects_up_to_half_the_parity_per_block() {
+        let data = sample(600); // 3 blocks
+        let mut enc = encode(&data, 16).unwrap();
+        // 8 errors per block at most: 24 spread out, then a burst of 20 that interleaving splits up
+        for i in 0..24 {
+            enc[i * 26 + 3] ^= 0xA5;
+        }
+        let (fixed, n) = decode(&enc, 600, 16).unwrap();
+        assert_eq!(fixed, data);
+        assert_eq!(n, 24);
+
+        let mut enc = encode(&data, 16).unwrap();
+        for b in &mut enc[100..120] {
+            *b = !*b;
+        }
+        assert_eq!(decode(&enc, 600, 16).unwrap().0, data);
+    }
+
+    #[test]
+    fn too_much_damage_is_reported() {
+        let data = sample(100);
+        let mut enc = encode(&data, 8).unwrap();
+        for b in &mut enc[..10] {
+            *b ^= 0xFF;
+        }
+        let err = decode(&enc, 100, 8).unwrap_err().to_string();
+        assert!(err.contains("Too much damage"), "{}", err);
+    }
+}
@@ -0,0 +1,261 @@
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::scan::{Finding, Kind};
+
+// `scan --where`: a small expression language over the fields of a finding, so a big sweep only prints
+// what's worth a look.
+//
+//   score > 0.8 && filetype == 'jpeg'
+//   !(detector == "chi-square") || path == './keep/this.png'
+//
+// Fields are `score` (a number) and `filetype`, `detector`, `detail` and `path` (text). Numbers take
+// == != < <= > >=, text == and !=, with strings in single or double quotes. `filetype` compares
+// without regard to case. && binds tighter than ||, ! tighter than both, parentheses group. Every
+// mistake is caught when the expression is parsed, before the scan starts.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Score,
+    Filetype,
+    Detector,
+    Detail,
+    Path,
+}
+
+impl Field {
+    const NAMES: &'static str = "score, filetype, detector, detail, path";
+
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "score" => Field::Score,
+            "filetype" => Field::Filetype,
+            "detector" => Field::Detector,
+            "detail" => Field::Detail,
+            "path" => Field::Path,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Number(Op, f64),
+    Text(Field, Op, String),
+}
+
+/// A parsed `--where` expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter(Expr);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(f64),
+    Str(String),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, StegError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((at, c)) = chars.next() {
+        let next_is = |chars: &mut std::iter::Peekable<std::str::CharIndices>, want: char| chars.next_if(|&(_, c)| c == want).is_some();
+        tokens.push(match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '&' if next_is(&mut chars, '&') => Token::And,
+            '|' if next_is(&mut chars, '|') => Token::Or,
+            '=' if next_is(&mut chars, '=') => Token::Op(Op::Eq),
+            '!' if next_is(&mut chars, '=') => Token::Op(Op::Ne),
+            '!' => Token::Not,
+            '<' if next_is(&mut chars, '=') => Token::Op(Op::Le),
+            '<' => Token::Op(Op::Lt),
+            '>' if next_is(&mut chars, '=') => Token::Op(Op::Ge),
+            '>' => Token::Op(Op::Gt),
+            '\'' | '"' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, q)) if q == c => break,
+                        Some((_, ch)) => s.push(ch),
+                        None => return Err(format!("Unterminated string starting at column {}", at + 1).into()),
+                    }
+                }
+                Token::Str(s)
+            }
+            c if c.is_ascii_digit() || c == '.' || c == '-' => {
+                let mut s = c.to_string();
+                while let Some((_, d)) = chars.next_if(|&(_, d)| d.is_ascii_digit() || d == '.') {
+                    s.push(d);
+                }
+                Token::Number(s.parse().map_err(|_| format!("Bad number '{}' at column {}", s, at + 1))?)
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut s = c.to_string();
+                while let Some((_, d)) = chars.next_if(|&(_, d)| d.is_ascii_alphanumeric() || d == '_') {
+                    s.push(d);
+                }
+                Token::Ident(s)
+            }
+            other => return Err(format!("Unexpected '{}' at column {}", other, at + 1).into()),
+        });
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    at: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.at).cloned();
+        self.at += 1;
+        t
+    }
+
+    fn eat(&mut self, want: &Token) -> bool {
+        let hit = self.tokens.get(self.at) == Some(want);
+        self.at += hit as usize;
+        hit
+    }
+
+    fn or(&mut self) -> Result<Expr, StegError> {
+        let mut left = self.and()?;
+        while self.eat(&Token::Or) {
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, StegError> {
+        let mut left = self.unary()?;
+        while self.eat(&Token::And) {
+            left = Expr::And(Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, StegError> {
+        if self.eat(&Token::Not) {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat(&Token::Open) {
+            let inner = self.or()?;
+            return if self.eat(&Token::Close) { Ok(inner) } else { Err("Missing ')'".into()) };
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, StegError> {
+        let name = match self.next() {
+            Some(Token::Ident(name)) => name,
+            Some(other) => return Err(format!("Expected a field name, got {:?}", other).into()),
+            None => return Err("Expression ends where a field name should be".into()),
+        };
+        let field = Field::parse(&name).ok_or_else(|| format!("Unknown field '{}' (fields: {})", name, Field::NAMES))?;
+        let Some(Token::Op(op)) = self.next() else {
+            return Err(format!("Expected a comparison after '{}'", name).into());
+        };
+        match (field, self.next()) {
+            (Field::Score, Some(Token::Number(n))) => Ok(Expr::Number(op, n)),
+            (Field::Score, _) => Err("'score' is a number, compare it with one".into()),
+            (_, Some(Token::Str(_))) if !matches!(op, Op::Eq | Op::Ne) => Err(format!("'{}' is text, it only takes == and !=", name).into()),
+            (Field::Filetype, Some(Token::Str(s))) => Ok(Expr::Text(field, op, s.to_lowercase())),
+            (_, Some(Token::Str(s))) => Ok(Expr::Text(field, op, s)),
+            _ => Err(format!("'{}' is text, compare it with a quoted string", name).into()),
+        }
+    }
+}
+
+fn kind_name(kind: Kind) -> String {
+    kind.label().to_lowercase()
+}
+
+impl Filter {
+    pub fn parse(text: &str) -> Result<Filter, StegError> {
+        let mut parser = Parser { tokens: tokenize(text)?, at: 0 };
+        let filter = parser.or()?;
+        match parser.next() {
+            None => Ok(Filter(filter)),
+            Some(extra) => Err(format!("Unexpected {:?} after a complete expression", extra).into()),
+        }
+    }
+
+    pub fn matches(&self, f: &Finding) -> bool {
+        self.0.matches(f)
+    }
+}
+
+impl Expr {
+    fn matches(&self, f: &Finding) -> bool {
+        match self {
+            Expr::And(a, b) => a.matches(f) && b.matches(f),
+            Expr::Or(a, b) => a.matches(f) || b.matches(f),
+            Expr::Not(a) => !a.matches(f),
+            Expr::Number(op, n) => match op {
+                Op::Eq => f.score == *n,
+                Op::Ne => f.score != *n,
+                Op::Lt => f.score < *n,
+                Op::Le => f.score <= *n,
+                Op::Gt => f.score > *n,
+                Op::Ge => f.score >= *n,
+            },
+            Expr::Text(field, op, s) => {
+                let value = match field {
+                    Field::Filetype => kind_name(f.filetype),
+                    Field::Detector => f.detector.to_string(),
+                    Field::Detail => f.detail.clone(),
+                    Field::Path => f.path.display().to_string(),
+                    Field::Score => unreachable!("score is compared as a number"),
+                };
+                (value == *s) == (*op == Op::Eq)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn finding(kind: Kind, detector: &'static str, score: f64) -> Finding {
+        Finding { path: PathBuf::from("a/b.jpg"), filetype: kind, detector, score, detail: "x".to_string() }
+    }
+
+    #[test]
+    fn expressions_pick_findings_and_reject_mistakes() {
+        let f = Filter::parse("score > 0.8 && filetype == 'JPEG' || detector == \"appended\"").unwrap();
+        assert!(f.matches(&finding(Kind::Jpeg, "marker", 0.9)));
+        assert!(!f.matches(&finding(Kind::Png, "marker", 0.9)));
+        assert!(!f.matches(&finding(Kind::Jpeg, "marker", 0.5)));
+        assert!(f.matches(&finding(Kind::Png, "appended", 0.1)), "&& binds tighter than ||");
+
+        let f = Filter::parse("!(score<=0.5) && path != 'a/b.jpg'").unwrap();
+        assert!(!f.matches(&finding(Kind::Jpeg, "marker", 0.9)));
+
+        for bad in ["score > 'high'", "filetype > 'a'", "size > 3", "score >", "(score > 1", "score > 1 score", "detail == 'x"] {
+            assert!(Filter::parse(bad).is_err(), "{} should not parse", bad);
+        }
+    }
+}
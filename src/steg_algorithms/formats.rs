@@ -0,0 +1,142 @@
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::registry;
+
+// Knowledge about which output containers each algorithm's payload survives.
+// Used by the CLI to catch `-i photo.png -o photo.jpg` style container changes before they silently
+// destroy the payload, and to pick an algorithm that suits the file when none is given.
+
+/// Normalized lowercase extension, with the jpeg/jpg and tiff/tif spellings collapsed.
+pub fn normalize_ext(ext: &str) -> String {
+    match ext.to_lowercase().as_str() {
+        "jpeg" => "jpg".to_string(),
+        "tiff" => "tif".to_string(),
+        other => other.to_string(),
+    }
+}
+
+pub fn is_jpeg(ext: &str) -> bool {
+    normalize_ext(ext) == "jpg"
+}
+
+pub fn is_gif(ext: &str) -> bool {
+    normalize_ext(ext) == "gif"
+}
+
+/// Picture containers the `image` crate writes without touching pixel values.
+pub fn is_lossless_picture(ext: &str) -> bool {
+    matches!(normalize_ext(ext).as_str(), "png" | "bmp" | "tif" | "tga" | "qoi" | "ppm" | "pgm" | "pnm" | "pam" | "ff")
+}
+
+/// Explain why writing `alg`'s output as `out_ext` breaks the payload, or `None` if it is fine.
+pub fn output_problem(filetype: &str, alg: &str, out_ext: &str) -> Option<String> {
+    match (filetype, alg) {
+        ("picture", "lsb") if !is_lossless_picture(out_ext) => Some(format!(
+            "LSB data does not survive being written as .{}: the encoder changes pixel values{}",
+            out_ext,
+            if is_jpeg(out_ext) { " (JPEG is lossy)" } else { "" }
+        )),
+        ("picture", "marker") if !is_jpeg(out_ext) => Some(format!(
+            "marker hijacking stores data in JPEG APPn segments, which .{} files don't have",
+            out_ext
+        )),
+        ("picture", "appext") if !is_gif(out_ext) => Some(format!(
+            "appext stores data in GIF application extensions, which .{} files don't have",
+            out_ext
+        )),
+        ("audio", _) if normalize_ext(out_ext) != "wav" => Some(format!(
+            "WAV carriers always write PCM WAV data, so the .{} file would just be a mislabeled WAV",
+            out_ext
+        )),
+        ("medical", _) if !matches!(normalize_ext(out_ext).as_str(), "dcm" | "dicom") => Some(format!(
+            "DICOM carriers always write a DICOM file, so the .{} file would just be a mislabeled DICOM",
+            out_ext
+        )),
+        ("astro", _) if !matches!(normalize_ext(out_ext).as_str(), "fits" | "fit" | "fts") => Some(format!(
+            "FITS carriers always write a FITS file, so the .{} file would just be a mislabeled FITS",
+            out_ext
+        )),
+        _ => None,
+    }
+}
+
+/// The likely reason an `alg` payload just written as `out_ext` didn't read back.
+pub fn likely_loss(filetype: &str, alg: &str, out_ext: &str) -> String {
+    if let Some(problem) = output_problem(filetype, alg, out_ext) {
+        return problem;
+    }
+    match (filetype, alg) {
+        ("picture", "overlay") => "the overlay is too faint to read back from this picture, raise --strength".to_string(),
+        (_, "lsb") => format!("the .{} encoder changed sample values (color, palette or bit depth conversion)", out_ext),
+        _ => "the output changed after it was written".to_string(),
+    }
+}
+
+/// An algorithm for `filetype` whose payload survives an `out_ext` output, if there is one.
+pub fn surviving_algorithm(filetype: &str, out_ext: &str) -> Option<&'static str> {
+    match filetype {
+        "picture" if is_jpeg(out_ext) => Some("marker"),
+        "picture" if is_lossless_picture(out_ext) => Some("lsb"),
+        _ => None,
+    }
+}
+
+/// The algorithm to use on an `ext` file of `filetype` when none is given, and why that one. The error
+/// for a filetype without algorithms lists the ones there are.
+pub fn default_algorithm(filetype: &str, ext: &str) -> Result<(&'static str, String), StegError> {
+    let ext = normalize_ext(ext);
+    match filetype {
+        "picture" if is_jpeg(&ext) => Ok(("marker", "JPEG re-encoding scrambles LSBs, marker leaves the pixels alone".to_string())),
+        "picture" if is_gif(&ext) => Ok(("appext", "GIF palettes don't keep LSBs, appext uses an extension block".to_string())),
+        "picture" if is_lossless_picture(&ext) => Ok(("lsb", format!(".{} keeps pixel values exactly", ext))),
+        "picture" => Ok(("lsb", format!("no algorithm is specific to .{}, lsb is the picture default", ext))),
+        "audio" if ext == "wav" || ext == "wave" => Ok(("lsb", "WAV keeps samples exactly".to_string())),
+        "audio" => Err(format!("There are no algorithms for .{} audio, only WAV (lsb, beat). Convert it to .wav first.", ext).into()),
+        "medical" => Ok(("tag", "a private DICOM tag leaves the pixel data alone".to_string())),
+        "astro" => Ok(("lsb", "FITS float mantissas have the most room".to_string())),
+        other => Err(format!("There are no {} algorithms yet. Available: {}", other, registry::available()).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_to_jpg_switches_lsb_to_marker() {
+        assert!(output_problem("picture", "lsb", "jpg").is_some());
+        assert_eq!(surviving_algorithm("picture", "JPEG"), Some("marker"));
+        assert!(output_problem("picture", "marker", "jpeg").is_none());
+    }
+
+    #[test]
+    fn jpg_to_png_switches_marker_to_lsb() {
+        assert!(output_problem("picture", "marker", "png").is_some());
+        assert_eq!(surviving_algorithm("picture", "png"), Some("lsb"));
+        assert!(output_problem("picture", "lsb", "png").is_none());
+    }
+
+    #[test]
+    fn lossy_outputs_are_explained() {
+        assert!(likely_loss("picture", "lsb", "jpg").contains("JPEG is lossy"));
+        assert!(likely_loss("picture", "overlay", "jpg").contains("--strength"));
+    }
+
+    #[test]
+    fn audio_has_no_alternative() {
+        assert!(output_problem("audio", "lsb", "mp3").is_some());
+        assert_eq!(surviving_algorithm("audio", "mp3"), None);
+    }
+
+    // the error lists the algorithms in the build
+    #[cfg(all(feature = "picture", feature = "audio", feature = "jpeg-marker"))]
+    #[test]
+    fn defaults_follow_the_extension() {
+        assert_eq!(default_algorithm("picture", "JPEG").unwrap().0, "marker");
+        assert_eq!(default_algorithm("picture", "png").unwrap().0, "lsb");
+        assert_eq!(default_algorithm("picture", "gif").unwrap().0, "appext");
+        assert_eq!(default_algorithm("audio", "wav").unwrap().0, "lsb");
+        assert!(default_algorithm("audio", "mp3").unwrap_err().to_string().contains("only WAV"));
+        let video = default_algorithm("video", "mp4").unwrap_err().to_string();
+        assert!(video.contains("picture (lsb, marker") && video.contains("audio (lsb, beat)"), "{}", video);
+    }
+}
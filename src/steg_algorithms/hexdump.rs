@@ -0,0 +1,54 @@
+// Binary payloads for a terminal, in the canonical layout of `hexdump -C`: the offset, 16 bytes in hex
+// split into two groups of eight, and the printable ones between bars, then a last line with the length.
+//
+//   00000000  72 75 73 74 2d 73 74 65  67 6f 0a 00 ff           |rust-stego...|
+//   0000000d
+
+const ROW: usize = 16;
+
+/// The line for `row` (at most 16 bytes) starting at `offset`.
+pub fn line(offset: usize, row: &[u8]) -> String {
+    let mut hex = String::with_capacity(3 * ROW + 1);
+    for i in 0..ROW {
+        if i == ROW / 2 {
+            hex.push(' ');
+        }
+        match row.get(i) {
+            Some(b) => hex.push_str(&format!("{:02x} ", b)),
+            None => hex.push_str("   "),
+        }
+    }
+    let ascii: String = row.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect();
+    format!("{:08x}  {} |{}|", offset, hex, ascii)
+}
+
+/// All of `data`, a line each, ending in a newline.
+pub fn dump(data: &[u8]) -> String {
+    let mut out = String::new();
+    for (i, row) in data.chunks(ROW).enumerate() {
+        out.push_str(&line(i * ROW, row));
+        out.push('\n');
+    }
+    out.push_str(&format!("{:08x}\n", data.len()));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_line_up_to_the_last_partial_one() {
+        let data: Vec<u8> = (0x41..0x41 + 20).chain([0x00, 0xff, b' ']).collect();
+        let out = dump(&data);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines, [
+            "00000000  41 42 43 44 45 46 47 48  49 4a 4b 4c 4d 4e 4f 50  |ABCDEFGHIJKLMNOP|",
+            "00000010  51 52 53 54 00 ff 20                              |QRST.. |",
+            "00000017",
+        ]);
+        // the ASCII column starts at the same place on every row
+        assert_eq!(lines[0].find('|'), lines[1].find('|'));
+        assert_eq!(dump(&[]), "00000000\n");
+    }
+}
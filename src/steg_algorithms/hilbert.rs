@@ -0,0 +1,117 @@
+// Hilbert traversal for the picture LSB carrier. A plain Hilbert curve only covers a square with a power
+// of two side, and cutting one down to a picture leaves it jumping across the gaps, so this is the
+// generalized curve (gilbert2d, Jakub Červený): the rectangle is split in halves and thirds down its
+// long side until what's left is a single row or column, which visits any width and height, odd ones
+// included, one neighbouring pixel after another. Where the long side is odd and the short one even it
+// can't get through in straight steps and takes a single diagonal one.
+// Like `scatter`, the order decides where payload bits are, so it is pinned: changing how the
+// rectangle is split breaks every carrier hidden along it.
+
+/// The pixels of a `width`x`height` rectangle along a generalized Hilbert curve, as (x, y) from the top
+/// left. Every pixel comes once, generated as they're needed.
+pub struct Hilbert {
+    // rectangles still to walk, the next one last: the corner and the two axes, as in gilbert2d
+    stack: Vec<[i64; 6]>,
+    // the row or column being walked: where, which way, and how many pixels are left in it
+    line: (i64, i64, i64, i64, i64),
+}
+
+impl Hilbert {
+    pub fn new(width: u32, height: u32) -> Self {
+        let (w, h) = (width as i64, height as i64);
+        let stack = match (w, h) {
+            (0, _) | (_, 0) => Vec::new(),
+            _ if w >= h => vec![[0, 0, w, 0, 0, h]],
+            _ => vec![[0, 0, 0, h, w, 0]],
+        };
+        Hilbert { stack, line: (0, 0, 0, 0, 0) }
+    }
+}
+
+impl Iterator for Hilbert {
+    type Item = (u32, u32);
+
+    fn next(&mut self) -> Option<(u32, u32)> {
+        loop {
+            let (x, y, dx, dy, left) = self.line;
+            if left > 0 {
+                self.line = (x + dx, y + dy, dx, dy, left - 1);
+                return Some((x as u32, y as u32));
+            }
+            let [x, y, ax, ay, bx, by] = self.stack.pop()?;
+            let (w, h) = ((ax + ay).abs(), (bx + by).abs());
+            let (dax, day, dbx, dby) = (ax.signum(), ay.signum(), bx.signum(), by.signum());
+            if h == 1 {
+                self.line = (x, y, dax, day, w);
+                continue;
+            }
+            if w == 1 {
+                self.line = (x, y, dbx, dby, h);
+                continue;
+            }
+            // floor division, as the reference's Python does
+            let (mut ax2, mut ay2, mut bx2, mut by2) = (ax.div_euclid(2), ay.div_euclid(2), bx.div_euclid(2), by.div_euclid(2));
+            let (w2, h2) = ((ax2 + ay2).abs(), (bx2 + by2).abs());
+            if 2 * w > 3 * h {
+                // long and thin: two halves along the long side, the first an even length
+                if w2 % 2 == 1 && w > 2 {
+                    (ax2, ay2) = (ax2 + dax, ay2 + day);
+                }
+                self.stack.push([x + ax2, y + ay2, ax - ax2, ay - ay2, bx, by]);
+                self.stack.push([x, y, ax2, ay2, bx, by]);
+            } else {
+                // up the first half of the short side, along the long one, and back down
+                if h2 % 2 == 1 && h > 2 {
+                    (bx2, by2) = (bx2 + dbx, by2 + dby);
+                }
+                self.stack.push([x + (ax - dax) + (bx2 - dbx), y + (ay - day) + (by2 - dby), -bx2, -by2, -(ax - ax2), -(ay - ay2)]);
+                self.stack.push([x + bx2, y + by2, ax, ay, bx - bx2, by - by2]);
+                self.stack.push([x, y, bx2, by2, ax2, ay2]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every pixel once, and each a step (straight or, once, diagonal) from the one before
+    fn check(width: u32, height: u32) {
+        let path: Vec<(u32, u32)> = Hilbert::new(width, height).collect();
+        assert_eq!(path.len(), width as usize * height as usize, "{}x{}", width, height);
+        let mut seen = vec![false; path.len()];
+        for &(x, y) in &path {
+            assert!(x < width && y < height, "{}x{}: ({}, {}) is outside", width, height, x, y);
+            let at = y as usize * width as usize + x as usize;
+            assert!(!seen[at], "{}x{}: ({}, {}) twice", width, height, x, y);
+            seen[at] = true;
+        }
+        let diagonal = path.windows(2).filter(|p| {
+            let (dx, dy) = (p[0].0.abs_diff(p[1].0), p[0].1.abs_diff(p[1].1));
+            assert!(dx <= 1 && dy <= 1, "{}x{}: jumps from {:?} to {:?}", width, height, p[0], p[1]);
+            dx + dy == 2
+        });
+        assert!(diagonal.count() <= 1, "{}x{}", width, height);
+    }
+
+    #[test]
+    fn visits_every_pixel_of_any_rectangle_once() {
+        for (w, h) in [(1, 1), (1, 7), (7, 1), (2, 2), (3, 3), (5, 8), (8, 5), (16, 16), (7, 4), (4, 7), (17, 31), (100, 3), (3, 100), (63, 64)] {
+            check(w, h);
+        }
+        check(1960, 1034);
+        assert_eq!(Hilbert::new(0, 5).count(), 0);
+    }
+
+    #[test]
+    fn order_is_pinned() {
+        // if this changes, every carrier hidden along the curve stops decoding
+        let first: Vec<(u32, u32)> = Hilbert::new(1960, 1034).take(8).collect();
+        assert_eq!(first, PINNED);
+        assert_eq!(Hilbert::new(1960, 1034).nth(1_000_000), Some((919, 69)));
+    }
+
+    // the reference gilbert2d's first steps over 1960x1034
+    const PINNED: [(u32, u32); 8] = [(0, 0), (1, 0), (1, 1), (0, 1), (0, 2), (0, 3), (1, 3), (1, 2)];
+}
@@ -0,0 +1,99 @@
+use crate::steg_algorithms::payload;
+
+// Carriers made before the RSTG framing (see payload.rs) hold the bare message right after the carrier's
+// own 32-bit length prefix, so they come out of the carriers as plain bytes without a magic header.
+// One wrinkle: the old `hide` command built a bit stream itself and handed that to the WAV carrier, which
+// turned it into bits again, so old CLI-made WAVs hold one byte (0 or 1) per message bit, led by their
+// own 32-bit length:
+//
+//   carrier length | 32 bytes of 0/1 (message length) | 8 bytes of 0/1 per message byte
+//
+// Old messages were always `--msg` text, which is what makes the `auto` guess safe.
+
+/// Whether `raw` (as a carrier returned it) is one of our frames or an FEC envelope around one. Anything
+/// framed is never read as legacy.
+pub fn is_framed(raw: &[u8]) -> bool {
+    raw.starts_with(&payload::MAGIC) || !matches!(payload::unprotect(raw), Ok(None))
+}
+
+/// The message in a legacy carrier's bytes. `audio` undoes the old CLI's bit-per-byte WAV encoding
+/// when the bytes look like it.
+pub fn unpack(raw: &[u8], audio: bool) -> Vec<u8> {
+    if audio && let Some(msg) = unpack_bit_bytes(raw) {
+        return msg;
+    }
+    raw.to_vec()
+}
+
+fn unpack_bit_bytes(raw: &[u8]) -> Option<Vec<u8>> {
+    if raw.len() < 32 || raw.iter().any(|&b| b > 1) {
+        return None;
+    }
+    let byte = |bits: &[u8]| bits.iter().fold(0u32, |v, &b| (v << 1) | b as u32);
+    let len = byte(&raw[..32]) as usize;
+    if raw.len() != 32 + len * 8 {
+        return None;
+    }
+    Some(raw[32..].chunks_exact(8).map(|c| byte(c) as u8).collect())
+}
+
+/// The message if `raw` is plausibly a legacy payload: unframed, non-empty text.
+pub fn detect(raw: &[u8], audio: bool) -> Option<Vec<u8>> {
+    if is_framed(raw) {
+        return None;
+    }
+    let msg = unpack(raw, audio);
+    let text = std::str::from_utf8(&msg).ok()?;
+    let plausible = !text.is_empty() && text.chars().all(|c| !c.is_control() || matches!(c, '\n' | '\r' | '\t'));
+    plausible.then_some(msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::steg_algorithms::payload::{FrameOptions, Payload};
+    #[cfg(all(feature = "picture", feature = "audio"))]
+    use {
+        crate::steg_algorithms::{audio::wav, picture::{general::lsb, jpg::marker_hijacking}},
+        std::path::{Path, PathBuf},
+    };
+
+    // made with the pre-framing code (baseline commit), don't regenerate them with the current one
+    #[cfg(all(feature = "picture", feature = "audio"))]
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/legacy").join(name)
+    }
+
+    #[cfg(all(feature = "picture", feature = "audio"))]
+    #[test]
+    fn reads_carriers_made_before_framing() {
+        let png = lsb::find_payload_sparse(&fixture("lsb.png"), None).unwrap();
+        assert_eq!(detect(&png, false).unwrap(), b"old picture secret");
+
+        let jpg = marker_hijacking::find_payload(&fixture("marker.jpg")).unwrap();
+        assert_eq!(detect(&jpg, false).unwrap(), b"old marker secret");
+
+        // the old CLI's doubled encoding and the carrier used directly
+        let cli = wav::lsb::find_wav_sparse(&fixture("cli.wav"), None).unwrap();
+        assert_eq!(detect(&cli, true).unwrap(), b"old wav secret");
+        let direct = wav::lsb::find_wav_sparse(&fixture("direct.wav"), None).unwrap();
+        assert_eq!(detect(&direct, true).unwrap(), b"hello wav stego!");
+    }
+
+    #[test]
+    fn framed_payloads_are_never_legacy() {
+        // even when the message inside is the kind of text legacy carriers hold
+        for opts in [FrameOptions::default(), FrameOptions { fec_parity: Some(8), ..Default::default() }] {
+            let mut framed = Payload::from_text("plain text").encode(&opts).unwrap();
+            assert!(is_framed(&framed));
+            assert_eq!(detect(&framed, false), None);
+            if opts.fec_parity.is_some() {
+                // a damaged first header copy still reads as an envelope
+                framed[0] ^= 0xFF;
+                assert_eq!(detect(&framed, true), None);
+            }
+        }
+        assert_eq!(detect(&[0x00, 0x9F, 0x13], false), None, "binary noise isn't a legacy message");
+        assert_eq!(detect(b"", false), None);
+    }
+}
@@ -0,0 +1,182 @@
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::steg_algorithms::atomic;
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::options::HideOptions;
+
+// The sidecar `hide --manifest` writes next to a carrier: the algorithm and the options it was hidden
+// with, so `find --manifest` can read it back without being told bits=2,stride=3 or the APPn marker, and
+// the payload's and carrier's hashes, so a batch leaves a record of what went where. The key and the
+// password are never in it, only whether find needs them; a manifest that does name one is refused
+// rather than read.
+//
+// `version` goes up when a field changes meaning or a new one is needed to read the payload back. Fields
+// this build doesn't know are an error, not skipped: a manifest from a newer version that got through
+// would read the carrier with half its settings.
+
+/// The manifest version this build writes and reads.
+pub const VERSION: u32 = 1;
+
+// what a manifest never holds, but someone might put in one by hand
+const SECRETS: [&str; 4] = ["key", "password", "hmac_key", "key_share"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    /// The rust-stego that wrote it.
+    pub tool_version: String,
+    pub unix_time: u64,
+    pub filetype: String,
+    pub algorithm: String,
+    /// The layout, copies, marker segments and cipher, without the lsb key or the password.
+    pub options: HideOptions,
+    /// appext: the GIF application identifier.
+    pub app_id: Option<String>,
+    pub secrets: Secrets,
+    pub payload: PayloadRecord,
+    pub carrier: CarrierRecord,
+}
+
+/// What find needs besides the manifest, which never holds it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Secrets {
+    /// The bits were scattered with `--key`.
+    pub key: bool,
+    /// The payload is encrypted with `--password`.
+    pub password: bool,
+    /// The payload is encrypted under a key split into two `--key-share` files.
+    pub key_shares: bool,
+    /// The payload carries an HMAC tag `--hmac-key` checks.
+    pub hmac_key: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct PayloadRecord {
+    /// The filename stored with it (`--msg-file`).
+    pub name: Option<String>,
+    /// The name it is stored under among the carrier's named payloads (`--name`).
+    pub stored_as: Option<String>,
+    pub len: usize,
+    pub sha256: String,
+    /// Bytes in the carrier: the payload framed, compressed, encrypted and padded.
+    pub framed_len: usize,
+    pub compressed: bool,
+    /// Reed-Solomon parity bytes per 255 (`--fec`).
+    pub fec_parity: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct CarrierRecord {
+    pub input: String,
+    pub input_sha256: String,
+    pub output: String,
+    pub output_sha256: String,
+}
+
+impl Manifest {
+    /// A manifest of the current version, stamped with the current time.
+    pub fn new(filetype: &str, algorithm: &str, mut options: HideOptions) -> Self {
+        // the options skip them when serializing, but a manifest must never carry them at all
+        options.lsb.key = None;
+        options.password = None;
+        Manifest {
+            version: VERSION,
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            unix_time: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+            filetype: filetype.to_string(),
+            algorithm: algorithm.to_string(),
+            options,
+            ..Manifest::default()
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("manifests always serialize")
+    }
+
+    /// Read a manifest written by `to_json`, checking its version and that every field in it is one
+    /// this version has.
+    pub fn parse(text: &str) -> Result<Manifest, StegError> {
+        let doc: Value = serde_json::from_str(text).map_err(|e| format!("Manifest is not valid JSON: {}", e))?;
+        let version = match doc.get("version") {
+            Some(v) => v.as_u64().ok_or("Manifest version must be a number")?,
+            None if doc.is_object() => return Err("Not a rust-stego manifest, it has no version".into()),
+            None => return Err("Not a rust-stego manifest, it isn't a JSON object".into()),
+        };
+        if version > VERSION as u64 {
+            return Err(format!("Manifest version {} is newer than this build reads (up to {}), update rust-stego", version, VERSION).into());
+        }
+        if version == 0 {
+            return Err("Manifest version 0 doesn't exist, the first is 1".into());
+        }
+        let known = serde_json::to_value(Manifest::default()).expect("manifests always serialize");
+        check_fields(&doc, &known, "")?;
+        serde_json::from_value(doc).map_err(|e| format!("Manifest: {}", e).into())
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), StegError> {
+        atomic::write(path, self.to_json() + "\n").map_err(|e| e.context(format!("Failed to write manifest {}", path.display())))
+    }
+
+    pub fn load(path: &Path) -> Result<Manifest, StegError> {
+        let text = fs::read_to_string(path).map_err(|e| StegError::io(format!("Failed to read manifest {}", path.display()), e))?;
+        Manifest::parse(&text).map_err(|e| e.context(path.display()))
+    }
+}
+
+// every field of `given` has to be in `known` (a default manifest), in the nested objects too
+fn check_fields(given: &Value, known: &Value, at: &str) -> Result<(), StegError> {
+    let (Value::Object(given), Value::Object(known)) = (given, known) else {
+        return Ok(());
+    };
+    for (field, value) in given {
+        let path = if at.is_empty() { field.clone() } else { format!("{}.{}", at, field) };
+        match known.get(field) {
+            Some(k) => check_fields(value, k, &path)?,
+            None if SECRETS.contains(&field.as_str()) => {
+                return Err(format!("Manifest has a `{}`, which manifests never hold; give it to find instead", path).into());
+            }
+            None => {
+                let fields: Vec<&str> = known.keys().map(String::as_str).collect();
+                return Err(format!("Unknown field `{}` in a version {} manifest (expected one of {})", path, VERSION, fields.join(", ")).into());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::steg_algorithms::picture::general::lsb::Region;
+
+    #[test]
+    fn manifests_read_back_and_refuse_what_they_dont_know() {
+        let opts = HideOptions::default().bits(2).stride(3).region(Region::parse("1,2,30,40").unwrap()).key("k").password("pw");
+        let mut manifest = Manifest::new("picture", "lsb", opts);
+        manifest.secrets = Secrets { key: true, password: true, ..Secrets::default() };
+        manifest.payload = PayloadRecord { len: 5, sha256: "ab".repeat(32), framed_len: 20, ..PayloadRecord::default() };
+        let json = manifest.to_json();
+        assert!(!json.contains("\"pw\"") && !json.contains("\"k\""));
+
+        let back = Manifest::parse(&json).unwrap();
+        assert_eq!(back.options.lsb.key, None);
+        assert_eq!((back.options.lsb.bits, back.options.lsb.stride, back.options.lsb.region), (2, Some(3), manifest.options.lsb.region));
+        assert_eq!(back.payload, manifest.payload);
+
+        let with = |edit: &dyn Fn(&mut Value)| {
+            let mut doc: Value = serde_json::from_str(&json).unwrap();
+            edit(&mut doc);
+            Manifest::parse(&doc.to_string()).unwrap_err().to_string()
+        };
+        assert!(with(&|d| d["options"]["strid"] = 4.into()).contains("Unknown field `options.strid`"));
+        assert!(with(&|d| d["options"]["marker"]["ap"] = 4.into()).contains("`options.marker.ap`"));
+        assert!(with(&|d| d["options"]["key"] = "k".into()).contains("never hold"));
+        assert!(with(&|d| d["version"] = 2.into()).contains("newer than this build"));
+        assert!(with(&|d| { d.as_object_mut().unwrap().remove("version"); }).contains("no version"));
+        assert!(with(&|d| d["options"]["stride"] = "three".into()).starts_with("Manifest: "));
+    }
+}
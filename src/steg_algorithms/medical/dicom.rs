@@ -0,0 +1,415 @@
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use crate::steg_algorithms::atomic;
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::picture::general::lsb::{self, Order};
+use crate::steg_algorithms::redundancy;
+
+// DICOM files (PS3.10): a 128-byte preamble, "DICM", then data elements in ascending tag order. The
+// file meta group (0002,xxxx) is always explicit VR little endian; its transfer syntax says how the
+// rest is encoded. Only the two uncompressed little-endian syntaxes are handled, compressed pixel
+// data would have to be decoded and encoded again.
+//
+//   element   group (2) | element (2) | VR (2) | length (2, or 2 reserved + 4 for OB/OW/SQ/...) | value
+//             implicit VR leaves the VR out and always has a 4-byte length
+//
+// Two places for a payload:
+//   tag   a private element (0009,xx00) in the block reserved by our private creator (0009,00xx)
+//         "RUST-STEGO", holding a 4-byte length and the bytes. The pixels aren't touched.
+//   lsb   the low bit of every Pixel Data sample, with the bitstream and options `lsb` uses.
+// Every other element is copied byte for byte, and the written file is parsed again to check that,
+// so patient, study and UID tags come out exactly as they went in.
+
+type Tag = (u16, u16);
+
+const PREAMBLE_LEN: usize = 128;
+const MAGIC: &[u8; 4] = b"DICM";
+const IMPLICIT_LE: &str = "1.2.840.10008.1.2";
+const EXPLICIT_LE: &str = "1.2.840.10008.1.2.1";
+const UNDEFINED_LENGTH: u32 = 0xFFFF_FFFF;
+// sequences of undefined length nest, don't follow a hostile file forever
+const MAX_DEPTH: usize = 64;
+
+const META_GROUP: u16 = 0x0002;
+const TRANSFER_SYNTAX: Tag = (0x0002, 0x0010);
+const BITS_ALLOCATED: Tag = (0x0028, 0x0100);
+const PIXEL_DATA: Tag = (0x7FE0, 0x0010);
+const ITEM_END: Tag = (0xFFFE, 0xE00D);
+const SEQUENCE_END: Tag = (0xFFFE, 0xE0DD);
+
+const PRIVATE_GROUP: u16 = 0x0009;
+const CREATOR: &str = "RUST-STEGO";
+
+struct Element {
+    tag: Tag,
+    start: usize,
+    value: Range<usize>,
+    undefined_length: bool,
+}
+
+impl Element {
+    fn end(&self) -> usize {
+        self.value.end
+    }
+}
+
+/// A parsed file: the top-level data set elements, nested ones are skipped over.
+struct Dicom {
+    buf: Vec<u8>,
+    explicit_vr: bool,
+    elements: Vec<Element>,
+}
+
+// VRs with the long (2 reserved + 4 bytes) length in explicit VR
+fn long_length(vr: &[u8]) -> bool {
+    matches!(vr, b"OB" | b"OD" | b"OF" | b"OL" | b"OV" | b"OW" | b"SQ" | b"SV" | b"UC" | b"UN" | b"UR" | b"UT" | b"UV")
+}
+
+fn le16(b: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([b[at], b[at + 1]])
+}
+
+fn le32(b: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap())
+}
+
+fn element_at(buf: &[u8], pos: usize, explicit_vr: bool, depth: usize) -> Result<Element, StegError> {
+    let header = |n: usize| buf.get(pos..pos + n).ok_or_else(|| format!("Element at offset {} is truncated", pos));
+    let h = header(8)?;
+    let tag = (le16(h, 0), le16(h, 2));
+    // item and delimiter tags never have a VR
+    let (len, value_start) = if explicit_vr && tag.0 != 0xFFFE {
+        if long_length(&h[4..6]) { (le32(header(12)?, 8), pos + 12) } else { (le16(h, 6) as u32, pos + 8) }
+    } else {
+        (le32(h, 4), pos + 8)
+    };
+    let end = if len == UNDEFINED_LENGTH {
+        if depth >= MAX_DEPTH {
+            return Err("Sequences nest too deeply".into());
+        }
+        // items or elements up to the matching delimiter
+        let mut at = value_start;
+        loop {
+            let inner = element_at(buf, at, explicit_vr, depth + 1)?;
+            at = inner.end();
+            if inner.tag == ITEM_END || inner.tag == SEQUENCE_END {
+                break at;
+            }
+        }
+    } else {
+        value_start.checked_add(len as usize).filter(|&end| end <= buf.len())
+            .ok_or_else(|| format!("Element ({:04X},{:04X}) runs past the end of the file", tag.0, tag.1))?
+    };
+    Ok(Element { tag, start: pos, value: value_start..end, undefined_length: len == UNDEFINED_LENGTH })
+}
+
+fn encode_element(tag: Tag, vr: &[u8; 2], value: &[u8], explicit_vr: bool) -> Vec<u8> {
+    let mut out = Vec::with_capacity(12 + value.len());
+    out.extend_from_slice(&tag.0.to_le_bytes());
+    out.extend_from_slice(&tag.1.to_le_bytes());
+    if !explicit_vr {
+        out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    } else if long_length(vr) {
+        out.extend_from_slice(vr);
+        out.extend_from_slice(&[0, 0]);
+        out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    } else {
+        out.extend_from_slice(vr);
+        out.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    }
+    out.extend_from_slice(value);
+    out
+}
+
+fn text(value: &[u8]) -> &str {
+    std::str::from_utf8(value).unwrap_or_default().trim_end_matches(['\0', ' '])
+}
+
+fn parse(buf: Vec<u8>) -> Result<Dicom, StegError> {
+    if buf.get(PREAMBLE_LEN..PREAMBLE_LEN + 4) != Some(MAGIC) {
+        return Err("Not a DICOM file (no DICM after the preamble)".into());
+    }
+    let mut pos = PREAMBLE_LEN + 4;
+    let mut syntax = None;
+    while buf.get(pos..pos + 2).is_some_and(|g| le16(g, 0) == META_GROUP) {
+        let el = element_at(&buf, pos, true, 0)?;
+        if el.tag == TRANSFER_SYNTAX {
+            syntax = Some(text(&buf[el.value.clone()]).to_string());
+        }
+        pos = el.end();
+    }
+    let explicit_vr = match syntax.as_deref() {
+        Some(EXPLICIT_LE) => true,
+        Some(IMPLICIT_LE) => false,
+        Some(other) => return Err(format!("Transfer syntax {} isn't uncompressed little endian ({} or {})", other, EXPLICIT_LE, IMPLICIT_LE).into()),
+        None => return Err("No transfer syntax in the file meta information".into()),
+    };
+    let mut elements = Vec::new();
+    while pos < buf.len() {
+        let el = element_at(&buf, pos, explicit_vr, 0)?;
+        pos = el.end();
+        elements.push(el);
+    }
+    Ok(Dicom { buf, explicit_vr, elements })
+}
+
+fn read(path: &Path) -> Result<Dicom, StegError> {
+    if !path.exists() {
+        return Err(StegError::not_found(path));
+    }
+    parse(fs::read(path)?)
+}
+
+impl Dicom {
+    fn get(&self, tag: Tag) -> Option<&Element> {
+        self.elements.iter().find(|e| e.tag == tag)
+    }
+
+    fn value(&self, el: &Element) -> &[u8] {
+        &self.buf[el.value.clone()]
+    }
+
+    // private creators live at (gggg,0010)..(gggg,00FF), each reserving the elements (gggg,xx00)..(gggg,xxFF)
+    fn creators(&self) -> impl Iterator<Item = (u16, &str)> {
+        self.elements
+            .iter()
+            .filter(|e| e.tag.0 == PRIVATE_GROUP && (0x10..=0xFF).contains(&e.tag.1))
+            .map(|e| (e.tag.1, text(self.value(e))))
+    }
+
+    /// The element our payload goes in, and whether the creator that reserves it has to be added.
+    fn payload_tag(&self) -> Result<(Tag, bool), StegError> {
+        if let Some((block, _)) = self.creators().find(|&(_, name)| name == CREATOR) {
+            return Ok(((PRIVATE_GROUP, block << 8), false));
+        }
+        let taken: Vec<u16> = self.creators().map(|(block, _)| block).collect();
+        let block = (0x10..=0xFF).find(|b| !taken.contains(b)).ok_or("Every private block in group 0009 is taken")?;
+        Ok(((PRIVATE_GROUP, block << 8), true))
+    }
+
+    /// The file with `new` elements put in tag order, replacing any with the same tag.
+    fn with_elements(&self, mut new: Vec<(Tag, Vec<u8>)>) -> Vec<u8> {
+        new.sort_by_key(|(tag, _)| *tag);
+        let body = self.elements.first().map_or(self.buf.len(), |e| e.start);
+        let mut out = self.buf[..body].to_vec();
+        let mut pending = new.iter().peekable();
+        for el in &self.elements {
+            while let Some((_, bytes)) = pending.next_if(|(tag, _)| *tag <= el.tag) {
+                out.extend_from_slice(bytes);
+            }
+            if !new.iter().any(|(tag, _)| *tag == el.tag) {
+                out.extend_from_slice(&self.buf[el.start..el.end()]);
+            }
+        }
+        pending.for_each(|(_, bytes)| out.extend_from_slice(bytes));
+        out
+    }
+
+    /// The Pixel Data value and the bytes per sample, for native (uncompressed) pixel data.
+    fn pixels(&self) -> Result<(Range<usize>, usize), StegError> {
+        let el = self.get(PIXEL_DATA).ok_or("No Pixel Data in this file")?;
+        if el.undefined_length {
+            return Err("Pixel Data is encapsulated (compressed), it has no LSBs to use".into());
+        }
+        let bits = self.get(BITS_ALLOCATED).map(|e| self.value(e)).filter(|v| v.len() >= 2).map(|v| le16(v, 0));
+        match bits {
+            Some(8) => Ok((el.value.clone(), 1)),
+            Some(16) => Ok((el.value.clone(), 2)),
+            Some(n) => Err(format!("{}-bit samples aren't supported, only 8 and 16", n).into()),
+            None => Err("No Bits Allocated in this file".into()),
+        }
+    }
+}
+
+// parse what we're about to write and make sure every element except `changed` is still there,
+// byte for byte and in the same order
+fn check_preserved(before: &Dicom, after: Vec<u8>, changed: &[Tag]) -> Result<Vec<u8>, StegError> {
+    let after = parse(after)?;
+    let kept = |d: &Dicom| -> Vec<(Tag, Vec<u8>)> {
+        d.elements.iter().filter(|e| !changed.contains(&e.tag)).map(|e| (e.tag, d.buf[e.start..e.end()].to_vec())).collect()
+    };
+    if kept(before) != kept(&after) {
+        return Err("Writing the payload would change other elements, refusing to write it".into());
+    }
+    Ok(after.buf)
+}
+
+/// Largest payload `hide_tag` takes: an element's 32-bit length, kept even, minus the 4-byte length.
+pub fn tag_capacity() -> usize {
+    (UNDEFINED_LENGTH - 1) as usize - 4
+}
+
+/// Hide `msg` in a private element of the DICOM file at `path` and write it to `out_path`. A payload
+/// already there is replaced.
+pub fn hide_tag(path: &Path, msg: impl AsRef<[u8]>, out_path: &Path) -> Result<(), StegError> {
+    let msg = msg.as_ref();
+    if msg.len() > tag_capacity() {
+        return Err(StegError::CapacityExceeded { needed: msg.len(), available: tag_capacity() });
+    }
+    let dicom = read(path)?;
+    let (tag, add_creator) = dicom.payload_tag()?;
+    let mut value = (msg.len() as u32).to_be_bytes().to_vec();
+    value.extend_from_slice(msg);
+    // values have even lengths
+    if value.len() % 2 == 1 {
+        value.push(0);
+    }
+    let mut new = vec![(tag, encode_element(tag, b"OB", &value, dicom.explicit_vr))];
+    if add_creator {
+        let creator = (PRIVATE_GROUP, tag.1 >> 8);
+        new.push((creator, encode_element(creator, b"LO", CREATOR.as_bytes(), dicom.explicit_vr)));
+    }
+    let changed: Vec<Tag> = new.iter().map(|(tag, _)| *tag).collect();
+    let out = check_preserved(&dicom, dicom.with_elements(new), &changed)?;
+    atomic::write(out_path, out)
+}
+
+/// Extract a payload written by `hide_tag`.
+pub fn find_tag(path: &Path) -> Result<Vec<u8>, StegError> {
+    let dicom = read(path)?;
+    let (tag, missing) = dicom.payload_tag()?;
+    let el = dicom.get(tag).filter(|_| !missing).ok_or_else(|| format!("No {} private element in this file", CREATOR))?;
+    let value = dicom.value(el);
+    let len = value.get(..4).map(|b| u32::from_be_bytes(b.try_into().unwrap()) as usize).ok_or("Private element is too short")?;
+    value.get(4..4 + len).map(<[u8]>::to_vec).ok_or_else(|| "Private element is shorter than its length header".into())
+}
+
+/// How many bytes `hide_lsb` can embed into the pixels of the file at `path` with the given stride.
+pub fn capacity(path: &Path, stride: usize) -> Result<usize, StegError> {
+    if stride == 0 {
+        return Err("Stride must be at least 1".into());
+    }
+    let (pixels, sample_bytes) = read(path)?.pixels()?;
+    Ok(((pixels.len() / sample_bytes).div_ceil(stride) / 8).saturating_sub(4))
+}
+
+/// Hide `msg` in the Pixel Data LSBs of the DICOM file at `path` and write it to `out_path`. `key`,
+/// `stride` and `copies` work as in `raw::hide`. Samples are little endian, the bit goes into the low
+/// byte.
+pub fn hide_lsb(path: &Path, msg: impl AsRef<[u8]>, out_path: &Path, stride: usize, key: Option<&str>, copies: usize) -> Result<(), StegError> {
+    if stride == 0 {
+        return Err("Stride must be at least 1".into());
+    }
+    let dicom = read(path)?;
+    let (pixels, sample_bytes) = dicom.pixels()?;
+    let slots = pixels.len() / sample_bytes;
+    let order = key.map_or(Order::Strided(stride), Order::Keyed);
+    let bits = redundancy::bitstream(msg.as_ref(), copies)?;
+    let capacity_bits = order.usable(slots);
+    if bits.len() > capacity_bits {
+        return Err(StegError::CapacityExceeded { needed: bits.len().div_ceil(8), available: capacity_bits / 8 });
+    }
+    let mut out = dicom.buf.clone();
+    for (slot, &bit) in order.slots(slots).zip(&bits) {
+        let at = pixels.start + slot * sample_bytes;
+        out[at] = (out[at] & !1) | bit;
+    }
+    let out = check_preserved(&dicom, out, &[PIXEL_DATA])?;
+    atomic::write(out_path, out)
+}
+
+/// Extract a payload written by `hide_lsb`. Without `key` and `stride` the stride is probed like
+/// `lsb::find_payload_sparse` does.
+pub fn find_lsb(path: &Path, stride: Option<usize>, key: Option<&str>) -> Result<Vec<u8>, StegError> {
+    let dicom = read(path)?;
+    let (pixels, sample_bytes) = dicom.pixels()?;
+    let bits: Vec<u8> = dicom.buf[pixels].iter().step_by(sample_bytes).map(|b| b & 1).collect();
+    match key {
+        Some(k) => lsb::extract_keyed(&bits, k),
+        None => lsb::extract_sparse(&bits, stride),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    // a small 16-bit CT-like image, with a nested sequence of undefined length and another vendor's
+    // private block already in group 0009
+    fn dicom(syntax: &str) -> Vec<u8> {
+        let explicit_vr = syntax != IMPLICIT_LE;
+        let mut buf = vec![0u8; PREAMBLE_LEN];
+        buf.extend_from_slice(MAGIC);
+        let mut uid = syntax.as_bytes().to_vec();
+        if uid.len() % 2 == 1 {
+            uid.push(0);
+        }
+        let meta = encode_element(TRANSFER_SYNTAX, b"UI", &uid, true);
+        buf.extend(encode_element((0x0002, 0x0000), b"UL", &(meta.len() as u32).to_le_bytes(), true));
+        buf.extend(meta);
+
+        let el = |tag: Tag, vr: &[u8; 2], value: &[u8]| encode_element(tag, vr, value, explicit_vr);
+        buf.extend(el((0x0008, 0x0016), b"UI", b"1.2.840.10008.5.1.4.1.1.2\0"));
+        buf.extend(el((0x0008, 0x0018), b"UI", b"1.2.3.4.5.6\0"));
+        let mut seq = vec![0x08, 0x00, 0x15, 0x11];
+        if explicit_vr {
+            seq.extend_from_slice(b"SQ\0\0");
+        }
+        seq.extend_from_slice(&UNDEFINED_LENGTH.to_le_bytes());
+        seq.extend_from_slice(&[0xFE, 0xFF, 0x00, 0xE0, 0xFF, 0xFF, 0xFF, 0xFF]);
+        seq.extend(el((0x0008, 0x1155), b"UI", b"1.2.3.7\0"));
+        seq.extend_from_slice(&[0xFE, 0xFF, 0x0D, 0xE0, 0, 0, 0, 0, 0xFE, 0xFF, 0xDD, 0xE0, 0, 0, 0, 0]);
+        buf.extend(seq);
+        buf.extend(el((0x0009, 0x0010), b"LO", b"OTHER VENDOR"));
+        buf.extend(el((0x0009, 0x1000), b"OB", b"\x01\x02"));
+        buf.extend(el((0x0010, 0x0010), b"PN", b"Doe^Jane"));
+        buf.extend(el((0x0010, 0x0020), b"LO", b"PID-0042"));
+        buf.extend(el((0x0028, 0x0010), b"US", &32u16.to_le_bytes()));
+        buf.extend(el((0x0028, 0x0011), b"US", &32u16.to_le_bytes()));
+        buf.extend(el(BITS_ALLOCATED, b"US", &16u16.to_le_bytes()));
+        buf.extend(el((0x0028, 0x0101), b"US", &12u16.to_le_bytes()));
+        let samples: Vec<u8> = (0..32 * 32u16).flat_map(|i| (i * 37 % 4096).to_le_bytes()).collect();
+        buf.extend(el(PIXEL_DATA, b"OW", &samples));
+        buf
+    }
+
+    #[test]
+    fn private_tag_leaves_everything_else_alone() {
+        let dir = tempdir().unwrap();
+        for syntax in [EXPLICIT_LE, IMPLICIT_LE] {
+            let (cover, stego) = (dir.path().join("c.dcm"), dir.path().join("s.dcm"));
+            let orig = dicom(syntax);
+            fs::write(&cover, &orig).unwrap();
+
+            hide_tag(&cover, b"provenance: scanner 7, site B", &stego).unwrap();
+            assert_eq!(find_tag(&stego).unwrap(), b"provenance: scanner 7, site B", "{}", syntax);
+            let out = read(&stego).unwrap();
+            // (0009,0010) belongs to someone else, so ours is the next block
+            assert_eq!(text(out.value(out.get((0x0009, 0x0011)).unwrap())), CREATOR);
+            assert!(out.get((0x0009, 0x1100)).is_some());
+            assert_eq!(out.elements.len(), parse(orig.clone()).unwrap().elements.len() + 2);
+            assert!(out.buf.ends_with(&orig[orig.len() - 2048..]), "pixels untouched");
+
+            // hiding again replaces the payload instead of adding another
+            hide_tag(&stego, b"odd", &stego).unwrap();
+            assert_eq!(find_tag(&stego).unwrap(), b"odd");
+            assert_eq!(read(&stego).unwrap().elements.len(), out.elements.len());
+            assert!(find_tag(&cover).is_err());
+        }
+    }
+
+    #[test]
+    fn pixel_lsbs_only_touch_low_bytes() {
+        let dir = tempdir().unwrap();
+        let (cover, stego) = (dir.path().join("c.dcm"), dir.path().join("s.dcm"));
+        let orig = dicom(EXPLICIT_LE);
+        fs::write(&cover, &orig).unwrap();
+        assert_eq!(capacity(&cover, 2).unwrap(), 1024 / 2 / 8 - 4);
+
+        hide_lsb(&cover, b"watermark", &stego, 2, None, 1).unwrap();
+        assert_eq!(find_lsb(&stego, Some(2), None).unwrap(), b"watermark");
+        hide_lsb(&cover, b"keyed", &stego, 1, Some("k"), 3).unwrap();
+        assert_eq!(find_lsb(&stego, None, Some("k")).unwrap(), b"keyed");
+
+        let out = fs::read(&stego).unwrap();
+        let pixels = orig.len() - 2048;
+        assert_eq!(out[..pixels], orig[..pixels]);
+        assert!(out[pixels..].iter().zip(&orig[pixels..]).all(|(a, b)| a ^ b <= 1));
+        assert!(out[pixels + 1..].iter().step_by(2).eq(orig[pixels + 1..].iter().step_by(2)), "high bytes stay");
+
+        fs::write(&cover, dicom("1.2.840.10008.1.2.4.50")).unwrap();
+        assert!(hide_lsb(&cover, b"x", &stego, 1, None, 1).unwrap_err().to_string().contains("isn't uncompressed"));
+    }
+}
@@ -0,0 +1,114 @@
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::parse::{self, Chunk};
+use crate::steg_algorithms::picture::general::png_chunks;
+use crate::steg_algorithms::picture::jpg::marker_hijacking::{self, MarkerOptions};
+use crate::steg_algorithms::wipe;
+
+// `hide --strip-metadata` and `info`: what a JPEG or PNG says about where it came from rather than how to
+// show it. In a JPEG that's the APP1 (EXIF, XMP), APP13 (Photoshop/IPTC) and COM segments before the
+// scan, in a PNG the tEXt, zTXt, iTXt and eXIf chunks. Only whole segments and chunks are cut, so the scan
+// and IDAT data, and with them the pixels and any LSB payload, stay byte for byte what they were.
+
+const JPEG_MARKERS: [u8; 3] = [0xE1, 0xED, 0xFE];
+const PNG_CHUNKS: [[u8; 4]; 4] = [*b"tEXt", *b"zTXt", *b"iTXt", *b"eXIf"];
+
+/// The metadata in `buf` as (start, end, what it is), in file order; none in files that aren't a JPEG or
+/// PNG. Segments holding `keep`'s payload are the payload rather than metadata and aren't included.
+pub fn find(buf: &[u8], keep: Option<&MarkerOptions>) -> Result<Vec<(usize, usize, String)>, StegError> {
+    let mode = parse::mode();
+    if buf.starts_with(&png_chunks::SIGNATURE) {
+        return Ok(png_chunks::chunks(buf, mode)?.items.into_iter()
+            .filter(|c| PNG_CHUNKS.contains(&c.id))
+            .map(|c| (c.start, c.end, chunk_name(buf, &c)))
+            .collect());
+    }
+    if buf.starts_with(&[0xFF, 0xD8]) {
+        return Ok(marker_hijacking::header_segments(buf, mode)?.items.into_iter()
+            .filter_map(|(marker, start, end)| {
+                let body = &buf[(start + 4).min(end)..end];
+                (JPEG_MARKERS.contains(&marker) && !keep.is_some_and(|k| k.carries(body)))
+                    .then(|| (start, end, wipe::segment_name(marker, body)))
+            })
+            .collect());
+    }
+    Ok(Vec::new())
+}
+
+/// `buf` without its metadata (see `find`), and what was cut.
+pub fn strip(buf: &[u8], keep: Option<&MarkerOptions>) -> Result<(Vec<u8>, Vec<String>), StegError> {
+    let found = find(buf, keep)?;
+    let ranges: Vec<(usize, usize)> = found.iter().map(|&(start, end, _)| (start, end)).collect();
+    let cut = found.into_iter().map(|(start, end, what)| format!("{} ({} bytes)", what, end - start)).collect();
+    Ok((wipe::cut(buf, &ranges), cut))
+}
+
+fn chunk_name(buf: &[u8], chunk: &Chunk) -> String {
+    let id = String::from_utf8_lossy(&chunk.id);
+    if &chunk.id == b"eXIf" {
+        return format!("{} chunk", id);
+    }
+    // the text chunks start with a NUL-terminated keyword of up to 79 bytes
+    let keyword: String = buf[(chunk.start + 8).min(chunk.end)..chunk.end].iter()
+        .take(79)
+        .take_while(|&&b| b != 0)
+        .map(|&b| b as char)
+        .collect();
+    format!("{} chunk '{}'", id, keyword)
+}
+
+#[cfg(all(test, feature = "picture"))]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+    use tempfile::tempdir;
+    use crate::steg_algorithms::picture::general::{lsb, transcode};
+
+    fn segment(marker: u8, body: &[u8]) -> Vec<u8> {
+        [&[0xFF, marker][..], &((body.len() + 2) as u16).to_be_bytes(), body].concat()
+    }
+
+    fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let body = [&id[..], data].concat();
+        [&(data.len() as u32).to_be_bytes()[..], &body, &crc32fast::hash(&body).to_be_bytes()].concat()
+    }
+
+    #[test]
+    fn jpeg_metadata_goes_and_the_marker_payload_stays() {
+        let dir = tempdir().unwrap();
+        let cover = dir.path().join("c.png");
+        RgbImage::from_fn(32, 32, |x, y| Rgb([(x * 8) as u8, (y * 8) as u8, 7])).save(&cover).unwrap();
+        let jpeg = transcode::to_jpeg(&cover, 90).unwrap();
+        let tagged = [&jpeg[..2], &segment(0xE1, b"Exif\0\0camera"), &segment(0xFE, b"shot on a phone"), &segment(0xED, b"Photoshop 3.0\0"), &jpeg[2..]].concat();
+        // the payload in APP1 too, where only its identifier tells it apart from EXIF
+        let opts = MarkerOptions { app: 0xE1, ..MarkerOptions::default() };
+        let carrier = marker_hijacking::hide_in_bytes_with(&tagged, b"kept", &opts).unwrap();
+
+        assert_eq!(find(&carrier, None).unwrap().len(), 4);
+        let (stripped, cut) = strip(&carrier, Some(&opts)).unwrap();
+        assert_eq!(cut, ["APP1 segment 'Exif' (16 bytes)", "COM segment (19 bytes)", "APP13 segment 'Photoshop 3.0' (18 bytes)"]);
+        assert!(find(&stripped, Some(&opts)).unwrap().is_empty());
+        // the scan is untouched
+        let sos = marker_hijacking::header_segments(&jpeg, parse::Mode::Strict).unwrap().end.unwrap();
+        assert!(stripped.ends_with(&jpeg[sos..]));
+        let path = dir.path().join("s.jpg");
+        std::fs::write(&path, &stripped).unwrap();
+        assert_eq!(marker_hijacking::find_payload_as(&path, None, &opts).unwrap(), b"kept");
+    }
+
+    #[test]
+    fn png_text_chunks_go_and_the_pixels_stay() {
+        let dir = tempdir().unwrap();
+        let (cover, out) = (dir.path().join("c.png"), dir.path().join("s.png"));
+        RgbImage::from_fn(32, 32, |x, y| Rgb([(x * 8) as u8, (y * 8) as u8, 7])).save(&cover).unwrap();
+        lsb::hide(&cover, b"in the pixels", &out).unwrap();
+        let png = std::fs::read(&out).unwrap();
+        // right after IHDR, which is 8 + 25 bytes in
+        let tagged = [&png[..33], &chunk(b"tEXt", b"Software\0paint"), &chunk(b"eXIf", b"MM\0*"), &png[33..]].concat();
+
+        let (stripped, cut) = strip(&tagged, None).unwrap();
+        assert_eq!(cut, ["tEXt chunk 'Software' (26 bytes)", "eXIf chunk (16 bytes)"]);
+        assert_eq!(stripped, png);
+        assert!(find(&stripped, None).unwrap().is_empty());
+        assert!(find(b"GIF89a", None).unwrap().is_empty());
+    }
+}
@@ -1,7 +1,46 @@
+pub mod astro;
+pub mod atomic;
 pub mod audio;
+pub mod audit;
+pub mod canary;
+pub mod cancel;
+pub mod catalog;
+pub mod chunking;
+pub mod convert;
+pub mod crypto;
+pub mod delta;
+pub mod detect;
+pub mod error;
+pub mod fec;
+pub mod filter;
+pub mod formats;
+pub mod hexdump;
+pub mod hilbert;
+pub mod legacy;
+pub mod manifest;
+pub mod medical;
+pub mod metadata;
+pub mod noise;
+pub mod options;
+pub mod params;
+pub mod parse;
+pub mod payload;
+pub mod plan;
+pub mod plane;
 pub mod picture;
+pub mod progress;
+pub mod redact;
+pub mod redundancy;
+pub mod registry;
+pub mod report;
+pub mod scan;
+pub mod scatter;
+pub mod session;
+pub mod shares;
 pub mod text;
 pub mod video;
+pub mod wipe;
+pub mod workspace;
 
 /* https://tenor.com/view/cat-stare-creepypasta-cat-schizo-cat-mentalcat-gif-2156904392573334588
  * https://tenor.com/view/ive-gone-completely-mental-gif-24710787
@@ -0,0 +1,204 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use serde::Serialize;
+use crate::steg_algorithms::error::StegError;
+#[cfg(feature = "audio")]
+use crate::steg_algorithms::audio::wav::lsb as wav_lsb;
+#[cfg(feature = "picture")]
+use {crate::steg_algorithms::picture::general::lsb as picture_lsb, image::{DynamicImage, GenericImageView}};
+
+// `hide --noise-report`: how much the carrier was changed, in the terms papers compare embeddings by, so
+// runs with different algorithms and settings line up. The output is compared with the cover as decoded
+// (pictures at 16 bits a sample when both are that deep and 8 otherwise, gray when both are gray, alpha
+// only when the embedding used it; WAVs as PCM16 samples), so whatever the container's encoder did on top
+// of the embedding (a JPEG re-encode, say) counts too: it is the noise the carrier really took.
+//
+//   mse        mean squared difference per value
+//   psnr_db    10 log10(peak² / mse), peak being 255, 65535 or 32767; absent when nothing changed
+//   bits_flipped, per million units (megapixels or million samples), and per payload bit
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NoiseReport {
+    pub filetype: String,
+    pub algorithm: String,
+    /// What `units` counts: "pixel" or "sample".
+    pub unit: &'static str,
+    pub units: u64,
+    /// Channel values (pictures) or samples (audio) that differ.
+    pub values_changed: u64,
+    pub bits_flipped: u64,
+    pub bits_flipped_per_million: f64,
+    /// The framed payload's bits, what the embedding had to store.
+    pub payload_bits: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bits_flipped_per_payload_bit: Option<f64>,
+    pub max_change: u32,
+    pub mse: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub psnr_db: Option<f64>,
+}
+
+impl NoiseReport {
+    /// One line of JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("noise reports always serialize")
+    }
+
+    /// Add the report to the JSON Lines file at `path`, so the runs of an experiment pile up in one place.
+    pub fn append(&self, path: &Path) -> Result<(), StegError> {
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open noise report {}: {}", path.display(), e))?;
+        writeln!(f, "{}", self.to_json()).map_err(|e| format!("Failed to write noise report {}: {}", path.display(), e).into())
+    }
+}
+
+/// Compare the output at `stego` with the cover at `cover`, `filetype` being picture or audio. `alpha`
+/// counts a picture's alpha channel in, for an embedding that used it.
+#[cfg_attr(not(any(feature = "picture", feature = "audio")), allow(unreachable_code))]
+#[cfg_attr(not(feature = "picture"), allow(unused_variables))]
+pub fn measure(filetype: &str, algorithm: &str, cover: &Path, stego: &Path, payload_len: usize, alpha: bool) -> Result<NoiseReport, StegError> {
+    let (unit, units, depth, values): (_, _, _, Vec<(i32, i32)>) = match filetype {
+        #[cfg(feature = "picture")]
+        "picture" => {
+            let (a, b) = (picture_lsb::decode(cover)?, picture_lsb::decode(stego)?);
+            if a.dimensions() != b.dimensions() {
+                return Err(format!("The output is {:?} but the cover {:?}, there's nothing to compare", b.dimensions(), a.dimensions()).into());
+            }
+            let (color, alpha) = (a.color().has_color() || b.color().has_color(), alpha && a.color().has_alpha() && b.color().has_alpha());
+            let deep = |img: &DynamicImage| img.color().bytes_per_pixel() == 2 * img.color().channel_count();
+            let sixteen = deep(&a) && deep(&b);
+            let (x, y) = (picture_values(&a, color, alpha, sixteen), picture_values(&b, color, alpha, sixteen));
+            ("pixel", a.width() as u64 * a.height() as u64, if sixteen { 16 } else { 8 }, x.into_iter().zip(y).collect())
+        }
+        #[cfg(feature = "audio")]
+        "audio" => {
+            let ((_, a), (_, b)) = (wav_lsb::read_samples(cover)?, wav_lsb::read_samples(stego)?);
+            if a.len() != b.len() {
+                return Err(format!("The output has {} samples but the cover {}, there's nothing to compare", b.len(), a.len()).into());
+            }
+            let values = a.iter().zip(&b).map(|(&x, &y)| (x as i32, y as i32)).collect();
+            ("sample", a.len() as u64, 16, values)
+        }
+        #[cfg(not(feature = "picture"))]
+        "picture" => return Err(StegError::not_built("Measuring a picture's noise", "picture")),
+        #[cfg(not(feature = "audio"))]
+        "audio" => return Err(StegError::not_built("Measuring a WAV's noise", "audio")),
+        other => return Err(format!("Noise reports are for pictures and WAV audio, not {}", other).into()),
+    };
+    Ok(summarize(filetype, algorithm, unit, units, depth, &values, payload_len))
+}
+
+// a picture's samples in one row, as `measure` compares them
+#[cfg(feature = "picture")]
+fn picture_values(img: &DynamicImage, color: bool, alpha: bool, sixteen: bool) -> Vec<i32> {
+    fn widen<T: Into<i32> + Copy>(raw: Vec<T>) -> Vec<i32> {
+        raw.into_iter().map(Into::into).collect()
+    }
+    match (color, alpha, sixteen) {
+        (false, false, false) => widen(img.to_luma8().into_raw()),
+        (false, true, false) => widen(img.to_luma_alpha8().into_raw()),
+        (true, false, false) => widen(img.to_rgb8().into_raw()),
+        (true, true, false) => widen(img.to_rgba8().into_raw()),
+        (false, false, true) => widen(img.to_luma16().into_raw()),
+        (false, true, true) => widen(img.to_luma_alpha16().into_raw()),
+        (true, false, true) => widen(img.to_rgb16().into_raw()),
+        (true, true, true) => widen(img.to_rgba16().into_raw()),
+    }
+}
+
+// `depth` bits per value: 8 or 16 (unsigned) for pictures, 16 (signed) for samples
+#[cfg_attr(not(any(feature = "picture", feature = "audio")), allow(dead_code))]
+fn summarize(filetype: &str, algorithm: &str, unit: &'static str, units: u64, depth: u32, values: &[(i32, i32)], payload_len: usize) -> NoiseReport {
+    let mask = (1u32 << depth) - 1;
+    let peak = if unit == "sample" { i16::MAX as f64 } else { mask as f64 };
+    let (mut changed, mut flipped, mut max, mut squares) = (0u64, 0u64, 0u32, 0f64);
+    for &(a, b) in values.iter().filter(|(a, b)| a != b) {
+        changed += 1;
+        // bits as stored, two's complement for samples
+        flipped += ((a ^ b) as u32 & mask).count_ones() as u64;
+        max = max.max(a.abs_diff(b));
+        squares += (a.abs_diff(b) as f64).powi(2);
+    }
+    let mse = if values.is_empty() { 0.0 } else { squares / values.len() as f64 };
+    let payload_bits = payload_len as u64 * 8;
+    NoiseReport {
+        filetype: filetype.to_string(),
+        algorithm: algorithm.to_string(),
+        unit,
+        units,
+        values_changed: changed,
+        bits_flipped: flipped,
+        bits_flipped_per_million: if units == 0 { 0.0 } else { flipped as f64 * 1e6 / units as f64 },
+        payload_bits,
+        bits_flipped_per_payload_bit: (payload_bits > 0).then(|| flipped as f64 / payload_bits as f64),
+        max_change: max,
+        mse,
+        psnr_db: (mse > 0.0).then(|| 10.0 * (peak * peak / mse).log10()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "picture")]
+    use image::{Rgb, RgbImage};
+
+    #[cfg(feature = "picture")]
+    #[test]
+    fn lsb_noise_in_standard_terms() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cover, stego) = (dir.path().join("cover.png"), dir.path().join("stego.png"));
+        let img = RgbImage::from_fn(100, 100, |x, y| Rgb([(x * 2) as u8, (y * 2) as u8, 77]));
+        img.save(&cover).unwrap();
+        // every red value one up
+        RgbImage::from_fn(100, 100, |x, y| Rgb([(x * 2 + 1) as u8, (y * 2) as u8, 77])).save(&stego).unwrap();
+
+        let r = measure("picture", "lsb", &cover, &stego, 1250, false).unwrap();
+        assert_eq!((r.unit, r.units, r.values_changed, r.max_change), ("pixel", 10_000, 10_000, 1));
+        // x * 2 is even, so + 1 only ever flips the LSB
+        assert_eq!(r.bits_flipped, 10_000);
+        assert_eq!(r.bits_flipped_per_million, 1e6);
+        assert_eq!(r.bits_flipped_per_payload_bit, Some(1.0));
+        assert!((r.mse - 1.0 / 3.0).abs() < 1e-12);
+        assert!((r.psnr_db.unwrap() - 10.0 * (255.0f64 * 255.0 * 3.0).log10()).abs() < 1e-9);
+
+        let same = measure("picture", "lsb", &cover, &cover, 0, false).unwrap();
+        assert_eq!((same.mse, same.psnr_db, same.bits_flipped_per_payload_bit), (0.0, None, None));
+        assert!(measure("medical", "tag", &cover, &cover, 0, false).is_err());
+    }
+
+    #[cfg(feature = "picture")]
+    #[test]
+    fn sixteen_bit_pictures_and_alpha_are_measured_as_they_are() {
+        use image::{ImageBuffer, Rgba, RgbaImage};
+
+        let dir = tempfile::tempdir().unwrap();
+        let (cover, stego) = (dir.path().join("cover.png"), dir.path().join("stego.png"));
+        // the low bit of a 16-bit sample is lost at 8 bits
+        ImageBuffer::from_fn(64, 64, |x, y| Rgb([x as u16 * 1000, y as u16 * 1000, 0x8000u16])).save(&cover).unwrap();
+        ImageBuffer::from_fn(64, 64, |x, y| Rgb([x as u16 * 1000 + 1, y as u16 * 1000, 0x8000u16])).save(&stego).unwrap();
+        let r = measure("picture", "lsb", &cover, &stego, 0, false).unwrap();
+        assert_eq!((r.values_changed, r.bits_flipped, r.max_change), (64 * 64, 64 * 64, 1));
+        assert!((r.psnr_db.unwrap() - 10.0 * (65535.0f64 * 65535.0 * 3.0).log10()).abs() < 1e-9);
+
+        // alpha counts when the embedding used it
+        RgbaImage::from_fn(64, 64, |x, y| Rgba([x as u8, y as u8, 9, 200])).save(&cover).unwrap();
+        RgbaImage::from_fn(64, 64, |x, y| Rgba([x as u8, y as u8, 9, 201])).save(&stego).unwrap();
+        assert_eq!(measure("picture", "lsb", &cover, &stego, 0, false).unwrap().values_changed, 0);
+        let r = measure("picture", "lsb", &cover, &stego, 0, true).unwrap();
+        assert_eq!(r.values_changed, 64 * 64);
+        assert!((r.mse - 0.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn samples_change_by_their_signed_difference() {
+        // -1 to 0 flips all 16 bits but moves the sample by one
+        let r = summarize("audio", "lsb", "sample", 2, 16, &[(-1, 0), (5, 5)], 1);
+        assert_eq!((r.bits_flipped, r.max_change, r.values_changed), (16, 1, 1));
+        assert!((r.mse - 0.5).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,259 @@
+use serde::{Deserialize, Serialize};
+
+use crate::steg_algorithms::audio::wav::lsb::TimeRange;
+use crate::steg_algorithms::crypto::Cipher;
+use crate::steg_algorithms::picture::general::lsb::{Embedding, LsbOptions, Region, Traversal};
+use crate::steg_algorithms::picture::jpg::marker_hijacking::MarkerOptions;
+
+// What the hide and find entry points (`lsb::hide_with`, `wav::lsb::hide_wav_with`, `marker::hide_with`
+// and their byte and stream forms) take besides the carrier and the payload, so a new knob is a new
+// field with a default instead of one more parameter on every function. Each algorithm reads the fields
+// it has a use for and ignores the rest, like `registry::Options`, which the CLI fills from its flags
+// and turns into these.
+//
+// Both serialize for manifests and JSON reports. The password and the lsb key are left out of what
+// they write, a record of how a carrier was made shouldn't be what opens it; they are read back when
+// present.
+
+/// How to hide: the lsb layout, the copies, and the marker segments and their sealing.
+///
+/// ```
+/// use rust_stego::steg::HideOptions;
+///
+/// let opts = HideOptions::default().bits(2).channels([0, 2]).stride(3).copies(3);
+/// assert_eq!((opts.lsb.bits, opts.lsb.stride, opts.copies), (2, Some(3), 3));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HideOptions {
+    /// lsb: which bits of which channels or samples carry the payload, and in what order.
+    #[serde(flatten)]
+    pub lsb: LsbOptions,
+    /// lsb: how many times each bit is stored, odd (see `redundancy`).
+    pub copies: usize,
+    /// marker: the segments the payload goes in.
+    pub marker: MarkerOptions,
+    /// marker: seal each segment with this (see `marker_hijacking::hide_sealed`).
+    #[serde(skip_serializing)]
+    pub password: Option<String>,
+    /// marker: the AEAD the segments are sealed with.
+    pub cipher: Cipher,
+}
+
+impl Default for HideOptions {
+    fn default() -> Self {
+        HideOptions { lsb: LsbOptions::default(), copies: 1, marker: MarkerOptions::default(), password: None, cipher: Cipher::default() }
+    }
+}
+
+impl HideOptions {
+    pub fn bits(mut self, bits: u8) -> Self {
+        self.lsb.bits = bits;
+        self
+    }
+
+    /// Indexes into R, G, B, ascending.
+    pub fn channels(mut self, channels: impl Into<Vec<usize>>) -> Self {
+        self.lsb.channels = channels.into();
+        self
+    }
+
+    /// Walk the alpha channel too, see `LsbOptions::use_alpha`.
+    pub fn use_alpha(mut self, use_alpha: bool) -> Self {
+        self.lsb.use_alpha = use_alpha;
+        self
+    }
+
+    /// Replace low bits or match them with ±1, see `Embedding`.
+    pub fn embedding(mut self, embedding: Embedding) -> Self {
+        self.lsb.embedding = embedding;
+        self
+    }
+
+    /// Seed the ±1 choices of `Embedding::Match`.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.lsb.seed = Some(seed);
+        self
+    }
+
+    /// Walk the pixels column by column, along a Hilbert curve or the most textured first, see
+    /// `Traversal`.
+    pub fn traversal(mut self, traversal: Traversal) -> Self {
+        self.lsb.traversal = traversal;
+        self
+    }
+
+    /// Leave out pixels less textured than this, see `LsbOptions::threshold`.
+    pub fn threshold(mut self, threshold: u8) -> Self {
+        self.lsb.threshold = threshold;
+        self
+    }
+
+    pub fn stride(mut self, stride: usize) -> Self {
+        self.lsb.stride = Some(stride);
+        self
+    }
+
+    /// Scatter the bits in an order derived from `key`; the stride is ignored then.
+    pub fn key(mut self, key: impl Into<String>) -> Self {
+        self.lsb.key = Some(key.into());
+        self
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.lsb.offset = offset;
+        self
+    }
+
+    pub fn region(mut self, region: Region) -> Self {
+        self.lsb.region = Some(region);
+        self
+    }
+
+    pub fn range(mut self, range: TimeRange) -> Self {
+        self.lsb.range = Some(range);
+        self
+    }
+
+    pub fn copies(mut self, copies: usize) -> Self {
+        self.copies = copies;
+        self
+    }
+
+    pub fn marker(mut self, marker: MarkerOptions) -> Self {
+        self.marker = marker;
+        self
+    }
+
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    pub fn cipher(mut self, cipher: Cipher) -> Self {
+        self.cipher = cipher;
+        self
+    }
+
+    /// The `FindOptions` that read back what these hid.
+    pub fn to_find(&self) -> FindOptions {
+        FindOptions { lsb: self.lsb.clone(), marker: self.marker.clone(), password: self.password.clone(), limit: None }
+    }
+}
+
+/// How to find: the lsb layout it was hidden with, the marker segments and their password, and how
+/// much to read.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FindOptions {
+    /// lsb: the layout the payload was hidden with. Without a stride every one up to
+    /// `lsb::MAX_PROBE_STRIDE` is tried; the copies are worked out from the carrier.
+    #[serde(flatten)]
+    pub lsb: LsbOptions,
+    /// marker: the segments to look in.
+    pub marker: MarkerOptions,
+    /// marker: open sealed segments with this.
+    #[serde(skip_serializing)]
+    pub password: Option<String>,
+    /// lsb: stop after this many bytes behind the length prefix, for a preview. Redundant payloads are
+    /// still read whole, every copy gets its vote.
+    pub limit: Option<usize>,
+}
+
+impl FindOptions {
+    pub fn bits(mut self, bits: u8) -> Self {
+        self.lsb.bits = bits;
+        self
+    }
+
+    pub fn channels(mut self, channels: impl Into<Vec<usize>>) -> Self {
+        self.lsb.channels = channels.into();
+        self
+    }
+
+    pub fn use_alpha(mut self, use_alpha: bool) -> Self {
+        self.lsb.use_alpha = use_alpha;
+        self
+    }
+
+    pub fn traversal(mut self, traversal: Traversal) -> Self {
+        self.lsb.traversal = traversal;
+        self
+    }
+
+    pub fn threshold(mut self, threshold: u8) -> Self {
+        self.lsb.threshold = threshold;
+        self
+    }
+
+    pub fn stride(mut self, stride: usize) -> Self {
+        self.lsb.stride = Some(stride);
+        self
+    }
+
+    pub fn key(mut self, key: impl Into<String>) -> Self {
+        self.lsb.key = Some(key.into());
+        self
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.lsb.offset = offset;
+        self
+    }
+
+    pub fn region(mut self, region: Region) -> Self {
+        self.lsb.region = Some(region);
+        self
+    }
+
+    pub fn range(mut self, range: TimeRange) -> Self {
+        self.lsb.range = Some(range);
+        self
+    }
+
+    pub fn marker(mut self, marker: MarkerOptions) -> Self {
+        self.marker = marker;
+        self
+    }
+
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_go_through_json_without_their_secrets() {
+        let opts = HideOptions::default()
+            .bits(2)
+            .channels([1])
+            .key("scatter")
+            .region(Region::parse("1,2,30,40").unwrap())
+            .range(TimeRange::parse("1.5s..10s").unwrap())
+            .copies(3)
+            .password("pw")
+            .cipher(Cipher::XChaCha20Poly1305);
+        let json = serde_json::to_value(&opts).unwrap();
+        assert_eq!(json["bits"], 2);
+        assert_eq!(json["region"], "1,2,30,40");
+        assert_eq!(json["range"], "1.5s..10s");
+        assert_eq!(json["cipher"], "xchacha20-poly1305");
+        assert!(json.get("password").is_none() && json.get("key").is_none());
+
+        let back: HideOptions = serde_json::from_value(json).unwrap();
+        assert_eq!(back, HideOptions { password: None, lsb: LsbOptions { key: None, ..opts.lsb.clone() }, ..opts.clone() });
+        // read back when they are there, and everything left out takes its default
+        let given: FindOptions = serde_json::from_str(r#"{"stride": 4, "key": "k", "password": "pw"}"#).unwrap();
+        assert_eq!(given, FindOptions::default().stride(4).key("k").password("pw"));
+        assert_eq!(serde_json::from_str::<HideOptions>("{}").unwrap(), HideOptions::default());
+    }
+}
@@ -0,0 +1,228 @@
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::picture::general::lsb::{self, Embedding, LsbOptions, Traversal};
+use crate::steg_algorithms::picture::jpg::marker_hijacking::{MarkerOptions, MAX_ID_LEN};
+
+// `--algorithm NAME:KEY=VALUE,...`: an algorithm together with its settings, e.g.
+// `lsb:bits=2,channels=rg,stride=3` or `marker:app=0xEC,id=MyApp`. A bare name takes every default, so
+// `--algorithm lsb` means what it always did.
+//
+// Parsing is in two steps. The string's syntax is checked when the command line is read, but which keys
+// are valid depends on the filetype (pictures have channels, WAV samples don't), which may only be known
+// from the input, so the typed options (`LsbOptions`, `MarkerOptions`, what the library functions take)
+// come from `lsb()`/`marker()` once it is.
+
+/// The value of `--algorithm`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlgorithmSpec {
+    pub name: String,
+    params: Vec<(String, String)>,
+}
+
+impl AlgorithmSpec {
+    pub fn parse(s: &str) -> Result<AlgorithmSpec, StegError> {
+        let (name, rest) = match s.split_once(':') {
+            Some((name, rest)) => (name.trim(), Some(rest)),
+            None => (s.trim(), None),
+        };
+        if name.is_empty() {
+            return Err("expected an algorithm name before the parameters, e.g. lsb:bits=2".into());
+        }
+        let mut params: Vec<(String, String)> = Vec::new();
+        for pair in rest.into_iter().flat_map(|r| r.split(',')) {
+            let (key, value) = pair.split_once('=').ok_or_else(|| format!("'{}' isn't KEY=VALUE", pair))?;
+            let key = key.trim().to_lowercase();
+            if params.iter().any(|(seen, _)| *seen == key) {
+                return Err(format!("'{}' is given twice", key).into());
+            }
+            params.push((key, value.trim().to_string()));
+        }
+        Ok(AlgorithmSpec { name: name.to_string(), params })
+    }
+
+    pub fn has_params(&self) -> bool {
+        !self.params.is_empty()
+    }
+
+    /// Fails on the first key the algorithm doesn't take on `filetype`, listing the ones it does.
+    pub fn check(&self, filetype: &str) -> Result<(), StegError> {
+        let valid = keys(filetype, &self.name);
+        match self.params.iter().find(|(key, _)| !valid.contains(&key.as_str())) {
+            Some((key, _)) if valid.is_empty() => Err(format!("{} takes no parameters (got '{}')", self.name, key).into()),
+            Some((key, _)) => Err(format!("Unknown {} parameter '{}' for {}; valid keys: {}", self.name, key, filetype, valid.join(", ")).into()),
+            None => Ok(()),
+        }
+    }
+
+    /// `base` (what --stride, --key and --offset say) with this spec's keys laid over it.
+    pub fn lsb(&self, filetype: &str, mut base: LsbOptions) -> Result<LsbOptions, StegError> {
+        self.check(filetype)?;
+        for (key, value) in &self.params {
+            match key.as_str() {
+                "bits" => base.bits = number(key, value, 1, lsb::MAX_BITS as u64)? as u8,
+                "channels" => {
+                    // channels=b:2 is channels=b,bits=2
+                    let (picked, bits) = value.split_once(':').map_or((value.as_str(), None), |(c, b)| (c, Some(b)));
+                    base.channels = channels(picked)?;
+                    if let Some(bits) = bits {
+                        if self.get("bits").is_some() {
+                            return Err("Give the bits once, in bits= or after the channels (channels=b:2)".into());
+                        }
+                        base.bits = number("bits", bits, 1, lsb::MAX_BITS as u64)? as u8;
+                    }
+                }
+                "alpha" => base.use_alpha = flag(key, value)?,
+                "matching" if flag(key, value)? => base.embedding = Embedding::Match,
+                "matching" => base.embedding = Embedding::Replace,
+                "seed" => base.seed = Some(number(key, value, 0, u64::MAX)?),
+                "traversal" => base.traversal = Traversal::parse(value)?,
+                "threshold" => base.threshold = number(key, value, 0, u8::MAX as u64)? as u8,
+                "stride" => base.stride = Some(number(key, value, 1, u32::MAX as u64)? as usize),
+                "key" => base.key = Some(value.clone()),
+                "offset" => base.offset = number(key, value, 0, u64::MAX)? as usize,
+                _ => unreachable!("check() let '{}' through", key),
+            }
+        }
+        if self.get("stride").is_some() && base.key.is_some() {
+            return Err("stride can't be combined with a key (the key decides the order)".into());
+        }
+        if self.get("key").is_some() {
+            base.stride = None;
+        }
+        if self.get("threshold").is_some() && base.traversal != Traversal::Texture {
+            return Err("threshold only applies to traversal=texture".into());
+        }
+        Ok(base)
+    }
+
+    /// The marker segments this spec names, the defaults for the keys it leaves out.
+    pub fn marker(&self) -> Result<MarkerOptions, StegError> {
+        self.check("picture")?;
+        let mut opts = MarkerOptions::default();
+        if let Some(app) = self.get("app") {
+            opts.app = number("app", app, 0xE0, 0xEF)? as u8;
+        }
+        if let Some(id) = self.get("id") {
+            if id.is_empty() || id.len() > MAX_ID_LEN || id.bytes().any(|b| b <= 1) {
+                return Err(format!("id must be 1 to {} bytes", MAX_ID_LEN).into());
+            }
+            opts.id = id.as_bytes().to_vec();
+        }
+        Ok(opts)
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.params.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+}
+
+/// The keys `algorithm` takes on `filetype`, for the error message and `list-algorithms`.
+pub fn keys(filetype: &str, algorithm: &str) -> &'static [&'static str] {
+    match (filetype, algorithm) {
+        ("picture", "lsb") => &["bits", "channels", "alpha", "matching", "seed", "traversal", "threshold", "stride", "key", "offset"],
+        (_, "lsb") => &["stride", "key"],
+        ("picture", "marker") => &["app", "id"],
+        _ => &[],
+    }
+}
+
+/// Any of r, g and b, e.g. "rg", as ascending indexes into RGB.
+pub fn channels(s: &str) -> Result<Vec<usize>, StegError> {
+    let mut picked = [false; 3];
+    for c in s.chars() {
+        let i = "rgb".find(c.to_ascii_lowercase()).ok_or_else(|| format!("Unknown channel '{}' (use r, g and b)", c))?;
+        picked[i] = true;
+    }
+    let channels: Vec<usize> = (0..3).filter(|&i| picked[i]).collect();
+    if channels.is_empty() {
+        return Err("Select at least one channel".into());
+    }
+    Ok(channels)
+}
+
+// true/false, yes/no, on/off or 1/0
+fn flag(key: &str, value: &str) -> Result<bool, StegError> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => Err(format!("{} must be true or false, not '{}'", key, value).into()),
+    }
+}
+
+// decimal or 0x hex, within min..=max
+fn number(key: &str, value: &str, min: u64, max: u64) -> Result<u64, StegError> {
+    let parsed = match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    match parsed {
+        Ok(n) if (min..=max).contains(&n) => Ok(n),
+        _ if max == u64::MAX => Err(format!("{} must be a number of at least {}, not '{}'", key, min, value).into()),
+        _ => Err(format!("{} must be a number from {} to {}, not '{}'", key, min, max, value).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare_names_keep_the_defaults() {
+        let spec = AlgorithmSpec::parse("lsb").unwrap();
+        assert_eq!(spec.name, "lsb");
+        assert!(!spec.has_params());
+        assert_eq!(spec.lsb("picture", LsbOptions::default()).unwrap(), LsbOptions::default());
+        assert_eq!(AlgorithmSpec::parse("marker").unwrap().marker().unwrap(), MarkerOptions::default());
+    }
+
+    #[test]
+    fn parameters_become_typed_options() {
+        let spec = AlgorithmSpec::parse("lsb:bits=2,channels=rg,stride=3").unwrap();
+        let opts = spec.lsb("picture", LsbOptions { offset: 9, ..LsbOptions::default() }).unwrap();
+        assert_eq!(opts, LsbOptions { bits: 2, channels: vec![0, 1], stride: Some(3), offset: 9, ..LsbOptions::default() });
+
+        let alpha = AlgorithmSpec::parse("lsb:alpha=yes").unwrap();
+        assert!(alpha.lsb("picture", LsbOptions::default()).unwrap().use_alpha);
+        let matching = AlgorithmSpec::parse("lsb:matching=true,seed=7").unwrap().lsb("picture", LsbOptions::default()).unwrap();
+        assert_eq!((matching.embedding, matching.seed), (Embedding::Match, Some(7)));
+        assert!(AlgorithmSpec::parse("lsb:alpha=maybe").unwrap().lsb("picture", LsbOptions::default()).is_err());
+
+        // the bits can ride along with the channels
+        let blue = AlgorithmSpec::parse("lsb:channels=b:2").unwrap().lsb("picture", LsbOptions::default()).unwrap();
+        assert_eq!((blue.channels, blue.bits), (vec![2], 2));
+        let curve = AlgorithmSpec::parse("lsb:traversal=Hilbert").unwrap().lsb("picture", LsbOptions::default()).unwrap();
+        assert_eq!(curve.traversal, Traversal::Hilbert);
+        assert!(AlgorithmSpec::parse("lsb:traversal=spiral").unwrap().lsb("picture", LsbOptions::default()).is_err());
+        let texture = AlgorithmSpec::parse("lsb:traversal=texture,threshold=40").unwrap().lsb("picture", LsbOptions::default()).unwrap();
+        assert_eq!((texture.traversal, texture.threshold), (Traversal::Texture, 40));
+        assert!(AlgorithmSpec::parse("lsb:threshold=40").unwrap().lsb("picture", LsbOptions::default()).is_err());
+
+        let keyed = AlgorithmSpec::parse("lsb:key=secret").unwrap();
+        assert_eq!(keyed.lsb("audio", LsbOptions { stride: Some(1), ..LsbOptions::default() }).unwrap().stride, None);
+
+        let marker = AlgorithmSpec::parse("marker:app=0xEC,id=MyApp").unwrap().marker().unwrap();
+        assert_eq!(marker, MarkerOptions { app: 0xEC, id: b"MyApp".to_vec() });
+    }
+
+    #[test]
+    fn bad_parameters_say_what_is_valid() {
+        let spec = AlgorithmSpec::parse("lsb:colour=red").unwrap();
+        assert_eq!(spec.check("picture").unwrap_err().to_string(), "Unknown lsb parameter 'colour' for picture; valid keys: bits, channels, alpha, matching, seed, traversal, threshold, stride, key, offset");
+        // WAV samples have no channels to pick
+        let spec = AlgorithmSpec::parse("lsb:channels=r").unwrap();
+        assert!(spec.lsb("audio", LsbOptions::default()).unwrap_err().to_string().contains("valid keys: stride, key"));
+        assert!(AlgorithmSpec::parse("tag:x=1").unwrap().check("medical").unwrap_err().to_string().contains("no parameters"));
+
+        assert!(AlgorithmSpec::parse("lsb:bits").is_err());
+        assert!(AlgorithmSpec::parse("lsb:bits=1,bits=2").is_err());
+        assert!(AlgorithmSpec::parse(":bits=1").is_err());
+        assert!(AlgorithmSpec::parse("lsb:bits=9").unwrap().lsb("picture", LsbOptions::default()).is_err());
+        assert!(AlgorithmSpec::parse("lsb:bits=5").unwrap().lsb("picture", LsbOptions::default()).is_err());
+        assert!(AlgorithmSpec::parse("marker:app=0xD0").unwrap().marker().is_err());
+        assert!(AlgorithmSpec::parse("lsb:stride=2,key=k").unwrap().lsb("picture", LsbOptions::default()).is_err());
+        let lsb = |s: &str| AlgorithmSpec::parse(s).unwrap().lsb("picture", LsbOptions::default()).unwrap_err().to_string();
+        assert_eq!(lsb("lsb:channels=bx"), "Unknown channel 'x' (use r, g and b)");
+        assert_eq!(lsb("lsb:channels="), "Select at least one channel");
+        assert!(lsb("lsb:channels=b:5").contains("bits must be a number from 1 to 4"));
+        assert!(lsb("lsb:channels=b:2,bits=2").contains("Give the bits once"));
+    }
+}
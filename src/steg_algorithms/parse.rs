@@ -0,0 +1,104 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::steg_algorithms::error::StegError;
+
+// How the container walkers (JPEG segments in marker_hijacking, PNG chunks in picture::general::png_chunks,
+// RIFF chunks in audio::wav::riff) treat a file that breaks its format's spec. Strict stops at the first
+// violation: hiding in a file this tool doesn't understand completely can give an output that isn't what
+// it looks like. Lenient, the default, notes the violation and goes on with whatever structure can still
+// be made out (garbage between segments, a bad CRC, a chunk cut short), which is what getting a payload
+// out of a damaged file takes.
+//
+// `--strict` sets the mode for the process. The walkers take it as an argument, so tests don't share it.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// Any spec violation is an error.
+    Strict,
+    /// Violations are noted and walked past.
+    #[default]
+    Lenient,
+}
+
+static STRICT: AtomicBool = AtomicBool::new(false);
+
+pub fn set_mode(mode: Mode) {
+    STRICT.store(mode == Mode::Strict, Ordering::Relaxed);
+}
+
+/// The process's mode, lenient unless `--strict` was given.
+pub fn mode() -> Mode {
+    if STRICT.load(Ordering::Relaxed) { Mode::Strict } else { Mode::Lenient }
+}
+
+/// A PNG or RIFF chunk: its type, and where it starts (at the length or id) and ends (exclusive, after
+/// the CRC or pad byte).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub id: [u8; 4],
+    pub start: usize,
+    pub end: usize,
+}
+
+/// What a walker made of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Parsed<T> {
+    pub items: Vec<T>,
+    /// Where the structure ends (the scan's SOS for JPEG headers, past IEND for PNG, past the RIFF
+    /// chunk), `None` when the file ran out first.
+    pub end: Option<usize>,
+    /// The violations walked past, always empty in strict mode.
+    pub problems: Vec<String>,
+}
+
+/// Collects a walk, making violations errors or notes as the mode says.
+pub struct Walker<T> {
+    mode: Mode,
+    items: Vec<T>,
+    problems: Vec<String>,
+}
+
+impl<T> Walker<T> {
+    pub fn new(mode: Mode) -> Self {
+        Walker { mode, items: Vec::new(), problems: Vec::new() }
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.items.push(item);
+    }
+
+    /// A spec violation at byte `at`. Strict mode fails with it as [`StegError::Malformed`]; in lenient
+    /// mode the caller recovers.
+    pub fn violation(&mut self, at: usize, what: impl Into<String>) -> Result<(), StegError> {
+        let e = StegError::Malformed { at, what: what.into() };
+        match self.mode {
+            Mode::Strict => Err(e),
+            Mode::Lenient => {
+                self.problems.push(e.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    pub fn finish(self, end: Option<usize>) -> Parsed<T> {
+        Parsed { items: self.items, end, problems: self.problems }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn violations_fail_or_pile_up() {
+        let mut strict: Walker<u8> = Walker::new(Mode::Strict);
+        let e = strict.violation(7, "Bad length").unwrap_err();
+        assert!(matches!(e, StegError::Malformed { at: 7, .. }));
+        assert_eq!(e.to_string(), "Bad length (at byte 7)");
+
+        let mut lenient = Walker::new(Mode::Lenient);
+        lenient.push(1u8);
+        lenient.violation(3, "Bad CRC").unwrap();
+        let parsed = lenient.finish(Some(10));
+        assert_eq!((parsed.items, parsed.end, parsed.problems), (vec![1], Some(10), vec!["Bad CRC (at byte 3)".to_string()]));
+    }
+}
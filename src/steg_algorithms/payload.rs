@@ -0,0 +1,925 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::Serialize;
+use sha2::Sha256;
+use crate::steg_algorithms::crypto::{self, Cipher};
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::fec;
+
+// Shared framing for everything we embed. The carriers only see the encoded bytes
+// (they still add their own 32-bit length prefix on top), so this is the one place
+// that knows what a payload looks like. All integers are big-endian:
+//
+//   magic     4 bytes   "RSTG"
+//   version   1 byte    VERSION
+//   flags     1 byte    FLAG_* bits describing how the rest is stored
+//   body      see below, or when FLAG_ENCRYPTED is set:
+//             sealed_len (4 bytes) + crypto::seal(body), with magic..flags as associated data.
+//             The sealed block starts with a cipher id byte, so find never needs --cipher.
+//   tag       32 bytes, only with FLAG_HMAC: HMAC-SHA256 over everything from magic to the end of the body
+//   meta      optional (`hide --meta`), see `Meta`: "META" | block_len (2 bytes) | block. It sits where
+//             older builds only expect padding, so they skip it without knowing about it. Not covered by
+//             the encryption or the tag.
+//   padding   optional, anything after the body (and tag, and meta) is ignored (see `pad_to`)
+//
+// With FrameOptions::fec_parity the whole thing above (tag included, padding not) is wrapped once more
+// so it survives some damaged bytes:
+//   header    magic | version | FLAG_FEC | parity (1 byte) | inner_len (4 bytes), stored 3 times and
+//             read back by bytewise majority vote
+//   codewords fec::encode(inner frame, parity)
+// Only the payload is protected, not the carrier's own length prefix.
+//
+// Several named payloads in one carrier (`hide --name`) are stored as a table, each entry being a
+// complete frame of its own (so every entry has its own compression/password/tag):
+//   magic | version | FLAG_TABLE | count (2 bytes)
+//   count x (name_len (1 byte) | name | offset (4 bytes) | len (4 bytes)), offsets relative to the data
+//   data      the entry frames back to back
+// The FEC envelope, when used, goes around the whole table.
+//
+// body:
+//   name_len  1 byte    0 when no filename was recorded
+//   name      name_len bytes of UTF-8
+//   data_len  4 bytes   length of `data` as stored (i.e. after compression)
+//   data      data_len bytes
+
+pub const MAGIC: [u8; 4] = *b"RSTG";
+pub const VERSION: u8 = 1;
+pub const MAX_NAME_LEN: usize = 255;
+
+/// `data` is raw deflate, inflate it on decode.
+pub const FLAG_COMPRESSED: u8 = 0b0000_0001;
+/// The body is sealed with a password, see `crypto`.
+pub const FLAG_ENCRYPTED: u8 = 0b0000_0010;
+/// An HMAC-SHA256 tag follows the body. Readable by anyone, tamper-evident for whoever has the key.
+pub const FLAG_HMAC: u8 = 0b0000_0100;
+
+/// Reed-Solomon envelope around a complete frame, see `fec`. Never combined with other flags.
+pub const FLAG_FEC: u8 = 0b0000_1000;
+
+/// A table of named entries, see `Table`. Never combined with other flags.
+pub const FLAG_TABLE: u8 = 0b0001_0000;
+
+const KNOWN_FLAGS: u8 = FLAG_COMPRESSED | FLAG_ENCRYPTED | FLAG_HMAC;
+const HMAC_LEN: usize = 32;
+const PREAMBLE_LEN: usize = 4 + 1 + 1;
+const FEC_HEADER_LEN: usize = PREAMBLE_LEN + 1 + 4;
+const FEC_HEADER_COPIES: usize = 3;
+
+// refuse to inflate past this, a few KB of deflate can otherwise expand into gigabytes
+const MAX_INFLATED_LEN: u64 = 1 << 30;
+
+const FIXED_HEADER_LEN: usize = PREAMBLE_LEN + 1 + 4;
+/// The most bytes a plain frame's header takes, what `Payload::decode_prefix` needs besides the data.
+pub const PREFIX_HEADER_MAX: usize = FIXED_HEADER_LEN + MAX_NAME_LEN;
+
+const META_MARKER: [u8; 4] = *b"META";
+
+/// How `Payload::encode` should store the data.
+#[derive(Debug, Clone, Default)]
+pub struct FrameOptions {
+    /// Deflate the data, unless that makes it bigger.
+    pub compress: bool,
+    /// Encrypt the body (filename included) with a key derived from this password.
+    pub password: Option<String>,
+    /// AEAD to seal with when `password` is set.
+    pub cipher: Cipher,
+    /// Append an HMAC-SHA256 tag keyed with this, independent of `password`.
+    pub hmac_key: Option<String>,
+    /// Reed-Solomon protect the frame with this many parity bytes per 255-byte codeword.
+    pub fec_parity: Option<usize>,
+    /// Stamp the frame with this metadata block.
+    pub meta: Option<Meta>,
+}
+
+/// When and with what a payload was embedded (`hide --meta`). Kept out of the payload bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Meta {
+    /// Unix seconds.
+    pub created: u64,
+    pub tool_version: String,
+    pub algorithm: String,
+}
+
+// block: created (8 bytes) | version_len (1 byte) | version | algorithm_len (1 byte) | algorithm
+impl Meta {
+    /// Metadata for an embed happening now with this build.
+    pub fn now(algorithm: &str) -> Self {
+        Meta {
+            created: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            algorithm: algorithm.to_string(),
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let version = cap_name(&self.tool_version);
+        let algorithm = cap_name(&self.algorithm);
+        let mut block = Vec::with_capacity(8 + 2 + version.len() + algorithm.len());
+        block.extend_from_slice(&self.created.to_be_bytes());
+        block.push(version.len() as u8);
+        block.extend_from_slice(version.as_bytes());
+        block.push(algorithm.len() as u8);
+        block.extend_from_slice(algorithm.as_bytes());
+
+        let mut out = Vec::with_capacity(META_MARKER.len() + 2 + block.len());
+        out.extend_from_slice(&META_MARKER);
+        out.extend_from_slice(&(block.len() as u16).to_be_bytes());
+        out.extend_from_slice(&block);
+        out
+    }
+
+    /// Parse a block written by `encode`, `None` if `buf` doesn't start with one. Fields a later version
+    /// appends to the block are skipped.
+    fn parse(buf: &[u8]) -> Option<Self> {
+        if buf.get(..4)? != META_MARKER {
+            return None;
+        }
+        let len = u16::from_be_bytes([*buf.get(4)?, *buf.get(5)?]) as usize;
+        let block = buf.get(6..6 + len)?;
+        let created = u64::from_be_bytes(block.get(..8)?.try_into().ok()?);
+        let mut pos = 8;
+        let mut text = || {
+            let n = *block.get(pos)? as usize;
+            let s = String::from_utf8(block.get(pos + 1..pos + 1 + n)?.to_vec()).ok()?;
+            pos += 1 + n;
+            Some(s)
+        };
+        let tool_version = text()?;
+        let algorithm = text()?;
+        Some(Meta { created, tool_version, algorithm })
+    }
+
+    /// `created` as "YYYY-MM-DD HH:MM:SS UTC".
+    pub fn created_utc(&self) -> String {
+        let (days, secs) = ((self.created / 86_400) as i64, self.created % 86_400);
+        // days since the epoch to a civil date (Howard Hinnant's algorithm)
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z - era * 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + (month <= 2) as i64;
+        format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC", year, month, day, secs / 3600, secs / 60 % 60, secs % 60)
+    }
+}
+
+/// What `Payload::decode` needs to unlock a protected payload.
+#[derive(Debug, Clone, Default)]
+pub struct DecodeOptions {
+    pub password: Option<String>,
+    /// Check the HMAC tag with this key. Decoding fails if the tag is missing or wrong.
+    pub hmac_key: Option<String>,
+}
+
+/// What `Payload::decode_verified` found out about the HMAC tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Auth {
+    /// The payload has no tag (and no key was given).
+    Absent,
+    /// There is a tag, but no key to check it with.
+    Unchecked,
+    /// The tag matched the key.
+    Verified,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Payload {
+    /// Original filename when the payload came from a file (`--msg-file`), `None` for plain messages.
+    pub name: Option<String>,
+    pub data: Vec<u8>,
+}
+
+impl Payload {
+    pub fn from_text(msg: &str) -> Self {
+        Payload { name: None, data: msg.as_bytes().to_vec() }
+    }
+
+    /// Read `path` and remember its filename (without any directories) for extraction later.
+    pub fn from_file(path: &Path) -> Result<Self, StegError> {
+        let data = fs::read(path).map_err(|e| StegError::io(format!("Failed to read {}", path.display()), e))?;
+        let name = path.file_name().and_then(|n| n.to_str()).map(cap_name);
+        Ok(Payload { name, data })
+    }
+
+    /// The frame for this payload, stored the way `opts` says. This is what the CLI hands an algorithm.
+    ///
+    /// ```
+    /// use rust_stego::steg::payload::{DecodeOptions, FrameOptions, Payload};
+    /// use rust_stego::steg::picture::lsb;
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let (cover, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+    /// image::RgbImage::from_fn(64, 64, |x, y| image::Rgb([x as u8 * 4, y as u8 * 4, 64])).save(&cover).unwrap();
+    ///
+    /// // what `rust-stego hide --password hunter2 --compress` would hide
+    /// let opts = FrameOptions { compress: true, password: Some("hunter2".into()), ..FrameOptions::default() };
+    /// let frame = Payload::from_text("attack at dawn").encode(&opts)?;
+    /// lsb::hide(&cover, &frame, &out)?;
+    ///
+    /// let found = lsb::find_bytes(&out)?;
+    /// let unlock = DecodeOptions { password: Some("hunter2".into()), ..DecodeOptions::default() };
+    /// assert_eq!(Payload::decode(&found, &unlock)?.data, b"attack at dawn");
+    /// assert!(Payload::decode(&found, &DecodeOptions::default()).is_err());
+    /// # Ok::<(), rust_stego::steg::StegError>(())
+    /// ```
+    pub fn encode(&self, opts: &FrameOptions) -> Result<Vec<u8>, StegError> {
+        let mut flags = 0u8;
+        let mut stored: &[u8] = &self.data;
+
+        let compressed;
+        if opts.compress {
+            compressed = deflate(&self.data);
+            // already-compressed data (zip, jpeg, ...) tends to grow, keep it raw then
+            if compressed.len() < self.data.len() {
+                flags |= FLAG_COMPRESSED;
+                stored = &compressed;
+            }
+        }
+
+        let name = cap_name(self.name.as_deref().unwrap_or(""));
+        let mut body = Vec::with_capacity(1 + name.len() + 4 + stored.len());
+        body.push(name.len() as u8);
+        body.extend_from_slice(name.as_bytes());
+        body.extend_from_slice(&(stored.len() as u32).to_be_bytes());
+        body.extend_from_slice(stored);
+
+        if opts.password.is_some() {
+            flags |= FLAG_ENCRYPTED;
+        }
+        if opts.hmac_key.is_some() {
+            flags |= FLAG_HMAC;
+        }
+        let mut out = Vec::with_capacity(PREAMBLE_LEN + 4 + opts.cipher.overhead() + body.len());
+        out.extend_from_slice(&MAGIC);
+        out.push(VERSION);
+        out.push(flags);
+
+        match &opts.password {
+            Some(pw) => {
+                let sealed = crypto::seal(opts.cipher, pw, &body, &out[..PREAMBLE_LEN])?;
+                out.extend_from_slice(&(sealed.len() as u32).to_be_bytes());
+                out.extend_from_slice(&sealed);
+            }
+            None => out.extend_from_slice(&body),
+        }
+        if let Some(key) = &opts.hmac_key {
+            let tag = hmac_sha256(key).chain_update(&out).finalize().into_bytes();
+            out.extend_from_slice(&tag);
+        }
+        if let Some(meta) = &opts.meta {
+            out.extend_from_slice(&meta.encode());
+        }
+        match opts.fec_parity {
+            Some(parity) => protect(&out, parity),
+            None => Ok(out),
+        }
+    }
+
+    /// The payload in `buf` (a frame as `encode` made it, padding after it allowed), unlocked with `opts`.
+    ///
+    /// ```
+    /// use rust_stego::steg::payload::{DecodeOptions, FrameOptions, Payload};
+    ///
+    /// let payload = Payload { name: Some("notes.txt".into()), data: b"see you there".to_vec() };
+    /// let frame = payload.encode(&FrameOptions { fec_parity: Some(16), ..FrameOptions::default() })?;
+    ///
+    /// // the error correction repairs a few damaged bytes
+    /// let mut damaged = frame.clone();
+    /// damaged[40] ^= 0xFF;
+    /// assert_eq!(Payload::decode(&damaged, &DecodeOptions::default())?, payload);
+    /// # Ok::<(), rust_stego::steg::StegError>(())
+    /// ```
+    pub fn decode(buf: &[u8], opts: &DecodeOptions) -> Result<Self, StegError> {
+        Self::decode_verified(buf, opts).map(|(p, _)| p)
+    }
+
+    /// Like `decode`, but also says whether an HMAC tag was present and checked.
+    pub fn decode_verified(buf: &[u8], opts: &DecodeOptions) -> Result<(Self, Auth), StegError> {
+        if let Some(inner) = unprotect(buf)? {
+            return Self::decode_frame(&inner.0, opts);
+        }
+        Self::decode_frame(buf, opts)
+    }
+
+    /// The first `max` data bytes of a plain frame (not compressed, encrypted, signed, FEC-protected or a
+    /// table) and how long the data is in all, from a frame that may be cut short after that. `Ok(None)`
+    /// for any other frame, those only decode whole.
+    pub fn decode_prefix(buf: &[u8], max: usize) -> Result<Option<(Self, usize)>, StegError> {
+        if buf.len() < PREAMBLE_LEN || buf[..4] != MAGIC || buf[4] != VERSION || buf[5] != 0 {
+            return Ok(None);
+        }
+        let rest = &buf[PREAMBLE_LEN..];
+        let name_len = rest.first().map_or(0, |&n| n as usize);
+        let header = rest.get(..1 + name_len + 4).ok_or("Payload truncated: the header is cut short")?;
+        let name = match &header[1..1 + name_len] {
+            [] => None,
+            raw => Some(String::from_utf8(raw.to_vec()).map_err(|_| "Stored filename is not valid UTF-8".to_string())?),
+        };
+        let total = u32::from_be_bytes(header[1 + name_len..].try_into().expect("4 bytes")) as usize;
+        let data = &rest[header.len()..];
+        let keep = max.min(total);
+        if data.len() < keep {
+            return Err(StegError::Truncated { expected: keep, got: data.len() });
+        }
+        Ok(Some((Payload { name, data: data[..keep].to_vec() }, total)))
+    }
+
+    /// The metadata block of a frame (already out of any FEC envelope, or a table entry), `None` when it
+    /// was embedded without `--meta`.
+    pub fn meta(buf: &[u8]) -> Option<Meta> {
+        if buf.len() < PREAMBLE_LEN || buf[..4] != MAGIC || buf[5] & !KNOWN_FLAGS != 0 {
+            return None;
+        }
+        let flags = buf[5];
+        let rest = &buf[PREAMBLE_LEN..];
+        let body_len = if flags & FLAG_ENCRYPTED != 0 { sealed_len(rest) } else { body_len(rest) }.ok()?;
+        let end = PREAMBLE_LEN + body_len + if flags & FLAG_HMAC != 0 { HMAC_LEN } else { 0 };
+        Meta::parse(buf.get(end..)?)
+    }
+
+    fn decode_frame(buf: &[u8], opts: &DecodeOptions) -> Result<(Self, Auth), StegError> {
+        if buf.len() < PREAMBLE_LEN || buf[..4] != MAGIC {
+            return Err(StegError::NoPayloadFound);
+        }
+        let version = buf[4];
+        if version != VERSION {
+            return Err(format!("Unsupported payload version {} (this build understands {})", version, VERSION).into());
+        }
+        let flags = buf[5];
+        if flags == FLAG_TABLE {
+            let names: Vec<String> = Table::parse(buf)?.map(|t| t.names().map(str::to_string).collect()).unwrap_or_default();
+            return Err(format!("This carrier holds several named payloads ({}), pick one with --name", names.join(", ")).into());
+        }
+        if flags & !KNOWN_FLAGS != 0 {
+            return Err(format!("Payload uses unknown flags {:#04x}, it was probably made by a newer version", flags).into());
+        }
+
+        let rest = &buf[PREAMBLE_LEN..];
+        let body_len = if flags & FLAG_ENCRYPTED != 0 { sealed_len(rest)? } else { body_len(rest)? };
+        let frame_len = PREAMBLE_LEN + body_len;
+        log::trace!("frame: {} bytes, flags {:#04x}", frame_len, flags);
+
+        // check the tag before spending time on Argon2 or inflating anything
+        let auth = match (flags & FLAG_HMAC != 0, &opts.hmac_key) {
+            (false, None) => Auth::Absent,
+            (false, Some(_)) => return Err("HMAC verification failed: the payload has no HMAC tag".into()),
+            (true, key) => {
+                let tag = buf
+                    .get(frame_len..frame_len + HMAC_LEN)
+                    .ok_or("Payload truncated: the HMAC tag is missing")?;
+                match key {
+                    None => Auth::Unchecked,
+                    Some(key) => {
+                        hmac_sha256(key)
+                            .chain_update(&buf[..frame_len])
+                            .verify_slice(tag)
+                            .map_err(|_| StegError::ChecksumMismatch)?;
+                        Auth::Verified
+                    }
+                }
+            }
+        };
+
+        if flags & FLAG_ENCRYPTED == 0 {
+            return parse_body(rest, flags).map(|p| (p, auth));
+        }
+
+        // an unknown cipher should say so even when no password was given
+        Cipher::from_id(rest[4])?;
+        let password = opts
+            .password
+            .as_deref()
+            .ok_or("Payload is encrypted, pass --password (or both --key-share files) to extract it")?;
+        let body = crypto::open(password, &rest[4..body_len], &buf[..PREAMBLE_LEN])?;
+        parse_body(&body, flags).map(|p| (p, auth))
+    }
+}
+
+/// Named payloads sharing one carrier. Entries are kept in insertion order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Table {
+    entries: Vec<(String, Vec<u8>)>,
+}
+
+impl Table {
+    /// Parse a table frame (already out of any FEC envelope). `Ok(None)` if `buf` is some other frame.
+    pub fn parse(buf: &[u8]) -> Result<Option<Self>, StegError> {
+        if buf.len() < PREAMBLE_LEN + 2 || buf[..4] != MAGIC || buf[5] != FLAG_TABLE {
+            return Ok(None);
+        }
+        if buf[4] != VERSION {
+            return Err(format!("Unsupported payload version {} (this build understands {})", buf[4], VERSION).into());
+        }
+        let truncated = || "Payload table truncated".to_string();
+        let count = u16::from_be_bytes([buf[6], buf[7]]) as usize;
+        let mut pos = PREAMBLE_LEN + 2;
+        let mut index = Vec::with_capacity(count);
+        for _ in 0..count {
+            let name_len = *buf.get(pos).ok_or_else(truncated)? as usize;
+            let name = buf.get(pos + 1..pos + 1 + name_len).ok_or_else(truncated)?;
+            let name = String::from_utf8(name.to_vec()).map_err(|_| "Payload table name is not valid UTF-8".to_string())?;
+            pos += 1 + name_len;
+            let nums = buf.get(pos..pos + 8).ok_or_else(truncated)?;
+            let offset = u32::from_be_bytes([nums[0], nums[1], nums[2], nums[3]]) as usize;
+            let len = u32::from_be_bytes([nums[4], nums[5], nums[6], nums[7]]) as usize;
+            pos += 8;
+            index.push((name, offset, len));
+        }
+        let data = &buf[pos..];
+        let entries = index
+            .into_iter()
+            .map(|(name, offset, len)| {
+                let frame = data.get(offset..offset.checked_add(len).ok_or_else(truncated)?).ok_or_else(truncated)?;
+                Ok((name, frame.to_vec()))
+            })
+            .collect::<Result<_, StegError>>()?;
+        Ok(Some(Table { entries }))
+    }
+
+    pub fn encode(&self, fec_parity: Option<usize>) -> Result<Vec<u8>, StegError> {
+        let count = u16::try_from(self.entries.len()).map_err(|_| "Too many named payloads".to_string())?;
+        let mut out = Vec::new();
+        out.extend_from_slice(&MAGIC);
+        out.push(VERSION);
+        out.push(FLAG_TABLE);
+        out.extend_from_slice(&count.to_be_bytes());
+        let mut offset = 0usize;
+        for (name, frame) in &self.entries {
+            out.push(name.len() as u8);
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(&(offset as u32).to_be_bytes());
+            out.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+            offset += frame.len();
+        }
+        for (_, frame) in &self.entries {
+            out.extend_from_slice(frame);
+        }
+        match fec_parity {
+            Some(parity) => protect(&out, parity),
+            None => Ok(out),
+        }
+    }
+
+    /// Add `frame` (a `Payload::encode` result) under `name`, replacing an entry of the same name.
+    pub fn insert(&mut self, name: &str, frame: Vec<u8>) -> Result<(), StegError> {
+        if name.is_empty() || name.len() > MAX_NAME_LEN {
+            return Err(format!("Payload names must be 1 to {} bytes long", MAX_NAME_LEN).into());
+        }
+        match self.entries.iter_mut().find(|(n, _)| n == name) {
+            Some(entry) => entry.1 = frame,
+            None => self.entries.push((name.to_string(), frame)),
+        }
+        Ok(())
+    }
+
+    /// The frame stored under `name`, ready for `Payload::decode`.
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        self.entries.iter().find(|(n, _)| n == name).map(|(_, f)| f.as_slice())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(n, _)| n.as_str())
+    }
+
+    /// (name, stored size, encrypted) for listing.
+    pub fn summary(&self) -> Vec<(&str, usize, bool)> {
+        self.entries
+            .iter()
+            .map(|(n, f)| (n.as_str(), f.len(), f.get(5).is_some_and(|flags| flags & FLAG_ENCRYPTED != 0)))
+            .collect()
+    }
+}
+
+/// Wrap a complete frame in the Reed-Solomon envelope.
+fn protect(frame: &[u8], parity: usize) -> Result<Vec<u8>, StegError> {
+    let mut header = Vec::with_capacity(FEC_HEADER_LEN);
+    header.extend_from_slice(&MAGIC);
+    header.push(VERSION);
+    header.push(FLAG_FEC);
+    header.push(u8::try_from(parity).map_err(|_| format!("Reed-Solomon parity {} is too large", parity))?);
+    header.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+
+    let codewords = fec::encode(frame, parity)?;
+    let mut out = Vec::with_capacity(FEC_HEADER_LEN * FEC_HEADER_COPIES + codewords.len());
+    for _ in 0..FEC_HEADER_COPIES {
+        out.extend_from_slice(&header);
+    }
+    out.extend_from_slice(&codewords);
+    Ok(out)
+}
+
+/// If `buf` holds a Reed-Solomon envelope, repair it and return the inner frame and how many bytes were
+/// corrected. `None` means there is no envelope and `buf` is a plain frame.
+pub fn unprotect(buf: &[u8]) -> Result<Option<(Vec<u8>, usize)>, StegError> {
+    let copies = FEC_HEADER_LEN * FEC_HEADER_COPIES;
+    if buf.len() < copies {
+        return Ok(None);
+    }
+    // bytewise majority of the three copies, so a damaged header byte doesn't lose the payload
+    let header: Vec<u8> = (0..FEC_HEADER_LEN)
+        .map(|i| {
+            let (a, b, c) = (buf[i], buf[FEC_HEADER_LEN + i], buf[2 * FEC_HEADER_LEN + i]);
+            (a & b) | (a & c) | (b & c)
+        })
+        .collect();
+    if header[..4] != MAGIC || header[5] != FLAG_FEC {
+        return Ok(None);
+    }
+    if header[4] != VERSION {
+        return Err(format!("Unsupported payload version {} (this build understands {})", header[4], VERSION).into());
+    }
+    let parity = header[6] as usize;
+    let inner_len = u32::from_be_bytes([header[7], header[8], header[9], header[10]]) as usize;
+    if fec::encoded_len(inner_len, parity.max(2)) > buf.len() - copies {
+        return Err(format!("Error-corrected payload truncated: header says {} bytes", inner_len).into());
+    }
+    fec::decode(&buf[copies..], inner_len, parity).map(Some)
+}
+
+fn hmac_sha256(key: &str) -> Hmac<Sha256> {
+    // HMAC takes keys of any length, this can't fail
+    Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts any key length")
+}
+
+/// Length of an unencrypted body at the start of `buf`, checking it is all there.
+fn body_len(buf: &[u8]) -> Result<usize, StegError> {
+    let name_len = *buf.first().ok_or("Payload header truncated")? as usize;
+    let pos = 1 + name_len;
+    let len_bytes = buf.get(pos..pos + 4).ok_or("Payload header truncated")?;
+    let data_len = u32::from_be_bytes([len_bytes[0], len_bytes[1], len_bytes[2], len_bytes[3]]) as usize;
+    if buf.len() - pos - 4 < data_len {
+        return Err(StegError::Truncated { expected: data_len, got: buf.len() - pos - 4 });
+    }
+    Ok(pos + 4 + data_len)
+}
+
+/// Length of `sealed_len` + the sealed block at the start of `buf`, checking it is all there.
+fn sealed_len(buf: &[u8]) -> Result<usize, StegError> {
+    // at least the length and a cipher id
+    if buf.len() < 5 {
+        return Err("Payload header truncated".into());
+    }
+    let sealed_len = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+    if buf.len() - 4 < sealed_len {
+        return Err(StegError::Truncated { expected: sealed_len, got: buf.len() - 4 });
+    }
+    Ok(4 + sealed_len)
+}
+
+fn parse_body(buf: &[u8], flags: u8) -> Result<Payload, StegError> {
+    let end = body_len(buf)?;
+    let name_len = buf[0] as usize;
+    let name = if name_len == 0 {
+        None
+    } else {
+        let raw = &buf[1..1 + name_len];
+        Some(String::from_utf8(raw.to_vec()).map_err(|_| "Stored filename is not valid UTF-8".to_string())?)
+    };
+
+    let stored = &buf[1 + name_len + 4..end];
+    let data = if flags & FLAG_COMPRESSED != 0 { inflate(stored)? } else { stored.to_vec() };
+    Ok(Payload { name, data })
+}
+
+/// Append pseudorandom bytes to an encoded payload until it is `target` bytes long (no-op if it already is).
+/// `decode` stops at `data_len`, so the padding only hides the true size from the carrier's own length
+/// header and removes the sharp edge where the embedded noise stops.
+pub fn pad_to(framed: &mut Vec<u8>, target: usize, rng: &mut impl RngCore) {
+    if target <= framed.len() {
+        return;
+    }
+    let start = framed.len();
+    framed.resize(target, 0);
+    rng.fill_bytes(&mut framed[start..]);
+}
+
+/// Length of the frame `encode` makes from `data_len` bytes of uncompressed, unnamed data.
+pub fn framed_len(data_len: usize, opts: &FrameOptions) -> usize {
+    let body = 1 + 4 + data_len;
+    let mut len = PREAMBLE_LEN + if opts.password.is_some() { 4 + opts.cipher.overhead() + body } else { body };
+    if opts.hmac_key.is_some() {
+        len += HMAC_LEN;
+    }
+    if let Some(meta) = &opts.meta {
+        len += meta.encode().len();
+    }
+    match opts.fec_parity {
+        Some(parity) => FEC_HEADER_LEN * FEC_HEADER_COPIES + fec::encoded_len(len, parity),
+        None => len,
+    }
+}
+
+/// Most uncompressed, unnamed data whose frame fits in `room` bytes, `None` if not even an empty one does.
+pub fn max_data_len(room: usize, opts: &FrameOptions) -> Option<usize> {
+    if framed_len(0, opts) > room {
+        return None;
+    }
+    // the frame only grows with the data (FEC in uneven steps), so bisect
+    let (mut lo, mut hi) = (0, room);
+    while lo < hi {
+        let mid = lo + (hi - lo).div_ceil(2);
+        if framed_len(mid, opts) <= room { lo = mid } else { hi = mid - 1 }
+    }
+    Some(lo)
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut enc = DeflateEncoder::new(Vec::new(), Compression::best());
+    // writing into a Vec can't fail
+    enc.write_all(data).expect("deflate into Vec");
+    enc.finish().expect("deflate into Vec")
+}
+
+fn inflate(data: &[u8]) -> Result<Vec<u8>, StegError> {
+    let mut out = Vec::new();
+    DeflateDecoder::new(data)
+        .take(MAX_INFLATED_LEN + 1)
+        .read_to_end(&mut out)
+        .map_err(|e| format!("Compressed payload is corrupt: {}", e))?;
+    if out.len() as u64 > MAX_INFLATED_LEN {
+        return Err("Compressed payload inflates to more than 1 GiB, refusing".into());
+    }
+    Ok(out)
+}
+
+/// Truncate a filename to MAX_NAME_LEN bytes without splitting a UTF-8 character.
+fn cap_name(name: &str) -> String {
+    let mut end = name.len().min(MAX_NAME_LEN);
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    name[..end].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_text() {
+        let p = Payload::from_text("fart hill");
+        let decoded = Payload::decode(&p.encode(&FrameOptions::default()).unwrap(), &DecodeOptions::default()).unwrap();
+        assert_eq!(decoded, p);
+        assert_eq!(decoded.name, None);
+    }
+
+    #[test]
+    fn roundtrip_named_binary() {
+        let p = Payload { name: Some("secret.bin".to_string()), data: vec![0x00, 0xFF, 0x10, 0x00] };
+        let enc = p.encode(&FrameOptions::default()).unwrap();
+        assert_eq!(enc[5], 0, "no flags without options");
+        assert_eq!(Payload::decode(&enc, &DecodeOptions::default()).unwrap(), p);
+    }
+
+    #[test]
+    fn long_names_are_capped_on_char_boundary() {
+        let name = "é".repeat(200); // 400 bytes
+        let capped = cap_name(&name);
+        assert!(capped.len() <= MAX_NAME_LEN);
+        assert!(capped.chars().all(|c| c == 'é'));
+    }
+
+    #[test]
+    fn rejects_missing_magic_and_truncation() {
+        assert!(Payload::decode(b"hello world, not a payload", &DecodeOptions::default()).is_err());
+
+        let enc = Payload::from_text("abcdef").encode(&FrameOptions::default()).unwrap();
+        assert!(Payload::decode(&enc[..enc.len() - 1], &DecodeOptions::default()).is_err());
+    }
+
+    #[test]
+    fn plain_frames_decode_a_prefix_from_a_cut_frame() {
+        let p = Payload { name: Some("big.log".to_string()), data: (0..200u8).collect() };
+        let enc = p.encode(&FrameOptions::default()).unwrap();
+        // cut right after the 16 bytes asked for
+        let (head, total) = Payload::decode_prefix(&enc[..enc.len() - 184], 16).unwrap().unwrap();
+        assert_eq!((head.name.as_deref(), &head.data[..], total), (Some("big.log"), &p.data[..16], 200));
+        assert_eq!(Payload::decode_prefix(&enc, 1000).unwrap().unwrap().0, p);
+        assert!(Payload::decode_prefix(&enc[..enc.len() - 185], 16).is_err());
+
+        let signed = p.encode(&FrameOptions { hmac_key: Some("k".to_string()), ..Default::default() }).unwrap();
+        assert_eq!(Payload::decode_prefix(&signed, 16).unwrap(), None);
+    }
+
+    #[test]
+    fn compression_shrinks_text_and_roundtrips() {
+        let p = Payload::from_text(&"all work and no play makes jack a dull boy\n".repeat(500));
+        let plain = p.encode(&FrameOptions::default()).unwrap();
+        let packed = p.encode(&FrameOptions { compress: true, ..Default::default() }).unwrap();
+        assert_eq!(packed[5] & FLAG_COMPRESSED, FLAG_COMPRESSED);
+        assert!(packed.len() * 10 < plain.len(), "{} vs {}", packed.len(), plain.len());
+        assert_eq!(Payload::decode(&packed, &DecodeOptions::default()).unwrap(), p);
+    }
+
+    #[test]
+    fn padding_is_ignored_on_decode() {
+        use rand::SeedableRng;
+
+        let p = Payload::from_text("short");
+        let mut enc = p.encode(&FrameOptions::default()).unwrap();
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(7);
+        pad_to(&mut enc, 4096, &mut rng);
+        assert_eq!(enc.len(), 4096);
+        assert_eq!(Payload::decode(&enc, &DecodeOptions::default()).unwrap(), p);
+
+        // never shrinks
+        pad_to(&mut enc, 10, &mut rng);
+        assert_eq!(enc.len(), 4096);
+    }
+
+    #[test]
+    fn incompressible_data_is_stored_raw() {
+        // xorshift noise doesn't deflate
+        let mut x = 0x2545F4914F6CDD1Du64;
+        let data: Vec<u8> = (0..4096).map(|_| { x ^= x << 13; x ^= x >> 7; x ^= x << 17; x as u8 }).collect();
+        let p = Payload { name: None, data };
+        let enc = p.encode(&FrameOptions { compress: true, ..Default::default() }).unwrap();
+        assert_eq!(enc[5] & FLAG_COMPRESSED, 0);
+        assert_eq!(Payload::decode(&enc, &DecodeOptions::default()).unwrap(), p);
+    }
+
+    fn with_password(pw: &str) -> DecodeOptions {
+        DecodeOptions { password: Some(pw.to_string()), ..Default::default() }
+    }
+
+    #[test]
+    fn encrypted_roundtrip_hides_name_and_data() {
+        let p = Payload { name: Some("plans.txt".to_string()), data: b"meet at the docks".to_vec() };
+        let opts = FrameOptions { compress: true, password: Some("hunter2".to_string()), ..Default::default() };
+        let enc = p.encode(&opts).unwrap();
+        assert_eq!(enc[5] & FLAG_ENCRYPTED, FLAG_ENCRYPTED);
+        assert!(!enc.windows(5).any(|w| w == b"plans"), "filename leaked in the clear");
+        assert_eq!(Payload::decode(&enc, &with_password("hunter2")).unwrap(), p);
+
+        // empty messages work too
+        let empty = Payload::from_text("");
+        let enc = empty.encode(&opts).unwrap();
+        assert_eq!(Payload::decode(&enc, &with_password("hunter2")).unwrap(), empty);
+    }
+
+    #[test]
+    fn encrypted_payload_needs_the_right_password() {
+        let enc = Payload::from_text("secret")
+            .encode(&FrameOptions { password: Some("hunter2".to_string()), ..Default::default() })
+            .unwrap();
+        let err = Payload::decode(&enc, &DecodeOptions::default()).unwrap_err().to_string();
+        assert!(err.contains("--password"), "{}", err);
+        assert!(matches!(Payload::decode(&enc, &with_password("hunter3")), Err(StegError::ChecksumMismatch)));
+
+        // the clear header is authenticated, clearing the compressed bit must not go unnoticed
+        let mut tampered = enc.clone();
+        tampered[5] |= FLAG_COMPRESSED;
+        assert!(Payload::decode(&tampered, &with_password("hunter2")).is_err());
+    }
+
+    fn hmac(key: &str) -> DecodeOptions {
+        DecodeOptions { hmac_key: Some(key.to_string()), ..Default::default() }
+    }
+
+    #[test]
+    fn hmac_verifies_and_stays_readable() {
+        use rand::SeedableRng;
+
+        let p = Payload::from_text("signed, not sealed");
+        let mut enc = p.encode(&FrameOptions { hmac_key: Some("k".to_string()), ..Default::default() }).unwrap();
+        assert_eq!(enc[5] & FLAG_HMAC, FLAG_HMAC);
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(3);
+        pad_to(&mut enc, 200, &mut rng);
+
+        assert_eq!(Payload::decode_verified(&enc, &hmac("k")).unwrap(), (p.clone(), Auth::Verified));
+        // no key: still readable, just not checked
+        assert_eq!(Payload::decode_verified(&enc, &DecodeOptions::default()).unwrap(), (p, Auth::Unchecked));
+
+        assert!(matches!(Payload::decode(&enc, &hmac("wrong")), Err(StegError::ChecksumMismatch)));
+        let mut tampered = enc.clone();
+        tampered[PREAMBLE_LEN + 7] ^= 1;
+        assert!(matches!(Payload::decode(&tampered, &hmac("k")), Err(StegError::ChecksumMismatch)));
+    }
+
+    #[test]
+    fn hmac_key_without_tag_fails() {
+        let enc = Payload::from_text("plain").encode(&FrameOptions::default()).unwrap();
+        assert!(Payload::decode(&enc, &hmac("k")).unwrap_err().to_string().contains("no HMAC tag"));
+        assert_eq!(Payload::decode_verified(&enc, &DecodeOptions::default()).unwrap().1, Auth::Absent);
+    }
+
+    #[test]
+    fn hmac_composes_with_encryption() {
+        let p = Payload::from_text("both");
+        let opts = FrameOptions { password: Some("pw".to_string()), hmac_key: Some("k".to_string()), ..Default::default() };
+        let enc = p.encode(&opts).unwrap();
+        assert_eq!(enc[5] & (FLAG_HMAC | FLAG_ENCRYPTED), FLAG_HMAC | FLAG_ENCRYPTED);
+
+        let both = DecodeOptions { password: Some("pw".to_string()), hmac_key: Some("k".to_string()) };
+        assert_eq!(Payload::decode_verified(&enc, &both).unwrap(), (p, Auth::Verified));
+        // the tag is checked before decryption, so a bad key fails without needing the password
+        assert!(matches!(Payload::decode(&enc, &hmac("nope")), Err(StegError::ChecksumMismatch)));
+    }
+
+    #[test]
+    fn xchacha_roundtrip_and_unknown_cipher() {
+        let p = Payload::from_text("no aes-ni on this board");
+        let opts = FrameOptions { password: Some("pw".to_string()), cipher: Cipher::XChaCha20Poly1305, ..Default::default() };
+        let mut enc = p.encode(&opts).unwrap();
+        assert_eq!(Payload::decode(&enc, &with_password("pw")).unwrap(), p);
+
+        // the cipher id sits right after sealed_len
+        enc[PREAMBLE_LEN + 4] = 0x7F;
+        let err = Payload::decode(&enc, &DecodeOptions::default()).unwrap_err().to_string();
+        assert!(err.contains("newer version"), "{}", err);
+    }
+
+    #[test]
+    fn meta_is_skipped_by_decode_and_read_back_separately() {
+        use rand::SeedableRng;
+
+        let p = Payload::from_text("stamped");
+        let meta = Meta { created: 1_700_000_000, tool_version: "0.1.0".into(), algorithm: "lsb".into() };
+        let opts = FrameOptions { hmac_key: Some("k".to_string()), meta: Some(meta.clone()), ..Default::default() };
+        let mut enc = p.encode(&opts).unwrap();
+        pad_to(&mut enc, 512, &mut rand_chacha::ChaCha20Rng::seed_from_u64(3));
+
+        assert_eq!(Payload::decode_verified(&enc, &hmac("k")).unwrap(), (p.clone(), Auth::Verified));
+        assert_eq!(Payload::meta(&enc), Some(meta.clone()));
+        assert_eq!(meta.created_utc(), "2023-11-14 22:13:20 UTC");
+
+        let plain = p.encode(&FrameOptions { password: Some("pw".into()), ..Default::default() }).unwrap();
+        assert_eq!(Payload::meta(&plain), None);
+    }
+
+    #[test]
+    fn fec_repairs_damage_and_keeps_the_inner_flags() {
+        let p = Payload::from_text(&"error correcting ".repeat(40));
+        let opts = FrameOptions { hmac_key: Some("k".to_string()), fec_parity: Some(16), ..Default::default() };
+        let mut enc = p.encode(&opts).unwrap();
+        assert_eq!(enc[5], FLAG_FEC);
+
+        // one bad header copy and a few bad codeword bytes
+        enc[2] ^= 0xFF;
+        enc[FEC_HEADER_LEN + 7] ^= 0x10;
+        for i in [40, 41, 300, 555] {
+            enc[i] ^= 0x5A;
+        }
+        assert_eq!(unprotect(&enc).unwrap().unwrap().1, 4);
+        assert_eq!(Payload::decode_verified(&enc, &hmac("k")).unwrap(), (p, Auth::Verified));
+
+        for b in &mut enc[40..200] {
+            *b = !*b;
+        }
+        assert!(Payload::decode(&enc, &DecodeOptions::default()).unwrap_err().to_string().contains("Too much damage"));
+    }
+
+    #[test]
+    fn table_holds_named_entries() {
+        let readme = Payload::from_text("read me first");
+        let key = Payload { name: None, data: vec![0xAB; 32] };
+        let mut table = Table::default();
+        table.insert("readme", readme.encode(&FrameOptions::default()).unwrap()).unwrap();
+        table.insert("key", key.encode(&FrameOptions { password: Some("pw".into()), ..Default::default() }).unwrap()).unwrap();
+
+        let enc = table.encode(Some(8)).unwrap();
+        let (inner, _) = unprotect(&enc).unwrap().unwrap();
+        let back = Table::parse(&inner).unwrap().unwrap();
+        assert_eq!(back, table);
+        assert_eq!(Payload::decode(back.get("readme").unwrap(), &DecodeOptions::default()).unwrap(), readme);
+        assert_eq!(Payload::decode(back.get("key").unwrap(), &with_password("pw")).unwrap(), key);
+        assert!(back.summary()[1].2, "key is encrypted");
+
+        // replacing keeps the order, and decoding the whole table asks for a name
+        let mut replaced = back.clone();
+        replaced.insert("readme", Payload::from_text("v2").encode(&FrameOptions::default()).unwrap()).unwrap();
+        assert_eq!(replaced.names().collect::<Vec<_>>(), ["readme", "key"]);
+        let err = Payload::decode(&table.encode(None).unwrap(), &DecodeOptions::default()).unwrap_err().to_string();
+        assert!(err.contains("(readme, key)"), "{}", err);
+        assert_eq!(Table::parse(&readme.encode(&FrameOptions::default()).unwrap()).unwrap(), None);
+    }
+
+    #[test]
+    fn framed_len_matches_encode() {
+        let opts = [
+            FrameOptions::default(),
+            FrameOptions { hmac_key: Some("k".into()), meta: Some(Meta::now("lsb")), ..Default::default() },
+            FrameOptions { password: Some("pw".into()), cipher: Cipher::XChaCha20Poly1305, fec_parity: Some(32), ..Default::default() },
+        ];
+        for opts in &opts {
+            for len in [0, 1, 300] {
+                let framed = Payload { name: None, data: vec![7; len] }.encode(opts).unwrap();
+                assert_eq!(framed_len(len, opts), framed.len(), "{} bytes, {:?}", len, opts.fec_parity);
+            }
+            let most = max_data_len(1000, opts).unwrap();
+            assert!(framed_len(most, opts) <= 1000 && framed_len(most + 1, opts) > 1000);
+        }
+        assert_eq!(max_data_len(10, &FrameOptions::default()), None);
+    }
+}
@@ -0,0 +1,232 @@
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use image::{GrayImage, ImageFormat, ImageReader};
+use crate::steg_algorithms::atomic;
+use crate::steg_algorithms::error::StegError;
+
+// Line-shift watermarking for scanned text documents (classic print-and-scan marking).
+// Text lines are found from the horizontal ink profile. Lines alternate between reference lines,
+// which never move, and data lines, which are moved up (bit 1) or down (bit 0) by `shift` pixels.
+// The detector compares each data line's distance to its two neighbours, so it needs no original and
+// no OCR, and a uniform rescale doesn't matter. The page has to be roughly deskewed though.
+// Capacity is tiny (one bit per two lines), so this carries a short raw tag, not a framed payload:
+//   len (1 byte) | data
+
+/// A row counts as text when more than 1/INK_ROW_FRACTION of it is ink (ignores scan specks).
+const INK_ROW_FRACTION: usize = 200;
+const INK_THRESHOLD: u8 = 128;
+/// Bands thinner than this are rules or dirt, not text.
+const MIN_LINE_HEIGHT: usize = 3;
+
+pub const DEFAULT_SHIFT: usize = 1;
+
+/// Text line bands as inclusive (top, bottom) rows.
+fn find_lines(img: &GrayImage) -> Vec<(usize, usize)> {
+    let (w, h) = (img.width() as usize, img.height() as usize);
+    let min_ink = w / INK_ROW_FRACTION + 1;
+    let inked: Vec<bool> = img
+        .as_raw()
+        .chunks(w)
+        .map(|row| row.iter().filter(|&&v| v < INK_THRESHOLD).count() >= min_ink)
+        .collect();
+
+    let mut lines = Vec::new();
+    let mut y = 0;
+    while y < h {
+        if !inked[y] {
+            y += 1;
+            continue;
+        }
+        let top = y;
+        while y < h && inked[y] {
+            y += 1;
+        }
+        if y - top >= MIN_LINE_HEIGHT {
+            lines.push((top, y - 1));
+        }
+    }
+    lines
+}
+
+/// Ink-weighted vertical centre of a band.
+fn centroid(img: &GrayImage, (top, bottom): (usize, usize)) -> f64 {
+    let w = img.width() as usize;
+    let (mut sum, mut weight) = (0f64, 0f64);
+    for (y, row) in img.as_raw().chunks(w).enumerate().take(bottom + 1).skip(top) {
+        let ink: f64 = row.iter().map(|&v| 255.0 - v as f64).sum();
+        sum += ink * y as f64;
+        weight += ink;
+    }
+    sum / weight.max(1.0)
+}
+
+/// How many data bytes fit in the document at `path` (after the length byte).
+pub fn capacity(path: &Path) -> Result<usize, StegError> {
+    let img = ImageReader::open(path)?.decode()?.to_luma8();
+    Ok((data_lines(find_lines(&img).len()) / 8).saturating_sub(1))
+}
+
+/// Lines 1, 3, 5, ... carry bits, as long as there is a reference line below them.
+fn data_lines(line_count: usize) -> usize {
+    line_count.saturating_sub(1) / 2
+}
+
+pub fn hide(path: &Path, msg: impl AsRef<[u8]>, out_path: &Path, shift: usize) -> Result<(), StegError> {
+    let msg = msg.as_ref();
+    if !path.exists() {
+        return Err(StegError::not_found(path));
+    }
+    if shift == 0 {
+        return Err("Shift must be at least 1 pixel".into());
+    }
+    let ext = out_path.extension()
+        .or_else(|| path.extension())
+        .and_then(|e| e.to_str())
+        .ok_or("Invalid file extension")?;
+    let format = ImageFormat::from_extension(ext).ok_or_else(|| StegError::UnsupportedFormat { found: ext.to_string() })?;
+
+    let original = ImageReader::open(path)?.decode()?;
+    let gray = original.to_luma8();
+    let lines = find_lines(&gray);
+
+    let mut bytes = vec![msg.len().min(255) as u8];
+    bytes.extend_from_slice(msg);
+    let bits: Vec<bool> = bytes.iter().flat_map(|b| (0..8).rev().map(move |i| (b >> i) & 1 == 1)).collect();
+    if msg.len() > 255 || bits.len() > data_lines(lines.len()) {
+        return Err(format!(
+            "Message too big: found {} text lines, enough for {} bytes",
+            lines.len(),
+            (data_lines(lines.len()) / 8).saturating_sub(1)
+        ).into());
+    }
+
+    // every line needs `shift` blank rows on both sides to move into
+    for (i, &(top, bottom)) in lines.iter().enumerate() {
+        let room_above = if i == 0 { top } else { top - lines[i - 1].1 - 1 };
+        let room_below = lines.get(i + 1).map_or(gray.height() as usize - 1 - bottom, |next| next.0 - bottom - 1);
+        if i % 2 == 1 && i / 2 < bits.len() && (room_above < shift || room_below < shift) {
+            return Err(format!("Text line {} is too close to its neighbours to shift by {} px", i + 1, shift).into());
+        }
+    }
+
+    let mut img = original.to_rgba8();
+    let src = img.clone();
+    for (bit, &(top, bottom)) in bits.iter().zip(lines.iter().skip(1).step_by(2)) {
+        // rows the line occupies before and after the move, vacated rows take the gap's background
+        let (blank_row, dest_top) = if *bit { (bottom + 1, top - shift) } else { (top - 1, top + shift) };
+        for y in top.min(dest_top)..=bottom.max(dest_top + bottom - top) {
+            let from = if (dest_top..=dest_top + bottom - top).contains(&y) { y + top - dest_top } else { blank_row };
+            for x in 0..img.width() {
+                img.put_pixel(x, y as u32, *src.get_pixel(x, from as u32));
+            }
+        }
+    }
+
+    atomic::write_with(out_path, |file| {
+        let mut out = BufWriter::new(file);
+        if format == ImageFormat::Jpeg {
+            image::DynamicImage::ImageRgba8(img).to_rgb8().write_to(&mut out, format)?;
+        } else {
+            img.write_to(&mut out, format)?;
+        }
+        Ok(out.flush()?)
+    })
+}
+
+pub fn find_payload(path: &Path) -> Result<Vec<u8>, StegError> {
+    if !path.exists() {
+        return Err(StegError::not_found(path));
+    }
+    let img = ImageReader::open(path)?.decode()?.to_luma8();
+    let lines = find_lines(&img);
+    let centres: Vec<f64> = lines.iter().map(|&l| centroid(&img, l)).collect();
+
+    // moved up -> closer to the line above than to the line below
+    let bits: Vec<bool> = (1..centres.len().saturating_sub(1))
+        .step_by(2)
+        .map(|i| centres[i] - centres[i - 1] < centres[i + 1] - centres[i])
+        .collect();
+    let bytes: Vec<u8> = bits.chunks_exact(8).map(|b| b.iter().fold(0u8, |acc, &v| (acc << 1) | v as u8)).collect();
+
+    let len = *bytes.first().ok_or_else(|| format!("Only {} text lines found, not enough for a watermark", lines.len()))? as usize;
+    if bytes.len() < 1 + len {
+        return Err(format!("No line-shift watermark found (claims {} bytes, page holds {})", len, bytes.len() - 1).into());
+    }
+    Ok(bytes[1..1 + len].to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::imageops::{self, FilterType};
+    use image::Luma;
+    use tempfile::tempdir;
+
+    /// Paint a blank page with `lines` rows of fake words, for tests.
+    fn fake_document(lines: usize) -> GrayImage {
+        use rand::{Rng, SeedableRng};
+        let (line_h, leading, margin) = (14u32, 10u32, 40u32);
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(3);
+        let mut img = GrayImage::from_pixel(900, margin * 2 + lines as u32 * (line_h + leading), Luma([250]));
+        for l in 0..lines as u32 {
+            let y0 = margin + l * (line_h + leading);
+            let mut x = margin;
+            while x < 900 - margin - 60 {
+                let word = rng.gen_range(15..60);
+                for dx in 0..word {
+                    // letters: a body with some ascenders
+                    let tall = rng.gen_bool(0.3);
+                    for dy in if tall { 0 } else { 4 }..line_h {
+                        if rng.gen_bool(0.6) {
+                            img.put_pixel(x + dx, y0 + dy, Luma([20]));
+                        }
+                    }
+                }
+                x += word + rng.gen_range(6..12);
+            }
+        }
+        img
+    }
+
+    #[test]
+    fn roundtrip_and_capacity() {
+        let dir = tempdir().unwrap();
+        let (src, out) = (dir.path().join("doc.png"), dir.path().join("marked.png"));
+        fake_document(41).save(&src).unwrap();
+
+        assert_eq!(capacity(&src).unwrap(), 1);
+        hide(&src, b"7", &out, DEFAULT_SHIFT).unwrap();
+        assert_eq!(find_payload(&out).unwrap(), b"7");
+        assert!(hide(&src, b"42", &out, DEFAULT_SHIFT).is_err());
+    }
+
+    #[test]
+    fn survives_blur_noise_and_rescale() {
+        use rand::{Rng, SeedableRng};
+
+        let dir = tempdir().unwrap();
+        let (src, out, scan) = (dir.path().join("doc.png"), dir.path().join("marked.png"), dir.path().join("scan.jpg"));
+        fake_document(60).save(&src).unwrap();
+        hide(&src, b"r9", &out, 2).unwrap();
+
+        // a poor man's print-and-scan: blur, grain, upscale, lossy save
+        let marked = ImageReader::open(&out).unwrap().decode().unwrap().to_luma8();
+        let mut scanned = imageops::blur(&marked, 1.2);
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(9);
+        for p in scanned.pixels_mut() {
+            p[0] = (p[0] as i32 + rng.gen_range(-15..=15)).clamp(0, 255) as u8;
+        }
+        let (w, h) = scanned.dimensions();
+        imageops::resize(&scanned, w * 3 / 2, h * 3 / 2, FilterType::Triangle).save(&scan).unwrap();
+
+        assert_eq!(find_payload(&scan).unwrap(), b"r9");
+    }
+
+    #[test]
+    fn too_few_lines_is_an_error() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("doc.png");
+        fake_document(5).save(&src).unwrap();
+        assert!(find_payload(&src).is_err());
+    }
+}
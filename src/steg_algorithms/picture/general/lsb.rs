@@ -1,126 +1,1211 @@
-use std::fs::File;
-use std::path::{Path};
-use image::{ImageFormat, ImageReader};
-use png::{Encoder, ColorType, BitDepth};
+use crate::steg_algorithms::audio::wav::lsb::TimeRange;
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::payload::MAGIC;
+use crate::steg_algorithms::redundancy;
+use crate::steg_algorithms::scatter::KeyedOrder;
+use serde::{Deserialize, Serialize};
 
-pub fn hide(path: &Path, msg: &str, out_path: &Path) -> Result<(), String> {
+// Without the `picture` feature only the layout and the bit-level extraction are left, which DICOM and
+// FITS lay their samples out with too.
+#[cfg(feature = "picture")]
+use {
+    crate::steg_algorithms::atomic,
+    crate::steg_algorithms::hilbert::Hilbert,
+    crate::steg_algorithms::options::{FindOptions, HideOptions},
+    crate::steg_algorithms::plan::Plan,
+    crate::steg_algorithms::progress,
+    image::{ColorType, DynamicImage, ImageDecoder, ImageFormat, ImageReader},
+    rand::{Rng, RngCore, SeedableRng},
+    rand_chacha::ChaCha20Rng,
+    std::borrow::Cow,
+    std::cell::{OnceCell, RefCell},
+    std::collections::HashSet,
+    std::io::{BufRead, BufReader, Cursor, Read, Seek, Write},
+    std::path::Path,
+};
+
+/// `find` without an explicit stride tries every stride up to this one.
+pub const MAX_PROBE_STRIDE: usize = 64;
+
+/// The most low bits of each channel a layout can use. Past 4 the changes show.
+pub const MAX_BITS: u8 = 4;
+
+// A layout with more than one bit a channel, fewer channels than R, G and B, alpha or another traversal
+// than row by row opens with a layout header: the traversal's tag (`Traversal::tag`) and the number of
+// bits, with a bit set above them for each of R, G and B (`CHANNEL_FLAGS`, none set when it's all
+// three) and `ALPHA_FLAG` for alpha, 8 bits each MSB first, in the first 16 slots past the offset
+// whatever the stride or key. The texture traversal adds a third byte, its threshold. The payload is
+// laid out in the slots after the header as if the offset were that much further. find reads the header
+// for each traversal, depth and channel mask, with and without alpha, to tell which one the payload was
+// hidden at; one bit of R, G and B row by row has no header, which keeps it the layout of every carrier
+// from before there were depths.
+const CHANNEL_FLAGS: u8 = 0x10;
+const ALPHA_FLAG: u8 = 0x80;
+
+/// The longest payload `hide_with` can embed into the picture at `path` with `opts`: the layout's
+/// depth, channels, alpha, traversal, stride and offset, less the length header and the layout header,
+/// divided between the copies. `hide_with` checks against the same number, so a payload of exactly
+/// this many bytes fits and one more doesn't. Only reads the image header, not the pixels, unless
+/// the traversal is by texture.
+#[cfg(feature = "picture")]
+pub fn capacity(path: &Path, opts: &HideOptions) -> Result<usize, StegError> {
     if !path.exists() {
-        return Err(format!("Path {} doesn't exist!", path.display()));
+        return Err(StegError::not_found(path));
     }
+    capacity_in(ImageReader::open(path)?.with_guessed_format()?, opts)
+}
 
-    let ext = path.extension()
-        .and_then(|e| e.to_str())
-        .ok_or("Invalid file extension")?;
+/// `capacity` for a carrier in memory, see `hide_bytes_with`.
+#[cfg(feature = "picture")]
+pub fn capacity_bytes(carrier: &[u8], opts: &HideOptions) -> Result<usize, StegError> {
+    capacity_in(ImageReader::new(Cursor::new(carrier)).with_guessed_format()?, opts)
+}
 
-    // load and normalize to RGBA8 (so layout is predictable)
-    let dyn_i = ImageReader::open(path).map_err(|e| e.to_string())?.decode().map_err(|e| e.to_string())?;
-    let mut img = dyn_i.to_rgba8();
-    let (w, h) = img.dimensions();
-    let bytes_per_pixel = 4usize; // RGBA8
+/// `capacity` for a bare `LsbOptions`, one copy.
+#[cfg(feature = "picture")]
+#[deprecated(note = "use capacity and HideOptions, this goes in the next release")]
+pub fn capacity_with(path: &Path, opts: &LsbOptions) -> Result<usize, StegError> {
+    capacity(path, &HideOptions { lsb: opts.clone(), ..HideOptions::default() })
+}
+
+#[cfg(feature = "picture")]
+fn capacity_in(reader: ImageReader<impl BufRead + Seek>, opts: &HideOptions) -> Result<usize, StegError> {
+    opts.lsb.check()?;
+    redundancy::check(opts.copies)?;
+    // only texture needs the pixels, the rest is in the image header
+    let bits = match opts.lsb.traversal {
+        Traversal::Texture => room(&Walk::over(&opts.lsb, &native(reader.decode()?))?, &opts.lsb)?,
+        _ => {
+            let decoder = reader.into_decoder()?;
+            let ((w, h), color) = (decoder.dimensions(), decoder.color_type());
+            room(&Walk::new(&opts.lsb, Shape::new(w, h, color))?, &opts.lsb)?
+        }
+    };
+    Ok(redundancy::capacity((bits / 8).saturating_sub(4), opts.copies))
+}
 
-    // --- build bitstream: 32-bit BE length header + message bits (MSB-first per byte) ---
-    let msg_len = msg.len() as u32;
-    let mut bits: Vec<u8> = Vec::with_capacity(32 + msg.len() * 8);
-    for i in (0..32).rev() {
-        bits.push(((msg_len >> i) & 1) as u8);
+// the slots a payload's bitstream can take along `walk`: past the offset and the layout header, and
+// only every stride-th of those. `lay` and `capacity` both go by this, so they can't disagree.
+#[cfg(feature = "picture")]
+fn room(walk: &Walk, opts: &LsbOptions) -> Result<usize, StegError> {
+    let slots = walk.slots()?;
+    if opts.start() >= slots {
+        return Err(format!("Offset {} is past the last of the image's {} channel slots", opts.offset, slots).into());
     }
-    for b in msg.bytes() {
-        for i in (0..8).rev() {
-            bits.push(((b >> i) & 1) as u8);
+    let usable = opts.order().usable(slots - opts.start());
+    opts.header_fits(usable)?;
+    Ok(usable)
+}
+
+/// Hide `msg` in the lowest bit of every RGB channel of the picture at `path`, writing the result to
+/// `out_path` in the format its extension names (which has to be lossless).
+///
+/// ```
+/// use rust_stego::steg::picture::lsb;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let (cover, out) = (dir.path().join("cover.png"), dir.path().join("out.bmp"));
+/// image::RgbImage::from_fn(32, 32, |x, y| image::Rgb([x as u8 * 8, y as u8 * 8, 64])).save(&cover).unwrap();
+///
+/// lsb::hide(&cover, b"\x00binary\xff", &out)?;
+/// // 32 * 32 pixels, 3 bits each, minus the 4-byte length
+/// assert!(lsb::hide(&cover, vec![0u8; 32 * 32 * 3 / 8 - 3], &out).is_err());
+/// # Ok::<(), rust_stego::steg::StegError>(())
+/// ```
+#[cfg(feature = "picture")]
+pub fn hide(path: &Path, msg: impl AsRef<[u8]>, out_path: &Path) -> Result<(), StegError> {
+    hide_sparse(path, msg, out_path, 1)
+}
+
+/// Like `hide`, but only every `stride`-th RGB channel slot carries a bit, so the changes are spread thinner.
+#[cfg(feature = "picture")]
+pub fn hide_sparse(path: &Path, msg: impl AsRef<[u8]>, out_path: &Path, stride: usize) -> Result<(), StegError> {
+    if stride == 0 {
+        return Err("Stride must be at least 1".into());
+    }
+    hide_with(path, msg, out_path, &HideOptions::default().stride(stride))
+}
+
+/// Like `hide`, but the bits (length header included) go into RGB channel slots in an order derived
+/// from `key`, scattered over the whole image. Same capacity as `hide`.
+#[cfg(feature = "picture")]
+pub fn hide_keyed(path: &Path, msg: impl AsRef<[u8]>, out_path: &Path, key: &str) -> Result<(), StegError> {
+    hide_with(path, msg, out_path, &HideOptions::default().key(key))
+}
+
+/// Like `hide_sparse`/`hide_keyed`, but every bit is stored `copies` times (odd), see `redundancy`.
+/// Needs `copies` times the room; find works out `copies` on its own.
+#[cfg(feature = "picture")]
+pub fn hide_redundant(path: &Path, msg: impl AsRef<[u8]>, out_path: &Path, stride: usize, key: Option<&str>, copies: usize) -> Result<(), StegError> {
+    let opts = HideOptions { lsb: LsbOptions { stride: Some(stride), key: key.map(String::from), ..LsbOptions::default() }, copies, ..HideOptions::default() };
+    hide_with(path, msg, out_path, &opts)
+}
+
+/// Which bits of which channels carry the payload and how they're walked: the typed form of
+/// `--algorithm lsb:bits=2,channels=rg,stride=3` (see `params`). The default is the layout of the
+/// plain functions here, bit 0 of R, G and B in every pixel.
+///
+/// A slot is one bit of one channel. Slots are numbered pixel by pixel in raster order, within a pixel
+/// channel by channel, within a channel from bit 0 up; `offset` and `stride` count these. With a
+/// `region` only its pixels have slots, numbered as if the region were the whole image. A grayscale
+/// picture has the one gray channel where RGB has `channels`, and keeps being grayscale. The bitstream
+/// (each byte MSB first) goes into the slots in order, so at `bits: 2` a byte's first bit is bit 0 of
+/// red and its second bit 1 of red. Alpha, with `use_alpha`, is walked after the selected channels.
+/// Above one bit, on fewer channels or with alpha the layout opens with a 16-slot header recording
+/// `bits`, `channels` and `use_alpha`, which is what lets find work them out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LsbOptions {
+    /// How many of each channel's low bits carry payload, 1 to `MAX_BITS`. Each one more is another
+    /// pixels * channels bits of room. find reads it from the carrier when left at 1.
+    pub bits: u8,
+    /// Indexes into R, G, B, ascending.
+    pub channels: Vec<usize>,
+    /// Use every Nth slot. `None` means 1 when hiding and is probed when finding.
+    pub stride: Option<usize>,
+    /// Scatter the bits in an order only this key reproduces, instead of striding. Left out when
+    /// serialized, like a password.
+    #[serde(skip_serializing)]
+    pub key: Option<String>,
+    /// Slots at the start (the top rows, a header area) to leave alone.
+    pub offset: usize,
+    /// Keep the payload to these pixels, leaving every other one as it was.
+    pub region: Option<Region>,
+    /// WAV only: keep the payload to this stretch of the file (see `audio::wav::lsb::TimeRange`).
+    pub range: Option<TimeRange>,
+    /// Walk the alpha channel too, for a third more room than RGB alone. Off by default: some
+    /// renderers show alpha changes, and a cover without alpha gets a fully opaque one that then
+    /// isn't. The output has to be a format that keeps alpha.
+    pub use_alpha: bool,
+    /// How a channel whose bit doesn't match is changed. find reads both the same.
+    pub embedding: Embedding,
+    /// Seeds the ±1 choices of `Embedding::Match`, for an output that comes out the same every time.
+    /// Without one they're random.
+    pub seed: Option<u64>,
+    /// The order the pixels are numbered in, and so the order the payload fills them.
+    pub traversal: Traversal,
+    /// `Traversal::Texture` only: pixels less textured than this are left out, for less room but none of
+    /// it in flat areas. 0 takes every pixel, the most textured first.
+    pub threshold: u8,
+}
+
+/// How `hide_with` changes a channel whose low bit isn't the payload's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Embedding {
+    /// Overwrite the bit. Each pair of values 2k, 2k+1 ends up about as common as the other, which is
+    /// what the chi-square attack (`scan`) looks for.
+    #[default]
+    Replace,
+    /// LSB matching: add or subtract 1 at random (1 at 0, -1 at 255), which flips the low bit too but
+    /// leaves the histogram's shape alone. Only at one bit a channel.
+    Match,
+}
+
+/// The order a picture's pixels (of the region, with one) are walked in. The slots of a pixel are
+/// numbered together whichever it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Traversal {
+    /// Row by row from the top left. A payload smaller than the picture fills its top rows, which a
+    /// look at the top strip's low bits or cropping it gives away.
+    #[default]
+    Row,
+    /// Column by column from the top left.
+    Column,
+    /// Along a generalized Hilbert curve (`hilbert`), which fills a block from the top left corner that
+    /// grows in both directions rather than a strip.
+    Hilbert,
+    /// The most textured pixels first (edges, foliage, noise), where changed low bits stand out least
+    /// and flat areas like sky last. Texture is the Sobel magnitude over the top four bits of each
+    /// sample, which no layout writes to, so find ranks the carrier's pixels the same as hide ranked the
+    /// cover's. Can't be combined with `Embedding::Match`, whose ±1 can carry into those bits.
+    Texture,
+}
+
+impl Traversal {
+    pub const ALL: [Traversal; 4] = [Traversal::Row, Traversal::Column, Traversal::Hilbert, Traversal::Texture];
+
+    pub fn parse(s: &str) -> Result<Traversal, StegError> {
+        match s.to_lowercase().as_str() {
+            "row" => Ok(Traversal::Row),
+            "column" => Ok(Traversal::Column),
+            "hilbert" => Ok(Traversal::Hilbert),
+            "texture" => Ok(Traversal::Texture),
+            _ => Err(format!("Unknown traversal '{}' (use row, column, hilbert or texture)", s).into()),
         }
     }
-    // -------------------------------------------------------------------------------
 
-    // capacity check (we use RGB channels only)
-    let pixels = (w as usize) * (h as usize);
-    let capacity_bits = pixels * 3; // R,G,B per pixel
-    if bits.len() > capacity_bits {
-        return Err(format!(
-            "Message too big: need {} bits but capacity is {} bits",
-            bits.len(),
-            capacity_bits
-        ));
-    }
-
-    // embed bits into LSBs of R,G,B, preserve alpha
-    let buf = img.as_mut(); // &mut [u8] raw RGBA bytes
-    let mut it = bits.iter();
-    'outer: for chunk in buf.chunks_mut(bytes_per_pixel) {
-        for c in 0..3 { // R,G,B
-            if let Some(&bit) = it.next() {
-                // chunk[c] and bit are u8; ensure only use lowest bit
-                chunk[c] = (chunk[c] & !1) | (bit & 1);
-            } else {
-                break 'outer;
+    pub fn name(self) -> &'static str {
+        match self {
+            Traversal::Row => "row",
+            Traversal::Column => "column",
+            Traversal::Hilbert => "hilbert",
+            Traversal::Texture => "texture",
+        }
+    }
+
+    // the layout header's first byte; `d` was the only one before there was a choice
+    fn tag(self) -> u8 {
+        match self {
+            Traversal::Row => b'd',
+            Traversal::Column => b'c',
+            Traversal::Hilbert => b'h',
+            Traversal::Texture => b't',
+        }
+    }
+}
+
+impl Default for LsbOptions {
+    fn default() -> Self {
+        LsbOptions {
+            bits: 1,
+            channels: vec![0, 1, 2],
+            stride: None,
+            key: None,
+            offset: 0,
+            region: None,
+            range: None,
+            use_alpha: false,
+            embedding: Embedding::Replace,
+            seed: None,
+            traversal: Traversal::Row,
+            threshold: 0,
+        }
+    }
+}
+
+impl LsbOptions {
+    /// Whether the bits sit where the plain functions put them (bit 0 of R, G and B, row by row), which
+    /// is all `perturb` and the raw formats know.
+    pub fn plain_layout(&self) -> bool {
+        self.bits == 1 && self.channels == [0, 1, 2] && !self.use_alpha && self.traversal == Traversal::Row
+    }
+
+    fn check(&self) -> Result<(), StegError> {
+        if !(1..=MAX_BITS).contains(&self.bits) {
+            return Err(StegError::UnsupportedDepth { bits: self.bits, max: MAX_BITS });
+        }
+        if self.channels.is_empty() || self.channels.iter().any(|&c| c > 2) || !self.channels.is_sorted() {
+            return Err("channels must be some of R, G and B, in that order".into());
+        }
+        if self.stride == Some(0) {
+            return Err("Stride must be at least 1".into());
+        }
+        if self.embedding == Embedding::Match && self.bits != 1 {
+            return Err("lsb matching only works at one bit a channel, a ±1 would carry into the next".into());
+        }
+        if self.embedding == Embedding::Match && self.traversal == Traversal::Texture {
+            return Err("lsb matching can't walk by texture, a ±1 can carry into the bits the texture is read from".into());
+        }
+        Ok(())
+    }
+
+    // the layout header's slot bits, none at one bit of RGB
+    fn layout_header(&self) -> Vec<u8> {
+        if self.plain_layout() {
+            return Vec::new();
+        }
+        let channels = match self.channels[..] {
+            [0, 1, 2] => 0,
+            _ => self.channels.iter().fold(0, |mask, &c| mask | CHANNEL_FLAGS << c),
+        };
+        let bits = self.bits | channels | if self.use_alpha { ALPHA_FLAG } else { 0 };
+        let threshold = (self.traversal == Traversal::Texture).then_some(self.threshold);
+        [self.traversal.tag(), bits].into_iter().chain(threshold).flat_map(|b| (0..8).rev().map(move |i| (b >> i) & 1)).collect()
+    }
+
+    // the first slot of the payload, past the offset and the layout header
+    #[cfg(feature = "picture")]
+    fn start(&self) -> usize {
+        self.offset + self.layout_header().len()
+    }
+
+    // how many pixels of a `w`x`h` image have slots, failing when the region doesn't fit in it
+    #[cfg(feature = "picture")]
+    fn pixels(&self, w: u32, h: u32) -> Result<usize, StegError> {
+        match self.region {
+            Some(r) if r.x as u64 + r.w as u64 > w as u64 || r.y as u64 + r.h as u64 > h as u64 => {
+                Err(format!("Region {} reaches past the edge of the {}x{} image", r, w, h).into())
             }
+            Some(r) => Ok(r.w as usize * r.h as usize),
+            None => Ok(w as usize * h as usize),
+        }
+    }
+
+    // a region that can't even hold the length header is a mistake, not a carrier that's full
+    #[cfg(feature = "picture")]
+    fn header_fits(&self, capacity_bits: usize) -> Result<(), StegError> {
+        match self.region {
+            Some(r) if capacity_bits < 32 => Err(format!("Region {} holds {} bits, too few for even the 32-bit length header", r, capacity_bits).into()),
+            _ => Ok(()),
+        }
+    }
+
+    #[cfg(feature = "picture")]
+    fn order(&self) -> Order<'_> {
+        self.key.as_deref().map_or(Order::Strided(self.stride.unwrap_or(1)), Order::Keyed)
+    }
+}
+
+/// A picture's size, which channels its pixels have and whether a sample is 8 or 16 bits: lsb walks a
+/// picture in the color type it has, so a grayscale or RGB one doesn't come out with channels it didn't
+/// have, and a 16-bit one keeps its depth with the bits in the low end of each sample.
+#[cfg(feature = "picture")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Shape {
+    width: u32,
+    height: u32,
+    color: bool,
+    alpha: bool,
+    sixteen: bool,
+}
+
+#[cfg(feature = "picture")]
+impl Shape {
+    fn new(width: u32, height: u32, color: ColorType) -> Shape {
+        let sixteen = color.bytes_per_pixel() > color.channel_count();
+        Shape { width, height, color: color.has_color(), alpha: color.has_alpha(), sixteen }
+    }
+
+    fn of(img: &DynamicImage) -> Shape {
+        Shape::new(img.width(), img.height(), img.color())
+    }
+
+    // samples a pixel: gray or R, G and B, then alpha
+    fn samples(self) -> usize {
+        (if self.color { 3 } else { 1 }) + self.alpha as usize
+    }
+
+    // the largest value a sample holds
+    fn max(self) -> u16 {
+        if self.sixteen { u16::MAX } else { u8::MAX as u16 }
+    }
+}
+
+// `LsbOptions` over a picture of a `Shape`: the samples of each pixel it walks, in order, for the
+// Hilbert traversal the curve as far as it has been followed, and for the texture one the pixels it
+// takes, most textured first, both as row-major indexes into the region
+#[cfg(feature = "picture")]
+struct Walk<'a> {
+    opts: &'a LsbOptions,
+    shape: Shape,
+    walked: Vec<usize>,
+    curve: RefCell<(Hilbert, Vec<u32>)>,
+    ranked: Cow<'a, [u32]>,
+}
+
+#[cfg(feature = "picture")]
+impl<'a> Walk<'a> {
+    fn new(opts: &'a LsbOptions, shape: Shape) -> Result<Walk<'a>, StegError> {
+        let mut walked = match shape.color {
+            true => opts.channels.clone(),
+            false if opts.channels == [0, 1, 2] => vec![0],
+            false => return Err("A grayscale picture has only the one channel, leave channels out".into()),
+        };
+        if opts.use_alpha {
+            if !shape.alpha {
+                return Err("The picture has no alpha channel to hide in".into());
+            }
+            walked.push(shape.samples() - 1);
+        }
+        let (w, h) = opts.region.map_or((shape.width, shape.height), |r| (r.w, r.h));
+        Ok(Walk { opts, shape, walked, curve: RefCell::new((Hilbert::new(w, h), Vec::new())), ranked: Cow::Borrowed(&[]) })
+    }
+
+    // `new` for `img`, whose pixels the texture traversal is ranked from
+    fn over(opts: &'a LsbOptions, img: &DynamicImage) -> Result<Walk<'a>, StegError> {
+        let mut walk = Walk::new(opts, Shape::of(img))?;
+        if opts.traversal == Traversal::Texture {
+            walk.ranked = Cow::Owned(ranked(&texture(img, walk.rect()), opts.threshold));
+        }
+        Ok(walk)
+    }
+
+    // the pixels walked: the region, or the whole picture
+    fn rect(&self) -> Region {
+        self.opts.region.unwrap_or(Region { x: 0, y: 0, w: self.shape.width, h: self.shape.height })
+    }
+
+    fn per_pixel(&self) -> usize {
+        self.walked.len() * self.opts.bits as usize
+    }
+
+    // how many slots the picture has, failing when the region doesn't fit in it
+    fn slots(&self) -> Result<usize, StegError> {
+        let pixels = self.opts.pixels(self.shape.width, self.shape.height)?;
+        match self.opts.traversal {
+            Traversal::Texture => Ok(self.ranked.len() * self.per_pixel()),
+            _ => Ok(pixels * self.per_pixel()),
+        }
+    }
+
+    // the sample `slot` stands for, counted over the whole picture, and the bit in it
+    fn place(&self, slot: usize) -> (usize, u8) {
+        let (nth, within) = (slot / self.per_pixel(), slot % self.per_pixel());
+        let r = self.rect();
+        let (w, h) = (r.w as usize, r.h as usize);
+        let (x, y) = match self.opts.traversal {
+            Traversal::Row => (nth % w, nth / w),
+            Traversal::Column => (nth / h, nth % h),
+            Traversal::Hilbert => {
+                let (hilbert, seen) = &mut *self.curve.borrow_mut();
+                seen.extend(hilbert.by_ref().take((nth + 1).saturating_sub(seen.len())).map(|(x, y)| y * r.w + x));
+                (seen[nth] as usize % w, seen[nth] as usize / w)
+            }
+            Traversal::Texture => (self.ranked[nth] as usize % w, self.ranked[nth] as usize / w),
+        };
+        let pixel = (r.y as usize + y) * self.shape.width as usize + r.x as usize + x;
+        let bits = self.opts.bits as usize;
+        (pixel * self.shape.samples() + self.walked[within / bits], (within % bits) as u8)
+    }
+}
+
+// how textured each pixel of `r` is, row by row: the Sobel magnitude (|gx| + |gy|, at most 255) of the
+// top four bits of its color samples, edges clamped. Gray counts three times, as much as R, G and B.
+#[cfg(feature = "picture")]
+fn texture(img: &DynamicImage, r: Region) -> Vec<u8> {
+    let shape = Shape::of(img);
+    let (w, h) = (shape.width as i64, shape.height as i64);
+    let (colors, shift) = (if shape.color { 3 } else { 1 }, if shape.sixteen { 12 } else { 4 });
+    let level: Vec<i32> = (0..(w * h) as usize)
+        .map(|p| (0..colors).map(|c| (sample(img, p * shape.samples() + c) >> shift) as i32).sum::<i32>() * 3 / colors as i32)
+        .collect();
+    let at = |x: i64, y: i64| level[(y.clamp(0, h - 1) * w + x.clamp(0, w - 1)) as usize];
+    (r.y as i64..(r.y + r.h) as i64)
+        .flat_map(|y| (r.x as i64..(r.x + r.w) as i64).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let gx = at(x + 1, y - 1) + 2 * at(x + 1, y) + at(x + 1, y + 1) - at(x - 1, y - 1) - 2 * at(x - 1, y) - at(x - 1, y + 1);
+            let gy = at(x - 1, y + 1) + 2 * at(x, y + 1) + at(x + 1, y + 1) - at(x - 1, y - 1) - 2 * at(x, y - 1) - at(x + 1, y - 1);
+            (gx.abs() + gy.abs()).min(255) as u8
+        })
+        .collect()
+}
+
+// the pixels with a `texture` of at least `threshold`, the most textured first and row by row among
+// equals
+#[cfg(feature = "picture")]
+fn ranked(texture: &[u8], threshold: u8) -> Vec<u32> {
+    let mut by_texture = vec![Vec::new(); 256];
+    for (p, &t) in texture.iter().enumerate().filter(|&(_, &t)| t >= threshold) {
+        by_texture[t as usize].push(p as u32);
+    }
+    by_texture.into_iter().rev().flatten().collect()
+}
+
+/// A rectangle of pixels: `x,y,w,h` from the top left, as `--region` takes it, and serialized as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl Region {
+    pub fn parse(s: &str) -> Result<Region, StegError> {
+        let parts: Vec<u32> = s.split(',').map(|p| p.trim().parse()).collect::<Result<_, _>>()
+            .map_err(|_| format!("expected x,y,w,h in pixels, got '{}'", s))?;
+        let &[x, y, w, h] = parts.as_slice() else {
+            return Err(format!("expected x,y,w,h in pixels, got '{}'", s).into());
+        };
+        if w == 0 || h == 0 {
+            return Err(format!("the region needs a width and height of at least 1, got '{}'", s).into());
+        }
+        Ok(Region { x, y, w, h })
+    }
+}
+
+impl std::fmt::Display for Region {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{},{},{},{}", self.x, self.y, self.w, self.h)
+    }
+}
+
+impl From<Region> for String {
+    fn from(r: Region) -> String {
+        r.to_string()
+    }
+}
+
+impl TryFrom<String> for Region {
+    type Error = StegError;
+
+    fn try_from(s: String) -> Result<Region, StegError> {
+        Region::parse(&s)
+    }
+}
+
+/// The general form of the hide functions: lay the bits out as `opts.lsb` says, `opts.copies` times.
+/// find needs the same layout, offset, region and key; the stride it can probe for.
+#[cfg(feature = "picture")]
+pub fn hide_with(path: &Path, msg: impl AsRef<[u8]>, out_path: &Path, opts: &HideOptions) -> Result<(), StegError> {
+    opts.lsb.check()?;
+    embed(path, msg.as_ref(), out_path, &opts.lsb, opts.copies)
+}
+
+/// Which RGB channel slots carry the bitstream, in order.
+#[derive(Clone, Copy)]
+pub(crate) enum Order<'a> {
+    Strided(usize),
+    Keyed(&'a str),
+}
+
+impl Order<'_> {
+    pub(crate) fn usable(self, slots: usize) -> usize {
+        match self {
+            Order::Strided(stride) => slots.div_ceil(stride),
+            Order::Keyed(_) => slots,
         }
     }
-    img.save_with_format(out_path, ImageFormat::from_extension(ext).unwrap()).map_err(|e| e.to_string())
+
+    pub(crate) fn slots(self, slots: usize) -> Box<dyn Iterator<Item = usize>> {
+        match self {
+            Order::Strided(stride) => Box::new((0..slots).step_by(stride)),
+            Order::Keyed(key) => Box::new(KeyedOrder::new(key, slots)),
+        }
+    }
+}
+
+#[cfg(feature = "picture")]
+fn embed(path: &Path, msg: &[u8], out_path: &Path, opts: &LsbOptions, copies: usize) -> Result<(), StegError> {
+    let format = output_format(path, out_path)?;
+    keeps_alpha(opts, format)?;
+    let (img, _) = lay(fit(decode(path)?, format), msg, opts, copies)?;
+    save(&img, out_path, format)
+}
+
+/// `hide_with` for a carrier that's in memory rather than on disk, an upload say. The image comes back
+/// encoded in its own format, so a PNG stays a PNG.
+///
+/// ```
+/// use rust_stego::steg::picture::lsb::{find_bytes_with, hide_bytes_with};
+/// use rust_stego::steg::{FindOptions, HideOptions};
+///
+/// let mut upload = std::io::Cursor::new(Vec::new());
+/// image::RgbImage::new(32, 32).write_to(&mut upload, image::ImageFormat::Png).unwrap();
+///
+/// let stego = hide_bytes_with(upload.get_ref(), b"never on disk", &HideOptions::default())?;
+/// assert_eq!(find_bytes_with(&stego, &FindOptions::default())?, b"never on disk");
+/// # Ok::<(), rust_stego::steg::StegError>(())
+/// ```
+#[cfg(feature = "picture")]
+pub fn hide_bytes_with(carrier: &[u8], payload: &[u8], opts: &HideOptions) -> Result<Vec<u8>, StegError> {
+    let mut out = Cursor::new(Vec::new());
+    hide_stream_with(Cursor::new(carrier), &mut out, image::guess_format(carrier)?, payload, opts)?;
+    Ok(out.into_inner())
+}
+
+/// `hide_with` between streams, for a carrier that isn't a file: an object store body, a zip entry.
+/// The carrier's format is guessed from its first bytes and the output encoded as `format`. A picture
+/// is decoded whole whatever it's read from, and `out` has to seek because some encoders (TIFF) go back
+/// to patch their headers.
+#[cfg(feature = "picture")]
+pub fn hide_stream_with(carrier: impl Read + Seek, mut out: impl Write + Seek, format: ImageFormat, payload: &[u8], opts: &HideOptions) -> Result<(), StegError> {
+    opts.lsb.check()?;
+    keeps_alpha(&opts.lsb, format)?;
+    let (img, _) = lay(fit(load(BufReader::new(carrier), None)?, format), payload, &opts.lsb, opts.copies)?;
+    img.write_to(&mut out, format)?;
+    Ok(out.flush()?)
+}
+
+/// `hide_bytes_with` with the layout and copies as they were passed before `HideOptions`.
+#[cfg(feature = "picture")]
+#[deprecated(note = "use hide_bytes_with and HideOptions, this goes in the next release")]
+pub fn hide_bytes(carrier: &[u8], payload: &[u8], opts: &LsbOptions, copies: usize) -> Result<Vec<u8>, StegError> {
+    hide_bytes_with(carrier, payload, &HideOptions { lsb: opts.clone(), copies, ..HideOptions::default() })
+}
+
+/// `hide_stream_with` with the layout and copies as they were passed before `HideOptions`.
+#[cfg(feature = "picture")]
+#[deprecated(note = "use hide_stream_with and HideOptions, this goes in the next release")]
+pub fn hide_stream(carrier: impl Read + Seek, out: impl Write + Seek, format: ImageFormat, payload: &[u8], opts: &LsbOptions, copies: usize) -> Result<(), StegError> {
+    hide_stream_with(carrier, out, format, payload, &HideOptions { lsb: opts.clone(), copies, ..HideOptions::default() })
+}
+
+/// What `hide_with` would do to the cover, without writing anything. `out_path` only picks the encoder
+/// the output size is measured with.
+#[cfg(feature = "picture")]
+pub fn plan(path: &Path, msg: impl AsRef<[u8]>, out_path: &Path, opts: &LsbOptions, copies: usize) -> Result<Plan, StegError> {
+    opts.check()?;
+    let format = output_format(path, out_path)?;
+    keeps_alpha(opts, format)?;
+    let (img, mut plan) = lay(fit(decode(path)?, format), msg.as_ref(), opts, copies)?;
+    plan.output_bytes = encode(&img, format)?.len() as u64;
+    Ok(plan)
 }
 
-pub fn find(path: &Path) -> Result<String, String> {
+// the output container decides the encoder, fall back to the input's when out_path has no extension
+#[cfg(feature = "picture")]
+fn output_format(path: &Path, out_path: &Path) -> Result<ImageFormat, StegError> {
+    let ext = out_path.extension()
+        .or_else(|| path.extension())
+        .and_then(|e| e.to_str())
+        .ok_or("Invalid file extension")?;
+    ImageFormat::from_extension(ext).ok_or_else(|| StegError::UnsupportedFormat { found: ext.to_string() })
+}
+
+// a payload in alpha needs an encoder that writes the alpha channel as it is
+#[cfg(feature = "picture")]
+fn keeps_alpha(opts: &LsbOptions, format: ImageFormat) -> Result<(), StegError> {
+    if opts.use_alpha && !matches!(format, ImageFormat::Png | ImageFormat::Bmp | ImageFormat::Tiff | ImageFormat::Tga) {
+        return Err(format!("alpha needs an output that keeps it (png, bmp, tif or tga), not {}", format.extensions_str()[0]).into());
+    }
+    Ok(())
+}
+
+// `img` as `format` can write it: 16-bit samples only go into png and tif, and tif has no gray with
+// alpha, so the bits are laid into what gets written
+#[cfg(feature = "picture")]
+pub(crate) fn fit(img: DynamicImage, format: ImageFormat) -> DynamicImage {
+    let shape = Shape::of(&img);
+    match format {
+        ImageFormat::Tiff if shape.alpha && !shape.color => at_depth(DynamicImage::ImageRgba16(img.to_rgba16()), shape.sixteen),
+        ImageFormat::Png | ImageFormat::Tiff => img,
+        _ if shape.sixteen => at_depth(img, false),
+        _ => img,
+    }
+}
+
+// the cover with the bits laid into it, and what that changed
+#[cfg(feature = "picture")]
+fn lay(cover: DynamicImage, msg: &[u8], opts: &LsbOptions, copies: usize) -> Result<(DynamicImage, Plan), StegError> {
+    // 8 or 16 bits a sample, in the color type the cover has
+    let mut img = native(cover);
+    let (w, h) = (img.width(), img.height());
+    let walk = Walk::over(opts, &img)?;
+
+    // bitstream: 32-bit BE length header + message bits (MSB-first per byte), repeated with copies > 1
+    let bits = redundancy::bitstream(msg, copies)?;
+
+    // capacity check (copies multiply the need)
+    let (offset, start, order) = (opts.offset, opts.start(), opts.order());
+    let (slots, capacity_bits) = (walk.slots()?, room(&walk, opts)?);
+    if bits.len() > capacity_bits {
+        return Err(StegError::CapacityExceeded { needed: bits.len().div_ceil(8), available: capacity_bits / 8 });
+    }
+
+    // embed bits into the selected bits of R,G,B (and A with use_alpha); the layout header goes in as it is
+    let header = opts.layout_header();
+    let laid = (offset..start).zip(&header).chain(order.slots(slots - start).map(|s| s + start).zip(&bits));
+    let mut rng = opts.seed.map_or_else(ChaCha20Rng::from_entropy, ChaCha20Rng::seed_from_u64);
+    let mut touched = vec![false; (w as usize) * (h as usize)];
+    let (samples, max) = (walk.shape.samples(), walk.shape.max());
+    let mut buf = Samples::of(&mut img);
+    for (slot, &bit) in laid {
+        // slot numbering only counts alpha with use_alpha, so otherwise it is never touched
+        let (idx, at) = walk.place(slot);
+        let (old, bit) = (buf.get(idx), bit as u16 & 1);
+        let value = match (opts.embedding, old) {
+            (Embedding::Match, v) if v & 1 == bit => v,
+            (Embedding::Match, 0) => 1,
+            (Embedding::Match, v) if v == max => v - 1,
+            (Embedding::Match, v) if rng.gen_bool(0.5) => v + 1,
+            (Embedding::Match, v) => v - 1,
+            (Embedding::Replace, v) => (v & !(1 << at)) | (bit << at),
+        };
+        touched[idx / samples] |= value != old;
+        buf.set(idx, value);
+    }
+    let changed = touched.iter().filter(|&&t| t).count();
+    Ok((img, Plan::new(bits.len(), capacity_bits, "pixel", touched.len(), changed)))
+}
+
+// `ImageReader::open(path).decode()`, reading through `progress`
+#[cfg(feature = "picture")]
+pub(crate) fn decode(path: &Path) -> Result<DynamicImage, StegError> {
     if !path.exists() {
-        return Err(format!("Path {} doesn't exist!", path.display()));
+        return Err(StegError::not_found(path));
     }
+    load(progress::open(path)?, ImageFormat::from_path(path).ok())
+}
 
-    // open + normalize to RGBA8 so buffer layout is predictable
-    let dyn_i = ImageReader::open(path).map_err(|e| e.to_string())?.decode().map_err(|e| e.to_string())?;
-    let img = dyn_i.to_rgba8();
-    let (w, h) = img.dimensions();
-    let bytes_per_pixel = 4usize; // RGBA8
+// the picture `reader` holds, read as `format` when that's known and as its first bytes say otherwise
+#[cfg(feature = "picture")]
+fn load(reader: impl BufRead + Seek, format: Option<ImageFormat>) -> Result<DynamicImage, StegError> {
+    let reader = match format {
+        Some(format) => ImageReader::with_format(reader, format),
+        None => ImageReader::new(reader).with_guessed_format()?,
+    };
+    Ok(native(reader.decode()?))
+}
 
-    let buf = img.into_raw(); // Vec<u8> with layout [R,G,B,A, R,G,B,A, ...]
-    let pixels = (w as usize) * (h as usize);
+// `img` at 8 bits a sample, or 16 when it has more than 8, keeping whether it's gray or color and
+// whether it has alpha
+#[cfg(feature = "picture")]
+pub(crate) fn native(img: DynamicImage) -> DynamicImage {
+    use DynamicImage::*;
+    match img {
+        ImageLuma8(_) | ImageLumaA8(_) | ImageRgb8(_) | ImageRgba8(_) => img,
+        ImageLuma16(_) | ImageLumaA16(_) | ImageRgb16(_) | ImageRgba16(_) => img,
+        _ => at_depth(img, true),
+    }
+}
 
-    // collect LSBs (RGB order) into bits vec
-    let mut bits: Vec<u8> = Vec::with_capacity(pixels * 3);
-    for chunk in buf.chunks(bytes_per_pixel) {
-        // chunk length is 4 because we normalized to RGBA8
-        bits.push(chunk[0] & 1);
-        bits.push(chunk[1] & 1);
-        bits.push(chunk[2] & 1);
+// `img` at 8 or 16 bits a sample, keeping whether it's gray or color and whether it has alpha
+#[cfg(feature = "picture")]
+fn at_depth(img: DynamicImage, sixteen: bool) -> DynamicImage {
+    use DynamicImage::*;
+    match (img.color().has_color(), img.color().has_alpha(), sixteen) {
+        (false, false, false) => ImageLuma8(img.to_luma8()),
+        (false, true, false) => ImageLumaA8(img.to_luma_alpha8()),
+        (true, false, false) => ImageRgb8(img.to_rgb8()),
+        (true, true, false) => ImageRgba8(img.to_rgba8()),
+        (false, false, true) => ImageLuma16(img.to_luma16()),
+        (false, true, true) => ImageLumaA16(img.to_luma_alpha16()),
+        (true, false, true) => ImageRgb16(img.to_rgb16()),
+        (true, true, true) => ImageRgba16(img.to_rgba16()),
     }
+}
 
-    if bits.len() < 32 {
-        return Err("Image too small to contain header".to_string());
+// the samples of a `native` picture, pixel by pixel, at the depth it has
+#[cfg(feature = "picture")]
+enum Samples<'a> {
+    Eight(&'a mut [u8]),
+    Sixteen(&'a mut [u16]),
+}
+
+#[cfg(feature = "picture")]
+impl<'a> Samples<'a> {
+    fn of(img: &'a mut DynamicImage) -> Samples<'a> {
+        use DynamicImage::*;
+        match img {
+            ImageLuma8(i) => Samples::Eight(i),
+            ImageLumaA8(i) => Samples::Eight(i),
+            ImageRgb8(i) => Samples::Eight(i),
+            ImageRgba8(i) => Samples::Eight(i),
+            ImageLuma16(i) => Samples::Sixteen(i),
+            ImageLumaA16(i) => Samples::Sixteen(i),
+            ImageRgb16(i) => Samples::Sixteen(i),
+            ImageRgba16(i) => Samples::Sixteen(i),
+            _ => unreachable!("lsb only walks 8 and 16-bit pictures"),
+        }
     }
 
-    // read 32-bit big-endian length header
-    let mut len: u32 = 0;
-    for i in 0..32 {
-        len = (len << 1) | (bits[i] as u32);
+    fn get(&self, idx: usize) -> u16 {
+        match self {
+            Samples::Eight(buf) => buf[idx] as u16,
+            Samples::Sixteen(buf) => buf[idx],
+        }
+    }
+
+    fn set(&mut self, idx: usize, value: u16) {
+        match self {
+            Samples::Eight(buf) => buf[idx] = value as u8,
+            Samples::Sixteen(buf) => buf[idx] = value,
+        }
+    }
+
+    fn flip(&mut self, idx: usize) {
+        self.set(idx, self.get(idx) ^ 1);
     }
+}
 
-    let needed_bits = (len as usize) * 8;
-    if bits.len() < 32 + needed_bits {
-        return Err(format!(
-            "Image does not contain full message: header says {} bytes but capacity is {} bits",
-            len,
-            bits.len() - 32
-        ));
+// every gray or R, G and B sample of a `native` picture (alpha never) through `f`, which also gets the
+// largest value a sample holds
+#[cfg(feature = "picture")]
+pub(crate) fn map_color_samples(img: &mut DynamicImage, mut f: impl FnMut(u16, u16) -> u16) {
+    let shape = Shape::of(img);
+    let color = if shape.color { 3 } else { 1 };
+    let mut buf = Samples::of(img);
+    for idx in (0..shape.width as usize * shape.height as usize * shape.samples()).filter(|i| i % shape.samples() < color) {
+        buf.set(idx, f(buf.get(idx), shape.max()));
     }
+}
+
+// indexes of the samples of a `native` picture that lsb walks with `channels` and one bit a sample,
+// in slot order, and how many bits each has
+#[cfg(feature = "picture")]
+pub(crate) fn channel_samples(img: &DynamicImage, channels: &[usize]) -> Result<(Vec<usize>, u8), StegError> {
+    let opts = LsbOptions { channels: channels.to_vec(), ..LsbOptions::default() };
+    let walk = Walk::new(&opts, Shape::of(img))?;
+    Ok(((0..walk.slots()?).map(|slot| walk.place(slot).0).collect(), if walk.shape.sixteen { 16 } else { 8 }))
+}
 
-    // reconstruct message bytes (MSB-first per byte)
-    let mut bytes: Vec<u8> = Vec::with_capacity(len as usize);
-    let start = 32;
-    for byte_idx in 0..(len as usize) {
-        let base = start + byte_idx * 8;
-        let mut b: u8 = 0;
-        for j in 0..8 {
-            b = (b << 1) | (bits[base + j] & 1);
+// sample `idx` of a `native` picture set to `value`
+#[cfg(feature = "picture")]
+pub(crate) fn set_sample(img: &mut DynamicImage, idx: usize, value: u16) {
+    Samples::of(img).set(idx, value);
+}
+
+// sample `idx` of a `native` picture
+#[cfg(feature = "picture")]
+pub(crate) fn sample(img: &DynamicImage, idx: usize) -> u16 {
+    let bytes = img.as_bytes();
+    match Shape::of(img).sixteen {
+        true => u16::from_ne_bytes([bytes[2 * idx], bytes[2 * idx + 1]]),
+        false => bytes[idx] as u16,
+    }
+}
+
+// `img.save_with_format(path, format)`, writing through `progress`
+#[cfg(feature = "picture")]
+pub(crate) fn save(img: &DynamicImage, path: &Path, format: ImageFormat) -> Result<(), StegError> {
+    atomic::write_with(path, |file| {
+        let mut out = progress::create(file, None);
+        img.write_to(&mut out, format)?;
+        Ok(out.flush()?)
+    })
+}
+
+// `img` encoded as `format`, in memory
+#[cfg(feature = "picture")]
+fn encode(img: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>, StegError> {
+    let mut out = Cursor::new(Vec::new());
+    img.write_to(&mut out, format)?;
+    Ok(out.into_inner())
+}
+
+/// Flip the LSB of `count` random RGB (or gray) channels past the end of a `payload_len` byte payload hidden at
+/// `offset` and `stride`, rewriting `path` in place. Makes the file's hash differ even when the payload
+/// bits happened to match. Returns how many channels were flipped (fewer than `count` if the tail is too
+/// short).
+#[cfg(feature = "picture")]
+pub fn perturb(path: &Path, payload_len: usize, offset: usize, stride: usize, count: usize, rng: &mut impl RngCore) -> Result<usize, StegError> {
+    let ext = path.extension().and_then(|e| e.to_str()).ok_or("Invalid file extension")?;
+    let format = ImageFormat::from_extension(ext).ok_or_else(|| StegError::UnsupportedFormat { found: ext.to_string() })?;
+    let mut img = decode(path)?;
+    let plain = LsbOptions::default();
+    let walk = Walk::new(&plain, Shape::of(&img))?;
+
+    let slots = walk.slots()?;
+    let used = (offset + (32 + payload_len * 8 - 1) * stride + 1).min(slots);
+    let free = slots - used;
+    let count = count.min(free);
+    let mut buf = Samples::of(&mut img);
+    for i in rand::seq::index::sample(rng, free, count) {
+        buf.flip(walk.place(used + i).0);
+    }
+    save(&img, path, format)?;
+    Ok(count)
+}
+
+/// Replace the LSB of every RGB (or gray) channel with a random bit, rewriting `path` in place. Destroys an LSB
+/// payload whatever stride or key it was hidden with. Returns how many channels that is.
+#[cfg(feature = "picture")]
+pub fn randomize(path: &Path, rng: &mut impl RngCore) -> Result<usize, StegError> {
+    let ext = path.extension().and_then(|e| e.to_str()).ok_or("Invalid file extension")?;
+    let format = ImageFormat::from_extension(ext).ok_or_else(|| StegError::UnsupportedFormat { found: ext.to_string() })?;
+    let mut img = decode(path)?;
+    let plain = LsbOptions::default();
+    let walk = Walk::new(&plain, Shape::of(&img))?;
+    let count = walk.slots()?;
+    let mut buf = Samples::of(&mut img);
+    for slot in 0..count {
+        let idx = walk.place(slot).0;
+        buf.set(idx, (buf.get(idx) & !1) | (rng.next_u32() & 1) as u16);
+    }
+    save(&img, path, format)?;
+    Ok(count)
+}
+
+/// `perturb` for images made with `hide_keyed`: the flipped channels are picked at random among the
+/// slots the key's order (starting at `offset`) didn't use for the payload.
+#[cfg(feature = "picture")]
+pub fn perturb_keyed(path: &Path, payload_len: usize, offset: usize, key: &str, count: usize, rng: &mut impl RngCore) -> Result<usize, StegError> {
+    let ext = path.extension().and_then(|e| e.to_str()).ok_or("Invalid file extension")?;
+    let format = ImageFormat::from_extension(ext).ok_or_else(|| StegError::UnsupportedFormat { found: ext.to_string() })?;
+    let mut img = decode(path)?;
+    let plain = LsbOptions::default();
+    let walk = Walk::new(&plain, Shape::of(&img))?;
+
+    let slots = walk.slots()?;
+    let mut taken: HashSet<usize> = KeyedOrder::new(key, slots.saturating_sub(offset)).take((4 + payload_len) * 8).map(|s| s + offset).collect();
+    let count = count.min(slots - taken.len());
+    let mut buf = Samples::of(&mut img);
+    let mut flipped = 0;
+    while flipped < count {
+        let slot = rng.gen_range(0..slots);
+        if taken.insert(slot) {
+            buf.flip(walk.place(slot).0);
+            flipped += 1;
+        }
+    }
+    save(&img, path, format)?;
+    Ok(count)
+}
+
+/// The message `hide` put in the picture at `path`, as text: `find_bytes`, refusing a payload that
+/// isn't UTF-8 rather than mangling it.
+///
+/// ```
+/// use rust_stego::steg::picture::lsb;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let (cover, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+/// image::RgbImage::from_fn(32, 32, |x, y| image::Rgb([x as u8 * 8, y as u8 * 8, 64])).save(&cover).unwrap();
+///
+/// lsb::hide(&cover, "héllo", &out)?;
+/// assert_eq!(lsb::find(&out)?, "héllo");
+/// # Ok::<(), rust_stego::steg::StegError>(())
+/// ```
+#[cfg(feature = "picture")]
+pub fn find(path: &Path) -> Result<String, StegError> {
+    let bytes = find_bytes(path)?;
+    String::from_utf8(bytes).map_err(|_| "The payload is binary, not UTF-8 text: find_bytes (or find -o) gives its bytes".into())
+}
+
+/// The bytes `hide` put in the picture at `path`, whatever they are. `find_bytes_with` reads a carrier
+/// already in memory.
+#[cfg(feature = "picture")]
+pub fn find_bytes(path: &Path) -> Result<Vec<u8>, StegError> {
+    find_payload_sparse(path, Some(1))
+}
+
+/// Extract a payload written by `hide_sparse`. With `stride: None` the stride is recovered by trying
+/// 1..=MAX_PROBE_STRIDE and picking the first one whose payload starts with the framing magic.
+#[cfg(feature = "picture")]
+pub fn find_payload_sparse(path: &Path, stride: Option<usize>) -> Result<Vec<u8>, StegError> {
+    if stride == Some(0) {
+        return Err("Stride must be at least 1".into());
+    }
+    extract_sparse(&read_lsbs(path)?, stride)
+}
+
+/// `find_payload_keyed` with a key, `find_payload_sparse` without, plus a confidence per byte when
+/// the payload was stored with --redundancy (a single copy has nothing to vote with).
+#[cfg(feature = "picture")]
+pub fn find_scored(path: &Path, stride: Option<usize>, key: Option<&str>) -> Result<(Vec<u8>, Option<Vec<f32>>), StegError> {
+    find_with(path, &FindOptions { lsb: LsbOptions { stride, key: key.map(String::from), ..LsbOptions::default() }, ..FindOptions::default() })
+}
+
+/// The general form of the find functions: the payload `hide_with` laid out as `opts.lsb` says, up to
+/// `opts.limit` bytes of it, with a confidence per byte when it was stored more than once. Past offset
+/// 0 there are no legacy carriers to think of, so anything that doesn't start with the framing magic is
+/// refused as the wrong offset/stride/key rather than handed on as data.
+#[cfg(feature = "picture")]
+pub fn find_with(path: &Path, opts: &FindOptions) -> Result<(Vec<u8>, Option<Vec<f32>>), StegError> {
+    opts.lsb.check()?;
+    find_in_image(&decode(path)?, &opts.lsb, opts.limit)
+}
+
+/// `find_with` for a carrier in memory, as `hide_bytes_with` returns it, without the confidence scores.
+#[cfg(feature = "picture")]
+pub fn find_bytes_with(carrier: &[u8], opts: &FindOptions) -> Result<Vec<u8>, StegError> {
+    find_stream_with(Cursor::new(carrier), opts)
+}
+
+/// `find_bytes_with` for a carrier read from a stream, see `hide_stream_with`.
+#[cfg(feature = "picture")]
+pub fn find_stream_with(carrier: impl Read + Seek, opts: &FindOptions) -> Result<Vec<u8>, StegError> {
+    opts.lsb.check()?;
+    let img = load(BufReader::new(carrier), None)?;
+    find_in_image(&img, &opts.lsb, opts.limit).map(|(data, _)| data)
+}
+
+// `find_in_slots` at the depth, channels and alpha the carrier's header says, when `opts` leaves them
+// out. 16 slots of a plain carrier can look like a header by chance, so one that then holds nothing is
+// read as plain after all.
+#[cfg(feature = "picture")]
+fn find_in_image(img: &DynamicImage, opts: &LsbOptions, limit: Option<usize>) -> Result<(Vec<u8>, Option<Vec<f32>>), StegError> {
+    let recorded = recorded_layout(img, opts);
+    match find_in_slots(&slots(img, &recorded)?, &recorded, limit) {
+        Err(e) if recorded != *opts => find_in_slots(&slots(img, opts)?, opts, limit).map_err(|_| e),
+        found => found,
+    }
+}
+
+/// `opts` with the bits a channel, channels, alpha and traversal `img` carries a payload at: as they
+/// are when `opts` asks for anything but the plain layout, otherwise the first whose layout header is
+/// there, one bit of RGB row by row when none is.
+#[cfg(feature = "picture")]
+pub fn recorded_layout(img: &DynamicImage, opts: &LsbOptions) -> LsbOptions {
+    if !opts.plain_layout() {
+        return opts.clone();
+    }
+    // all three channels first, the masks a header can record after
+    let masks = (1..8u8).rev().map(|mask| (0..3).filter(|c| mask >> c & 1 == 1).collect::<Vec<usize>>());
+    // the texture ranking is the same for every probe, and the threshold only cuts it short
+    let textured = OnceCell::new();
+    Traversal::ALL
+        .into_iter()
+        .flat_map(|traversal| [false, true].map(|use_alpha| (traversal, use_alpha)))
+        .flat_map(|(traversal, use_alpha)| masks.clone().map(move |channels| (traversal, channels, use_alpha)))
+        .flat_map(|(traversal, channels, use_alpha)| (1..=MAX_BITS).map(move |bits| (traversal, bits, channels.clone(), use_alpha)))
+        .skip(1)
+        .map(|(traversal, bits, channels, use_alpha)| LsbOptions { bits, channels, use_alpha, traversal, ..opts.clone() })
+        .find_map(|at| {
+            let threshold = {
+                let mut walk = Walk::new(&at, Shape::of(img)).ok()?;
+                if at.traversal == Traversal::Texture {
+                    let rect = walk.rect();
+                    walk.ranked = Cow::Borrowed(textured.get_or_init(|| ranked(&texture(img, rect), 0)));
+                }
+                if walk.slots().ok()? < at.start() {
+                    return None;
+                }
+                let bit = |slot| {
+                    let (idx, i) = walk.place(slot);
+                    (sample(img, idx) >> i) as u8 & 1
+                };
+                // the tag and the layout byte have to match, a texture threshold after them is read
+                let header = at.layout_header();
+                if !(at.offset..).zip(&header[..16]).all(|(slot, &b)| bit(slot) == b) {
+                    return None;
+                }
+                (at.offset + 16..at.start()).fold(0, |t, slot| t << 1 | bit(slot))
+            };
+            Some(LsbOptions { threshold, ..at })
+        })
+        .unwrap_or_else(|| opts.clone())
+}
+
+/// `find_stream_with` with the layout as it was passed before `FindOptions`.
+#[cfg(feature = "picture")]
+#[deprecated(note = "use find_stream_with and FindOptions, this goes in the next release")]
+pub fn find_stream(carrier: impl Read + Seek, opts: &LsbOptions) -> Result<Vec<u8>, StegError> {
+    find_stream_with(carrier, &FindOptions { lsb: opts.clone(), ..FindOptions::default() })
+}
+
+/// `find_with` on slot bits already read with `slots` for the same `bits` and `channels`, so trying
+/// other offsets, strides and keys doesn't decode the image again.
+pub fn find_in_slots(bits: &[u8], opts: &LsbOptions, limit: Option<usize>) -> Result<(Vec<u8>, Option<Vec<f32>>), StegError> {
+    opts.check()?;
+    if let Some(r) = opts.region && bits.len() < opts.offset + 32 {
+        return Err(format!("Region {} is too small to hold the 32-bit length header", r).into());
+    }
+    let offset = opts.offset;
+    let mut bits = bits.get(offset..).filter(|b| !b.is_empty())
+        .ok_or_else(|| format!("Offset {} is past the last of the image's {} channel slots", offset, bits.len()))?;
+    // past the layout header; a carrier from before there were headers starts right at the offset
+    let header = opts.layout_header();
+    if !header.is_empty() && bits.starts_with(&header) && bits.len() > header.len() {
+        bits = &bits[header.len()..];
+    }
+    let (stride, key) = (opts.stride, opts.key.as_deref());
+    let found = match key {
+        Some(k) => extract_keyed_scored(bits, k, limit),
+        None => extract_sparse_scored(bits, stride, limit),
+    };
+    match found {
+        Ok((data, _)) if offset > 0 && !data.starts_with(&MAGIC) => {
+            Err(format!("No payload at offset {} (the header magic doesn't match, wrong --offset, --stride or --key?)", offset).into())
         }
-        bytes.push(b);
+        Err(e) if offset > 0 => Err(format!("{} (wrong --offset?)", e).into()),
+        other => other,
+    }
+}
+
+/// `find_payload_sparse` on the slot LSBs of any carrier laid out like this one, see `picture::raw`.
+pub(crate) fn extract_sparse(bits: &[u8], stride: Option<usize>) -> Result<Vec<u8>, StegError> {
+    extract_sparse_scored(bits, stride, None).map(|(data, _)| data)
+}
+
+pub(crate) fn extract_sparse_scored(bits: &[u8], stride: Option<usize>, limit: Option<usize>) -> Result<(Vec<u8>, Option<Vec<f32>>), StegError> {
+    let stride = match stride {
+        Some(s) => s,
+        // fall back to 1 so a carrier without our framing still decodes (and fails) like it always did
+        None => {
+            let found = (1..=MAX_PROBE_STRIDE)
+                .find(|&s| has_magic(bits, s) || redundancy::header(&take_slots(bits, Order::Strided(s), 32 * redundancy::MAX_REDUNDANCY)).is_some());
+            log::debug!("lsb: {}", found.map_or("no stride up to the limit has a header, reading at stride 1".to_string(), |s| format!("header at stride {}", s)));
+            found.unwrap_or(1)
+        }
+    };
+    if let Some((data, confidence)) = redundancy::find_scored(|count| take_slots(bits, Order::Strided(stride), count))? {
+        return Ok((data, Some(confidence)));
+    }
+    Ok((decode_strided(bits, stride, limit)?, None))
+}
+
+/// Extract a payload written by `hide_keyed` with the same key.
+#[cfg(feature = "picture")]
+pub fn find_payload_keyed(path: &Path, key: &str) -> Result<Vec<u8>, StegError> {
+    extract_keyed(&read_lsbs(path)?, key)
+}
+
+/// `find_payload_keyed` on the slot LSBs of any carrier laid out like this one.
+pub(crate) fn extract_keyed(bits: &[u8], key: &str) -> Result<Vec<u8>, StegError> {
+    extract_keyed_scored(bits, key, None).map(|(data, _)| data)
+}
+
+pub(crate) fn extract_keyed_scored(bits: &[u8], key: &str, limit: Option<usize>) -> Result<(Vec<u8>, Option<Vec<f32>>), StegError> {
+    if bits.len() < 32 {
+        return Err("Image too small to contain header".into());
+    }
+    if let Some((data, confidence)) = redundancy::find_scored(|count| take_slots(bits, Order::Keyed(key), count))? {
+        return Ok((data, Some(confidence)));
+    }
+    let mut order = KeyedOrder::new(key, bits.len());
+    let mut next_bytes = |count: usize| -> Vec<u8> {
+        (0..count).map(|_| order.by_ref().take(8).fold(0u8, |b, slot| (b << 1) | bits[slot])).collect()
+    };
+
+    let len = u32::from_be_bytes(next_bytes(4).try_into().unwrap());
+    let available_bytes = (bits.len() - 32) / 8;
+    if len as u64 > available_bytes as u64 {
+        return Err(StegError::NoPayloadFound);
+    }
+    Ok((next_bytes(limit.map_or(len as usize, |l| l.min(len as usize))), None))
+}
+
+/// LSB of every R, G and B (or gray) channel, in raster order.
+#[cfg(feature = "picture")]
+fn read_lsbs(path: &Path) -> Result<Vec<u8>, StegError> {
+    slots(&decode(path)?, &LsbOptions::default())
+}
+
+/// The bits of every channel slot of `img` in slot order, as `opts.bits`, `opts.channels` and
+/// `opts.region` lay them out.
+#[cfg(feature = "picture")]
+pub fn slots(img: &DynamicImage, opts: &LsbOptions) -> Result<Vec<u8>, StegError> {
+    let walk = Walk::over(opts, img)?;
+    Ok((0..walk.slots()?).map(|slot| {
+        let (idx, at) = walk.place(slot);
+        (sample(img, idx) >> at) as u8 & 1
+    }).collect())
+}
+
+// the first `count` slot bits in embedding order
+fn take_slots(bits: &[u8], order: Order, count: usize) -> Vec<u8> {
+    order.slots(bits.len()).take(count).map(|slot| bits[slot]).collect()
+}
+
+// read `count` bytes (MSB-first) from every `stride`-th LSB, starting at the `start`-th of those
+fn read_bytes(bits: &[u8], stride: usize, start: usize, count: usize) -> Vec<u8> {
+    (0..count)
+        .map(|byte_idx| {
+            let mut b: u8 = 0;
+            for j in 0..8 {
+                b = (b << 1) | (bits[(start + byte_idx * 8 + j) * stride] & 1);
+            }
+            b
+        })
+        .collect()
+}
+
+// cheap check used for stride probing: only looks at the header and the first 4 payload bytes
+fn has_magic(bits: &[u8], stride: usize) -> bool {
+    bits.len().div_ceil(stride) >= 64 && read_bytes(bits, stride, 32, 4) == MAGIC
+}
+
+fn decode_strided(bits: &[u8], stride: usize, limit: Option<usize>) -> Result<Vec<u8>, StegError> {
+    let slots = bits.len().div_ceil(stride);
+    if slots < 32 {
+        return Err("Image too small to contain header".into());
+    }
+
+    // read 32-bit big-endian length header
+    let len = u32::from_be_bytes(read_bytes(bits, stride, 0, 4).try_into().unwrap());
+
+    // validate the claimed length against what the image can actually hold *before* allocating for it,
+    // a noise image can easily claim 4 GB
+    let available_bytes = (slots - 32) / 8;
+    if len as u64 > available_bytes as u64 {
+        return Err(StegError::NoPayloadFound);
     }
 
-    String::from_utf8(bytes).map_err(|_| "<invalid utf8>".to_string())
+    // reconstruct message bytes (MSB-first per byte), only as many as asked for
+    Ok(read_bytes(bits, stride, 32, limit.map_or(len as usize, |l| l.min(len as usize))))
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "picture"))]
 mod tests {
     use super::*;
     use std::fs::{File};
     use std::path::Path;
-    use image::codecs::png;
+    use png::{Encoder, ColorType, BitDepth};
     use tempfile::tempdir;
 
     // create a test PNG at `path` with given width/height, RGB
@@ -169,6 +1254,53 @@ mod tests {
         assert_eq!(&decoded_bytes[..message.len()], message.as_bytes());
     }
 
+    #[test]
+    fn test_plan_matches_what_hide_writes() {
+        let dir = tempdir().unwrap();
+        let (path, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+        create_test_png(&path, 40, 30);
+        let opts = LsbOptions { key: Some("k".to_string()), ..LsbOptions::default() };
+
+        let plan = plan(&path, "abc", &out, &opts, 3).unwrap();
+        assert!(!out.exists());
+        assert_eq!((plan.bits, plan.capacity_bits, plan.units), ((4 + 3) * 8 * 3, 40 * 30 * 3, 1200));
+        hide_with(&path, "abc", &out, &HideOptions { lsb: opts.clone(), copies: 3, ..HideOptions::default() }).unwrap();
+        let (a, b) = (decode(&path).unwrap().to_rgba8(), decode(&out).unwrap().to_rgba8());
+        assert_eq!(plan.changed, a.pixels().zip(b.pixels()).filter(|(x, y)| x != y).count());
+        assert_eq!(plan.output_bytes, std::fs::metadata(&out).unwrap().len());
+        assert!(super::plan(&path, "x".repeat(500), &out, &opts, 1).is_err());
+    }
+
+    #[test]
+    fn test_region_keeps_the_rest_of_the_picture() {
+        let dir = tempdir().unwrap();
+        let (path, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+        create_test_png(&path, 40, 30);
+        let region = Region::parse("10, 5, 12, 8").unwrap();
+        let opts = HideOptions::default().region(region).key("k");
+
+        // 12x8 pixels of 3 slots, less the length header
+        assert_eq!(capacity(&path, &opts).unwrap(), 12 * 8 * 3 / 8 - 4);
+        hide_with(&path, "in the box", &out, &opts).unwrap();
+        let (a, b) = (decode(&path).unwrap().to_rgba8(), decode(&out).unwrap().to_rgba8());
+        for (x, y, p) in b.enumerate_pixels() {
+            if !(10..22).contains(&x) || !(5..13).contains(&y) {
+                assert_eq!(p, a.get_pixel(x, y));
+            }
+        }
+        let mut find = opts.to_find();
+        assert_eq!(find_with(&out, &find).unwrap().0, b"in the box");
+        find.lsb.region = None;
+        assert!(find_with(&out, &find).is_err());
+
+        let outside = HideOptions::default().region(Region { x: 30, y: 0, w: 11, h: 1 });
+        assert!(hide_with(&path, "x", &out, &outside).unwrap_err().to_string().contains("past the edge of the 40x30 image"));
+        let tiny = HideOptions::default().region(Region { x: 0, y: 0, w: 2, h: 2 });
+        assert!(hide_with(&path, "", &out, &tiny).unwrap_err().to_string().contains("too few for even the 32-bit length header"));
+        assert!(find_with(&out, &tiny.to_find()).unwrap_err().to_string().contains("too small"));
+        assert!(Region::parse("1,2,3").is_err() && Region::parse("1,2,0,4").is_err());
+    }
+
     #[test]
     fn test_message_too_big() {
         let dir = tempdir().unwrap();
@@ -200,7 +1332,720 @@ mod tests {
 
         let decoded = find(&path).expect("Failed to decode empty message");
         // just ensure decoding didn't return the invalid-utf8 sentinel
-        assert_ne!(decoded, "<invalid utf8>");
+        assert_eq!(decoded, "");
+    }
+
+    #[test]
+    fn binary_payloads_come_back_whole_from_find_payload() {
+        let dir = tempdir().unwrap();
+        let (path, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+        create_test_png(&path, 32, 32);
+
+        let msg = [0x00, 0xFF, 0x00, 0x00, 0xFF, 0xFE, 0x80, 0x00, 0xFF];
+        hide(&path, msg, &out).unwrap();
+        assert_eq!(find_bytes(&out).unwrap(), msg);
+        // find is for text, and says where the bytes are rather than mangling them
+        assert!(find(&out).unwrap_err().to_string().contains("binary, not UTF-8 text: find_bytes"));
+    }
+
+    #[test]
+    fn test_compressed_payload_fits_where_raw_does_not() {
+        use crate::steg_algorithms::payload::{DecodeOptions, FrameOptions, Payload};
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("small.png");
+        let out = dir.path().join("small_out.png");
+
+        // 330x330 holds ~40 KB raw
+        create_test_png(&path, 330, 330);
+        let text = "the quick brown fox jumps over the lazy dog. ".repeat(100 * 1024 / 45);
+        let payload = Payload::from_text(&text);
+
+        assert!(hide(&path, payload.encode(&FrameOptions::default()).unwrap(), &out).is_err());
+
+        hide(&path, payload.encode(&FrameOptions { compress: true, ..Default::default() }).unwrap(), &out).expect("compressed payload should fit");
+        let decoded = Payload::decode(&find_bytes(&out).unwrap(), &DecodeOptions::default()).unwrap();
+        assert_eq!(decoded.data, text.as_bytes());
+    }
+
+    #[test]
+    fn test_encrypted_roundtrip() {
+        use crate::steg_algorithms::payload::{DecodeOptions, FrameOptions, Payload};
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("enc.png");
+        let out = dir.path().join("enc_out.png");
+        create_test_png(&path, 64, 64);
+
+        let opts = FrameOptions { password: Some("correct horse".to_string()), ..Default::default() };
+        let unlock = DecodeOptions { password: Some("correct horse".to_string()), ..Default::default() };
+        for text in ["", "nobody can read this"] {
+            hide(&path, Payload::from_text(text).encode(&opts).unwrap(), &out).unwrap();
+            let decoded = Payload::decode(&find_bytes(&out).unwrap(), &unlock).unwrap();
+            assert_eq!(decoded.data, text.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_hmac_roundtrip_and_tamper() {
+        use crate::steg_algorithms::payload::{Auth, DecodeOptions, FrameOptions, Payload};
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("h.png");
+        let out = dir.path().join("h_out.png");
+        create_test_png(&path, 40, 40);
+
+        let key = Some("shared".to_string());
+        let framed = Payload::from_text("tamper evident").encode(&FrameOptions { hmac_key: key.clone(), ..Default::default() }).unwrap();
+        hide(&path, &framed, &out).unwrap();
+
+        let check = DecodeOptions { hmac_key: key, ..Default::default() };
+        let (p, auth) = Payload::decode_verified(&find_bytes(&out).unwrap(), &check).unwrap();
+        assert_eq!((p.data.as_slice(), auth), (&b"tamper evident"[..], Auth::Verified));
+
+        // flip one LSB inside the message text: slot 32 + 8*15 is pixel 50, channel 2
+        let mut img = image::open(&out).unwrap().to_rgba8();
+        (*img)[50 * 4 + 2] ^= 1;
+        img.save(&out).unwrap();
+        assert!(matches!(Payload::decode(&find_bytes(&out).unwrap(), &check), Err(StegError::ChecksumMismatch)));
+    }
+
+    // flip the LSB of the first bit of each listed message byte (stride 1, RGB slots)
+    fn damage_bytes(path: &Path, bytes: &[usize]) {
+        let mut img = image::open(path).unwrap().to_rgba8();
+        for &b in bytes {
+            let slot = 32 + 8 * b;
+            (*img)[slot / 3 * 4 + slot % 3] ^= 1;
+        }
+        img.save(path).unwrap();
+    }
+
+    #[test]
+    fn test_fec_survives_flipped_bytes() {
+        use crate::steg_algorithms::payload::{DecodeOptions, FrameOptions, Payload};
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("f.png");
+        let out = dir.path().join("f_out.png");
+        create_test_png(&path, 64, 64);
+
+        let framed = Payload::from_text("bent but not broken").encode(&FrameOptions { fec_parity: Some(16), ..Default::default() }).unwrap();
+        hide(&path, &framed, &out).unwrap();
+        damage_bytes(&out, &[3, 20, 40, 41, 42, 60]);
+        let p = Payload::decode(&find_bytes(&out).unwrap(), &DecodeOptions::default()).unwrap();
+        assert_eq!(p.data, b"bent but not broken");
+
+        // 16 parity bytes fix 8, not 20
+        damage_bytes(&out, &(35..55).collect::<Vec<_>>());
+        let err = Payload::decode(&find_bytes(&out).unwrap(), &DecodeOptions::default()).unwrap_err().to_string();
+        assert!(err.contains("Too much damage"), "{}", err);
+    }
+
+    #[test]
+    fn test_redundancy_outvotes_edited_pixels() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("r.png");
+        let out = dir.path().join("r_out.png");
+        create_test_png(&path, 40, 40);
+        let msg = b"three times over";
+
+        // 40x40x3 slots hold 596 plain bytes but only 196 at three copies
+        assert!(matches!(hide_redundant(&path, vec![1u8; 197], &out, 1, None, 3), Err(StegError::CapacityExceeded { .. })));
+        hide_redundant(&path, msg, &out, 1, None, 3).unwrap();
+
+        // scribble over the first copy's start and the second copy's end, plus one header repeat
+        let data_bits = msg.len() * 8;
+        let mut img = image::open(&out).unwrap().to_rgba8();
+        let slots = (96..96 + 40).chain(96 + 2 * data_bits - 40..96 + 2 * data_bits).chain([4]);
+        for slot in slots {
+            (*img)[slot / 3 * 4 + slot % 3] ^= 1;
+        }
+        img.save(&out).unwrap();
+        assert_eq!(find_bytes(&out).unwrap(), msg);
+        assert_eq!(find_payload_sparse(&out, None).unwrap(), msg);
+
+        hide_redundant(&path, msg, &out, 1, Some("k"), 5).unwrap();
+        assert_eq!(find_payload_keyed(&out, "k").unwrap(), msg);
+    }
+
+    #[test]
+    fn test_named_payloads_grow_without_clobbering() {
+        use crate::steg_algorithms::payload::{DecodeOptions, FrameOptions, Payload, Table};
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("n.png");
+        let out = dir.path().join("n_out.png");
+        create_test_png(&path, 40, 40);
+        let frame = |text: &str| Payload::from_text(text).encode(&FrameOptions::default()).unwrap();
+
+        let mut table = Table::default();
+        table.insert("readme", frame("hello")).unwrap();
+        hide(&path, table.encode(None).unwrap(), &out).unwrap();
+
+        // what hide --name does: read the table back, add an entry, re-embed the lot
+        let mut table = Table::parse(&find_bytes(&out).unwrap()).unwrap().unwrap();
+        table.insert("key", frame("0xDEADBEEF")).unwrap();
+        hide(&out, table.encode(None).unwrap(), &out).unwrap();
+        let back = Table::parse(&find_bytes(&out).unwrap()).unwrap().unwrap();
+        assert_eq!(Payload::decode(back.get("readme").unwrap(), &DecodeOptions::default()).unwrap().data, b"hello");
+        assert_eq!(Payload::decode(back.get("key").unwrap(), &DecodeOptions::default()).unwrap().data, b"0xDEADBEEF");
+
+        // an entry that doesn't fit fails before the output is touched
+        let before = std::fs::read(&out).unwrap();
+        table.insert("huge", frame(&"x".repeat(700))).unwrap();
+        assert!(hide(&out, table.encode(None).unwrap(), &out).is_err());
+        assert_eq!(std::fs::read(&out).unwrap(), before);
+    }
+
+    #[test]
+    fn test_perturb_keeps_payload() {
+        use rand::SeedableRng;
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("p.png");
+        let out = dir.path().join("p_out.png");
+        create_test_png(&path, 40, 40);
+
+        hide(&path, "dedup me", &out).unwrap();
+        let before = image::open(&out).unwrap().to_rgba8();
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(1);
+        assert_eq!(perturb(&out, 8, 0, 1, 10, &mut rng).unwrap(), 10);
+
+        let after = image::open(&out).unwrap().to_rgba8();
+        assert_eq!(before.as_raw().iter().zip(after.as_raw()).filter(|(a, b)| a != b).count(), 10);
+        assert_eq!(find(&out).unwrap(), "dedup me");
+    }
+
+    #[test]
+    fn test_keyed_roundtrip_and_scatter() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("k.png");
+        let out = dir.path().join("k_out.png");
+        create_test_png(&path, 64, 64);
+
+        hide_keyed(&path, "only with the key", &out, "s3cret").unwrap();
+        assert_eq!(find_payload_keyed(&out, "s3cret").unwrap(), b"only with the key");
+        assert_ne!(find_payload_keyed(&out, "guess").ok().as_deref(), Some(&b"only with the key"[..]));
+        assert_ne!(find_bytes(&out).ok().as_deref(), Some(&b"only with the key"[..]));
+
+        // the header isn't parked at the start: changes land all over the image
+        let before = image::open(&path).unwrap().to_rgba8();
+        let after = image::open(&out).unwrap().to_rgba8();
+        let changed: Vec<usize> = (0..before.as_raw().len()).filter(|&i| before.as_raw()[i] != after.as_raw()[i]).collect();
+        let half = before.as_raw().len() / 2;
+        assert!(changed.iter().any(|&i| i < half) && changed.iter().any(|&i| i >= half));
+        assert!(changed.iter().filter(|&&i| i < 32 / 3 * 4).count() < 4);
+    }
+
+    #[test]
+    fn test_keyed_perturb_keeps_payload() {
+        use rand::SeedableRng;
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("kp.png");
+        let out = dir.path().join("kp_out.png");
+        create_test_png(&path, 32, 32);
+
+        hide_keyed(&path, "abc", &out, "k").unwrap();
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(5);
+        assert_eq!(perturb_keyed(&out, 3, 0, "k", 50, &mut rng).unwrap(), 50);
+        assert_eq!(find_payload_keyed(&out, "k").unwrap(), b"abc");
+    }
+
+    #[test]
+    fn test_sparse_roundtrip_and_probe() {
+        use crate::steg_algorithms::payload::{FrameOptions, Payload};
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("sparse.png");
+        let out = dir.path().join("sparse_out.png");
+        create_test_png(&path, 101, 67);
+
+        let framed = Payload::from_text("spread me out").encode(&FrameOptions::default()).unwrap();
+        hide_sparse(&path, &framed, &out, 7).unwrap();
+
+        // explicit stride and probed stride both work
+        assert_eq!(find_payload_sparse(&out, Some(7)).unwrap(), framed);
+        assert_eq!(find_payload_sparse(&out, None).unwrap(), framed);
+        // the wrong stride doesn't give us our payload back
+        assert_ne!(find_payload_sparse(&out, Some(1)).ok(), Some(framed));
+    }
+
+    #[test]
+    fn test_offset_skips_slots_and_must_match() {
+        use crate::steg_algorithms::payload::{FrameOptions, Payload};
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("off.png");
+        let out = dir.path().join("off_out.png");
+        create_test_png(&path, 64, 64);
+
+        let framed = Payload::from_text("below the fold").encode(&FrameOptions::default()).unwrap();
+        let at = |offset, stride, key: Option<&str>| HideOptions {
+            lsb: LsbOptions { offset, stride, key: key.map(String::from), ..LsbOptions::default() },
+            ..HideOptions::default()
+        };
+        hide_with(&path, &framed, &out, &at(5000, Some(4), None)).unwrap();
+        let (before, after) = (image::open(&path).unwrap().to_rgba8(), image::open(&out).unwrap().to_rgba8());
+        // slot 5000 is in pixel 1666
+        assert_eq!(before.as_raw()[..1666 * 4], after.as_raw()[..1666 * 4]);
+
+        assert_eq!(find_with(&out, &at(5000, Some(4), None).to_find()).unwrap().0, framed);
+        assert_eq!(find_with(&out, &at(5000, None, None).to_find()).unwrap().0, framed);
+        assert!(find_with(&out, &at(4000, Some(4), None).to_find()).unwrap_err().to_string().contains("offset"));
+        assert_eq!(capacity(&path, &at(5000, Some(4), None)).unwrap(), (64 * 64 * 3usize - 5000).div_ceil(4) / 8 - 4);
+        assert!(hide_with(&path, &framed, &out, &at(64 * 64 * 3, None, None)).is_err());
+
+        hide_with(&path, &framed, &out, &at(300, None, Some("k")).copies(3)).unwrap();
+        assert_eq!(find_with(&out, &at(300, None, Some("k")).to_find()).unwrap().0, framed);
+    }
+
+    #[test]
+    fn test_bits_and_channels_layout() {
+        use crate::steg_algorithms::payload::{FrameOptions, Payload};
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("layout.png");
+        let out = dir.path().join("layout_out.png");
+        create_test_png(&path, 32, 32);
+        let opts = HideOptions::default().bits(2).channels([0, 1]).stride(3);
+        // two bits of two channels is four slots a pixel, past the 16 of the depth header
+        assert_eq!(capacity(&path, &opts).unwrap(), (32 * 32 * 4usize - 16).div_ceil(3) / 8 - 4);
+
+        let framed = Payload::from_text("two bits of red and green").encode(&FrameOptions::default()).unwrap();
+        hide_with(&path, &framed, &out, &opts).unwrap();
+        let (before, after) = (image::open(&path).unwrap().to_rgba8(), image::open(&out).unwrap().to_rgba8());
+        for (p, q) in before.pixels().zip(after.pixels()) {
+            assert_eq!(p[0] & !3, q[0] & !3);
+            assert_eq!(p[1] & !3, q[1] & !3);
+            assert_eq!(p[2], q[2]);
+        }
+        assert_eq!(find_with(&out, &FindOptions::default().bits(2).channels([0, 1])).unwrap().0, framed);
+        assert_ne!(find_payload_sparse(&out, None).ok(), Some(framed));
+        assert!(capacity(&path, &opts.bits(9)).is_err());
+    }
+
+    #[test]
+    fn every_depth_round_trips_and_find_reads_it_from_the_header() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("depth.png");
+        create_test_png(&path, 24, 24);
+        let msg = vec![0xA5; 100];
+        for bits in 1..=MAX_BITS {
+            let out = dir.path().join(format!("depth{}.png", bits));
+            let opts = HideOptions::default().bits(bits);
+            let header = if bits == 1 { 0 } else { 16 };
+            assert_eq!(capacity(&path, &opts).unwrap(), (24 * 24 * 3 * bits as usize - header) / 8 - 4);
+            hide_with(&path, &msg, &out, &opts).unwrap();
+            assert_eq!(find_with(&out, &FindOptions::default()).unwrap().0, msg, "bits={}", bits);
+            assert_eq!(find_with(&out, &FindOptions::default().bits(bits)).unwrap().0, msg);
+            assert_eq!(recorded_layout(&image::open(&out).unwrap(), &LsbOptions::default()).bits, bits);
+            // and a key scatters the payload at any depth
+            hide_with(&path, &msg, &out, &opts.clone().key("k")).unwrap();
+            assert_eq!(find_with(&out, &FindOptions::default().key("k")).unwrap().0, msg);
+        }
+    }
+
+    #[test]
+    fn depths_past_four_are_refused() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("cover.png");
+        create_test_png(&path, 8, 8);
+        for bits in [0, 5, 8] {
+            let err = hide_with(&path, b"x", &dir.path().join("out.png"), &HideOptions::default().bits(bits)).unwrap_err();
+            assert!(matches!(err, StegError::UnsupportedDepth { bits: b, max: MAX_BITS } if b == bits));
+            assert!(matches!(find_with(&path, &FindOptions::default().bits(bits)), Err(StegError::UnsupportedDepth { .. })));
+        }
+    }
+
+    #[test]
+    fn a_payload_too_big_for_one_bit_fits_in_two() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("cover.png");
+        create_test_png(&path, 16, 16);
+        // 16 * 16 * 3 bits is 96 bytes, less the length header
+        let msg = vec![7u8; 120];
+        let err = hide_with(&path, &msg, &dir.path().join("one.png"), &HideOptions::default()).unwrap_err();
+        assert!(matches!(err, StegError::CapacityExceeded { .. }));
+        hide_with(&path, &msg, &dir.path().join("two.png"), &HideOptions::default().bits(2)).unwrap();
+        assert_eq!(find_with(&dir.path().join("two.png"), &FindOptions::default()).unwrap().0, msg);
+    }
+
+    #[test]
+    fn alpha_adds_a_channel_for_opaque_and_transparent_covers() {
+        let dir = tempdir().unwrap();
+        for alpha in [255, 0] {
+            let path = dir.path().join(format!("rgba{}.png", alpha));
+            let out = dir.path().join(format!("rgba{}_out.png", alpha));
+            image::RgbaImage::from_fn(20, 20, |x, y| image::Rgba([x as u8 * 9, y as u8 * 9, 77, alpha])).save(&path).unwrap();
+            let opts = HideOptions::default().use_alpha(true);
+            // four channels a pixel, past the 16 slots of the layout header
+            let cap = capacity(&path, &opts).unwrap();
+            assert_eq!(cap, (20 * 20 * 4 - 16) / 8 - 4);
+
+            let msg = vec![0x5A; cap];
+            hide_with(&path, &msg, &out, &opts).unwrap();
+            assert_eq!(find_with(&out, &FindOptions::default()).unwrap().0, msg, "alpha {}", alpha);
+            let (before, after) = (image::open(&path).unwrap().to_rgba8(), image::open(&out).unwrap().to_rgba8());
+            assert!(before.pixels().zip(after.pixels()).all(|(p, q)| (0..4).all(|c| p[c] & !1 == q[c] & !1)));
+            assert!(before.pixels().zip(after.pixels()).any(|(p, q)| p[3] != q[3]));
+        }
+    }
+
+    #[test]
+    fn alpha_is_left_alone_unless_asked_for() {
+        let dir = tempdir().unwrap();
+        // an RGB cover has no alpha to touch, and doesn't get one
+        let (path, out) = (dir.path().join("rgb.png"), dir.path().join("out.png"));
+        create_test_png(&path, 16, 16);
+        for opts in [HideOptions::default(), HideOptions::default().bits(2), HideOptions::default().key("k")] {
+            let cap = capacity(&path, &opts).unwrap();
+            hide_with(&path, vec![0xFF; cap], &out, &opts).unwrap();
+            assert_eq!(image::open(&out).unwrap().color(), image::ColorType::Rgb8);
+        }
+        let err = hide_with(&path, b"x", &out, &HideOptions::default().use_alpha(true)).unwrap_err();
+        assert!(err.to_string().contains("no alpha channel"));
+        let err = hide_with(&path, b"x", &dir.path().join("out.ppm"), &HideOptions::default().use_alpha(true)).unwrap_err();
+        assert!(err.to_string().contains("keeps it"));
+    }
+
+    #[test]
+    fn covers_keep_their_color_type() {
+        use image::{DynamicImage, GrayAlphaImage, GrayImage, LumaA, RgbaImage};
+
+        let dir = tempdir().unwrap();
+        let (path, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+        let rgb = image::RgbImage::from_fn(24, 24, |x, y| image::Rgb([x as u8 * 10, y as u8 * 10, 90]));
+        let covers = [
+            DynamicImage::ImageLuma8(GrayImage::from_fn(24, 24, |x, y| image::Luma([(x * 10 + y) as u8]))),
+            DynamicImage::ImageLumaA8(GrayAlphaImage::from_fn(24, 24, |x, y| LumaA([(x * 10 + y) as u8, 200]))),
+            DynamicImage::ImageRgb8(rgb.clone()),
+            DynamicImage::ImageRgba8(RgbaImage::from_fn(24, 24, |x, y| image::Rgba([x as u8 * 10, y as u8 * 10, 90, 200]))),
+        ];
+        for cover in covers {
+            cover.save(&path).unwrap();
+            let mut runs = vec![HideOptions::default(), HideOptions::default().bits(3).key("k")];
+            if cover.color().has_alpha() {
+                runs.push(HideOptions::default().bits(2).use_alpha(true));
+            }
+            for opts in runs {
+                let cap = capacity(&path, &opts).unwrap();
+                let msg: Vec<u8> = (0..cap).map(|i| (i * 7) as u8).collect();
+                hide_with(&path, msg.clone(), &out, &opts).unwrap();
+                assert_eq!(image::open(&out).unwrap().color(), cover.color());
+                let find = FindOptions { lsb: LsbOptions { key: opts.lsb.key.clone(), ..LsbOptions::default() }, ..FindOptions::default() };
+                assert_eq!(find_with(&out, &find).unwrap().0, msg, "{:?}", cover.color());
+            }
+        }
+
+        // one slot a pixel for gray, three for RGB
+        DynamicImage::ImageLuma8(GrayImage::new(24, 24)).save(&path).unwrap();
+        let gray = capacity(&path, &HideOptions::default()).unwrap();
+        DynamicImage::ImageRgb8(rgb).save(&path).unwrap();
+        assert_eq!((capacity(&path, &HideOptions::default()).unwrap(), gray), (24 * 24 * 3 / 8 - 4, 24 * 24 / 8 - 4));
+        DynamicImage::ImageLuma8(GrayImage::new(24, 24)).save(&path).unwrap();
+        let err = hide_with(&path, b"x", &out, &HideOptions::default().channels(vec![1])).unwrap_err();
+        assert!(err.to_string().contains("grayscale"));
+    }
+
+    #[test]
+    fn the_channel_mask_is_recorded_and_other_channels_see_nothing() {
+        let dir = tempdir().unwrap();
+        let (path, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+        create_test_png(&path, 32, 32);
+        // a third of the room, on a third of the channels
+        let blue = HideOptions::default().channels([2]);
+        let cap = capacity(&path, &blue).unwrap();
+        assert_eq!(cap, (32 * 32 - 16) / 8 - 4);
+        let msg: Vec<u8> = (0..cap).map(|i| (i * 5) as u8).collect();
+        hide_with(&path, msg.clone(), &out, &blue).unwrap();
+
+        let (before, after) = (image::open(&path).unwrap().to_rgb8(), image::open(&out).unwrap().to_rgb8());
+        assert!(before.pixels().zip(after.pixels()).all(|(a, b)| a[0] == b[0] && a[1] == b[1]));
+        // find reads the mask from the carrier, one asked for red only looks at red and finds nothing
+        assert_eq!(find_with(&out, &FindOptions::default()).unwrap().0, msg);
+        assert_eq!(recorded_layout(&image::open(&out).unwrap(), &LsbOptions::default()).channels, [2]);
+        let red = FindOptions { lsb: LsbOptions { channels: vec![0], ..LsbOptions::default() }, ..FindOptions::default() };
+        assert!(find_with(&out, &red).is_err());
+
+        // and with more bits: blue's two low bits, red and green untouched
+        let deep = HideOptions::default().channels([2]).bits(2);
+        hide_with(&path, msg.clone(), &out, &deep).unwrap();
+        let after = image::open(&out).unwrap().to_rgb8();
+        assert!(before.pixels().zip(after.pixels()).all(|(a, b)| a[0] == b[0] && a[1] == b[1] && a[2] >> 2 == b[2] >> 2));
+        let recorded = recorded_layout(&image::open(&out).unwrap(), &LsbOptions::default());
+        assert_eq!((recorded.channels, recorded.bits), (vec![2], 2));
+        assert_eq!(find_with(&out, &FindOptions::default()).unwrap().0, msg);
+    }
+
+    #[test]
+    fn traversals_round_trip_and_find_reads_them_from_the_header() {
+        let dir = tempdir().unwrap();
+        let (path, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+        create_test_png(&path, 37, 23);
+        let region = Region::parse("3,2,29,17").unwrap();
+        for traversal in [Traversal::Column, Traversal::Hilbert] {
+            for opts in [
+                HideOptions::default().traversal(traversal),
+                HideOptions::default().traversal(traversal).bits(2).channels([0, 2]),
+                HideOptions::default().traversal(traversal).region(region).key("k"),
+            ] {
+                let cap = capacity(&path, &opts).unwrap();
+                let msg: Vec<u8> = (0..cap).map(|i| (i * 11) as u8).collect();
+                hide_with(&path, msg.clone(), &out, &opts).unwrap();
+                let find = FindOptions { lsb: LsbOptions { region: opts.lsb.region, key: opts.lsb.key.clone(), ..LsbOptions::default() }, ..FindOptions::default() };
+                assert_eq!(find_with(&out, &find).unwrap().0, msg, "{:?}", opts.lsb);
+                assert_eq!(recorded_layout(&image::open(&out).unwrap(), &find.lsb).traversal, traversal);
+            }
+        }
+    }
+
+    #[test]
+    fn a_small_payload_stays_out_of_the_top_rows_unless_walked_by_row() {
+        let dir = tempdir().unwrap();
+        let (path, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+        create_test_png(&path, 64, 64);
+        let cover = image::open(&path).unwrap().to_rgb8();
+        // the rows and columns the payload touched
+        let spread = |traversal| {
+            hide_with(&path, vec![0x5A; 24], &out, &HideOptions::default().traversal(traversal)).unwrap();
+            let after = image::open(&out).unwrap().to_rgb8();
+            let touched: Vec<(u32, u32)> = cover.enumerate_pixels().filter(|&(x, y, p)| after.get_pixel(x, y) != p).map(|(x, y, _)| (x, y)).collect();
+            (touched.iter().map(|t| t.0).max().unwrap(), touched.iter().map(|t| t.1).max().unwrap())
+        };
+        // 16 header slots and 28 bytes with the length are 240 slots, 80 pixels
+        assert_eq!(spread(Traversal::Row).1, 1);
+        assert_eq!(spread(Traversal::Column).0, 1);
+        let (x, y) = spread(Traversal::Hilbert);
+        assert!(x < 16 && y < 16 && x.max(y) > 4, "{} {}", x, y);
+    }
+
+    // flat on the left, noise on the right
+    fn half_textured(path: &Path) {
+        let mut rng = ChaCha20Rng::seed_from_u64(9);
+        image::RgbImage::from_fn(48, 32, |x, _| match x < 24 {
+            true => image::Rgb([120, 160, 200]),
+            false => image::Rgb([0, 0, 0].map(|_: u8| rng.gen_range(0..=255))),
+        })
+        .save(path)
+        .unwrap();
+    }
+
+    #[test]
+    fn texture_order_survives_its_own_changes() {
+        let dir = tempdir().unwrap();
+        let (path, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+        half_textured(&path);
+        let cover = image::open(&path).unwrap().to_rgb8();
+        for opts in [
+            HideOptions::default().traversal(Traversal::Texture),
+            HideOptions::default().traversal(Traversal::Texture).bits(4).channels([1, 2]),
+            HideOptions::default().traversal(Traversal::Texture).threshold(60).key("k"),
+        ] {
+            let cap = capacity(&path, &opts).unwrap();
+            let msg: Vec<u8> = (0..cap).map(|i| (i * 29) as u8).collect();
+            hide_with(&path, msg.clone(), &out, &opts).unwrap();
+            // the low bits of a full carrier are all the payload's, and the ranking is read from what's above them
+            let after = image::open(&out).unwrap().to_rgb8();
+            assert!(cover.pixels().zip(after.pixels()).filter(|(a, b)| a != b).count() > cap);
+            let find = FindOptions { lsb: LsbOptions { key: opts.lsb.key.clone(), ..LsbOptions::default() }, ..FindOptions::default() };
+            assert_eq!(find_with(&out, &find).unwrap().0, msg, "{:?}", opts.lsb);
+            let recorded = recorded_layout(&image::open(&out).unwrap(), &find.lsb);
+            assert_eq!((recorded.traversal, recorded.threshold), (Traversal::Texture, opts.lsb.threshold));
+        }
+        let matching = HideOptions::default().traversal(Traversal::Texture).embedding(Embedding::Match);
+        assert!(hide_with(&path, b"x", &out, &matching).unwrap_err().to_string().contains("texture"));
+    }
+
+    #[test]
+    fn texture_fills_the_textured_half_first_and_the_threshold_leaves_the_flat_one() {
+        let dir = tempdir().unwrap();
+        let (path, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+        half_textured(&path);
+        let cover = image::open(&path).unwrap().to_rgb8();
+        let texture = HideOptions::default().traversal(Traversal::Texture);
+        hide_with(&path, vec![0xA5; 100], &out, &texture).unwrap();
+        let after = image::open(&out).unwrap().to_rgb8();
+        // the flat half's inside never sees a change, its edge next to the noise is textured too
+        assert!(cover.enumerate_pixels().all(|(x, y, p)| x > 21 || after.get_pixel(x, y) == p));
+
+        // every pixel at 0, the noisy half and the seam at a threshold the flat half is under
+        let all = capacity(&path, &texture).unwrap();
+        let textured = capacity(&path, &texture.clone().threshold(20)).unwrap();
+        assert_eq!(all, (48 * 32 * 3 - 24) / 8 - 4);
+        assert!(textured < all * 6 / 10 && textured > all * 4 / 10, "{} of {}", textured, all);
+        assert!(hide_with(&path, vec![0; textured + 1], &out, &texture.threshold(20)).is_err());
+    }
+
+    #[test]
+    fn sixteen_bit_covers_keep_their_depth() {
+        use image::{DynamicImage, ImageBuffer, Luma, Rgb, Rgba};
+
+        let dir = tempdir().unwrap();
+        let (path, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+        let covers = [
+            DynamicImage::ImageRgb16(ImageBuffer::from_fn(24, 24, |x, y| Rgb([x as u16 * 2700, y as u16 * 2700, 0xFFFF]))),
+            DynamicImage::ImageRgba16(ImageBuffer::from_fn(24, 24, |x, y| Rgba([x as u16 * 2700, 0, y as u16 * 2700, 0xABCD]))),
+            DynamicImage::ImageLuma16(ImageBuffer::from_fn(24, 24, |x, y| Luma([(x * 24 + y) as u16 * 113]))),
+        ];
+        for cover in covers {
+            cover.save(&path).unwrap();
+            let mut runs = vec![HideOptions::default(), HideOptions::default().bits(4).key("k"), HideOptions::default().embedding(Embedding::Match).seed(3)];
+            if cover.color().has_alpha() {
+                runs.push(HideOptions::default().use_alpha(true));
+            }
+            for opts in runs {
+                // the same slots a pixel as at 8 bits, so the same room
+                let cap = capacity(&path, &opts).unwrap();
+                let msg: Vec<u8> = (0..cap).map(|i| (i * 13) as u8).collect();
+                hide_with(&path, msg.clone(), &out, &opts).unwrap();
+                let stego = image::open(&out).unwrap();
+                assert_eq!(stego.color(), cover.color());
+                let find = FindOptions { lsb: LsbOptions { key: opts.lsb.key.clone(), ..LsbOptions::default() }, ..FindOptions::default() };
+                assert_eq!(find_with(&out, &find).unwrap().0, msg, "{:?}", cover.color());
+                // changes stay in the low bits of each 16-bit sample, 0xFFFF included
+                let (a, b) = (cover.to_rgba16(), stego.to_rgba16());
+                assert!(a.iter().zip(b.iter()).all(|(&a, &b)| a.abs_diff(b) < 1 << opts.lsb.bits));
+            }
+        }
+
+        // an output that has no 16-bit samples gets 8, with the bits laid into those
+        let bmp = dir.path().join("out.bmp");
+        DynamicImage::ImageRgb16(ImageBuffer::from_fn(24, 24, |x, y| Rgb([x as u16 * 2700, y as u16 * 2700, 0xFFFF]))).save(&path).unwrap();
+        hide_with(&path, b"eight", &bmp, &HideOptions::default()).unwrap();
+        assert_eq!(image::open(&bmp).unwrap().color(), image::ColorType::Rgb8);
+        assert_eq!(find_bytes(&bmp).unwrap(), b"eight");
+    }
+
+    #[test]
+    fn matching_keeps_the_pairs_chi_square_looks_for_uneven() {
+        use crate::steg_algorithms::scan::chi_square_embedding;
+        use rand::SeedableRng;
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("cover.png");
+        // multiples of 4 three times as common as the even values between, none odd: a histogram whose
+        // pairs replacement evens out and ±1 doesn't
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(6);
+        image::RgbImage::from_fn(96, 96, |_, _| {
+            image::Rgb([0, 0, 0].map(|_: u8| rng.gen_range(2..60u8) * 4 + if rng.gen_ratio(1, 4) { 2 } else { 0 }))
+        }).save(&path).unwrap();
+        let msg: Vec<u8> = (0..capacity(&path, &HideOptions::default()).unwrap()).map(|_| rng.r#gen()).collect();
+
+        let chi = |opts: &HideOptions, name: &str| {
+            let out = dir.path().join(name);
+            hide_with(&path, &msg, &out, opts).unwrap();
+            // find doesn't need to know which it was
+            assert_eq!(find_with(&out, &FindOptions::default()).unwrap().0, msg);
+            let img = image::open(&out).unwrap().to_rgb8();
+            (chi_square_embedding(img.as_raw().iter().map(|&v| v as i64)), std::fs::read(&out).unwrap())
+        };
+        let (replaced, _) = chi(&HideOptions::default(), "replaced.png");
+        let (matched, first) = chi(&HideOptions::default().embedding(Embedding::Match).seed(1), "matched.png");
+        assert!(replaced > 0.95, "replacement p = {}", replaced);
+        assert!(matched < 0.01, "matching p = {}", matched);
+
+        // the same seed makes the same picture, another one another
+        assert_eq!(chi(&HideOptions::default().embedding(Embedding::Match).seed(1), "again.png").1, first);
+        assert_ne!(chi(&HideOptions::default().embedding(Embedding::Match).seed(2), "other.png").1, first);
+        assert!(hide_with(&path, b"x", &dir.path().join("deep.png"), &HideOptions::default().bits(2).embedding(Embedding::Match)).is_err());
+    }
+
+    #[test]
+    fn matching_stays_within_0_and_255() {
+        let dir = tempdir().unwrap();
+        let (path, out) = (dir.path().join("extremes.png"), dir.path().join("out.png"));
+        image::RgbImage::from_fn(16, 16, |x, _| image::Rgb(if x % 2 == 0 { [0; 3] } else { [255; 3] })).save(&path).unwrap();
+        hide_with(&path, vec![0x96; 40], &out, &HideOptions::default().embedding(Embedding::Match)).unwrap();
+        let (before, after) = (image::open(&path).unwrap().to_rgb8(), image::open(&out).unwrap().to_rgb8());
+        for (p, q) in before.pixels().zip(after.pixels()).flat_map(|(p, q)| p.0.into_iter().zip(q.0)) {
+            assert!(p.abs_diff(q) <= 1);
+        }
+        assert_eq!(find_with(&out, &FindOptions::default()).unwrap().0, vec![0x96; 40]);
+    }
+
+    #[test]
+    fn bits_go_into_each_channel_from_bit_0_up() {
+        let dir = tempdir().unwrap();
+        let (path, out) = (dir.path().join("black.png"), dir.path().join("out.png"));
+        image::RgbImage::new(8, 8).save(&path).unwrap();
+        hide_with(&path, [0xC3], &out, &HideOptions::default().bits(2)).unwrap();
+        let px: Vec<[u8; 3]> = image::open(&out).unwrap().to_rgb8().pixels().map(|p| p.0).collect();
+        // six slots a pixel, bit 0 of a channel takes the first of two: the header 'd' (0110_0100) and
+        // 2 (0000_0010) fill the first 16
+        assert_eq!(px[..3], [[0b10, 0b01, 0b10], [0, 0, 0], [0, 0b01, 0]]);
+        // then the length, 1 as 32 bits, ending at slot 47, and the byte 1100_0011
+        assert_eq!(px[7], [0, 0, 0b10]);
+        assert_eq!(px[8], [0b11, 0, 0]);
+        assert_eq!(px[9][0], 0b11);
+    }
+
+    #[test]
+    fn hide_fits_exactly_what_capacity_says() {
+        let dir = tempdir().unwrap();
+        let (path, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+        image::RgbaImage::from_fn(37, 23, |x, y| image::Rgba([(x * 7) as u8, (y * 11) as u8, (x ^ y) as u8, 255])).save(&path).unwrap();
+        let carrier = std::fs::read(&path).unwrap();
+        let runs = [
+            HideOptions::default(),
+            HideOptions::default().bits(3).channels([0, 2]),
+            HideOptions::default().use_alpha(true).stride(3).offset(100),
+            HideOptions::default().key("k").copies(3),
+            HideOptions::default().traversal(Traversal::Hilbert).bits(2),
+            HideOptions::default().traversal(Traversal::Texture).threshold(8),
+        ];
+        for opts in runs {
+            let cap = capacity(&path, &opts).unwrap();
+            assert_eq!(capacity_bytes(&carrier, &opts).unwrap(), cap, "{:?}", opts.lsb);
+            hide_with(&path, vec![0xA5; cap], &out, &opts).unwrap();
+            let err = hide_with(&path, vec![0xA5; cap + 1], &out, &opts).unwrap_err();
+            assert!(matches!(err, StegError::CapacityExceeded { .. }), "{:?}: {}", opts.lsb, err);
+            assert!(hide_bytes_with(&carrier, &vec![0xA5; cap], &opts).is_ok());
+            assert!(hide_bytes_with(&carrier, &vec![0xA5; cap + 1], &opts).is_err());
+        }
+        assert!(capacity(&path, &HideOptions::default().offset(37 * 23 * 3)).unwrap_err().to_string().contains("past the last"));
+        assert!(capacity(&dir.path().join("missing.png"), &HideOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_capacity_is_exact() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("cap.png");
+        create_test_png(&path, 40, 30);
+
+        let cap = capacity(&path, &HideOptions::default().stride(3)).unwrap();
+        assert_eq!(cap, (40 * 30 * 3usize).div_ceil(3) / 8 - 4);
+        assert!(hide_sparse(&path, vec![1u8; cap], &dir.path().join("ok.png"), 3).is_ok());
+        assert!(hide_sparse(&path, vec![1u8; cap + 1], &dir.path().join("no.png"), 3).is_err());
+    }
+
+    #[test]
+    fn test_sparse_capacity_shrinks_with_stride() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("tiny.png");
+        // 32x32 RGB = 3072 slots -> 380 bytes dense, ~92 bytes at stride 4
+        create_test_png(&path, 32, 32);
+        let msg = vec![0x5Au8; 200];
+        assert!(hide_sparse(&path, &msg, &dir.path().join("a.png"), 1).is_ok());
+        assert!(hide_sparse(&path, &msg, &dir.path().join("b.png"), 4).is_err());
+    }
+
+    #[test]
+    fn test_noise_header_is_rejected() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("white.png");
+
+        // every LSB is 1, so the length header claims 0xFFFFFFFF bytes
+        let (width, height) = (64u32, 64u32);
+        let file = File::create(&path).unwrap();
+        let mut encoder = Encoder::new(file, width, height);
+        encoder.set_color(ColorType::Rgb);
+        encoder.set_depth(BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&vec![0xFF; (width * height * 3) as usize]).unwrap();
+        writer.finish().unwrap();
+
+        assert!(matches!(find(&path), Err(StegError::NoPayloadFound)), "noise should not decode");
     }
 
     #[test]
@@ -212,6 +2057,45 @@ mod tests {
         let result2 = find(bogus);
         assert!(result2.is_err());
     }
+
+    #[test]
+    fn bytes_round_trip_without_touching_disk() {
+        let img = image::RgbImage::from_fn(64, 64, |x, y| image::Rgb([(x * 4) as u8, (y * 4) as u8, 128]));
+        let mut carrier = Cursor::new(Vec::new());
+        img.write_to(&mut carrier, ImageFormat::Png).unwrap();
+        let opts = HideOptions::default().key("k").copies(3);
+
+        let stego = hide_bytes_with(carrier.get_ref(), b"uploaded", &opts).unwrap();
+        assert_eq!(image::guess_format(&stego).unwrap(), ImageFormat::Png);
+        assert_eq!(find_bytes_with(&stego, &opts.to_find()).unwrap(), b"uploaded");
+        assert!(matches!(hide_bytes_with(carrier.get_ref(), &[0; 4096], &opts.clone().copies(1)), Err(StegError::CapacityExceeded { .. })));
+        assert!(hide_bytes_with(b"not an image", b"x", &opts).is_err());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn the_signatures_before_hide_options_still_work() {
+        let mut carrier = Cursor::new(Vec::new());
+        image::RgbImage::from_fn(32, 32, |x, y| image::Rgb([(x * 8) as u8, (y * 8) as u8, 64])).write_to(&mut carrier, ImageFormat::Png).unwrap();
+        let opts = LsbOptions { stride: Some(2), ..LsbOptions::default() };
+        let stego = hide_bytes(carrier.get_ref(), b"old", &opts, 1).unwrap();
+        assert_eq!(find_bytes_with(&stego, &FindOptions::default().stride(2)).unwrap(), b"old");
+    }
+
+    #[test]
+    fn streams_can_change_the_format() {
+        let img = image::RgbImage::from_fn(64, 64, |x, y| image::Rgb([(x * 4) as u8, (y * 4) as u8, 128]));
+        let mut carrier = Cursor::new(Vec::new());
+        img.write_to(&mut carrier, ImageFormat::Png).unwrap();
+        carrier.set_position(0);
+
+        let opts = HideOptions::default().stride(1);
+        let mut out = Cursor::new(Vec::new());
+        hide_stream_with(carrier, &mut out, ImageFormat::Tiff, b"as a tiff", &opts).unwrap();
+        assert_eq!(image::guess_format(out.get_ref()).unwrap(), ImageFormat::Tiff);
+        out.set_position(0);
+        assert_eq!(find_stream_with(out, &opts.to_find()).unwrap(), b"as a tiff");
+    }
 }
 
 
@@ -1 +1,14 @@
-pub mod lsb;
\ No newline at end of file
+#[cfg(feature = "picture")]
+pub mod lineshift;
+pub mod lsb;
+#[cfg(feature = "picture")]
+pub mod overlay;
+pub mod png_chunks;
+#[cfg(feature = "picture")]
+pub mod prenoise;
+#[cfg(feature = "picture")]
+pub mod simulate;
+#[cfg(feature = "picture")]
+pub mod transcode;
+#[cfg(feature = "picture")]
+pub mod tune;
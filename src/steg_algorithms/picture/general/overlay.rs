@@ -0,0 +1,649 @@
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use image::{GrayImage, ImageFormat, ImageReader, RgbaImage};
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use crate::steg_algorithms::atomic;
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::{fec, redundancy};
+
+// Low-amplitude overlay watermark, for tracing leaks through screenshots, prints and re-encodes.
+// The image is split into a GRID x GRID array of cells (like QR modules, but sized to the image).
+// Each data cell carries one chip: its luminance is nudged by +-strength in a 2x2 quadrant pattern
+// (top-left/bottom-right up, the other two down, or the reverse). The pattern is zero-mean, so smooth
+// image content mostly cancels out when the detector correlates against it.
+// The 64-byte frame (16-bit length + message) is Reed-Solomon coded with PARITY check bytes, every
+// coded bit is repeated REPEAT times, shuffled across the whole image, and the detector sums the
+// correlations (soft majority vote) before RS fixes the bytes the vote still got wrong.
+// The MARK x MARK cell blocks in the four corners hold registration marks instead of data: concentric
+// rings of +-strength, round in pixels whatever the aspect ratio. The detector first tries the capture
+// as the full, axis-aligned image (cells are proportional, so rescaling is fine). When that doesn't
+// decode, it looks for the four rings near the corners of the capture, fits the homography that takes
+// their centres back to where they were, and decodes through it: a photo of a print or a screen, tilted
+// or shot at an angle, as long as the whole picture is in frame and fills most of it.
+
+const GRID: usize = 64;
+const MARK: usize = 12;
+const RINGS: usize = MARK / 2;
+// one cell (of the image's shorter side) per ring, inside out. Not periodic, so a mark at the wrong
+// scale doesn't half match, and close to zero-mean so the mark doesn't show as a brighter disc
+const RING_SIGN: [f64; RINGS] = [1.0, -1.0, -1.0, 1.0, -1.0, 1.0];
+const FRAME_LEN: usize = MAX_PAYLOAD + 2;
+const PARITY: usize = 24;
+const DATA_CELLS: usize = GRID * GRID - 4 * MARK * MARK;
+const CODED_BITS: usize = (FRAME_LEN + PARITY) * 8;
+const REPEAT: usize = DATA_CELLS / CODED_BITS;
+// chip signs are scrambled so an all-zero payload doesn't show up as a visible grid
+const CHIP_SEED: u64 = 0x5253_5447_4f56_4c59; // "RSTGOVLY"
+// the marks are searched for on a copy of the capture shrunk until the widest rings it looks for are
+// about this many pixels across
+const SEARCH_RING: f64 = 3.5;
+
+/// Most bytes the overlay can carry (after its 16-bit length header).
+pub const MAX_PAYLOAD: usize = 62;
+/// Each cell needs at least 4x4 pixels to hold its quadrant pattern.
+pub const MIN_SIDE: u32 = GRID as u32 * 4;
+/// Default +-luminance change per pixel. Invisible on photos, survives a JPEG q75 re-encode.
+pub const DEFAULT_STRENGTH: u8 = 4;
+
+fn is_mark(col: usize, row: usize) -> bool {
+    let edge = |c: usize| !(MARK..GRID - MARK).contains(&c);
+    edge(col) && edge(row)
+}
+
+/// Chip sign of every cell, and which coded bit it carries (None for mark cells). The bit layout is a
+/// fixed shuffle so the REPEAT copies of a bit land scattered over the image instead of lined up in one column.
+fn layout() -> (Vec<f64>, Vec<Option<usize>>) {
+    let mut rng = ChaCha20Rng::seed_from_u64(CHIP_SEED);
+    let chips = (0..GRID * GRID).map(|_| if rng.gen_bool(0.5) { 1.0 } else { -1.0 }).collect();
+    let mut bits: Vec<usize> = (0..DATA_CELLS).map(|i| i % CODED_BITS).collect();
+    bits.shuffle(&mut rng);
+    let mut bits = bits.into_iter();
+    let bit_of = (0..GRID * GRID).map(|cell| if is_mark(cell % GRID, cell / GRID) { None } else { bits.next() }).collect();
+    (chips, bit_of)
+}
+
+/// Where the four marks are centred, in cells: top-left, top-right, bottom-right, bottom-left.
+fn mark_centres() -> [(f64, f64); 4] {
+    let (near, far) = (RINGS as f64, (GRID - RINGS) as f64);
+    [(near, near), (far, near), (far, far), (near, far)]
+}
+
+/// Pixel range of cell `c` along an axis of `len` pixels.
+fn cell_span(c: usize, len: usize) -> (usize, usize) {
+    (c * len / GRID, (c + 1) * len / GRID)
+}
+
+/// Which quadrant of its cell pixel (x, y) sits in: 0 top-left, 1 top-right, 2 bottom-left, 3 bottom-right.
+fn quadrant(x: usize, y: usize, w: usize, h: usize) -> usize {
+    let (x0, x1) = cell_span(x * GRID / w, w);
+    let (y0, y1) = cell_span(y * GRID / h, h);
+    let right = x - x0 >= (x1 - x0) / 2;
+    let bottom = y - y0 >= (y1 - y0) / 2;
+    (bottom as usize) << 1 | right as usize
+}
+
+// top-left and bottom-right go up, the other two down
+const QUADRANT_SIGN: [f64; 4] = [1.0, -1.0, -1.0, 1.0];
+
+pub fn hide(path: &Path, msg: impl AsRef<[u8]>, out_path: &Path, strength: u8) -> Result<(), StegError> {
+    let msg = msg.as_ref();
+    if !path.exists() {
+        return Err(StegError::not_found(path));
+    }
+    if msg.len() > MAX_PAYLOAD {
+        return Err(format!(
+            "Message too big: the overlay holds at most {} bytes but got {} (try --compress or a shorter message)",
+            MAX_PAYLOAD,
+            msg.len()
+        ).into());
+    }
+    if strength == 0 {
+        return Err("Strength must be at least 1".into());
+    }
+
+    let ext = out_path.extension()
+        .or_else(|| path.extension())
+        .and_then(|e| e.to_str())
+        .ok_or("Invalid file extension")?;
+    let format = ImageFormat::from_extension(ext).ok_or_else(|| StegError::UnsupportedFormat { found: ext.to_string() })?;
+
+    let mut img = ImageReader::open(path)?.decode()?.to_rgba8();
+    let (w, h) = img.dimensions();
+    if w < MIN_SIDE || h < MIN_SIDE {
+        return Err(format!("Image is {}x{}, the overlay needs at least {}x{}", w, h, MIN_SIDE, MIN_SIDE).into());
+    }
+
+    // 16-bit length + message, zero filled to the full frame so every cell carries something, then RS coded
+    let mut frame = Vec::with_capacity(FRAME_LEN);
+    frame.extend_from_slice(&(msg.len() as u16).to_be_bytes());
+    frame.extend_from_slice(msg);
+    frame.resize(FRAME_LEN, 0);
+    let bits: Vec<f64> = fec::encode(&frame, PARITY)?
+        .iter()
+        .flat_map(|b| (0..8).rev().map(move |i| if (b >> i) & 1 == 1 { 1.0 } else { -1.0 }))
+        .collect();
+
+    let (chips, bit_of) = layout();
+    let (wu, hu) = (w as usize, h as usize);
+    let ring_width = wu.min(hu) as f64 / GRID as f64;
+    for (x, y, px) in img.enumerate_pixels_mut() {
+        let (x, y) = (x as usize, y as usize);
+        let cell = (y * GRID / hu) * GRID + x * GRID / wu;
+        let sign = match bit_of[cell] {
+            Some(bit) => bits[bit] * chips[cell] * QUADRANT_SIGN[quadrant(x, y, wu, hu)],
+            None => {
+                // this corner's mark, centred in its block
+                let centre = |c: usize, len: usize| (if c < MARK { RINGS } else { GRID - RINGS }) as f64 * len as f64 / GRID as f64;
+                let (mx, my) = (centre(x * GRID / wu, wu), centre(y * GRID / hu, hu));
+                let ring = ((x as f64 + 0.5 - mx).hypot(y as f64 + 0.5 - my) / ring_width) as usize;
+                RING_SIGN.get(ring).copied().unwrap_or(0.0)
+            }
+        };
+        let delta = sign as i16 * strength as i16;
+        for c in &mut px.0[..3] {
+            *c = (*c as i16 + delta).clamp(0, 255) as u8;
+        }
+    }
+
+    save(img, out_path, format)
+}
+
+fn save(img: RgbaImage, out_path: &Path, format: ImageFormat) -> Result<(), StegError> {
+    // the point of this mode is surviving lossy containers, and JPEG can't take an alpha channel
+    atomic::write_with(out_path, |file| {
+        let mut out = BufWriter::new(file);
+        if format == ImageFormat::Jpeg {
+            image::DynamicImage::ImageRgba8(img).to_rgb8().write_to(&mut out, format)?;
+        } else {
+            img.write_to(&mut out, format)?;
+        }
+        Ok(out.flush()?)
+    })
+}
+
+/// A plane-to-plane perspective map, row major, applied to (x, y, 1).
+#[derive(Clone, Copy, Debug)]
+struct Homography([f64; 9]);
+
+impl Homography {
+    fn scale(sx: f64, sy: f64) -> Self {
+        Homography([sx, 0.0, 0.0, 0.0, sy, 0.0, 0.0, 0.0, 1.0])
+    }
+
+    /// The map taking the unit square's corners (0,0), (1,0), (1,1), (0,1) to `quad`.
+    fn from_unit_square(quad: [(f64, f64); 4]) -> Self {
+        let [(x0, y0), (x1, y1), (x2, y2), (x3, y3)] = quad;
+        let (dx1, dx2, dx3) = (x1 - x2, x3 - x2, x0 - x1 + x2 - x3);
+        let (dy1, dy2, dy3) = (y1 - y2, y3 - y2, y0 - y1 + y2 - y3);
+        let den = dx1 * dy2 - dx2 * dy1;
+        let g = (dx3 * dy2 - dx2 * dy3) / den;
+        let h = (dx1 * dy3 - dx3 * dy1) / den;
+        Homography([x1 - x0 + g * x1, x3 - x0 + h * x3, x0, y1 - y0 + g * y1, y3 - y0 + h * y3, y0, g, h, 1.0])
+    }
+
+    /// The map taking each point of `from` to the matching point of `to`.
+    fn between(from: [(f64, f64); 4], to: [(f64, f64); 4]) -> Option<Self> {
+        Homography::from_unit_square(to).then(&Homography::from_unit_square(from).inverse()?)
+    }
+
+    /// `self` after `first`.
+    fn then(&self, first: &Homography) -> Option<Self> {
+        let (a, b) = (self.0, first.0);
+        let mut m = [0.0; 9];
+        for (i, v) in m.iter_mut().enumerate() {
+            let (r, c) = (i / 3, i % 3);
+            *v = (0..3).map(|k| a[r * 3 + k] * b[k * 3 + c]).sum();
+        }
+        m.iter().all(|v| v.is_finite()).then_some(Homography(m))
+    }
+
+    fn inverse(&self) -> Option<Self> {
+        let [a, b, c, d, e, f, g, h, i] = self.0;
+        let det = a * (e * i - f * h) - b * (d * i - f * g) + c * (d * h - e * g);
+        if det.abs() < 1e-12 || !det.is_finite() {
+            return None;
+        }
+        let adj = [e * i - f * h, c * h - b * i, b * f - c * e, f * g - d * i, a * i - c * g, c * d - a * f, d * h - e * g, b * g - a * h, a * e - b * d];
+        Some(Homography(adj.map(|v| v / det)))
+    }
+
+    fn apply(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let m = &self.0;
+        let z = m[6] * x + m[7] * y + m[8];
+        ((m[0] * x + m[1] * y + m[2]) / z, (m[3] * x + m[4] * y + m[5]) / z)
+    }
+}
+
+/// Recover the bytes written by `hide`.
+pub fn find_payload(path: &Path) -> Result<Vec<u8>, StegError> {
+    find_scored(path).map(|(data, _)| data)
+}
+
+/// `find_payload`, plus a confidence per byte: how one-sided the correlations behind its bits were, from
+/// 0 (they cancelled out, the bit is a guess) to 1 (every cell carrying it agreed).
+pub fn find_scored(path: &Path) -> Result<redundancy::Scored, StegError> {
+    if !path.exists() {
+        return Err(StegError::not_found(path));
+    }
+    let img = ImageReader::open(path)?.decode()?.to_rgb8();
+    let (wu, hu) = (img.width() as usize, img.height() as usize);
+    if wu < GRID * 2 || hu < GRID * 2 {
+        return Err(format!("Image is {}x{}, too small to carry an overlay", wu, hu).into());
+    }
+    let luma = GrayImage::from_fn(img.width(), img.height(), |x, y| {
+        let [r, g, b] = img.get_pixel(x, y).0;
+        image::Luma([(0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64).round() as u8])
+    });
+
+    // the capture as the whole picture, straight on: screenshots, re-encodes and rescales
+    let whole = Homography::scale(wu as f64 / GRID as f64, hu as f64 / GRID as f64);
+    if let Some(found) = decode(&luma, &whole) {
+        return Ok(found);
+    }
+    // a photo of it: find the marks and undo the perspective
+    find_marks(&luma)
+        .and_then(|centres| Homography::between(mark_centres(), centres))
+        .and_then(|grid_to_capture| decode(&luma, &grid_to_capture))
+        .ok_or(StegError::NoPayloadFound)
+}
+
+/// Read the data cells through `grid_to_capture` (grid in cells, capture in pixels). None when the
+/// votes don't RS decode: there is no overlay there, or not one lined up the way `grid_to_capture` says.
+fn decode(luma: &GrayImage, grid_to_capture: &Homography) -> Option<redundancy::Scored> {
+    let to_grid = grid_to_capture.inverse()?;
+
+    // correlate each cell's luminance with the quadrant pattern, using quadrant means so cells that
+    // split unevenly (odd sizes) don't let plain brightness leak in. TL - TR - BL + BR also cancels
+    // any linear gradient across the cell.
+    let mut sums = vec![[[0f64; 2]; 4]; GRID * GRID]; // per quadrant: luma sum, pixels
+    for (x, y, px) in luma.enumerate_pixels() {
+        let (u, v) = to_grid.apply((x as f64 + 0.5, y as f64 + 0.5));
+        if !(0.0..GRID as f64).contains(&u) || !(0.0..GRID as f64).contains(&v) {
+            continue;
+        }
+        let q = ((v.fract() >= 0.5) as usize) << 1 | (u.fract() >= 0.5) as usize;
+        let quad = &mut sums[v as usize * GRID + u as usize][q];
+        quad[0] += px.0[0] as f64;
+        quad[1] += 1.0;
+    }
+    let mut corr: Vec<f64> = sums
+        .iter()
+        .map(|cell| {
+            if cell.iter().any(|[_, n]| *n == 0.0) {
+                return 0.0;
+            }
+            cell.iter().zip(QUADRANT_SIGN).map(|([l, n], s)| s * l / n).sum()
+        })
+        .collect();
+
+    // a hard edge running through a cell swamps its chip. Clip every cell to a few times the typical
+    // magnitude so those cells only cost one vote instead of deciding the bit.
+    let mut mags: Vec<f64> = corr.iter().map(|c| c.abs()).collect();
+    mags.sort_by(f64::total_cmp);
+    let limit = mags[mags.len() / 2] * 2.0;
+    for c in &mut corr {
+        *c = c.clamp(-limit, limit);
+    }
+
+    // soft vote across the REPEAT copies of every bit
+    let (chips, bit_of) = layout();
+    let (mut votes, mut weight) = (vec![0f64; CODED_BITS], vec![0f64; CODED_BITS]);
+    for ((c, chip), bit) in corr.iter().zip(&chips).zip(&bit_of) {
+        if let Some(bit) = bit {
+            votes[*bit] += c * chip;
+            weight[*bit] += c.abs();
+        }
+    }
+    debug_assert_eq!(DATA_CELLS, CODED_BITS * REPEAT);
+
+    let coded: Vec<u8> = votes
+        .chunks(8)
+        .map(|bits| bits.iter().fold(0u8, |acc, v| (acc << 1) | (*v > 0.0) as u8))
+        .collect();
+    let (frame, _) = fec::decode(&coded, FRAME_LEN, PARITY).ok()?;
+    let len = u16::from_be_bytes([frame[0], frame[1]]) as usize;
+    if len > MAX_PAYLOAD {
+        return None;
+    }
+    // RS is systematic and one block here, so the frame's bits are the first ones voted on
+    let margins: Vec<f32> = votes.iter().zip(&weight).map(|(v, w)| if *w > 0.0 { (v.abs() / w) as f32 } else { 0.0 }).collect();
+    let confidence = redundancy::byte_confidence(&margins[..FRAME_LEN * 8]);
+    Some((frame[2..2 + len].to_vec(), confidence[2..2 + len].to_vec()))
+}
+
+/// Centres of the four marks in the capture, in pixels, in `mark_centres` order. Searched for on a
+/// shrunk copy, each in its own corner of the frame, then pinned down on the full capture. None when the
+/// capture is too small to search.
+fn find_marks(luma: &GrayImage) -> Option<[(f64, f64); 4]> {
+    let (w, h) = (luma.width() as usize, luma.height() as usize);
+    // ring width is a cell of the picture's shorter side, which can't be longer than the frame's. Anything
+    // under ~40% of the frame is out of scope, and would take a much wider search
+    let widest = w.min(h) as f64 / GRID as f64 * 1.1;
+    let factor = ((widest / SEARCH_RING).ceil() as usize).max(1);
+    let (sw, sh) = (w / factor, h / factor);
+    let full: Vec<f64> = luma.pixels().map(|p| p.0[0] as f64).collect();
+    let small: Vec<f64> = (0..sw * sh)
+        .map(|i| {
+            let (x, y) = (i % sw * factor, i / sw * factor);
+            let block = (y..y + factor).flat_map(|y| (x..x + factor).map(move |x| (x, y)));
+            block.map(|(x, y)| full[y * w + x]).sum::<f64>() / (factor * factor) as f64
+        })
+        .collect();
+    let (full, small) = (residual(&full, w, h, factor), residual(&small, sw, sh, 1));
+
+    let widest = widest / factor as f64;
+    let widths: Vec<f64> = std::iter::successors(Some(widest), |s| Some(s / 1.07)).take_while(|s| *s >= widest * 0.4 && *s >= 1.0).collect();
+    if widths.is_empty() {
+        return None;
+    }
+
+    // every offset inside the biggest mark, with its half-pixel radius bin
+    let reach = (widest * RINGS as f64).ceil() as isize;
+    let bins = (reach * 2) as usize + 1;
+    let disc: Vec<(isize, isize, usize)> = (-reach..=reach)
+        .flat_map(|dy| (-reach..=reach).map(move |dx| (dx, dy)))
+        .filter_map(|(dx, dy)| {
+            let bin = ((dx as f64).hypot(dy as f64) * 2.0) as usize;
+            (bin < bins).then_some((dx, dy, bin))
+        })
+        .collect();
+
+    // (score, x, y, ring width) of the best match for the mark centred on (cx, cy) of the shrunk copy
+    let at = |cx: usize, cy: usize, widths: &[f64]| {
+        let mut stats = vec![[0f64; 3]; bins]; // per radius bin: sum, sum of squares, pixels
+        for &(dx, dy, bin) in &disc {
+            let (x, y) = (cx as isize + dx, cy as isize + dy);
+            if x < 0 || y < 0 || x >= sw as isize || y >= sh as isize {
+                continue;
+            }
+            let v = small[y as usize * sw + x as usize];
+            let s = &mut stats[bin];
+            s[0] += v;
+            s[1] += v * v;
+            s[2] += 1.0;
+        }
+        widths
+            .iter()
+            .map(|&width| {
+                let score = ring_correlation(stats.iter().enumerate().map(|(bin, s)| ((bin as f64 + 0.5) / 2.0 / width, *s)));
+                (score, cx as f64 + 0.5, cy as f64 + 0.5, width)
+            })
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .unwrap_or((f64::MIN, 0.0, 0.0, 0.0))
+    };
+    // the best match in `corner`'s part of the frame
+    let search = |corner: usize, widths: &[f64]| {
+        let span = |len: usize, far: bool| {
+            let reach = len * 45 / 100;
+            if far { len - reach..len } else { 0..reach }
+        };
+        let xs = span(sw, corner == 1 || corner == 2);
+        span(sh, corner >= 2)
+            .flat_map(|cy| xs.clone().map(move |cx| (cx, cy)))
+            .map(|(cx, cy)| at(cx, cy, widths))
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+    };
+    let mut found = [search(0, &widths)?, search(1, &widths)?, search(2, &widths)?, search(3, &widths)?];
+    // the four rings are about the same size unless the shot is very oblique. One that isn't is most
+    // likely a bit of picture that happened to match better, so look again at sizes near the others'
+    let mut sizes = found.map(|(_, _, _, width)| width);
+    sizes.sort_by(f64::total_cmp);
+    let typical = (sizes[1] + sizes[2]) / 2.0;
+    let near: Vec<f64> = widths.iter().copied().filter(|s| (s / typical - 1.0).abs() <= 0.15).collect();
+    for (corner, best) in found.iter_mut().enumerate() {
+        if (best.3 / typical - 1.0).abs() > 0.15 {
+            *best = search(corner, &near).unwrap_or(*best);
+        }
+    }
+
+    let centres = found.map(|(_, cx, cy, width)| refine((&full, w, h), (cx * factor as f64, cy * factor as f64), width * factor as f64));
+    Some(fit((&full, w, h), centres))
+}
+
+/// Move the four mark centres (and the picture's aspect ratio, which decides how round the rings were in
+/// cells) until the rings, drawn through the homography those centres give, correlate best with the
+/// capture. Perspective squashes the marks into different ellipses, which a round template pulls off centre.
+fn fit((plane, w, h): (&[f64], usize, usize), start: [(f64, f64); 4]) -> [(f64, f64); 4] {
+    let score = |centres: [(f64, f64); 4], aspect: f64| -> f64 {
+        let Some(grid_to_capture) = Homography::between(mark_centres(), centres) else { return f64::MIN };
+        let Some(to_grid) = grid_to_capture.inverse() else { return f64::MIN };
+        // a ring is a cell of the shorter side wide, so in cells it is 1 / aspect wide along the longer one
+        let (du, dv) = if aspect >= 1.0 { (1.0 / aspect, 1.0) } else { (1.0, aspect) };
+        let mut total = 0.0;
+        for ((cx, cy), (mu, mv)) in centres.iter().zip(mark_centres()) {
+            // corners of the block the rings sit in decide which pixels to look at
+            let reach = RINGS as f64 * 1.2;
+            let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].map(|(su, sv)| grid_to_capture.apply((mu + su * reach * du, mv + sv * reach * dv)));
+            let (x0, x1) = corners.iter().fold((*cx, *cx), |(lo, hi), p| (lo.min(p.0), hi.max(p.0)));
+            let (y0, y1) = corners.iter().fold((*cy, *cy), |(lo, hi), p| (lo.min(p.1), hi.max(p.1)));
+            // a few samples across each ring are plenty
+            let step = (((x1 - x0).min(y1 - y0) / RINGS as f64 / 2.0 / 4.0) as usize).max(1);
+            let xs = (x0.max(0.0) as usize..(x1.ceil() as usize).min(w)).step_by(step);
+            let ys = (y0.max(0.0) as usize..(y1.ceil() as usize).min(h)).step_by(step);
+            total += ring_correlation(ys.flat_map(|y| xs.clone().map(move |x| (x, y))).map(|(x, y)| {
+                let (u, v) = to_grid.apply((x as f64 + 0.5, y as f64 + 0.5));
+                let value = plane[y * w + x];
+                (((u - mu) / du).hypot((v - mv) / dv), [value, value * value, 1.0])
+            }));
+        }
+        total
+    };
+
+    // the centres are about as far apart as the picture's sides, which gives a first guess at its shape
+    let side = |a: usize, b: usize| (start[a].0 - start[b].0).hypot(start[a].1 - start[b].1);
+    let mut aspect = (side(0, 1) + side(3, 2)) / (side(0, 3) + side(1, 2));
+    let mut centres = start;
+    let mut best = score(centres, aspect);
+    // pattern search again, over all nine numbers: a step each way on every centre's x and y, and the aspect
+    let mut step = side(0, 1).min(side(0, 3)) / (GRID - MARK) as f64 / 2.0;
+    let finest = step / 16.0;
+    while step > finest {
+        let mut moved = false;
+        for param in 0..9 {
+            for sign in [1.0, -1.0] {
+                let (mut next, mut next_aspect) = (centres, aspect);
+                match param {
+                    8 => next_aspect *= 1.0 + sign * step / 100.0,
+                    _ if param % 2 == 0 => next[param / 2].0 += sign * step,
+                    _ => next[param / 2].1 += sign * step,
+                }
+                let s = score(next, next_aspect);
+                if s > best {
+                    (centres, aspect, best, moved) = (next, next_aspect, s, true);
+                }
+            }
+        }
+        if !moved {
+            step /= 2.0;
+        }
+    }
+    centres
+}
+
+/// How far each pixel stands out from the (2 * radius + 1) square around it, clipped to a few steps. The
+/// picture itself is mostly smooth areas and hard edges: taking the local mean out drops the first, and
+/// the clipping stops the second outvoting a whole mark's worth of faint ring edges.
+fn residual(plane: &[f64], w: usize, h: usize, radius: usize) -> Vec<f64> {
+    let mut table = vec![0f64; (w + 1) * (h + 1)]; // summed area
+    for y in 0..h {
+        for x in 0..w {
+            table[(y + 1) * (w + 1) + x + 1] = plane[y * w + x] + table[y * (w + 1) + x + 1] + table[(y + 1) * (w + 1) + x] - table[y * (w + 1) + x];
+        }
+    }
+    (0..w * h)
+        .map(|i| {
+            let (x, y) = (i % w, i / w);
+            let (x0, x1, y0, y1) = (x.saturating_sub(radius), (x + radius + 1).min(w), y.saturating_sub(radius), (y + radius + 1).min(h));
+            let area = table[y1 * (w + 1) + x1] - table[y0 * (w + 1) + x1] - table[y1 * (w + 1) + x0] + table[y0 * (w + 1) + x0];
+            (plane[i] - area / ((x1 - x0) * (y1 - y0)) as f64).clamp(-3.0, 3.0)
+        })
+        .collect()
+}
+
+/// Pearson correlation between the pixels of a mark-sized disc and the ring pattern, from (radius in
+/// rings, [sum, sum of squares, pixels]) groups, times the square root of the pixel count: how many noise
+/// levels it stands out by, so a small disc matching by chance doesn't beat a big one. Groups past the
+/// outer ring are left out.
+fn ring_correlation(groups: impl Iterator<Item = (f64, [f64; 3])>) -> f64 {
+    let (mut n, mut sum, mut squares, mut signs, mut signed) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for (radius, [s, q, count]) in groups {
+        let Some(sign) = RING_SIGN.get(radius as usize) else { continue };
+        n += count;
+        sum += s;
+        squares += q;
+        signs += sign * count;
+        signed += sign * s;
+    }
+    let spread = (n - signs * signs / n) * (squares - sum * sum / n);
+    if n == 0.0 || spread <= 0.0 { 0.0 } else { (signed - signs * sum / n) / spread.sqrt() * n.sqrt() }
+}
+
+/// Move a mark found on the shrunk copy to where it correlates best on the full capture, at sub-pixel steps.
+fn refine((plane, w, h): (&[f64], usize, usize), start: (f64, f64), width: f64) -> (f64, f64) {
+    let score = |(cx, cy): (f64, f64), width: f64| {
+        let reach = width * RINGS as f64;
+        let xs = (cx - reach).floor().max(0.0) as usize..((cx + reach).ceil() as usize).min(w);
+        let ys = (cy - reach).floor().max(0.0) as usize..((cy + reach).ceil() as usize).min(h);
+        let pixels = ys.flat_map(|y| xs.clone().map(move |x| (x, y)));
+        ring_correlation(pixels.map(|(x, y)| {
+            let v = plane[y * w + x];
+            ((x as f64 + 0.5 - cx).hypot(y as f64 + 0.5 - cy) / width, [v, v * v, 1.0])
+        }))
+    };
+
+    // pattern search: try a step each way in x, y and ring width, halving the steps when nothing helps
+    let (mut at, mut width) = (start, width);
+    let mut best = score(at, width);
+    let mut step = width / 2.0;
+    while step > width / 32.0 {
+        let moves = [(step, 0.0, 1.0), (-step, 0.0, 1.0), (0.0, step, 1.0), (0.0, -step, 1.0), (0.0, 0.0, 1.0 + step / width / 4.0), (0.0, 0.0, 1.0 - step / width / 4.0)];
+        let better = moves.iter().find_map(|(dx, dy, grow)| {
+            let next = ((at.0 + dx, at.1 + dy), width * grow);
+            let s = score(next.0, next.1);
+            (s > best).then_some((next, s))
+        });
+        match better {
+            Some(((next, w), s)) => {
+                (at, width, best) = (next, w, s);
+            }
+            None => step /= 2.0,
+        }
+    }
+    at
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::imageops::FilterType;
+    use image::{DynamicImage, Rgb, RgbImage};
+    use tempfile::tempdir;
+
+    // something photo-ish: gradients plus a bit of grain and a few hard edges
+    fn make_cover(path: &Path, w: u32, h: u32) {
+        let mut rng = ChaCha20Rng::seed_from_u64(1);
+        let img = RgbImage::from_fn(w, h, |x, y| {
+            let edge = if (x / 97 + y / 61) % 2 == 0 { 40 } else { 0 };
+            let n: i32 = rng.gen_range(-6..=6);
+            let v = |base: u32| (base as i32 + edge + n).clamp(0, 255) as u8;
+            Rgb([v(x * 200 / w + 20), v(y * 180 / h + 30), v((x + y) * 100 / (w + h) + 60)])
+        });
+        img.save(path).unwrap();
+    }
+
+    #[test]
+    fn roundtrip_lossless() {
+        let dir = tempdir().unwrap();
+        let (src, out) = (dir.path().join("c.png"), dir.path().join("o.png"));
+        make_cover(&src, 320, 300);
+
+        hide(&src, b"leaked by: alice", &out, DEFAULT_STRENGTH).unwrap();
+        assert_eq!(find_payload(&out).unwrap(), b"leaked by: alice");
+    }
+
+    #[test]
+    fn survives_jpeg_and_rescale() {
+        let dir = tempdir().unwrap();
+        let (src, out) = (dir.path().join("c.png"), dir.path().join("o.jpg"));
+        make_cover(&src, 640, 480);
+
+        // q75 jpeg, then shrink like a screenshot at a different zoom level
+        hide(&src, b"copy #0042", &out, DEFAULT_STRENGTH).unwrap();
+        assert_eq!(find_payload(&out).unwrap(), b"copy #0042");
+
+        let scaled = dir.path().join("scaled.png");
+        let img = ImageReader::open(&out).unwrap().decode().unwrap();
+        DynamicImage::ImageRgba8(image::imageops::resize(&img, 452, 339, FilterType::Triangle)).save(&scaled).unwrap();
+        assert_eq!(find_payload(&scaled).unwrap(), b"copy #0042");
+
+        // the lossy copies decode the same bytes, less convincingly than the original does
+        let mean = |path: &Path| {
+            let (_, confidence) = find_scored(path).unwrap();
+            confidence.iter().sum::<f32>() / confidence.len() as f32
+        };
+        let lossless = dir.path().join("o.png");
+        hide(&src, b"copy #0042", &lossless, DEFAULT_STRENGTH).unwrap();
+        assert!(mean(&scaled) > 0.0 && mean(&scaled) < mean(&lossless));
+    }
+
+    // photograph `src` at an angle: its corners land on `quad` in a w x h frame, over a textured background
+    fn capture(src: &Path, dest: &Path, quad: [(f64, f64); 4], (w, h): (u32, u32)) {
+        let img = ImageReader::open(src).unwrap().decode().unwrap().to_rgb8();
+        let (iw, ih) = (img.width() as f64, img.height() as f64);
+        let to_picture = Homography::between(quad, [(0.0, 0.0), (iw, 0.0), (iw, ih), (0.0, ih)]).unwrap();
+        let shot = RgbImage::from_fn(w, h, |x, y| {
+            let (u, v) = to_picture.apply((x as f64 + 0.5, y as f64 + 0.5));
+            let (u, v) = (u - 0.5, v - 0.5);
+            if u < 0.0 || v < 0.0 || u >= iw - 1.0 || v >= ih - 1.0 {
+                let t = ((x / 7 + y / 5) % 3 * 25 + 90) as u8;
+                return Rgb([t, t, t + 10]);
+            }
+            // bilinear, like a camera's blur would smear it anyway
+            let (x0, y0, fx, fy) = (u as u32, v as u32, u.fract(), v.fract());
+            let at = |dx: u32, dy: u32, c: usize| img.get_pixel(x0 + dx, y0 + dy).0[c] as f64;
+            Rgb([0, 1, 2].map(|c| {
+                let top = at(0, 0, c) * (1.0 - fx) + at(1, 0, c) * fx;
+                let bottom = at(0, 1, c) * (1.0 - fx) + at(1, 1, c) * fx;
+                (top * (1.0 - fy) + bottom * fy).round() as u8
+            }))
+        });
+        shot.save(dest).unwrap();
+    }
+
+    #[test]
+    fn rectifies_a_photo_taken_at_an_angle() {
+        let dir = tempdir().unwrap();
+        let (src, out) = (dir.path().join("c.png"), dir.path().join("o.png"));
+        make_cover(&src, 640, 480);
+        hide(&src, b"printed for: bob", &out, DEFAULT_STRENGTH).unwrap();
+
+        // tilted a few degrees, nearer at the bottom than the top, on a desk, saved as a q90 jpeg
+        let shot = dir.path().join("shot.jpg");
+        capture(&out, &shot, [(58.0, 41.0), (672.0, 70.0), (688.0, 571.0), (31.0, 540.0)], (720, 600));
+
+        assert_eq!(find_payload(&shot).unwrap(), b"printed for: bob");
+
+        // a print lying flat, shot from low down: the far edge much shorter than the near one
+        capture(&out, &shot, [(100.0, 40.0), (620.0, 40.0), (700.0, 560.0), (20.0, 560.0)], (720, 600));
+        assert_eq!(find_payload(&shot).unwrap(), b"printed for: bob");
+    }
+
+    #[test]
+    fn unmarked_is_not_found() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("c.png");
+        make_cover(&src, 320, 300);
+        assert!(matches!(find_payload(&src), Err(StegError::NoPayloadFound)));
+    }
+
+    #[test]
+    fn rejects_oversized_and_tiny() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("c.png");
+        make_cover(&src, 128, 128);
+        let out = dir.path().join("o.png");
+        assert!(hide(&src, [0u8; MAX_PAYLOAD + 1], &out, DEFAULT_STRENGTH).is_err());
+        assert!(hide(&src, b"hi", &out, DEFAULT_STRENGTH).unwrap_err().to_string().contains("at least"));
+    }
+}
@@ -0,0 +1,79 @@
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::parse::{Chunk, Mode, Parsed, Walker};
+
+// PNG's chunk layout, for looking at a file's structure without decoding it: the 8-byte signature, then
+// chunks of
+//
+//   length  4 bytes, big-endian, of the data
+//   type    4 ASCII letters
+//   data    length bytes
+//   crc     CRC-32 over type and data
+//
+// IHDR first, IEND last. Whatever follows IEND isn't part of the PNG (see scan for what it may be).
+
+pub const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+/// The chunks of the PNG in `buf`, the walk's `end` being just past IEND. Lenient mode keeps chunks with
+/// a bad CRC and stops, keeping what it has, at a chunk it can't make sense of.
+pub fn chunks(buf: &[u8], mode: Mode) -> Result<Parsed<Chunk>, StegError> {
+    let mut walk = Walker::new(mode);
+    if !buf.starts_with(&SIGNATURE) {
+        walk.violation(0, "No PNG signature")?;
+    }
+    let mut pos = SIGNATURE.len();
+    while let Some(header) = buf.get(pos..pos + 8) {
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let id: [u8; 4] = [header[4], header[5], header[6], header[7]];
+        if !id.iter().all(u8::is_ascii_alphabetic) {
+            walk.violation(pos, "Chunk type isn't four letters")?;
+            return Ok(walk.finish(None));
+        }
+        let name = String::from_utf8_lossy(&id).into_owned();
+        if pos == SIGNATURE.len() && &id != b"IHDR" {
+            walk.violation(pos, format!("The first chunk is {}, not IHDR", name))?;
+        }
+        let Some(crc) = buf.get(pos + 8 + len..pos + 12 + len) else {
+            walk.violation(pos, format!("{} chunk runs past the end of the file", name))?;
+            return Ok(walk.finish(None));
+        };
+        if crc32fast::hash(&buf[pos + 4..pos + 8 + len]).to_be_bytes() != crc {
+            walk.violation(pos, format!("{} chunk has a bad CRC", name))?;
+        }
+        walk.push(Chunk { id, start: pos, end: pos + 12 + len });
+        pos += 12 + len;
+        if &id == b"IEND" {
+            return Ok(walk.finish(Some(pos)));
+        }
+    }
+    walk.violation(pos, "The file ends before IEND")?;
+    Ok(walk.finish(None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let body = [&id[..], data].concat();
+        [&(data.len() as u32).to_be_bytes()[..], &body, &crc32fast::hash(&body).to_be_bytes()].concat()
+    }
+
+    #[test]
+    fn damage_fails_strict_and_is_walked_past_leniently() {
+        let png = [&SIGNATURE[..], &chunk(b"IHDR", &[0; 13]), &chunk(b"tEXt", b"a\0b"), &chunk(b"IEND", &[])].concat();
+        let parsed = chunks(&png, Mode::Strict).unwrap();
+        assert_eq!(parsed.items.iter().map(|c| &c.id).collect::<Vec<_>>(), [b"IHDR", b"tEXt", b"IEND"]);
+        assert_eq!(parsed.end, Some(png.len()));
+
+        let mut damaged = png.clone();
+        damaged[SIGNATURE.len() + 25 + 9] ^= 1;
+        assert!(chunks(&damaged, Mode::Strict).unwrap_err().to_string().contains("tEXt chunk has a bad CRC"));
+        let parsed = chunks(&damaged, Mode::Lenient).unwrap();
+        assert_eq!((parsed.items.len(), parsed.end, parsed.problems.len()), (3, Some(png.len()), 1));
+
+        let cut = &png[..png.len() - 6];
+        assert!(chunks(cut, Mode::Strict).is_err());
+        let parsed = chunks(cut, Mode::Lenient).unwrap();
+        assert_eq!((parsed.items.len(), parsed.end), (2, None));
+    }
+}
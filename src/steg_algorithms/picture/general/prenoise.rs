@@ -0,0 +1,107 @@
+use std::path::Path;
+use image::{DynamicImage, ImageFormat};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use sha2::{Digest, Sha256};
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::picture::general::lsb as picture_lsb;
+
+// `hide --prenoise SIGMA`: add Gaussian noise (standard deviation SIGMA, in 8-bit levels) to the cover's
+// gray or RGB channels before embedding. On a smooth or synthetic cover the LSB plane is far too regular, and
+// the payload's bits stand out against it; noising the cover first gives them a noisy baseline to hide
+// in. The noise comes from a ChaCha20 stream seeded from the LSB key, or from the cover's own pixels
+// without one, so the same cover, key and sigma always give the same noisy cover. The cover keeps its
+// color type and depth, a 16-bit one getting the same noise scaled up to its range.
+
+const SEED_DOMAIN: &[u8] = b"rust-stego prenoise v1\0";
+
+fn seed(key: Option<&str>, img: &DynamicImage) -> [u8; 32] {
+    let hasher = Sha256::new().chain_update(SEED_DOMAIN);
+    match key {
+        Some(k) => hasher.chain_update([1]).chain_update(k.as_bytes()),
+        None => hasher.chain_update([0]).chain_update(img.as_bytes()),
+    }
+    .finalize()
+    .into()
+}
+
+// Box-Muller, one sample per pair of uniforms
+fn gaussian(rng: &mut impl Rng) -> f64 {
+    let u1: f64 = 1.0 - rng.r#gen::<f64>(); // (0, 1], ln(0) is -inf
+    let u2: f64 = rng.r#gen();
+    (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+}
+
+/// Add the noise to the gray or RGB channels of `img`, an 8 or 16-bit picture as lsb reads it, in place
+/// and clamped to the sample range. Alpha is left alone.
+pub fn apply(img: &mut DynamicImage, sigma: f64, key: Option<&str>) {
+    let mut rng = ChaCha20Rng::from_seed(seed(key, img));
+    picture_lsb::map_color_samples(img, |v, max| {
+        let noise = gaussian(&mut rng) * sigma * max as f64 / 255.0;
+        (v as f64 + noise).round().clamp(0.0, max as f64) as u16
+    });
+}
+
+/// Write a noised copy of the picture at `path` to `out` as PNG, which is lossless and takes every color
+/// type and depth lsb does, so the embedding reads exactly the noise that was added.
+pub fn noisy_copy(path: &Path, sigma: f64, key: Option<&str>, out: &Path) -> Result<(), StegError> {
+    let mut img = picture_lsb::decode(path)?;
+    apply(&mut img, sigma, key);
+    picture_lsb::save(&img, out, ImageFormat::Png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ColorType, ImageReader, Luma, Rgb, Rgba, RgbaImage};
+    use tempfile::tempdir;
+    use crate::steg_algorithms::picture::general::lsb;
+
+    #[test]
+    fn noise_is_reproducible_and_about_sigma() {
+        let flat = DynamicImage::ImageRgba8(RgbaImage::from_pixel(64, 64, Rgba([128, 128, 128, 200])));
+        let noised = |key| {
+            let mut img = flat.clone();
+            apply(&mut img, 2.0, key);
+            img.to_rgba8()
+        };
+        assert_eq!(noised(Some("k")), noised(Some("k")));
+        assert_ne!(noised(Some("k")), noised(Some("j")));
+        assert_eq!(noised(None), noised(None));
+
+        let img = noised(Some("k"));
+        assert!(img.pixels().all(|p| p[3] == 200), "alpha is never touched");
+        let diffs: Vec<f64> = img.pixels().flat_map(|p| p.0[..3].to_vec()).map(|c| c as f64 - 128.0).collect();
+        let sd = (diffs.iter().map(|d| d * d).sum::<f64>() / diffs.len() as f64).sqrt();
+        assert!((sd - 2.0).abs() < 0.2, "standard deviation {}", sd);
+    }
+
+    #[test]
+    fn payload_survives_on_a_noised_cover() {
+        let dir = tempdir().unwrap();
+        let (cover, noisy, out) = (dir.path().join("c.png"), dir.path().join("n.png"), dir.path().join("o.png"));
+        RgbaImage::from_fn(40, 40, |x, y| Rgba([x as u8 * 6, y as u8 * 6, 0, 255])).save(&cover).unwrap();
+        noisy_copy(&cover, 1.5, Some("k"), &noisy).unwrap();
+        lsb::hide_keyed(&noisy, b"under the noise", &out, "k").unwrap();
+        assert_eq!(lsb::find_payload_keyed(&out, "k").unwrap(), b"under the noise");
+    }
+
+    #[test]
+    fn keeps_the_cover_color_type_and_depth() {
+        let dir = tempdir().unwrap();
+        let rgb16 = image::ImageBuffer::from_fn(32, 32, |x, y| Rgb([x as u16 * 2000, y as u16 * 2000, 30000]));
+        let gray = image::GrayImage::from_fn(32, 32, |x, y| Luma([(x * 4 + y) as u8]));
+        for (name, cover, color) in [("16.png", DynamicImage::ImageRgb16(rgb16), ColorType::Rgb16), ("8.png", DynamicImage::ImageLuma8(gray), ColorType::L8)] {
+            let (path, noisy, out) = (dir.path().join(name), dir.path().join(format!("n{}", name)), dir.path().join(format!("o{}", name)));
+            cover.save(&path).unwrap();
+            noisy_copy(&path, 1.5, Some("k"), &noisy).unwrap();
+            let noised = ImageReader::open(&noisy).unwrap().decode().unwrap();
+            assert_eq!(noised.color(), color);
+            assert_ne!(noised, cover);
+
+            lsb::hide_keyed(&noisy, b"still deep", &out, "k").unwrap();
+            assert_eq!(ImageReader::open(&out).unwrap().decode().unwrap().color(), color);
+            assert_eq!(lsb::find_payload_keyed(&out, "k").unwrap(), b"still deep");
+        }
+    }
+}
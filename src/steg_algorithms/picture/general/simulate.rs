@@ -0,0 +1,261 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use image::ImageReader;
+use image::imageops::FilterType;
+use crate::steg_algorithms::payload::{self, Table};
+use crate::steg_algorithms::picture::gif::app_extension;
+use crate::steg_algorithms::picture::jpg::marker_hijacking;
+use super::{lsb, overlay, transcode};
+use crate::steg_algorithms::error::StegError;
+
+// `simulate`: push a stego picture through a chain of the things that happen to pictures out in the
+// wild (re-encodes, rescales, crops) and check after every step whether each payload found in the
+// original still comes out. A payload counts as intact when its frame is byte-identical to the
+// original's after any FEC repair, so encrypted payloads can be checked without the password.
+
+/// Identifier `hide` uses for appext unless told otherwise.
+const DEFAULT_APP_ID: &[u8; 11] = b"RSTEGANO1.0";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    /// Re-encode as JPEG at this quality.
+    Jpeg(u8),
+    /// Scale both sides by this factor.
+    Resize(f64),
+    /// Keep this fraction of each side, centered.
+    Crop(f64),
+    /// Gaussian blur with this sigma.
+    Blur(f32),
+    /// Decode and save as PNG, dropping everything but the pixels.
+    Png,
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::Jpeg(q) => write!(f, "jpeg:{}", q),
+            Op::Resize(s) => write!(f, "resize:{}", s),
+            Op::Crop(s) => write!(f, "crop:{}", s),
+            Op::Blur(s) => write!(f, "blur:{}", s),
+            Op::Png => write!(f, "png"),
+        }
+    }
+}
+
+impl Op {
+    /// Parse one step, e.g. `jpeg:85` or `resize:0.9`.
+    pub fn parse(s: &str) -> Result<Self, StegError> {
+        let (name, arg) = match s.trim().split_once(':') {
+            Some((n, a)) => (n, Some(a)),
+            None => (s.trim(), None),
+        };
+        let num = |lo: f64, hi: f64| -> Result<f64, StegError> {
+            let a = arg.ok_or_else(|| format!("'{}' needs a value, e.g. {}:{}", name, name, hi))?;
+            match a.parse::<f64>() {
+                Ok(v) if v > lo && v <= hi => Ok(v),
+                _ => Err(format!("'{}' takes a value above {} and up to {}, got '{}'", name, lo, hi, a).into()),
+            }
+        };
+        match name {
+            "jpeg" | "jpg" => Ok(Op::Jpeg(num(0.0, 100.0)?.round() as u8)),
+            "resize" | "scale" => Ok(Op::Resize(num(0.0, 4.0)?)),
+            "crop" => Ok(Op::Crop(num(0.0, 1.0)?)),
+            "blur" => Ok(Op::Blur(num(0.0, 20.0)? as f32)),
+            "png" if arg.is_none() => Ok(Op::Png),
+            _ => Err(format!("Unknown operation '{}' (use jpeg:Q, resize:F, crop:F, blur:SIGMA or png)", s.trim()).into()),
+        }
+    }
+
+    /// Apply to the picture at `src`, writing the result into `dir`. Returns the new file.
+    fn apply(&self, src: &Path, dir: &Path, step: usize) -> Result<PathBuf, StegError> {
+        if let Op::Jpeg(q) = self {
+            let out = dir.join(format!("step{}.jpg", step));
+            std::fs::write(&out, transcode::to_jpeg(src, *q)?)?;
+            return Ok(out);
+        }
+        let img = ImageReader::open(src)?.decode()?;
+        let (w, h) = (img.width(), img.height());
+        let side = |len: u32, f: f64| ((len as f64 * f).round() as u32).max(1);
+        let img = match self {
+            Op::Resize(f) => img.resize_exact(side(w, *f), side(h, *f), FilterType::Lanczos3),
+            Op::Crop(f) => {
+                let (cw, ch) = (side(w, *f), side(h, *f));
+                img.crop_imm((w - cw) / 2, (h - ch) / 2, cw, ch)
+            }
+            Op::Blur(sigma) => img.blur(*sigma),
+            _ => img,
+        };
+        let out = dir.join(format!("step{}.png", step));
+        img.save(&out)?;
+        Ok(out)
+    }
+}
+
+/// One payload found in the original.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Carried {
+    pub algorithm: &'static str,
+    /// Entry name when the carrier holds a table of named payloads.
+    pub name: Option<String>,
+    /// The frame as stored, out of any FEC envelope.
+    pub frame: Vec<u8>,
+}
+
+impl Carried {
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(n) => format!("{} '{}'", self.algorithm, n),
+            None => self.algorithm.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Survival {
+    /// Extracts exactly as before, after repairing this many bytes.
+    Intact { corrected: usize },
+    /// Something still extracts, but it isn't the original payload.
+    Damaged,
+    /// Nothing extracts, with the reason.
+    Lost(String),
+}
+
+impl fmt::Display for Survival {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Survival::Intact { corrected: 0 } => write!(f, "intact"),
+            Survival::Intact { corrected } => write!(f, "intact ({} bytes corrected)", corrected),
+            Survival::Damaged => write!(f, "damaged (extracts, but the payload changed)"),
+            Survival::Lost(why) => write!(f, "lost ({})", why),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub op: Op,
+    /// Survival of each payload, in the order `run` found them.
+    pub results: Vec<Survival>,
+}
+
+impl Step {
+    pub fn all_intact(&self) -> bool {
+        self.results.iter().all(|r| matches!(r, Survival::Intact { .. }))
+    }
+}
+
+const ALGORITHMS: [&str; 4] = ["lsb", "overlay", "marker", "appext"];
+
+/// (entry name, frame) pairs, the name only set for table entries.
+type Frames = Vec<(Option<String>, Vec<u8>)>;
+
+/// Frames `algorithm` finds in the picture at `path`, and how many bytes FEC had to repair.
+fn extract(algorithm: &str, path: &Path, key: Option<&str>, stride: Option<usize>) -> Result<(Frames, usize), StegError> {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    let raw = match algorithm {
+        "lsb" => match key {
+            Some(k) => lsb::find_payload_keyed(path, k)?,
+            None => lsb::find_payload_sparse(path, stride)?,
+        },
+        "overlay" => overlay::find_payload(path)?,
+        "marker" if ext == "jpg" || ext == "jpeg" => marker_hijacking::find_payload(path)?,
+        "appext" if ext == "gif" => app_extension::find_payload(path, DEFAULT_APP_ID)?,
+        _ => return Err(format!("not a .{} carrier anymore", if algorithm == "marker" { "jpg" } else { "gif" }).into()),
+    };
+    let (frame, corrected) = payload::unprotect(&raw)?.unwrap_or((raw, 0));
+    if !frame.starts_with(&payload::MAGIC) {
+        return Err("no payload header".into());
+    }
+    let frames = match Table::parse(&frame)? {
+        Some(table) => table.names().map(|n| (Some(n.to_string()), table.get(n).unwrap_or_default().to_vec())).collect(),
+        None => vec![(None, frame)],
+    };
+    Ok((frames, corrected))
+}
+
+/// Every payload in the picture at `path`, by any picture algorithm.
+pub fn payloads(path: &Path, key: Option<&str>, stride: Option<usize>) -> Vec<Carried> {
+    ALGORITHMS
+        .iter()
+        .filter_map(|&alg| extract(alg, path, key, stride).ok().map(|(frames, _)| (alg, frames)))
+        .flat_map(|(algorithm, frames)| frames.into_iter().map(move |(name, frame)| Carried { algorithm, name, frame }))
+        .collect()
+}
+
+/// Apply `ops` to the picture at `path` one after the other and report, after each step, which of the
+/// original's payloads still extract. `key`/`stride` are the lsb settings used at hide time.
+pub fn run(path: &Path, ops: &[Op], key: Option<&str>, stride: Option<usize>) -> Result<(Vec<Carried>, Vec<Step>), StegError> {
+    let carried = payloads(path, key, stride);
+    if carried.is_empty() {
+        return Err(format!("No payload found in {}, nothing to simulate (pass --key/--stride if lsb used them)", path.display()).into());
+    }
+    let dir = tempfile::tempdir()?;
+    let mut current = path.to_path_buf();
+    let mut steps = Vec::with_capacity(ops.len());
+    for (i, op) in ops.iter().enumerate() {
+        current = op.apply(&current, dir.path(), i + 1).map_err(|e| format!("{} failed: {}", op, e))?;
+        let mut found = std::collections::HashMap::new();
+        let results = carried
+            .iter()
+            .map(|c| {
+                let (frames, corrected) = found
+                    .entry(c.algorithm)
+                    .or_insert_with(|| extract(c.algorithm, &current, key, stride))
+                    .as_ref()
+                    .map_err(|e| Survival::Lost(e.to_string()))?;
+                match frames.iter().find(|(n, _)| *n == c.name) {
+                    Some((_, f)) if *f == c.frame => Ok(Survival::Intact { corrected: *corrected }),
+                    Some(_) => Ok(Survival::Damaged),
+                    None => Err(Survival::Lost("entry missing".to_string())),
+                }
+            })
+            .map(|r| r.unwrap_or_else(|lost| lost))
+            .collect();
+        steps.push(Step { op: *op, results });
+    }
+    Ok((carried, steps))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+    use rand::{Rng, SeedableRng};
+    use tempfile::tempdir;
+    use crate::steg_algorithms::payload::{FrameOptions, Payload};
+
+    #[test]
+    fn parses_steps() {
+        let ops: Vec<Op> = "jpeg:85, resize:0.9,png".split(',').map(|s| Op::parse(s).unwrap()).collect();
+        assert_eq!(ops, [Op::Jpeg(85), Op::Resize(0.9), Op::Png]);
+        assert_eq!(ops[1].to_string(), "resize:0.9");
+        assert!(Op::parse("jpeg").unwrap_err().to_string().contains("needs a value"));
+        assert!(Op::parse("crop:1.5").is_err());
+        assert!(Op::parse("sharpen:2").unwrap_err().to_string().contains("Unknown operation"));
+    }
+
+    #[test]
+    fn overlay_survives_jpeg_where_lsb_does_not() {
+        let dir = tempdir().unwrap();
+        let (cover, marked, stego) = (dir.path().join("c.png"), dir.path().join("m.png"), dir.path().join("s.png"));
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(5);
+        RgbImage::from_fn(320, 288, |x, y| {
+            let n: i32 = rng.gen_range(-6..=6);
+            let v = |base: u32| (base as i32 + n).clamp(0, 255) as u8;
+            Rgb([v(x * 200 / 320 + 20), v(y * 180 / 288 + 30), v(90)])
+        })
+        .save(&cover)
+        .unwrap();
+
+        let frame = |msg: &str| Payload::from_text(msg).encode(&FrameOptions::default()).unwrap();
+        overlay::hide(&cover, frame("traced"), &marked, overlay::DEFAULT_STRENGTH).unwrap();
+        lsb::hide(&marked, frame("fragile"), &stego).unwrap();
+
+        let (carried, steps) = run(&stego, &[Op::Png, Op::Jpeg(90)], None, None).unwrap();
+        let labels: Vec<String> = carried.iter().map(Carried::label).collect();
+        assert_eq!(labels, ["lsb", "overlay"]);
+        assert!(steps[0].all_intact());
+        assert!(matches!(steps[1].results[0], Survival::Lost(_)), "{:?}", steps[1].results);
+        assert_eq!(steps[1].results[1], Survival::Intact { corrected: 0 });
+    }
+}
@@ -0,0 +1,17 @@
+use std::io::Cursor;
+use std::path::Path;
+use image::codecs::jpeg::JpegEncoder;
+use image::ImageReader;
+use crate::steg_algorithms::error::StegError;
+
+/// Decode any picture the `image` crate understands and re-encode it as a baseline JPEG.
+/// Used when the user asks for a .jpg output from a non-JPEG carrier, so marker hijacking has something to hijack.
+pub fn to_jpeg(path: &Path, quality: u8) -> Result<Vec<u8>, StegError> {
+    let dyn_i = ImageReader::open(path)?.decode()?;
+    // JPEG has no alpha, flatten to RGB8 first
+    let rgb = dyn_i.to_rgb8();
+    let mut out = Cursor::new(Vec::new());
+    JpegEncoder::new_with_quality(&mut out, quality)
+        .encode_image(&rgb)?;
+    Ok(out.into_inner())
+}
@@ -0,0 +1,180 @@
+use std::path::Path;
+use image::{GrayImage, ImageReader};
+use crate::steg_algorithms::formats;
+use crate::steg_algorithms::payload::{FrameOptions, Payload};
+use super::{lsb, overlay};
+use crate::steg_algorithms::error::StegError;
+
+// Rate-distortion search for `hide --target-quality`. Every candidate configuration is actually
+// embedded (into a temp file with the real output extension, so lossy containers count), read back to
+// make sure the payload survives, and scored with SSIM against the cover. The best scoring survivor wins.
+
+/// Strides tried for lsb, the sparser the better when the payload is small.
+const LSB_STRIDES: [usize; 7] = [1, 2, 4, 8, 16, 32, 64];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tuned {
+    pub algorithm: &'static str,
+    pub compress: bool,
+    pub stride: usize,
+    pub strength: u8,
+    /// Mean SSIM of the result against the cover, 1.0 is untouched.
+    pub quality: f64,
+}
+
+/// Mean SSIM over 8x8 luma windows (non-overlapping, which is plenty to rank candidates).
+pub fn ssim(a: &GrayImage, b: &GrayImage) -> f64 {
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+    let (w, h) = a.dimensions();
+    let (mut total, mut windows) = (0f64, 0usize);
+    for wy in (0..h.saturating_sub(7)).step_by(8) {
+        for wx in (0..w.saturating_sub(7)).step_by(8) {
+            let (mut sa, mut sb, mut saa, mut sbb, mut sab) = (0f64, 0f64, 0f64, 0f64, 0f64);
+            for y in wy..wy + 8 {
+                for x in wx..wx + 8 {
+                    let (pa, pb) = (a.get_pixel(x, y)[0] as f64, b.get_pixel(x, y)[0] as f64);
+                    sa += pa;
+                    sb += pb;
+                    saa += pa * pa;
+                    sbb += pb * pb;
+                    sab += pa * pb;
+                }
+            }
+            let n = 64.0;
+            let (ma, mb) = (sa / n, sb / n);
+            let (va, vb, cov) = (saa / n - ma * ma, sbb / n - mb * mb, sab / n - ma * mb);
+            total += ((2.0 * ma * mb + C1) * (2.0 * cov + C2)) / ((ma * ma + mb * mb + C1) * (va + vb + C2));
+            windows += 1;
+        }
+    }
+    if windows == 0 { 1.0 } else { total / windows as f64 }
+}
+
+fn luma(path: &Path) -> Result<GrayImage, StegError> {
+    Ok(ImageReader::open(path)?.decode()?.to_luma8())
+}
+
+/// Find the least visible way to hide `payload` in the picture at `in_path`, written as `out_ext`.
+/// `algorithm` restricts the search to one algorithm; `key` means lsb will be keyed (stride is moot).
+/// Fails when nothing fits, or when the best candidate is still below `target`.
+pub fn search(
+    in_path: &Path,
+    payload: &Payload,
+    base: &FrameOptions,
+    out_ext: &str,
+    algorithm: Option<&str>,
+    key: Option<&str>,
+    target: f64,
+) -> Result<Tuned, StegError> {
+    let cover = luma(in_path)?;
+    let dir = tempfile::tempdir()?;
+    let candidate_path = dir.path().join(format!("candidate.{}", out_ext));
+
+    let mut best: Option<Tuned> = None;
+    let mut consider = |tuned: Tuned| {
+        if best.as_ref().is_none_or(|b| tuned.quality > b.quality) {
+            best = Some(tuned);
+        }
+    };
+
+    for compress in [false, true] {
+        let framed = payload.encode(&FrameOptions { compress, ..base.clone() })?;
+        if compress && framed.len() >= payload.encode(base)?.len() {
+            continue; // compression didn't help, nothing new to try
+        }
+
+        if algorithm.is_none_or(|a| a == "lsb") && formats::output_problem("picture", "lsb", out_ext).is_none() {
+            let strides: &[usize] = if key.is_some() { &[1] } else { &LSB_STRIDES };
+            for &stride in strides {
+                let embedded = match key {
+                    Some(k) => lsb::hide_keyed(in_path, &framed, &candidate_path, k),
+                    None => lsb::hide_sparse(in_path, &framed, &candidate_path, stride),
+                };
+                // too big at this stride means every sparser one is too big as well
+                if embedded.is_err() {
+                    break;
+                }
+                let quality = ssim(&cover, &luma(&candidate_path)?);
+                consider(Tuned { algorithm: "lsb", compress, stride, strength: 0, quality });
+            }
+        }
+
+        if algorithm.is_none_or(|a| a == "overlay") && framed.len() <= overlay::MAX_PAYLOAD {
+            // the weakest strength that still reads back is the least visible one
+            for strength in 1..=32u8 {
+                if overlay::hide(in_path, &framed, &candidate_path, strength).is_err() {
+                    break;
+                }
+                if overlay::find_payload(&candidate_path).ok().as_deref() == Some(&framed[..]) {
+                    let quality = ssim(&cover, &luma(&candidate_path)?);
+                    consider(Tuned { algorithm: "overlay", compress, stride: 1, strength, quality });
+                    break;
+                }
+            }
+        }
+    }
+
+    match best {
+        None => Err("No configuration fits this payload into the picture".into()),
+        Some(b) if b.quality < target => Err(format!(
+            "Best configuration ({} stride {} strength {}{}) only reaches quality {:.4}, below the {} target",
+            b.algorithm,
+            b.stride,
+            b.strength,
+            if b.compress { " compressed" } else { "" },
+            b.quality,
+            target
+        ).into()),
+        Some(b) => Ok(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+    use tempfile::tempdir;
+
+    fn make_cover(path: &Path, w: u32, h: u32) {
+        let img = RgbImage::from_fn(w, h, |x, y| Rgb([(x * 7 % 256) as u8, (y * 5 % 256) as u8, ((x ^ y) % 256) as u8]));
+        img.save(path).unwrap();
+    }
+
+    #[test]
+    fn ssim_of_identical_is_one() {
+        let img = GrayImage::from_fn(32, 32, |x, y| image::Luma([(x * y % 256) as u8]));
+        assert!((ssim(&img, &img) - 1.0).abs() < 1e-12);
+        let mut noisy = img.clone();
+        for p in noisy.pixels_mut() {
+            p[0] = p[0].wrapping_add(40);
+        }
+        assert!(ssim(&img, &noisy) < 0.99);
+    }
+
+    #[test]
+    fn prefers_compression_and_sparse_lsb_for_png() {
+        let dir = tempdir().unwrap();
+        let cover = dir.path().join("c.png");
+        make_cover(&cover, 300, 300);
+        let payload = Payload::from_text(&"la la la ".repeat(2000));
+
+        let tuned = search(&cover, &payload, &FrameOptions::default(), "png", Some("lsb"), None, 0.9).unwrap();
+        assert_eq!(tuned.algorithm, "lsb");
+        assert!(tuned.compress, "repetitive text should be compressed: {:?}", tuned);
+        assert!(tuned.quality > 0.99);
+
+        assert!(search(&cover, &payload, &FrameOptions::default(), "png", Some("lsb"), None, 1.01).is_err());
+    }
+
+    #[test]
+    fn only_overlay_survives_jpeg() {
+        let dir = tempdir().unwrap();
+        let cover = dir.path().join("c.png");
+        make_cover(&cover, 300, 300);
+
+        let tuned = search(&cover, &Payload::from_text("hi"), &FrameOptions::default(), "jpg", None, None, 0.0).unwrap();
+        assert_eq!(tuned.algorithm, "overlay");
+        assert!(tuned.strength >= 1);
+    }
+}
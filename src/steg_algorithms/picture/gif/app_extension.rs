@@ -0,0 +1,305 @@
+use std::fs;
+use std::path::Path;
+use crate::steg_algorithms::atomic;
+use crate::steg_algorithms::error::StegError;
+
+// GIF89a application extension layout:
+//   0x21 0xFF 0x0B <8-byte application id><3-byte auth code> <data sub-blocks> 0x00
+// Each data sub-block is a length byte (1..=255) followed by that many bytes.
+
+const EXTENSION_INTRODUCER: u8 = 0x21;
+const APPLICATION_LABEL: u8 = 0xFF;
+const COMMENT_LABEL: u8 = 0xFE;
+const IMAGE_SEPARATOR: u8 = 0x2C;
+const TRAILER: u8 = 0x3B;
+const APP_BLOCK_LEN: u8 = 11;
+
+/// Application id + auth code used when the caller doesn't pick one.
+pub const DEFAULT_IDENTIFIER: [u8; 11] = *b"RSTEGANO1.0";
+
+// keep each extension a sane size, like an APPn segment; seq(u16)+total(u16) prefix every chunk
+const MAX_CHUNK_BODY: usize = 65_535 - 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlockKind {
+    Application([u8; 11]),
+    OtherExtension,
+    Image,
+}
+
+// (kind, start, end) of one block, end exclusive
+type Block = (BlockKind, usize, usize);
+
+// every block between the logical screen and the trailer, plus the trailer offset
+fn walk_blocks(buf: &[u8]) -> Result<(Vec<Block>, usize), StegError> {
+    if buf.len() < 13 || !(buf.starts_with(b"GIF89a") || buf.starts_with(b"GIF87a")) {
+        return Err("not a GIF file".into());
+    }
+    let packed = buf[10];
+    let mut pos = 13;
+    if packed & 0x80 != 0 {
+        pos += 3 * (1 << ((packed & 0x07) + 1)); // global color table
+    }
+
+    let mut blocks = Vec::new();
+    loop {
+        let start = pos;
+        match buf.get(pos) {
+            Some(&EXTENSION_INTRODUCER) => {
+                let label = *buf.get(pos + 1).ok_or("truncated extension")?;
+                let kind = if label == APPLICATION_LABEL && buf.get(pos + 2) == Some(&APP_BLOCK_LEN) {
+                    let id = buf.get(pos + 3..pos + 14).ok_or("truncated application extension")?;
+                    BlockKind::Application(id.try_into().unwrap())
+                } else {
+                    BlockKind::OtherExtension
+                };
+                // the fixed-size part is itself size-prefixed, so it skips like any sub-block
+                pos = skip_sub_blocks(buf, pos + 2)?;
+                blocks.push((kind, start, pos));
+            }
+            Some(&IMAGE_SEPARATOR) => {
+                let packed = *buf.get(pos + 9).ok_or("truncated image descriptor")?;
+                pos += 10;
+                if packed & 0x80 != 0 {
+                    pos += 3 * (1 << ((packed & 0x07) + 1)); // local color table
+                }
+                pos += 1; // LZW minimum code size
+                pos = skip_sub_blocks(buf, pos)?;
+                blocks.push((BlockKind::Image, start, pos));
+            }
+            Some(&TRAILER) => return Ok((blocks, pos)),
+            Some(other) => return Err(format!("unexpected block type {:#04x} at offset {}", other, pos).into()),
+            None => return Err("GIF ends without a trailer".into()),
+        }
+    }
+}
+
+fn skip_sub_blocks(buf: &[u8], mut pos: usize) -> Result<usize, StegError> {
+    loop {
+        let n = *buf.get(pos).ok_or("truncated data sub-blocks")? as usize;
+        pos += 1;
+        if n == 0 {
+            return Ok(pos);
+        }
+        pos += n;
+    }
+}
+
+// concatenated contents of the data sub-blocks of an application extension spanning start..end
+fn app_data(buf: &[u8], start: usize, end: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut pos = start + 14; // introducer, label, block size, 11 id bytes
+    while pos < end {
+        let n = buf[pos] as usize;
+        if n == 0 {
+            break;
+        }
+        out.extend_from_slice(&buf[pos + 1..pos + 1 + n]);
+        pos += 1 + n;
+    }
+    out
+}
+
+fn make_app_extension(identifier: &[u8; 11], data: &[u8]) -> Vec<u8> {
+    let mut ext = Vec::with_capacity(15 + data.len() + data.len() / 255 + 1);
+    ext.extend_from_slice(&[EXTENSION_INTRODUCER, APPLICATION_LABEL, APP_BLOCK_LEN]);
+    ext.extend_from_slice(identifier);
+    for block in data.chunks(255) {
+        ext.push(block.len() as u8);
+        ext.extend_from_slice(block);
+    }
+    ext.push(0);
+    ext
+}
+
+// looping/animation control that viewers rely on
+const NEEDED_APPLICATIONS: [&[u8; 11]; 2] = [b"NETSCAPE2.0", b"ANIMEXTS1.0"];
+
+/// Application and comment extensions that could carry hidden data, as (start, end, description).
+/// Animation control extensions are left alone.
+pub fn removable_extensions(buf: &[u8]) -> Result<Vec<(usize, usize, String)>, StegError> {
+    let (blocks, _) = walk_blocks(buf)?;
+    Ok(blocks
+        .into_iter()
+        .filter_map(|(kind, start, end)| match kind {
+            BlockKind::Application(id) if !NEEDED_APPLICATIONS.contains(&&id) => {
+                Some((start, end, format!("application extension '{}'", String::from_utf8_lossy(&id))))
+            }
+            BlockKind::OtherExtension if buf[start + 1] == COMMENT_LABEL => Some((start, end, "comment extension".to_string())),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Remove any application extensions carrying `identifier` and add `payload` as new ones, just before the trailer.
+/// Other extensions (NETSCAPE2.0 looping, comments, XMP, ...) and all frames are kept byte-for-byte.
+pub fn insert_or_replace_app_extension(original: &[u8], identifier: &[u8; 11], payload: &[u8]) -> Result<Vec<u8>, StegError> {
+    let (blocks, trailer) = walk_blocks(original)?;
+    let header_end = blocks.first().map_or(trailer, |b| b.1);
+
+    let mut out = Vec::with_capacity(original.len() + payload.len() + 64);
+    out.extend_from_slice(&original[..header_end]);
+    for (kind, start, end) in &blocks {
+        if *kind != BlockKind::Application(*identifier) {
+            out.extend_from_slice(&original[*start..*end]);
+        }
+    }
+
+    let total = payload.len().div_ceil(MAX_CHUNK_BODY).max(1) as u16;
+    let chunks: Vec<&[u8]> = if payload.is_empty() { vec![&[]] } else { payload.chunks(MAX_CHUNK_BODY).collect() };
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        let mut data = Vec::with_capacity(4 + chunk.len());
+        data.extend_from_slice(&(i as u16).to_be_bytes());
+        data.extend_from_slice(&total.to_be_bytes());
+        data.extend_from_slice(chunk);
+        out.extend_from_slice(&make_app_extension(identifier, &data));
+    }
+
+    out.extend_from_slice(&original[trailer..]);
+    Ok(out)
+}
+
+/// Reassemble the payload stored under `identifier`. Ok(None) if there is none, Err on incomplete sets.
+pub fn extract_app_extension_payload(original: &[u8], identifier: &[u8; 11]) -> Result<Option<Vec<u8>>, StegError> {
+    let (blocks, _) = walk_blocks(original)?;
+    let mut placed: Vec<Option<Vec<u8>>> = Vec::new();
+    for (kind, start, end) in blocks {
+        if kind != BlockKind::Application(*identifier) {
+            continue;
+        }
+        let data = app_data(original, start, end);
+        if data.len() < 4 {
+            return Err("found matching extension with too-small header".into());
+        }
+        let seq = u16::from_be_bytes([data[0], data[1]]) as usize;
+        let total = u16::from_be_bytes([data[2], data[3]]) as usize;
+        if total == 0 || seq >= total {
+            return Err(format!("chunk seq {} >= total {}", seq, total).into());
+        }
+        if placed.len() < total {
+            placed.resize(total, None);
+        }
+        placed[seq] = Some(data[4..].to_vec());
+    }
+
+    if placed.is_empty() {
+        return Ok(None);
+    }
+    let mut out = Vec::new();
+    for (i, slot) in placed.into_iter().enumerate() {
+        out.extend(slot.ok_or_else(|| format!("missing chunk {}", i))?);
+    }
+    Ok(Some(out))
+}
+
+/// Most bytes `hide` can embed: 65535 extensions (the chunk count is a u16) minus the length header.
+/// Doesn't depend on the picture.
+pub fn capacity() -> usize {
+    u16::MAX as usize * MAX_CHUNK_BODY - 4
+}
+
+/// Hide `msg` into the GIF at `path` under `identifier`, write the result to `out_path`.
+pub fn hide(path: &Path, msg: impl AsRef<[u8]>, out_path: &Path, identifier: &[u8; 11]) -> Result<(), StegError> {
+    if !path.exists() {
+        return Err(StegError::not_found(path));
+    }
+    let original = fs::read(path)?;
+
+    // 4-byte BE length header + message, same as the JPEG marker carrier
+    let msg = msg.as_ref();
+    let mut payload = Vec::with_capacity(4 + msg.len());
+    payload.extend_from_slice(&(msg.len() as u32).to_be_bytes());
+    payload.extend_from_slice(msg);
+
+    let new_gif = insert_or_replace_app_extension(&original, identifier, &payload)?;
+    atomic::write(out_path, new_gif)
+}
+
+/// Extract the bytes hidden by `hide` with the same `identifier`.
+pub fn find_payload(path: &Path, identifier: &[u8; 11]) -> Result<Vec<u8>, StegError> {
+    if !path.exists() {
+        return Err(StegError::not_found(path));
+    }
+    let buf = fs::read(path)?;
+    let payload = extract_app_extension_payload(&buf, identifier)?
+        .ok_or(StegError::NoPayloadFound)?;
+
+    if payload.len() < 4 {
+        return Err(StegError::Truncated { expected: 4, got: payload.len() });
+    }
+    let len = u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]) as usize;
+    if payload.len() - 4 < len {
+        return Err(StegError::Truncated { expected: len, got: payload.len() - 4 });
+    }
+    Ok(payload[4..4 + len].to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1x1 GIF89a with a 2-entry global color table, a NETSCAPE2.0 loop block and a comment
+    fn build_dummy_gif() -> Vec<u8> {
+        let mut v = Vec::new();
+        v.extend_from_slice(b"GIF89a");
+        v.extend_from_slice(&[1, 0, 1, 0, 0x80, 0, 0]); // 1x1, GCT with 2 entries
+        v.extend_from_slice(&[0, 0, 0, 0xFF, 0xFF, 0xFF]);
+        v.extend_from_slice(&[0x21, 0xFF, 0x0B]);
+        v.extend_from_slice(b"NETSCAPE2.0");
+        v.extend_from_slice(&[3, 1, 0, 0, 0]); // loop forever
+        v.extend_from_slice(&[0x21, 0xFE, 2, b'h', b'i', 0]); // comment
+        v.extend_from_slice(&[0x2C, 0, 0, 0, 0, 1, 0, 1, 0, 0]); // image descriptor
+        v.extend_from_slice(&[2, 2, 0x4C, 0x01, 0]); // LZW data
+        v.push(0x3B);
+        v
+    }
+
+    fn netscape_block(buf: &[u8]) -> Option<Vec<u8>> {
+        let (blocks, _) = walk_blocks(buf).unwrap();
+        blocks
+            .into_iter()
+            .find(|b| b.0 == BlockKind::Application(*b"NETSCAPE2.0"))
+            .map(|(_, s, e)| buf[s..e].to_vec())
+    }
+
+    #[test]
+    fn insert_and_extract_roundtrip() {
+        let orig = build_dummy_gif();
+        let out = insert_or_replace_app_extension(&orig, &DEFAULT_IDENTIFIER, b"hello-gif").unwrap();
+        let got = extract_app_extension_payload(&out, &DEFAULT_IDENTIFIER).unwrap();
+        assert_eq!(got.as_deref(), Some(&b"hello-gif"[..]));
+        assert_eq!(netscape_block(&out), netscape_block(&orig), "looping block must survive untouched");
+        assert_eq!(*out.last().unwrap(), TRAILER);
+    }
+
+    #[test]
+    fn replaces_previous_payload_and_keeps_other_ids() {
+        let orig = build_dummy_gif();
+        let other = *b"OTHERAPP1.0";
+        let a = insert_or_replace_app_extension(&orig, &other, b"keep me").unwrap();
+        let b = insert_or_replace_app_extension(&a, &DEFAULT_IDENTIFIER, b"first").unwrap();
+        let c = insert_or_replace_app_extension(&b, &DEFAULT_IDENTIFIER, b"second").unwrap();
+
+        assert_eq!(extract_app_extension_payload(&c, &DEFAULT_IDENTIFIER).unwrap().unwrap(), b"second");
+        assert_eq!(extract_app_extension_payload(&c, &other).unwrap().unwrap(), b"keep me");
+        let (blocks, _) = walk_blocks(&c).unwrap();
+        let ours = blocks.iter().filter(|b| b.0 == BlockKind::Application(DEFAULT_IDENTIFIER)).count();
+        assert_eq!(ours, 1);
+    }
+
+    #[test]
+    fn large_payload_spans_several_extensions() {
+        let orig = build_dummy_gif();
+        let payload: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let out = insert_or_replace_app_extension(&orig, &DEFAULT_IDENTIFIER, &payload).unwrap();
+        assert_eq!(extract_app_extension_payload(&out, &DEFAULT_IDENTIFIER).unwrap().unwrap(), payload);
+    }
+
+    #[test]
+    fn rejects_non_gif_and_truncated() {
+        assert!(walk_blocks(b"\x89PNG\r\n\x1a\n0000000").is_err());
+        let gif = build_dummy_gif();
+        assert!(walk_blocks(&gif[..gif.len() - 4]).is_err());
+        assert_eq!(extract_app_extension_payload(&gif, &DEFAULT_IDENTIFIER).unwrap(), None);
+    }
+}
@@ -1,10 +1,75 @@
-use std::fs;
-use std::io::{self, Write};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::path::Path;
+use crate::steg_algorithms::atomic;
+use crate::steg_algorithms::crypto::{self, Cipher};
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::options::{FindOptions, HideOptions};
+use crate::steg_algorithms::parse::{self, Mode, Parsed, Walker};
+use serde::{Deserialize, Serialize};
+
+/// Starts every APP11 segment `hide` writes.
+pub const IDENTIFIER: &[u8] = b"Ducky\0";
+/// Starts the segments of a payload sealed segment by segment (`hide_sealed`). Each one holds
+/// seq (2 bytes) | total (2 bytes) | one `crypto::seal_chunks` chunk, and the identifier through total
+/// is that chunk's associated data. A fresh salt and base nonce per hide mean the same plaintext chunk
+/// never seals to the same bytes twice, and any segment that survives decrypts without the others.
+pub const SEALED_IDENTIFIER: &[u8] = b"Ducky\x01";
+const APP11: u8 = 0xEB;
+
+/// Which segments carry the payload: the typed form of `--algorithm marker:app=0xEC,id=MyApp` (see
+/// `params`). Plain segments start with `id` and a 0 byte, sealed ones with `id` and a 1 byte, so the
+/// default is `IDENTIFIER`/`SEALED_IDENTIFIER` in APP11. find goes by the identifier alone, whichever
+/// APPn holds it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MarkerOptions {
+    /// The second marker byte, 0xE0 (APP0) to 0xEF (APP15).
+    pub app: u8,
+    /// Up to `MAX_ID_LEN` bytes, no 0 or 1 bytes.
+    pub id: Vec<u8>,
+}
+
+/// Longest `MarkerOptions::id`.
+pub const MAX_ID_LEN: usize = 32;
+
+impl Default for MarkerOptions {
+    fn default() -> Self {
+        MarkerOptions { app: APP11, id: b"Ducky".to_vec() }
+    }
+}
+
+impl MarkerOptions {
+    fn check(&self) -> Result<(), StegError> {
+        if !(0xE0..=0xEF).contains(&self.app) {
+            return Err(format!("app must be an APPn marker, 0xE0 to 0xEF, not {:#04X}", self.app).into());
+        }
+        if self.id.is_empty() || self.id.len() > MAX_ID_LEN || self.id.iter().any(|&b| b <= 1) {
+            return Err(format!("id must be 1 to {} bytes without 0 or 1 bytes", MAX_ID_LEN).into());
+        }
+        Ok(())
+    }
+
+    fn identifier(&self) -> Vec<u8> {
+        [self.id.as_slice(), b"\0"].concat()
+    }
+
+    fn sealed_identifier(&self) -> Vec<u8> {
+        [self.id.as_slice(), b"\x01"].concat()
+    }
+
+    /// Whether a segment body (after the length field) is one of these segments, plain or sealed.
+    pub fn carries(&self, body: &[u8]) -> bool {
+        body.starts_with(&self.identifier()) || body.starts_with(&self.sealed_identifier())
+    }
+}
 
 const SOI: [u8; 2] = [0xFF, 0xD8];
 const SOS_MARKER: u8 = 0xDA;
-const MAX_SEGMENT_TOTAL_LEN: usize = 65_535;
+const EOI_MARKER: u8 = 0xD9;
+// define number of lines, only ever after the first scan
+const DNL_MARKER: u8 = 0xDC;
+// a segment's length field counts itself, so 65535 leaves 65533 bytes of body
 const MAX_SEGMENT_PAYLOAD: usize = 65_533;
 
 fn make_app_segment(app_marker: u8, payload: &[u8]) -> Vec<u8> {
@@ -19,63 +84,122 @@ fn make_app_segment(app_marker: u8, payload: &[u8]) -> Vec<u8> {
 
 
 
-fn find_sos_index(buf: &[u8]) -> Option<usize> {
-    let mut i = 2usize; // skip initial SOI (0..1)
+/// The segments before the scan as (marker, start, end), the walk's `end` being where the SOS segment
+/// starts. Lenient mode skips stray bytes, bad lengths and markers that don't belong in a header, and
+/// stops at an EOI or a segment that runs past the end of the file.
+pub fn header_segments(buf: &[u8], mode: Mode) -> Result<Parsed<(u8, usize, usize)>, StegError> {
+    let mut walk = Walker::new(mode);
+    if !buf.starts_with(&SOI) {
+        walk.violation(0, "No SOI marker")?;
+    }
+    // every step moves `i` forward, so a walk takes at most one step per byte whatever the file holds
+    let mut i = 2usize;
     while i + 1 < buf.len() {
         if buf[i] != 0xFF {
-            // JPEG markers should be 0xFF followed by marker byte.
-            // sometimes padding 0xFF bytes exist; skip them.
-            i += 1;
+            let skip = buf[i..].iter().position(|&b| b == 0xFF).unwrap_or(buf.len() - i);
+            walk.violation(i, format!("{} stray bytes between segments", skip))?;
+            i += skip;
             continue;
         }
         let marker = buf[i + 1];
+        if marker == 0xFF {
+            // fill byte before a marker, allowed
+            i += 1;
+            continue;
+        }
         if marker == SOS_MARKER {
-            return Some(i);
+            return Ok(walk.finish(Some(i)));
         }
-
-        // markers without length (RSTn, SOI, EOI) can be skipped, but here we assume we're inside header
-        // for APPn/COM we have a 2 byte length after marker
-        if marker == 0x00 || (marker >= 0xD0 && marker <= 0xD7) {
-            // stuffed byte or RSTn, move on
+        // the image ends without a scan; whatever follows isn't its header
+        if marker == EOI_MARKER {
+            walk.violation(i, "EOI before the scan")?;
+            return Ok(walk.finish(None));
+        }
+        // stuffing, restarts and SOI have no length and no place before the scan
+        if matches!(marker, 0x00 | 0x01 | 0xD0..=0xD8) {
+            walk.violation(i, format!("Marker {:#04X} before the scan", marker))?;
             i += 2;
             continue;
         }
         if i + 3 >= buf.len() {
-            return None;
+            break;
+        }
+        if marker == DNL_MARKER {
+            walk.violation(i, "DNL marker before the scan")?;
         }
         let len = u16::from_be_bytes([buf[i + 2], buf[i + 3]]) as usize;
-        // length includes the two length bytes, so the payload length is len - 2
+        // the length counts its own two bytes
+        if len < 2 {
+            walk.violation(i, format!("Segment {:#04X} has length {}", marker, len))?;
+            i += 2;
+            continue;
+        }
+        if i + 2 + len > buf.len() {
+            walk.violation(i, format!("Segment {:#04X} runs past the end of the file", marker))?;
+            return Ok(walk.finish(None));
+        }
+        walk.push((marker, i, i + 2 + len));
         i += 2 + len;
     }
-    None
+    walk.violation(buf.len(), "The file ends before the scan")?;
+    Ok(walk.finish(None))
 }
 
-fn collect_app_segments(buf: &[u8]) -> Vec<(u8, usize, usize)> {
-    let mut res = Vec::new();
-    let mut i = 2usize; // skip SOI
-    while i + 1 < buf.len() {
-        if buf[i] != 0xFF {
-            i += 1;
+// The header of the JPEG `r` holds, from SOI up to and including the SOS marker, walked the way
+// `header_segments` walks a buffer so that it stops where that walk would. The scan, nearly all of the
+// file, is left in `r`. A file that ends first gives what there was.
+fn read_header(r: &mut impl BufRead) -> Result<Vec<u8>, StegError> {
+    let mut buf = Vec::new();
+    r.by_ref().take(2).read_to_end(&mut buf)?;
+    while let Some(b) = next_byte(r, &mut buf)? {
+        if b != 0xFF {
             continue;
         }
-        let marker = buf[i + 1];
-        if marker == SOS_MARKER {
+        // fill bytes before the marker
+        let mut marker = 0xFF;
+        while marker == 0xFF {
+            let Some(m) = next_byte(r, &mut buf)? else { return Ok(buf) };
+            marker = m;
+        }
+        if marker == SOS_MARKER || marker == EOI_MARKER {
             break;
         }
-        if marker == 0x00 || (marker >= 0xD0 && marker <= 0xD7) {
-            i += 2;
+        if matches!(marker, 0x00 | 0x01 | 0xD0..=0xD8) {
             continue;
         }
-        if i + 3 >= buf.len() { break; }
-        let len = u16::from_be_bytes([buf[i + 2], buf[i + 3]]) as usize;
-        let seg_start = i;
-        let seg_end = i + 2 + len; // exclusive
-        if seg_end > buf.len() { break; }
-        // keep only APPn (0xE0..0xEF) and COM (0xFE) if you want; here we return all segments
-        res.push((marker, seg_start, seg_end));
-        i = seg_end;
+        let at = buf.len();
+        if r.by_ref().take(2).read_to_end(&mut buf)? < 2 {
+            break;
+        }
+        let len = u16::from_be_bytes([buf[at], buf[at + 1]]) as u64;
+        if len >= 2 && r.by_ref().take(len - 2).read_to_end(&mut buf)? < (len - 2) as usize {
+            break;
+        }
+    }
+    Ok(buf)
+}
+
+fn next_byte(r: &mut impl Read, buf: &mut Vec<u8>) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    if r.read(&mut byte)? == 0 {
+        return Ok(None);
     }
-    res
+    buf.push(byte[0]);
+    Ok(Some(byte[0]))
+}
+
+// `carrier` into `out` with the header rewritten by `rewrite` and the scan copied through as it's read
+fn restream(carrier: impl Read, mut out: impl Write, rewrite: impl FnOnce(&[u8]) -> Result<Vec<u8>, StegError>) -> Result<(), StegError> {
+    let mut carrier = BufReader::new(carrier);
+    let header = read_header(&mut carrier)?;
+    out.write_all(&rewrite(&header)?)?;
+    io::copy(&mut carrier, &mut out)?;
+    Ok(out.flush()?)
+}
+
+// the segments before the scan, as the process's mode reads them
+fn collect_app_segments(buf: &[u8]) -> Result<Vec<(u8, usize, usize)>, StegError> {
+    header_segments(buf, parse::mode()).map(|parsed| parsed.items)
 }
 
 fn chunk_payload_with_identifier(payload: &[u8], identifier: &[u8]) -> Vec<Vec<u8>> {
@@ -83,7 +207,7 @@ fn chunk_payload_with_identifier(payload: &[u8], identifier: &[u8]) -> Vec<Vec<u
     let max_body = MAX_SEGMENT_PAYLOAD.saturating_sub(header_len);
     assert!(max_body > 0, "identifier too large for APPn segment");
     let mut chunks = Vec::new();
-    let total = ((payload.len() + max_body - 1) / max_body) as u16;
+    let total = payload.len().div_ceil(max_body) as u16;
     for (i, chunk) in payload.chunks(max_body).enumerate() {
         let mut v = Vec::with_capacity(header_len + chunk.len());
         v.extend_from_slice(identifier);
@@ -95,49 +219,62 @@ fn chunk_payload_with_identifier(payload: &[u8], identifier: &[u8]) -> Vec<Vec<u
     chunks
 }
 
+/// `original` with `payload` in `app_marker` segments (0xEB for APP11), each starting with `identifier`
+/// and its seq and total, in place of any segments before the scan that already started with
+/// `identifier`. Read it back with `extract_payload_from_bytes`.
+///
+/// ```
+/// use rust_stego::steg::jpeg::marker::{extract_payload_from_bytes, insert_or_replace_appn};
+///
+/// let mut jpeg = Vec::new();
+/// image::RgbImage::from_pixel(16, 16, image::Rgb([200, 30, 30]))
+///     .write_to(&mut std::io::Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+///     .unwrap();
+///
+/// let first = insert_or_replace_appn(&jpeg, 0xEB, Some(b"MyApp\0"), b"version 1")?;
+/// let second = insert_or_replace_appn(&first, 0xEB, Some(b"MyApp\0"), b"version 2")?;
+/// assert_eq!(extract_payload_from_bytes(&second, b"MyApp\0")?, Some(b"version 2".to_vec()));
+/// assert_eq!(extract_payload_from_bytes(&jpeg, b"MyApp\0")?, None);
+/// # Ok::<(), rust_stego::steg::StegError>(())
+/// ```
 pub fn insert_or_replace_appn(
     original: &[u8],
     app_marker: u8,
     identifier: Option<&[u8]>,
     payload: &[u8],
-) -> io::Result<Vec<u8>> {
-    // find SOS index
-    let sos_idx = find_sos_index(original).ok_or_else(|| {
-        io::Error::new(io::ErrorKind::InvalidData, "no SOS marker found in JPEG")
-    })?;
+) -> Result<Vec<u8>, StegError> {
+    let chunks = chunk_payload_with_identifier(payload, identifier.unwrap_or(&[]));
+    replace_segments(original, app_marker, identifier.as_slice(), chunks)
+}
 
-    // collect segments before SOS
-    let segments = collect_app_segments(original);
+/// Rebuild the header with `bodies` as new `app_marker` segments, dropping every segment before the
+/// scan whose payload starts with one of `remove`.
+fn replace_segments(original: &[u8], app_marker: u8, remove: &[&[u8]], bodies: Vec<Vec<u8>>) -> Result<Vec<u8>, StegError> {
+    let header = header_segments(original, parse::mode())?;
+    // nothing is written until the whole header has been walked, so a bad one never gives half an output
+    let Some(sos_idx) = header.end else {
+        return Err(StegError::Malformed { at: original.len(), what: "No scan for the segments to go in front of".into() });
+    };
+    let segments = header.items;
 
     // build a new header area: keep segments that do NOT match identifier
     let mut new_buf = Vec::new();
-    // push SOI
-    new_buf.extend_from_slice(&original[0..2]);
+    // a lenient walk may have got past a missing or damaged one
+    new_buf.extend_from_slice(&SOI);
 
-    // iterate through existing segments before SOS, keep those not matching the identifier
-    for (marker, start, end) in segments.iter() {
-        // only operate on APPn or COM if desired; here we check payload start for identifier
+    // iterate through existing segments before SOS, keep those not matching an identifier
+    for (_marker, start, end) in segments.iter() {
         let payload_start = start + 4; // 0xFF, marker, len_hi, len_lo -> payload
         if payload_start > *end { continue; }
         let payload_slice = &original[payload_start..*end];
-        let should_remove = if let Some(id) = identifier {
-            payload_slice.starts_with(id)
-        } else {
-            false
-        };
-        if !should_remove {
+        if !remove.iter().any(|id| payload_slice.starts_with(id)) {
             new_buf.extend_from_slice(&original[*start..*end]);
-        } else {
-            // skip removing segment (effectively replaced)
         }
     }
 
-    // build new chunks from payload and insert them as new APPn segments
-    let id = identifier.unwrap_or(&[]);
-    let chunks = chunk_payload_with_identifier(payload, id);
-    for chunk_payload in chunks {
-        let seg = make_app_segment(app_marker, &chunk_payload);
-        new_buf.extend_from_slice(&seg);
+    // insert the new chunks as APPn segments
+    for body in bodies {
+        new_buf.extend_from_slice(&make_app_segment(app_marker, &body));
     }
 
     // append the rest of original jpeg starting at sos_idx
@@ -146,6 +283,39 @@ pub fn insert_or_replace_appn(
     Ok(new_buf)
 }
 
+/// The APPn and COM segments before the scan, as (marker, body after the length field).
+pub fn app_segments(buf: &[u8]) -> Result<Vec<(u8, &[u8])>, StegError> {
+    Ok(collect_app_segments(buf)?
+        .into_iter()
+        .filter(|&(marker, _, _)| (0xE0..=0xEF).contains(&marker) || marker == 0xFE)
+        .map(|(marker, start, end)| (marker, &buf[(start + 4).min(end)..end]))
+        .collect())
+}
+
+// segments decoders need to show the picture right: JFIF/JFXX, ICC profiles, Adobe color transform
+const NEEDED_SEGMENTS: [(u8, &[u8]); 4] = [(0xE0, b"JFIF\0"), (0xE0, b"JFXX\0"), (0xE2, b"ICC_PROFILE\0"), (0xEE, b"Adobe")];
+
+/// APPn and COM segments before the scan that could carry hidden data (ours, and whatever other tools
+/// stash in comments, EXIF or vendor segments), as (marker, start, end). Everything but the segments
+/// decoders need.
+pub(crate) fn removable_segments(buf: &[u8]) -> Result<Vec<(u8, usize, usize)>, StegError> {
+    Ok(collect_app_segments(buf)?
+        .into_iter()
+        .filter(|&(marker, start, end)| {
+            let body = &buf[(start + 4).min(end)..end];
+            ((0xE0..=0xEF).contains(&marker) || marker == 0xFE)
+                && !NEEDED_SEGMENTS.iter().any(|&(m, id)| m == marker && body.starts_with(id))
+        })
+        .collect())
+}
+
+/// Most bytes `hide` can embed. The segment count is stored as a u16, so this is what 65535 full APP11
+/// segments hold (minus the length header); the picture itself doesn't limit it.
+pub fn capacity() -> usize {
+    let max_body = MAX_SEGMENT_PAYLOAD - (b"Ducky\0".len() + 4);
+    u16::MAX as usize * max_body - 4
+}
+
 /// Hide payload (bytes) into `input_jpeg_path` and write result to `output_jpeg_path`.
 /// `app_marker` is the second byte of the APP marker (e.g. 0xEB for APP11).
 /// `identifier` must match the one used by `chunk_payload_with_identifier`.
@@ -155,39 +325,61 @@ pub fn hide_payload_file(
     app_marker: u8,
     identifier: &[u8],
     payload: &[u8],
-) -> io::Result<()> {
+) -> Result<(), StegError> {
     let original = fs::read(input_jpeg_path)?;
     let new_jpeg = insert_or_replace_appn(&original, app_marker, Some(identifier), payload)?;
-    fs::write(output_jpeg_path, new_jpeg)?;
+    atomic::write(Path::new(output_jpeg_path), new_jpeg)?;
     Ok(())
 }
 
-/// Extract payload bytes from a JPEG buffer. Returns Ok(Some(payload)) if found,
-/// Ok(None) if no matching identifier segments exist, Err on malformed/incomplete sets.
-pub fn extract_payload_from_bytes(original: &[u8], identifier: &[u8]) -> io::Result<Option<Vec<u8>>> {
-    // gather segments before SOS
-    let segments = collect_app_segments(original);
+/// (seq, total, chunk) for each segment of a split payload.
+type Chunks<'a> = Vec<(u16, u16, &'a [u8])>;
 
-    // collect all matching chunks: (seq, total, chunk_bytes)
-    let mut chunks: Vec<(u16, u16, Vec<u8>)> = Vec::new();
-    for (_marker, start, end) in segments.iter() {
-        let payload_start = start + 4;
-        if payload_start > *end { continue; }
-        let payload_slice = &original[payload_start..*end];
+/// The segments before the scan that start with `identifier`, as (seq, total, chunk).
+fn matching_chunks<'a>(original: &'a [u8], identifier: &[u8]) -> Result<Chunks<'a>, StegError> {
+    let mut chunks = Vec::new();
+    let segments = collect_app_segments(original)?;
+    for (_marker, start, end) in segments {
+        let payload_slice = &original[(start + 4).min(end)..end];
         if !payload_slice.starts_with(identifier) {
             continue;
         }
         // need at least identifier + 4 bytes for seq+total
         let hdr_len = identifier.len() + 4;
         if payload_slice.len() < hdr_len {
-            return Err(io::Error::new(io::ErrorKind::InvalidData, "found matching segment with too-small header"));
+            return Err(StegError::Malformed { at: start, what: "Segment too short for the seq and total after its identifier".into() });
         }
         let seq_off = identifier.len();
         let seq = u16::from_be_bytes([payload_slice[seq_off], payload_slice[seq_off + 1]]);
         let total = u16::from_be_bytes([payload_slice[seq_off + 2], payload_slice[seq_off + 3]]);
-        let chunk_data = payload_slice[hdr_len..].to_vec();
-        chunks.push((seq, total, chunk_data));
+        chunks.push((seq, total, &payload_slice[hdr_len..]));
     }
+    Ok(chunks)
+}
+
+/// Extract payload bytes from a JPEG buffer. Returns Ok(Some(payload)) if found,
+/// Ok(None) if no matching identifier segments exist, Err on malformed/incomplete sets.
+///
+/// ```
+/// use rust_stego::steg::jpeg::marker::{extract_payload_from_bytes, insert_or_replace_appn};
+///
+/// let mut jpeg = Vec::new();
+/// image::RgbImage::from_pixel(16, 16, image::Rgb([30, 200, 30]))
+///     .write_to(&mut std::io::Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+///     .unwrap();
+///
+/// // 200 KB takes several segments of at most 64 KB each
+/// let big = vec![7u8; 200_000];
+/// let stego = insert_or_replace_appn(&jpeg, 0xEC, Some(b"big\0"), &big)?;
+/// assert_eq!(extract_payload_from_bytes(&stego, b"big\0")?, Some(big));
+/// # Ok::<(), rust_stego::steg::StegError>(())
+/// ```
+pub fn extract_payload_from_bytes(original: &[u8], identifier: &[u8]) -> Result<Option<Vec<u8>>, StegError> {
+    // collect all matching chunks: (seq, total, chunk_bytes)
+    let chunks: Vec<(u16, u16, Vec<u8>)> = matching_chunks(original, identifier)?
+        .into_iter()
+        .map(|(seq, total, data)| (seq, total, data.to_vec()))
+        .collect();
 
     if chunks.is_empty() {
         return Ok(None);
@@ -202,7 +394,7 @@ pub fn extract_payload_from_bytes(original: &[u8], identifier: &[u8]) -> io::Res
     let expected_total = expected_total.unwrap();
 
     if expected_total == 0 {
-        return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid total=0 in headers"));
+        return Err("invalid total=0 in headers".into());
     }
 
     // place chunks into a vector by seq
@@ -210,7 +402,7 @@ pub fn extract_payload_from_bytes(original: &[u8], identifier: &[u8]) -> io::Res
     for (seq, total, data) in chunks {
         let seq_idx = seq as usize;
         if seq_idx >= expected_total {
-            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("chunk seq {} >= total {}", seq_idx, expected_total)));
+            return Err(format!("chunk seq {} >= total {}", seq_idx, expected_total).into());
         }
         // simple overwrite if duplicates — keep the first if you prefer, but overwrite is fine
         placed[seq_idx] = Some(data);
@@ -224,16 +416,14 @@ pub fn extract_payload_from_bytes(original: &[u8], identifier: &[u8]) -> io::Res
     // verify all chunks present
     for (i, slot) in placed.iter().enumerate() {
         if slot.is_none() {
-            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("missing chunk {}", i)));
+            return Err(format!("missing chunk {}", i).into());
         }
     }
 
     // concat all chunks in order
     let mut out = Vec::new();
-    for slot in placed.into_iter() {
-        if let Some(mut s) = slot {
-            out.append(&mut s);
-        }
+    for mut s in placed.into_iter().flatten() {
+        out.append(&mut s);
     }
 
     Ok(Some(out))
@@ -241,11 +431,11 @@ pub fn extract_payload_from_bytes(original: &[u8], identifier: &[u8]) -> io::Res
 
 /// Convenience: read a JPEG file, extract payload with `identifier`, and write payload to `out_path`.
 /// Returns Ok(true) if found+written, Ok(false) if not found.
-pub fn extract_payload_file(jpeg_path: &str, identifier: &[u8], out_path: &str) -> io::Result<bool> {
+pub fn extract_payload_file(jpeg_path: &str, identifier: &[u8], out_path: &str) -> Result<bool, StegError> {
     let buf = fs::read(jpeg_path)?;
     match extract_payload_from_bytes(&buf, identifier)? {
         Some(payload) => {
-            fs::write(out_path, &payload)?;
+            atomic::write(Path::new(out_path), &payload)?;
             Ok(true)
         }
         None => Ok(false),
@@ -254,75 +444,257 @@ pub fn extract_payload_file(jpeg_path: &str, identifier: &[u8], out_path: &str)
 
 /// Hide `msg` string into JPEG at `path`, write stego JPEG to `out_path`.
 /// Uses APP11 (0xEB) segments and identifier `b"Ducky\0"`.
-pub fn hide(path: &Path, msg: &str, out_path: &Path) -> Result<(), String> {
+///
+/// ```
+/// use rust_stego::steg::jpeg::marker;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let (cover, out) = (dir.path().join("cover.jpg"), dir.path().join("out.jpg"));
+/// image::RgbImage::from_fn(32, 32, |x, y| image::Rgb([x as u8 * 8, y as u8 * 8, 64])).save(&cover).unwrap();
+///
+/// marker::hide(&cover, "the pixels don't change", &out)?;
+/// let (before, after) = (image::open(&cover).unwrap(), image::open(&out).unwrap());
+/// assert_eq!(before, after);
+/// # Ok::<(), rust_stego::steg::StegError>(())
+/// ```
+pub fn hide(path: &Path, msg: impl AsRef<[u8]>, out_path: &Path) -> Result<(), StegError> {
     if !path.exists() {
-        return Err(format!("Path {} doesn't exist!", path.display()));
+        return Err(StegError::not_found(path));
     }
 
     // read original jpeg bytes
-    let original = fs::read(path).map_err(|e| e.to_string())?;
+    let original = fs::read(path)?;
+    let new_jpeg = hide_in_bytes(&original, msg)?;
+
+    atomic::write(out_path, &new_jpeg)?;
+    Ok(())
+}
+
+/// Same as `hide` but works on an in-memory JPEG, returning the stego JPEG bytes.
+pub fn hide_in_bytes(original: &[u8], msg: impl AsRef<[u8]>) -> Result<Vec<u8>, StegError> {
+    hide_in_bytes_with(original, msg, &MarkerOptions::default())
+}
 
+/// `hide_in_bytes` into the segments `opts` names.
+pub fn hide_in_bytes_with(original: &[u8], msg: impl AsRef<[u8]>, opts: &MarkerOptions) -> Result<Vec<u8>, StegError> {
+    opts.check()?;
     // build payload: 4-byte BE length header + message bytes
-    let msg_bytes = msg.as_bytes();
+    let msg_bytes = msg.as_ref();
     if msg_bytes.len() > u32::MAX as usize {
-        return Err("message too large".to_string());
+        return Err("message too large".into());
     }
     let len_be = (msg_bytes.len() as u32).to_be_bytes();
     let mut payload: Vec<u8> = Vec::with_capacity(4 + msg_bytes.len());
     payload.extend_from_slice(&len_be);
     payload.extend_from_slice(msg_bytes);
 
-    // insert/replace APPn segments (this uses your helper)
-    // APP11 = 0xEB, identifier = b"Ducky\0"
-    let app_marker: u8 = 0xEB;
-    let identifier: &[u8] = b"Ducky\0";
+    // APPn segments starting with the identifier, replacing a sealed payload too
+    let (identifier, sealed) = (opts.identifier(), opts.sealed_identifier());
+    let chunks = chunk_payload_with_identifier(&payload, &identifier);
+    replace_segments(original, opts.app, &[&identifier, &sealed], chunks)
+}
 
-    let new_jpeg = insert_or_replace_appn(&original, app_marker, Some(identifier), &payload)
-        .map_err(|e| e.to_string())?;
+/// The general form of the hide functions: into the segments `opts.marker` names, each sealed with
+/// `opts.password` when there is one (see `hide_sealed`).
+pub fn hide_with(path: &Path, msg: impl AsRef<[u8]>, out_path: &Path, opts: &HideOptions) -> Result<(), StegError> {
+    let original = fs::read(path).map_err(|e| StegError::io(format!("Failed to read {}", path.display()), e))?;
+    let new_jpeg = hide_bytes_with(&original, msg, opts)?;
+    atomic::write(out_path, &new_jpeg)
+}
 
-    fs::write(out_path, &new_jpeg).map_err(|e| e.to_string())?;
-    Ok(())
+/// `hide_with` on an in-memory JPEG, returning the stego JPEG bytes.
+pub fn hide_bytes_with(original: &[u8], msg: impl AsRef<[u8]>, opts: &HideOptions) -> Result<Vec<u8>, StegError> {
+    match &opts.password {
+        Some(pw) => hide_sealed_in_bytes_with(original, msg.as_ref(), pw, opts.cipher, &opts.marker),
+        None => hide_in_bytes_with(original, msg, &opts.marker),
+    }
+}
+
+/// `hide_with` between streams, for a carrier that isn't a file: an object store body, a zip entry.
+/// Only the header is held in memory, the scan goes from `carrier` to `out` as it's read.
+pub fn hide_stream_with(carrier: impl Read, out: impl Write, msg: impl AsRef<[u8]>, opts: &HideOptions) -> Result<(), StegError> {
+    restream(carrier, out, |header| hide_bytes_with(header, msg, opts))
+}
+
+/// `hide_in_bytes_with` between streams.
+#[deprecated(note = "use hide_stream_with and HideOptions, this goes in the next release")]
+pub fn hide_stream(carrier: impl Read, out: impl Write, msg: impl AsRef<[u8]>, opts: &MarkerOptions) -> Result<(), StegError> {
+    hide_stream_with(carrier, out, msg, &HideOptions { marker: opts.clone(), ..HideOptions::default() })
+}
+
+/// `hide` with every segment sealed on its own under `password`, see `SEALED_IDENTIFIER`.
+pub fn hide_sealed(path: &Path, payload: &[u8], out_path: &Path, password: &str, cipher: Cipher) -> Result<(), StegError> {
+    let original = fs::read(path).map_err(|e| StegError::io(format!("Failed to read {}", path.display()), e))?;
+    let new_jpeg = hide_sealed_in_bytes(&original, payload, password, cipher)?;
+    atomic::write(out_path, &new_jpeg)
+}
+
+/// Same as `hide_sealed` on an in-memory JPEG.
+pub fn hide_sealed_in_bytes(original: &[u8], payload: &[u8], password: &str, cipher: Cipher) -> Result<Vec<u8>, StegError> {
+    hide_sealed_in_bytes_with(original, payload, password, cipher, &MarkerOptions::default())
+}
+
+/// `hide_sealed_in_bytes` into the segments `opts` names.
+pub fn hide_sealed_in_bytes_with(original: &[u8], payload: &[u8], password: &str, cipher: Cipher, opts: &MarkerOptions) -> Result<Vec<u8>, StegError> {
+    opts.check()?;
+    let (identifier, sealed_id) = (opts.identifier(), opts.sealed_identifier());
+    // every chunk is exactly its plaintext, so unlike `hide_in_bytes` there's no length header
+    let max_body = MAX_SEGMENT_PAYLOAD - (sealed_id.len() + 4) - cipher.overhead();
+    let mut pieces: Vec<&[u8]> = payload.chunks(max_body).collect();
+    if pieces.is_empty() {
+        pieces.push(&[]);
+    }
+    let total = u16::try_from(pieces.len()).map_err(|_| "message too large")?;
+    let sealed = crypto::seal_chunks(cipher, password, &pieces, |i| sealed_header(&sealed_id, i as u16, total))?;
+    let bodies = sealed.into_iter().enumerate().map(|(i, chunk)| [sealed_header(&sealed_id, i as u16, total), chunk].concat()).collect();
+    replace_segments(original, opts.app, &[&identifier, &sealed_id], bodies)
+}
+
+/// `hide_sealed_in_bytes_with` between streams.
+#[deprecated(note = "use hide_stream_with and HideOptions with a password, this goes in the next release")]
+pub fn hide_sealed_stream(carrier: impl Read, out: impl Write, payload: &[u8], password: &str, cipher: Cipher, opts: &MarkerOptions) -> Result<(), StegError> {
+    let opts = HideOptions { marker: opts.clone(), password: Some(password.to_string()), cipher, ..HideOptions::default() };
+    hide_stream_with(carrier, out, payload, &opts)
+}
+
+fn sealed_header(identifier: &[u8], seq: u16, total: u16) -> Vec<u8> {
+    [identifier, &seq.to_be_bytes(), &total.to_be_bytes()].concat()
+}
+
+/// The sealed chunks in `buf`, each decrypted on its own, in order: `None` for a chunk whose segment
+/// is missing or fails authentication. `Ok(None)` when there are no sealed segments at all.
+pub fn open_sealed(buf: &[u8], password: &str) -> Result<Option<Vec<Option<Vec<u8>>>>, StegError> {
+    open_sealed_as(buf, password, SEALED_IDENTIFIER)
+}
+
+fn open_sealed_as(buf: &[u8], password: &str, sealed_id: &[u8]) -> Result<Option<Vec<Option<Vec<u8>>>>, StegError> {
+    let chunks = matching_chunks(buf, sealed_id)?;
+    // a forged total only makes the chunks claiming it fail authentication
+    let Some(total) = chunks.iter().map(|&(_, total, _)| total).max() else {
+        return Ok(None);
+    };
+    let indexed: Vec<(u32, &[u8])> = chunks.iter().map(|&(seq, _, chunk)| (seq as u32, chunk)).collect();
+    let opened = crypto::open_chunks(password, &indexed, |i| sealed_header(sealed_id, i as u16, total));
+    let mut placed = vec![None; total as usize];
+    for ((seq, _, _), result) in chunks.iter().zip(opened) {
+        if let (Some(slot @ None), Ok(plain)) = (placed.get_mut(*seq as usize), result) {
+            *slot = Some(plain);
+        }
+    }
+    Ok(Some(placed))
+}
+
+/// (segments present, segments in the set, sealed bytes) of the sealed payload in `buf`, if it holds
+/// one. Needs no password.
+pub fn sealed_segments(buf: &[u8]) -> Option<(usize, usize, usize)> {
+    let chunks = matching_chunks(buf, SEALED_IDENTIFIER).ok()?;
+    let total = chunks.iter().map(|&(_, total, _)| total as usize).max()?;
+    Some((chunks.len(), total, chunks.iter().map(|&(_, _, chunk)| chunk.len()).sum()))
+}
+
+/// Whether `buf` holds a payload sealed segment by segment in the segments `opts` names.
+pub fn holds_sealed(buf: &[u8], opts: &MarkerOptions) -> bool {
+    matching_chunks(buf, &opts.sealed_identifier()).is_ok_and(|c| !c.is_empty())
 }
 
 /// Find and extract hidden message from JPEG at `path`. Returns the recovered string.
 /// Expects the same marker/identifier used by `hide`.
-pub fn find(path: &Path) -> Result<String, String> {
+///
+/// ```
+/// use rust_stego::steg::jpeg::marker;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let (cover, out) = (dir.path().join("cover.jpg"), dir.path().join("out.jpg"));
+/// image::RgbImage::from_fn(32, 32, |x, y| image::Rgb([x as u8 * 8, y as u8 * 8, 64])).save(&cover).unwrap();
+///
+/// marker::hide(&cover, "in the header", &out)?;
+/// assert_eq!(marker::find(&out)?, "in the header");
+/// assert!(marker::find(&cover).is_err());
+/// # Ok::<(), rust_stego::steg::StegError>(())
+/// ```
+pub fn find(path: &Path) -> Result<String, StegError> {
+    let bytes = find_payload(path)?;
+    String::from_utf8(bytes).map_err(|_| "<invalid utf8>".into())
+}
+
+/// Same as `find` but returns the raw bytes, for binary payloads.
+pub fn find_payload(path: &Path) -> Result<Vec<u8>, StegError> {
+    find_payload_with(path, None)
+}
+
+/// `find_payload` for a carrier that may hold a sealed payload (`hide_sealed`), which takes `password`.
+pub fn find_payload_with(path: &Path, password: Option<&str>) -> Result<Vec<u8>, StegError> {
+    find_payload_as(path, password, &MarkerOptions::default())
+}
+
+/// `find_payload_with` for a payload in the segments `opts` names.
+pub fn find_payload_as(path: &Path, password: Option<&str>, opts: &MarkerOptions) -> Result<Vec<u8>, StegError> {
     if !path.exists() {
-        return Err(format!("Path {} doesn't exist!", path.display()));
+        return Err(StegError::not_found(path));
     }
+    find_in(File::open(path)?, password, opts)
+}
 
-    let buf = fs::read(path).map_err(|e| e.to_string())?;
-    let identifier: &[u8] = b"Ducky\0";
+/// The general form of the find functions: the payload in the segments `opts.marker` names, sealed
+/// ones opened with `opts.password`.
+pub fn find_with(path: &Path, opts: &FindOptions) -> Result<Vec<u8>, StegError> {
+    find_payload_as(path, opts.password.as_deref(), &opts.marker)
+}
 
-    // use helper to reassemble payload across chunks
-    let opt_payload = extract_payload_from_bytes(&buf, identifier)
-        .map_err(|e| e.to_string())?;
+/// `find_with` for a carrier in memory, as `hide_bytes_with` returns it.
+pub fn find_bytes_with(carrier: &[u8], opts: &FindOptions) -> Result<Vec<u8>, StegError> {
+    find_stream_with(carrier, opts)
+}
 
-    let payload = match opt_payload {
-        Some(p) => p,
-        None => return Err("no matching segments found".to_string()),
-    };
+/// `find_with` for a carrier read from a stream, which is read no further than the start of the scan.
+pub fn find_stream_with(carrier: impl Read, opts: &FindOptions) -> Result<Vec<u8>, StegError> {
+    find_in(carrier, opts.password.as_deref(), &opts.marker)
+}
+
+/// `find_payload_as` for a carrier read from a stream.
+#[deprecated(note = "use find_stream_with and FindOptions, this goes in the next release")]
+pub fn find_stream(carrier: impl Read, password: Option<&str>, opts: &MarkerOptions) -> Result<Vec<u8>, StegError> {
+    find_in(carrier, password, opts)
+}
+
+fn find_in(carrier: impl Read, password: Option<&str>, opts: &MarkerOptions) -> Result<Vec<u8>, StegError> {
+    opts.check()?;
+    let (identifier, sealed_id) = (opts.identifier(), opts.sealed_identifier());
+
+    let buf = read_header(&mut BufReader::new(carrier))?;
+    if holds_sealed(&buf, opts) {
+        let password = password.ok_or("Payload is encrypted segment by segment, pass --password (or both --key-share files) to extract it")?;
+        let placed = open_sealed_as(&buf, password, &sealed_id)?.unwrap_or_default();
+        let missing: Vec<String> = placed.iter().enumerate().filter(|(_, c)| c.is_none()).map(|(i, _)| i.to_string()).collect();
+        if missing.len() == placed.len() {
+            return Err(StegError::ChecksumMismatch);
+        }
+        if !missing.is_empty() {
+            let recovered: usize = placed.iter().flatten().map(Vec::len).sum();
+            return Err(format!(
+                "{} of {} encrypted segments are missing or damaged (chunk {}), the other {} decrypt to {} bytes",
+                missing.len(), placed.len(), missing.join(", "), placed.len() - missing.len(), recovered
+            ).into());
+        }
+        return Ok(placed.into_iter().flatten().flatten().collect());
+    }
+    // use helper to reassemble payload across chunks
+    let payload = extract_payload_from_bytes(&buf, &identifier)?.ok_or(StegError::NoPayloadFound)?;
 
     // payload format: [4-byte BE length][msg bytes]
     if payload.len() < 4 {
-        return Err("payload too small to contain length header".to_string());
+        return Err(StegError::Truncated { expected: 4, got: payload.len() });
     }
     let len = u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]) as usize;
     if payload.len() < 4 + len {
-        return Err(format!(
-            "payload shorter than claimed length: header says {} bytes but have {}",
-            len,
-            payload.len() - 4
-        ));
+        return Err(StegError::Truncated { expected: len, got: payload.len() - 4 });
     }
-    let msg_bytes = &payload[4..4 + len];
-    String::from_utf8(msg_bytes.to_vec()).map_err(|_| "<invalid utf8>".to_string())
+    Ok(payload[4..4 + len].to_vec())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::HashSet;
 
     /// Helper to build a minimal "jpeg-like" buffer:
     /// SOI, then zero or more APP segments, then SOS, some dummy scan bytes, and EOI.
@@ -400,7 +772,7 @@ mod tests {
         assert_eq!(recovered, new_payload);
 
         // Ensure there's at least one APP11 segment starting with our identifier
-        let segs = collect_app_segments(&out);
+        let segs = header_segments(&out, Mode::Strict).unwrap().items;
         let identifier = b"Ducky\0";
         let mut found = false;
         for (marker, start, end) in segs.iter() {
@@ -419,6 +791,103 @@ mod tests {
     }
 
 
+    #[test]
+    fn test_encrypted_roundtrip() {
+        use crate::steg_algorithms::payload::{DecodeOptions, FrameOptions, Payload};
+
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("enc.jpg");
+        let orig = build_dummy_jpeg(vec![(0xE0, b"JFIF\0".to_vec())]);
+
+        let opts = FrameOptions { password: Some("correct horse".to_string()), ..Default::default() };
+        let unlock = DecodeOptions { password: Some("correct horse".to_string()), ..Default::default() };
+        for text in ["", "nobody can see this"] {
+            let framed = Payload::from_text(text).encode(&opts).unwrap();
+            fs::write(&out, hide_in_bytes(&orig, &framed).unwrap()).unwrap();
+            let decoded = Payload::decode(&find_payload(&out).unwrap(), &unlock).unwrap();
+            assert_eq!(decoded.data, text.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_custom_segments_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("custom.jpg");
+        let orig = build_dummy_jpeg(vec![(0xE0, b"JFIF\0".to_vec())]);
+        let opts = MarkerOptions { app: 0xEC, id: b"MyApp".to_vec() };
+
+        fs::write(&out, hide_in_bytes_with(&orig, b"in APP12", &opts).unwrap()).unwrap();
+        assert!(app_segments(&fs::read(&out).unwrap()).unwrap().iter().any(|(m, body)| *m == 0xEC && body.starts_with(b"MyApp\0")));
+        assert_eq!(find_payload_as(&out, None, &opts).unwrap(), b"in APP12");
+        assert!(find_payload(&out).is_err());
+
+        let sealed = hide_sealed_in_bytes_with(&orig, b"sealed", "pw", Cipher::Aes256Gcm, &opts).unwrap();
+        fs::write(&out, sealed).unwrap();
+        assert_eq!(find_payload_as(&out, Some("pw"), &opts).unwrap(), b"sealed");
+        assert!(hide_in_bytes_with(&orig, b"x", &MarkerOptions { app: 0xDB, ..opts }).is_err());
+    }
+
+    #[test]
+    fn test_sealed_segments_decrypt_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("sealed.jpg");
+        // an older plain payload in the cover is replaced, not left next to the sealed one
+        let orig = hide_in_bytes(&build_dummy_jpeg(vec![(0xE0, b"JFIF\0".to_vec())]), b"old").unwrap();
+        let payload: Vec<u8> = (0..150_000u32).map(|i| (i % 7) as u8).collect();
+
+        let stego = hide_sealed_in_bytes(&orig, &payload, "pw", Cipher::Aes256Gcm).unwrap();
+        assert_eq!(sealed_segments(&stego).map(|(n, total, _)| (n, total)), Some((3, 3)));
+        assert!(extract_payload_from_bytes(&stego, IDENTIFIER).unwrap().is_none());
+        // the repeating pattern gives chunks 0 and 1 the same plaintext
+        let bodies = matching_chunks(&stego, SEALED_IDENTIFIER).unwrap();
+        assert_ne!(bodies[0].2, bodies[1].2);
+        let again = hide_sealed_in_bytes(&orig, &payload, "pw", Cipher::Aes256Gcm).unwrap();
+        assert_ne!(matching_chunks(&again, SEALED_IDENTIFIER).unwrap()[2].2, bodies[2].2, "fresh per hide");
+
+        fs::write(&out, &stego).unwrap();
+        assert_eq!(find_payload_with(&out, Some("pw")).unwrap(), payload);
+        assert!(find_payload(&out).unwrap_err().to_string().contains("--password"));
+        assert!(matches!(find_payload_with(&out, Some("pv")), Err(StegError::ChecksumMismatch)));
+
+        // drop the middle segment: the rest still decrypts
+        let (_, start, end) = header_segments(&stego, Mode::Strict)
+            .unwrap()
+            .items
+            .into_iter()
+            .filter(|&(_, s, e)| stego[s + 4..e].starts_with(SEALED_IDENTIFIER))
+            .nth(1)
+            .unwrap();
+        let damaged = [&stego[..start], &stego[end..]].concat();
+        let placed = open_sealed(&damaged, "pw").unwrap().unwrap();
+        assert!(placed[1].is_none());
+        let body = MAX_SEGMENT_PAYLOAD - (SEALED_IDENTIFIER.len() + 4) - Cipher::Aes256Gcm.overhead();
+        assert_eq!(placed[0].as_deref(), Some(&payload[..body]));
+        assert_eq!(placed[2].as_deref(), Some(&payload[2 * body..]));
+        fs::write(&out, &damaged).unwrap();
+        assert!(find_payload_with(&out, Some("pw")).unwrap_err().to_string().contains("1 of 3 encrypted segments"));
+    }
+
+    #[test]
+    fn test_header_walk_by_mode() {
+        let orig = build_dummy_jpeg(vec![(0xE0, b"JFIF\0".to_vec()), (0xEB, b"Ducky\0body".to_vec())]);
+        let clean = header_segments(&orig, Mode::Strict).unwrap();
+        assert_eq!(clean.items.iter().map(|s| s.0).collect::<Vec<_>>(), [0xE0, 0xEB]);
+        assert_eq!(clean.end, Some(orig.len() - 9));
+
+        // junk between the two segments, as a sloppy editor or a bad disk leaves it
+        let mut damaged = orig.clone();
+        damaged.splice(11..11, [0x12, 0x34, 0x56]);
+        assert!(header_segments(&damaged, Mode::Strict).unwrap_err().to_string().contains("3 stray bytes"));
+        let lenient = header_segments(&damaged, Mode::Lenient).unwrap();
+        assert_eq!((lenient.items.len(), lenient.problems.len()), (2, 1));
+        // hiding rebuilds the header from the segments found, leaving the junk out
+        assert!(header_segments(&hide_in_bytes(&damaged, b"x").unwrap(), Mode::Strict).is_ok());
+
+        let cut = &orig[..10];
+        assert!(header_segments(cut, Mode::Strict).is_err());
+        assert_eq!(header_segments(cut, Mode::Lenient).unwrap().end, None);
+    }
+
     #[test]
     fn test_missing_chunk_returns_error() {
         // craft a jpeg containing a Ducky header that claims total=2 but only include seq=0
@@ -434,4 +903,117 @@ mod tests {
         let res = extract_payload_from_bytes(&orig, b"Ducky\0");
         assert!(res.is_err(), "expected error due to missing chunk");
     }
+    #[test]
+    fn streams_read_no_further_than_they_need() {
+        let mut orig = build_dummy_jpeg(vec![(0xE1, b"JFIF\0".to_vec())]);
+        // stray bytes and fill bytes are walked like the buffer walk does
+        orig.splice(2..2, [0x42, 0xFF, 0xFF]);
+        let opts = HideOptions::default();
+
+        // a byte slice reads but can't seek, like a socket
+        let mut out = Vec::new();
+        hide_stream_with(&orig[..], &mut out, b"streamed", &opts).unwrap();
+        assert_eq!(out, hide_in_bytes_with(&orig, b"streamed", &opts.marker).unwrap());
+
+        // find stops at the scan, so a stream cut off right behind it still reads
+        let sos = header_segments(&out, Mode::Lenient).unwrap().end.unwrap();
+        assert_eq!(find_stream_with(&out[..sos + 2], &opts.to_find()).unwrap(), b"streamed");
+
+        let mut sealed = Vec::new();
+        let opts = opts.password("pw").cipher(Cipher::Aes256Gcm);
+        hide_stream_with(&orig[..], &mut sealed, b"secret", &opts).unwrap();
+        assert!(sealed.ends_with(&orig[orig.len() - 9..]));
+        assert_eq!(find_stream_with(&sealed[..], &opts.to_find()).unwrap(), b"secret");
+        assert!(matches!(find_bytes_with(&sealed, &FindOptions::default()), Err(StegError::Other(_))));
+        assert!(hide_stream_with(&orig[..orig.len() - 9], Vec::new(), b"x", &opts).is_err());
+    }
+
+    // every entry point that walks a header, none of which may panic or hang on `buf`
+    fn walk_everything(buf: &[u8]) {
+        let strict = header_segments(buf, Mode::Strict);
+        let lenient = header_segments(buf, Mode::Lenient).expect("lenient walks always finish");
+        if let Err(e) = &strict {
+            assert!(matches!(e, StegError::Malformed { .. }), "{:?}", e);
+        }
+        assert!(lenient.items.iter().all(|&(_, start, end)| start + 4 <= end && end <= buf.len()));
+        let _ = extract_payload_from_bytes(buf, IDENTIFIER);
+        let _ = sealed_segments(buf);
+        let _ = find_stream_with(buf, &FindOptions::default().password("pw"));
+        match insert_or_replace_appn(buf, APP11, Some(IDENTIFIER), b"x") {
+            Ok(out) => {
+                assert!(lenient.end.is_some());
+                assert!(header_segments(&out, Mode::Lenient).unwrap().end.is_some());
+            }
+            Err(e) => assert!(matches!(e, StegError::Malformed { .. }), "{:?}", e),
+        }
+        // the streaming walk stops where the buffer walk does
+        let mut streamed = Vec::new();
+        match hide_stream_with(buf, &mut streamed, b"x", &HideOptions::default()) {
+            Ok(()) => assert_eq!(streamed, hide_in_bytes(buf, b"x").unwrap()),
+            Err(_) => assert!(hide_in_bytes(buf, b"x").is_err()),
+        }
+    }
+
+    #[test]
+    fn malformed_headers_are_errors_not_panics_or_hangs() {
+        let scan = [0xFF, 0xDA, 0x00, 0x02, 0x11, 0xFF, 0xD9];
+        let with_scan = |header: &[u8]| [&[0xFF, 0xD8][..], header, &scan].concat();
+        let corpus: Vec<(&str, Vec<u8>)> = vec![
+            ("empty", Vec::new()),
+            ("half a SOI", vec![0xFF]),
+            ("SOI only", vec![0xFF, 0xD8]),
+            ("marker at EOF", vec![0xFF, 0xD8, 0xFF]),
+            ("marker without a length", vec![0xFF, 0xD8, 0xFF, 0xE1]),
+            ("truncated length", vec![0xFF, 0xD8, 0xFF, 0xE1, 0x00]),
+            ("zero length", with_scan(&[0xFF, 0xE1, 0x00, 0x00])),
+            ("length of one", with_scan(&[0xFF, 0xE1, 0x00, 0x01, 0xAA])),
+            ("length past the end", vec![0xFF, 0xD8, 0xFF, 0xE1, 0xFF, 0xFF, 0x00]),
+            ("EOI before the scan", with_scan(&[0xFF, 0xE1, 0x00, 0x03, 0xAA, 0xFF, 0xD9])),
+            ("DNL in the header", with_scan(&[0xFF, 0xDC, 0x00, 0x04, 0x00, 0x10])),
+            ("standalone markers", with_scan(&[0xFF, 0x01, 0xFF, 0xD3, 0xFF, 0x00, 0xFF, 0xD8])),
+            ("nothing but fill", [&[0xFF, 0xD8][..], &[0xFF; 4096]].concat()),
+            ("no SOI", scan.to_vec()),
+        ];
+        for (what, buf) in &corpus {
+            let strict = header_segments(buf, Mode::Strict);
+            assert!(strict.is_err(), "{} passes a strict walk", what);
+            walk_everything(buf);
+        }
+
+        let eoi = &corpus[9].1;
+        let lenient = header_segments(eoi, Mode::Lenient).unwrap();
+        assert_eq!((lenient.items.len(), lenient.end), (1, None));
+        assert!(matches!(header_segments(eoi, Mode::Strict), Err(StegError::Malformed { at: 7, .. })));
+        // the scan after the EOI isn't this image's, so nothing gets hidden in front of it
+        assert!(matches!(insert_or_replace_appn(eoi, APP11, None, b"x"), Err(StegError::Malformed { .. })));
+
+        // a header that walks fine, with our identifier in a segment too short to be ours
+        let short = with_scan(&make_app_segment(APP11, b"Ducky\0\x00"));
+        walk_everything(&short);
+        assert!(matches!(extract_payload_from_bytes(&short, IDENTIFIER), Err(StegError::Malformed { at: 2, .. })));
+    }
+
+    #[test]
+    fn damaged_headers_never_panic() {
+        use rand::{Rng, SeedableRng};
+
+        let orig = build_dummy_jpeg(vec![(0xE0, b"JFIF\0".to_vec()), (APP11, b"Ducky\0\x00\x00\x00\x01body".to_vec())]);
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(86);
+        for _ in 0..2000 {
+            let mut buf = orig.clone();
+            for _ in 0..rng.gen_range(1..4) {
+                let at = rng.gen_range(0..buf.len());
+                match rng.gen_range(0..4) {
+                    0 => buf[at] = rng.r#gen(),
+                    1 => buf[at] = [0x00, 0x01, 0xFF][rng.gen_range(0..3)],
+                    2 => buf.truncate(at),
+                    _ => buf.insert(at, 0xFF),
+                }
+                if buf.is_empty() {
+                    break;
+                }
+            }
+            walk_everything(&buf);
+        }
+    }
 }
@@ -1,2 +1,5 @@
 pub mod general;
-pub mod jpg;
\ No newline at end of file
+pub mod gif;
+pub mod jpg;
+#[cfg(feature = "picture")]
+pub mod raw;
@@ -0,0 +1,21 @@
+use super::Raster;
+use crate::steg_algorithms::error::StegError;
+
+// farbfeld: "farbfeld" | width (4 bytes) | height (4 bytes) | RGBA, 16-bit big-endian per channel.
+
+const MAGIC: &[u8; 8] = b"farbfeld";
+const HEADER_LEN: usize = 16;
+
+pub fn is_farbfeld(buf: &[u8]) -> bool {
+    buf.starts_with(MAGIC)
+}
+
+pub fn parse(buf: Vec<u8>) -> Result<Raster, StegError> {
+    if !is_farbfeld(&buf) || buf.len() < HEADER_LEN {
+        return Err("Not a farbfeld image".into());
+    }
+    let dim = |at: usize| u32::from_be_bytes(buf[at..at + 4].try_into().unwrap()) as usize;
+    let (width, height) = (dim(8), dim(12));
+    // alpha is left alone
+    Raster::new(buf, HEADER_LEN, width, height, 4, 3, u16::MAX)
+}
@@ -0,0 +1,335 @@
+pub mod farbfeld;
+pub mod netpbm;
+pub mod qoi;
+
+use std::fs;
+use std::path::Path;
+use rand::RngCore;
+use crate::steg_algorithms::atomic;
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::picture::general::lsb::{self, Order};
+use crate::steg_algorithms::redundancy;
+
+// LSB embedding for the simply structured raster formats (netpbm, farbfeld, QOI) without going through
+// `image`. netpbm and farbfeld store plain samples, so the bits are set right in the file's bytes and
+// everything else (header comments, PAM tuple types, 16-bit depth, trailing bytes) survives as is.
+// QOI is decoded to RGBA and encoded again, see `qoi`.
+//
+// The slots and the bitstream are the ones `lsb` uses: every color sample in raster order (alpha never
+// carries), 32-bit length header, MSB first, with the same stride/key/redundancy options. For 16-bit
+// samples the bit goes into the low byte. An 8-bit RGB PPM written by `lsb` reads back here and the
+// other way round.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// P5/P6/P7
+    Netpbm,
+    Farbfeld,
+    Qoi,
+}
+
+impl Format {
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_lowercase().as_str() {
+            "pgm" | "ppm" | "pnm" | "pam" => Some(Format::Netpbm),
+            "ff" | "farbfeld" => Some(Format::Farbfeld),
+            "qoi" => Some(Format::Qoi),
+            _ => None,
+        }
+    }
+
+    /// The extension files of this format usually have.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Netpbm => "pnm",
+            Format::Farbfeld => "ff",
+            Format::Qoi => "qoi",
+        }
+    }
+
+    pub fn sniff(buf: &[u8]) -> Option<Self> {
+        if netpbm::is_netpbm(buf) {
+            Some(Format::Netpbm)
+        } else if farbfeld::is_farbfeld(buf) {
+            Some(Format::Farbfeld)
+        } else if qoi::is_qoi(buf) {
+            Some(Format::Qoi)
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether `path` is a file this module handles (by extension).
+pub fn handles(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()).and_then(Format::from_extension).is_some()
+}
+
+/// Samples of an image, in a buffer that is either the whole file or a decoded QOI's RGBA.
+pub struct Raster {
+    pub buf: Vec<u8>,
+    start: usize,
+    pixels: usize,
+    /// Samples per pixel, and how many of those (from the front) are color.
+    channels: usize,
+    color: usize,
+    /// 1 or 2 bytes, big-endian.
+    sample_bytes: usize,
+    maxval: u16,
+}
+
+impl Raster {
+    fn new(buf: Vec<u8>, start: usize, width: usize, height: usize, channels: usize, color: usize, maxval: u16) -> Result<Self, StegError> {
+        let sample_bytes = if maxval > 255 { 2 } else { 1 };
+        let pixels = width.checked_mul(height).ok_or("Image dimensions overflow")?;
+        let needed = pixels.checked_mul(channels * sample_bytes).and_then(|n| n.checked_add(start));
+        if needed.is_none_or(|n| n > buf.len()) {
+            return Err(format!("Image data is truncated ({}x{} needs more than the {} bytes there)", width, height, buf.len()).into());
+        }
+        Ok(Raster { buf, start, pixels, channels, color, sample_bytes, maxval })
+    }
+
+    /// How many samples can carry a bit.
+    pub fn slots(&self) -> usize {
+        self.pixels * self.color
+    }
+
+    // offset of the low byte of color sample `slot`
+    fn low_byte(&self, slot: usize) -> usize {
+        let sample = (slot / self.color) * self.channels + slot % self.color;
+        self.start + (sample + 1) * self.sample_bytes - 1
+    }
+
+    fn bit(&self, slot: usize) -> u8 {
+        self.buf[self.low_byte(slot)] & 1
+    }
+
+    fn set_bit(&mut self, slot: usize, bit: u8) {
+        let at = self.low_byte(slot);
+        if self.buf[at] & 1 == bit {
+            return;
+        }
+        let value = match self.sample_bytes {
+            2 => u16::from_be_bytes([self.buf[at - 1], self.buf[at]]),
+            _ => self.buf[at] as u16,
+        };
+        // an even maxval (say 100) has no odd neighbour above it, step down instead
+        let value = if value == self.maxval { value - 1 } else { value ^ 1 };
+        let bytes = value.to_be_bytes();
+        self.buf[at] = bytes[1];
+        if self.sample_bytes == 2 {
+            self.buf[at - 1] = bytes[0];
+        }
+    }
+
+    pub fn lsbs(&self) -> Vec<u8> {
+        (0..self.slots()).map(|slot| self.bit(slot)).collect()
+    }
+}
+
+/// The samples of the image in `buf`, and the decoded QOI when it was one (to encode again).
+fn load(buf: Vec<u8>) -> Result<(Raster, Option<qoi::Image>), StegError> {
+    match Format::sniff(&buf) {
+        Some(Format::Netpbm) => Ok((netpbm::parse(buf)?, None)),
+        Some(Format::Farbfeld) => Ok((farbfeld::parse(buf)?, None)),
+        Some(Format::Qoi) => qoi_raster(qoi::decode(&buf)?),
+        None => Err("Not a netpbm, farbfeld or QOI image".into()),
+    }
+}
+
+fn qoi_raster(mut img: qoi::Image) -> Result<(Raster, Option<qoi::Image>), StegError> {
+    let rgba = std::mem::take(&mut img.rgba);
+    let raster = Raster::new(rgba, 0, img.width as usize, img.height as usize, 4, 3, 255)?;
+    Ok((raster, Some(img)))
+}
+
+fn read(path: &Path) -> Result<(Raster, Option<qoi::Image>), StegError> {
+    if !path.exists() {
+        return Err(StegError::not_found(path));
+    }
+    load(fs::read(path)?)
+}
+
+/// How many bytes `hide` can embed into the image at `path` with the given stride.
+pub fn capacity(path: &Path, stride: usize) -> Result<usize, StegError> {
+    if stride == 0 {
+        return Err("Stride must be at least 1".into());
+    }
+    let (raster, _) = read(path)?;
+    Ok((raster.slots().div_ceil(stride) / 8).saturating_sub(4))
+}
+
+/// Hide `msg` into the image at `path` and write it, in the same format, to `out_path`. `key` scatters
+/// the bits like `lsb::hide_keyed` (then `stride` is ignored), `copies` > 1 stores them redundantly.
+/// A `.qoi` output can also come from any picture `image` reads.
+pub fn hide(path: &Path, msg: impl AsRef<[u8]>, out_path: &Path, stride: usize, key: Option<&str>, copies: usize) -> Result<(), StegError> {
+    if stride == 0 {
+        return Err("Stride must be at least 1".into());
+    }
+    let to_qoi = out_path.extension().and_then(|e| e.to_str()).and_then(Format::from_extension) == Some(Format::Qoi);
+    let (mut raster, qoi) = match read(path) {
+        Ok((_, None)) | Err(_) if to_qoi => qoi_raster(qoi::from_picture(path)?)?,
+        loaded => loaded?,
+    };
+    let order = key.map_or(Order::Strided(stride), Order::Keyed);
+    let bits = redundancy::bitstream(msg.as_ref(), copies)?;
+    let capacity_bits = order.usable(raster.slots());
+    if bits.len() > capacity_bits {
+        return Err(StegError::CapacityExceeded { needed: bits.len().div_ceil(8), available: capacity_bits / 8 });
+    }
+    for (slot, &bit) in order.slots(raster.slots()).zip(&bits) {
+        raster.set_bit(slot, bit);
+    }
+    write(raster, qoi, out_path)
+}
+
+fn write(raster: Raster, qoi: Option<qoi::Image>, out_path: &Path) -> Result<(), StegError> {
+    let out = match qoi {
+        Some(img) => qoi::encode_verified(&qoi::Image { rgba: raster.buf, ..img })?,
+        None => raster.buf,
+    };
+    atomic::write(out_path, out)
+}
+
+/// Replace the LSB of every color sample with a random bit, rewriting `path` in place. Returns how many
+/// samples that is.
+pub fn randomize(path: &Path, rng: &mut impl RngCore) -> Result<usize, StegError> {
+    let (mut raster, qoi) = read(path)?;
+    let slots = raster.slots();
+    for slot in 0..slots {
+        raster.set_bit(slot, (rng.next_u32() & 1) as u8);
+    }
+    write(raster, qoi, path)?;
+    Ok(slots)
+}
+
+/// Extract a payload written by `hide`. Without `key` and `stride` the stride is probed like
+/// `lsb::find_payload_sparse` does.
+pub fn find_payload(path: &Path, stride: Option<usize>, key: Option<&str>) -> Result<Vec<u8>, StegError> {
+    find_scored(path, stride, key).map(|(data, _)| data)
+}
+
+/// `find_payload` with the per-byte confidence of a redundant payload, like `lsb::find_scored`.
+pub fn find_scored(path: &Path, stride: Option<usize>, key: Option<&str>) -> Result<(Vec<u8>, Option<Vec<f32>>), StegError> {
+    let bits = read(path)?.0.lsbs();
+    match key {
+        Some(k) => lsb::extract_keyed_scored(&bits, k, None),
+        None => lsb::extract_sparse_scored(&bits, stride, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn ppm(width: usize, height: usize, maxval: u16) -> Vec<u8> {
+        let mut buf = format!("P6\n# made for a test\n{} {}\n{}\n", width, height, maxval).into_bytes();
+        for i in 0..width * height * 3 {
+            let v = (i * 37 % (maxval as usize + 1)) as u16;
+            if maxval > 255 { buf.extend_from_slice(&v.to_be_bytes()) } else { buf.push(v as u8) }
+        }
+        buf
+    }
+
+    #[test]
+    fn netpbm_keeps_header_and_ppm_matches_lsb() {
+        let dir = tempdir().unwrap();
+        let (cover, stego) = (dir.path().join("c.ppm"), dir.path().join("s.ppm"));
+        let orig = ppm(40, 30, 255);
+        fs::write(&cover, &orig).unwrap();
+
+        hide(&cover, b"netpbm secret", &stego, 2, None, 1).unwrap();
+        let out = fs::read(&stego).unwrap();
+        assert_eq!(out.len(), orig.len());
+        assert!(out.starts_with(b"P6\n# made for a test\n40 30\n255\n"), "header is byte for byte the same");
+        assert_eq!(find_payload(&stego, Some(2), None).unwrap(), b"netpbm secret");
+        // an 8-bit PPM has the slot layout the image-based lsb uses
+        assert_eq!(lsb::find_payload_sparse(&stego, Some(2)).unwrap(), b"netpbm secret");
+    }
+
+    #[test]
+    fn sixteen_bit_and_odd_maxval_samples() {
+        let dir = tempdir().unwrap();
+        // 16-bit PGM: the bit goes into the low byte, the high byte stays
+        let mut pgm = b"P5 16 16 65535\n".to_vec();
+        pgm.extend((0..256u16).flat_map(|i| (i * 257).to_be_bytes()));
+        let (cover, stego) = (dir.path().join("c.pgm"), dir.path().join("s.pgm"));
+        fs::write(&cover, &pgm).unwrap();
+        hide(&cover, b"deep", &stego, 1, Some("k"), 1).unwrap();
+        let out = fs::read(&stego).unwrap();
+        assert!(out.iter().zip(&pgm).skip(15).step_by(2).all(|(a, b)| a == b));
+        assert_eq!(find_payload(&stego, None, Some("k")).unwrap(), b"deep");
+
+        // maxval 100: no sample may end up above it
+        fs::write(&cover, ppm(20, 20, 100)).unwrap();
+        hide(&cover, [0xFF; 100], &stego, 1, None, 1).unwrap();
+        let raster = netpbm::parse(fs::read(&stego).unwrap()).unwrap();
+        assert!(raster.buf[raster.start..].iter().all(|&v| v <= 100));
+        assert_eq!(find_payload(&stego, Some(1), None).unwrap(), [0xFF; 100]);
+    }
+
+    #[test]
+    fn farbfeld_and_pam_leave_alpha_alone() {
+        let dir = tempdir().unwrap();
+        let mut ff = b"farbfeld".to_vec();
+        ff.extend(12u32.to_be_bytes());
+        ff.extend(10u32.to_be_bytes());
+        ff.extend((0..120u16 * 4).flat_map(|i| (i * 97).to_be_bytes()));
+        let mut pam = b"P7\nWIDTH 12\nHEIGHT 10\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n".to_vec();
+        let header = pam.len();
+        pam.extend((0..480u32).map(|i| (i * 31 % 256) as u8));
+
+        for (name, cover_bytes, alpha_bytes) in [("c.ff", ff, 16..16 + 960), ("c.pam", pam, header..header + 480)] {
+            let (cover, stego) = (dir.path().join(name), dir.path().join(format!("s{}", name)));
+            fs::write(&cover, &cover_bytes).unwrap();
+            hide(&cover, b"alpha stays", &stego, 1, None, 3).unwrap();
+            let out = fs::read(&stego).unwrap();
+            let sample = (alpha_bytes.len() / 120) / 4;
+            let alpha = |buf: &[u8]| -> Vec<u8> { buf[alpha_bytes.clone()].chunks(sample).skip(3).step_by(4).flatten().copied().collect() };
+            assert_eq!(alpha(&out), alpha(&cover_bytes), "{}", name);
+            assert_eq!(find_payload(&stego, None, None).unwrap(), b"alpha stays", "{}", name);
+        }
+    }
+
+    #[test]
+    fn qoi_codec_roundtrips_and_matches_image() {
+        let dir = tempdir().unwrap();
+        // runs, small diffs, luma steps, big jumps and alpha changes, so every op gets used
+        let rgba: Vec<u8> = (0..64 * 48u32)
+            .flat_map(|i| {
+                let (x, y) = (i % 64, i / 64);
+                let v = if x < 20 { 128 } else if x < 40 { (x * 3 + y) as u8 } else { (i * 7919 % 251) as u8 };
+                [v, v.wrapping_add(x as u8), v / 2, if y == 20 { 128 } else { 255 }]
+            })
+            .collect();
+        let img = qoi::Image { width: 64, height: 48, channels: 4, colorspace: 0, rgba };
+        let encoded = qoi::encode(&img);
+        assert_eq!(qoi::decode(&encoded).unwrap(), img);
+        assert_eq!(image::load_from_memory(&encoded).unwrap().to_rgba8().into_raw(), img.rgba, "image decodes ours");
+
+        let (cover, stego) = (dir.path().join("c.qoi"), dir.path().join("s.qoi"));
+        fs::write(&cover, &encoded).unwrap();
+        hide(&cover, b"quite ok secret", &stego, 1, None, 1).unwrap();
+        assert_eq!(find_payload(&stego, None, None).unwrap(), b"quite ok secret");
+        assert_eq!(capacity(&cover, 1).unwrap(), 64 * 48 * 3 / 8 - 4);
+    }
+
+    #[test]
+    fn qoi_output_from_any_picture_keeps_every_modified_pixel() {
+        let dir = tempdir().unwrap();
+        // one flat color is a single long run, so every embedded bit has to break it up
+        let (cover, stego) = (dir.path().join("flat.png"), dir.path().join("s.qoi"));
+        image::RgbImage::from_pixel(90, 70, image::Rgb([200, 200, 200])).save(&cover).unwrap();
+        let msg: Vec<u8> = (0..500u32).map(|i| (i * 131) as u8).collect();
+        hide(&cover, &msg, &stego, 1, None, 1).unwrap();
+
+        let img = qoi::decode(&fs::read(&stego).unwrap()).unwrap();
+        assert_eq!(img.channels, 3, "no alpha in, none recorded out");
+        assert_eq!(find_payload(&stego, Some(1), None).unwrap(), msg);
+        let bits = redundancy::bitstream(&msg, 1).unwrap();
+        let lsbs: Vec<u8> = img.rgba.chunks(4).flat_map(|p| [p[0] & 1, p[1] & 1, p[2] & 1]).take(bits.len()).collect();
+        assert_eq!(lsbs, bits);
+        assert!(qoi::encode_verified(&img).is_ok());
+    }
+}
@@ -0,0 +1,91 @@
+use super::Raster;
+use crate::steg_algorithms::error::StegError;
+
+// Binary netpbm: P5 (PGM, gray), P6 (PPM, RGB) and P7 (PAM). P5/P6 headers are whitespace separated
+// tokens with `#` comments, ended by a single whitespace byte:
+//   P6 <width> <height> <maxval>\n<samples>
+// PAM headers are `KEY value` lines up to ENDHDR:
+//   P7\nWIDTH 4\nHEIGHT 2\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n<samples>
+// Samples are one byte when maxval < 256, otherwise two bytes big-endian. The plain (ASCII) variants
+// P2/P3 aren't supported.
+
+pub fn is_netpbm(buf: &[u8]) -> bool {
+    matches!(buf.get(..2), Some(b"P5" | b"P6" | b"P7"))
+}
+
+/// Where the samples of the netpbm image in `buf` are. The header and any trailing bytes stay untouched.
+pub fn parse(buf: Vec<u8>) -> Result<Raster, StegError> {
+    let (width, height, depth, maxval, start) = match buf.get(..2) {
+        Some(b"P5") | Some(b"P6") => {
+            let mut pos = 2;
+            let mut next = || token(&buf, &mut pos);
+            let (w, h, maxval) = (next()?, next()?, next()?);
+            // exactly one whitespace byte between maxval and the samples
+            let depth = if buf[1] == b'5' { 1 } else { 3 };
+            (w, h, depth, maxval, pos + 1)
+        }
+        Some(b"P7") => parse_pam_header(&buf)?,
+        Some(b"P2") | Some(b"P3") => return Err("Plain (ASCII) netpbm isn't supported, convert it to binary P5/P6".into()),
+        _ => return Err("Not a binary netpbm image".into()),
+    };
+    if maxval == 0 || maxval > 65_535 {
+        return Err(format!("Invalid netpbm maxval {}", maxval).into());
+    }
+    // a trailing alpha channel is kept as is
+    let color = match depth {
+        1 | 3 => depth,
+        2 | 4 => depth - 1,
+        other => return Err(StegError::UnsupportedFormat { found: format!("PAM depth {}", other) }),
+    };
+    Raster::new(buf, start, width, height, depth, color, maxval as u16)
+}
+
+// next decimal token, skipping whitespace and comments
+fn token(buf: &[u8], pos: &mut usize) -> Result<usize, StegError> {
+    loop {
+        match buf.get(*pos) {
+            Some(b'#') => {
+                while buf.get(*pos).is_some_and(|&b| b != b'\n') {
+                    *pos += 1;
+                }
+            }
+            Some(b) if b.is_ascii_whitespace() => *pos += 1,
+            Some(_) => break,
+            None => return Err("Truncated netpbm header".into()),
+        }
+    }
+    let begin = *pos;
+    while buf.get(*pos).is_some_and(u8::is_ascii_digit) {
+        *pos += 1;
+    }
+    std::str::from_utf8(&buf[begin..*pos])
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| "Malformed netpbm header".into())
+}
+
+// (width, height, depth, maxval, sample start)
+fn parse_pam_header(buf: &[u8]) -> Result<(usize, usize, usize, usize, usize), StegError> {
+    let (mut width, mut height, mut depth, mut maxval) = (None, None, None, None);
+    let mut pos = 3;
+    loop {
+        let end = buf[pos..].iter().position(|&b| b == b'\n').map(|i| pos + i).ok_or("Truncated PAM header")?;
+        let line = std::str::from_utf8(&buf[pos..end]).map_err(|_| "Malformed PAM header")?.trim();
+        pos = end + 1;
+        let (key, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let number = || value.trim().parse::<usize>().map_err(|_| format!("Malformed PAM {} '{}'", key, value.trim()));
+        match key {
+            "ENDHDR" => break,
+            "WIDTH" => width = Some(number()?),
+            "HEIGHT" => height = Some(number()?),
+            "DEPTH" => depth = Some(number()?),
+            "MAXVAL" => maxval = Some(number()?),
+            // TUPLTYPE only names what DEPTH already says, comments and blank lines are fine too
+            _ => {}
+        }
+    }
+    match (width, height, depth, maxval) {
+        (Some(w), Some(h), Some(d), Some(m)) => Ok((w, h, d, m, pos)),
+        _ => Err("PAM header lacks WIDTH, HEIGHT, DEPTH or MAXVAL".into()),
+    }
+}
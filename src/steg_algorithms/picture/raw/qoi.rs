@@ -0,0 +1,168 @@
+use std::path::Path;
+use image::ImageReader;
+use crate::steg_algorithms::error::StegError;
+
+// QOI ("Quite OK Image", https://qoiformat.org/qoi-specification.pdf). Lossless but compressed, so
+// unlike the other raw formats its pixels are decoded to RGBA, changed there and encoded again.
+//
+//   header   "qoif" | width (4 bytes) | height (4 bytes) | channels (3 or 4) | colorspace
+//   chunks   one op per pixel or run of pixels, see the OP_* tags
+//   end      7 zero bytes and a 1
+
+const MAGIC: &[u8; 4] = b"qoif";
+const HEADER_LEN: usize = 14;
+const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+
+const OP_INDEX: u8 = 0x00;
+const OP_DIFF: u8 = 0x40;
+const OP_LUMA: u8 = 0x80;
+const OP_RUN: u8 = 0xC0;
+const OP_RGB: u8 = 0xFE;
+const OP_RGBA: u8 = 0xFF;
+const MASK_2: u8 = 0xC0;
+
+// the spec caps images at 400 million pixels
+const MAX_PIXELS: usize = 400_000_000;
+
+/// A decoded QOI image, always as RGBA.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    /// 3 or 4, as recorded in the header. Only informative, `rgba` always has alpha.
+    pub channels: u8,
+    pub colorspace: u8,
+    pub rgba: Vec<u8>,
+}
+
+pub fn is_qoi(buf: &[u8]) -> bool {
+    buf.starts_with(MAGIC)
+}
+
+fn hash(px: [u8; 4]) -> usize {
+    let [r, g, b, a] = px.map(|c| c as usize);
+    (r * 3 + g * 5 + b * 7 + a * 11) % 64
+}
+
+pub fn decode(buf: &[u8]) -> Result<Image, StegError> {
+    if !is_qoi(buf) || buf.len() < HEADER_LEN + END_MARKER.len() {
+        return Err("Not a QOI image".into());
+    }
+    let dim = |at: usize| u32::from_be_bytes(buf[at..at + 4].try_into().unwrap());
+    let (width, height, channels, colorspace) = (dim(4), dim(8), buf[12], buf[13]);
+    let pixels = width as usize * height as usize;
+    if !matches!(channels, 3 | 4) || colorspace > 1 || pixels > MAX_PIXELS {
+        return Err("Invalid QOI header".into());
+    }
+
+    let data = &buf[HEADER_LEN..buf.len() - END_MARKER.len()];
+    let mut rgba = Vec::with_capacity(pixels * 4);
+    let mut index = [[0u8; 4]; 64];
+    let mut px = [0, 0, 0, 255u8];
+    let mut pos = 0;
+    let mut byte = || -> Result<u8, StegError> {
+        let b = *data.get(pos).ok_or("QOI data ends early")?;
+        pos += 1;
+        Ok(b)
+    };
+    while rgba.len() < pixels * 4 {
+        let b1 = byte()?;
+        let mut run = 1;
+        match b1 {
+            OP_RGB => px = [byte()?, byte()?, byte()?, px[3]],
+            OP_RGBA => px = [byte()?, byte()?, byte()?, byte()?],
+            _ => match b1 & MASK_2 {
+                OP_INDEX => px = index[b1 as usize],
+                OP_DIFF => {
+                    let d = |shift: u8| ((b1 >> shift) & 0x03).wrapping_sub(2);
+                    px = [px[0].wrapping_add(d(4)), px[1].wrapping_add(d(2)), px[2].wrapping_add(d(0)), px[3]];
+                }
+                OP_LUMA => {
+                    let b2 = byte()?;
+                    let dg = (b1 & 0x3F).wrapping_sub(32);
+                    let dr = dg.wrapping_add(b2 >> 4).wrapping_sub(8);
+                    let db = dg.wrapping_add(b2 & 0x0F).wrapping_sub(8);
+                    px = [px[0].wrapping_add(dr), px[1].wrapping_add(dg), px[2].wrapping_add(db), px[3]];
+                }
+                _ => run = (b1 & 0x3F) as usize + 1,
+            },
+        }
+        index[hash(px)] = px;
+        for _ in 0..run.min(pixels - rgba.len() / 4) {
+            rgba.extend_from_slice(&px);
+        }
+    }
+    Ok(Image { width, height, channels, colorspace, rgba })
+}
+
+pub fn encode(img: &Image) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LEN + img.rgba.len() + END_MARKER.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&img.width.to_be_bytes());
+    out.extend_from_slice(&img.height.to_be_bytes());
+    out.push(img.channels);
+    out.push(img.colorspace);
+
+    let mut index = [[0u8; 4]; 64];
+    let mut prev = [0, 0, 0, 255u8];
+    let mut run = 0u8;
+    let pixels = img.rgba.chunks_exact(4).map(|c| [c[0], c[1], c[2], c[3]]);
+    for (i, px) in pixels.enumerate() {
+        if px == prev {
+            run += 1;
+            if run == 62 || i + 1 == img.rgba.len() / 4 {
+                out.push(OP_RUN | (run - 1));
+                run = 0;
+            }
+            continue;
+        }
+        if run > 0 {
+            out.push(OP_RUN | (run - 1));
+            run = 0;
+        }
+        let h = hash(px);
+        if index[h] == px {
+            out.push(OP_INDEX | h as u8);
+        } else {
+            index[h] = px;
+            if px[3] == prev[3] {
+                let d = |c: usize| px[c].wrapping_sub(prev[c]) as i8;
+                let (dr, dg, db) = (d(0), d(1), d(2));
+                let (dr_dg, db_dg) = (dr.wrapping_sub(dg), db.wrapping_sub(dg));
+                if (-2..=1).contains(&dr) && (-2..=1).contains(&dg) && (-2..=1).contains(&db) {
+                    out.push(OP_DIFF | ((dr + 2) as u8) << 4 | ((dg + 2) as u8) << 2 | (db + 2) as u8);
+                } else if (-8..=7).contains(&dr_dg) && (-32..=31).contains(&dg) && (-8..=7).contains(&db_dg) {
+                    out.push(OP_LUMA | (dg + 32) as u8);
+                    out.push(((dr_dg + 8) as u8) << 4 | (db_dg + 8) as u8);
+                } else {
+                    out.extend_from_slice(&[OP_RGB, px[0], px[1], px[2]]);
+                }
+            } else {
+                out.extend_from_slice(&[OP_RGBA, px[0], px[1], px[2], px[3]]);
+            }
+        }
+        prev = px;
+    }
+    out.extend_from_slice(&END_MARKER);
+    out
+}
+
+/// `encode`, then decode the result again and make sure every pixel comes back exactly. A wrong run or
+/// index op would otherwise quietly merge modified pixels into their neighbours and take the LSBs along.
+pub fn encode_verified(img: &Image) -> Result<Vec<u8>, StegError> {
+    let encoded = encode(img);
+    let back = decode(&encoded)?;
+    let changed = back.rgba.chunks_exact(4).zip(img.rgba.chunks_exact(4)).filter(|(a, b)| a != b).count();
+    if changed > 0 || back.rgba.len() != img.rgba.len() {
+        return Err(format!("QOI round trip changed {} pixels, refusing to write it", changed).into());
+    }
+    Ok(encoded)
+}
+
+/// An image `image` can read, as a QOI to encode: RGBA, with 4 channels recorded only when it had alpha.
+pub fn from_picture(path: &Path) -> Result<Image, StegError> {
+    let img = ImageReader::open(path)?.decode()?;
+    let channels = if img.color().has_alpha() { 4 } else { 3 };
+    let rgba = img.to_rgba8();
+    Ok(Image { width: rgba.width(), height: rgba.height(), channels, colorspace: 0, rgba: rgba.into_raw() })
+}
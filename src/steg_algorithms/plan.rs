@@ -0,0 +1,29 @@
+use serde::Serialize;
+
+// `hide --dry-run`: what an embedding would do to a cover, worked out as far as encoding the output in
+// memory but never written anywhere, for weighing candidate covers against each other.
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Plan {
+    /// Bits the embedding writes: the length prefix and the framed payload, times the copies.
+    pub bits: usize,
+    /// Bits the cover has room for with these settings.
+    pub capacity_bits: usize,
+    /// `bits` as a percentage of `capacity_bits`.
+    pub capacity_used: f64,
+    /// What `units` counts: "pixel" or "sample".
+    pub unit: &'static str,
+    pub units: usize,
+    /// Pixels or samples that would differ from the cover. Where the cover already has the payload's
+    /// bit nothing changes.
+    pub changed: usize,
+    /// The output's size once encoded.
+    pub output_bytes: u64,
+}
+
+impl Plan {
+    pub fn new(bits: usize, capacity_bits: usize, unit: &'static str, units: usize, changed: usize) -> Self {
+        let capacity_used = if capacity_bits == 0 { 100.0 } else { bits as f64 * 100.0 / capacity_bits as f64 };
+        Plan { bits, capacity_bits, capacity_used, unit, units, changed, output_bytes: 0 }
+    }
+}
@@ -0,0 +1,286 @@
+use std::path::Path;
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::params;
+#[cfg(feature = "audio")]
+use crate::steg_algorithms::audio::wav::lsb as wav_lsb;
+#[cfg(feature = "picture")]
+use {
+    crate::steg_algorithms::formats,
+    crate::steg_algorithms::picture::general::lsb as picture_lsb,
+    image::{DynamicImage, ImageFormat},
+};
+
+// Bit planes as a raw bitstream (`export-plane` / `import-plane`), for working on them with other tools.
+//
+// A picture's slots are its gray or RGB samples in raster order, at the depth the picture has, the same
+// numbering LSB hide uses: slot pixel * 3 + channel for color, one slot a pixel for gray, alpha never
+// included. --channels drops some of the three, leaving the order of the rest alone. A WAV's slots are its PCM16 samples, interleaved as stored. Each slot gives one bit per
+// selected plane, in the order the planes were listed, so `--bits 0,1` puts a slot's LSB before its second
+// bit. The bits are packed MSB first; the last byte is padded with zeros.
+//
+// Importing wants exactly as many bytes as exporting the same selection from the same carrier produced,
+// and changes nothing outside the selected planes.
+
+/// Which planes of which slots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Planes {
+    /// Bit positions, 0 being the LSB.
+    pub bits: Vec<u8>,
+    /// Pictures only: channel indexes into RGB, in ascending order.
+    pub channels: Vec<usize>,
+}
+
+impl Planes {
+    pub fn new(bits: &[u8], channels: &str) -> Result<Planes, StegError> {
+        if bits.is_empty() {
+            return Err("Select at least one bit plane".into());
+        }
+        if let Some(b) = bits.iter().enumerate().find_map(|(i, b)| bits[..i].contains(b).then_some(b)) {
+            return Err(format!("Bit plane {} is listed twice", b).into());
+        }
+        Ok(Planes { bits: bits.to_vec(), channels: params::channels(channels)? })
+    }
+
+    #[cfg_attr(not(any(feature = "picture", feature = "audio")), allow(dead_code))]
+    fn check_depth(&self, depth: u8, what: &str) -> Result<(), StegError> {
+        match self.bits.iter().find(|&&b| b >= depth) {
+            Some(b) => Err(format!("{} only have bit planes 0 to {}, not {}", what, depth - 1, b).into()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The selected planes of the carrier at `path`, packed.
+#[cfg_attr(not(any(feature = "picture", feature = "audio")), allow(unused_variables))]
+pub fn export(filetype: &str, path: &Path, planes: &Planes) -> Result<Vec<u8>, StegError> {
+    match filetype {
+        #[cfg(feature = "picture")]
+        "picture" => {
+            let img = picture_lsb::decode(path)?;
+            let slots = picture_slots(&img, planes)?;
+            Ok(pack(slots.iter().map(|&i| picture_lsb::sample(&img, i)), &planes.bits))
+        }
+        #[cfg(feature = "audio")]
+        "audio" => {
+            wav_only(path)?;
+            planes.check_depth(16, "PCM16 samples")?;
+            let (_, samples) = wav_lsb::read_samples(path)?;
+            Ok(pack(samples.iter().map(|&s| s as u16), &planes.bits))
+        }
+        #[cfg(not(feature = "picture"))]
+        "picture" => Err(StegError::not_built("Picture bit planes", "picture")),
+        #[cfg(not(feature = "audio"))]
+        "audio" => Err(StegError::not_built("WAV bit planes", "audio")),
+        other => Err(format!("Bit planes are only for pictures and WAV audio, not {}", other).into()),
+    }
+}
+
+/// Write `path` to `out_path` with the selected planes replaced by `plane`, as packed by `export`.
+#[cfg_attr(not(any(feature = "picture", feature = "audio")), allow(unused_variables))]
+pub fn import(filetype: &str, path: &Path, plane: &[u8], out_path: &Path, planes: &Planes) -> Result<(), StegError> {
+    let out_ext = out_path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    match filetype {
+        #[cfg(feature = "picture")]
+        "picture" => {
+            if !formats::is_lossless_picture(&out_ext) {
+                return Err(format!("A .{} output would not keep the plane's bits; use .png, .bmp or .tiff", out_ext).into());
+            }
+            let format = ImageFormat::from_extension(&out_ext).ok_or_else(|| StegError::UnsupportedFormat { found: out_ext.to_string() })?;
+            let mut img = picture_lsb::decode(path)?;
+            let slots = picture_slots(&img, planes)?;
+            let mut values: Vec<u16> = slots.iter().map(|&i| picture_lsb::sample(&img, i)).collect();
+            unpack(&mut values, plane, &planes.bits)?;
+            for (&i, v) in slots.iter().zip(values) {
+                picture_lsb::set_sample(&mut img, i, v);
+            }
+            // the planes only mean anything in the samples they came from, so no converting on the way out
+            let color = img.color();
+            let img = picture_lsb::fit(img, format);
+            if img.color() != color {
+                return Err(format!("A .{} output can't hold this picture's {:?} samples as they are; use .png", out_ext, color).into());
+            }
+            picture_lsb::save(&img, out_path, format)
+        }
+        #[cfg(feature = "audio")]
+        "audio" => {
+            wav_only(path)?;
+            if out_ext != "wav" && out_ext != "wave" {
+                return Err(format!("Audio planes can only be written to .wav, not .{}", out_ext).into());
+            }
+            planes.check_depth(16, "PCM16 samples")?;
+            let (spec, samples) = wav_lsb::read_samples(path)?;
+            let mut values: Vec<u16> = samples.iter().map(|&s| s as u16).collect();
+            unpack(&mut values, plane, &planes.bits)?;
+            let samples: Vec<i16> = values.into_iter().map(|v| v as i16).collect();
+            wav_lsb::write_samples(out_path, spec, &samples)
+        }
+        #[cfg(not(feature = "picture"))]
+        "picture" => Err(StegError::not_built("Picture bit planes", "picture")),
+        #[cfg(not(feature = "audio"))]
+        "audio" => Err(StegError::not_built("WAV bit planes", "audio")),
+        other => Err(format!("Bit planes are only for pictures and WAV audio, not {}", other).into()),
+    }
+}
+
+#[cfg(feature = "audio")]
+fn wav_only(path: &Path) -> Result<(), StegError> {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    if ext == "wav" || ext == "wave" {
+        Ok(())
+    } else {
+        Err(format!("Audio planes are only read from WAV, not .{}", ext).into())
+    }
+}
+
+// sample indexes of the selected channels of a picture as lsb reads it, in slot order, once the
+// selected bits are known to be in its samples
+#[cfg(feature = "picture")]
+fn picture_slots(img: &DynamicImage, planes: &Planes) -> Result<Vec<usize>, StegError> {
+    let (slots, depth) = picture_lsb::channel_samples(img, &planes.channels)?;
+    planes.check_depth(depth, &format!("{}-bit pictures", depth))?;
+    Ok(slots)
+}
+
+#[cfg_attr(not(any(feature = "picture", feature = "audio")), allow(dead_code))]
+fn pack(values: impl Iterator<Item = u16>, bits: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut n = 0usize;
+    for v in values {
+        for &b in bits {
+            if n.is_multiple_of(8) {
+                out.push(0);
+            }
+            let last = out.len() - 1;
+            out[last] |= (((v >> b) & 1) as u8) << (7 - n % 8);
+            n += 1;
+        }
+    }
+    out
+}
+
+#[cfg_attr(not(any(feature = "picture", feature = "audio")), allow(dead_code))]
+fn unpack(values: &mut [u16], plane: &[u8], bits: &[u8]) -> Result<(), StegError> {
+    let need = (values.len() * bits.len()).div_ceil(8);
+    if plane.len() != need {
+        return Err(format!("The plane file is {} bytes but this carrier and selection take exactly {}", plane.len(), need).into());
+    }
+    let mut n = 0usize;
+    for v in values.iter_mut() {
+        for &b in bits {
+            let bit = ((plane[n / 8] >> (7 - n % 8)) & 1) as u16;
+            *v = (*v & !(1 << b)) | (bit << b);
+            n += 1;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "picture")]
+    use image::{GrayImage, ImageBuffer, Luma, Rgb, Rgba, RgbaImage};
+
+    #[test]
+    fn selection_is_validated() {
+        assert_eq!(Planes::new(&[0], "BR").unwrap().channels, vec![0, 2]);
+        assert!(Planes::new(&[0, 1, 0], "rgb").unwrap_err().to_string().contains("twice"));
+        assert!(Planes::new(&[0], "rgx").unwrap_err().to_string().contains("'x'"));
+        assert!(Planes::new(&[], "rgb").is_err());
+    }
+
+    #[cfg(feature = "picture")]
+    #[test]
+    fn picture_plane_matches_lsb_hide_and_roundtrips() {
+        let dir = tempfile::tempdir().unwrap();
+        let cover = dir.path().join("cover.png");
+        RgbaImage::from_fn(32, 32, |x, y| Rgba([(x * 7) as u8, (y * 5) as u8, (x ^ y) as u8, 255])).save(&cover).unwrap();
+        let stego = dir.path().join("stego.png");
+        picture_lsb::hide(&cover, b"plane", &stego).unwrap();
+
+        // the LSB plane of all three channels is exactly what hide wrote: length header, then the data
+        let lsb = export("picture", &stego, &Planes::new(&[0], "rgb").unwrap()).unwrap();
+        assert_eq!(lsb.len(), 32 * 32 * 3 / 8);
+        let found = picture_lsb::find_bytes(&stego).unwrap();
+        assert_eq!(lsb[..4], (found.len() as u32).to_be_bytes());
+        assert_eq!(&lsb[4..4 + found.len()], &found[..]);
+
+        let planes = Planes::new(&[1, 0], "gb").unwrap();
+        let mut plane = export("picture", &cover, &planes).unwrap();
+        plane.iter_mut().for_each(|b| *b = !*b);
+        let out = dir.path().join("out.png");
+        import("picture", &cover, &plane, &out, &planes).unwrap();
+        assert_eq!(export("picture", &out, &planes).unwrap(), plane);
+        // red and the upper bits are untouched
+        let (a, b) = (image::open(&cover).unwrap().to_rgba8(), image::open(&out).unwrap().to_rgba8());
+        for (p, q) in a.pixels().zip(b.pixels()) {
+            assert_eq!(p[0], q[0]);
+            assert_eq!(p[1] & !3, q[1] & !3);
+            assert_eq!(p[1] & 3, !q[1] & 3);
+        }
+
+        assert!(import("picture", &cover, &plane[1..], &out, &planes).unwrap_err().to_string().contains("exactly"));
+        assert!(import("picture", &cover, &plane, &dir.path().join("out.jpg"), &planes).is_err());
+        assert!(export("picture", &cover, &Planes::new(&[8], "rgb").unwrap()).is_err());
+    }
+
+    #[cfg(feature = "picture")]
+    #[test]
+    fn gray_and_16_bit_planes_are_the_samples_lsb_hide_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let gray = DynamicImage::ImageLuma8(GrayImage::from_fn(32, 32, |x, y| Luma([(x * 7 + y) as u8])));
+        let deep = DynamicImage::ImageRgb16(ImageBuffer::from_fn(32, 32, |x, y| Rgb([x as u16 * 2000 + 3, y as u16 * 1500, 60000])));
+        for (name, cover, slots) in [("gray.png", gray, 32 * 32), ("deep.png", deep, 32 * 32 * 3)] {
+            let (path, stego) = (dir.path().join(name), dir.path().join(format!("stego-{}", name)));
+            cover.save(&path).unwrap();
+            picture_lsb::hide(&path, b"native depth", &stego).unwrap();
+
+            // one slot a gray pixel, and the 16-bit samples' own LSBs
+            let lsb = export("picture", &stego, &Planes::new(&[0], "rgb").unwrap()).unwrap();
+            assert_eq!(lsb.len(), slots / 8, "{}", name);
+            let found = picture_lsb::find_bytes(&stego).unwrap();
+            assert_eq!(lsb[..4], (found.len() as u32).to_be_bytes());
+            assert_eq!(&lsb[4..4 + found.len()], &found[..]);
+
+            // an import keeps the picture as it was, and only the selected bits change
+            let planes = Planes::new(&[0], "rgb").unwrap();
+            let out = dir.path().join(format!("out-{}", name));
+            import("picture", &stego, &vec![0u8; lsb.len()], &out, &planes).unwrap();
+            let back = image::open(&out).unwrap();
+            assert_eq!(back.color(), cover.color());
+            assert!(export("picture", &out, &planes).unwrap().iter().all(|&b| b == 0));
+        }
+
+        // 16-bit samples have planes up to 15, 8-bit ones up to 7
+        let deep = dir.path().join("stego-deep.png");
+        assert_eq!(export("picture", &deep, &Planes::new(&[15], "r").unwrap()).unwrap().len(), 32 * 32 / 8);
+        let gray = dir.path().join("stego-gray.png");
+        assert!(export("picture", &gray, &Planes::new(&[8], "rgb").unwrap()).unwrap_err().to_string().contains("8-bit pictures only have bit planes 0 to 7"));
+        assert!(export("picture", &gray, &Planes::new(&[0], "g").unwrap()).unwrap_err().to_string().contains("grayscale"));
+        assert!(import("picture", &deep, &[0; 384], &dir.path().join("out.bmp"), &Planes::new(&[0], "rgb").unwrap()).unwrap_err().to_string().contains(".png"));
+    }
+
+    #[cfg(feature = "audio")]
+    #[test]
+    fn wav_plane_roundtrips() {
+        let dir = tempfile::tempdir().unwrap();
+        let cover = dir.path().join("cover.wav");
+        let spec = hound::WavSpec { channels: 2, sample_rate: 8000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let samples: Vec<i16> = (0..1000).map(|i| (i * 37 % 2000 - 1000) as i16).collect();
+        wav_lsb::write_samples(&cover, spec, &samples).unwrap();
+
+        let planes = Planes::new(&[15], "rgb").unwrap();
+        let plane = export("audio", &cover, &planes).unwrap();
+        assert_eq!(plane.len(), 1000 / 8);
+        // the top bit of a sample is its sign
+        assert_eq!(plane[0] >> 7, (samples[0] < 0) as u8);
+
+        let zeros = vec![0u8; plane.len()];
+        let out = dir.path().join("out.wav");
+        import("audio", &cover, &zeros, &out, &planes).unwrap();
+        let (_, back) = wav_lsb::read_samples(&out).unwrap();
+        for (a, b) in samples.iter().zip(back) {
+            assert_eq!(*a as u16 & 0x7fff, b as u16);
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+// Progress through the slow part of hiding in or finding in a big carrier: reading and writing the
+// file. The library never prints it. A caller that wants to show it runs the work inside `report_to`,
+// and every carrier read through `open` or written through `create` in the meantime calls back with the
+// bytes done so far. The callback is per thread, so work on other threads doesn't report into it.
+
+/// Report at most once per this many bytes (and at the end of a read).
+const STEP: u64 = 256 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Reading,
+    Writing,
+}
+
+type Callback = Box<dyn FnMut(Stage, u64, Option<u64>)>;
+
+thread_local! {
+    static CALLBACK: RefCell<Option<Callback>> = const { RefCell::new(None) };
+}
+
+/// Run `work`, calling `callback(stage, bytes done, total bytes if known)` as it reads and writes
+/// carriers. A stage is over once `done` reaches the total.
+pub fn report_to<T>(callback: impl FnMut(Stage, u64, Option<u64>) + 'static, work: impl FnOnce() -> T) -> T {
+    let previous = CALLBACK.with(|c| c.replace(Some(Box::new(callback))));
+    let result = work();
+    CALLBACK.with(|c| c.replace(previous));
+    result
+}
+
+fn report(stage: Stage, done: u64, total: Option<u64>) {
+    CALLBACK.with(|c| {
+        if let Some(callback) = c.borrow_mut().as_mut() {
+            callback(stage, done, total);
+        }
+    });
+}
+
+/// A reader or writer that counts the bytes through it and reports them.
+pub struct Counted<T> {
+    inner: T,
+    stage: Stage,
+    done: u64,
+    total: Option<u64>,
+    reported: u64,
+}
+
+impl<T> Counted<T> {
+    /// Reports the start of the stage, as done == 0.
+    pub fn new(inner: T, stage: Stage, total: Option<u64>) -> Self {
+        report(stage, 0, total);
+        Counted { inner, stage, done: 0, total, reported: 0 }
+    }
+
+    fn advance(&mut self, n: usize) {
+        self.done += n as u64;
+        if self.done >= self.reported + STEP || (n > 0 && Some(self.done) == self.total) {
+            self.reported = self.done;
+            report(self.stage, self.done, self.total);
+        }
+    }
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.advance(n);
+        Ok(n)
+    }
+}
+
+impl<W: Write> Write for Counted<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.advance(n);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// decoders skip around and encoders go back to patch headers, the count is the furthest point reached
+impl<T: Seek> Seek for Counted<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let at = self.inner.seek(pos)?;
+        self.done = self.done.max(at);
+        Ok(at)
+    }
+}
+
+// the end of a stage is reported as done == total, also when the total wasn't known
+impl<T> Drop for Counted<T> {
+    fn drop(&mut self) {
+        if self.done > 0 && self.reported != self.done {
+            report(self.stage, self.done, Some(self.done));
+        }
+    }
+}
+
+/// Open a carrier for reading, reporting against its size.
+pub fn open(path: &Path) -> io::Result<BufReader<Counted<File>>> {
+    let file = File::open(path)?;
+    let total = file.metadata()?.len();
+    Ok(BufReader::new(Counted::new(file, Stage::Reading, Some(total))))
+}
+
+/// Write a carrier to `out` (a file `atomic::write_with` hands over), `total` being its expected size if
+/// that's known up front.
+pub fn create<W: Write>(out: W, total: Option<u64>) -> BufWriter<Counted<W>> {
+    BufWriter::new(Counted::new(out, Stage::Writing, total))
+}
+
+#[cfg(all(test, feature = "picture"))]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+    use tempfile::tempdir;
+    use crate::steg_algorithms::picture::general::lsb;
+
+    #[test]
+    fn reports_only_inside_report_to() {
+        let dir = tempdir().unwrap();
+        let (cover, out) = (dir.path().join("c.png"), dir.path().join("o.png"));
+        // noisy enough that the PNG is a few STEPs big
+        image::RgbImage::from_fn(600, 600, |x, y| image::Rgb([((x * 7) ^ (y * 13)) as u8, (x * y) as u8, (x + y * 3) as u8])).save(&cover).unwrap();
+        let size = std::fs::metadata(&cover).unwrap().len();
+        assert!(size > 2 * STEP);
+
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&seen);
+        report_to(move |stage, done, total| sink.borrow_mut().push((stage, done, total)), || {
+            lsb::hide_sparse(&cover, b"progress", &out, 1).unwrap();
+        });
+        let seen = seen.borrow();
+        let reads: Vec<_> = seen.iter().filter(|s| s.0 == Stage::Reading).collect();
+        assert!(reads.len() > 1, "{:?}", seen);
+        assert_eq!(reads[0].1, 0, "the start of a read is reported too");
+        assert!(reads.iter().all(|s| s.2 == Some(size)));
+        assert_eq!(reads.last().unwrap().1, size, "the end of a read is always reported");
+        let last_write = seen.iter().rev().find(|s| s.0 == Stage::Writing).unwrap();
+        assert_eq!(Some(last_write.1), last_write.2, "a write of unknown size still ends");
+
+        assert!(CALLBACK.with(|c| c.borrow().is_none()), "uninstalled afterwards");
+    }
+}
@@ -0,0 +1,65 @@
+use regex::bytes::Regex;
+use crate::steg_algorithms::delta::sha256_hex;
+use crate::steg_algorithms::error::StegError;
+
+// `find --redact-pattern`: the payload goes out with every match of the patterns replaced, for payloads
+// that are logs or personal data and get exported for someone who may know they were recovered but not
+// read them whole. The SHA-256 of the original is kept (in the report and the audit log), so whoever holds
+// the carrier can later show which payload the redacted copy came from.
+//
+// Patterns run over the raw bytes, so a binary payload is fine as long as the pattern is. They are applied
+// in the order given, each one to the output of the last.
+
+/// Replacement when --redact-with isn't given.
+pub const DEFAULT_MARK: &str = "[REDACTED]";
+
+/// For clap: compile a pattern when the command line is read.
+pub fn pattern(s: &str) -> Result<Regex, StegError> {
+    Regex::new(s).map_err(|e| format!("bad pattern: {}", e).into())
+}
+
+/// What `redact` did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redacted {
+    pub data: Vec<u8>,
+    /// Matches replaced, over all the patterns.
+    pub matches: usize,
+    pub original_sha256: String,
+}
+
+pub fn redact(data: &[u8], patterns: &[Regex], mark: &str) -> Redacted {
+    let original_sha256 = sha256_hex(data);
+    let mut out = data.to_vec();
+    let mut matches = 0;
+    for p in patterns {
+        // an empty match has nothing to hide and would put a mark between every byte
+        matches += p.find_iter(&out).filter(|m| !m.is_empty()).count();
+        out = p.replace_all(&out, |c: &regex::bytes::Captures| match c.get(0) {
+            Some(m) if m.is_empty() => Vec::new(),
+            _ => mark.as_bytes().to_vec(),
+        }).into_owned();
+    }
+    Redacted { data: out, matches, original_sha256 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_are_replaced_and_the_original_hashed() {
+        let log = b"2024-05-01 login alice@example.com from 10.0.0.7\n2024-05-01 login bob@example.org from 10.0.0.9\n";
+        let patterns = [pattern(r"[\w.]+@[\w.]+").unwrap(), pattern(r"\d+\.\d+\.\d+\.\d+").unwrap()];
+        let r = redact(log, &patterns, DEFAULT_MARK);
+        assert_eq!(
+            String::from_utf8(r.data).unwrap(),
+            "2024-05-01 login [REDACTED] from [REDACTED]\n2024-05-01 login [REDACTED] from [REDACTED]\n"
+        );
+        assert_eq!(r.matches, 4);
+        assert_eq!(r.original_sha256, sha256_hex(log));
+
+        let none = redact(b"nothing here", &[pattern("x*").unwrap()], "#");
+        assert_eq!((none.data.as_slice(), none.matches), (&b"nothing here"[..], 0));
+        assert!(pattern("(unclosed").unwrap_err().to_string().starts_with("bad pattern"));
+    }
+}
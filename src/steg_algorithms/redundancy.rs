@@ -0,0 +1,190 @@
+use crate::steg_algorithms::error::StegError;
+
+// Repetition coding for the LSB carriers (`--redundancy N`): every bit is stored N times and read back by
+// majority vote. Cheaper and dumber than `fec`, but it also survives losing a whole region, e.g. the
+// end of a clipped WAV. Works on the carrier's bit slots, in embedding order:
+//
+//   header   32-bit word (N << 24 | len), each bit repeated N times in a row
+//   copies   N copies of the len*8 data bits, one after the other, so a damaged or missing
+//            stretch of the carrier only ever hits one copy of any given bit
+//
+// N is odd and at least 3, so the header's top byte is never 0 like a plain `len | data` header's is
+// (plain lengths are far below 16 MB) and find can tell the two apart without being told N.
+
+pub const MAX_REDUNDANCY: usize = 15;
+
+/// Bits a payload of `len` bytes takes at redundancy `n` (1 = the plain layout).
+pub fn bits_needed(len: usize, n: usize) -> usize {
+    32 * n + len * 8 * n
+}
+
+/// Largest payload that fits at redundancy `n` where `plain_capacity` bytes fit plainly.
+pub fn capacity(plain_capacity: usize, n: usize) -> usize {
+    (plain_capacity + 4).saturating_sub(4 * n) / n
+}
+
+/// Payload length whose plain layout ends where the redundant one does, for helpers like `perturb`
+/// that only know the plain layout.
+pub fn plain_equivalent_len(len: usize, n: usize) -> usize {
+    (bits_needed(len, n) - 32).div_ceil(8)
+}
+
+pub fn check(n: usize) -> Result<(), StegError> {
+    if n == 1 || (n >= 3 && n % 2 == 1 && n <= MAX_REDUNDANCY) {
+        Ok(())
+    } else {
+        Err(format!("Redundancy must be 1 or an odd number from 3 to {}, got {}", MAX_REDUNDANCY, n).into())
+    }
+}
+
+fn push_bits(out: &mut Vec<u8>, bytes: &[u8], repeat: usize) {
+    for &b in bytes {
+        for i in (0..8).rev() {
+            out.extend(std::iter::repeat_n((b >> i) & 1, repeat));
+        }
+    }
+}
+
+/// The slot bits to embed for `msg`. With `n == 1` this is the plain 32-bit length + data.
+pub fn bitstream(msg: &[u8], n: usize) -> Result<Vec<u8>, StegError> {
+    check(n)?;
+    let mut bits = Vec::with_capacity(bits_needed(msg.len(), n));
+    if n == 1 {
+        push_bits(&mut bits, &(msg.len() as u32).to_be_bytes(), 1);
+        push_bits(&mut bits, msg, 1);
+        return Ok(bits);
+    }
+    if msg.len() >= 1 << 24 {
+        return Err("Payloads over 16 MB can't be stored redundantly".into());
+    }
+    push_bits(&mut bits, &((n as u32) << 24 | msg.len() as u32).to_be_bytes(), n);
+    for _ in 0..n {
+        push_bits(&mut bits, msg, 1);
+    }
+    Ok(bits)
+}
+
+/// Majority of the votes that are present; a tie (only possible when copies are missing) goes to 1.
+fn vote(votes: impl Iterator<Item = u8>) -> u8 {
+    let (ones, total) = votes.fold((0, 0), |(o, t), v| (o + v as usize, t + 1));
+    (ones * 2 >= total && total > 0) as u8
+}
+
+fn bytes_from_bits(bits: impl Iterator<Item = u8>) -> Vec<u8> {
+    let bits: Vec<u8> = bits.collect();
+    bits.chunks_exact(8).map(|c| c.iter().fold(0u8, |b, &v| (b << 1) | v)).collect()
+}
+
+/// (N, len) if the slot bits start with a redundant header.
+pub fn header(slots: &[u8]) -> Option<(usize, usize)> {
+    (3..=MAX_REDUNDANCY).step_by(2).find_map(|n| {
+        let head = slots.get(..32 * n)?;
+        let groups: Vec<&[u8]> = head.chunks_exact(n).collect();
+        // real repeats almost all agree, ordinary data cut into groups of n doesn't
+        let outvoted: usize = groups.iter().map(|g| g.iter().filter(|&&b| b == 1).count()).map(|ones| ones.min(n - ones)).sum();
+        if outvoted > 2 * n {
+            return None;
+        }
+        let word = bytes_from_bits(groups.iter().map(|g| vote(g.iter().copied())));
+        let word = u32::from_be_bytes(word.try_into().ok()?);
+        ((word >> 24) as usize == n).then_some((n, (word & 0xFF_FFFF) as usize))
+    })
+}
+
+/// Read a redundant payload back. `read(count)` gives the first `count` slot bits in embedding order
+/// (fewer if the carrier is shorter). `Ok(None)` means the slots hold a plain payload.
+pub fn find(read: impl Fn(usize) -> Vec<u8>) -> Result<Option<Vec<u8>>, StegError> {
+    Ok(find_scored(read)?.map(|(data, _)| data))
+}
+
+/// Extracted bytes and a confidence for each, see `byte_confidence`.
+pub type Scored = (Vec<u8>, Vec<f32>);
+
+/// `find`, plus a confidence for every byte from how far the copies agreed.
+pub fn find_scored(read: impl Fn(usize) -> Vec<u8>) -> Result<Option<Scored>, StegError> {
+    let Some((n, len)) = header(&read(32 * MAX_REDUNDANCY)) else {
+        return Ok(None);
+    };
+    let want = bits_needed(len, n);
+    let slots = read(want);
+    let data_bits = len * 8;
+    // a majority of the copies has to be there, anything past the end just doesn't vote
+    let complete_copies = slots.len().saturating_sub(32 * n) / data_bits.max(1);
+    if slots.len() < want && complete_copies <= n / 2 {
+        return Err(format!(
+            "Redundant payload truncated: header says {} copies of {} bytes, only {} are complete",
+            n, len, complete_copies
+        ).into());
+    }
+    let copies = &slots[32 * n..];
+    let votes = |i: usize| (0..n).filter_map(move |c| copies.get(c * data_bits + i).copied());
+    let data = bytes_from_bits((0..data_bits).map(|i| vote(votes(i))));
+    let margins: Vec<f32> = (0..data_bits)
+        .map(|i| {
+            let (ones, total) = votes(i).fold((0, 0), |(o, t), v| (o + v as usize, t + 1));
+            ones.abs_diff(total - ones) as f32 / total.max(1) as f32
+        })
+        .collect();
+    Ok(Some((data, byte_confidence(&margins))))
+}
+
+/// Per-byte confidence from per-bit vote margins (0 = the votes cancelled out, 1 = they all agreed,
+/// MSB first): a byte is as trustworthy as its shakiest bit.
+pub fn byte_confidence(margins: &[f32]) -> Vec<f32> {
+    margins.chunks_exact(8).map(|bits| bits.iter().copied().fold(1.0, f32::min)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_layout_is_unchanged_and_not_mistaken_for_redundant() {
+        let bits = bitstream(b"hi", 1).unwrap();
+        assert_eq!(bits.len(), 32 + 16);
+        assert_eq!(&bits[..32], &[[0u8; 30].as_slice(), &[1, 0]].concat()[..]);
+        assert_eq!(find(|c| bits.iter().take(c).copied().collect()).unwrap(), None);
+    }
+
+    #[test]
+    fn votes_out_flipped_and_missing_bits() {
+        let msg = b"majority rules";
+        let mut bits = bitstream(msg, 5).unwrap();
+        assert_eq!(bits.len(), bits_needed(msg.len(), 5));
+        let data_bits = msg.len() * 8;
+        // two bad copies of a few header bits and of every data bit...
+        for i in [0, 9, 31] {
+            bits[i * 5] ^= 1;
+            bits[i * 5 + 3] ^= 1;
+        }
+        for i in 0..data_bits {
+            bits[160 + (i % 5) * data_bits + i] ^= 1;
+            bits[160 + ((i + 2) % 5) * data_bits + i] ^= 1;
+        }
+        assert_eq!(find(|c| bits.iter().take(c).copied().collect()).unwrap().unwrap(), msg);
+        // every bit won 3 to 2
+        let (_, confidence) = find_scored(|c| bits.iter().take(c).copied().collect()).unwrap().unwrap();
+        assert_eq!(confidence, vec![0.2; msg.len()]);
+
+        // ...or the last copies cut off
+        let bits = bitstream(msg, 5).unwrap();
+        let clipped = &bits[..160 + 3 * data_bits + 5];
+        assert_eq!(find(|c| clipped.iter().take(c).copied().collect()).unwrap().unwrap(), msg);
+        let (_, confidence) = find_scored(|c| clipped.iter().take(c).copied().collect()).unwrap().unwrap();
+        assert!(confidence.iter().all(|&c| c == 1.0), "missing copies don't vote against");
+        let too_short = &bits[..160 + 2 * data_bits];
+        assert!(find(|c| too_short.iter().take(c).copied().collect()).unwrap_err().to_string().contains("truncated"));
+
+        // a plain payload read in groups of 3 doesn't agree with itself
+        let plain = bitstream(&[0x03, 0x55, 0xAA, 0x0F, 0xF0, 0x33, 0xCC, 0x99, 0x66, 0x5A, 0xA5, 0x3C, 0xC3], 1).unwrap();
+        assert_eq!(header(&plain), None);
+    }
+
+    #[test]
+    fn rejects_even_or_huge_redundancy() {
+        assert!(bitstream(b"x", 2).is_err());
+        assert!(bitstream(b"x", 17).is_err());
+        assert_eq!(capacity(100, 1), 100);
+        assert_eq!(capacity(100, 3), 30);
+    }
+}
@@ -0,0 +1,634 @@
+use std::path::Path;
+use crate::steg_algorithms::astro::fits;
+use crate::steg_algorithms::catalog::{self, AlgorithmInfo};
+use crate::steg_algorithms::crypto::Cipher;
+use crate::steg_algorithms::formats;
+use crate::steg_algorithms::medical::dicom;
+use crate::steg_algorithms::options::{FindOptions, HideOptions};
+use crate::steg_algorithms::picture::general::lsb::LsbOptions;
+use crate::steg_algorithms::picture::gif::app_extension;
+use crate::steg_algorithms::picture::jpg::marker_hijacking::MarkerOptions;
+use crate::steg_algorithms::redundancy;
+use crate::steg_algorithms::error::StegError;
+#[cfg(feature = "audio")]
+use crate::steg_algorithms::audio::wav;
+#[cfg(feature = "jpeg-marker")]
+use {crate::steg_algorithms::atomic, crate::steg_algorithms::picture::jpg::marker_hijacking, std::fs};
+#[cfg(feature = "picture")]
+use crate::steg_algorithms::picture::{general::{lineshift, lsb::{self, Embedding}, overlay}, raw};
+#[cfg(all(feature = "picture", feature = "jpeg-marker"))]
+use crate::steg_algorithms::picture::general::transcode;
+
+// Every algorithm behind one trait, so the CLI looks `--algorithm` up here instead of matching on
+// filetype and name in every command. Adding an algorithm means an implementation, an entry in
+// ALGORITHMS and one in `catalog::algorithms()` (a test keeps the two in step); hide, find, capacity,
+// convert, info, detect and list-algorithms pick it up from there. The checks that are about the
+// command line rather than the carrier (--offset given to something that isn't lsb, ...) stay in
+// main.rs. An algorithm whose feature was left out of the build is in LEFT_OUT instead, so asking
+// for it says which feature to build with.
+
+/// What a carrier is: its filetype (picture, audio, medical, astro) and its format, a normalized
+/// extension (see `formats::normalize_ext`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CarrierKind {
+    pub filetype: String,
+    pub format: String,
+}
+
+impl CarrierKind {
+    pub fn new(filetype: &str, format: &str) -> Self {
+        CarrierKind { filetype: filetype.to_string(), format: formats::normalize_ext(format) }
+    }
+
+    /// The `filetype` file at `path`, going by its extension.
+    pub fn of(filetype: &str, path: &Path) -> Self {
+        CarrierKind::new(filetype, path.extension().and_then(|e| e.to_str()).unwrap_or(""))
+    }
+}
+
+/// Everything the algorithms take besides the carrier and the payload. Each one reads the fields it
+/// has a use for and ignores the rest.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// The lsb algorithms' layout: stride or key, bits, channels, offset, region and WAV range.
+    pub lsb: LsbOptions,
+    /// The segments marker hides in and looks for.
+    pub marker: MarkerOptions,
+    /// Copies of the payload the lsb algorithms spread over the carrier (see `redundancy`).
+    pub copies: usize,
+    /// marker: seal each segment with this when hiding (see `marker_hijacking::hide_sealed`), open
+    /// sealed ones with it when extracting.
+    pub password: Option<String>,
+    pub cipher: Cipher,
+    /// overlay: how far the pattern moves pixel values.
+    #[cfg(feature = "picture")]
+    pub strength: u8,
+    /// lineshift: how many pixels a marked text line moves.
+    #[cfg(feature = "picture")]
+    pub shift: usize,
+    /// appext: the GIF application identifier.
+    pub app_id: [u8; 11],
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            lsb: LsbOptions::default(),
+            marker: MarkerOptions::default(),
+            copies: 1,
+            password: None,
+            cipher: Cipher::default(),
+            #[cfg(feature = "picture")]
+            strength: overlay::DEFAULT_STRENGTH,
+            #[cfg(feature = "picture")]
+            shift: lineshift::DEFAULT_SHIFT,
+            app_id: app_extension::DEFAULT_IDENTIFIER,
+        }
+    }
+}
+
+impl Options {
+    /// What the `_with` entry points take for hiding with these.
+    pub fn hide_options(&self) -> HideOptions {
+        HideOptions { lsb: self.lsb.clone(), copies: self.copies, marker: self.marker.clone(), password: self.password.clone(), cipher: self.cipher }
+    }
+
+    /// What they take for finding with these, `limit` bytes at most.
+    pub fn find_options(&self, limit: Option<usize>) -> FindOptions {
+        FindOptions { lsb: self.lsb.clone(), marker: self.marker.clone(), password: self.password.clone(), limit }
+    }
+}
+
+/// Bytes read back from a carrier, with a confidence per byte from the algorithms that vote.
+pub type Extracted = (Vec<u8>, Option<Vec<f32>>);
+
+pub trait StegAlgorithm: Sync {
+    /// The name `--algorithm` takes.
+    fn name(&self) -> &'static str;
+
+    /// The filetype the algorithm is for; names are only unique within one.
+    fn filetype(&self) -> &'static str;
+
+    /// Whether a payload can live in a `carrier`, i.e. whether reading one back from it makes sense.
+    /// Hiding may take more (marker re-encodes other pictures as JPEG), the output has to be one.
+    fn supports(&self, carrier: &CarrierKind) -> bool;
+
+    /// Write the cover at `cover` with `payload` in it to `out`.
+    fn hide(&self, cover: &Path, payload: &[u8], out: &Path, opts: &Options) -> Result<(), StegError>;
+
+    /// The payload `hide` put into the carrier at `path`, or its first `limit` bytes if the algorithm
+    /// can stop early.
+    fn extract(&self, path: &Path, opts: &Options, limit: Option<usize>) -> Result<Extracted, StegError>;
+
+    /// Bytes the carrier at `path` takes with `opts` (after the algorithm's own length header).
+    fn capacity(&self, path: &Path, opts: &Options) -> Result<usize, StegError>;
+
+    /// What `capacity` depends on, in words.
+    fn limited_by(&self) -> &'static str;
+
+    /// Whether the payload is a frame (see `payload`) rather than bare bytes, which is what `detect`
+    /// can recognize and the framing options apply to.
+    fn framed(&self) -> bool {
+        true
+    }
+
+    /// Its description in `catalog`.
+    fn info(&self) -> AlgorithmInfo {
+        catalog::algorithms()
+            .into_iter()
+            .find(|a| a.filetype == self.filetype() && a.name == self.name())
+            .expect("every registered algorithm is in the catalog")
+    }
+}
+
+static ALGORITHMS: &[&dyn StegAlgorithm] = &[
+    #[cfg(feature = "picture")]
+    &PictureLsb,
+    #[cfg(feature = "jpeg-marker")]
+    &Marker,
+    #[cfg(feature = "picture")]
+    &Overlay,
+    #[cfg(feature = "picture")]
+    &Lineshift,
+    &AppExt,
+    #[cfg(feature = "audio")]
+    &WavLsb,
+    #[cfg(feature = "audio")]
+    &Beat,
+    &DicomTag,
+    &DicomLsb,
+    &FitsLsb,
+    &FitsCards,
+];
+
+// filetype, name and the feature that builds it
+static LEFT_OUT: &[(&str, &str, &str)] = &[
+    #[cfg(not(feature = "picture"))]
+    ("picture", "lsb", "picture"),
+    #[cfg(not(feature = "jpeg-marker"))]
+    ("picture", "marker", "jpeg-marker"),
+    #[cfg(not(feature = "picture"))]
+    ("picture", "overlay", "picture"),
+    #[cfg(not(feature = "picture"))]
+    ("picture", "lineshift", "picture"),
+    #[cfg(not(feature = "audio"))]
+    ("audio", "lsb", "audio"),
+    #[cfg(not(feature = "audio"))]
+    ("audio", "beat", "audio"),
+];
+
+/// Every algorithm in this build, grouped by filetype in the order `list-algorithms` shows them.
+pub fn all() -> &'static [&'static dyn StegAlgorithm] {
+    ALGORITHMS
+}
+
+/// The algorithms for `filetype`.
+pub fn for_filetype(filetype: &str) -> impl Iterator<Item = &'static dyn StegAlgorithm> + '_ {
+    ALGORITHMS.iter().copied().filter(move |a| a.filetype() == filetype)
+}
+
+/// The `filetype` algorithm called `name`.
+pub fn get(filetype: &str, name: &str) -> Result<&'static dyn StegAlgorithm, StegError> {
+    if let Some(found) = for_filetype(filetype).find(|a| a.name() == name) {
+        return Ok(found);
+    }
+    if let Some((_, _, feature)) = LEFT_OUT.iter().find(|(ft, n, _)| *ft == filetype && *n == name) {
+        return Err(StegError::not_built(format!("The {} {} algorithm", filetype, name), feature));
+    }
+    let names: Vec<&str> = for_filetype(filetype).map(|a| a.name()).collect();
+    if names.is_empty() {
+        return Err(format!("There are no {} algorithms yet. Available: {}", filetype, available()).into());
+    }
+    Err(format!("Unsupported algorithm '{}' for {}; {} has {}", name, filetype, filetype, names.join(", ")).into())
+}
+
+/// Every filetype with its algorithms, e.g. "picture (lsb, marker); audio (lsb)".
+pub fn available() -> String {
+    let mut filetypes: Vec<&str> = ALGORITHMS.iter().map(|a| a.filetype()).collect();
+    filetypes.dedup();
+    filetypes
+        .into_iter()
+        .map(|ft| format!("{} ({})", ft, for_filetype(ft).map(|a| a.name()).collect::<Vec<_>>().join(", ")))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+fn stride(opts: &Options) -> usize {
+    opts.lsb.stride.unwrap_or(1)
+}
+
+fn key(opts: &Options) -> Option<&str> {
+    opts.lsb.key.as_deref()
+}
+
+#[cfg(any(feature = "picture", feature = "jpeg-marker"))]
+fn ext(path: &Path) -> String {
+    path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase()
+}
+
+fn plain(data: Vec<u8>) -> Extracted {
+    (data, None)
+}
+
+#[cfg(feature = "picture")]
+struct PictureLsb;
+
+#[cfg(feature = "picture")]
+impl PictureLsb {
+    // the raw formats (netpbm, farbfeld, QOI) go through `raw` when the output stays one, or is QOI,
+    // which the image crate can't write
+    fn raw(cover: &Path, out: &Path) -> bool {
+        let (raw_in, raw_out) = (raw::Format::from_extension(&ext(cover)), raw::Format::from_extension(&ext(out)));
+        raw_out == Some(raw::Format::Qoi) || (raw_in.is_some() && raw_in == raw_out)
+    }
+}
+
+#[cfg(feature = "picture")]
+impl StegAlgorithm for PictureLsb {
+    fn name(&self) -> &'static str { "lsb" }
+    fn filetype(&self) -> &'static str { "picture" }
+
+    fn supports(&self, carrier: &CarrierKind) -> bool {
+        carrier.filetype == "picture" && formats::is_lossless_picture(&carrier.format)
+    }
+
+    fn hide(&self, cover: &Path, payload: &[u8], out: &Path, opts: &Options) -> Result<(), StegError> {
+        if !PictureLsb::raw(cover, out) {
+            return lsb::hide_with(cover, payload, out, &opts.hide_options());
+        }
+        if !opts.lsb.plain_layout() || opts.lsb.embedding != Embedding::Replace {
+            return Err(format!("lsb bits, channels, alpha, traversal and matching aren't supported for .{} files", ext(cover)).into());
+        }
+        raw::hide(cover, payload, out, stride(opts), key(opts), opts.copies)
+    }
+
+    fn extract(&self, path: &Path, opts: &Options, limit: Option<usize>) -> Result<Extracted, StegError> {
+        if !raw::handles(path) {
+            return lsb::find_with(path, &opts.find_options(limit));
+        }
+        if opts.lsb.offset > 0 {
+            return Err(format!("--offset isn't supported for {}", path.display()).into());
+        }
+        if opts.lsb.region.is_some() {
+            return Err(format!("--region isn't supported for {}", path.display()).into());
+        }
+        if !opts.lsb.plain_layout() {
+            return Err(format!("lsb bits, channels, alpha, traversal and matching aren't supported for {}", path.display()).into());
+        }
+        raw::find_scored(path, opts.lsb.stride, key(opts))
+    }
+
+    fn capacity(&self, path: &Path, opts: &Options) -> Result<usize, StegError> {
+        if !raw::handles(path) {
+            return lsb::capacity(path, &opts.hide_options());
+        }
+        Ok(redundancy::capacity(raw::capacity(path, stride(opts))?, opts.copies))
+    }
+
+    fn limited_by(&self) -> &'static str { "the pixel count" }
+}
+
+#[cfg(feature = "jpeg-marker")]
+struct Marker;
+
+#[cfg(feature = "jpeg-marker")]
+impl Marker {
+    // other pictures are re-encoded first, which takes the image crate
+    #[cfg(feature = "picture")]
+    fn to_jpeg(cover: &Path) -> Result<Vec<u8>, StegError> {
+        transcode::to_jpeg(cover, 90)
+    }
+
+    #[cfg(not(feature = "picture"))]
+    fn to_jpeg(cover: &Path) -> Result<Vec<u8>, StegError> {
+        Err(StegError::not_built(format!("Re-encoding .{} as JPEG", ext(cover)), "picture"))
+    }
+}
+
+#[cfg(feature = "jpeg-marker")]
+impl StegAlgorithm for Marker {
+    fn name(&self) -> &'static str { "marker" }
+    fn filetype(&self) -> &'static str { "picture" }
+
+    fn supports(&self, carrier: &CarrierKind) -> bool {
+        carrier.filetype == "picture" && formats::is_jpeg(&carrier.format)
+    }
+
+    fn hide(&self, cover: &Path, payload: &[u8], out: &Path, opts: &Options) -> Result<(), StegError> {
+        let jpeg = if formats::is_jpeg(&ext(cover)) {
+            fs::read(cover).map_err(|e| StegError::io(format!("Failed to read {}", cover.display()), e))?
+        } else {
+            Marker::to_jpeg(cover)?
+        };
+        atomic::write(out, marker_hijacking::hide_bytes_with(&jpeg, payload, &opts.hide_options())?)
+    }
+
+    fn extract(&self, path: &Path, opts: &Options, _limit: Option<usize>) -> Result<Extracted, StegError> {
+        if !formats::is_jpeg(&ext(path)) {
+            return Err("You can only use marker hijacking with jpeg files >:(".into());
+        }
+        marker_hijacking::find_with(path, &opts.find_options(None)).map(plain)
+    }
+
+    fn capacity(&self, _path: &Path, _opts: &Options) -> Result<usize, StegError> {
+        Ok(marker_hijacking::capacity())
+    }
+
+    fn limited_by(&self) -> &'static str { "the 65535-segment limit, not the picture" }
+}
+
+#[cfg(feature = "picture")]
+struct Overlay;
+
+#[cfg(feature = "picture")]
+impl StegAlgorithm for Overlay {
+    fn name(&self) -> &'static str { "overlay" }
+    fn filetype(&self) -> &'static str { "picture" }
+
+    fn supports(&self, carrier: &CarrierKind) -> bool {
+        carrier.filetype == "picture"
+    }
+
+    fn hide(&self, cover: &Path, payload: &[u8], out: &Path, opts: &Options) -> Result<(), StegError> {
+        overlay::hide(cover, payload, out, opts.strength)
+    }
+
+    fn extract(&self, path: &Path, _opts: &Options, _limit: Option<usize>) -> Result<Extracted, StegError> {
+        overlay::find_scored(path).map(|(data, confidence)| (data, Some(confidence)))
+    }
+
+    fn capacity(&self, _path: &Path, _opts: &Options) -> Result<usize, StegError> {
+        Ok(overlay::MAX_PAYLOAD)
+    }
+
+    fn limited_by(&self) -> &'static str { "the overlay grid" }
+}
+
+#[cfg(feature = "picture")]
+struct Lineshift;
+
+#[cfg(feature = "picture")]
+impl StegAlgorithm for Lineshift {
+    fn name(&self) -> &'static str { "lineshift" }
+    fn filetype(&self) -> &'static str { "picture" }
+
+    fn supports(&self, carrier: &CarrierKind) -> bool {
+        carrier.filetype == "picture" && formats::is_lossless_picture(&carrier.format)
+    }
+
+    fn hide(&self, cover: &Path, payload: &[u8], out: &Path, opts: &Options) -> Result<(), StegError> {
+        lineshift::hide(cover, payload, out, opts.shift)
+    }
+
+    fn extract(&self, path: &Path, _opts: &Options, _limit: Option<usize>) -> Result<Extracted, StegError> {
+        lineshift::find_payload(path).map(plain)
+    }
+
+    fn capacity(&self, path: &Path, _opts: &Options) -> Result<usize, StegError> {
+        lineshift::capacity(path)
+    }
+
+    fn limited_by(&self) -> &'static str { "the number of text lines" }
+
+    // a page only holds a few bits, the framing header alone wouldn't fit
+    fn framed(&self) -> bool {
+        false
+    }
+}
+
+struct AppExt;
+
+impl StegAlgorithm for AppExt {
+    fn name(&self) -> &'static str { "appext" }
+    fn filetype(&self) -> &'static str { "picture" }
+
+    fn supports(&self, carrier: &CarrierKind) -> bool {
+        carrier.filetype == "picture" && formats::is_gif(&carrier.format)
+    }
+
+    fn hide(&self, cover: &Path, payload: &[u8], out: &Path, opts: &Options) -> Result<(), StegError> {
+        app_extension::hide(cover, payload, out, &opts.app_id)
+    }
+
+    fn extract(&self, path: &Path, opts: &Options, _limit: Option<usize>) -> Result<Extracted, StegError> {
+        app_extension::find_payload(path, &opts.app_id).map(plain)
+    }
+
+    fn capacity(&self, _path: &Path, _opts: &Options) -> Result<usize, StegError> {
+        Ok(app_extension::capacity())
+    }
+
+    fn limited_by(&self) -> &'static str { "the 65535-block limit, not the picture" }
+}
+
+#[cfg(feature = "audio")]
+fn is_wav(carrier: &CarrierKind) -> bool {
+    carrier.filetype == "audio" && matches!(carrier.format.as_str(), "wav" | "wave")
+}
+
+#[cfg(feature = "audio")]
+struct WavLsb;
+
+#[cfg(feature = "audio")]
+impl StegAlgorithm for WavLsb {
+    fn name(&self) -> &'static str { "lsb" }
+    fn filetype(&self) -> &'static str { "audio" }
+
+    fn supports(&self, carrier: &CarrierKind) -> bool {
+        is_wav(carrier)
+    }
+
+    fn hide(&self, cover: &Path, payload: &[u8], out: &Path, opts: &Options) -> Result<(), StegError> {
+        wav::lsb::hide_wav_with(cover, out, payload, &opts.hide_options())
+    }
+
+    fn extract(&self, path: &Path, opts: &Options, limit: Option<usize>) -> Result<Extracted, StegError> {
+        wav::lsb::find_wav_with(path, &opts.find_options(limit))
+    }
+
+    fn capacity(&self, path: &Path, opts: &Options) -> Result<usize, StegError> {
+        wav::lsb::capacity_in(path, stride(opts), opts.lsb.range.as_ref()).map(|c| redundancy::capacity(c, opts.copies))
+    }
+
+    fn limited_by(&self) -> &'static str { "the sample count" }
+}
+
+#[cfg(feature = "audio")]
+struct Beat;
+
+#[cfg(feature = "audio")]
+impl StegAlgorithm for Beat {
+    fn name(&self) -> &'static str { "beat" }
+    fn filetype(&self) -> &'static str { "audio" }
+
+    fn supports(&self, carrier: &CarrierKind) -> bool {
+        is_wav(carrier)
+    }
+
+    fn hide(&self, cover: &Path, payload: &[u8], out: &Path, _opts: &Options) -> Result<(), StegError> {
+        wav::beat::hide(cover, out, payload)
+    }
+
+    fn extract(&self, path: &Path, _opts: &Options, _limit: Option<usize>) -> Result<Extracted, StegError> {
+        wav::beat::find(path).map(plain)
+    }
+
+    fn capacity(&self, path: &Path, _opts: &Options) -> Result<usize, StegError> {
+        wav::beat::capacity(path)
+    }
+
+    fn limited_by(&self) -> &'static str { "the number of beats" }
+}
+
+struct DicomTag;
+
+impl StegAlgorithm for DicomTag {
+    fn name(&self) -> &'static str { "tag" }
+    fn filetype(&self) -> &'static str { "medical" }
+
+    fn supports(&self, carrier: &CarrierKind) -> bool {
+        carrier.filetype == "medical"
+    }
+
+    fn hide(&self, cover: &Path, payload: &[u8], out: &Path, _opts: &Options) -> Result<(), StegError> {
+        dicom::hide_tag(cover, payload, out)
+    }
+
+    fn extract(&self, path: &Path, _opts: &Options, _limit: Option<usize>) -> Result<Extracted, StegError> {
+        dicom::find_tag(path).map(plain)
+    }
+
+    fn capacity(&self, _path: &Path, _opts: &Options) -> Result<usize, StegError> {
+        Ok(dicom::tag_capacity())
+    }
+
+    fn limited_by(&self) -> &'static str { "the 4 GB element length, not the image" }
+}
+
+struct DicomLsb;
+
+impl StegAlgorithm for DicomLsb {
+    fn name(&self) -> &'static str { "lsb" }
+    fn filetype(&self) -> &'static str { "medical" }
+
+    fn supports(&self, carrier: &CarrierKind) -> bool {
+        carrier.filetype == "medical"
+    }
+
+    fn hide(&self, cover: &Path, payload: &[u8], out: &Path, opts: &Options) -> Result<(), StegError> {
+        dicom::hide_lsb(cover, payload, out, stride(opts), key(opts), opts.copies)
+    }
+
+    fn extract(&self, path: &Path, opts: &Options, _limit: Option<usize>) -> Result<Extracted, StegError> {
+        dicom::find_lsb(path, opts.lsb.stride, key(opts)).map(plain)
+    }
+
+    fn capacity(&self, path: &Path, opts: &Options) -> Result<usize, StegError> {
+        dicom::capacity(path, stride(opts)).map(|c| redundancy::capacity(c, opts.copies))
+    }
+
+    fn limited_by(&self) -> &'static str { "the pixel count" }
+}
+
+struct FitsLsb;
+
+impl StegAlgorithm for FitsLsb {
+    fn name(&self) -> &'static str { "lsb" }
+    fn filetype(&self) -> &'static str { "astro" }
+
+    fn supports(&self, carrier: &CarrierKind) -> bool {
+        carrier.filetype == "astro"
+    }
+
+    fn hide(&self, cover: &Path, payload: &[u8], out: &Path, opts: &Options) -> Result<(), StegError> {
+        fits::hide_lsb(cover, payload, out, stride(opts), key(opts), opts.copies)
+    }
+
+    fn extract(&self, path: &Path, opts: &Options, _limit: Option<usize>) -> Result<Extracted, StegError> {
+        fits::find_lsb(path, opts.lsb.stride, key(opts)).map(plain)
+    }
+
+    fn capacity(&self, path: &Path, opts: &Options) -> Result<usize, StegError> {
+        fits::capacity(path, stride(opts)).map(|c| redundancy::capacity(c, opts.copies))
+    }
+
+    fn limited_by(&self) -> &'static str { "the finite floating-point samples" }
+}
+
+struct FitsCards;
+
+impl StegAlgorithm for FitsCards {
+    fn name(&self) -> &'static str { "cards" }
+    fn filetype(&self) -> &'static str { "astro" }
+
+    fn supports(&self, carrier: &CarrierKind) -> bool {
+        carrier.filetype == "astro"
+    }
+
+    fn hide(&self, cover: &Path, payload: &[u8], out: &Path, _opts: &Options) -> Result<(), StegError> {
+        fits::hide_cards(cover, payload, out)
+    }
+
+    fn extract(&self, path: &Path, _opts: &Options, _limit: Option<usize>) -> Result<Extracted, StegError> {
+        fits::find_cards(path).map(plain)
+    }
+
+    fn capacity(&self, _path: &Path, _opts: &Options) -> Result<usize, StegError> {
+        Ok(fits::cards_capacity())
+    }
+
+    fn limited_by(&self) -> &'static str { "the 4-byte length, not the image" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_registry_and_the_catalog_list_the_same_algorithms() {
+        let registered: Vec<(&str, &str)> = all().iter().map(|a| (a.filetype(), a.name())).collect();
+        let described: Vec<(&str, &str)> = catalog::algorithms().iter().map(|a| (a.filetype, a.name)).collect();
+        assert_eq!(registered, described);
+        assert!(all().iter().all(|a| a.info().framed == a.framed()));
+        assert!(LEFT_OUT.iter().all(|&(ft, name, _)| for_filetype(ft).all(|a| a.name() != name)));
+    }
+
+    #[cfg(all(feature = "picture", feature = "audio", feature = "jpeg-marker"))]
+    #[test]
+    fn lookups_name_what_there_is() {
+        assert_eq!(get("audio", "lsb").unwrap().limited_by(), "the sample count");
+        assert_eq!(get("audio", "marker").err().unwrap().to_string(), "Unsupported algorithm 'marker' for audio; audio has lsb, beat");
+        assert!(get("video", "lsb").err().unwrap().to_string().starts_with("There are no video algorithms yet. Available: picture (lsb, marker, overlay, lineshift, appext); audio (lsb, beat)"));
+    }
+
+    #[cfg(not(feature = "audio"))]
+    #[test]
+    fn algorithms_left_out_say_which_feature_builds_them() {
+        let e = get("audio", "lsb").err().unwrap().to_string();
+        assert!(e.contains("built without audio support (cargo build --features audio)"), "{}", e);
+        assert!(get("audio", "nope").is_err());
+    }
+
+    #[cfg(all(feature = "picture", feature = "jpeg-marker"))]
+    #[test]
+    fn each_picture_algorithm_reads_back_what_it_hid() {
+        use image::{Rgb, RgbImage};
+        use tempfile::tempdir;
+
+        let dir = tempdir().unwrap();
+        let cover = dir.path().join("c.png");
+        RgbImage::from_fn(300, 300, |x, y| Rgb([(x * 255 / 300) as u8, (y * 255 / 300) as u8, 90])).save(&cover).unwrap();
+        let png = CarrierKind::of("picture", &cover);
+        let opts = Options::default();
+        for (alg, out) in [("lsb", "s.png"), ("marker", "s.jpg"), ("overlay", "s.png"), ("lsb", "s.qoi")] {
+            let alg = get("picture", alg).unwrap();
+            let out = dir.path().join(out);
+            alg.hide(&cover, b"registered", &out, &opts).unwrap();
+            assert!(alg.supports(&CarrierKind::of("picture", &out)));
+            assert_eq!(alg.extract(&out, &opts, None).unwrap().0, b"registered", "{}", alg.name());
+        }
+        let supported: Vec<&str> = for_filetype("picture").filter(|a| a.supports(&png)).map(|a| a.name()).collect();
+        assert_eq!(supported, ["lsb", "overlay", "lineshift"]);
+        assert!(!get("picture", "marker").unwrap().supports(&png));
+    }
+}
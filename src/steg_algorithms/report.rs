@@ -0,0 +1,295 @@
+use std::path::PathBuf;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::{Serialize, Serializer};
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::payload::{Auth, Meta};
+
+// The results of find and capacity as data, and the `--json` envelope around them (detect's `Report`
+// goes in the same envelope). `ok` is false only when the command failed, with `error` saying why;
+// `warnings` has the notes that went to stderr along the way.
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Response<T> {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub warnings: Vec<String>,
+    #[serde(flatten)]
+    pub result: Option<T>,
+}
+
+impl<T: Serialize> Response<T> {
+    pub fn new(result: Result<T, StegError>, warnings: Vec<String>) -> Self {
+        match result {
+            Ok(r) => Response { ok: true, error: None, warnings, result: Some(r) },
+            Err(e) => Response { ok: false, error: Some(e.to_string()), warnings, result: None },
+        }
+    }
+
+    /// One line of JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("reports always serialize")
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FindReport {
+    pub filetype: String,
+    pub algorithm: String,
+    /// The filename stored with the payload.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub payload_size: usize,
+    /// The payload itself, unless it went to a file or the clipboard.
+    #[serde(rename = "payload_base64", serialize_with = "base64_bytes", skip_serializing_if = "Option::is_none")]
+    pub payload: Option<Vec<u8>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub written: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hmac: Option<Auth>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<MetaReport>,
+    /// How sure the extraction was of the carried bytes, for the algorithms that vote.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<ConfidenceReport>,
+    /// The named payloads, when the carrier holds a table and none was picked with --name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entries: Option<Vec<Entry>>,
+    /// With --redact-pattern: the SHA-256 of the payload before redaction (`payload_size` and
+    /// `payload_base64` are of the redacted copy).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_sha256: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redactions: Option<usize>,
+    /// With --max-bytes: only the start of the payload was read, `total_len` bytes being all of it.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_len: Option<usize>,
+}
+
+/// A directory's worth of results, one per carrier that was tried.
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchReport<T> {
+    pub files: Vec<FileReport<T>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileReport<T> {
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub response: Response<T>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Entry {
+    pub name: String,
+    pub size: usize,
+    pub encrypted: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MetaReport {
+    pub created_utc: String,
+    pub tool_version: String,
+    pub algorithm: String,
+}
+
+impl From<&Meta> for MetaReport {
+    fn from(m: &Meta) -> Self {
+        MetaReport { created_utc: m.created_utc(), tool_version: m.tool_version.clone(), algorithm: m.algorithm.clone() }
+    }
+}
+
+/// Confidence from 0 (a guess) to 1 (every vote agreed) for each byte the carrier held, which is the
+/// framed payload as embedded, before any decryption or decompression.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConfidenceReport {
+    pub min: f32,
+    pub mean: f32,
+    pub per_byte: Vec<f32>,
+}
+
+impl ConfidenceReport {
+    pub fn new(per_byte: Vec<f32>) -> Self {
+        let min = per_byte.iter().copied().fold(1.0, f32::min);
+        let mean = if per_byte.is_empty() { 1.0 } else { per_byte.iter().sum::<f32>() / per_byte.len() as f32 };
+        ConfidenceReport { min, mean, per_byte }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CapacityReport {
+    pub filetype: String,
+    pub algorithm: String,
+    /// What fits once the payload framing is taken off.
+    pub payload_bytes: usize,
+    /// The carrier's own room, after its length header.
+    pub carrier_bytes: usize,
+    pub limited_by: &'static str,
+}
+
+/// What `verify` made of a carrier: whether the payload it holds is the expected one, and the hashes
+/// that decided it.
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyReport {
+    pub filetype: String,
+    pub algorithm: String,
+    pub matches: bool,
+    pub found_size: usize,
+    pub found_sha256: String,
+    /// Only known when the expected payload was given as a file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_size: Option<usize>,
+    pub expected_sha256: String,
+}
+
+/// What `bench` measured: hide and find with each algorithm on made-up carriers of `size`.
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchReport {
+    pub size: String,
+    pub iterations: u32,
+    pub results: Vec<BenchResult>,
+}
+
+/// One algorithm's medians, in milliseconds. The MB/s are of the stego file, which is what find reads
+/// and (with the cover) what hide goes through.
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchResult {
+    pub filetype: String,
+    pub algorithm: String,
+    pub carrier_bytes: u64,
+    pub stego_bytes: u64,
+    pub payload_bytes: usize,
+    pub hide_ms: f64,
+    pub find_ms: f64,
+    /// Left out for algorithms that don't report their reads and writes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hide_phases: Option<HidePhases>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub find_phases: Option<FindPhases>,
+    pub hide_mb_per_s: f64,
+    pub find_mb_per_s: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HidePhases {
+    pub decode_ms: f64,
+    pub embed_ms: f64,
+    pub encode_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FindPhases {
+    pub decode_ms: f64,
+    pub extract_ms: f64,
+}
+
+/// What `info` found out about a carrier. Every part a probe couldn't fill in is left out, the
+/// warnings say why.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct InfoReport {
+    pub size: u64,
+    /// The filetype hide and find would take the file as, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filetype: Option<String>,
+    /// The format the content says the file is in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub picture: Option<PictureInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio: Option<AudioInfo>,
+    /// JPEG only: the APPn and COM segments before the scan, in file order.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub segments: Option<Vec<SegmentInfo>>,
+    /// JPEG and PNG only: the metadata segments and chunks hide --strip-metadata leaves out.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Vec<String>>,
+    /// The algorithms that apply to the file, with their default settings.
+    pub algorithms: Vec<AlgorithmRoom>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PictureInfo {
+    pub width: u32,
+    pub height: u32,
+    pub color_type: String,
+    pub bits_per_channel: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AudioInfo {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub bits_per_sample: u16,
+    /// Samples per channel.
+    pub frames: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SegmentInfo {
+    /// APP0 to APP15, or COM.
+    pub marker: String,
+    /// Bytes after the length field.
+    pub size: usize,
+    /// The printable text the segment starts with (JFIF, Exif, ...), if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identifier: Option<String>,
+}
+
+/// One algorithm's room in an `InfoReport`: the capacity report's numbers, or why it couldn't be sized.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AlgorithmRoom {
+    pub algorithm: String,
+    #[serde(flatten)]
+    pub room: Option<RoomReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RoomReport {
+    pub payload_bytes: usize,
+    pub carrier_bytes: usize,
+    pub limited_by: &'static str,
+}
+
+fn base64_bytes<S: Serializer>(bytes: &Option<Vec<u8>>, s: S) -> Result<S::Ok, S::Error> {
+    match bytes {
+        Some(b) => s.serialize_str(&STANDARD.encode(b)),
+        None => s.serialize_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn envelope_flattens_the_result() {
+        let found = FindReport {
+            filetype: "picture".to_string(),
+            algorithm: "lsb".to_string(),
+            payload_size: 3,
+            payload: Some(b"hi!".to_vec()),
+            hmac: Some(Auth::Unchecked),
+            ..Default::default()
+        };
+        let ok = Response::new(Ok(found), vec!["payload has an HMAC tag".to_string()]);
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&ok.to_json()).unwrap(), json!({
+            "ok": true,
+            "warnings": ["payload has an HMAC tag"],
+            "filetype": "picture",
+            "algorithm": "lsb",
+            "payload_size": 3,
+            "payload_base64": "aGkh",
+            "hmac": "unchecked",
+        }));
+
+        let failed = Response::<CapacityReport>::new(Err("no such file".into()), Vec::new());
+        assert_eq!(failed.to_json(), r#"{"ok":false,"error":"no such file","warnings":[]}"#);
+    }
+}
@@ -0,0 +1,566 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "picture")]
+use image::ImageReader;
+use rayon::iter::{ParallelBridge, ParallelIterator};
+use serde::Serialize;
+use crate::steg_algorithms::audio::wav::riff;
+use crate::steg_algorithms::cancel;
+use crate::steg_algorithms::legacy;
+use crate::steg_algorithms::parse;
+use crate::steg_algorithms::picture::general::png_chunks;
+#[cfg(feature = "audio")]
+use crate::steg_algorithms::audio::wav;
+#[cfg(feature = "picture")]
+use crate::steg_algorithms::picture::general::lsb;
+use crate::steg_algorithms::picture::gif::app_extension;
+use crate::steg_algorithms::picture::jpg::marker_hijacking;
+
+// `scan`: sweep files for hidden data. Every file goes through a cheap pre-filter first (magic bytes,
+// size, a look at the first and last few KB), which gives it a priority. With `--deep` the tree is
+// walked recursively and only files the pre-filter finds suspicious get the expensive detectors
+// (full parse, decoding, extraction, statistics), most suspicious first, on all cores. Findings are
+// handed out as soon as they turn up so a long sweep shows results right away. Ctrl-C stops the sweep
+// after the files being looked at; the summary counts the ones it didn't get to.
+// Symlinks are never followed, which also keeps a link loop from walking forever.
+
+/// How much of the start and end of a file the pre-filter reads.
+const HEAD_SAMPLE: u64 = 64 * 1024;
+const TAIL_SAMPLE: u64 = 4 * 1024;
+/// Nothing smaller holds anything worth finding.
+const MIN_SIZE: u64 = 64;
+/// Chi-square p-value above which LSB pairs count as suspiciously even.
+#[cfg(any(feature = "picture", feature = "audio"))]
+const CHI_SQUARE_THRESHOLD: f64 = 0.95;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Png,
+    Jpeg,
+    Gif,
+    Bmp,
+    Wav,
+}
+
+impl Kind {
+    pub fn sniff(head: &[u8]) -> Option<Self> {
+        match head {
+            [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => Some(Kind::Png),
+            [0xFF, 0xD8, 0xFF, ..] => Some(Kind::Jpeg),
+            [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some(Kind::Gif),
+            [b'B', b'M', ..] => Some(Kind::Bmp),
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => Some(Kind::Wav),
+            _ => None,
+        }
+    }
+
+    fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Kind::Png => &["png"],
+            Kind::Jpeg => &["jpg", "jpeg", "jpe", "jfif"],
+            Kind::Gif => &["gif"],
+            Kind::Bmp => &["bmp", "dib"],
+            Kind::Wav => &["wav", "wave"],
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Kind::Png => "PNG",
+            Kind::Jpeg => "JPEG",
+            Kind::Gif => "GIF",
+            Kind::Bmp => "BMP",
+            Kind::Wav => "WAV",
+        }
+    }
+
+    /// Containers whose samples LSB embedding survives, so any of them could hold an LSB payload.
+    fn lossless(self) -> bool {
+        matches!(self, Kind::Png | Kind::Bmp | Kind::Wav)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub path: PathBuf,
+    pub filetype: Kind,
+    /// Which detector fired.
+    pub detector: &'static str,
+    /// 0..1, how sure the detector is.
+    pub score: f64,
+    pub detail: String,
+}
+
+/// A file that passed the pre-filter.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub path: PathBuf,
+    pub kind: Kind,
+    pub size: u64,
+    /// 0..1, higher is scanned first. 0 means nothing cheap looked off.
+    pub priority: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScanOptions {
+    /// Recurse into directories and only run the detectors on suspicious files.
+    pub deep: bool,
+    /// Skip files bigger than this.
+    pub max_size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// Regular files looked at.
+    pub files: usize,
+    /// Files in a format we know.
+    pub candidates: usize,
+    /// Files the detectors ran on.
+    pub probed: usize,
+    pub findings: usize,
+    /// Files that couldn't be read.
+    pub unreadable: usize,
+    /// Files left unexamined because the scan was interrupted.
+    pub interrupted: usize,
+}
+
+/// Shannon entropy in bits per byte.
+fn entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0usize; 256];
+    for &b in data {
+        counts[b as usize] += 1;
+    }
+    let n = data.len() as f64;
+    counts.iter().filter(|&&c| c > 0).map(|&c| c as f64 / n).map(|p| -p * p.log2()).sum()
+}
+
+fn read_sample(file: &mut File, offset: u64, len: u64) -> std::io::Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buf = Vec::with_capacity(len as usize);
+    file.take(len).read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// Whether a file of `size` bytes ending in `tail` ends where its format says it does, judged from the
+/// samples alone. `None` when the samples can't tell.
+fn ends_cleanly(kind: Kind, head: &[u8], tail: &[u8], size: u64) -> Option<bool> {
+    let le32 = |b: &[u8], at: usize| b.get(at..at + 4).map(|s| u32::from_le_bytes([s[0], s[1], s[2], s[3]]) as u64);
+    match kind {
+        Kind::Png => Some(tail.ends_with(&[b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82])),
+        Kind::Jpeg => Some(tail.ends_with(&[0xFF, 0xD9])),
+        Kind::Gif => Some(tail.ends_with(&[0x3B])),
+        Kind::Bmp => le32(head, 2).map(|declared| declared == size),
+        Kind::Wav => le32(head, 4).map(|declared| (declared + 8).div_ceil(2) * 2 == size.div_ceil(2) * 2),
+    }
+}
+
+/// The cheap look: magic, size and samples from both ends. `Ok(None)` for files we don't handle.
+pub fn prefilter(path: &Path, max_size: Option<u64>) -> std::io::Result<Option<Candidate>> {
+    let size = fs::metadata(path)?.len();
+    if size < MIN_SIZE || max_size.is_some_and(|m| size > m) {
+        return Ok(None);
+    }
+    let mut file = File::open(path)?;
+    let head = read_sample(&mut file, 0, HEAD_SAMPLE)?;
+    let Some(kind) = Kind::sniff(&head) else {
+        return Ok(None);
+    };
+    let tail = read_sample(&mut file, size.saturating_sub(TAIL_SAMPLE), TAIL_SAMPLE)?;
+
+    let mut priority = 0.0;
+    if kind.lossless() {
+        // LSB can't be ruled out without decoding
+        priority += 0.2;
+    }
+    if ends_cleanly(kind, &head, &tail, size) == Some(false) {
+        priority += 0.5;
+        // appended compressed or encrypted data looks like noise
+        if entropy(&tail) > 7.0 {
+            priority += 0.2;
+        }
+    }
+    if [&head, &tail].iter().any(|s| s.windows(4).any(|w| w == crate::steg_algorithms::payload::MAGIC)) {
+        priority += 0.6;
+    }
+    // the segment identifiers marker and appext write (also used by some editors, so only a hint)
+    let carrier_id: &[u8] = if kind == Kind::Jpeg { b"Ducky\0" } else { b"RSTEGANO1.0" };
+    if matches!(kind, Kind::Jpeg | Kind::Gif) && head.windows(carrier_id.len()).any(|w| w == carrier_id) {
+        priority += 0.3;
+    }
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    if !kind.extensions().contains(&ext.as_str()) {
+        priority += 0.1;
+    }
+    Ok(Some(Candidate { path: path.to_path_buf(), kind, size, priority: f64::min(priority, 1.0) }))
+}
+
+/// Where the container really ends, by walking its structure.
+pub fn container_end(kind: Kind, buf: &[u8]) -> Option<usize> {
+    let le32 = |at: usize| buf.get(at..at + 4).map(|s| u32::from_le_bytes([s[0], s[1], s[2], s[3]]) as usize);
+    match kind {
+        Kind::Png => png_chunks::chunks(buf, parse::mode()).ok()?.end,
+        Kind::Jpeg => jpeg_end(buf),
+        Kind::Gif => gif_end(buf),
+        Kind::Bmp => le32(2).filter(|&n| n <= buf.len()),
+        Kind::Wav => riff::chunks(buf, parse::mode()).ok()?.1.end,
+    }
+}
+
+fn jpeg_end(buf: &[u8]) -> Option<usize> {
+    let mut pos = 2;
+    loop {
+        while *buf.get(pos)? != 0xFF {
+            pos += 1;
+        }
+        while *buf.get(pos)? == 0xFF {
+            pos += 1;
+        }
+        let marker = buf[pos];
+        pos += 1;
+        match marker {
+            0xD9 => return Some(pos),
+            0xD0..=0xD7 | 0x01 => continue,
+            _ => {
+                let len = u16::from_be_bytes([*buf.get(pos)?, *buf.get(pos + 1)?]) as usize;
+                pos += len;
+                if marker == 0xDA {
+                    // entropy-coded data runs until a real marker (not stuffing or a restart)
+                    loop {
+                        let ff = pos + buf.get(pos..)?.iter().position(|&b| b == 0xFF)?;
+                        match *buf.get(ff + 1)? {
+                            0x00 | 0xD0..=0xD7 | 0xFF => pos = ff + 1,
+                            _ => {
+                                pos = ff;
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn gif_end(buf: &[u8]) -> Option<usize> {
+    let table = |flags: u8| if flags & 0x80 != 0 { 3 << ((flags & 0x07) + 1) } else { 0 };
+    let skip_sub_blocks = |mut pos: usize| -> Option<usize> {
+        loop {
+            let n = *buf.get(pos)? as usize;
+            pos += 1 + n;
+            if n == 0 {
+                return Some(pos);
+            }
+        }
+    };
+    let mut pos = 13 + table(*buf.get(10)?);
+    loop {
+        match *buf.get(pos)? {
+            0x3B => return Some(pos + 1),
+            0x21 => pos = skip_sub_blocks(pos + 2)?,
+            0x2C => pos = skip_sub_blocks(pos + 10 + table(*buf.get(pos + 9)?) + 1)?,
+            _ => return None,
+        }
+    }
+}
+
+/// What a blob of appended data is, from its first bytes.
+fn sniff_appended(data: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: [(&[u8], &str); 10] = [
+        (b"PK\x03\x04", "ZIP archive"),
+        (b"%PDF", "PDF document"),
+        (b"Rar!", "RAR archive"),
+        (b"7z\xBC\xAF\x27\x1C", "7z archive"),
+        (b"\x1F\x8B", "gzip data"),
+        (b"\x7FELF", "ELF executable"),
+        (b"MZ", "Windows executable"),
+        (b"\x89PNG", "PNG image"),
+        (b"\xFF\xD8\xFF", "JPEG image"),
+        (b"RSTG", "rust-stego payload"),
+    ];
+    SIGNATURES.iter().find(|(sig, _)| data.starts_with(sig)).map(|(_, what)| *what)
+}
+
+fn appended_data(c: &Candidate, buf: &[u8]) -> Option<Finding> {
+    let end = container_end(c.kind, buf)?;
+    let extra = &buf[end..];
+    // a few bytes of padding after the end is common and harmless
+    if extra.len() < 16 || extra.iter().all(|&b| b == 0 || b == 0xFF) {
+        return None;
+    }
+    let (score, what) = match sniff_appended(extra) {
+        Some(what) => (0.95, format!("a {} (polyglot)", what)),
+        None if entropy(extra) > 7.5 => (0.8, "high-entropy data".to_string()),
+        None => (0.6, "data".to_string()),
+    };
+    Some(Finding {
+        path: c.path.clone(),
+        filetype: c.kind,
+        detector: "appended",
+        score,
+        detail: format!("{} bytes of {} after the end of the {} at offset {}", extra.len(), what, c.kind.label(), end),
+    })
+}
+
+fn own_payload(c: &Candidate) -> Option<Finding> {
+    if c.kind == Kind::Jpeg
+        && let Some((present, total, _)) = fs::read(&c.path).ok().as_deref().and_then(marker_hijacking::sealed_segments)
+    {
+        let detail = format!("marker payload encrypted segment by segment, {} of {} segments", present, total);
+        return Some(Finding { path: c.path.clone(), filetype: c.kind, detector: "rust-stego", score: 1.0, detail });
+    }
+    let (algorithm, raw) = match c.kind {
+        #[cfg(feature = "picture")]
+        Kind::Png | Kind::Bmp => ("lsb", lsb::find_payload_sparse(&c.path, None)),
+        #[cfg(feature = "audio")]
+        Kind::Wav => ("lsb", wav::lsb::find_wav_sparse(&c.path, None)),
+        Kind::Jpeg => ("marker", marker_hijacking::find_payload(&c.path)),
+        Kind::Gif => ("appext", app_extension::find_payload(&c.path, b"RSTEGANO1.0")),
+        // the lsb algorithm for these isn't in this build
+        #[cfg(not(all(feature = "picture", feature = "audio")))]
+        _ => return None,
+    };
+    let raw = raw.ok()?;
+    let (score, detail) = if legacy::is_framed(&raw) {
+        (1.0, format!("{} payload, {} bytes embedded", algorithm, raw.len()))
+    } else {
+        let msg = legacy::detect(&raw, c.kind == Kind::Wav)?;
+        (0.7, format!("{} legacy (pre-framing) text payload, {} bytes", algorithm, msg.len()))
+    };
+    Some(Finding { path: c.path.clone(), filetype: c.kind, detector: "rust-stego", score, detail })
+}
+
+/// Upper tail of the chi-square distribution with `dof` degrees of freedom (Wilson-Hilferty).
+fn chi_square_sf(x: f64, dof: f64) -> f64 {
+    let v = 2.0 / (9.0 * dof);
+    let z = ((x / dof).cbrt() - (1.0 - v)) / v.sqrt();
+    0.5 * erfc(z / std::f64::consts::SQRT_2)
+}
+
+// Abramowitz & Stegun 7.1.26, plenty for a detector score
+fn erfc(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let e = poly * (-x * x).exp();
+    if x >= 0.0 { e } else { 2.0 - e }
+}
+
+/// Westfeld & Pfitzmann's chi-square attack: LSB replacement evens out the counts of each pair of
+/// values (2k, 2k+1). Returns the probability that `samples` carry embedded bits.
+pub fn chi_square_embedding(samples: impl Iterator<Item = i64>) -> f64 {
+    let mut counts: HashMap<i64, usize> = HashMap::new();
+    for s in samples {
+        *counts.entry(s).or_default() += 1;
+    }
+    let (mut chi, mut pairs) = (0.0, 0usize);
+    for (&even, &n_even) in counts.iter().filter(|(v, _)| *v & 1 == 0) {
+        let n_odd = counts.get(&(even + 1)).copied().unwrap_or(0);
+        let expected = (n_even + n_odd) as f64 / 2.0;
+        // sparse pairs say nothing and blow up the statistic
+        if expected < 5.0 {
+            continue;
+        }
+        chi += (n_even as f64 - expected).powi(2) / expected;
+        pairs += 1;
+    }
+    if pairs < 2 {
+        return 0.0;
+    }
+    chi_square_sf(chi, (pairs - 1) as f64)
+}
+
+#[cfg(any(feature = "picture", feature = "audio"))]
+fn lsb_statistics(c: &Candidate) -> Option<Finding> {
+    let samples: Vec<i64> = match c.kind {
+        #[cfg(feature = "picture")]
+        Kind::Png | Kind::Bmp => {
+            let img = ImageReader::open(&c.path).ok()?.decode().ok()?.to_rgb8();
+            img.into_raw().into_iter().map(i64::from).collect()
+        }
+        #[cfg(feature = "audio")]
+        Kind::Wav => hound::WavReader::open(&c.path).ok()?.samples::<i16>().filter_map(Result::ok).map(i64::from).collect(),
+        _ => return None,
+    };
+    // sequential embedding only touches the start, so look at that as well as the whole thing
+    let prefix = (samples.len() / 10).max(4096).min(samples.len());
+    let p = chi_square_embedding(samples[..prefix].iter().copied()).max(chi_square_embedding(samples.iter().copied()));
+    (p > CHI_SQUARE_THRESHOLD).then(|| Finding {
+        path: c.path.clone(),
+        filetype: c.kind,
+        detector: "chi-square",
+        score: p,
+        detail: format!("LSB value pairs are suspiciously even (p = {:.3}), typical of LSB replacement", p),
+    })
+}
+
+/// Run every detector on one file.
+pub fn detect(c: &Candidate) -> Vec<Finding> {
+    let mut found = Vec::new();
+    if let Ok(buf) = fs::read(&c.path) {
+        found.extend(appended_data(c, &buf));
+    }
+    found.extend(own_payload(c));
+    // a payload we can read already says it all
+    #[cfg(any(feature = "picture", feature = "audio"))]
+    if found.iter().all(|f| f.detector != "rust-stego") {
+        found.extend(lsb_statistics(c));
+    }
+    found
+}
+
+/// Regular files under `paths`, recursing into directories when `recursive` (one level otherwise).
+pub fn walk(paths: &[PathBuf], recursive: bool, unreadable: &AtomicUsize) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs: Vec<(PathBuf, bool)> = Vec::new();
+    for p in paths {
+        match fs::symlink_metadata(p) {
+            Ok(m) if m.is_dir() => dirs.push((p.clone(), true)),
+            Ok(m) if m.is_file() => files.push(p.clone()),
+            Ok(_) => {}
+            Err(_) => {
+                unreadable.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+    while let Some((dir, descend)) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            unreadable.fetch_add(1, Ordering::Relaxed);
+            continue;
+        };
+        for entry in entries.flatten() {
+            // file_type() doesn't follow symlinks
+            match entry.file_type() {
+                Ok(t) if t.is_file() => files.push(entry.path()),
+                Ok(t) if t.is_dir() && descend && recursive => dirs.push((entry.path(), true)),
+                _ => {}
+            }
+        }
+    }
+    files
+}
+
+/// Scan `paths` (files or directories), calling `on_finding` from worker threads as findings turn up.
+pub fn scan(paths: &[PathBuf], opts: ScanOptions, on_finding: impl Fn(&Finding) + Sync) -> Summary {
+    let _cooperating = cancel::cooperate();
+    let (unreadable, interrupted) = (AtomicUsize::new(0), AtomicUsize::new(0));
+    let files = walk(paths, opts.deep, &unreadable);
+
+    let mut candidates: Vec<Candidate> = files
+        .iter()
+        .par_bridge()
+        .filter_map(|p| {
+            if cancel::requested() {
+                interrupted.fetch_add(1, Ordering::Relaxed);
+                return None;
+            }
+            prefilter(p, opts.max_size)
+                .inspect_err(|_| {
+                    unreadable.fetch_add(1, Ordering::Relaxed);
+                })
+                .ok()
+                .flatten()
+        })
+        .collect();
+    let total_candidates = candidates.len();
+    if opts.deep {
+        candidates.retain(|c| c.priority > 0.0);
+    }
+    // most suspicious first; par_bridge hands them to the workers in this order
+    candidates.sort_by(|a, b| b.priority.total_cmp(&a.priority).then_with(|| a.size.cmp(&b.size)));
+
+    let findings = AtomicUsize::new(0);
+    let probed = AtomicUsize::new(0);
+    candidates.into_iter().par_bridge().for_each(|c| {
+        if cancel::requested() {
+            interrupted.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        probed.fetch_add(1, Ordering::Relaxed);
+        for f in detect(&c) {
+            findings.fetch_add(1, Ordering::Relaxed);
+            on_finding(&f);
+        }
+    });
+
+    Summary {
+        files: files.len(),
+        candidates: total_candidates,
+        probed: probed.into_inner(),
+        findings: findings.into_inner(),
+        unreadable: unreadable.into_inner(),
+        interrupted: interrupted.into_inner(),
+    }
+}
+
+#[cfg(all(test, feature = "picture"))]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use image::{Rgb, RgbImage};
+    use rand::{Rng, SeedableRng};
+    use tempfile::tempdir;
+
+    fn noisy_cover(path: &Path) {
+        // only even values, so the pairs are as uneven as they get until something is embedded
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(9);
+        RgbImage::from_fn(96, 96, |_, _| Rgb([0, 0, 0].map(|_: u8| rng.gen_range(20..110u8) * 2))).save(path).unwrap();
+    }
+
+    #[test]
+    fn chi_square_tells_full_lsb_embedding_from_a_clean_cover() {
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(4);
+        let cover: Vec<i64> = (0..30_000).map(|_| rng.gen_range(10..120) * 2).collect();
+        assert!(chi_square_embedding(cover.iter().copied()) < 0.01);
+        let stego: Vec<i64> = cover.iter().map(|&v| v | rng.gen_range(0..2)).collect();
+        assert!(chi_square_embedding(stego.iter().copied()) > CHI_SQUARE_THRESHOLD);
+    }
+
+    #[test]
+    fn container_ends_are_found() {
+        let dir = tempdir().unwrap();
+        let png = dir.path().join("c.png");
+        noisy_cover(&png);
+        let buf = fs::read(&png).unwrap();
+        assert_eq!(container_end(Kind::Png, &buf), Some(buf.len()));
+
+        let jpg = fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/legacy/marker.jpg")).unwrap();
+        assert_eq!(container_end(Kind::Jpeg, &jpg), Some(jpg.len()));
+    }
+
+    #[test]
+    fn deep_sweep_finds_polyglots_and_payloads_but_skips_clean_jpegs() {
+        let dir = tempdir().unwrap();
+        let nested = dir.path().join("a/b");
+        fs::create_dir_all(&nested).unwrap();
+
+        let cover = dir.path().join("cover.png");
+        noisy_cover(&cover);
+        let framed = crate::steg_algorithms::payload::Payload::from_text("found me").encode(&Default::default()).unwrap();
+        lsb::hide(&cover, framed, &nested.join("stego.png")).unwrap();
+
+        let jpg = crate::steg_algorithms::picture::general::transcode::to_jpeg(&cover, 80).unwrap();
+        fs::write(nested.join("clean.jpg"), &jpg).unwrap();
+        let mut polyglot = jpg.clone();
+        polyglot.extend_from_slice(b"PK\x03\x04");
+        polyglot.extend(std::iter::repeat_n(0x42u8, 200));
+        fs::write(dir.path().join("a/holiday.jpg"), &polyglot).unwrap();
+        fs::write(dir.path().join("notes.txt"), "not a carrier at all, just some text").unwrap();
+
+        let found = Mutex::new(Vec::new());
+        let summary = scan(&[dir.path().to_path_buf()], ScanOptions { deep: true, max_size: None }, |f| {
+            found.lock().unwrap().push((f.path.file_name().unwrap().to_string_lossy().into_owned(), f.detector));
+        });
+        let mut found = found.into_inner().unwrap();
+        found.sort();
+        assert_eq!(found, [("holiday.jpg".to_string(), "appended"), ("stego.png".to_string(), "rust-stego")]);
+        assert_eq!((summary.files, summary.candidates), (5, 4));
+        // clean.jpg never reached the detectors
+        assert_eq!(summary.probed, 3);
+    }
+}
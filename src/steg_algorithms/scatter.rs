@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use sha2::{Digest, Sha256};
+
+// Keyed embedding order for the LSB carriers. A key is hashed into a ChaCha20 seed and drives a
+// Fisher-Yates shuffle of the carrier's slot indices, so the payload (header included) is spread over
+// the whole file and only someone with the key knows where to look.
+// The order has to stay the same across platforms and releases, so everything here is pinned:
+// SHA-256 with a fixed domain prefix for the seed, rand_chacha's ChaCha20 stream (which is specified
+// to be reproducible), and our own shuffle instead of rand's (whose algorithm may change).
+// Changing any of it breaks every keyed carrier out there.
+
+const SEED_DOMAIN: &[u8] = b"rust-stego scatter v1\0";
+
+fn seed_from_key(key: &str) -> [u8; 32] {
+    Sha256::new().chain_update(SEED_DOMAIN).chain_update(key.as_bytes()).finalize().into()
+}
+
+/// Lazily generated permutation of `0..n`, only the positions actually visited cost memory.
+/// This is a partial Fisher-Yates shuffle that remembers displaced values in a map.
+pub struct KeyedOrder {
+    rng: ChaCha20Rng,
+    n: usize,
+    i: usize,
+    displaced: HashMap<usize, usize>,
+}
+
+impl KeyedOrder {
+    pub fn new(key: &str, n: usize) -> Self {
+        KeyedOrder { rng: ChaCha20Rng::from_seed(seed_from_key(key)), n, i: 0, displaced: HashMap::new() }
+    }
+}
+
+impl Iterator for KeyedOrder {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.i >= self.n {
+            return None;
+        }
+        let remaining = (self.n - self.i) as u64;
+        // modulo bias is below 2^-20 for any carrier we can hold in memory, and it's deterministic
+        let j = self.i + (self.rng.next_u64() % remaining) as usize;
+        let at_i = self.displaced.remove(&self.i).unwrap_or(self.i);
+        let at_j = if j == self.i { at_i } else { self.displaced.get(&j).copied().unwrap_or(j) };
+        if j != self.i {
+            self.displaced.insert(j, at_i);
+        }
+        self.i += 1;
+        Some(at_j)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_a_permutation() {
+        let mut seen: Vec<usize> = KeyedOrder::new("k", 1000).collect();
+        seen.sort_unstable();
+        assert_eq!(seen, (0..1000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn order_is_pinned() {
+        // if this changes, every keyed carrier made so far stops decoding
+        let first: Vec<usize> = KeyedOrder::new("correct horse", 1_000_000).take(6).collect();
+        assert_eq!(first, PINNED);
+        assert_ne!(KeyedOrder::new("other", 1_000_000).take(6).collect::<Vec<_>>(), first);
+    }
+
+    const PINNED: [usize; 6] = [978469, 897961, 810092, 51849, 972863, 628102];
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use crate::steg_algorithms::audio::wav::lsb as wav_lsb;
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::legacy;
+use crate::steg_algorithms::payload::{self, Auth, DecodeOptions, Payload, Table};
+use crate::steg_algorithms::picture::general::lsb::{LsbOptions, Region};
+#[cfg(feature = "picture")]
+use {
+    crate::steg_algorithms::picture::{general::lsb, raw},
+    image::DynamicImage,
+    std::collections::hash_map::Entry,
+};
+
+// `repl`: a suspicious file kept in memory while algorithms, keys and offsets are tried against it. The
+// carrier is decoded once (a picture to RGBA, a WAV to its sample LSBs) and the slot bits of every lsb
+// layout tried are kept, so a new stride, key or offset only walks bits already in memory. What each
+// attempt carried is kept as it came out, and decoding it again with another password or HMAC key
+// doesn't touch the carrier at all.
+//
+// Carriers with nothing to cache (raw formats, DICOM, FITS, the algorithms that aren't lsb) are read from
+// the file as `find` reads them; the session only keeps what came out.
+
+/// What came out of an attempt, with the per-byte confidence of the algorithms that vote.
+pub type Found = Result<(Vec<u8>, Option<Vec<f32>>), StegError>;
+
+/// A carrier opened in a session.
+pub struct Carrier {
+    pub path: PathBuf,
+    pub filetype: String,
+    /// What was decoded up front, or why nothing was.
+    decoded: Result<Decoded, StegError>,
+    // slot bits by bits per channel, channels and region, the picture layouts tried so far
+    #[cfg_attr(not(feature = "picture"), allow(dead_code))]
+    slots: HashMap<(u8, Vec<usize>, Option<Region>), Vec<u8>>,
+}
+
+// a build without `audio` never decodes a WAV, without `picture` never a picture
+#[cfg_attr(not(feature = "audio"), allow(dead_code))]
+enum Decoded {
+    #[cfg(feature = "picture")]
+    Picture(DynamicImage),
+    /// The LSB of every sample.
+    Audio(Vec<u8>),
+}
+
+impl Carrier {
+    /// Decode the carrier at `path` for lsb, when `filetype` has anything to decode. A carrier that
+    /// doesn't decode still opens: the algorithms that read the file as is may get something out of it.
+    pub fn open(path: &Path, filetype: &str) -> Result<Carrier, StegError> {
+        if !path.is_file() {
+            return Err(format!("{} isn't a file", path.display()).into());
+        }
+        let decoded = match filetype {
+            #[cfg(feature = "picture")]
+            "picture" if raw::handles(path) => Err("raw formats are read from the file every time".into()),
+            #[cfg(feature = "picture")]
+            "picture" => lsb::decode(path).map(Decoded::Picture),
+            #[cfg(feature = "audio")]
+            "audio" => wav_lsb::read_samples(path).map(|(_, samples)| Decoded::Audio(wav_lsb::lsbs(&samples))),
+            other => Err(format!("{} carriers are read from the file every time", other).into()),
+        };
+        Ok(Carrier { path: path.to_path_buf(), filetype: filetype.to_string(), decoded, slots: HashMap::new() })
+    }
+
+    /// What is held in memory, for `status`.
+    pub fn describe(&self) -> String {
+        match &self.decoded {
+            #[cfg(feature = "picture")]
+            Ok(Decoded::Picture(img)) => {
+                format!("{}x{} picture decoded, {} lsb layouts cached", img.width(), img.height(), self.slots.len())
+            }
+            Ok(Decoded::Audio(bits)) => format!("{} samples decoded", bits.len()),
+            Err(why) => format!("not decoded: {}", why),
+        }
+    }
+
+    /// lsb on the decoded carrier, `None` when there is none and the file has to be read instead.
+    pub fn find_lsb(&mut self, opts: &LsbOptions, limit: Option<usize>) -> Option<Found> {
+        match self.decoded.as_ref().ok()? {
+            #[cfg(feature = "picture")]
+            Decoded::Picture(img) => {
+                let opts = &lsb::recorded_layout(img, opts);
+                let bits = match self.slots.entry((opts.bits, opts.channels.clone(), opts.region)) {
+                    Entry::Occupied(cached) => cached.into_mut(),
+                    Entry::Vacant(slot) => match lsb::slots(img, opts) {
+                        Ok(bits) => slot.insert(bits),
+                        Err(e) => return Some(Err(e)),
+                    },
+                };
+                Some(lsb::find_in_slots(bits, opts, limit))
+            }
+            Decoded::Audio(bits) => Some(wav_lsb::find_in_lsbs(bits, opts.stride, opts.key.as_deref(), limit)),
+        }
+    }
+}
+
+/// What an attempt got out of a carrier, as it came out.
+pub struct Kept {
+    /// The algorithm and settings it came out with, e.g. "lsb:key=k on cat.png".
+    pub label: String,
+    pub carried: Vec<u8>,
+    pub confidence: Option<Vec<f32>>,
+    /// Carried as is, with no framing to decode (lineshift).
+    pub bare: bool,
+}
+
+/// A kept payload, decoded.
+#[derive(Debug, PartialEq)]
+pub enum Opened {
+    Payload(Payload, Auth),
+    /// The names, sizes and whether each is encrypted, of a table of payloads.
+    Table(Vec<(String, usize, bool)>),
+    /// A message from before the framing existed.
+    Legacy(Vec<u8>),
+}
+
+impl Kept {
+    /// Decode what was carried with `opts`, picking `name` out of a table.
+    pub fn open(&self, opts: &DecodeOptions, name: Option<&str>, audio: bool) -> Result<Opened, StegError> {
+        if self.bare {
+            return Ok(Opened::Payload(Payload { name: None, data: self.carried.clone() }, Auth::Absent));
+        }
+        if name.is_none() && opts.hmac_key.is_none() && let Some(msg) = legacy::detect(&self.carried, audio) {
+            return Ok(Opened::Legacy(msg));
+        }
+        let bytes = match payload::unprotect(&self.carried)? {
+            Some((inner, _)) => inner,
+            None => self.carried.clone(),
+        };
+        match (Table::parse(&bytes)?, name) {
+            (Some(table), Some(n)) => match table.get(n) {
+                Some(frame) => Payload::decode_verified(frame, opts).map(|(p, a)| Opened::Payload(p, a)),
+                None => Err(format!("No payload named '{}' (have: {})", n, table.names().collect::<Vec<_>>().join(", ")).into()),
+            },
+            (Some(table), None) => Ok(Opened::Table(table.summary().into_iter().map(|(n, size, enc)| (n.to_string(), size, enc)).collect())),
+            (None, Some(_)) => Err("This payload is a single unnamed one, leave out the name".into()),
+            (None, None) => Payload::decode_verified(&bytes, opts).map(|(p, a)| Opened::Payload(p, a)),
+        }
+    }
+}
+
+/// The carrier being looked at, the keys to decode with, and everything found so far.
+#[derive(Default)]
+pub struct Session {
+    pub carrier: Option<Carrier>,
+    pub password: Option<String>,
+    pub hmac_key: Option<String>,
+    /// In the order they were found; `show 1` is the first.
+    pub kept: Vec<Kept>,
+}
+
+impl Session {
+    pub fn decode_options(&self) -> DecodeOptions {
+        DecodeOptions { password: self.password.clone(), hmac_key: self.hmac_key.clone() }
+    }
+
+    /// The kept payload numbered `n`, counting from 1.
+    pub fn get(&self, n: usize) -> Result<&Kept, StegError> {
+        n.checked_sub(1).and_then(|i| self.kept.get(i))
+            .ok_or_else(|| format!("No payload {} (there are {})", n, self.kept.len()).into())
+    }
+}
+
+#[cfg(all(test, feature = "picture"))]
+mod tests {
+    use super::*;
+    use crate::steg_algorithms::options::HideOptions;
+    use crate::steg_algorithms::payload::FrameOptions;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn attempts_reuse_the_decoded_carrier() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cover, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+        RgbImage::from_fn(64, 64, |x, y| Rgb([(x * 4) as u8, (y * 4) as u8, 99])).save(&cover).unwrap();
+        let framed = Payload::from_text("under the carpet").encode(&FrameOptions { password: Some("pw".to_string()), ..FrameOptions::default() }).unwrap();
+        let keyed = LsbOptions { key: Some("k".to_string()), ..LsbOptions::default() };
+        lsb::hide_with(&cover, &framed, &out, &HideOptions { lsb: keyed.clone(), ..HideOptions::default() }).unwrap();
+
+        let mut carrier = Carrier::open(&out, "picture").unwrap();
+        assert!(carrier.find_lsb(&LsbOptions::default(), None).unwrap().is_err());
+        // the file is gone, the attempt after it runs on the decoded picture
+        std::fs::remove_file(&out).unwrap();
+        let (carried, _) = carrier.find_lsb(&keyed, None).unwrap().unwrap();
+        assert!(carrier.describe().contains("1 lsb layouts cached"));
+
+        let kept = Kept { label: "lsb:key=k".to_string(), carried, confidence: None, bare: false };
+        assert!(kept.open(&DecodeOptions::default(), None, false).is_err());
+        let opts = DecodeOptions { password: Some("pw".to_string()), hmac_key: None };
+        assert_eq!(kept.open(&opts, None, false).unwrap(), Opened::Payload(Payload::from_text("under the carpet"), Auth::Absent));
+
+        let session = Session { kept: vec![kept], ..Session::default() };
+        assert!(session.get(1).is_ok());
+        assert!(session.get(0).is_err() && session.get(2).is_err());
+    }
+
+    #[test]
+    fn undecodable_carriers_still_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let junk = dir.path().join("junk.png");
+        std::fs::write(&junk, b"not a picture").unwrap();
+        let mut carrier = Carrier::open(&junk, "picture").unwrap();
+        assert!(carrier.find_lsb(&LsbOptions::default(), None).is_none());
+        assert!(carrier.describe().starts_with("not decoded"));
+        assert!(Carrier::open(&dir.path().join("missing.png"), "picture").is_err());
+    }
+}
@@ -0,0 +1,121 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use rand::RngCore;
+use crate::steg_algorithms::error::StegError;
+
+// Two-person integrity (`hide --key-share A --key-share B`): the payload is encrypted under a random
+// secret nobody types in, and the secret is XOR-split into two share files. Either share alone is
+// uniformly random and says nothing about the secret; find needs both (`find --key-share A --key-share
+// B`), so recovering the content takes whoever holds each file. The secret goes in as the password, so
+// the payload itself is an ordinary encrypted frame.
+//
+// A share file is one line: `rust-stego-share v1 <index>/2 <set> <share>`, with the set id (random,
+// the same in both files) and the share in hex. The set id catches shares from two different hides.
+
+const SECRET_BYTES: usize = 32;
+const SET_BYTES: usize = 8;
+const TAG: &str = "rust-stego-share v1";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+    /// 1 or 2.
+    pub index: u8,
+    pub set: [u8; SET_BYTES],
+    pub bytes: [u8; SECRET_BYTES],
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex<const N: usize>(s: &str) -> Option<[u8; N]> {
+    if s.len() != N * 2 || !s.is_ascii() {
+        return None;
+    }
+    let mut out = [0u8; N];
+    for (i, b) in out.iter_mut().enumerate() {
+        *b = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(out)
+}
+
+impl Share {
+    pub fn encode(&self) -> String {
+        format!("{} {}/2 {} {}\n", TAG, self.index, hex(&self.set), hex(&self.bytes))
+    }
+
+    pub fn parse(text: &str) -> Result<Share, StegError> {
+        let bad = || "Not a rust-stego key share".to_string();
+        let rest = text.trim().strip_prefix(TAG).ok_or_else(bad)?;
+        let [index, set, bytes] = rest.split_whitespace().collect::<Vec<_>>().try_into().map_err(|_| bad())?;
+        let index = match index {
+            "1/2" => 1,
+            "2/2" => 2,
+            _ => return Err(bad().into()),
+        };
+        Ok(Share { index, set: unhex(set).ok_or_else(bad)?, bytes: unhex(bytes).ok_or_else(bad)? })
+    }
+
+    pub fn read(path: &Path) -> Result<Share, StegError> {
+        let text = std::fs::read_to_string(path).map_err(|e| StegError::io(format!("Failed to read {}", path.display()), e))?;
+        Share::parse(&text).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    /// Write the share to a new file, readable by the owner only. An existing file is never replaced:
+    /// the payloads its share opens would be lost.
+    pub fn write(&self, path: &Path) -> Result<(), StegError> {
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        file.write_all(self.encode().as_bytes()).map_err(|e| format!("Failed to write {}: {}", path.display(), e).into())
+    }
+}
+
+/// A fresh secret, as the password to encrypt with, and its two shares.
+pub fn split(rng: &mut impl RngCore) -> (String, [Share; 2]) {
+    let (mut secret, mut first, mut set) = ([0u8; SECRET_BYTES], [0u8; SECRET_BYTES], [0u8; SET_BYTES]);
+    rng.fill_bytes(&mut secret);
+    rng.fill_bytes(&mut first);
+    rng.fill_bytes(&mut set);
+    let second: [u8; SECRET_BYTES] = std::array::from_fn(|i| secret[i] ^ first[i]);
+    (hex(&secret), [Share { index: 1, set, bytes: first }, Share { index: 2, set, bytes: second }])
+}
+
+/// The password the two shares were split from, given in either order.
+pub fn combine(a: &Share, b: &Share) -> Result<String, StegError> {
+    if a.set != b.set {
+        return Err("The key shares come from different hides".into());
+    }
+    if a.index == b.index {
+        return Err(format!("Both key shares are share {}, find needs share 1 and share 2", a.index).into());
+    }
+    Ok(hex(&std::array::from_fn::<u8, SECRET_BYTES, _>(|i| a.bytes[i] ^ b.bytes[i])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use tempfile::tempdir;
+
+    #[test]
+    fn shares_roundtrip_through_files_and_need_each_other() {
+        let dir = tempdir().unwrap();
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(7);
+        let (password, [one, two]) = split(&mut rng);
+        let (p1, p2) = (dir.path().join("alice.share"), dir.path().join("bob.share"));
+        one.write(&p1).unwrap();
+        two.write(&p2).unwrap();
+        assert!(one.write(&p1).is_err(), "never overwrites a share");
+
+        let (one, two) = (Share::read(&p1).unwrap(), Share::read(&p2).unwrap());
+        assert_eq!(combine(&two, &one).unwrap(), password);
+        assert!(combine(&one, &one).unwrap_err().to_string().contains("share 1 and share 2"));
+        let (_, [other, _]) = split(&mut rng);
+        assert!(combine(&other, &two).unwrap_err().to_string().contains("different hides"));
+        assert!(Share::parse("rust-stego-share v1 3/2 00 00").is_err());
+    }
+}
@@ -0,0 +1,207 @@
+use std::fs;
+use std::path::Path;
+use rand::RngCore;
+use crate::steg_algorithms::atomic;
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::picture::gif::app_extension;
+use crate::steg_algorithms::picture::jpg::marker_hijacking;
+use crate::steg_algorithms::scan::{self, Kind};
+#[cfg(feature = "audio")]
+use crate::steg_algorithms::audio::wav;
+#[cfg(feature = "picture")]
+use crate::steg_algorithms::picture::{general::lsb, raw};
+
+// `wipe`: make a cleaned copy of a possibly-stego file. Container-level hiding places (APPn/COM segments,
+// GIF application/comment extensions, data after the end of the container) are cut out byte for byte,
+// and LSB carriers get a fresh random LSB plane, which destroys any bitstream in it whatever the stride
+// or key while changing each sample by at most 1. Random rather than zeroed bits, so the copy doesn't
+// look tampered with itself.
+
+/// Scopes `wipe` can be limited to.
+pub const SCOPES: [&str; 4] = ["lsb", "marker", "appext", "appended"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Removal {
+    /// Which scope removed it.
+    pub scope: &'static str,
+    pub detail: String,
+}
+
+// only a build with `picture` tells raw formats and other pictures apart from the rest
+#[cfg_attr(not(feature = "picture"), allow(dead_code))]
+#[derive(Clone, Copy, PartialEq)]
+enum Target {
+    Container(Kind),
+    Raw,
+    /// Anything else `image` reads; only the LSB plane applies.
+    Picture,
+}
+
+impl Target {
+    fn label(self) -> &'static str {
+        match self {
+            Target::Container(kind) => kind.label(),
+            Target::Raw => "netpbm/farbfeld/QOI",
+            Target::Picture => "picture",
+        }
+    }
+
+    fn scopes(self) -> &'static [&'static str] {
+        match self {
+            Target::Container(Kind::Jpeg) => &["marker", "appended"],
+            Target::Container(Kind::Gif) => &["appext", "appended"],
+            Target::Container(_) => &["lsb", "appended"],
+            Target::Raw | Target::Picture => &["lsb"],
+        }
+    }
+}
+
+/// `buf` with `ranges` (sorted, not overlapping) cut out.
+pub fn cut(buf: &[u8], ranges: &[(usize, usize)]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(buf.len());
+    let mut pos = 0;
+    for &(start, end) in ranges {
+        out.extend_from_slice(&buf[pos..start]);
+        pos = end;
+    }
+    out.extend_from_slice(&buf[pos..]);
+    out
+}
+
+/// What a JPEG segment is, from its marker and body: "COM segment", "APP1 segment 'Exif'".
+pub fn segment_name(marker: u8, body: &[u8]) -> String {
+    if marker == 0xFE {
+        return "COM segment".to_string();
+    }
+    // most APPn segments start with a NUL-terminated identifier
+    let id: String = body.iter().take(16).take_while(|&&b| b != 0).map(|&b| b as char).collect();
+    if !id.is_empty() && id.chars().all(|c| c.is_ascii_graphic() || c == ' ') {
+        format!("APP{} segment '{}'", marker - 0xE0, id)
+    } else {
+        format!("APP{} segment", marker - 0xE0)
+    }
+}
+
+/// Clean the file at `input` into `output`, or with `output: None` only report what would be removed.
+/// `only` limits the work to one of `SCOPES`.
+pub fn wipe(input: &Path, output: Option<&Path>, only: Option<&str>, rng: &mut impl RngCore) -> Result<Vec<Removal>, StegError> {
+    let mut buf = fs::read(input).map_err(|e| StegError::io(format!("Failed to read {}", input.display()), e))?;
+    let target = match Kind::sniff(&buf) {
+        Some(kind) => Target::Container(kind),
+        #[cfg(feature = "picture")]
+        None if raw::Format::sniff(&buf).is_some() => Target::Raw,
+        #[cfg(feature = "picture")]
+        None if image::guess_format(&buf).is_ok() => Target::Picture,
+        None => return Err(format!("{} isn't a picture or WAV file this tool can clean", input.display()).into()),
+    };
+    if let Some(scope) = only && !target.scopes().contains(&scope) {
+        return Err(format!("'{}' doesn't apply to a {} file (try {})", scope, target.label(), target.scopes().join(", ")).into());
+    }
+    let wanted = |scope: &str| target.scopes().contains(&scope) && only.is_none_or(|o| o == scope);
+    let mut removals = Vec::new();
+
+    if wanted("marker") {
+        let segments = marker_hijacking::removable_segments(&buf)?;
+        for &(marker, start, end) in &segments {
+            let detail = format!("{} ({} bytes)", segment_name(marker, &buf[(start + 4).min(end)..end]), end - start);
+            removals.push(Removal { scope: "marker", detail });
+        }
+        let ranges: Vec<(usize, usize)> = segments.iter().map(|&(_, start, end)| (start, end)).collect();
+        buf = cut(&buf, &ranges);
+    }
+    if wanted("appext") {
+        let extensions = app_extension::removable_extensions(&buf)?;
+        for (start, end, what) in &extensions {
+            removals.push(Removal { scope: "appext", detail: format!("{} ({} bytes)", what, end - start) });
+        }
+        let ranges: Vec<(usize, usize)> = extensions.iter().map(|&(start, end, _)| (start, end)).collect();
+        buf = cut(&buf, &ranges);
+    }
+    if wanted("appended") && let Target::Container(kind) = target
+        && let Some(end) = scan::container_end(kind, &buf) && end < buf.len()
+    {
+        removals.push(Removal { scope: "appended", detail: format!("{} bytes after the end of the {}", buf.len() - end, kind.label()) });
+        buf.truncate(end);
+    }
+
+    let randomize = wanted("lsb").then(|| randomizer(target)).transpose()?;
+    let Some(output) = output else {
+        if randomize.is_some() {
+            removals.push(Removal { scope: "lsb", detail: "LSB plane (replaced with random bits)".to_string() });
+        }
+        return Ok(removals);
+    };
+    atomic::write(output, &buf).map_err(|e| e.context(format!("Failed to write {}", output.display())))?;
+    if let Some(randomize) = randomize {
+        let count = randomize(output, rng)?;
+        removals.push(Removal { scope: "lsb", detail: format!("LSB plane of {} samples (replaced with random bits)", count) });
+    }
+    Ok(removals)
+}
+
+type Randomize = fn(&Path, &mut dyn RngCore) -> Result<usize, StegError>;
+
+// what gives a `target` file a fresh LSB plane, when this build has the lsb algorithm that reads it
+fn randomizer(target: Target) -> Result<Randomize, StegError> {
+    match target {
+        #[cfg(feature = "audio")]
+        Target::Container(Kind::Wav) => Ok(|path, mut rng| wav::lsb::randomize(path, &mut rng)),
+        #[cfg(not(feature = "audio"))]
+        Target::Container(Kind::Wav) => Err(StegError::not_built("Wiping the LSB plane of a WAV", "audio")),
+        #[cfg(feature = "picture")]
+        Target::Raw => Ok(|path, mut rng| raw::randomize(path, &mut rng)),
+        #[cfg(feature = "picture")]
+        _ => Ok(|path, mut rng| lsb::randomize(path, &mut rng)),
+        #[cfg(not(feature = "picture"))]
+        _ => Err(StegError::not_built(format!("Wiping the LSB plane of a {}", target.label()), "picture")),
+    }
+}
+
+#[cfg(all(test, feature = "picture"))]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+    use rand::SeedableRng;
+    use tempfile::tempdir;
+    use crate::steg_algorithms::payload::{FrameOptions, Payload};
+    use crate::steg_algorithms::picture::general::transcode;
+
+    #[test]
+    fn strips_jpeg_segments_and_appended_data() {
+        let dir = tempdir().unwrap();
+        let (cover, stego, clean) = (dir.path().join("c.png"), dir.path().join("s.jpg"), dir.path().join("w.jpg"));
+        RgbImage::from_fn(64, 48, |x, y| Rgb([x as u8 * 3, y as u8 * 5, 90])).save(&cover).unwrap();
+        let jpeg = transcode::to_jpeg(&cover, 90).unwrap();
+        let mut marked = marker_hijacking::hide_in_bytes(&jpeg, b"hidden in a segment").unwrap();
+        marked.extend_from_slice(b"PK\x03\x04 a zip riding along");
+        fs::write(&stego, &marked).unwrap();
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(1);
+
+        let planned = wipe(&stego, None, None, &mut rng).unwrap();
+        assert!(!clean.exists(), "a dry run writes nothing");
+        let done = wipe(&stego, Some(&clean), None, &mut rng).unwrap();
+        assert_eq!(planned, done);
+        assert_eq!(done.iter().map(|r| r.scope).collect::<Vec<_>>(), ["marker", "appended"]);
+        assert!(done[0].detail.starts_with("APP11 segment 'Ducky'"), "{}", done[0].detail);
+
+        assert!(marker_hijacking::find_payload(&clean).is_err());
+        assert_eq!(fs::read(&clean).unwrap(), jpeg, "only the additions go, JFIF and the scan stay");
+        assert!(wipe(&stego, None, Some("lsb"), &mut rng).unwrap_err().to_string().contains("doesn't apply"));
+    }
+
+    #[test]
+    fn lsb_plane_is_replaced() {
+        let dir = tempdir().unwrap();
+        let (cover, stego, clean) = (dir.path().join("c.png"), dir.path().join("s.png"), dir.path().join("w.png"));
+        RgbImage::from_fn(80, 60, |x, y| Rgb([x as u8 * 3, y as u8 * 4, 120])).save(&cover).unwrap();
+        let framed = Payload::from_text("gone soon").encode(&FrameOptions::default()).unwrap();
+        lsb::hide_keyed(&cover, &framed, &stego, "k").unwrap();
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(2);
+
+        let done = wipe(&stego, Some(&clean), Some("lsb"), &mut rng).unwrap();
+        assert_eq!(done, [Removal { scope: "lsb", detail: "LSB plane of 14400 samples (replaced with random bits)".to_string() }]);
+        assert!(lsb::find_payload_keyed(&clean, "k").is_err());
+        let (a, b) = (image::open(&stego).unwrap().to_rgb8(), image::open(&clean).unwrap().to_rgb8());
+        assert!(a.as_raw().iter().zip(b.as_raw()).all(|(x, y)| x.abs_diff(*y) <= 1));
+    }
+}
@@ -0,0 +1,115 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tempfile::TempDir;
+use crate::steg_algorithms::error::StegError;
+
+// Scratch space for the intermediate files some algorithms need (a noised cover now; decoded video
+// frames or re-encoded audio later). Every Workspace is a fresh directory of its own, so parallel runs
+// and threads never trip over each other's files. It goes under `root` (--tmpdir, else the system
+// temp directory), can be held to a byte quota, and is removed when the Workspace is dropped, on
+// success and on error alike. The Ctrl-C handler calls `remove_all` for the ones still alive, since an
+// interrupted process never gets to drop them.
+
+// the directories of every live Workspace
+static LIVE: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Default)]
+pub struct WorkspaceOptions {
+    /// Where workspaces are created, the system temp directory if `None`.
+    pub root: Option<PathBuf>,
+    /// Most bytes one workspace may hold.
+    pub quota: Option<u64>,
+}
+
+#[derive(Debug)]
+pub struct Workspace {
+    dir: TempDir,
+    quota: Option<u64>,
+    files: AtomicUsize,
+}
+
+fn size_of(path: &Path) -> u64 {
+    let Ok(meta) = fs::symlink_metadata(path) else { return 0 };
+    if !meta.is_dir() {
+        return meta.len();
+    }
+    fs::read_dir(path).into_iter().flatten().flatten().map(|e| size_of(&e.path())).sum()
+}
+
+impl Workspace {
+    pub fn new(opts: &WorkspaceOptions) -> Result<Self, StegError> {
+        let root = opts.root.clone().unwrap_or_else(std::env::temp_dir);
+        let dir = tempfile::Builder::new()
+            .prefix("rust-stego-")
+            .tempdir_in(&root)
+            .map_err(|e| format!("Failed to create a workspace in {}: {}", root.display(), e))?;
+        LIVE.lock().unwrap_or_else(|e| e.into_inner()).push(dir.path().to_path_buf());
+        log::trace!("workspace {}", dir.path().display());
+        Ok(Workspace { dir, quota: opts.quota, files: AtomicUsize::new(0) })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// A path in the workspace no other call handed out, ending in `suffix` (e.g. ".png"). Nothing is
+    /// created.
+    pub fn file(&self, suffix: &str) -> PathBuf {
+        self.path().join(format!("{}{}", self.files.fetch_add(1, Ordering::Relaxed), suffix))
+    }
+
+    /// Bytes the workspace holds now, or an error once that's over the quota. Call it after writing.
+    pub fn check_quota(&self) -> Result<u64, StegError> {
+        let used = size_of(self.path());
+        match self.quota {
+            Some(quota) if used > quota => Err(format!(
+                "Temporary files need {} bytes, more than the {} byte quota (see --tmp-quota)", used, quota
+            ).into()),
+            _ => Ok(used),
+        }
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        LIVE.lock().unwrap_or_else(|e| e.into_inner()).retain(|p| p != self.dir.path());
+    }
+}
+
+/// Remove every live workspace's directory, for when the process is about to die without dropping them.
+pub fn remove_all() {
+    for dir in LIVE.lock().unwrap_or_else(|e| e.into_inner()).drain(..) {
+        let _ = fs::remove_dir_all(dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn workspaces_are_separate_held_to_quota_and_cleaned_up() {
+        let root = tempdir().unwrap();
+        let opts = WorkspaceOptions { root: Some(root.path().to_path_buf()), quota: Some(1000) };
+        let (a, b) = (Workspace::new(&opts).unwrap(), Workspace::new(&opts).unwrap());
+        assert_ne!(a.path(), b.path());
+        assert!(a.path().starts_with(root.path()));
+        assert_ne!(a.file(".png"), a.file(".png"));
+
+        fs::write(a.file(".bin"), [0u8; 600]).unwrap();
+        assert_eq!(a.check_quota().unwrap(), 600);
+        fs::write(a.file(".bin"), [0u8; 600]).unwrap();
+        assert!(a.check_quota().unwrap_err().to_string().contains("1000 byte quota"));
+        assert_eq!(b.check_quota().unwrap(), 0, "quotas are per workspace");
+
+        let (pa, pb) = (a.path().to_path_buf(), b.path().to_path_buf());
+        drop(a);
+        assert!(!pa.exists());
+        remove_all();
+        assert!(!pb.exists(), "interrupted runs don't leave workspaces behind");
+        drop(b);
+    }
+}
@@ -0,0 +1,64 @@
+//! PNG LSB for JavaScript, built with the `wasm` feature for `wasm32-unknown-unknown` so a web page
+//! can hide and find payloads without the picture leaving the machine.
+//!
+//! ```js
+//! import init, { hide_png, find_png } from "./pkg/rust_stego.js";
+//!
+//! await init();
+//! const stego = hide_png(cover, new TextEncoder().encode("meet at noon"));
+//! new TextDecoder().decode(find_png(stego)); // "meet at noon"
+//! ```
+//!
+//! Both take and return `Uint8Array`s and throw an `Error` with the message of the `StegError` when
+//! they fail. They are `steg::picture::lsb::hide_bytes_with` and `find_bytes_with` with the default
+//! options, so a server using the crate reads what the page hid.
+//!
+//! There is no filesystem in the browser: only the byte APIs (`hide_bytes_with`, `find_bytes_with`) work there,
+//! the path-based functions fail with an unsupported I/O error.
+
+use wasm_bindgen::prelude::*;
+
+use crate::steg_algorithms::error::StegError;
+use crate::steg_algorithms::options::{FindOptions, HideOptions};
+use crate::steg_algorithms::picture::general::lsb;
+
+/// `payload` hidden in the PNG `carrier`, one bit in each of R, G and B per pixel, as a new PNG.
+#[wasm_bindgen]
+pub fn hide_png(carrier: &[u8], payload: &[u8]) -> Result<Vec<u8>, JsError> {
+    lsb::hide_bytes_with(carrier, payload, &HideOptions::default()).map_err(js)
+}
+
+/// What `hide_png` hid in the PNG `carrier`.
+#[wasm_bindgen]
+pub fn find_png(carrier: &[u8]) -> Result<Vec<u8>, JsError> {
+    lsb::find_bytes_with(carrier, &FindOptions::default().stride(1)).map_err(js)
+}
+
+fn js(e: StegError) -> JsError {
+    JsError::new(&e.to_string())
+}
+
+// in wasm under Node: `wasm-pack test --node --features wasm -- --lib`
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    fn png(w: u32, h: u32) -> Vec<u8> {
+        let mut out = Cursor::new(Vec::new());
+        image::RgbImage::from_fn(w, h, |x, y| image::Rgb([(x * 4) as u8, (y * 4) as u8, 128])).write_to(&mut out, image::ImageFormat::Png).unwrap();
+        out.into_inner()
+    }
+
+    #[wasm_bindgen_test]
+    fn png_round_trip() {
+        let stego = hide_png(&png(64, 64), b"meet at noon").unwrap();
+        assert_eq!(find_png(&stego).unwrap(), b"meet at noon");
+    }
+
+    #[wasm_bindgen_test]
+    fn a_cover_too_small_is_an_error_not_a_trap() {
+        assert!(hide_png(&png(2, 2), b"meet at noon").is_err());
+    }
+}
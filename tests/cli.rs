@@ -0,0 +1,756 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use sha2::Digest;
+use std::path::Path;
+use tempfile::tempdir;
+
+// The binary end to end: every failure is a message on stderr and a non-zero exit, never a panic.
+
+fn stego() -> Command {
+    Command::cargo_bin("rust-stego").unwrap()
+}
+
+fn gradient(path: &Path) {
+    image::RgbImage::from_fn(64, 64, |x, y| image::Rgb([(x * 4) as u8, (y * 4) as u8, 128]))
+        .save(path)
+        .unwrap();
+}
+
+#[test]
+fn marker_find_on_a_clean_jpeg_fails_cleanly() {
+    let dir = tempdir().unwrap();
+    let clean = dir.path().join("clean.jpg");
+    gradient(&clean);
+
+    stego()
+        .args(["find", "-a", "marker", "-i"])
+        .arg(&clean)
+        .assert()
+        .code(6)
+        .stderr(predicate::str::contains("panicked").not())
+        .stderr(predicate::str::contains("No hidden payload found"));
+}
+
+#[test]
+fn marker_hide_outside_jpeg_exits_non_zero() {
+    let dir = tempdir().unwrap();
+    let cover = dir.path().join("cover.png");
+    gradient(&cover);
+
+    stego()
+        .args(["hide", "-a", "marker", "--msg", "hi", "-i"])
+        .arg(&cover)
+        .arg("-o")
+        .arg(dir.path().join("out.png"))
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("panicked").not());
+}
+
+#[test]
+fn hide_then_find_succeeds() {
+    let dir = tempdir().unwrap();
+    let (cover, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+    gradient(&cover);
+
+    stego().args(["hide", "--msg", "round trip", "-i"]).arg(&cover).arg("-o").arg(&out).assert().success();
+    stego().args(["find", "-i"]).arg(&out).assert().success().stdout(predicate::str::contains("Result: round trip"));
+}
+
+#[test]
+fn failures_keep_their_exit_codes() {
+    let dir = tempdir().unwrap();
+    let missing = dir.path().join("missing.png");
+
+    stego().args(["find", "-i"]).arg(&missing).assert().code(3).stderr(predicate::str::contains("doesn't exist"));
+    stego()
+        .args(["--json", "find", "-i"])
+        .arg(&missing)
+        .assert()
+        .code(3)
+        .stdout(predicate::str::starts_with(r#"{"ok":false"#));
+    // detect: 2 when the file can't be probed, 1 when nothing was found in it
+    stego().args(["detect", "-i"]).arg(&missing).assert().code(2);
+    let clean = dir.path().join("clean.png");
+    gradient(&clean);
+    stego().args(["detect", "-i"]).arg(&clean).assert().code(1);
+}
+
+#[test]
+fn each_kind_of_failure_has_its_own_exit_code() {
+    let dir = tempdir().unwrap();
+    let (cover, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+    gradient(&cover);
+
+    let big = "x".repeat(64 * 64 * 3);
+    stego().args(["hide", "--msg", &big, "-i"]).arg(&cover).arg("-o").arg(&out)
+        .assert().code(5).stderr(predicate::str::contains("capacity shows"));
+    stego().args(["hide", "--password", "right", "--msg", "hi", "-i"]).arg(&cover).arg("-o").arg(&out).assert().success();
+    stego().args(["find", "--password", "wrong", "-i"]).arg(&out)
+        .assert().code(7).stderr(predicate::str::contains("Checksum mismatch"));
+    stego().args(["--help"]).assert().success().stdout(predicate::str::contains("5    the payload doesn't fit"));
+}
+
+#[test]
+fn find_to_stdout_writes_the_raw_bytes() {
+    let dir = tempdir().unwrap();
+    let (cover, out, secret) = (dir.path().join("cover.png"), dir.path().join("out.png"), dir.path().join("secret.bin"));
+    gradient(&cover);
+    let bytes: Vec<u8> = (0..=255).collect();
+    std::fs::write(&secret, &bytes).unwrap();
+
+    stego().args(["hide", "--msg-file"]).arg(&secret).arg("-i").arg(&cover).arg("-o").arg(&out).assert().success();
+    // everything but the payload goes to stderr
+    stego().args(["-v", "find", "--show-meta", "-o", "-", "-i"]).arg(&out).assert().success().stdout(bytes);
+}
+
+#[test]
+fn info_describes_what_it_can_read() {
+    let dir = tempdir().unwrap();
+    let (cover, junk) = (dir.path().join("cover.png"), dir.path().join("junk.png"));
+    gradient(&cover);
+    std::fs::write(&junk, b"not a picture at all").unwrap();
+
+    stego()
+        .args(["info", "-i"])
+        .arg(&cover)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("64x64 Rgb8, 8 bits per channel"))
+        .stdout(predicate::str::contains("lsb        holds"));
+    // a corrupt file still gets the parts that don't need its content
+    stego()
+        .args(["--json", "info", "-i"])
+        .arg(&junk)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#""filetype":"picture""#))
+        .stdout(predicate::str::contains(r#""error":"#));
+}
+
+#[test]
+fn hide_never_clobbers_without_force() {
+    let dir = tempdir().unwrap();
+    let (cover, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+    gradient(&cover);
+    std::fs::write(&out, b"keep me").unwrap();
+
+    stego()
+        .args(["hide", "--msg", "hi", "-i"])
+        .arg(&cover)
+        .arg("-o")
+        .arg(&out)
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("exists, pass --force"));
+    assert_eq!(std::fs::read(&out).unwrap(), b"keep me");
+
+    // into the cover itself: a failure leaves it as it was, success replaces it
+    let before = std::fs::read(&cover).unwrap();
+    stego()
+        .args(["hide", "--force", "--preserve-length", "-a", "overlay", "--msg", "hi", "-i"])
+        .arg(&cover)
+        .arg("-o")
+        .arg(&cover)
+        .assert()
+        .code(1);
+    assert_eq!(std::fs::read(&cover).unwrap(), before);
+    stego().args(["hide", "--force", "--msg", "in place", "-i"]).arg(&cover).arg("-o").arg(&cover).assert().success();
+    stego().args(["find", "-i"]).arg(&cover).assert().success().stdout(predicate::str::contains("Result: in place"));
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2, "no temporary file is left behind");
+}
+
+#[test]
+fn algorithms_lists_every_filetype() {
+    stego()
+        .arg("algorithms")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("audio\n  lsb"))
+        .stdout(predicate::str::contains("marker"))
+        .stdout(predicate::str::contains("(lossless carriers only)"));
+    stego()
+        .args(["--json", "algorithms"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#""lossless_only": true"#));
+}
+
+#[test]
+fn span_update_only_rewrites_the_changed_carriers() {
+    let dir = tempdir().unwrap();
+    let (covers, out) = (dir.path().join("covers"), dir.path().join("out"));
+    std::fs::create_dir(&covers).unwrap();
+    for i in 0..32 {
+        gradient(&covers.join(format!("c{:02}.png", i)));
+    }
+    let text: String = (0..200).map(|i| format!("line {} of the backup, ", i * 7919 % 1000)).collect();
+    let hide = |msg: &str, force: bool| {
+        let mut cmd = stego();
+        cmd.args(["hide", "--span", "--chunk-size", "256", "--msg", msg, "-i"]).arg(&covers).arg("-o").arg(&out);
+        if force {
+            cmd.arg("--force");
+        }
+        cmd.assert()
+    };
+    let snapshot = || batch_bytes(&out);
+
+    hide(&text, false).success();
+    let before = snapshot();
+    hide(&text, false).failure().stderr(predicate::str::contains("--force"));
+    let edited = text.replacen("line 5", "line five", 1);
+    hide(&edited, true).success().stdout(predicate::str::contains("already in place"));
+    let changed = before.iter().zip(snapshot()).filter(|(a, b)| **a != *b).count();
+    assert!((1..=4).contains(&changed), "{} carriers changed", changed);
+
+    stego().args(["find", "--span", "-i"]).arg(&out).assert().success().stdout(format!("Result: {}\n", edited));
+}
+
+fn batch_bytes(dir: &Path) -> Vec<Vec<u8>> {
+    let mut files: Vec<_> = std::fs::read_dir(dir).unwrap().map(|e| e.unwrap().path()).collect();
+    files.sort();
+    files.iter().map(|p| std::fs::read(p).unwrap()).collect()
+}
+
+#[test]
+fn jpeg_defaults_to_marker() {
+    let dir = tempdir().unwrap();
+    let (cover, out) = (dir.path().join("cover.jpg"), dir.path().join("out.jpg"));
+    gradient(&cover);
+
+    stego()
+        .args(["-v", "hide", "--msg", "through the re-encode", "-i"])
+        .arg(&cover)
+        .arg("-o")
+        .arg(&out)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("auto-selected algorithm 'marker'"));
+    stego().args(["find", "-i"]).arg(&out).assert().success().stdout("Result: through the re-encode\n");
+}
+
+#[test]
+fn lossless_outputs_are_verified_too_unless_no_verify() {
+    let dir = tempdir().unwrap();
+    let (cover, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+    gradient(&cover);
+
+    stego().args(["-v", "hide", "--msg", "checked", "-i"]).arg(&cover).arg("-o").arg(&out)
+        .assert().success().stderr(predicate::str::contains("verified: the payload reads back"));
+    stego().args(["-v", "hide", "--no-verify", "--force", "--msg", "unchecked", "-i"]).arg(&cover).arg("-o").arg(&out)
+        .assert().success().stderr(predicate::str::contains("verified").not());
+}
+
+#[test]
+fn algorithm_parameters_reach_the_embedding() {
+    let dir = tempdir().unwrap();
+    let (cover, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+    gradient(&cover);
+
+    stego()
+        .args(["hide", "-a", "lsb:bits=2,channels=rg,stride=3", "--msg", "two bits", "-i"])
+        .arg(&cover)
+        .arg("-o")
+        .arg(&out)
+        .assert()
+        .success();
+    let blue = |p: &Path| image::open(p).unwrap().to_rgb8().pixels().map(|px| px[2]).collect::<Vec<_>>();
+    assert_eq!(blue(&cover), blue(&out));
+    stego().args(["find", "-a", "lsb:bits=2,channels=rg", "-i"]).arg(&out).assert().success().stdout("Result: two bits\n");
+
+    stego()
+        .args(["find", "-a", "lsb:depth=2", "-i"])
+        .arg(&out)
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("valid keys: bits, channels, alpha, matching, seed, traversal, threshold, stride, key, offset"));
+}
+
+#[test]
+fn redacted_find_keeps_the_original_hash() {
+    let dir = tempdir().unwrap();
+    let (cover, out, log) = (dir.path().join("cover.png"), dir.path().join("out.png"), dir.path().join("audit.jsonl"));
+    gradient(&cover);
+    let msg = "user=alice ip=10.0.0.7";
+    stego().args(["hide", "--msg", msg, "-i"]).arg(&cover).arg("-o").arg(&out).assert().success();
+
+    stego()
+        .args(["--audit-log"])
+        .arg(&log)
+        .args(["find", "--redact-pattern", r"\d+\.\d+\.\d+\.\d+", "--redact-pattern", "alice", "--redact-with", "***", "-i"])
+        .arg(&out)
+        .assert()
+        .success()
+        .stdout("Result: user=*** ip=***\n")
+        .stderr(predicate::str::contains(format!("redacted 2 matches, original payload sha256 {:x}", sha2::Sha256::digest(msg))));
+    let audit = std::fs::read_to_string(&log).unwrap();
+    assert!(audit.contains(&format!("{:x}", sha2::Sha256::digest(msg))) && !audit.contains("alice"), "{}", audit);
+}
+
+#[test]
+fn convert_moves_an_encrypted_payload_to_marker() {
+    let dir = tempdir().unwrap();
+    let (cover, png, jpg) = (dir.path().join("cover.png"), dir.path().join("stego.png"), dir.path().join("stego.jpg"));
+    gradient(&cover);
+    stego().args(["hide", "--msg", "before the recompression", "--password", "pw", "--compress", "-i"]).arg(&cover).arg("-o").arg(&png).assert().success();
+
+    stego().args(["convert", "-i"]).arg(&png).arg("-o").arg(&jpg).assert().success().stderr(predicate::str::contains("from lsb to marker"));
+    stego().args(["find", "--password", "pw", "-i"]).arg(&jpg).assert().success().stdout("Result: before the recompression\n");
+    stego().args(["find", "-i"]).arg(&jpg).assert().failure();
+
+    // a destination too small to hold it is never written
+    let tiny = dir.path().join("tiny.png");
+    image::RgbImage::new(4, 4).save(&tiny).unwrap();
+    let out = dir.path().join("out.png");
+    stego().args(["convert", "--cover"]).arg(&tiny).arg("-i").arg(&png).arg("-o").arg(&out).assert().failure().stderr(predicate::str::contains("too small"));
+    assert!(!out.exists());
+}
+
+#[test]
+fn glob_inputs_run_as_a_batch() {
+    let dir = tempdir().unwrap();
+    let covers = dir.path().join("covers");
+    std::fs::create_dir(&covers).unwrap();
+    for name in ["a.png", "b.png", ".hidden.png"] {
+        gradient(&covers.join(name));
+    }
+    std::fs::write(covers.join("notes.txt"), "not a carrier").unwrap();
+    let pattern = covers.join("*");
+    let out = dir.path().join("out");
+
+    stego().args(["hide", "--msg", "globbed", "-i"]).arg(&pattern).arg("-o").arg(dir.path().join("out.png")).assert().failure();
+    stego()
+        .args(["-v", "hide", "--msg", "globbed", "-i"])
+        .arg(&pattern)
+        .arg("-o")
+        .arg(&out)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2 hidden, 0 skipped, 0 failed"))
+        .stderr(predicate::str::contains("skipping").and(predicate::str::contains(".hidden.png")));
+    assert_eq!(batch_bytes(&out).len(), 2);
+    stego().args(["find", "-i"]).arg(out.join("*.png")).assert().success().stdout(predicate::str::contains("2 found"));
+    stego().args(["find", "-i"]).arg(out.join("*.gif")).assert().failure().stderr(predicate::str::contains("matches no files"));
+}
+
+#[test]
+fn detect_walks_a_tree_and_streams_json_lines() {
+    let dir = tempdir().unwrap();
+    let nested = dir.path().join("dump/a/b");
+    std::fs::create_dir_all(&nested).unwrap();
+    let cover = dir.path().join("cover.png");
+    gradient(&cover);
+    stego().args(["hide", "--msg", "deep down", "-i"]).arg(&cover).arg("-o").arg(nested.join("s.png")).assert().success();
+    gradient(&dir.path().join("dump/clean.png"));
+    std::fs::write(dir.path().join("dump/a/broken.png"), "not a picture").unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink("../..", nested.join("loop")).unwrap();
+    let dump = dir.path().join("dump");
+
+    // the top level alone holds nothing, which isn't an error
+    stego().args(["detect", "-i"]).arg(&dump).assert().success().stderr(predicate::str::contains("0 hits"));
+    let out = stego().args(["--json", "detect", "--recursive", "-i"]).arg(&dump).assert().success().get_output().stdout.clone();
+    let lines: Vec<serde_json::Value> = String::from_utf8(out).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(lines.len(), 2);
+    // sorted by path, so a/b/s.png before a/broken.png
+    assert_eq!((lines[0]["algorithm"].as_str(), lines[0]["path"].as_str()), (Some("lsb"), nested.join("s.png").to_str()));
+    assert!(lines[1]["error"].as_str().unwrap().contains("broken.png"));
+    stego().args(["detect", "-r", "--max-size", "10", "-i"]).arg(&dump).assert().success().stderr(predicate::str::contains("0 probed"));
+}
+
+#[test]
+fn max_bytes_peeks_at_the_start_of_a_payload() {
+    let dir = tempdir().unwrap();
+    let (cover, carrier) = (dir.path().join("cover.png"), dir.path().join("carrier.png"));
+    gradient(&cover);
+    let msg = "0123456789".repeat(50);
+    stego().args(["hide", "--msg", &msg, "-i"]).arg(&cover).arg("-o").arg(&carrier).assert().success();
+
+    stego()
+        .args(["find", "--max-bytes", "12", "-i"])
+        .arg(&carrier)
+        .assert()
+        .success()
+        .stdout("Result: 012345678901\n")
+        .stderr(predicate::str::contains("first 12 of the payload's 500 bytes"));
+    let out = stego().args(["--json", "find", "--max-bytes", "12", "-i"]).arg(&carrier).assert().success().get_output().stdout.clone();
+    let report: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!((report["truncated"].as_bool(), report["total_len"].as_u64(), report["payload_size"].as_u64()), (Some(true), Some(500), Some(12)));
+    // asking for more than there is isn't a truncation
+    let out = stego().args(["--json", "find", "--max-bytes", "600", "-i"]).arg(&carrier).assert().success().get_output().stdout.clone();
+    assert!(serde_json::from_slice::<serde_json::Value>(&out).unwrap().get("truncated").is_none());
+}
+
+#[test]
+fn repl_tries_keys_and_passwords_against_one_carrier() {
+    let dir = tempdir().unwrap();
+    let (cover, carrier) = (dir.path().join("cover.png"), dir.path().join("carrier.png"));
+    gradient(&cover);
+    stego().args(["hide", "--msg", "in the attic", "--key", "k", "--password", "pw", "-i"]).arg(&cover).arg("-o").arg(&carrier).assert().success();
+
+    let saved = dir.path().join("saved.txt");
+    let script = format!("find\nfind lsb:key=k\nbogus\nset password pw\ndecode 1\nsave 1 {}\npayloads\nquit\nfind\n", saved.display());
+    let out = stego().args(["repl", "-i"]).arg(&carrier).write_stdin(script).assert().success().get_output().clone();
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    // the unkeyed attempt finds nothing, the keyed one is kept and decodes once there's a password
+    assert!(stdout.contains("1. 72 bytes from lsb:key=k on carrier.png"), "{}", stdout);
+    assert!(stdout.contains("doesn't decode") && stdout.contains("message, 12 bytes"), "{}", stdout);
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(stderr.contains("Unknown command 'bogus'") && stderr.matches("error: ").count() == 2, "{}", stderr);
+    // nothing runs after quit
+    assert_eq!(stdout.matches("1. 72 bytes").count(), 2);
+    assert_eq!(std::fs::read_to_string(&saved).unwrap(), "in the attic");
+}
+
+#[test]
+fn binary_payloads_print_as_a_hexdump() {
+    let dir = tempdir().unwrap();
+    let (cover, carrier, secret) = (dir.path().join("cover.png"), dir.path().join("carrier.png"), dir.path().join("blob"));
+    gradient(&cover);
+    std::fs::write(&secret, [0x00, 0xff, b'h', b'i']).unwrap();
+    stego().args(["hide", "--msg-file"]).arg(&secret).arg("-i").arg(&cover).arg("-o").arg(&carrier).assert().success();
+    let text = dir.path().join("text.png");
+    stego().args(["hide", "--msg", "plain", "-i"]).arg(&cover).arg("-o").arg(&text).assert().success();
+
+    // a named payload is only dumped when asked
+    stego().arg("find").arg("-i").arg(&carrier).assert().success().stdout(predicate::str::contains("Recovered file 'blob'"));
+    let dump = "00000000  00 ff 68 69                                       |..hi|\n00000004\n";
+    stego().args(["find", "--hex", "-i"]).arg(&carrier).assert().success().stdout(dump);
+    stego().args(["find", "--hex", "-i"]).arg(&text).assert().success().stdout(predicate::str::contains("|plain|"));
+
+    let out = dir.path().join("out.bin");
+    stego().args(["-v", "find", "--hex", "-i"]).arg(&carrier).arg("-o").arg(&out).assert().success().stdout("").stderr(predicate::str::contains(dump));
+    assert_eq!(std::fs::read(&out).unwrap(), [0x00, 0xff, b'h', b'i']);
+    stego().args(["--json", "find", "--hex", "-i"]).arg(&carrier).assert().failure();
+}
+
+#[test]
+fn interactive_checks_answers_and_runs_the_command_they_make() {
+    let dir = tempdir().unwrap();
+    let cover = dir.path().join("cover.png");
+    gradient(&cover);
+    let out = dir.path().join("cover.stego.png");
+
+    // a missing input and a message that doesn't fit are asked again, the defaults take the rest
+    let answers = format!("hide\n{}\n{}\n\n{}\nfits\n\n\n", dir.path().join("missing.png").display(), cover.display(), "x".repeat(2000));
+    stego()
+        .arg("interactive")
+        .write_stdin(answers)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("isn't a file").and(predicate::str::contains("that's 2000 bytes")).and(predicate::str::contains("running: rust-stego hide")));
+    stego().arg("find").arg("-i").arg(&out).assert().success().stdout("Result: fits\n");
+
+    stego().arg("interactive").write_stdin(format!("find\n{}\n\n\n", out.display())).assert().success().stdout(predicate::str::contains("Result: fits"));
+    // running out of answers writes nothing
+    std::fs::remove_file(&out).unwrap();
+    stego().arg("interactive").write_stdin(format!("hide\n{}\n\nfits\n", cover.display())).assert().code(130);
+    assert!(!out.exists());
+}
+
+#[test]
+fn dry_run_reports_without_touching_the_output() {
+    let dir = tempdir().unwrap();
+    let (cover, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+    gradient(&cover);
+    let json = stego().args(["--json", "hide", "--dry-run", "--msg", "a quiet word", "-i"]).arg(&cover).arg("-o").arg(&out).assert().success().get_output().stdout.clone();
+    let plan: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!((plan["unit"].as_str(), plan["units"].as_u64(), plan["capacity_bits"].as_u64()), (Some("pixel"), Some(64 * 64), Some(64 * 64 * 3)));
+    assert!(plan["changed"].as_u64().unwrap() > 0 && plan["output_bytes"].as_u64().unwrap() > 0);
+    assert!(!out.exists());
+
+    // the cover as its own output isn't staged or replaced either
+    let before = std::fs::read(&cover).unwrap();
+    stego().args(["hide", "--dry-run", "--force", "--msg", "x", "-i"]).arg(&cover).arg("-o").arg(&cover).assert().success().stdout(predicate::str::contains("left alone"));
+    assert_eq!(std::fs::read(&cover).unwrap(), before);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    stego().args(["hide", "--dry-run", "-a", "overlay", "--msg", "x", "-i"]).arg(&cover).arg("-o").arg(&out).assert().failure();
+}
+
+#[test]
+fn strip_metadata_leaves_the_payload_and_nothing_else() {
+    let dir = tempdir().unwrap();
+    let (cover, out) = (dir.path().join("cover.jpg"), dir.path().join("out.jpg"));
+    gradient(&cover);
+    // an EXIF segment and a comment right after SOI
+    let jpeg = std::fs::read(&cover).unwrap();
+    let exif = [&[0xFF, 0xE1, 0x00, 0x0C][..], b"Exif\0\0abcd"].concat();
+    let comment = [&[0xFF, 0xFE, 0x00, 0x08][..], b"by me!"].concat();
+    std::fs::write(&cover, [&jpeg[..2], &exif, &comment, &jpeg[2..]].concat()).unwrap();
+    stego().args(["info", "-i"]).arg(&cover).assert().success()
+        .stdout(predicate::str::contains("metadata   APP1 segment 'Exif'").and(predicate::str::contains("COM segment")));
+
+    stego()
+        .args(["-v", "hide", "--strip-metadata", "--msg", "no exif here", "-i"])
+        .arg(&cover)
+        .arg("-o")
+        .arg(&out)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("debug: stripped APP1 segment 'Exif' (14 bytes)"));
+    stego().args(["info", "-i"]).arg(&out).assert().success().stdout(predicate::str::contains("metadata   none"));
+    stego().args(["find", "-i"]).arg(&out).assert().success().stdout("Result: no exif here\n");
+
+    let (bmp, bmp_out) = (dir.path().join("cover.bmp"), dir.path().join("out.bmp"));
+    gradient(&bmp);
+    stego().args(["hide", "--strip-metadata", "--msg", "x", "-i"]).arg(&bmp).arg("-o").arg(&bmp_out)
+        .assert().failure().stderr(predicate::str::contains("--strip-metadata"));
+    assert!(!bmp_out.exists());
+}
+
+#[test]
+fn region_payload_survives_a_watermark_outside_it() {
+    let dir = tempdir().unwrap();
+    let (cover, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+    gradient(&cover);
+    stego().args(["hide", "--region", "8,8,32,16", "--msg", "up in the sky", "-i"]).arg(&cover).arg("-o").arg(&out).assert().success();
+
+    // stamp the bottom rows, well away from the region
+    let mut img = image::open(&out).unwrap().to_rgb8();
+    for y in 48..64 {
+        for x in 0..64 {
+            img.put_pixel(x, y, image::Rgb([255, 255, 255]));
+        }
+    }
+    img.save(&out).unwrap();
+    stego().args(["find", "--region", "8,8,32,16", "-i"]).arg(&out).assert().success().stdout("Result: up in the sky\n");
+
+    stego().args(["hide", "--region", "40,0,32,8", "--msg", "x", "-i"]).arg(&cover).arg("-o").arg(dir.path().join("o2.png"))
+        .assert().failure().stderr(predicate::str::contains("past the edge of the 64x64 image"));
+    stego().args(["hide", "--region", "0,0,3,3", "--msg", "x", "-i"]).arg(&cover).arg("-o").arg(dir.path().join("o3.png"))
+        .assert().failure().stderr(predicate::str::contains("length header"));
+}
+
+#[test]
+fn range_payload_survives_trimming_and_fading_the_ends() {
+    let dir = tempdir().unwrap();
+    let (cover, out) = (dir.path().join("cover.wav"), dir.path().join("out.wav"));
+    let spec = hound::WavSpec { channels: 1, sample_rate: 8000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+    let mut w = hound::WavWriter::create(&cover, spec).unwrap();
+    for i in 0..16000i32 {
+        w.write_sample(((i * 7919) % 20000 - 10000) as i16).unwrap();
+    }
+    w.finalize().unwrap();
+    stego().args(["hide", "--range", "0.5s..1.5s", "--msg", "after the intro", "-i"]).arg(&cover).arg("-o").arg(&out).assert().success();
+
+    // cut the first quarter second and fade out the last half
+    let samples: Vec<i16> = hound::WavReader::open(&out).unwrap().samples::<i16>().map(Result::unwrap).collect();
+    let mut w = hound::WavWriter::create(&out, spec).unwrap();
+    for (i, &s) in samples[2000..].iter().enumerate() {
+        w.write_sample(if i < 10000 { s } else { (s as i32 * (14000 - i as i32) / 4000) as i16 }).unwrap();
+    }
+    w.finalize().unwrap();
+    stego().args(["find", "-i"]).arg(&out).assert().success().stdout("Result: after the intro\n");
+    stego().args(["find", "--range", "0.5s..1.5s", "-i"]).arg(&out).assert().failure()
+        .stderr(predicate::str::contains("one opens at sample 2000 (hidden at 4000"));
+
+    stego().args(["hide", "--range", "1s..3s", "--msg", "x", "-i"]).arg(&cover).arg("-o").arg(dir.path().join("o2.wav"))
+        .assert().failure().stderr(predicate::str::contains("the file is 16000 frames (2.00s) long"));
+}
+
+#[test]
+fn carriers_come_from_stdin_with_a_filetype() {
+    let dir = tempdir().unwrap();
+    let (cover, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+    gradient(&cover);
+    let piped = std::fs::read(&cover).unwrap();
+    stego().args(["hide", "-i", "-", "--filetype", "picture", "--msg", "down the pipe", "-o"]).arg(&out)
+        .write_stdin(piped.clone()).assert().success();
+    stego().args(["find", "-i", "-", "--filetype", "picture"]).write_stdin(std::fs::read(&out).unwrap())
+        .assert().success().stdout("Result: down the pipe\n");
+
+    stego().args(["hide", "-i", "-", "--msg", "x", "-o"]).arg(dir.path().join("o2.png")).write_stdin(piped)
+        .assert().failure().stderr(predicate::str::contains("pass --filetype"));
+    stego().args(["find", "-i", "-", "--filetype", "picture"]).write_stdin("not a picture")
+        .assert().failure().stderr(predicate::str::contains("Couldn't tell what kind of picture"));
+}
+
+#[test]
+fn urls_are_only_for_the_input() {
+    let dir = tempdir().unwrap();
+    let cover = dir.path().join("cover.png");
+    gradient(&cover);
+    stego().args(["hide", "--msg", "x", "-i"]).arg(&cover).args(["-o", "https://example.com/out.png"])
+        .assert().code(2).stderr(predicate::str::contains("those only work for -i"));
+    if !cfg!(feature = "http") {
+        stego().args(["find", "-i", "https://example.com/cover.png"])
+            .assert().failure().stderr(predicate::str::contains("need the `http` feature"));
+    }
+}
+
+#[cfg(not(feature = "serve"))]
+#[test]
+fn serve_says_which_feature_it_needs() {
+    stego().args(["serve", "--listen", "127.0.0.1:0"]).assert().failure().stderr(predicate::str::contains("cargo build --features serve"));
+}
+
+// serves `body` as an extensionless image/png at /c, with /r redirecting there
+#[cfg(feature = "http")]
+fn serve(body: Vec<u8>) -> String {
+    use std::io::{BufRead, BufReader, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut reader = BufReader::new(stream);
+            let mut request = String::new();
+            reader.read_line(&mut request).unwrap();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let mut stream = reader.into_inner();
+            if request.starts_with("GET /r ") {
+                let _ = stream.write_all(b"HTTP/1.1 302 Found\r\nLocation: /c\r\nContent-Length: 0\r\n\r\n");
+            } else {
+                let head = format!("HTTP/1.1 200 OK\r\nContent-Type: image/png; charset=binary\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len());
+                let _ = stream.write_all(head.as_bytes()).and_then(|_| stream.write_all(&body));
+            }
+        }
+    });
+    base
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn find_fetches_a_url_within_the_limits() {
+    let dir = tempdir().unwrap();
+    let (cover, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+    gradient(&cover);
+    stego().args(["hide", "--msg", "over the wire", "-i"]).arg(&cover).arg("-o").arg(&out).assert().success();
+    let base = serve(std::fs::read(&out).unwrap());
+
+    stego().args(["find", "-i"]).arg(format!("{}/r", base)).assert().success().stdout("Result: over the wire\n");
+    stego().args(["hide", "--msg", "again", "-o"]).arg(dir.path().join("o2.png")).arg("-i").arg(format!("{}/c", base)).assert().success();
+    stego().args(["find", "--max-redirects", "0", "-i"]).arg(format!("{}/r", base))
+        .assert().failure().stderr(predicate::str::contains("redirects more than 0 times"));
+    stego().args(["find", "--max-download", "100", "-i"]).arg(format!("{}/c", base))
+        .assert().failure().stderr(predicate::str::contains("over 100 bytes"));
+}
+
+#[test]
+fn verify_exits_by_whether_the_payload_is_the_expected_one() {
+    let dir = tempdir().unwrap();
+    let (cover, out, secret) = (dir.path().join("cover.png"), dir.path().join("out.png"), dir.path().join("secret.bin"));
+    gradient(&cover);
+    std::fs::write(&secret, [7u8; 300]).unwrap();
+    stego().args(["hide", "--compress", "--password", "pw", "--msg-file"]).arg(&secret).arg("-i").arg(&cover).arg("-o").arg(&out)
+        .assert().success();
+    let hash = format!("{:x}", sha2::Sha256::digest([7u8; 300]));
+
+    stego().args(["verify", "--password", "pw", "-i"]).arg(&out).arg("--expect-file").arg(&secret)
+        .assert().success().stdout(predicate::str::contains(format!("match: {} holds the expected payload (300 bytes, sha256 {})", out.display(), hash)));
+    stego().args(["verify", "--password", "pw", "--expect-sha256", &"ab".repeat(32), "-i"]).arg(&out)
+        .assert().code(1).stdout(predicate::str::contains("MISMATCH").and(predicate::str::contains(&hash)));
+    let json = stego().args(["verify", "--json", "--password", "pw", "--expect-sha256", &"ab".repeat(32), "-i"]).arg(&out)
+        .assert().code(1).get_output().stdout.clone();
+    let v: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!((v["ok"].as_bool(), v["matches"].as_bool()), (Some(true), Some(false)));
+    assert_eq!(v["found_sha256"], hash.as_str());
+    assert_eq!(v["expected_sha256"], "ab".repeat(32));
+
+    stego().args(["verify", "--expect-sha256", &hash, "-i"]).arg(&cover).assert().code(2).stderr(predicate::str::contains("verify failed"));
+    stego().args(["verify", "--expect-sha256", &hash, "-i"]).arg(&out).assert().code(2);
+}
+
+#[test]
+fn bench_times_every_algorithm_it_round_trips() {
+    let out = stego().args(["bench", "--json", "--size", "256x256", "--iterations", "1"]).assert().success().get_output().stdout.clone();
+    let report: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    let results = report["results"].as_array().unwrap();
+    let names: Vec<String> = results.iter().map(|r| format!("{} {}", r["filetype"].as_str().unwrap(), r["algorithm"].as_str().unwrap())).collect();
+    assert_eq!(names, ["picture lsb", "picture overlay", "picture marker", "audio lsb"]);
+    assert!(results.iter().all(|r| r["hide_ms"].as_f64().unwrap() > 0.0 && r["payload_bytes"].as_u64().unwrap() > 0));
+    // lsb reads and writes through the progress reports, so it gets phases
+    assert!(results[0]["hide_phases"]["encode_ms"].as_f64().is_some() && results[3]["find_phases"]["extract_ms"].as_f64().is_some());
+
+    stego().args(["bench", "--size", "100x100"]).assert().failure().stderr(predicate::str::contains("between 256 and 16384"));
+}
+
+#[test]
+fn logging_follows_the_level_and_never_shows_secrets() {
+    let dir = tempdir().unwrap();
+    let (cover, out) = (dir.path().join("cover.png"), dir.path().join("out.png"));
+    gradient(&cover);
+    let secret = predicate::str::contains("the eagle lands").or(predicate::str::contains("hunter2")).not();
+    stego().args(["hide", "-vv", "--password", "hunter2", "--msg", "the eagle lands", "-i"]).arg(&cover).arg("-o").arg(&out)
+        .assert().success()
+        .stderr(predicate::str::contains("debug: auto-selected algorithm 'lsb'").and(predicate::str::contains("trace: payload sha256")).and(secret));
+    stego().args(["find", "-v", "--password", "hunter2", "-i"]).arg(&out)
+        .assert().success().stdout("Result: the eagle lands\n").stderr(predicate::str::contains("debug: find succeeded, 15 bytes recovered"));
+
+    // warnings show by default and --quiet leaves only errors
+    stego().args(["hide", "--pad", "100000000", "--msg", "x", "-i"]).arg(&cover).arg("-o").arg(dir.path().join("o2.png"))
+        .assert().success().stderr(predicate::str::contains("warning: padding reduced"));
+    stego().args(["hide", "-q", "--pad", "100000000", "--msg", "x", "-i"]).arg(&cover).arg("-o").arg(dir.path().join("o3.png"))
+        .assert().success().stderr("");
+    stego().args(["find", "-q", "-i"]).arg(&cover).assert().failure().stderr(predicate::str::is_empty().not());
+}
+
+#[test]
+fn manifest_carries_the_settings_to_find() {
+    let dir = tempdir().unwrap();
+    let (cover, out, manifest) = (dir.path().join("cover.png"), dir.path().join("out.png"), dir.path().join("out.json"));
+    gradient(&cover);
+
+    stego()
+        .args(["hide", "-a", "lsb:bits=2,channels=rg", "--region", "4,4,40,40", "--key", "k", "--password", "hunter2", "--msg", "by the book", "-i"])
+        .arg(&cover).arg("-o").arg(&out).arg("--manifest").arg(&manifest)
+        .assert()
+        .success();
+    let text = std::fs::read_to_string(&manifest).unwrap();
+    assert!(!text.contains("hunter2") && !text.contains("\"k\""));
+    let doc: serde_json::Value = serde_json::from_str(&text).unwrap();
+    assert_eq!((doc["version"].as_u64(), doc["options"]["bits"].as_u64(), doc["options"]["region"].as_str()), (Some(1), Some(2), Some("4,4,40,40")));
+    assert_eq!(doc["secrets"]["key"], true);
+
+    let find = || {
+        let mut cmd = stego();
+        cmd.args(["find", "--manifest"]).arg(&manifest).arg("-i").arg(&out);
+        cmd
+    };
+    find().assert().failure().stderr(predicate::str::contains("pass it with --key"));
+    find().args(["--key", "k", "--password", "hunter2"]).assert().success()
+        .stdout("Result: by the book\n").stderr(predicate::str::contains("matches the manifest's sha256"));
+    find().args(["--stride", "2"]).assert().failure().stderr(predicate::str::contains("cannot be used with"));
+
+    std::fs::write(&manifest, text.replacen("\"bits\"", "\"bitz\": 2, \"bits\"", 1)).unwrap();
+    find().args(["--key", "k", "--password", "hunter2"]).assert().failure().stderr(predicate::str::contains("Unknown field `options.bitz`"));
+}
+
+#[test]
+fn wipe_refuses_a_truncated_wav_without_panicking() {
+    let dir = tempdir().unwrap();
+    let (cover, out) = (dir.path().join("cut.wav"), dir.path().join("out.wav"));
+    let spec = hound::WavSpec { channels: 1, sample_rate: 8000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+    let mut w = hound::WavWriter::create(&cover, spec).unwrap();
+    for i in 0..4000i32 {
+        w.write_sample((i % 200 - 100) as i16).unwrap();
+    }
+    w.finalize().unwrap();
+    // the header still promises all 4000 samples, and a byte of the last one is gone too
+    let bytes = std::fs::read(&cover).unwrap();
+    std::fs::write(&cover, &bytes[..bytes.len() - 3001]).unwrap();
+
+    stego().args(["wipe", "-i"]).arg(&cover).arg("-o").arg(&out).assert().code(3).stderr(predicate::str::contains("wipe failed"));
+}
+
+#[test]
+fn prenoise_keeps_the_cover_color_type_and_depth() {
+    let dir = tempdir().unwrap();
+    let rgb16 = image::ImageBuffer::from_fn(48, 48, |x, y| image::Rgb([x as u16 * 1300, y as u16 * 1300, 40000]));
+    let gray = image::GrayImage::from_fn(48, 48, |x, y| image::Luma([(x * 5) as u8 ^ y as u8]));
+    let covers = [
+        ("rgb16.png", image::DynamicImage::ImageRgb16(rgb16), image::ColorType::Rgb16),
+        ("gray.png", image::DynamicImage::ImageLuma8(gray), image::ColorType::L8),
+    ];
+    for (name, cover, color) in covers {
+        let (path, out) = (dir.path().join(name), dir.path().join(format!("out-{}", name)));
+        cover.save(&path).unwrap();
+
+        stego().args(["hide", "--prenoise", "1.5", "--msg", "noisy", "-i"]).arg(&path).arg("-o").arg(&out).assert().success();
+        assert_eq!(image::open(&out).unwrap().color(), color, "{}", name);
+        stego().args(["find", "-i"]).arg(&out).assert().success().stdout(predicate::str::contains("Result: noisy"));
+    }
+}
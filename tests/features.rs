@@ -0,0 +1,27 @@
+use std::path::Path;
+use std::process::Command;
+
+// Each filetype feature on its own, and none at all, has to build: the cfg guards only get exercised
+// by a build that leaves the other features out, which the default test run never does. They get their
+// own target directory, the one this test runs from is locked by the Cargo that started it.
+
+fn check(features: &str) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let out = Command::new(cargo)
+        .current_dir(root)
+        .args(["check", "--quiet", "--lib", "--tests", "--no-default-features", "--features", features])
+        .arg("--target-dir")
+        .arg(root.join("target/feature-check"))
+        .env("RUSTFLAGS", "-D warnings")
+        .output()
+        .expect("cargo runs");
+    assert!(out.status.success(), "--features '{}':\n{}", features, String::from_utf8_lossy(&out.stderr));
+}
+
+#[test]
+fn each_filetype_builds_on_its_own() {
+    for features in ["", "picture", "audio", "jpeg-marker"] {
+        check(features);
+    }
+}
@@ -0,0 +1,60 @@
+#![cfg(feature = "ffi")]
+
+use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::tempdir;
+
+// ffi/example.c compiled with the system C compiler and linked against the cdylib Cargo built for
+// this test, so the header, the exports and the library agree. `cargo test-ffi` runs it with the
+// release-ffi profile, the one the library ships in.
+
+// target/<profile>/deps, where this test runs from and Cargo leaves the cdylib it built for it
+fn lib_dir() -> PathBuf {
+    let dir = std::env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    assert!(dir.join(format!("{}rust_stego{}", DLL_PREFIX, DLL_SUFFIX)).exists(), "no cdylib in {}", dir.display());
+    dir
+}
+
+fn compile(out: &Path) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(cc)
+        .arg(root.join("ffi/example.c"))
+        .arg("-I")
+        .arg(root.join("ffi"))
+        .arg("-L")
+        .arg(lib_dir())
+        .args(["-lrust_stego", "-o"])
+        .arg(out)
+        .status()
+        .expect("no C compiler, set CC");
+    assert!(status.success());
+}
+
+fn example(exe: &Path) -> Command {
+    let mut cmd = Command::new(exe);
+    cmd.env("LD_LIBRARY_PATH", lib_dir()).env("DYLD_LIBRARY_PATH", lib_dir());
+    cmd
+}
+
+#[test]
+fn the_c_example_hides_and_finds() {
+    let dir = tempdir().unwrap();
+    let exe = dir.path().join("example");
+    compile(&exe);
+
+    let (cover, out, small) = (dir.path().join("cover.png"), dir.path().join("out.png"), dir.path().join("small.png"));
+    image::RgbImage::from_fn(64, 64, |x, y| image::Rgb([(x * 4) as u8, (y * 4) as u8, 128])).save(&cover).unwrap();
+    image::RgbImage::new(2, 2).save(&small).unwrap();
+
+    let run = example(&exe).arg(&cover).arg(&out).arg("meet at noon").output().unwrap();
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "meet at noon\n");
+    // and the Rust API reads what the C side hid
+    assert_eq!(rust_stego::steg::picture::lsb::find(&out).unwrap(), "meet at noon");
+
+    let run = example(&exe).arg(&small).arg(&out).arg("meet at noon").output().unwrap();
+    assert_eq!(run.status.code(), Some(5));
+    assert!(String::from_utf8_lossy(&run.stderr).contains("Carrier too small"));
+}